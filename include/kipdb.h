@@ -0,0 +1,75 @@
+/*
+ * KipDB C语言绑定
+ *
+ * 对应src/capi.rs，需以`cargo build --release --features capi`构建动态链接库后链接使用
+ *
+ * 所有函数均返回KipStatus作为错误码，由本库分配的Value需通过kip_free_value释放
+ */
+#ifndef KIPDB_H
+#define KIPDB_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 不透明的数据库句柄 */
+typedef struct KipStore KipStore;
+
+/* 错误码 */
+typedef enum KipStatus {
+    KIP_STATUS_OK = 0,
+    KIP_STATUS_NOT_FOUND = 1,
+    KIP_STATUS_INVALID_ARGUMENT = 2,
+    KIP_STATUS_IO_ERROR = 3,
+    KIP_STATUS_INTERNAL = 4,
+    KIP_STATUS_CORRUPTION = 5,
+} KipStatus;
+
+/* 遍历回调，返回false时停止遍历，指针仅在回调期间有效 */
+typedef bool (*KipScanCallback)(
+    void *ctx,
+    const uint8_t *key,
+    size_t key_len,
+    const uint8_t *value,
+    size_t value_len);
+
+/* 通过数据目录路径开启数据库，成功时将句柄写入store_out */
+KipStatus kip_open(const char *path, KipStore **store_out);
+
+/* 关闭数据库并释放句柄，传入NULL时不做处理 */
+void kip_close(KipStore *store);
+
+/* 设置键值对 */
+KipStatus kip_set(
+    const KipStore *store,
+    const uint8_t *key,
+    size_t key_len,
+    const uint8_t *value,
+    size_t value_len);
+
+/* 通过键获取对应的值，Key不存在时返回KIP_STATUS_NOT_FOUND，成功时Value需通过kip_free_value释放 */
+KipStatus kip_get(
+    const KipStore *store,
+    const uint8_t *key,
+    size_t key_len,
+    uint8_t **value_out,
+    size_t *value_len_out);
+
+/* 释放kip_get返回的Value，传入NULL时不做处理 */
+void kip_free_value(uint8_t *value, size_t value_len);
+
+/* 通过键删除键值对，Key不存在时返回KIP_STATUS_NOT_FOUND */
+KipStatus kip_remove(const KipStore *store, const uint8_t *key, size_t key_len);
+
+/* 按Key升序遍历所有键值对，ctx会原样传递至callback */
+KipStatus kip_scan(const KipStore *store, KipScanCallback callback, void *ctx);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KIPDB_H */
@@ -1,9 +1,11 @@
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use bytes::Bytes;
 use criterion::{Criterion, criterion_group, criterion_main};
+use futures::future;
 use tempfile::TempDir;
 use kip_db::kernel::{KVStore, hash_kv::HashStore};
-use kip_db::kernel::lsm::lsm_kv::LsmStore;
+use kip_db::kernel::lsm::lsm_kv::{Config, LsmStore};
 use kip_db::kernel::sled_kv::SledStore;
 use kip_db::kernel::Result;
 
@@ -72,11 +74,63 @@ fn kv_benchmark(c: &mut Criterion) {
     kv_benchmark_with_store::<SledStore>(c);
 }
 
+/// 并发查询的数据量
+const CONCURRENT_KEY_COUNT: u64 = 10000;
+
+/// 同一批SSTable上的并发查询，用于观察定位读取下并发查询的扩展性
+///
+/// 缩小Block缓存以使查询尽可能读取磁盘
+fn concurrent_get_benchmark(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+    let store = Arc::new(rt.block_on(async {
+        let config = Config::new(temp_dir.path())
+            .block_cache_size(16);
+        let store = LsmStore::open_with_config(config).await.unwrap();
+
+        for i in 0..CONCURRENT_KEY_COUNT {
+            store.set(&i.to_be_bytes(), Bytes::from(i.to_be_bytes().repeat(16))).await.unwrap();
+        }
+        store.flush().await.unwrap();
+        store
+    }));
+
+    for concurrency in [1, 4, 16] {
+        let seq = AtomicU64::new(0);
+
+        c.bench_function(&format!("{}: concurrent get x{concurrency}", LsmStore::name()), |b|
+            b.to_async(&rt).iter(|| {
+                let tasks = (0..concurrency)
+                    .map(|_| {
+                        let store = Arc::clone(&store);
+                        let key = (seq.fetch_add(1, Ordering::SeqCst) % CONCURRENT_KEY_COUNT).to_be_bytes();
+
+                        tokio::spawn(async move {
+                            store.get(&key).await
+                                .unwrap()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                async {
+                    for result in future::join_all(tasks).await {
+                        let _ = result.unwrap();
+                    }
+                }
+            }));
+    }
+}
+
 fn store_name_with_test<T: KVStore>(test_name :& str) -> String {
     format!("{}: {}",T::name(), test_name)
 }
 
-criterion_group!(benches, kv_benchmark);
+criterion_group!(benches, kv_benchmark, concurrent_get_benchmark);
 criterion_main!(benches);
 
 // 测试用序列化方法
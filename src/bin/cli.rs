@@ -90,6 +90,12 @@ async fn main() -> Result<()> {
             client.flush().await?;
             info!("Done!");
         }
+        Command::Increment { key, delta } => {
+            info!("{}", client.increment(encode(&key), delta).await?);
+        }
+        Command::Info => {
+            info!("{:?}", client.info().await?);
+        }
         _ => {}
     }
 
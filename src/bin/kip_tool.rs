@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use tracing::info;
+use kip_db::kernel::lsm::FORMAT_VERSION;
+use kip_db::kernel::lsm::history::rollback_manifest;
+#[cfg(feature = "rocksdb_import")]
+use kip_db::kernel::lsm::import::import_rocksdb;
+use kip_db::kernel::lsm::import::{DEFAULT_IMPORT_BATCH_SIZE, import_rdb};
+use kip_db::kernel::lsm::lsm_kv::{Config, LsmStore};
+use kip_db::kernel::{KVStore, Result};
+
+#[derive(Parser, Debug)]
+#[clap(name = "KipDB-Tool", version, author, about = "KipDB Offline Tools")]
+struct Cli {
+    #[clap(subcommand)]
+    command: ToolCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ToolCommand {
+    /// 将数据目录中旧格式的SSTable重写为当前格式，中断后可再次执行以继续
+    Migrate {
+        dir: PathBuf,
+        /// 格式版本低于该值的SSTable会被重写
+        #[clap(long, default_value_t = FORMAT_VERSION)]
+        to_version: u32,
+    },
+    /// 将数据目录的Manifest回滚若干次变更，用于错误的导入或压缩后恢复数据
+    ///
+    /// 此前的Version所引用的SSTable需由`Config::manifest_history`保留
+    RollbackManifest {
+        dir: PathBuf,
+        /// 回滚的变更次数
+        #[clap(long)]
+        steps: usize,
+    },
+    /// 从RocksDB或LevelDB的数据目录导入数据
+    #[cfg(feature = "rocksdb_import")]
+    ImportRocksdb {
+        src: PathBuf,
+        dst: PathBuf,
+        /// 单次导入的数据大小上限
+        #[clap(long, default_value_t = DEFAULT_IMPORT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+    /// 从Redis的RDB快照导入字符串类型的Key
+    ImportRdb {
+        src: PathBuf,
+        dst: PathBuf,
+        /// 导入的Redis数据库编号
+        #[clap(long, default_value_t = 0)]
+        db: u64,
+        /// 单次导入的数据大小上限
+        #[clap(long, default_value_t = DEFAULT_IMPORT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+}
+
+/// 离线工具，需在Server未使用该数据目录时执行
+/// 调用方法:./kip-tool migrate ./data --to-version 3
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::try_init().unwrap();
+
+    match Cli::parse().command {
+        ToolCommand::Migrate { dir, to_version } => {
+            let kv_store = LsmStore::open(dir).await?;
+            let migrated = kv_store.migrate(to_version).await?;
+
+            info!("Done! {} SSTables migrated to format version {}", migrated, to_version);
+        }
+        ToolCommand::RollbackManifest { dir, steps } => {
+            let remaining = rollback_manifest(Config::new(dir), steps).await?;
+
+            info!("Done! Manifest rolled back {} steps, {} steps remaining", steps, remaining);
+        }
+        #[cfg(feature = "rocksdb_import")]
+        ToolCommand::ImportRocksdb { src, dst, batch_size } => {
+            let kv_store = LsmStore::open(dst).await?;
+            let imported = import_rocksdb(src, &kv_store, batch_size).await?;
+            kv_store.flush().await?;
+
+            info!("Done! {} key-value pairs imported", imported);
+        }
+        ToolCommand::ImportRdb { src, dst, db, batch_size } => {
+            let kv_store = LsmStore::open(dst).await?;
+            let report = import_rdb(src, &kv_store, db, batch_size).await?;
+            kv_store.flush().await?;
+
+            info!(
+                "Done! {} keys imported ({} without their TTL), {} expired, {} non-string and {} in other databases skipped",
+                report.imported, report.with_ttl, report.expired, report.skipped, report.other_db
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -3,6 +3,7 @@ use tokio::net::TcpListener;
 
 use kip_db::{DEFAULT_PORT, LOCAL_IP};
 use kip_db::net::{server, Result};
+use kip_db::net::server::ServerConfig;
 
 /// 服务启动方法
 /// 二进制执行文件调用方法:./kip-db-cli
@@ -13,11 +14,18 @@ pub async fn main() -> Result<()> {
     let cli = Cli::parse();
     let ip = cli.ip.unwrap_or(LOCAL_IP.to_string());
     let port = cli.port.unwrap_or(DEFAULT_PORT);
+    let mut config = ServerConfig::default();
+    if let Some(data_dir) = cli.data_dir {
+        config = config.data_dir(data_dir);
+    }
+    if let Some(namespaces_dir) = cli.namespaces_dir {
+        config = config.namespaces_dir(namespaces_dir);
+    }
 
     // Bind a TCP listener
     let listener = TcpListener::bind(&format!("{ip}:{port}")).await?;
 
-    server::run(listener, quit()).await?;
+    server::run_with_config(listener, config, quit()).await?;
 
     Ok(())
 }
@@ -45,5 +53,11 @@ struct Cli {
     #[clap(long)]
     ip: Option<String>,
     #[clap(long)]
-    port: Option<u16>
+    port: Option<u16>,
+    /// 默认命名空间的数据目录
+    #[clap(long)]
+    data_dir: Option<String>,
+    /// 其余命名空间的数据目录
+    #[clap(long)]
+    namespaces_dir: Option<String>,
 }
\ No newline at end of file
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+use bytes::Bytes;
+use tokio::runtime::{Builder, Runtime};
+use crate::kernel::{KVStore, Result};
+use crate::kernel::lsm::lsm_kv;
+use crate::kernel::lsm::lsm_kv::Config;
+
+/// 同步阻塞的LsmStore
+///
+/// 内部持有独立的tokio运行时，供非异步的应用直接使用
+/// 注意: 不可在异步上下文中调用，否则会因嵌套运行时而Panic
+pub struct LsmStore {
+    // 字段按声明顺序Drop，需保证Store先于运行时释放
+    store: lsm_kv::LsmStore,
+    rt: Runtime,
+}
+
+impl LsmStore {
+    /// 通过数据目录路径开启数据库
+    #[inline]
+    pub fn open(path: impl Into<PathBuf> + Send) -> Result<Self> {
+        Self::open_with_config(Config::new(path))
+    }
+
+    /// 通过Config开启数据库
+    #[inline]
+    pub fn open_with_config(config: Config) -> Result<Self> {
+        let rt = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("kip-db-blocking")
+            .enable_all()
+            .build()?;
+        let store = rt.block_on(lsm_kv::LsmStore::open_with_config(config))?;
+
+        Ok(LsmStore { store, rt })
+    }
+
+    /// 强制将数据刷入硬盘
+    #[inline]
+    pub fn flush(&self) -> Result<()> {
+        self.rt.block_on(self.store.flush())
+    }
+
+    /// 设置键值对
+    #[inline]
+    pub fn set(&self, key: &[u8], value: Bytes) -> Result<()> {
+        self.rt.block_on(self.store.set(key, value))
+    }
+
+    /// 通过键获取对应的值
+    #[inline]
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        self.rt.block_on(self.store.get(key))
+    }
+
+    /// 通过键删除键值对
+    #[inline]
+    pub fn remove(&self, key: &[u8]) -> Result<()> {
+        self.rt.block_on(self.store.remove(key))
+    }
+
+    /// 比较并交换，详见`lsm_kv::LsmStore::compare_and_swap`
+    #[inline]
+    pub fn compare_and_swap(&self, key: &[u8], expected: Option<&[u8]>, new: Option<Bytes>) -> Result<bool> {
+        self.rt.block_on(self.store.compare_and_swap(key, expected, new))
+    }
+
+    /// 原子自增，详见`lsm_kv::LsmStore::increment`
+    #[inline]
+    pub fn increment(&self, key: &[u8], delta: i64) -> Result<i64> {
+        self.rt.block_on(self.store.increment(key, delta))
+    }
+
+    #[inline]
+    pub fn size_of_disk(&self) -> Result<u64> {
+        self.rt.block_on(self.store.size_of_disk())
+    }
+
+    #[inline]
+    pub fn len(&self) -> Result<usize> {
+        self.rt.block_on(self.store.len())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rt.block_on(self.store.is_empty())
+    }
+
+    /// 按Key升序遍历所有键值对，`fn_scan`返回false时停止遍历
+    ///
+    /// 以开始遍历时的Sequence对内存表与SSTable进行归并读取，遍历期间的写入不可见
+    #[inline]
+    pub fn scan<F>(&self, mut fn_scan: F) -> Result<()>
+        where F: FnMut(&[u8], &[u8]) -> bool
+    {
+        self.rt.block_on(
+            self.store.scan_with(None, self.store.latest_sequence(), |key, value| fn_scan(&key, &value))
+        )
+    }
+
+    /// 获取内部的异步Store，用于调用未在此处封装的方法
+    #[inline]
+    pub fn inner(&self) -> &lsm_kv::LsmStore {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::blocking::LsmStore;
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_blocking_store() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        let kv_store = LsmStore::open(temp_dir.path())?;
+        kv_store.set(b"k1", Bytes::from_static(b"v1"))?;
+        assert_eq!(kv_store.get(b"k1")?, Some(Bytes::from_static(b"v1")));
+        assert_eq!(kv_store.increment(b"k2", 2)?, 2);
+        kv_store.remove(b"k1")?;
+        assert_eq!(kv_store.get(b"k1")?, None);
+        kv_store.flush()?;
+        drop(kv_store);
+
+        let kv_store = LsmStore::open(temp_dir.path())?;
+        assert_eq!(kv_store.get(b"k1")?, None);
+        assert_eq!(kv_store.increment(b"k2", 1)?, 3);
+
+        kv_store.set(b"k3", Bytes::from_static(b"v3"))?;
+        let mut vec_key = Vec::new();
+        kv_store.scan(|key, _| {
+            vec_key.push(key.to_vec());
+            true
+        })?;
+        assert_eq!(vec_key, vec![b"k2".to_vec(), b"k3".to_vec()]);
+
+        // 遍历不进行Flush，内存表中的删除同样生效
+        kv_store.remove(b"k2")?;
+        let mut vec_key = Vec::new();
+        kv_store.scan(|key, _| {
+            vec_key.push(key.to_vec());
+            true
+        })?;
+        assert_eq!(vec_key, vec![b"k3".to_vec()]);
+        assert!(kv_store.rt.block_on(kv_store.inner().disk_iter())?.all(|(key, _)| &key[..] != b"k3"));
+
+        Ok(())
+    }
+}
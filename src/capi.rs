@@ -0,0 +1,297 @@
+//! C语言绑定
+//!
+//! 以稳定的`extern "C"`签名暴露open/get/set/remove/scan/close，可供C、Python(ctypes)等语言嵌入使用
+//!
+//! 构建动态链接库: `cargo build --release --features capi`，头文件位于`include/kipdb.h`
+//!
+//! 所有函数均返回`KipStatus`作为错误码，由本库分配的Value需通过`kip_free_value`释放
+use std::ffi::{c_char, c_void, CStr};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::{ptr, slice};
+use bytes::Bytes;
+use crate::blocking::LsmStore;
+use crate::KernelError;
+
+/// 不透明的数据库句柄
+pub type KipStore = LsmStore;
+
+/// 遍历回调，返回false时停止遍历
+pub type KipScanCallback = extern "C" fn(
+    ctx: *mut c_void,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+) -> bool;
+
+/// 错误码
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KipStatus {
+    Ok = 0,
+    NotFound = 1,
+    InvalidArgument = 2,
+    IoError = 3,
+    Internal = 4,
+    Corruption = 5,
+}
+
+impl From<KernelError> for KipStatus {
+    #[inline]
+    fn from(err: KernelError) -> Self {
+        match err {
+            KernelError::KeyNotFound => KipStatus::NotFound,
+            err if err.is_corruption() => KipStatus::Corruption,
+            KernelError::Io(_) => KipStatus::IoError,
+            _ => KipStatus::Internal
+        }
+    }
+}
+
+/// 执行闭包并将错误与Panic统一转换为错误码，避免Panic跨越FFI边界
+fn guard<F>(fn_ffi: F) -> KipStatus
+    where F: FnOnce() -> crate::kernel::Result<KipStatus>
+{
+    match catch_unwind(AssertUnwindSafe(fn_ffi)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(err)) => err.into(),
+        Err(_) => KipStatus::Internal
+    }
+}
+
+/// 将指针与长度转换为切片，空指针仅在长度为0时合法
+unsafe fn bytes_from_raw<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        (len == 0).then_some(&[])
+    } else {
+        Some(unsafe { slice::from_raw_parts(data, len) })
+    }
+}
+
+/// 通过数据目录路径开启数据库，成功时将句柄写入`store_out`
+///
+/// # Safety
+/// `path`需为合法的UTF-8且以'\0'结尾的字符串，`store_out`需为可写的指针
+#[no_mangle]
+pub unsafe extern "C" fn kip_open(path: *const c_char, store_out: *mut *mut KipStore) -> KipStatus {
+    if path.is_null() || store_out.is_null() {
+        return KipStatus::InvalidArgument;
+    }
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(path) => path,
+        Err(_) => return KipStatus::InvalidArgument
+    };
+
+    guard(|| {
+        let store = LsmStore::open(path)?;
+        unsafe { *store_out = Box::into_raw(Box::new(store)) };
+
+        Ok(KipStatus::Ok)
+    })
+}
+
+/// 关闭数据库并释放句柄
+///
+/// # Safety
+/// `store`需为`kip_open`返回的句柄且仅可关闭一次，传入空指针时不做处理
+#[no_mangle]
+pub unsafe extern "C" fn kip_close(store: *mut KipStore) {
+    if !store.is_null() {
+        let _ = guard(|| {
+            drop(unsafe { Box::from_raw(store) });
+
+            Ok(KipStatus::Ok)
+        });
+    }
+}
+
+/// 设置键值对
+///
+/// # Safety
+/// `store`需为有效的句柄，`key`与`value`需分别指向长度为`key_len`与`value_len`的有效内存
+#[no_mangle]
+pub unsafe extern "C" fn kip_set(
+    store: *const KipStore,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+) -> KipStatus {
+    let (Some(store), Some(key), Some(value)) = (
+        unsafe { store.as_ref() },
+        unsafe { bytes_from_raw(key, key_len) },
+        unsafe { bytes_from_raw(value, value_len) }
+    ) else {
+        return KipStatus::InvalidArgument;
+    };
+
+    guard(|| {
+        store.set(key, Bytes::copy_from_slice(value))?;
+
+        Ok(KipStatus::Ok)
+    })
+}
+
+/// 通过键获取对应的值，Key不存在时返回`KipStatus::NotFound`
+///
+/// 成功时Value写入`value_out`与`value_len_out`，需通过`kip_free_value`释放
+///
+/// # Safety
+/// `store`需为有效的句柄，`key`需指向长度为`key_len`的有效内存，`value_out`与`value_len_out`需为可写的指针
+#[no_mangle]
+pub unsafe extern "C" fn kip_get(
+    store: *const KipStore,
+    key: *const u8,
+    key_len: usize,
+    value_out: *mut *mut u8,
+    value_len_out: *mut usize,
+) -> KipStatus {
+    let (Some(store), Some(key)) = (
+        unsafe { store.as_ref() },
+        unsafe { bytes_from_raw(key, key_len) }
+    ) else {
+        return KipStatus::InvalidArgument;
+    };
+    if value_out.is_null() || value_len_out.is_null() {
+        return KipStatus::InvalidArgument;
+    }
+
+    guard(|| {
+        Ok(match store.get(key)? {
+            Some(value) => {
+                let value = value.to_vec().into_boxed_slice();
+                unsafe {
+                    *value_len_out = value.len();
+                    *value_out = Box::into_raw(value).cast();
+                }
+                KipStatus::Ok
+            }
+            None => KipStatus::NotFound
+        })
+    })
+}
+
+/// 释放`kip_get`返回的Value
+///
+/// # Safety
+/// `value`与`value_len`需为`kip_get`所返回的值且仅可释放一次，传入空指针时不做处理
+#[no_mangle]
+pub unsafe extern "C" fn kip_free_value(value: *mut u8, value_len: usize) {
+    if !value.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(value, value_len)) });
+    }
+}
+
+/// 通过键删除键值对，Key不存在时返回`KipStatus::NotFound`
+///
+/// # Safety
+/// `store`需为有效的句柄，`key`需指向长度为`key_len`的有效内存
+#[no_mangle]
+pub unsafe extern "C" fn kip_remove(store: *const KipStore, key: *const u8, key_len: usize) -> KipStatus {
+    let (Some(store), Some(key)) = (
+        unsafe { store.as_ref() },
+        unsafe { bytes_from_raw(key, key_len) }
+    ) else {
+        return KipStatus::InvalidArgument;
+    };
+
+    guard(|| {
+        store.remove(key)?;
+
+        Ok(KipStatus::Ok)
+    })
+}
+
+/// 按Key升序遍历所有键值对，回调中的指针仅在回调期间有效
+///
+/// # Safety
+/// `store`需为有效的句柄，`ctx`会原样传递至`callback`
+#[no_mangle]
+pub unsafe extern "C" fn kip_scan(
+    store: *const KipStore,
+    callback: KipScanCallback,
+    ctx: *mut c_void,
+) -> KipStatus {
+    let Some(store) = (unsafe { store.as_ref() }) else {
+        return KipStatus::InvalidArgument;
+    };
+
+    guard(|| {
+        store.scan(|key, value| {
+            callback(ctx, key.as_ptr(), key.len(), value.as_ptr(), value.len())
+        })?;
+
+        Ok(KipStatus::Ok)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_void, CString};
+    use std::ptr;
+    use tempfile::TempDir;
+    use crate::capi::{KipStatus, KipStore, kip_close, kip_free_value, kip_get, kip_open, kip_remove, kip_scan, kip_set};
+
+    extern "C" fn count_callback(
+        ctx: *mut c_void,
+        _key: *const u8,
+        _key_len: usize,
+        _value: *const u8,
+        _value_len: usize,
+    ) -> bool {
+        unsafe { *ctx.cast::<usize>() += 1 };
+        true
+    }
+
+    #[test]
+    fn test_capi_header() {
+        let header = include_str!("../include/kipdb.h");
+
+        for function in ["kip_open", "kip_close", "kip_set", "kip_get", "kip_free_value", "kip_remove", "kip_scan"] {
+            assert!(header.contains(&format!("{function}(")), "{function} is not declared in kipdb.h");
+        }
+        for (name, status) in [
+            ("KIP_STATUS_OK", KipStatus::Ok),
+            ("KIP_STATUS_NOT_FOUND", KipStatus::NotFound),
+            ("KIP_STATUS_INVALID_ARGUMENT", KipStatus::InvalidArgument),
+            ("KIP_STATUS_IO_ERROR", KipStatus::IoError),
+            ("KIP_STATUS_INTERNAL", KipStatus::Internal),
+            ("KIP_STATUS_CORRUPTION", KipStatus::Corruption),
+        ] {
+            assert!(header.contains(&format!("{name} = {},", status as i32)), "{name} does not match KipStatus");
+        }
+    }
+
+    #[test]
+    fn test_capi() {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let path = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut store: *mut KipStore = ptr::null_mut();
+            assert_eq!(kip_open(path.as_ptr(), &mut store), KipStatus::Ok);
+
+            let (key, value) = (b"k1", b"v1");
+            assert_eq!(kip_set(store, key.as_ptr(), key.len(), value.as_ptr(), value.len()), KipStatus::Ok);
+
+            let mut value_out: *mut u8 = ptr::null_mut();
+            let mut value_len = 0;
+            assert_eq!(kip_get(store, key.as_ptr(), key.len(), &mut value_out, &mut value_len), KipStatus::Ok);
+            assert_eq!(std::slice::from_raw_parts(value_out, value_len), value);
+            kip_free_value(value_out, value_len);
+
+            assert_eq!(kip_remove(store, key.as_ptr(), key.len()), KipStatus::Ok);
+            assert_eq!(kip_get(store, key.as_ptr(), key.len(), &mut value_out, &mut value_len), KipStatus::NotFound);
+            assert_eq!(kip_remove(store, ptr::null(), 1), KipStatus::InvalidArgument);
+
+            let key = b"k2";
+            assert_eq!(kip_set(store, key.as_ptr(), key.len(), value.as_ptr(), value.len()), KipStatus::Ok);
+            let mut count = 0_usize;
+            let count_ptr: *mut usize = &mut count;
+            assert_eq!(kip_scan(store, count_callback, count_ptr.cast()), KipStatus::Ok);
+            assert_eq!(count, 1);
+
+            kip_close(store);
+        }
+    }
+}
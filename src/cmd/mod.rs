@@ -14,7 +14,13 @@ pub enum Command {
     BatchRemove { keys: Vec<String> },
     BatchGet { keys: Vec<String> },
     SizeOfDisk,
-    Len
+    Len,
+    Increment {
+        key: String,
+        #[clap(allow_negative_numbers = true)]
+        delta: i64
+    },
+    Info,
 }
 
 impl Command {
@@ -52,5 +58,15 @@ impl Command {
     pub fn batch_get(keys: Vec<String>) -> Command {
         Command::BatchGet { keys }
     }
+
+    #[inline]
+    pub fn increment(key: String, delta: i64) -> Command {
+        Command::Increment { key, delta }
+    }
+
+    #[inline]
+    pub fn info() -> Command {
+        Command::Info
+    }
 }
 
@@ -1,91 +1,267 @@
 use std::io;
-use failure::Fail;
+use std::path::PathBuf;
+use thiserror::Error;
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::oneshot::error::RecvError;
+use crate::kernel::lsm::hook::WriteRejection;
 
 /// Error type for kvs
-#[derive(Fail, Debug)]
+#[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum KernelError {
     /// IO error
-    #[fail(display = "{}", _0)]
-    Io(#[cause] io::Error),
-    #[fail(display = "{}", _0)]
-    Recv(#[cause] RecvError),
+    #[error("{}", .0)]
+    Io(#[source] io::Error),
+    #[error("{}", .0)]
+    Recv(#[source] RecvError),
 
     /// Serialization or deserialization error
-    #[fail(display = "{}", _0)]
-    SerdeBinCode(#[cause] Box<bincode::ErrorKind>),
+    #[error("{}", .0)]
+    SerdeBinCode(#[source] Box<bincode::ErrorKind>),
+    #[error("{}", .0)]
+    SerdeJson(#[source] serde_json::Error),
     /// Remove no-existent key error
-    #[fail(display = "Key not found")]
+    #[error("Key not found")]
     KeyNotFound,
-    #[fail(display = "Data is empty")]
+    #[error("Data is empty")]
     DataEmpty,
-    #[fail(display = "Max Level is 7")]
+    #[error("Max Level is 7")]
     LevelOver,
-    #[fail(display = "Not the correct type of Cmd")]
+    #[error("Not the correct type of Cmd")]
     NotMatchCmd,
-    #[fail(display = "CRC code does not match")]
+    #[error("CRC code does not match")]
     CrcMisMatch,
-    #[fail(display = "{}", _0)]
-    SledErr(#[cause] sled::Error),
-    #[fail(display = "Cache size overflow")]
+    /// 文件中的数据损坏，附带其所在的文件、Gen与偏移量
+    #[error("Data corrupted in {path:?} (gen: {gen}, offset: {offset}): {reason}")]
+    Corrupted {
+        path: PathBuf,
+        gen: i64,
+        offset: u64,
+        reason: String,
+    },
+    #[cfg(feature = "sled")]
+    #[error("{}", .0)]
+    SledErr(#[source] sled::Error),
+    #[cfg(feature = "rocksdb_import")]
+    #[error("{}", .0)]
+    RocksDB(#[source] rocksdb::Error),
+    #[error("Cache size overflow")]
     CacheSizeOverFlow,
-    #[fail(display = "Cache sharding and size overflow")]
+    #[error("Cache sharding and size overflow")]
     CacheShardingNotAlign,
-    #[fail(display = "File not found")]
+    #[error("File not found")]
     FileNotFound,
     /// 正常情况wal在内存中存在索引则表示硬盘中存在有对应的数据
     /// 而错误则是内存存在索引却在硬盘中不存在这个数据
-    #[fail(display = "WAL log load error")]
+    #[error("WAL log load error")]
     WalLoad,
-    #[fail(display = "Could not found the SSTable")]
+    #[error("Could not found the SSTable")]
     SSTableLost,
     /// Unexpected command type error.
     /// It indicated a corrupted log or a program bug.
-    #[fail(display = "Unexpected command type")]
+    #[error("Unexpected command type")]
     UnexpectedCommandType,
-    #[fail(display = "Process already exists")]
+    #[error("Process already exists")]
     ProcessExists,
-    #[fail(display = "iterator index out of bounds")]
+    #[error("iterator index out of bounds")]
     OutOfBounds,
-    #[fail(display = "channel is closed")]
+    #[error("channel is closed")]
     ChannelClose,
-    #[fail(display = "{}", _0)]
-    NotSupport(&'static str)
+    #[error("{}", .0)]
+    NotSupport(&'static str),
+    #[error("Value is not a numeric")]
+    ValueNotNumeric,
+    #[error("Numeric overflow")]
+    NumericOverflow,
+    /// 租约已过期或不存在
+    #[error("Lease {} expired or not found", .0)]
+    LeaseExpired(i64),
+    /// JSON Pointer格式错误，或其路径与文档的结构不符
+    #[error("Invalid JSON pointer: {}", .0)]
+    InvalidJsonPointer(String),
+    #[error("SSTable scopes overlapped in level {}", .0)]
+    LevelOverlap(usize),
+    #[error("Ingested data must be sorted by key without duplicates")]
+    IngestNotSorted,
+    #[error("WAL replay failed during recovery, the store is read-only")]
+    RecoveryFailed,
+    /// 此前的WAL同步失败，已写入WAL的数据无法确认落盘，因此Store转为只读
+    #[error("WAL sync failed, the store is read-only")]
+    WalSyncFailed,
+    #[error("Prepared batch {} not found", .0)]
+    PreparedNotFound(i64),
+    #[error("Savepoint not found")]
+    SavepointNotFound,
+    #[error("Operation timed out")]
+    TimedOut,
+    /// 内部不变量被破坏，通常意味着程序缺陷
+    #[error("Internal invariant violated: {}", .0)]
+    Internal(String),
+    /// 复合Key不符合`KeyEncoder`的编码
+    #[error("Invalid key encoding")]
+    InvalidKey,
+
+    /// Redis RDB文件格式错误或包含不支持的数据
+    #[error("Invalid RDB file: {}", .0)]
+    InvalidRdb(String),
+    /// 远程Store的连接错误
+    #[error("{}", .0)]
+    Remote(#[source] Box<ConnectionError>),
+    /// 事务快照超出存活时长上限而被强制释放
+    #[error("Snapshot exceeded the max age and was released")]
+    SnapshotExpired,
+
+    /// 配置与数据目录中已持久化的选项不兼容
+    #[error("Incompatible options: {}", .0)]
+    IncompatibleOptions(String),
+
+    /// 不支持的数据格式版本
+    #[error("Unsupported format version: {}", .0)]
+    UnsupportedFormatVersion(u32),
+
+    /// 压力测试中读取的结果与模型不一致
+    #[cfg(feature = "stress")]
+    #[error("Stress check failed: {}", .0)]
+    StressCheckFailed(String),
+
+    /// 回滚的变更次数超出Manifest中记录的变更次数
+    #[error("Manifest history only has {} steps", .0)]
+    ManifestHistoryExceeded(usize),
+
+    /// 时间点读取的Sequence早于保留的版本，附带可读取的最旧Sequence
+    #[error("Sequence is not retained, the oldest readable sequence: {}", .0)]
+    SequenceNotRetained(i64),
+
+    /// 写入被写入钩子拒绝
+    #[error("Write rejected, code: {}, reason: {}", .0.code, .0.reason)]
+    WriteRejected(WriteRejection),
 }
 
-#[derive(Fail, Debug)]
+#[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum ConnectionError {
-    #[fail(display = "{}", _0)]
-    IO(#[cause] io::Error),
-    #[fail(display = "disconnected")]
+    #[error("{}", .0)]
+    IO(#[source] io::Error),
+    #[error("disconnected")]
     Disconnected,
-    #[fail(display = "write failed")]
+    #[error("write failed")]
     WriteFailed,
-    #[fail(display = "wrong instruction")]
+    #[error("wrong instruction")]
     WrongInstruction,
-    #[fail(display = "encode error")]
+    #[error("encode error")]
     EncodeErr,
-    #[fail(display = "decode error")]
+    #[error("decode error")]
     DecodeErr,
-    #[fail(display = "server flush error")]
+    #[error("server flush error")]
     FlushError,
-    #[fail(display = "{}", _0)]
-    StoreErr(#[cause] KernelError),
+    #[error("connection timed out")]
+    Timeout,
+    #[error("frame size {} exceeds the limit", .0)]
+    FrameTooLarge(usize),
+    #[error("permission denied")]
+    PermissionDenied,
+    #[error("throttled, retry after {} ms", .0)]
+    Throttled(u64),
+    /// 服务端在等待时长内未应用读取所要求的Sequence，附带服务端已应用的Sequence
+    #[error("server is stale, applied sequence: {}", .0)]
+    Stale(i64),
+    /// 写入被服务端的写入钩子拒绝
+    #[error("write rejected, code: {}, reason: {}", .0.code, .0.reason)]
+    WriteRejected(WriteRejection),
+    #[error("{}", .0)]
+    StoreErr(#[source] KernelError),
 }
 
-#[derive(Fail, Debug)]
+#[derive(Error, Debug)]
 #[non_exhaustive]
 #[allow(missing_copy_implementations)]
 pub enum CacheError {
-    #[fail(display = "The number of caches cannot be divisible by the number of shards")]
+    #[error("The number of caches cannot be divisible by the number of shards")]
     ShardingNotAlign,
-    #[fail(display = "Cache size overflow")]
+    #[error("Cache size overflow")]
     CacheSizeOverFlow,
-    #[fail(display = "{}", _0)]
-    StoreErr(#[cause] KernelError),
+    #[error("{}", .0)]
+    StoreErr(#[source] KernelError),
+}
+
+impl KernelError {
+    /// 是否为可重试的暂时性错误，如被中断的IO、超时与文件锁被其他进程持有
+    #[inline]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            KernelError::Io(err) => is_retryable_io(err),
+            KernelError::TimedOut | KernelError::ProcessExists => true,
+            KernelError::Remote(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// 是否为数据损坏，此类错误重试无效，需从备份或副本中恢复
+    #[inline]
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            KernelError::Io(err) => matches!(err.kind(), io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof),
+            KernelError::CrcMisMatch
+            | KernelError::Corrupted { .. }
+            | KernelError::WalLoad
+            | KernelError::SSTableLost
+            | KernelError::UnexpectedCommandType
+            | KernelError::LevelOverlap(_) => true,
+            KernelError::Remote(err) => err.is_corruption(),
+            _ => false,
+        }
+    }
+
+    /// 为数据损坏的错误附带其所在的文件、Gen与偏移量，其余错误保持不变
+    pub(crate) fn with_location(self, path: PathBuf, gen: i64, offset: u64) -> Self {
+        match self {
+            KernelError::CrcMisMatch => KernelError::Corrupted {
+                path,
+                gen,
+                offset,
+                reason: self.to_string(),
+            },
+            err => err,
+        }
+    }
+}
+
+impl ConnectionError {
+    /// 是否为可重试的暂时性错误
+    #[inline]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConnectionError::IO(err) => is_retryable_io(err),
+            ConnectionError::Timeout | ConnectionError::Throttled(_) | ConnectionError::Stale(_) => true,
+            ConnectionError::StoreErr(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// 是否为服务端的数据损坏
+    #[inline]
+    pub fn is_corruption(&self) -> bool {
+        matches!(self, ConnectionError::StoreErr(err) if err.is_corruption())
+    }
+}
+
+impl CacheError {
+    #[inline]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, CacheError::StoreErr(err) if err.is_retryable())
+    }
+
+    #[inline]
+    pub fn is_corruption(&self) -> bool {
+        matches!(self, CacheError::StoreErr(err) if err.is_corruption())
+    }
+}
+
+fn is_retryable_io(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
 }
 
 impl<T> From<SendError<T>> for KernelError {
@@ -123,6 +299,14 @@ impl From<Box<bincode::ErrorKind>> for KernelError {
     }
 }
 
+impl From<serde_json::Error> for KernelError {
+    #[inline]
+    fn from(err: serde_json::Error) -> Self {
+        KernelError::SerdeJson(err)
+    }
+}
+
+#[cfg(feature = "sled")]
 impl From<sled::Error> for KernelError {
     #[inline]
     fn from(err: sled::Error) -> Self {
@@ -130,6 +314,14 @@ impl From<sled::Error> for KernelError {
     }
 }
 
+#[cfg(feature = "rocksdb_import")]
+impl From<rocksdb::Error> for KernelError {
+    #[inline]
+    fn from(err: rocksdb::Error) -> Self {
+        KernelError::RocksDB(err)
+    }
+}
+
 impl From<KernelError> for ConnectionError {
     #[inline]
     fn from(err: KernelError) -> Self {
@@ -137,6 +329,19 @@ impl From<KernelError> for ConnectionError {
     }
 }
 
+impl From<ConnectionError> for KernelError {
+    #[inline]
+    fn from(err: ConnectionError) -> Self {
+        match err {
+            ConnectionError::StoreErr(kv_error) => kv_error,
+            ConnectionError::IO(err) => KernelError::Io(err),
+            ConnectionError::WriteRejected(rejection) => KernelError::WriteRejected(rejection),
+            ConnectionError::Timeout => KernelError::TimedOut,
+            err => KernelError::Remote(Box::new(err)),
+        }
+    }
+}
+
 impl From<CacheError> for KernelError {
     #[inline]
     fn from(value: CacheError) -> Self {
@@ -153,4 +358,37 @@ impl From<KernelError> for CacheError {
     fn from(value: KernelError) -> Self {
         CacheError::StoreErr(value)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io;
+    use std::path::PathBuf;
+    use crate::error::{ConnectionError, KernelError};
+
+    #[test]
+    fn test_error_classification() {
+        let interrupted = KernelError::Io(io::Error::from(io::ErrorKind::Interrupted));
+        assert!(interrupted.is_retryable());
+        assert!(!interrupted.is_corruption());
+
+        let corrupted = KernelError::CrcMisMatch
+            .with_location(PathBuf::from("1.sst"), 1, 64);
+        assert!(matches!(corrupted, KernelError::Corrupted { gen: 1, offset: 64, .. }));
+        assert!(corrupted.is_corruption());
+        assert!(!corrupted.is_retryable());
+
+        let err = ConnectionError::StoreErr(interrupted);
+        assert!(err.is_retryable());
+        // 错误链可通过std::error::Error获取
+        assert!(err.source().and_then(Error::source).is_some());
+
+        // 远程Store的错误映射为内核错误后保持原有的分类
+        assert!(matches!(KernelError::from(ConnectionError::Timeout), KernelError::TimedOut));
+        assert!(matches!(KernelError::from(err), KernelError::Io(_)));
+        let throttled = KernelError::from(ConnectionError::Throttled(10));
+        assert!(matches!(throttled, KernelError::Remote(_)));
+        assert!(throttled.is_retryable());
+    }
+}
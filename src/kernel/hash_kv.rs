@@ -4,12 +4,12 @@ use std::collections::{BTreeMap, HashSet};
 use itertools::Itertools;
 use async_trait::async_trait;
 use bytes::Bytes;
-use fslock::LockFile;
 use parking_lot::RwLock;
 use tracing::error;
 
 use crate::kernel::{CommandData, CommandPackage, CommandPos, DEFAULT_LOCK_FILE, FileExtension, KVStore, lock_or_time_out, Result, sorted_gen_list};
 use crate::kernel::io::{IoFactory, IoReader, IoType, IoWriter};
+use crate::kernel::utils::lock_file::LockFile;
 use crate::KernelError;
 
 /// 默认压缩大小触发阈值
@@ -177,10 +177,10 @@ impl HashStore {
 
 impl Drop for HashStore {
     #[inline]
-    #[allow(clippy::expect_used)]
     fn drop(&mut self) {
-        self.lock_file.unlock()
-            .expect("LockFile unlock failed!");
+        if let Err(err) = self.lock_file.unlock() {
+            error!("[HashStore][drop][LockFile unlock failed]: {:?}", err);
+        }
     }
 }
 
@@ -4,7 +4,7 @@ use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use std::sync::Arc;
 use parking_lot::Mutex;
-use crate::kernel::io::{FileExtension, IoType, IoReader, IoWriter};
+use crate::kernel::io::{fadvise, fallocate, read_at, FileAdvice, FileExtension, IoType, IoReader, IoWriter};
 use crate::kernel::Result;
 
 type SyncReader = Mutex<BufReaderWithPos<File>>;
@@ -17,6 +17,8 @@ pub(crate) struct BufIoReader {
     gen: i64,
     dir_path: Arc<PathBuf>,
     reader: SyncReader,
+    /// 与reader共享同一文件，用于无需加锁的定位读取
+    fs: File,
     extension: Arc<FileExtension>,
 }
 
@@ -24,12 +26,14 @@ impl BufIoReader {
     pub(crate) fn new(dir_path: Arc<PathBuf>, gen: i64, extension: Arc<FileExtension>) -> Result<Self> {
         let path = extension.path_with_gen(&dir_path, gen);
 
-        let reader = Mutex::new(BufReaderWithPos::new(File::open(path)?)?);
+        let fs = File::open(path)?;
+        let reader = Mutex::new(BufReaderWithPos::new(fs.try_clone()?)?);
 
         Ok(BufIoReader {
             gen,
             dir_path,
             reader,
+            fs,
             extension,
         })
     }
@@ -41,7 +45,12 @@ pub(crate) struct BufIoWriter {
 }
 
 impl BufIoWriter {
-    pub(crate) fn new(dir_path: Arc<PathBuf>, gen: i64, extension: Arc<FileExtension>) -> Result<Self> {
+    pub(crate) fn new(
+        dir_path: Arc<PathBuf>,
+        gen: i64,
+        extension: Arc<FileExtension>,
+        buffer_size: usize
+    ) -> Result<Self> {
         // 通过路径构造写入器
         let file = OpenOptions::new()
             .create(true)
@@ -49,7 +58,7 @@ impl BufIoWriter {
             .read(true)
             .open(extension.path_with_gen(&dir_path, gen))?;
 
-        Ok(BufIoWriter { writer: BufWriterWithPos::new(file)? })
+        Ok(BufIoWriter { writer: BufWriterWithPos::new(file, buffer_size)? })
     }
 }
 
@@ -70,12 +79,9 @@ impl IoReader for BufIoReader {
     }
 
     fn read_with_pos(&self, start: u64, len: usize) -> Result<Vec<u8>> {
-        let mut reader = self.reader.lock();
-
         let mut buffer = vec![0; len];
-        // 使用Vec buffer获取数据
-        let _ignore = reader.seek(SeekFrom::Start(start))?;
-        let _ignore1 = reader.read(&mut buffer)?;
+        // 定位读取不经过BufReader，也不改变其游标
+        let _ignore = read_at(&self.fs, &mut buffer, start)?;
 
         Ok(buffer)
     }
@@ -83,6 +89,10 @@ impl IoReader for BufIoReader {
     fn get_type(&self) -> IoType {
         IoType::Buf
     }
+
+    fn advise(&self, advice: FileAdvice) -> Result<()> {
+        fadvise(&self.fs, advice)
+    }
 }
 
 impl Write for BufIoWriter {
@@ -106,6 +116,16 @@ impl IoWriter for BufIoWriter {
         self.writer.flush()?;
         Ok(())
     }
+
+    fn io_sync(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.writer.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    fn preallocate(&self, len: u64) -> Result<()> {
+        fallocate(self.writer.writer.get_ref(), len)
+    }
 }
 
 #[derive(Debug)]
@@ -146,10 +166,10 @@ pub(crate) struct BufWriterWithPos<W: Write + Seek> {
 }
 
 impl<W: Write + Seek> BufWriterWithPos<W> {
-    fn new(mut inner: W) -> Result<Self> {
+    fn new(mut inner: W, capacity: usize) -> Result<Self> {
         let pos = inner.stream_position()?;
         Ok(BufWriterWithPos {
-            writer: BufWriter::new(inner),
+            writer: BufWriter::with_capacity(capacity, inner),
             pos,
         })
     }
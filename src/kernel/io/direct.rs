@@ -1,16 +1,15 @@
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, Write};
 use std::path::PathBuf;
 use std::sync::Arc;
-use parking_lot::Mutex;
-use crate::kernel::io::{FileExtension, IoReader, IoType, IoWriter};
+use crate::kernel::io::{fadvise, fallocate, FileAdvice, FileExtension, IoReader, IoType, IoWriter, read_at};
 use crate::kernel::Result;
 
 #[derive(Debug)]
 pub(crate) struct DirectIoReader {
     gen: i64,
     dir_path: Arc<PathBuf>,
-    fs: Mutex<File>,
+    fs: File,
     extension: Arc<FileExtension>,
 }
 
@@ -22,7 +21,7 @@ pub(crate) struct DirectIoWriter {
 impl DirectIoReader {
     pub(crate) fn new(dir_path: Arc<PathBuf>, gen: i64, extension: Arc<FileExtension>) -> Result<Self> {
         let path = extension.path_with_gen(&dir_path, gen);
-        let fs = Mutex::new(File::open(path)?);
+        let fs = File::open(path)?;
 
         Ok(DirectIoReader {
             gen,
@@ -48,7 +47,7 @@ impl DirectIoWriter {
 
 impl Read for DirectIoReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.fs.lock().read(buf)
+        self.fs.read(buf)
     }
 }
 
@@ -63,12 +62,9 @@ impl IoReader for DirectIoReader {
     }
 
     fn read_with_pos(&self, start: u64, len: usize) -> Result<Vec<u8>> {
-        let mut reader = self.fs.lock();
-
         let mut buffer = vec![0; len];
-        // 使用Vec buffer获取数据
-        let _ignore = reader.seek(SeekFrom::Start(start))?;
-        let _ignore1 = reader.read(&mut buffer)?;
+        // 定位读取无需加锁，同一SSTable的并发查询不会相互阻塞
+        let _ignore = read_at(&self.fs, &mut buffer, start)?;
 
         Ok(buffer)
     }
@@ -76,6 +72,10 @@ impl IoReader for DirectIoReader {
     fn get_type(&self) -> IoType {
         IoType::Direct
     }
+
+    fn advise(&self, advice: FileAdvice) -> Result<()> {
+        fadvise(&self.fs, advice)
+    }
 }
 
 impl Write for DirectIoWriter {
@@ -99,4 +99,13 @@ impl IoWriter for DirectIoWriter {
         self.fs.flush()?;
         Ok(())
     }
+
+    fn io_sync(&mut self) -> Result<()> {
+        self.fs.sync_data()?;
+        Ok(())
+    }
+
+    fn preallocate(&self, len: u64) -> Result<()> {
+        fallocate(&self.fs, len)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,268 @@
+use std::io;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use parking_lot::{Mutex, RwLock};
+use crate::kernel::io::{FileAdvice, IoReader, IoType, IoWriter};
+use crate::kernel::Result;
+use crate::kernel::utils::rng::XorShift64;
+
+/// 错误率的分母，错误率以每百万次操作中的失败次数表示
+const ERROR_RATE_SCALE: u32 = 1_000_000;
+
+/// 可注入故障的IO操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoOperation {
+    /// 文件读取，包括定位读取
+    Read,
+    /// 文件写入，包括缓冲区的刷写
+    Write,
+    /// 同步至磁盘
+    Sync,
+}
+
+impl IoOperation {
+    fn index(self) -> usize {
+        match self {
+            IoOperation::Read => 0,
+            IoOperation::Write => 1,
+            IoOperation::Sync => 2,
+        }
+    }
+}
+
+/// 单类IO操作的故障
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fault {
+    latency: Duration,
+    error_rate: u32,
+}
+
+impl Fault {
+    /// 每次操作前额外等待的时长
+    #[inline]
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// 每百万次操作中返回IO错误的次数，超过1_000_000时视为每次操作均失败
+    #[inline]
+    pub fn error_rate(mut self, error_rate: u32) -> Self {
+        self.error_rate = error_rate.min(ERROR_RATE_SCALE);
+        self
+    }
+
+    fn is_none(&self) -> bool {
+        self.latency.is_zero() && self.error_rate == 0
+    }
+}
+
+/// 故障注入器，在IO层为读取、写入与同步注入延迟与错误，用于演练存储设备性能下降或故障时服务的表现
+///
+/// 通过`Config::fault_injector`传入后对WAL、Manifest与SSTable的文件读写生效，
+/// 持有其Arc即可在运行时通过`set_fault`调整，无需重启Store
+///
+/// Tips: 延迟以阻塞当前线程的方式实现，与真实的慢盘相同，会占用调用方(如tokio的工作线程)
+#[derive(Debug)]
+pub struct FaultInjector {
+    faults: RwLock<[Fault; 3]>,
+    rng: Mutex<XorShift64>,
+    injected_errors: AtomicU64,
+}
+
+impl Default for FaultInjector {
+    #[inline]
+    fn default() -> Self {
+        FaultInjector::new(0)
+    }
+}
+
+impl FaultInjector {
+    /// 以seed作为错误率的随机数种子，相同的种子与操作顺序产生相同的错误
+    #[inline]
+    pub fn new(seed: u64) -> Self {
+        FaultInjector {
+            faults: RwLock::new([Fault::default(); 3]),
+            rng: Mutex::new(XorShift64::new(seed)),
+            injected_errors: AtomicU64::new(0),
+        }
+    }
+
+    /// 设置该类IO操作的故障，覆盖此前的设置
+    #[inline]
+    pub fn set_fault(&self, op: IoOperation, fault: Fault) {
+        self.faults.write()[op.index()] = fault;
+    }
+
+    /// 该类IO操作当前的故障
+    #[inline]
+    pub fn fault(&self, op: IoOperation) -> Fault {
+        self.faults.read()[op.index()]
+    }
+
+    /// 清除所有故障
+    #[inline]
+    pub fn clear(&self) {
+        *self.faults.write() = [Fault::default(); 3];
+    }
+
+    /// 累计注入的错误次数
+    #[inline]
+    pub fn injected_errors(&self) -> u64 {
+        self.injected_errors.load(Ordering::Relaxed)
+    }
+
+    /// 在IO操作前调用，按该类操作的故障等待并决定是否返回错误
+    pub(crate) fn inject(&self, op: IoOperation) -> io::Result<()> {
+        let fault = self.fault(op);
+        if fault.is_none() {
+            return Ok(());
+        }
+        if !fault.latency.is_zero() {
+            thread::sleep(fault.latency);
+        }
+        if fault.error_rate > 0
+            && self.rng.lock().below(u64::from(ERROR_RATE_SCALE)) < u64::from(fault.error_rate)
+        {
+            let _ = self.injected_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(io::Error::other(format!("injected {op:?} fault")));
+        }
+
+        Ok(())
+    }
+}
+
+/// 注入故障的读取器，包装IoFactory所创建的读取器
+pub(crate) struct FaultyIoReader {
+    inner: Box<dyn IoReader>,
+    injector: Arc<FaultInjector>,
+}
+
+impl FaultyIoReader {
+    pub(crate) fn new(inner: Box<dyn IoReader>, injector: Arc<FaultInjector>) -> Self {
+        FaultyIoReader { inner, injector }
+    }
+}
+
+impl Read for FaultyIoReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.injector.inject(IoOperation::Read)?;
+        self.inner.read(buf)
+    }
+}
+
+impl IoReader for FaultyIoReader {
+    fn get_gen(&self) -> i64 {
+        self.inner.get_gen()
+    }
+
+    fn get_path(&self) -> PathBuf {
+        self.inner.get_path()
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        self.inner.file_size()
+    }
+
+    fn read_with_pos(&self, start: u64, len: usize) -> Result<Vec<u8>> {
+        self.injector.inject(IoOperation::Read)?;
+        self.inner.read_with_pos(start, len)
+    }
+
+    fn get_type(&self) -> IoType {
+        self.inner.get_type()
+    }
+
+    fn advise(&self, advice: FileAdvice) -> Result<()> {
+        self.inner.advise(advice)
+    }
+}
+
+/// 注入故障的写入器，包装IoFactory所创建的写入器
+pub(crate) struct FaultyIoWriter {
+    inner: Box<dyn IoWriter>,
+    injector: Arc<FaultInjector>,
+}
+
+impl FaultyIoWriter {
+    pub(crate) fn new(inner: Box<dyn IoWriter>, injector: Arc<FaultInjector>) -> Self {
+        FaultyIoWriter { inner, injector }
+    }
+}
+
+impl Write for FaultyIoWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.injector.inject(IoOperation::Write)?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.injector.inject(IoOperation::Write)?;
+        self.inner.flush()
+    }
+}
+
+impl IoWriter for FaultyIoWriter {
+    fn io_write(&mut self, buf: Vec<u8>) -> Result<(u64, usize)> {
+        self.injector.inject(IoOperation::Write)?;
+        self.inner.io_write(buf)
+    }
+
+    fn io_flush(&mut self) -> Result<()> {
+        self.injector.inject(IoOperation::Write)?;
+        self.inner.io_flush()
+    }
+
+    fn io_sync(&mut self) -> Result<()> {
+        self.injector.inject(IoOperation::Sync)?;
+        self.inner.io_sync()
+    }
+
+    fn preallocate(&self, len: u64) -> Result<()> {
+        self.inner.preallocate(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+    use crate::kernel::io::{FileExtension, IoFactory, IoType};
+    use crate::kernel::io::fault::{Fault, FaultInjector, IoOperation};
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_fault_injector() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let injector = Arc::new(FaultInjector::new(42));
+        let factory = IoFactory::new(temp_dir.path(), FileExtension::Log)?
+            .fault_injector(Some(Arc::clone(&injector)));
+
+        let mut writer = factory.writer(1, IoType::Buf)?;
+        let _ = writer.io_write(b"KipDB".to_vec())?;
+        writer.io_flush()?;
+
+        injector.set_fault(IoOperation::Sync, Fault::default().error_rate(1_000_000));
+        assert!(writer.io_sync().is_err());
+        assert_eq!(injector.injected_errors(), 1);
+
+        // 仅对设置了故障的操作类型生效
+        injector.set_fault(IoOperation::Read, Fault::default().latency(Duration::from_millis(20)));
+        let reader = factory.reader(1, IoType::Buf)?;
+        let start = Instant::now();
+        assert_eq!(reader.read_with_pos(0, 5)?, b"KipDB");
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // 运行时清除故障后恢复正常
+        injector.clear();
+        writer.io_sync()?;
+        assert_eq!(injector.injected_errors(), 1);
+
+        Ok(())
+    }
+}
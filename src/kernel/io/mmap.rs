@@ -4,8 +4,10 @@ use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::Arc;
 use memmap2::{Mmap, MmapMut};
+#[cfg(unix)]
+use memmap2::Advice;
 use crate::kernel::Result;
-use crate::kernel::io::{FileExtension, IoType, IoReader, IoWriter};
+use crate::kernel::io::{FileAdvice, FileExtension, IoType, IoReader, IoWriter};
 
 /// 使用MMap作为实现的IOHandler
 /// 目前主要用途是作为缓存读取器，尽可能减少磁盘IO弥补BufHandler读取性能上的不足
@@ -73,6 +75,11 @@ impl IoWriter for MMapIoWriter {
 
         Ok(())
     }
+
+    /// MMap的flush即为msync，已同步至磁盘
+    fn io_sync(&mut self) -> Result<()> {
+        self.io_flush()
+    }
 }
 
 impl Read for MMapIoReader {
@@ -101,6 +108,10 @@ impl IoReader for MMapIoReader {
     fn get_type(&self) -> IoType {
         IoType::MMap
     }
+
+    fn advise(&self, advice: FileAdvice) -> Result<()> {
+        self.reader.advise(advice)
+    }
 }
 
 #[derive(Debug)]
@@ -114,6 +125,26 @@ impl MMapReader {
         Ok(&self.mmap[start..end])
     }
 
+    /// 映射区域通过`madvise`给出提示
+    ///
+    /// `madvise`的DONTNEED会丢弃私有映射的页，而此处为只读映射，因此可以安全使用
+    #[cfg(unix)]
+    fn advise(&self, advice: FileAdvice) -> Result<()> {
+        let advice = match advice {
+            FileAdvice::Normal => Advice::Normal,
+            FileAdvice::Sequential => Advice::Sequential,
+            FileAdvice::Random => Advice::Random,
+            FileAdvice::WillNeed => Advice::WillNeed,
+            FileAdvice::DontNeed => Advice::DontNeed,
+        };
+        Ok(self.mmap.advise(advice)?)
+    }
+
+    #[cfg(not(unix))]
+    fn advise(&self, _advice: FileAdvice) -> Result<()> {
+        Ok(())
+    }
+
     #[allow(unsafe_code)]
     fn new(file: &File) -> Result<MMapReader> {
         let mmap = unsafe{ Mmap::map(file) }?;
@@ -1,14 +1,19 @@
 pub(crate) mod buf;
+#[cfg(not(target_family = "wasm"))]
 pub(crate) mod mmap;
 pub(crate) mod direct;
+pub mod fault;
 
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::fs;
+use std::sync::{Arc, OnceLock};
+use std::{fs, io, thread};
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
 use crate::kernel::io::buf::{BufIoReader, BufIoWriter};
 use crate::kernel::io::direct::{DirectIoReader, DirectIoWriter};
+use crate::kernel::io::fault::{FaultInjector, FaultyIoReader, FaultyIoWriter};
+#[cfg(not(target_family = "wasm"))]
 use crate::kernel::io::mmap::{MMapIoReader, MMapIoWriter};
 use crate::kernel::Result;
 
@@ -36,15 +41,48 @@ impl FileExtension {
     }
 }
 
+/// 默认的写入缓冲区大小
+pub(crate) const DEFAULT_WRITE_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// 重命名因文件被占用而失败时的重试次数
+const RENAME_RETRY_TIMES: u64 = 5;
+
+/// 探测文件系统能力时所使用的临时文件名
+const PROBE_FILE_NAME: &str = "io_probe";
+
 #[derive(Debug)]
 pub struct IoFactory {
     dir_path: Arc<PathBuf>,
     extension: Arc<FileExtension>,
+    /// Buf类型写入器的缓冲区大小
+    write_buffer_size: usize,
+    /// 为所创建的读写器注入故障
+    fault_injector: Option<Arc<FaultInjector>>,
+    /// 文件被打开时是否仍可被重命名与截断，于首次回收时探测
+    can_recycle_open: OnceLock<bool>,
+}
+
+/// 文件访问模式的提示，用于指导操作系统的预读与页缓存回收
+///
+/// 仅在Linux下通过`posix_fadvise`生效，其余平台忽略
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum FileAdvice {
+    /// 恢复默认的预读策略
+    Normal,
+    /// 顺序读取，增大预读窗口
+    Sequential,
+    /// 随机读取，关闭预读
+    Random,
+    /// 数据即将被读取，提前载入页缓存
+    WillNeed,
+    /// 数据不再被读取，从页缓存中释放
+    DontNeed,
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum IoType {
     Buf,
+    /// wasm32-wasi不支持内存映射，此时以Buf代替
     MMap,
     Direct,
 }
@@ -56,10 +94,18 @@ impl IoFactory {
         let dir_path = Arc::clone(&self.dir_path);
         let extension = Arc::clone(&self.extension);
 
-        Ok(match io_type {
+        let reader: Box<dyn IoReader> = match io_type {
             IoType::Buf => Box::new(BufIoReader::new(dir_path, gen, extension)?),
+            #[cfg(not(target_family = "wasm"))]
             IoType::MMap => Box::new(MMapIoReader::new(dir_path, gen, extension)?),
+            #[cfg(target_family = "wasm")]
+            IoType::MMap => Box::new(BufIoReader::new(dir_path, gen, extension)?),
             IoType::Direct => Box::new(DirectIoReader::new(dir_path, gen, extension)?)
+        };
+
+        Ok(match &self.fault_injector {
+            Some(injector) => Box::new(FaultyIoReader::new(reader, Arc::clone(injector))),
+            None => reader,
         })
     }
 
@@ -69,10 +115,18 @@ impl IoFactory {
         let dir_path = Arc::clone(&self.dir_path);
         let extension = Arc::clone(&self.extension);
 
-        Ok(match io_type {
-            IoType::Buf => Box::new(BufIoWriter::new(dir_path, gen, extension)?),
+        let writer: Box<dyn IoWriter> = match io_type {
+            IoType::Buf => Box::new(BufIoWriter::new(dir_path, gen, extension, self.write_buffer_size)?),
+            #[cfg(not(target_family = "wasm"))]
             IoType::MMap => Box::new(MMapIoWriter::new(dir_path, gen, extension)?),
+            #[cfg(target_family = "wasm")]
+            IoType::MMap => Box::new(BufIoWriter::new(dir_path, gen, extension, self.write_buffer_size)?),
             IoType::Direct => Box::new(DirectIoWriter::new(dir_path, gen, extension)?)
+        };
+
+        Ok(match &self.fault_injector {
+            Some(injector) => Box::new(FaultyIoWriter::new(writer, Arc::clone(injector))),
+            None => writer,
         })
     }
 
@@ -93,15 +147,101 @@ impl IoFactory {
         let dir_path = Arc::new(path_buf);
         let extension = Arc::new(extension);
 
-        Ok(Self { dir_path, extension })
+        Ok(Self {
+            dir_path,
+            extension,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            fault_injector: None,
+            can_recycle_open: OnceLock::new(),
+        })
+    }
+
+    /// 设置Buf类型写入器的缓冲区大小
+    ///
+    /// 写入的数据先积攒于缓冲区中，缓冲区满或`io_flush`时才会写入文件，以减少系统调用
+    #[inline]
+    pub fn write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = write_buffer_size;
+        self
+    }
+
+    /// 设置故障注入器，所创建的读写器在IO操作前按其当前的故障注入延迟与错误
+    #[inline]
+    pub fn fault_injector(mut self, fault_injector: Option<Arc<FaultInjector>>) -> Self {
+        self.fault_injector = fault_injector;
+        self
     }
 
+    /// 删除对应Gen的文件
+    ///
+    /// 文件已不存在时视为删除成功，使重复的清理(如崩溃后重启)可以幂等执行
     #[inline]
     pub fn clean(&self, gen: i64) -> Result<()>{
-        fs::remove_file(
+        match fs::remove_file(
             self.extension
                 .path_with_gen(&self.dir_path, gen)
-        )?;
+        ) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => self.sync_dir()
+        }
+    }
+
+    /// 回收旧Gen的文件作为新Gen的文件，返回是否回收成功
+    ///
+    /// 通过清空并重命名旧文件，复用其inode，以减少文件新建与删除带来的文件系统元数据变更
+    ///
+    /// 文件系统不支持重命名或截断被打开的文件(如Windows下被映射的文件)时不进行回收，
+    /// 此时由调用方删除旧文件，新Gen的文件则于创建写入器时新建
+    #[inline]
+    pub fn recycle(&self, old_gen: i64, new_gen: i64) -> Result<bool> {
+        if !self.can_recycle_open() {
+            return Ok(false);
+        }
+        let old_path = self.extension.path_with_gen(&self.dir_path, old_gen);
+
+        let result = OpenOptions::new()
+            .write(true)
+            .open(&old_path)
+            .and_then(|file| file.set_len(0))
+            .and_then(|_| rename_replace(&old_path, &self.extension.path_with_gen(&self.dir_path, new_gen)));
+        match result {
+            Ok(()) => Ok(true),
+            Err(err) if is_sharing_violation(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// 文件被打开时是否仍可被重命名与截断
+    ///
+    /// 该能力取决于平台与数据目录所在的文件系统，因此于运行时在数据目录中实际操作一个被打开的临时文件进行探测
+    #[inline]
+    pub fn can_recycle_open(&self) -> bool {
+        *self.can_recycle_open.get_or_init(|| {
+            let probe_path = self.dir_path.join(PROBE_FILE_NAME);
+            let renamed_path = probe_path.with_extension("tmp");
+            let is_supported = File::create(&probe_path)
+                .and_then(|file| {
+                    file.set_len(1)?;
+                    let _opened = File::open(&probe_path)?;
+                    OpenOptions::new().write(true).open(&probe_path)?.set_len(0)?;
+                    fs::rename(&probe_path, &renamed_path)
+                })
+                .is_ok();
+            let _ignore = fs::remove_file(&probe_path);
+            let _ignore = fs::remove_file(&renamed_path);
+
+            is_supported
+        })
+    }
+
+    /// 持久化文件夹的元数据，使文件的新建与删除在崩溃后依旧可见
+    ///
+    /// Windows下无法直接以文件的形式打开文件夹，且NTFS的元数据由日志保证，因此直接跳过
+    #[inline]
+    pub fn sync_dir(&self) -> Result<()> {
+        #[cfg(unix)]
+        File::open(self.dir_path.as_path())?.sync_all()?;
+
         Ok(())
     }
 
@@ -135,11 +275,218 @@ pub trait IoReader: Send + Sync + 'static + Read {
         let len = self.file_size()?;
         self.read_with_pos(0, len as usize)
     }
+
+    /// 对整个文件给出访问模式的提示
+    ///
+    /// 仅为提示，不影响读取结果，不支持的实现默认忽略
+    #[inline]
+    fn advise(&self, _advice: FileAdvice) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 将from重命名为to，to已存在时将其替换
+///
+/// Windows下`fs::rename`以`MoveFileExW(MOVEFILE_REPLACE_EXISTING)`实现，同样会替换已存在的文件，
+/// 但文件被其他进程(如杀毒软件、索引服务)短暂打开时会因共享冲突而失败，因此对此类失败进行退避重试
+pub(crate) fn rename_replace(from: &Path, to: &Path) -> io::Result<()> {
+    let mut backoff = 1;
+
+    for _ in 0..RENAME_RETRY_TIMES {
+        match fs::rename(from, to) {
+            Err(err) if is_sharing_violation(&err) => {
+                thread::sleep(Duration::from_millis(backoff * 10));
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+
+    fs::rename(from, to)
+}
+
+/// 是否为文件被占用导致的失败，仅Windows下存在此类失败
+fn is_sharing_violation(err: &io::Error) -> bool {
+    // ERROR_ACCESS_DENIED、ERROR_SHARING_VIOLATION、ERROR_LOCK_VIOLATION与ERROR_USER_MAPPED_FILE
+    cfg!(windows) && matches!(err.raw_os_error(), Some(5 | 32 | 33 | 1224))
+}
+
+/// 从offset处进行定位读取，直至填满buf或到达文件末尾，返回读取的长度
+///
+/// 使用`pread`类的定位读取，不依赖文件游标，因此同一文件可被多个线程同时读取而无需加锁
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    let mut read_len = 0;
+
+    while read_len < buf.len() {
+        match read_at_(file, &mut buf[read_len..], offset + read_len as u64) {
+            Ok(0) => break,
+            Ok(len) => read_len += len,
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(read_len)
+}
+
+#[cfg(unix)]
+fn read_at_(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+/// Windows下的seek_read会移动文件游标，但读取位置仅由offset决定，因此同样无需加锁
+#[cfg(windows)]
+fn read_at_(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+/// 其余平台(如wasm32-wasi)不提供稳定的定位读取，以Seek代替，此类平台通常为单线程运行
+#[cfg(not(any(unix, windows)))]
+fn read_at_(mut file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::io::{Seek, SeekFrom};
+
+    let _ = file.seek(SeekFrom::Start(offset))?;
+    file.read(buf)
+}
+
+/// 通过`posix_fadvise`对整个文件给出访问模式的提示
+#[cfg(target_os = "linux")]
+#[allow(unsafe_code)]
+pub(crate) fn fadvise(file: &File, advice: FileAdvice) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let advice = match advice {
+        FileAdvice::Normal => libc::POSIX_FADV_NORMAL,
+        FileAdvice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+        FileAdvice::Random => libc::POSIX_FADV_RANDOM,
+        FileAdvice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        FileAdvice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+    // offset与len均为0时作用于整个文件，失败时直接返回错误码而非设置errno
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) } {
+        0 => Ok(()),
+        errno => Err(std::io::Error::from_raw_os_error(errno).into())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn fadvise(_file: &File, _advice: FileAdvice) -> Result<()> {
+    Ok(())
+}
+
+/// 通过`fallocate`为文件预先分配磁盘空间
+///
+/// 使用`FALLOC_FL_KEEP_SIZE`使文件长度不变，因此读取时不会读到预分配的空白部分，
+/// 文件系统不支持时忽略
+#[cfg(target_os = "linux")]
+#[allow(unsafe_code)]
+pub(crate) fn fallocate(file: &File, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if len == 0 {
+        return Ok(());
+    }
+    let len = libc::off_t::try_from(len).unwrap_or(libc::off_t::MAX);
+    if unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, len) } == 0 {
+        return Ok(());
+    }
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EOPNOTSUPP | libc::ENOSYS) => Ok(()),
+        _ => Err(err.into())
+    }
+}
+
+/// 其余平台下预分配会改变文件长度，因此不进行预分配
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn fallocate(_file: &File, _len: u64) -> Result<()> {
+    Ok(())
 }
 
 pub trait IoWriter: Send + Sync + 'static + Write {
 
     fn io_write(&mut self, buf: Vec<u8>) -> Result<(u64, usize)>;
 
+    /// 将缓冲区中的数据写入文件，此时数据仅位于系统的页缓存中，进程崩溃时不丢失，但断电时可能丢失
     fn io_flush(&mut self) -> Result<()>;
-}
\ No newline at end of file
+
+    /// 将缓冲区中的数据写入文件并同步至磁盘，断电时也不丢失
+    fn io_sync(&mut self) -> Result<()>;
+
+    /// 为文件预先分配len长度的磁盘空间，以减少写入时的元数据变更与碎片
+    ///
+    /// 不改变文件长度，不支持的实现默认忽略
+    #[inline]
+    fn preallocate(&self, _len: u64) -> Result<()> {
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use crate::kernel::io::{FileExtension, IoFactory, IoType, PROBE_FILE_NAME, rename_replace};
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_rename_replace() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let (from, to) = (temp_dir.path().join("from"), temp_dir.path().join("to"));
+
+        fs::write(&from, b"new")?;
+        fs::write(&to, b"old")?;
+        rename_replace(&from, &to)?;
+        assert!(!from.exists());
+        assert_eq!(fs::read(&to)?, b"new");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recycle() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let factory = IoFactory::new(temp_dir.path(), FileExtension::Log)?;
+
+        let mut writer = factory.writer(1, IoType::Buf)?;
+        let _ = writer.io_write(b"kip".to_vec())?;
+        writer.io_flush()?;
+
+        // 被打开的文件同样可被回收，回收后的文件已被清空
+        let _reader = factory.reader(1, IoType::Buf)?;
+        assert!(factory.can_recycle_open());
+        assert!(factory.recycle(1, 2)?);
+        assert!(!factory.has_gen(1)?);
+        assert_eq!(factory.reader(2, IoType::Buf)?.file_size()?, 0);
+        // 探测所用的临时文件不会残留于数据目录
+        assert!(!temp_dir.path().join(PROBE_FILE_NAME).exists());
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_open_handles() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let factory = IoFactory::new(temp_dir.path(), FileExtension::Log)?;
+
+        // 目标文件被打开时仍可被替换
+        let (from, to) = (temp_dir.path().join("from"), temp_dir.path().join("to"));
+        fs::write(&from, b"new")?;
+        fs::write(&to, b"old")?;
+        let opened = fs::File::open(&to)?;
+        rename_replace(&from, &to)?;
+        drop(opened);
+        assert_eq!(fs::read(&to)?, b"new");
+
+        // 被映射的文件无法截断，此时不进行回收且保留原数据
+        let mut writer = factory.writer(1, IoType::Buf)?;
+        let _ = writer.io_write(b"kip".to_vec())?;
+        writer.io_flush()?;
+        let reader = factory.reader(1, IoType::MMap)?;
+        assert!(!factory.recycle(1, 2)?);
+        assert!(!factory.has_gen(2)?);
+        assert_eq!(reader.bytes()?, b"kip");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::ops::RangeInclusive;
+
+/// 读取次数超过写入次数的该倍数时视为读多写少
+const READ_HEAVY_RATIO: u64 = 4;
+
+/// 写入次数超过读取次数的该倍数时视为写多读少
+const WRITE_HEAVY_RATIO: u64 = 4;
+
+/// 自适应压缩控制器
+///
+/// 每次压缩前根据上次调整以来的读写比例与压缩积压，在配置的范围内逐步调整Major压缩阈值
+/// (各Level的阈值以其为基数，因此同时影响所有Level的压缩频率):
+/// - 存在积压的压缩任务时，说明写入方正在等待压缩，提高阈值以降低每次Flush所需的压缩量
+/// - 读多写少时降低阈值，使数据更快地被合并至下层，减少读取时需访问的SSTable
+/// - 写多读少时提高阈值，减少写放大
+///
+/// 每次仅调整1，避免负载短暂波动时阈值剧烈变化
+#[derive(Debug)]
+pub(crate) struct AdaptiveController {
+    bounds: RangeInclusive<usize>,
+    major_threshold: usize,
+    last_reads: u64,
+    last_writes: u64,
+}
+
+impl AdaptiveController {
+    pub(crate) fn new(bounds: RangeInclusive<usize>, major_threshold: usize) -> Self {
+        let bounds = (*bounds.start()).max(1)..=(*bounds.end()).max(*bounds.start()).max(1);
+
+        AdaptiveController {
+            major_threshold: major_threshold.clamp(*bounds.start(), *bounds.end()),
+            bounds,
+            last_reads: 0,
+            last_writes: 0,
+        }
+    }
+
+    pub(crate) fn major_threshold(&self) -> usize {
+        self.major_threshold
+    }
+
+    /// 以累计的读写次数与当前积压的压缩任务数调整阈值，返回调整后的阈值
+    pub(crate) fn adjust(&mut self, reads: u64, writes: u64, compaction_pending: u64) -> usize {
+        let reads_delta = reads.saturating_sub(self.last_reads);
+        let writes_delta = writes.saturating_sub(self.last_writes);
+        self.last_reads = reads;
+        self.last_writes = writes;
+
+        let threshold = self.major_threshold;
+        self.major_threshold = if compaction_pending > 0 || writes_delta > reads_delta.saturating_mul(WRITE_HEAVY_RATIO) {
+            threshold.saturating_add(1)
+        } else if reads_delta > writes_delta.saturating_mul(READ_HEAVY_RATIO) {
+            threshold.saturating_sub(1)
+        } else {
+            threshold
+        }.clamp(*self.bounds.start(), *self.bounds.end());
+
+        self.major_threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::lsm::adaptive::AdaptiveController;
+
+    #[test]
+    fn test_adaptive_controller() {
+        let mut controller = AdaptiveController::new(2..=4, 10);
+        assert_eq!(controller.major_threshold(), 4);
+
+        // 读多写少时逐步降低至下界
+        assert_eq!(controller.adjust(100, 1, 0), 3);
+        assert_eq!(controller.adjust(200, 2, 0), 2);
+        assert_eq!(controller.adjust(300, 3, 0), 2);
+        // 读写均衡时保持不变
+        assert_eq!(controller.adjust(310, 13, 0), 2);
+        // 存在积压时即使读多写少也提高阈值
+        assert_eq!(controller.adjust(410, 14, 1), 3);
+        // 写多读少时逐步提高至上界
+        assert_eq!(controller.adjust(411, 114, 0), 4);
+        assert_eq!(controller.adjust(412, 214, 0), 4);
+    }
+}
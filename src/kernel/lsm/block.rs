@@ -1,22 +1,106 @@
 use std::cmp::min;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
 use std::io::{Cursor, Read, Write};
 use std::mem;
+use std::sync::Arc;
 use bytes::{Buf, BufMut, Bytes};
 use itertools::Itertools;
 use lz4::Decoder;
+use serde::{Deserialize, Serialize};
 use varuint::{ReadVarint, WriteVarint};
 use crate::kernel::Result;
-use crate::kernel::lsm::lsm_kv::Config;
-use crate::kernel::utils::lru_cache::ShardingLruCache;
+use crate::kernel::lsm::InlineKey;
+use crate::kernel::lsm::lsm_kv::{Compression, Config};
+use crate::kernel::utils::lru_cache::{CacheStats, ShardingLruCache};
 use crate::KernelError;
 
+pub(crate) type BlockKey = (i64, Option<Index>);
+
 /// BlockCache类型 可同时缓存两种类型
 ///
 /// Key为SSTable的gen且Index为None时返回Index类型
 ///
 /// Key为SSTable的gen且Index为Some时返回Data类型
-#[allow(dead_code)]
-pub(crate) type BlockCache = ShardingLruCache<(i64, Option<Index>), BlockType>;
+///
+/// 两种类型分别缓存于拥有独立容量的分区中，避免扫描读取的大量DataBlock驱逐点查所依赖的IndexBlock，
+/// 布隆过滤器则作为MetaBlock随SSTable常驻于SSTable缓存中
+pub(crate) struct BlockCache {
+    data: ShardingLruCache<BlockKey, BlockType>,
+    index: ShardingLruCache<BlockKey, BlockType>,
+}
+
+impl BlockCache {
+    pub(crate) fn new(config: &Config) -> Result<Self> {
+        Ok(BlockCache {
+            data: ShardingLruCache::new(config.block_cache_size, 16, RandomState::default())?,
+            index: ShardingLruCache::new(config.index_cache_size, 16, RandomState::default())?,
+        })
+    }
+
+    fn partition(&self, key: &BlockKey) -> &ShardingLruCache<BlockKey, BlockType> {
+        if key.1.is_some() { &self.data } else { &self.index }
+    }
+
+    pub(crate) fn get_or_insert<F>(&self, key: BlockKey, fn_once: F) -> Result<Arc<BlockType>>
+        where F: FnOnce(&BlockKey) -> Result<BlockType>
+    {
+        Ok(self.partition(&key).get_or_insert(key, |key| Ok(fn_once(key)?))?)
+    }
+
+    pub(crate) async fn get_or_insert_async<F, Fut>(&self, key: BlockKey, fn_load: F) -> Result<Arc<BlockType>>
+        where F: FnOnce(&BlockKey) -> Fut, Fut: Future<Output = Result<BlockType>>
+    {
+        Ok(self.partition(&key).get_or_insert_async(key, |key| {
+            let future = fn_load(key);
+            async move { Ok(future.await?) }
+        }).await?)
+    }
+
+    /// 缓存中所有Block的Key，IndexBlock排列于DataBlock之前
+    pub(crate) fn keys(&self) -> Vec<BlockKey> {
+        let mut keys = self.index.keys();
+        keys.append(&mut self.data.keys());
+
+        keys
+    }
+
+    /// DataBlock的数量
+    #[allow(dead_code)]
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// 修改可缓存的DataBlock数量
+    pub(crate) fn set_capacity(&self, cap: usize) -> Result<()> {
+        Ok(self.data.set_capacity(cap)?)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// DataBlock缓存的统计数据
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.data.stats()
+    }
+
+    /// IndexBlock缓存的统计数据
+    pub(crate) fn index_stats(&self) -> CacheStats {
+        self.index.stats()
+    }
+
+    /// DataBlock分区中各Block实际占用的内存大小之和
+    pub(crate) fn data_memory_usage(&self) -> usize {
+        self.data.sum_by(BlockType::memory_usage)
+    }
+
+    /// IndexBlock分区中各Block实际占用的内存大小之和
+    pub(crate) fn index_memory_usage(&self) -> usize {
+        self.index.sum_by(BlockType::memory_usage)
+    }
+}
 
 pub(crate) const DEFAULT_BLOCK_SIZE: usize = 4 * 1024;
 
@@ -29,34 +113,48 @@ const CRC_SIZE: usize = 4;
 
 pub(crate) type KeyValue<T> = (Bytes, T);
 
+/// 缓存中的Block，以Arc共享以便迭代器持有时不受缓存驱逐的影响
 pub(crate) enum BlockType {
-    Data(Block<Value>),
-    Index(Block<Index>),
+    Data(Arc<Block<Value>>),
+    Index(Arc<Block<Index>>),
+}
+
+impl BlockType {
+    fn memory_usage(&self) -> usize {
+        match self {
+            BlockType::Data(block) => block.memory_usage(),
+            BlockType::Index(block) => block.memory_usage(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) struct Entry<T> {
     unshared_len: usize,
     shared_len: usize,
-    pub(crate) key: Bytes,
+    pub(crate) key: InlineKey,
     pub(crate) item: T
 }
 
 impl<T> Entry<T> where T: BlockItem {
-    pub(crate) fn key(&self) -> &Bytes {
+    pub(crate) fn key(&self) -> &[u8] {
         &self.key
     }
 
     pub(crate) fn item(&self) -> &T {
         &self.item
     }
+
+    pub(crate) fn shared_len(&self) -> usize {
+        self.shared_len
+    }
 }
 
 impl<T> Entry<T> where T: BlockItem {
     pub(crate) fn new(
         shared_len: usize,
         unshared_len: usize,
-        key: Bytes,
+        key: InlineKey,
         item: T
     ) -> Self {
         Entry {
@@ -86,15 +184,15 @@ impl<T> Entry<T> where T: BlockItem {
             let unshared_len = ReadVarint::<u32>::read_varint(cursor)? as usize;
             let shared_len = ReadVarint::<u32>::read_varint(cursor)? as usize;
 
-            let mut bytes = vec![0u8; unshared_len];
-            let _ = cursor.read(&mut bytes)?;
+            let mut key = InlineKey::from_elem(0, unshared_len);
+            let _ = cursor.read(&mut key)?;
 
             let item = T::decode(cursor)?;
 
             vec_entry.push((index, Self {
                 unshared_len,
                 shared_len,
-                key:  Bytes::from(bytes),
+                key,
                 item,
             }));
             index += 1;
@@ -123,7 +221,7 @@ impl From<Option<Bytes>> for Value {
 }
 
 /// Block索引
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, Serialize, Deserialize)]
 pub(crate) struct Index {
     offset: u32,
     len: usize,
@@ -148,6 +246,11 @@ pub(crate) trait BlockItem: Sized + Clone {
     fn decode<T>(reader: &mut T) -> Result<Self> where T: Read + ?Sized;
 
     fn encode(&self) -> Result<Vec<u8>>;
+
+    /// Item在堆上额外占用的内存大小
+    fn heap_size(&self) -> usize {
+        0
+    }
 }
 
 impl BlockItem for Value {
@@ -176,6 +279,10 @@ impl BlockItem for Value {
         }
         Ok(buf)
     }
+
+    fn heap_size(&self) -> usize {
+        self.value_len
+    }
 }
 
 impl BlockItem for Index {
@@ -198,12 +305,32 @@ impl BlockItem for Index {
     }
 }
 
-#[derive(Clone, Copy)]
+/// 记录于MetaBlock中，因此仅可在末尾追加新的压缩方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum CompressType {
     None,
     LZ4
 }
 
+impl From<Compression> for CompressType {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => CompressType::None,
+            Compression::Lz4 => CompressType::LZ4,
+        }
+    }
+}
+
+impl CompressType {
+    /// 压缩方式的名称，记录于OPTIONS文件中
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            CompressType::None => "none",
+            CompressType::LZ4 => "lz4",
+        }
+    }
+}
+
 /// Block SSTable最小的存储单位
 ///
 /// 分为DataBlock和IndexBlock
@@ -363,6 +490,9 @@ impl BlockBuilder {
     }
 
     /// 构建多个Block连续序列化组合成的两个Bytes 前者为多个DataBlock，后者为单个IndexBlock
+    ///
+    /// IndexBlock中的Key并非各Block的last_key，而是介于该Block的last_key与下一Block的first_key之间最短的分隔Key，
+    /// 最后一个Block则使用其last_key的最短后继，以此减少IndexBlock的大小与查找时的比较开销
     pub(crate) fn build(mut self) -> Result<(Vec<u8>, Vec<u8>)> {
         self.build_();
 
@@ -370,15 +500,25 @@ impl BlockBuilder {
         let mut vec_index = Vec::with_capacity(
             self.vec_block.len()
         );
+        let vec_separator = self.vec_block.iter()
+            .enumerate()
+            .map(|(i, (_, last_key))| {
+                match self.vec_block.get(i + 1) {
+                    Some((next_block, _)) => shortest_separator(last_key, next_block.get_entry(0).key()),
+                    None => short_successor(last_key),
+                }
+            })
+            .collect_vec();
 
         let blocks_bytes = self.vec_block
             .into_iter()
-            .flat_map(|(block, last_key)| {
+            .zip(vec_separator)
+            .flat_map(|((block, _), separator)| {
                 block.encode(self.options.compress_type)
                     .map(|block_bytes| {
                         let len = block_bytes.len();
                         vec_index.push(
-                            (last_key, Index::new(offset, len))
+                            (separator, Index::new(offset, len))
                         );
                         offset += len as u32;
                         block_bytes
@@ -396,13 +536,20 @@ impl BlockBuilder {
 
 impl Block<Value> {
     /// 通过Key查询对应Value
-    pub(crate) fn find(&self, key: &[u8]) -> Option<Bytes> {
+    ///
+    /// 返回Some(None)时表示该Key在此Block中已被删除
+    pub(crate) fn find(&self, key: &[u8]) -> Option<Option<Bytes>> {
         self.binary_search(key)
             .ok()
             .and_then(|index| {
                 self.vec_entry.get(index).map(|(_, entry)| entry.item.bytes.clone())
             })
-            .flatten()
+    }
+
+    /// 获取index(不含)之前最近的未被删除的Entry的index
+    pub(crate) fn rposition_live(&self, index: usize) -> Option<usize> {
+        self.vec_entry[..index].iter()
+            .rposition(|(_, entry)| entry.item.bytes.is_some())
     }
 }
 
@@ -412,6 +559,20 @@ impl<T> Block<T> {
         self.vec_entry.len()
     }
 
+    /// Block解码后实际占用的内存大小，包括Entry数组、溢出至堆上的Key以及Value
+    pub(crate) fn memory_usage(&self) -> usize where T: BlockItem {
+        let heap_size: usize = self.vec_entry.iter()
+            .map(|(_, entry)| {
+                let key_size = if entry.key.spilled() { entry.key.capacity() } else { 0 };
+                key_size + entry.item.heap_size()
+            })
+            .sum();
+
+        mem::size_of::<Self>()
+            + self.vec_entry.capacity() * mem::size_of::<(usize, Entry<T>)>()
+            + heap_size
+    }
+
     /// 获取该Entry对应的shared_key前缀
     ///
     /// 具体原理是通过被固定的restart_interval进行前缀压缩的Block，
@@ -456,7 +617,7 @@ impl<T> Block<T> where T: BlockItem {
                 (index, Entry::new(
                     shared_len,
                     key.len() - shared_len,
-                    Bytes::copy_from_slice(&key[shared_len..]),
+                    InlineKey::from_slice(&key[shared_len..]),
                     item
                 ))
             })
@@ -484,9 +645,9 @@ impl<T> Block<T> where T: BlockItem {
                     let shared_len = min(entry.shared_len, key.len());
                     key[0..shared_len]
                         .cmp(self.shared_key_prefix(*index, shared_len))
-                        .then_with(|| key[shared_len..].cmp(&entry.key))
+                        .then_with(|| key[shared_len..].cmp(entry.key()))
                 } else {
-                    key.cmp(&entry.key)
+                    key.cmp(entry.key())
                 }.reverse()
             })
     }
@@ -529,8 +690,9 @@ impl<T> Block<T> where T: BlockItem {
 
     /// 读取Bytes进行Block的反序列化
     pub(crate) fn from_raw(mut buf: Vec<u8>, restart_interval: usize) -> Result<Self> {
-        let date_bytes_len = buf.len() - CRC_SIZE;
-        if crc32fast::hash(&buf) == bincode::deserialize::<u32>(
+        let date_bytes_len = buf.len().checked_sub(CRC_SIZE)
+            .ok_or(KernelError::CrcMisMatch)?;
+        if crc32fast::hash(&buf[..date_bytes_len]) != bincode::deserialize::<u32>(
             &buf[date_bytes_len..]
         )? {
             return Err(KernelError::CrcMisMatch)
@@ -539,25 +701,54 @@ impl<T> Block<T> where T: BlockItem {
 
         let mut cursor = Cursor::new(buf);
         let vec_entry = Entry::<T>::decode_with_cursor(&mut cursor)?;
-        Ok(Self {
-            restart_interval,
-            vec_entry
-        })
+        // 还原前向编码的Key后，重新以Restart分组的公共前缀进行内存中的前缀压缩
+        let mut vec_kv: Vec<KeyValue<T>> = Vec::with_capacity(vec_entry.len());
+        for (index, entry) in vec_entry {
+            let key = match vec_kv.last() {
+                Some((prev_key, _)) if entry.shared_len > 0 => {
+                    let shared_key = prev_key.get(..entry.shared_len)
+                        .ok_or_else(|| KernelError::Internal(format!(
+                            "entry {index} shares {} bytes with a {} bytes key", entry.shared_len, prev_key.len()
+                        )))?;
+                    Bytes::from([shared_key, entry.key()].concat())
+                }
+                _ => Bytes::copy_from_slice(entry.key()),
+            };
+            vec_kv.push((key, entry.item));
+        }
+
+        Ok(Self::new(vec_kv, restart_interval))
     }
 
     /// 序列化该Block
     ///
     /// 与from_raw对应，序列化时会生成crc_code用于反序列化时校验
+    ///
+    /// 写入时Restart之间的Key以前向编码(front-coding)进行压缩，即仅记录与上一个Key不同的部分，
+    /// 相较于内存中以整个Restart分组的公共前缀进行压缩，对路径、URL等层级较深的Key可节省更多空间
+    ///
+    /// Tips: 旧格式中的shared_len为分组的公共前缀，必然也是与上一个Key的公共前缀，因此可以直接读取
     pub(crate) fn to_raw(&self) -> Result<Vec<u8>> {
         let mut bytes_block = Vec::with_capacity(DEFAULT_BLOCK_SIZE);
-
-        bytes_block.append(
-            &mut self.vec_entry
-                .iter()
-                .flat_map(|(_, entry)| entry.encode())
-                .flatten()
-                .collect_vec()
-        );
+        let mut prev_key = Vec::new();
+
+        for (index, entry) in &self.vec_entry {
+            let key = [self.shared_key_prefix(*index, entry.shared_len), entry.key()].concat();
+            let shared_len = if index % self.restart_interval == 0 { 0 } else {
+                key.iter()
+                    .zip(&prev_key)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            };
+
+            bytes_block.append(&mut Entry::new(
+                shared_len,
+                key.len() - shared_len,
+                InlineKey::from_slice(&key[shared_len..]),
+                entry.item.clone()
+            ).encode()?);
+            prev_key = key;
+        }
         let check_crc = crc32fast::hash(&bytes_block);
         bytes_block.append(&mut bincode::serialize(&check_crc)?);
 
@@ -621,23 +812,61 @@ fn longest_shared_len<T>(sharding: Vec<&KeyValue<T>>) -> usize {
     }
 }
 
+/// 获取满足`start <= separator < limit`的最短Key
+///
+/// 参考LevelDB的FindShortestSeparator，当无法缩短时直接使用start
+fn shortest_separator(start: &[u8], limit: &[u8]) -> Bytes {
+    let diff_index = start.iter()
+        .zip(limit)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if diff_index < min(start.len(), limit.len()) {
+        let diff_byte = start[diff_index];
+        if diff_byte < u8::MAX && diff_byte + 1 < limit[diff_index] {
+            let mut separator = start[..=diff_index].to_vec();
+            separator[diff_index] += 1;
+            return Bytes::from(separator);
+        }
+    }
+    Bytes::copy_from_slice(start)
+}
+
+/// 获取大于等于key的最短Key
+///
+/// 参考LevelDB的FindShortSuccessor，将第一个非0xff的字节加一并截断
+fn short_successor(key: &[u8]) -> Bytes {
+    key.iter()
+        .position(|byte| *byte != u8::MAX)
+        .map_or_else(|| Bytes::copy_from_slice(key), |index| {
+            let mut successor = key[..=index].to_vec();
+            successor[index] += 1;
+            Bytes::from(successor)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
+    use std::mem;
     use bincode::Options;
     use bytes::Bytes;
     use itertools::Itertools;
     use crate::kernel::Result;
-    use crate::kernel::lsm::block::{Block, BlockBuilder, BlockOptions, CompressType, Entry, Index, Value};
+    use crate::kernel::lsm::InlineKey;
+    use std::sync::Arc;
+    use crate::kernel::lsm::block::{Block, BlockBuilder, BlockCache, BlockOptions, BlockType, CompressType, Entry, Index, short_successor, shortest_separator, Value};
+    use crate::kernel::lsm::lsm_kv::Config;
     use crate::kernel::utils::lru_cache::LruCache;
+    use crate::KernelError;
 
     #[test]
     fn test_entry_serialization() -> Result<()> {
         let entry1 = Entry::new(
-            0, 1,Bytes::from(vec![b'1']), Value::from(Some(Bytes::from(vec![b'1'])))
+            0, 1,InlineKey::from_slice(b"1"), Value::from(Some(Bytes::from(vec![b'1'])))
         );
         let entry2 = Entry::new(
-            0, 1,Bytes::from(vec![b'1']), Value::from(Some(Bytes::from(vec![b'1'])))
+            0, 1,InlineKey::from_slice(b"1"), Value::from(Some(Bytes::from(vec![b'1'])))
         );
 
         let bytes_vec_entry = entry1.encode()?
@@ -697,15 +926,95 @@ mod tests {
                 )?;
                 Ok(target_block)
             })?;
-            assert_eq!(data_block.find(key), Some(value.clone()))
+            assert_eq!(data_block.find(key), Some(Some(value.clone())))
         }
 
         test_block_serialization_(block.clone(), CompressType::None, options.data_restart_interval)?;
         test_block_serialization_(block.clone(), CompressType::LZ4, options.data_restart_interval)?;
 
+        // 数据损坏时CRC校验失败
+        let mut raw = block.to_raw()?;
+        raw[0] ^= 0xFF;
+        assert!(matches!(
+            Block::<Value>::from_raw(raw, options.data_restart_interval),
+            Err(KernelError::CrcMisMatch)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_front_coding() -> Result<()> {
+        let restart_interval = 16;
+        let vec_kv = (0..64)
+            .map(|i| {
+                let key = format!("/home/user_{}/projects/kip_db/src/file_{i}.rs", i / 4);
+                (Bytes::from(key), Value::from(None))
+            })
+            .collect_vec();
+        let block = Block::new(vec_kv, restart_interval);
+        // 内存中以Restart分组的公共前缀进行压缩时的大小
+        let group_coding_len: usize = block.vec_entry.iter()
+            .map(|(_, entry)| entry.encode().map(|bytes| bytes.len()))
+            .sum::<Result<usize>>()?;
+        let raw = block.to_raw()?;
+
+        assert!(raw.len() < group_coding_len);
+        assert_eq!(Block::<Value>::from_raw(raw, restart_interval)?, block);
+
+        // 旧格式的Block依旧可以读取
+        let mut legacy_raw = block.vec_entry.iter()
+            .map(|(_, entry)| entry.encode())
+            .flatten_ok()
+            .collect::<Result<Vec<u8>>>()?;
+        let check_crc = crc32fast::hash(&legacy_raw);
+        legacy_raw.append(&mut bincode::serialize(&check_crc)?);
+        assert_eq!(Block::<Value>::from_raw(legacy_raw, restart_interval)?, block);
+
         Ok(())
     }
 
+    #[test]
+    fn test_block_cache_partition() -> Result<()> {
+        let config = Config::new("").block_cache_size(16).index_cache_size(16);
+        let cache = BlockCache::new(&config)?;
+        let fn_index_block = |_: &_| Ok(BlockType::Index(Arc::new(Block::new(vec![], 1))));
+
+        let _ = cache.get_or_insert((1, None), fn_index_block)?;
+        // 大量读取DataBlock不会驱逐IndexBlock
+        for i in 0..100 {
+            let _ = cache.get_or_insert(
+                (1, Some(Index::new(i, 0))),
+                |_| Ok(BlockType::Data(Arc::new(Block::new(vec![], 1))))
+            )?;
+        }
+        let _ = cache.get_or_insert((1, None), fn_index_block)?;
+
+        assert_eq!(cache.len(), 16);
+        assert_eq!(cache.stats().evictions, 84);
+        assert_eq!(cache.index_stats().hits, 1);
+        assert_eq!(cache.index_stats().evictions, 0);
+        // 两个分区分别以各自Block的实际大小计算内存占用
+        let empty_block_size = mem::size_of::<Block<Value>>();
+        assert_eq!(cache.data_memory_usage(), 16 * empty_block_size);
+        assert_eq!(cache.index_memory_usage(), mem::size_of::<Block<Index>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shortest_separator() {
+        assert_eq!(shortest_separator(b"abcd", b"abzz"), Bytes::from_static(b"abd"));
+        // 差异字节仅相差1时无法缩短
+        assert_eq!(shortest_separator(b"abcd", b"abdz"), Bytes::from_static(b"abcd"));
+        // start为limit的前缀时无法缩短
+        assert_eq!(shortest_separator(b"ab", b"abc"), Bytes::from_static(b"ab"));
+
+        assert_eq!(short_successor(b"abc"), Bytes::from_static(b"b"));
+        assert_eq!(short_successor(&[u8::MAX, 1, 2]), Bytes::from(vec![u8::MAX, 2]));
+        assert_eq!(short_successor(&[u8::MAX; 2]), Bytes::from(vec![u8::MAX; 2]));
+    }
+
     fn test_block_serialization_(block: Block<Value>, compress_type: CompressType, restart_interval: usize) -> Result<()> {
         let de_block = Block::decode(
             block.encode(compress_type)?, compress_type, restart_interval
@@ -0,0 +1,203 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use bytes::{BufMut, Bytes, BytesMut};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::kernel::Result;
+use crate::KernelError;
+
+/// 分块清单的前缀，以此区分清单与普通的Value
+const MANIFEST_MAGIC: &[u8] = b"\x00KipDB-Chunked\x00";
+
+/// 内部Key的前缀，以0xFF开头使其排在绝大多数Key之后，扫描时被跳过
+pub(crate) const INTERNAL_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-";
+
+const CHUNK_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-Chunk\x00";
+
+const BLOB_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-Blob\x00";
+
+const BLOB_REF_PREFIX: &[u8] = b"\xFF\xFFKipDB-BlobRef\x00";
+
+const MANIFEST_MARK_PREFIX: &[u8] = b"\xFF\xFFKipDB-Manifest\x00";
+
+/// 分块存储的大Value的清单，作为原Key的Value写入
+///
+/// 批量写入、事务等途径直接写入Value而不经过分块，因此以清单前缀开头的Value仅在存在其标记时视为清单，
+/// 标记以`原Key + 清单的摘要`作为Key，先于清单写入并在清单被替换后删除
+///
+/// 各分块以`原Key + 分块内容的摘要`作为Key写入，因此覆盖写入时内容未变化的分块无需重复写入，
+/// 摘要仅在同一Key内去重，删除或覆盖时可以直接删除不再被引用的分块
+///
+/// 开启去重时，各分块仅以摘要作为Key写入，使不同Key中内容相同的分块只存储一份，
+/// 并为每个使用该分块的Key写入一条引用，分块在没有引用后于压缩时回收
+///
+/// Tips: 摘要并非密码学Hash，读取时以整个Value的CRC校验拼接结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ChunkManifest {
+    len: u64,
+    crc: u32,
+    dedup: bool,
+    digests: Vec<u128>,
+}
+
+impl ChunkManifest {
+    /// 以chunk_size切分Value，返回清单与各分块的摘要及内容
+    pub(crate) fn split(value: &Bytes, chunk_size: usize, dedup: bool) -> (Self, Vec<(u128, Bytes)>) {
+        let chunks = (0..value.len())
+            .step_by(chunk_size.max(1))
+            .map(|start| {
+                let chunk = value.slice(start..value.len().min(start + chunk_size));
+                (digest(&chunk), chunk)
+            })
+            .collect_vec();
+        let manifest = ChunkManifest {
+            len: value.len() as u64,
+            crc: crc32fast::hash(value),
+            dedup,
+            digests: chunks.iter().map(|(digest, _)| *digest).collect(),
+        };
+
+        (manifest, chunks)
+    }
+
+    /// 解析Value中的清单，非清单的Value返回None
+    pub(crate) fn from_value(value: &[u8]) -> Option<Self> {
+        value.strip_prefix(MANIFEST_MAGIC)
+            .and_then(|bytes| bincode::deserialize(bytes).ok())
+    }
+
+    /// 是否需要分块存储
+    ///
+    /// 以清单前缀开头的小Value同样需分块存储，避免读取时被误认为清单
+    pub(crate) fn is_required(value: &[u8], chunk_size: usize) -> bool {
+        value.len() > chunk_size || value.starts_with(MANIFEST_MAGIC)
+    }
+
+    pub(crate) fn to_value(&self) -> Result<Bytes> {
+        let mut bytes = MANIFEST_MAGIC.to_vec();
+        bytes.append(&mut bincode::serialize(self)?);
+
+        Ok(Bytes::from(bytes))
+    }
+
+    pub(crate) fn digests(&self) -> &[u128] {
+        &self.digests
+    }
+
+    pub(crate) fn is_dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// 分块的Key
+    ///
+    /// 未去重时: 前缀 + 原Key长度 + 原Key + 摘要，去重时: 前缀 + 摘要
+    pub(crate) fn chunk_key(&self, key: &[u8], digest: u128) -> Bytes {
+        if self.dedup {
+            return blob_key(digest);
+        }
+        let mut chunk_key = BytesMut::with_capacity(CHUNK_KEY_PREFIX.len() + 4 + key.len() + 16);
+        chunk_key.put_slice(CHUNK_KEY_PREFIX);
+        chunk_key.put_u32(key.len() as u32);
+        chunk_key.put_slice(key);
+        chunk_key.put_u128(digest);
+
+        chunk_key.freeze()
+    }
+
+    /// 拼接各分块，并校验长度与CRC
+    pub(crate) fn assemble(&self, chunks: Vec<Bytes>) -> Result<Bytes> {
+        let mut value = BytesMut::with_capacity(self.len as usize);
+        for chunk in chunks {
+            value.put(chunk);
+        }
+
+        if value.len() as u64 != self.len || crc32fast::hash(&value) != self.crc {
+            return Err(KernelError::CrcMisMatch);
+        }
+        Ok(value.freeze())
+    }
+}
+
+/// 去重分块的Key
+pub(crate) fn blob_key(digest: u128) -> Bytes {
+    let mut blob_key = BytesMut::with_capacity(BLOB_KEY_PREFIX.len() + 16);
+    blob_key.put_slice(BLOB_KEY_PREFIX);
+    blob_key.put_u128(digest);
+
+    blob_key.freeze()
+}
+
+/// 去重分块所有引用的公共前缀
+pub(crate) fn blob_ref_prefix(digest: u128) -> Bytes {
+    let mut prefix = BytesMut::with_capacity(BLOB_REF_PREFIX.len() + 16);
+    prefix.put_slice(BLOB_REF_PREFIX);
+    prefix.put_u128(digest);
+
+    prefix.freeze()
+}
+
+/// Key对去重分块的引用: 引用前缀 + 分隔符 + 原Key
+///
+/// 分隔符使引用的Key总是大于前缀，以便从前缀之后开始扫描
+pub(crate) fn blob_ref_key(digest: u128, key: &[u8]) -> Bytes {
+    let mut ref_key = BytesMut::from(&blob_ref_prefix(digest)[..]);
+    ref_key.put_u8(0);
+    ref_key.put_slice(key);
+
+    ref_key.freeze()
+}
+
+/// 清单的标记Key: 前缀 + 原Key长度 + 原Key + 清单Value的摘要
+pub(crate) fn manifest_mark_key(key: &[u8], manifest_value: &[u8]) -> Bytes {
+    let mut mark_key = BytesMut::with_capacity(MANIFEST_MARK_PREFIX.len() + 4 + key.len() + 16);
+    mark_key.put_slice(MANIFEST_MARK_PREFIX);
+    mark_key.put_u32(key.len() as u32);
+    mark_key.put_slice(key);
+    mark_key.put_u128(digest(manifest_value));
+
+    mark_key.freeze()
+}
+
+/// 以两个不同种子的SipHash组成128位的摘要
+fn digest(chunk: &[u8]) -> u128 {
+    let hash_with_seed = |seed: u8| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u8(seed);
+        hasher.write(chunk);
+        hasher.finish()
+    };
+
+    u128::from(hash_with_seed(0)) << 64 | u128::from(hash_with_seed(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use crate::kernel::lsm::chunk::ChunkManifest;
+    use crate::kernel::Result;
+    use crate::KernelError;
+
+    #[test]
+    fn test_chunk_manifest() -> Result<()> {
+        let value = Bytes::from((0..=255u8).cycle().take(1000).collect::<Vec<u8>>());
+        let (manifest, chunks) = ChunkManifest::split(&value, 256, false);
+
+        assert_eq!(chunks.len(), 4);
+        // 内容相同的分块摘要相同
+        assert_eq!(chunks[0].0, chunks[1].0);
+        assert_ne!(chunks[2].0, chunks[3].0);
+
+        let manifest_value = manifest.to_value()?;
+        assert!(ChunkManifest::is_required(&manifest_value, 256));
+        assert_eq!(ChunkManifest::from_value(&manifest_value), Some(manifest.clone()));
+        assert_eq!(ChunkManifest::from_value(&value), None);
+
+        let chunks = chunks.into_iter()
+            .map(|(_, chunk)| chunk)
+            .collect::<Vec<_>>();
+        assert_eq!(manifest.assemble(chunks.clone())?, value);
+        assert!(matches!(manifest.assemble(chunks[1..].to_vec()), Err(KernelError::CrcMisMatch)));
+
+        Ok(())
+    }
+}
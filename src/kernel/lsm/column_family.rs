@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use crate::kernel::KVStore;
+use crate::kernel::lsm::block::CompressType;
+use crate::kernel::lsm::lsm_kv::{CompactionStyle, Compression, Config, LsmStore};
+use crate::kernel::lsm::mem_table::KeyValue;
+use crate::kernel::lsm::ss_table::DATA_COMPRESS_TYPE;
+use crate::kernel::Result;
+use crate::kernel::lsm::lsm_kv::Gen;
+use crate::kernel::lsm::write_quota::ColumnFamilyWriteStats;
+use crate::KernelError;
+
+/// 列族Key的前缀，属于内部Key，因此不会与普通的Key冲突，也不会出现在分页扫描的结果中
+const CF_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-CF\x00";
+
+/// 列族名称 -> 列族ID的元数据Key的前缀
+const CF_META_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-CF-Meta\x00";
+
+/// 设置了TTL的列族中，Value前记录的到期时间(毫秒)的长度
+const EXPIRE_AT_LEN: usize = 8;
+
+/// 列族的选项，创建列族时指定并随列族的元数据持久化，未指定的选项沿用Store的`Config`
+///
+/// Tips: 各列族共享同一Store的WAL与MemTable，
+/// 指定了压缩方式、压缩算法或Block大小的列族的数据在Flush与压缩时被写入仅包含该列族数据的SSTable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnFamilyOptions {
+    ttl: Option<Duration>,
+    table: TableOptions,
+}
+
+/// 仅包含TTL的旧版本列族选项
+#[derive(Deserialize)]
+struct TtlColumnFamilyOptions {
+    ttl: Option<Duration>,
+}
+
+impl From<TtlColumnFamilyOptions> for ColumnFamilyOptions {
+    #[inline]
+    fn from(legacy: TtlColumnFamilyOptions) -> Self {
+        ColumnFamilyOptions { ttl: legacy.ttl, table: TableOptions::default() }
+    }
+}
+
+impl ColumnFamilyOptions {
+    /// 列族中数据的存活时长，自写入时起超过ttl后不可读取，并在此后的压缩中被丢弃
+    ///
+    /// 适用于缓存、会话等仅需短期保留的数据，默认不过期
+    #[inline]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// 列族的压缩方式，如Key以时间戳为前缀的事件数据适用`CompactionStyle::TimeWindow`，默认沿用`Config::compaction_style`
+    #[inline]
+    pub fn compaction_style(mut self, compaction_style: CompactionStyle) -> Self {
+        self.table.compaction_style = Some(compaction_style);
+        self
+    }
+
+    /// 列族的DataBlock的压缩算法，如Value已自行压缩时使用`Compression::None`，默认为LZ4
+    #[inline]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.table.compression = Some(compression);
+        self
+    }
+
+    /// 列族的DataBlock大小，如以点查为主的索引列族适用较小的Block，默认沿用`Config::block_size`
+    #[inline]
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.table.block_size = Some(block_size);
+        self
+    }
+}
+
+/// 列族指定的SSTable选项，随Manifest持久化，未指定的选项沿用`Config`
+///
+/// 同一SSTable中的数据总是具有相同的选项，因此以SSTable的第一个Key即可确定其选项
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TableOptions {
+    compaction_style: Option<CompactionStyle>,
+    compression: Option<Compression>,
+    block_size: Option<usize>,
+}
+
+impl TableOptions {
+    /// 获取Key所属前缀的SSTable选项，未指定时为默认选项，即沿用`Config`
+    pub(crate) fn with_key(prefix_options: &[(Bytes, TableOptions)], key: &[u8]) -> Self {
+        prefix_options.iter()
+            .find(|(prefix, _)| key.starts_with(prefix))
+            .map(|(_, table_options)| *table_options)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == TableOptions::default()
+    }
+
+    pub(crate) fn compaction_style(&self, config: &Config) -> CompactionStyle {
+        self.compaction_style.unwrap_or(config.compaction_style)
+    }
+
+    pub(crate) fn compress_type(&self) -> CompressType {
+        self.compression.map_or(DATA_COMPRESS_TYPE, CompressType::from)
+    }
+
+    pub(crate) fn block_size(&self, config: &Config) -> usize {
+        self.block_size.unwrap_or(config.block_size)
+    }
+}
+
+/// 列族的管理视图，通过`LsmStore::column_families`获取
+///
+/// 列族在运行时创建与删除，无需重新打开Store，
+/// 各列族以创建时分配的ID作为其Key的前缀，因此删除后以相同名称重新创建的列族不会看到此前的数据
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy)]
+pub struct ColumnFamilies<'a> {
+    kv_store: &'a LsmStore,
+}
+
+impl<'a> ColumnFamilies<'a> {
+    pub(crate) fn new(kv_store: &'a LsmStore) -> Self {
+        ColumnFamilies { kv_store }
+    }
+
+    /// 以默认选项创建列族，列族已存在时直接返回该列族
+    #[inline]
+    pub async fn create(&self, name: &[u8]) -> Result<ColumnFamily<'a>> {
+        self.create_with_options(name, ColumnFamilyOptions::default()).await
+    }
+
+    /// 以options创建列族，列族已存在时直接返回该列族，且不会修改其选项
+    #[inline]
+    pub async fn create_with_options(&self, name: &[u8], options: ColumnFamilyOptions) -> Result<ColumnFamily<'a>> {
+        let meta_key = meta_key(name);
+        let _guard = self.kv_store.latches.lock(&meta_key).await;
+
+        if let Some(cf) = self.get_(name).await? {
+            return Ok(cf);
+        }
+        // Gen在重启后以当前时间戳为起点，需避免与已删除的列族的ID重复
+        let version = self.kv_store.current_version().await;
+        let id = loop {
+            let id = Gen::create();
+            if !version.dropped_prefixes().contains(&cf_prefix(id)) {
+                break id;
+            }
+        };
+        let cf = ColumnFamily::new(self.kv_store, name, id, options);
+        // 先于元数据记录TTL，使列族可见时其过期的数据即可在压缩中被丢弃
+        if let Some(ttl) = options.ttl {
+            self.kv_store.set_prefix_ttl(cf.prefix.clone(), ttl).await?;
+        }
+        // 同样先于元数据记录，使列族的数据自首次Flush起即写入单独的SSTable
+        if !options.table.is_default() {
+            self.kv_store.set_prefix_options(cf.prefix.clone(), options.table).await?;
+        }
+        let mut meta = BytesMut::new();
+        meta.put_i64(id);
+        meta.put_slice(&bincode::serialize(&options)?);
+        let _ = self.kv_store.append_cmd_data((meta_key, Some(meta.freeze())), None).await?;
+
+        Ok(cf)
+    }
+
+    /// 获取列族，列族不存在时返回None
+    #[inline]
+    pub async fn get(&self, name: &[u8]) -> Result<Option<ColumnFamily<'a>>> {
+        self.get_(name).await
+    }
+
+    /// 删除列族，返回false时表示列族不存在
+    ///
+    /// 列族的数据立即不可见，仅包含该列族数据的SSTable随即在后台删除，
+    /// 其余数据在此后的压缩中被丢弃，因此无需等待数据重写
+    ///
+    /// Tips: 删除后仍被持有的`ColumnFamily`的读写不会报错，但其写入同样会被丢弃
+    #[inline]
+    pub async fn drop(&self, name: &[u8]) -> Result<bool> {
+        let meta_key = meta_key(name);
+        let _guard = self.kv_store.latches.lock(&meta_key).await;
+
+        let Some(cf) = self.get_(name).await? else {
+            return Ok(false);
+        };
+        // 先删除元数据再记录被删除的前缀，中途崩溃时至多残留无法访问的数据，而不会使存在的列族丢失数据
+        let _ = self.kv_store.append_cmd_data((meta_key, None), None).await?;
+        self.kv_store.write_hooks().unbind_column_family(&cf.prefix);
+        self.kv_store.write_quotas().unbind_column_family(&cf.prefix);
+        self.kv_store.keyspace_events().unbind_column_family(&cf.prefix);
+        let _ = self.kv_store.drop_prefix(cf.prefix).await?;
+
+        Ok(true)
+    }
+
+    /// 元数据为列族ID + 列族的选项，仅包含TTL的旧版本选项同样可以读取
+    async fn get_(&self, name: &[u8]) -> Result<Option<ColumnFamily<'a>>> {
+        let Some(mut meta) = self.kv_store.get_local(&meta_key(name)).await? else {
+            return Ok(None);
+        };
+        if meta.len() < 8 {
+            return Err(KernelError::Internal("malformed column family meta".to_owned()));
+        }
+        let id = meta.get_i64();
+        let options = bincode::deserialize(&meta)
+            .or_else(|_| bincode::deserialize::<TtlColumnFamilyOptions>(&meta).map(ColumnFamilyOptions::from))?;
+
+        Ok(Some(ColumnFamily::new(self.kv_store, name, id, options)))
+    }
+}
+
+/// 列族视图，通过`ColumnFamilies::create`或`ColumnFamilies::get`获取
+///
+/// 各列族共享同一Store的WAL、MemTable与SSTable，
+/// 列族内的Key以列族ID作为前缀存储，因此不同列族中相同的Key互不影响
+///
+/// 需要同时写入多个列族时(如数据与其二级索引)，通过`WriteBatch`与`LsmStore::write`原子性地写入
+#[allow(missing_debug_implementations)]
+#[derive(Clone)]
+pub struct ColumnFamily<'a> {
+    kv_store: &'a LsmStore,
+    prefix: Bytes,
+    options: ColumnFamilyOptions,
+}
+
+impl<'a> ColumnFamily<'a> {
+    fn new(kv_store: &'a LsmStore, name: &[u8], id: i64, options: ColumnFamilyOptions) -> Self {
+        let prefix = cf_prefix(id);
+        kv_store.write_hooks().bind_column_family(&prefix, name, options.ttl.is_some());
+        kv_store.write_quotas().bind_column_family(&prefix, name);
+        kv_store.keyspace_events().bind_column_family(&prefix, name);
+
+        ColumnFamily { kv_store, prefix, options }
+    }
+
+    #[inline]
+    pub fn options(&self) -> &ColumnFamilyOptions {
+        &self.options
+    }
+
+    /// 该列族的写入统计，包括因超出`Config::column_family_write_quota`而等待的次数与时长
+    #[inline]
+    pub fn write_stats(&self) -> ColumnFamilyWriteStats {
+        self.kv_store.write_quotas().stats(&self.prefix)
+    }
+
+    #[inline]
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        let Some(mut value) = self.kv_store.get(&self.key(key)).await? else {
+            return Ok(None);
+        };
+        if self.options.ttl.is_some() {
+            if value.len() < EXPIRE_AT_LEN {
+                return Err(KernelError::Internal("malformed column family value".to_owned()));
+            }
+            if is_expired(&value, self.now_millis()) {
+                return Ok(None);
+            }
+            value.advance(EXPIRE_AT_LEN);
+        }
+
+        Ok(Some(value))
+    }
+
+    #[inline]
+    pub async fn set(&self, key: &[u8], value: Bytes) -> Result<()> {
+        self.kv_store.set(&self.key(key), self.value(value)).await
+    }
+
+    /// 删除列族中的Key，Key不存在时返回`KernelError::KeyNotFound`
+    ///
+    /// Tips: 已过期但尚未在压缩中被丢弃的Key仍视为存在
+    #[inline]
+    pub async fn remove(&self, key: &[u8]) -> Result<()> {
+        self.kv_store.remove(&self.key(key)).await
+    }
+
+    fn key(&self, key: &[u8]) -> Bytes {
+        let mut cf_key = BytesMut::with_capacity(self.prefix.len() + key.len());
+        cf_key.put_slice(&self.prefix);
+        cf_key.put_slice(key);
+
+        cf_key.freeze()
+    }
+
+    /// 设置了TTL时，以写入时间 + TTL作为到期时间记录于Value前
+    fn value(&self, value: Bytes) -> Bytes {
+        let Some(ttl) = self.options.ttl else {
+            return value;
+        };
+        let mut cf_value = BytesMut::with_capacity(EXPIRE_AT_LEN + value.len());
+        cf_value.put_i64(self.now_millis() + ttl.as_millis() as i64);
+        cf_value.put_slice(&value);
+
+        cf_value.freeze()
+    }
+
+    fn now_millis(&self) -> i64 {
+        self.kv_store.config().clock.now_millis()
+    }
+}
+
+/// 可跨越多个列族的批量写入，通过`LsmStore::write`原子性地提交
+///
+/// 同一Key的多次写入以最后一次为准
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch {
+    writes: BTreeMap<Bytes, Option<Bytes>>,
+}
+
+impl WriteBatch {
+    #[inline]
+    pub fn set(&mut self, key: &[u8], value: Bytes) -> &mut Self {
+        let _ = self.writes.insert(Bytes::copy_from_slice(key), Some(value));
+        self
+    }
+
+    #[inline]
+    pub fn remove(&mut self, key: &[u8]) -> &mut Self {
+        let _ = self.writes.insert(Bytes::copy_from_slice(key), None);
+        self
+    }
+
+    /// 写入列族中的Key
+    #[inline]
+    pub fn set_cf(&mut self, cf: &ColumnFamily<'_>, key: &[u8], value: Bytes) -> &mut Self {
+        let _ = self.writes.insert(cf.key(key), Some(cf.value(value)));
+        self
+    }
+
+    /// 删除列族中的Key，与`ColumnFamily::remove`不同，Key不存在时不会返回错误
+    #[inline]
+    pub fn remove_cf(&mut self, cf: &ColumnFamily<'_>, key: &[u8]) -> &mut Self {
+        let _ = self.writes.insert(cf.key(key), None);
+        self
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    pub(crate) fn into_data(self) -> Vec<KeyValue> {
+        self.writes.into_iter().collect()
+    }
+}
+
+/// 判断设置了TTL的列族中的Value是否已于now(毫秒)过期
+pub(crate) fn is_expired(value: &[u8], now: i64) -> bool {
+    value.get(..EXPIRE_AT_LEN)
+        .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+        .is_some_and(|bytes| i64::from_be_bytes(bytes) <= now)
+}
+
+/// 将列族中的Key拆分为列族的Key前缀与列族内的Key，非列族的Key时返回None
+pub(crate) fn split_cf_key(key: &[u8]) -> Option<(&[u8], &[u8])> {
+    key.starts_with(CF_KEY_PREFIX)
+        .then(|| key.split_at_checked(CF_KEY_PREFIX.len() + 8))
+        .flatten()
+}
+
+/// 去除设置了TTL的列族中Value前记录的到期时间
+pub(crate) fn strip_expire_at(value: &[u8]) -> &[u8] {
+    value.get(EXPIRE_AT_LEN..).unwrap_or_default()
+}
+
+fn meta_key(name: &[u8]) -> Bytes {
+    let mut key = BytesMut::with_capacity(CF_META_KEY_PREFIX.len() + name.len());
+    key.put_slice(CF_META_KEY_PREFIX);
+    key.put_slice(name);
+
+    key.freeze()
+}
+
+fn cf_prefix(id: i64) -> Bytes {
+    let mut prefix = BytesMut::with_capacity(CF_KEY_PREFIX.len() + 8);
+    prefix.put_slice(CF_KEY_PREFIX);
+    prefix.put_i64(id);
+
+    prefix.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::column_family::{ColumnFamilyOptions, WriteBatch};
+    use crate::kernel::lsm::lsm_kv::{CompactionStyle, Compression, Config, LsmStore};
+    use crate::kernel::Result;
+    use crate::kernel::utils::clock::VirtualClock;
+    use crate::kernel::utils::quota::Quota;
+
+    #[test]
+    fn test_write_batch_across_column_families() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            {
+                let users = kv_store.column_families().create(b"users").await?;
+                let by_name = kv_store.column_families().create(b"users_by_name").await?;
+                users.set(b"1", Bytes::from_static(b"old")).await?;
+
+                let mut batch = WriteBatch::default();
+                let _ = batch.set_cf(&users, b"1", Bytes::from_static(b"kip"))
+                    .set_cf(&by_name, b"kip", Bytes::from_static(b"1"))
+                    .remove_cf(&by_name, b"old")
+                    .set(b"1", Bytes::from_static(b"default"));
+                assert_eq!(batch.len(), 4);
+                let _ = kv_store.write(batch).await?;
+
+                assert_eq!(users.get(b"1").await?, Some(Bytes::from_static(b"kip")));
+                assert_eq!(by_name.get(b"kip").await?, Some(Bytes::from_static(b"1")));
+                // 不同列族中相同的Key互不影响
+                assert_eq!(kv_store.get(b"1").await?, Some(Bytes::from_static(b"default")));
+                assert_eq!(by_name.get(b"1").await?, None);
+            }
+            drop(kv_store);
+
+            // 批量数据由WAL整体恢复
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            let column_families = kv_store.column_families();
+            let users = column_families.get(b"users").await?.expect("column family lost");
+            let by_name = column_families.get(b"users_by_name").await?.expect("column family lost");
+            assert_eq!(users.get(b"1").await?, Some(Bytes::from_static(b"kip")));
+            assert_eq!(by_name.get(b"kip").await?, Some(Bytes::from_static(b"1")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_drop_column_family() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path());
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            let column_families = kv_store.column_families();
+            {
+                let logs = column_families.create(b"logs").await?;
+                // 使列族的元数据与数据位于不同的SSTable中
+                kv_store.flush().await?;
+                for i in 0..100_u32 {
+                    logs.set(&i.to_be_bytes(), Bytes::from_static(b"kip")).await?;
+                }
+                kv_store.flush().await?;
+                kv_store.set(b"k", Bytes::from_static(b"v")).await?;
+                kv_store.flush().await?;
+                assert_eq!(kv_store.current_version().await.level_sst_count()[0], 3);
+
+                assert!(column_families.drop(b"logs").await?);
+                assert!(!column_families.drop(b"logs").await?);
+                assert!(column_families.get(b"logs").await?.is_none());
+            }
+            // 仅包含该列族数据的SSTable被直接删除
+            assert_eq!(kv_store.current_version().await.level_sst_count()[0], 2);
+
+            // 以相同名称重新创建的列族不会看到此前的数据
+            let logs = column_families.create(b"logs").await?;
+            assert_eq!(logs.get(&0_u32.to_be_bytes()).await?, None);
+            logs.set(b"new", Bytes::from_static(b"kip")).await?;
+            kv_store.flush().await?;
+            drop(kv_store);
+
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let logs = kv_store.column_families().get(b"logs").await?.expect("column family lost");
+            assert_eq!(logs.get(&1_u32.to_be_bytes()).await?, None);
+            assert_eq!(logs.get(b"new").await?, Some(Bytes::from_static(b"kip")));
+            assert_eq!(kv_store.get(b"k").await?, Some(Bytes::from_static(b"v")));
+            assert_eq!(kv_store.current_version().await.dropped_prefixes().len(), 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_column_family_ttl() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .clock(Arc::new(clock.clone()));
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            let options = ColumnFamilyOptions::default().ttl(Duration::from_secs(60));
+            {
+                let sessions = kv_store.column_families().create_with_options(b"sessions", options).await?;
+                let users = kv_store.column_families().create(b"users").await?;
+                sessions.set(b"1", Bytes::from_static(b"kip")).await?;
+                users.set(b"1", Bytes::from_static(b"kip")).await?;
+                kv_store.flush().await?;
+                assert_eq!(sessions.get(b"1").await?, Some(Bytes::from_static(b"kip")));
+
+                clock.advance(Duration::from_secs(61));
+                assert_eq!(sessions.get(b"1").await?, None);
+                assert_eq!(users.get(b"1").await?, Some(Bytes::from_static(b"kip")));
+
+                // 过期的数据在压缩中被丢弃，新的SSTable在下一次压缩时才会被合并至Level 1
+                sessions.set(b"2", Bytes::from_static(b"db")).await?;
+                kv_store.flush().await?;
+                sessions.set(b"3", Bytes::from_static(b"db")).await?;
+                kv_store.flush().await?;
+                assert_eq!(kv_store.get(&sessions.key(b"1")).await?, None);
+                assert_eq!(sessions.get(b"2").await?, Some(Bytes::from_static(b"db")));
+            }
+            drop(kv_store);
+
+            // 列族的选项随元数据持久化
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let sessions = kv_store.column_families().get(b"sessions").await?.expect("column family lost");
+            assert_eq!(sessions.options(), &options);
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(sessions.get(b"2").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_column_family_table_options() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .clock(Arc::new(clock.clone()));
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            let events_options = ColumnFamilyOptions::default()
+                .compaction_style(CompactionStyle::TimeWindow {
+                    window: Duration::from_secs(60),
+                    ttl: Some(Duration::from_secs(60)),
+                })
+                .compression(Compression::None);
+            let value = Bytes::from(vec![b'k'; 1024]);
+            {
+                let events = kv_store.column_families().create_with_options(b"events", events_options).await?;
+                let index = kv_store.column_families()
+                    .create_with_options(b"index", ColumnFamilyOptions::default().block_size(64)).await?;
+                for i in 0..100_u32 {
+                    events.set(&i.to_be_bytes(), value.clone()).await?;
+                    index.set(&i.to_be_bytes(), value.clone()).await?;
+                }
+                kv_store.set(b"k", Bytes::from_static(b"v")).await?;
+                kv_store.flush().await?;
+
+                // 时间窗口压缩的列族的SSTable在窗口结束前保留于Level 0，其余的数据被分层压缩至Level 1
+                let version = kv_store.current_version().await;
+                let ss_tables_l0 = version.get_ss_tables_for_level(0).await;
+                assert!(!ss_tables_l0.is_empty());
+                assert!(ss_tables_l0.iter().all(|ss_table| ss_table.get_scope().start.starts_with(&events.prefix)));
+                // 未压缩的列族与以LZ4压缩的列族写入同样的数据
+                let events_size: u64 = ss_tables_l0.iter()
+                    .map(|ss_table| ss_table.get_size_of_disk())
+                    .sum();
+                let index_size: u64 = version.get_ss_tables_for_level(1).await
+                    .iter()
+                    .filter(|ss_table| ss_table.get_scope().start.starts_with(&index.prefix))
+                    .map(|ss_table| ss_table.get_size_of_disk())
+                    .sum();
+                assert!(events_size > 100 * 1024);
+                assert!(index_size > 0 && index_size < events_size / 4);
+            }
+            drop(kv_store);
+
+            // 列族的SSTable选项随Manifest持久化
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let events = kv_store.column_families().get(b"events").await?.expect("column family lost");
+            let index = kv_store.column_families().get(b"index").await?.expect("column family lost");
+            assert_eq!(events.options(), &events_options);
+            assert_eq!(kv_store.current_version().await.prefix_options().len(), 2);
+            assert_eq!(events.get(&0_u32.to_be_bytes()).await?, Some(value.clone()));
+            assert_eq!(index.get(&99_u32.to_be_bytes()).await?, Some(value.clone()));
+
+            // 窗口结束ttl后列族的SSTable被整体删除，其余的数据不受影响
+            clock.advance(Duration::from_secs(121));
+            kv_store.flush().await?;
+            assert_eq!(events.get(&0_u32.to_be_bytes()).await?, None);
+            assert_eq!(index.get(&99_u32.to_be_bytes()).await?, Some(value));
+            assert_eq!(kv_store.get(b"k").await?, Some(Bytes::from_static(b"v")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_column_family_write_quota() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .column_family_write_quota(b"events", Quota::default().requests_per_sec(2));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let events = kv_store.column_families().create(b"events").await?;
+            let users = kv_store.column_families().create(b"users").await?;
+
+            // 初始配额可供两次写入，第三次写入需等待令牌补足
+            let start = Instant::now();
+            for i in 0..3_u8 {
+                events.set(&[i], Bytes::from_static(b"kip")).await?;
+            }
+            assert!(start.elapsed() >= Duration::from_millis(400));
+
+            let stats = events.write_stats();
+            assert_eq!(stats.writes, 3);
+            assert_eq!(stats.written_bytes, 3 * (events.key(&[0]).len() as u64 + 3));
+            assert_eq!(stats.throttled_writes, 1);
+            assert!(stats.throttled_micros > 0);
+
+            // 未设置配额的列族不受限制，批量写入同样计入列族的统计
+            let mut batch = WriteBatch::default();
+            let _ = batch.set_cf(&users, b"1", Bytes::from_static(b"kip"))
+                .set_cf(&users, b"2", Bytes::from_static(b"kip"))
+                .set_cf(&users, b"3", Bytes::from_static(b"kip"));
+            let _ = kv_store.write(batch).await?;
+            users.remove(b"1").await?;
+            let stats = users.write_stats();
+            assert_eq!(stats.writes, 4);
+            assert_eq!(stats.throttled_writes, 0);
+
+            Ok(())
+        })
+    }
+}
@@ -1,22 +1,29 @@
 use std::collections::HashSet;
+use std::{fs, iter};
 use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use bytes::Bytes;
 use futures::future;
 use itertools::Itertools;
 use tokio::sync::oneshot;
-use tracing::info;
+use tracing::{error, info, warn};
 use crate::KernelError;
-use crate::kernel::io::IoFactory;
+use crate::kernel::io::{FileAdvice, FileExtension, IoFactory};
 use crate::kernel::Result;
+use crate::kernel::lsm::adaptive::AdaptiveController;
 use crate::kernel::lsm::block::BlockCache;
-use crate::kernel::lsm::lsm_kv::{Config, Gen, StoreInner};
-use crate::kernel::lsm::data_sharding;
+use crate::kernel::lsm::column_family;
+use crate::kernel::lsm::column_family::TableOptions;
+use crate::kernel::lsm::event::QuarantineReport;
+use crate::kernel::lsm::lsm_kv::{CompactionStyle, Config, Gen, Sequence, StoreInner};
+use crate::kernel::lsm::{data_sharding_with_options, FORMAT_VERSION, split_with_options};
 use crate::kernel::lsm::iterator::DiskIter;
 use crate::kernel::lsm::iterator::sstable_iter::SSTableIter;
 use crate::kernel::lsm::mem_table::{KeyValue, MemTable};
-use crate::kernel::lsm::ss_table::{Scope, SSTable};
-use crate::kernel::lsm::version::{VersionEdit, VersionStatus};
+use crate::kernel::lsm::ss_table::{LostRange, Scope, SequenceRange, SSTable};
+use crate::kernel::lsm::stats::Statistics;
+use crate::kernel::lsm::version::{DEFAULT_QUARANTINE_PATH, DiscardFilter, Version, VersionEdit, VersionStatus};
 
 pub(crate) const LEVEL_0: usize = 0;
 
@@ -24,13 +31,28 @@ pub(crate) const LEVEL_0: usize = 0;
 /// 包含对应分片的Gen与数据
 pub(crate) type MergeShardingVec = Vec<(i64, Vec<KeyValue>)>;
 
+/// 时间窗口压缩时同一窗口的SSTable，未记录窗口时为None
+type WindowGroup = (Option<i64>, Vec<SSTable>);
+
 /// Major压缩时的待删除Gen封装(N为此次Major所压缩的Level)，第一个为Level N级，第二个为Level N+1级
 pub(crate) type DelGenVec = (Vec<i64>, Vec<i64>);
 
 /// Store与Compactor的交互信息
 #[derive(Debug)]
 pub(crate) enum CompactTask {
-    Flush(Option<oneshot::Sender<()>>)
+    Flush(Option<oneshot::Sender<()>>),
+    /// 将有序且唯一的数据直接导入为Level 0的SSTable
+    Ingest(Vec<KeyValue>, oneshot::Sender<Result<()>>),
+    /// 隔离指定Gen的SSTable
+    Quarantine(i64, oneshot::Sender<Result<QuarantineReport>>),
+    /// 将旧格式的SSTable重写为当前格式
+    Migrate(u32, oneshot::Sender<Result<usize>>),
+    /// 删除Key前缀
+    DropPrefix(Bytes, oneshot::Sender<Result<usize>>),
+    /// 设置Key前缀的TTL
+    PrefixTtl(Bytes, Duration, oneshot::Sender<Result<()>>),
+    /// 设置Key前缀的SSTable选项
+    PrefixOptions(Bytes, TableOptions, oneshot::Sender<Result<()>>),
 }
 
 /// 压缩器
@@ -38,16 +60,102 @@ pub(crate) enum CompactTask {
 /// 负责Minor和Major压缩
 pub(crate) struct Compactor {
     store_inner: Arc<StoreInner>,
+    /// 自适应压缩控制器，为None时使用固定的Major压缩阈值
+    adaptive: Option<AdaptiveController>,
 }
 
 impl Compactor {
 
     pub(crate) fn new(store_inner: Arc<StoreInner>) -> Self {
-        Compactor { store_inner }
+        let config = &store_inner.config;
+        let adaptive = config.adaptive_compaction.clone()
+            .map(|bounds| AdaptiveController::new(bounds, config.major_threshold_with_sst_size));
+        let major_threshold = adaptive.as_ref()
+            .map_or(config.major_threshold_with_sst_size, AdaptiveController::major_threshold);
+        store_inner.stats.major_threshold.store(major_threshold, Ordering::Relaxed);
+
+        Compactor { store_inner, adaptive }
+    }
+
+    /// 当前生效的Major压缩阈值
+    fn major_threshold(&self) -> usize {
+        self.adaptive.as_ref()
+            .map_or(self.config().major_threshold_with_sst_size, AdaptiveController::major_threshold)
+    }
+
+    /// 根据负载调整自适应压缩的阈值
+    fn adjust_major_threshold(&mut self) {
+        let stats = &self.store_inner.stats;
+        if let Some(adaptive) = &mut self.adaptive {
+            let major_threshold = adaptive.adjust(
+                stats.get_latency.count(),
+                stats.set_latency.count(),
+                stats.compaction_pending.load(Ordering::Relaxed)
+            );
+            stats.major_threshold.store(major_threshold, Ordering::Relaxed);
+        }
+    }
+
+    /// 获取Level对应存储路径的IoFactory
+    fn sst_factory(&self, level: usize) -> &IoFactory {
+        self.store_inner.ver_status
+            .get_sst_factory_ref(self.config().sst_path_id(level))
+    }
+
+    /// 新增SSTable的VersionEdit，同时记录其所在的存储路径
+    fn new_file_edits(&self, vec_gen: Vec<i64>, level: usize, index: usize) -> Vec<VersionEdit> {
+        vec![
+            VersionEdit::FilePath(vec_gen.clone(), self.config().sst_path_id(level)),
+            VersionEdit::NewFile((vec_gen, level), index),
+        ]
     }
 
-    fn sst_factory(&self) -> &IoFactory {
-        self.store_inner.ver_status.get_sst_factory_ref()
+    /// SSTable所属前缀的压缩方式
+    fn compaction_style(&self, version: &Version, ss_table: &SSTable) -> CompactionStyle {
+        version.table_options(&ss_table.get_scope().start)
+            .compaction_style(self.config())
+    }
+
+    fn is_leveled(&self, version: &Version, ss_table: &SSTable) -> bool {
+        self.compaction_style(version, ss_table) == CompactionStyle::Leveled
+    }
+
+    /// Store中的数据所使用的各压缩方式，未指定压缩方式的前缀使用`Config::compaction_style`
+    fn compaction_styles<'a>(&'a self, version: &'a Version) -> impl Iterator<Item = CompactionStyle> + 'a {
+        iter::once(self.config().compaction_style)
+            .chain(version.prefix_options()
+                .iter()
+                .map(|(_, table_options)| table_options.compaction_style(self.config())))
+    }
+
+    /// 以压缩所输出的数据创建SSTable，DataBlock的大小与压缩方式由数据所属前缀的SSTable选项决定
+    ///
+    /// 开启`Config::verify_compaction_output`时创建后立即重新读取并校验，校验失败时删除该文件并返回错误，
+    /// 使此次压缩不会写入Manifest，被压缩的SSTable保持不变
+    fn create_output(
+        &self,
+        version: &Version,
+        gen: i64,
+        vec_data: Vec<KeyValue>,
+        level: usize,
+        sequence_range: SequenceRange
+    ) -> Result<SSTable> {
+        let expected_len = vec_data.len();
+        let table_options = vec_data.first()
+            .map(|(key, _)| version.table_options(key))
+            .unwrap_or_default();
+        let ss_table = SSTable::create_with_sequence(
+            self.config(), gen, self.sst_factory(level), vec_data, level, sequence_range, &table_options
+        )?;
+
+        if self.config().verify_compaction_output {
+            if let Err(err) = ss_table.verify_output(expected_len) {
+                error!("[Compactor][verify_output][SSTable: {}][Level: {}]: {:?}", gen, level, err);
+                let _ignore = fs::remove_file(ss_table.get_path());
+                return Err(err);
+            }
+        }
+        Ok(ss_table)
     }
 
     /// 检查并进行压缩 （默认为 异步、被动 的Lazy压缩）
@@ -60,13 +168,42 @@ impl Compactor {
         &mut self,
         option_tx: Option<oneshot::Sender<()>>
     ) -> Result<()> {
-        if let Some(values) = self.mem_table().swap() {
+        let _ = self.mem_table().expire_snapshots();
+        self.adjust_major_threshold();
+        // 先删除过期的SSTable与仅包含被删除前缀数据的SSTable，避免其数据被合并
+        self.drop_expired_ss_tables().await?;
+        let _ = self.drop_prefix_ss_tables().await?;
+        // 弹出的数据被Flush前的Version，用于版本保留时的时间点读取
+        let base = self.ver_status().current().await;
+        if let Some((values, sequence_range)) = self.mem_table().swap(Some(base)) {
             if !values.is_empty() {
                 let gen = self.switch_wal()?;
                 let start = Instant::now();
                 // 目前minor触发major时是同步进行的，所以此处对live_tag是在此方法体保持存活
-                self.minor_compaction(gen, values).await?;
+                self.minor_compaction(gen, values, sequence_range).await?;
                 info!("[Compactor][Compaction Drop][Time: {:?}]", start.elapsed());
+
+                let stats = &self.store_inner.stats;
+                let _ = stats.minor_compaction_count.fetch_add(1, Ordering::Relaxed);
+                Statistics::add_micros(&stats.compaction_micros, start.elapsed());
+                stats.compaction_latency.record(start.elapsed());
+                // MemTable已被清空，重置内存预算的检测水位
+                stats.memory_check_watermark.store(0, Ordering::Relaxed);
+
+                let version = self.ver_status().current().await;
+                if self.compaction_styles(&version).any(|style| style == CompactionStyle::Leveled) {
+                    self.level_0_intra_compaction().await?;
+                    self.small_file_merge().await?;
+                }
+            }
+        }
+
+        // 去重分块的引用计数在压缩时统一处理
+        self.store_inner.sweep_blobs().await?;
+        // 隔离失败时不影响压缩的响应，仅进行记录
+        for gen in self.store_inner.take_corrupted() {
+            if let Err(err) = self.quarantine(gen).await {
+                error!("[Compactor][quarantine][SSTable: {}][Error]: {:?}", gen, err);
             }
         }
 
@@ -76,6 +213,269 @@ impl Compactor {
         Ok(())
     }
 
+    /// 将有序且唯一的数据直接构建为SSTable并导入至Level 0，跳过MemTable与WAL
+    ///
+    /// 导入前会先持久化MemTable并丢弃Immutable MemTable，使导入的数据比已写入的数据更新
+    pub(crate) async fn ingest(&mut self, values: Vec<KeyValue>) -> Result<()> {
+        self.check_then_compaction(None).await?;
+        self.mem_table().discard_immut();
+
+        if values.is_empty() {
+            return Ok(());
+        }
+        let start = Instant::now();
+        let len = values.len();
+        let version = self.ver_status().current().await;
+        let mut vec_ss_table = Vec::new();
+        // 导入的数据视为在当前Sequence写入
+        let sequence = Sequence::latest();
+
+        for (gen, sharding) in data_sharding_with_options(
+            values,
+            self.config().target_file_size(LEVEL_0),
+            version.prefix_options()
+        ) {
+            let table_options = version.table_options(&sharding[0].0);
+            vec_ss_table.push(SSTable::create_with_sequence(
+                self.config(),
+                gen,
+                self.sst_factory(LEVEL_0),
+                sharding,
+                LEVEL_0,
+                SequenceRange::new(sequence, sequence),
+                &table_options
+            )?);
+        }
+        let vec_gen = SSTable::collect_gen(&vec_ss_table)?;
+        self.ver_status().insert_vec_ss_table(vec_ss_table.clone()).await?;
+
+        let mut vec_ver_edit = self.new_file_edits(vec_gen, LEVEL_0, 0);
+        vec_ver_edit.push(VersionEdit::LastSequence(Sequence::latest()));
+        self.compaction_with_style(&vec_ss_table, vec_ver_edit).await?;
+        info!("[Compactor][Ingest][Len: {}][Time: {:?}]", len, start.elapsed());
+
+        Ok(())
+    }
+
+    /// 隔离损坏的SSTable
+    ///
+    /// 将其复制至隔离目录并从Version中移除，原文件在不再被旧Version引用后由Cleaner删除，
+    /// 此后的读取不再访问该SSTable
+    ///
+    /// 开启`Config::rebuild_quarantined`时，可通过校验的数据与经`Config::read_fallback`取回的数据
+    /// 会被重建为新的SSTable置于原位置；
+    /// Level 0的SSTable之间以位置决定新旧，而新SSTable总是被置于Level 0的最尾，
+    /// 因此位于其后的SSTable会随之依次重写，使重建的SSTable仍位于它们之前
+    pub(crate) async fn quarantine(&mut self, gen: i64) -> Result<QuarantineReport> {
+        let version = self.ver_status().current().await;
+        let (level, ss_table) = version.get_all_ss_tables().await
+            .into_iter()
+            .enumerate()
+            .find_map(|(level, ss_tables)| {
+                ss_tables.into_iter()
+                    .find(|ss_table| ss_table.get_gen() == gen)
+                    .map(|ss_table| (level, ss_table))
+            })
+            .ok_or(KernelError::SSTableLost)?;
+        let index = version.get_index(level, gen)
+            .ok_or(KernelError::SSTableLost)?;
+        let table_options = version.table_options(&ss_table.get_scope().start);
+        drop(version);
+
+        let quarantine_dir = self.config().dir_path.join(DEFAULT_QUARANTINE_PATH);
+        fs::create_dir_all(&quarantine_dir)?;
+        let path = FileExtension::SSTable.path_with_gen(&quarantine_dir, gen);
+        let _ = fs::copy(ss_table.get_path(), &path)?;
+
+        let (mut vec_data, lost_ranges) = ss_table.salvage();
+        let readable_len = vec_data.len();
+        let fallback_reads = self.store_inner.take_fallback_reads(gen);
+        let mut vec_ver_edit = vec![VersionEdit::DeleteFile((vec![gen], level))];
+        let mut rebuilt_gen = None;
+        let mut repaired_len = 0;
+
+        if self.config().rebuild_quarantined {
+            let scope = ss_table.get_scope();
+            let mut vec_repaired = self.fetch_lost_ranges(&lost_ranges, scope).await;
+            // scan所取回的数据优先于此前get所取回的数据，排序为稳定排序因此去重时保留前者
+            vec_repaired.extend(
+                fallback_reads.into_iter()
+                    .filter(|(key, _)| scope.meet_with_key(key))
+                    .map(|(key, value)| (key, Some(value)))
+            );
+            vec_repaired.sort_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+            vec_repaired.dedup_by(|(key_a, _), (key_b, _)| key_a == key_b);
+            // 可读取的数据中已存在的Key不再补全
+            vec_repaired.retain(|(key, _)| vec_data.binary_search_by(|(data_key, _)| data_key.cmp(key)).is_err());
+            repaired_len = vec_repaired.len();
+            vec_data.append(&mut vec_repaired);
+            vec_data.sort_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+        }
+        if self.config().rebuild_quarantined && !vec_data.is_empty() {
+            let new_gen = Gen::create();
+            let new_ss_table = SSTable::create_with_sequence(
+                self.config(),
+                new_gen,
+                self.sst_factory(level),
+                vec_data,
+                level,
+                ss_table.get_sequence_range(),
+                &table_options
+            )?;
+            self.ver_status().insert_vec_ss_table(vec![new_ss_table]).await?;
+            if level == LEVEL_0 {
+                let newer_ss_tables = self.ver_status().current().await
+                    .get_ss_tables_for_level(LEVEL_0).await
+                    .into_iter()
+                    .skip_while(|ss_table| ss_table.get_gen() != gen)
+                    .skip(1)
+                    .collect_vec();
+                let mut vec_new_gen = vec![new_gen];
+
+                for newer_ss_table in newer_ss_tables.iter() {
+                    let (rewritten_gen, mut vec_attr_edit) = self.rewrite_ss_table(newer_ss_table, LEVEL_0).await?;
+                    vec_new_gen.push(rewritten_gen);
+                    vec_ver_edit.append(&mut vec_attr_edit);
+                }
+                if !newer_ss_tables.is_empty() {
+                    vec_ver_edit.push(VersionEdit::DeleteFile((SSTable::collect_gen(&newer_ss_tables)?, LEVEL_0)));
+                }
+                vec_ver_edit.append(&mut self.new_file_edits(vec_new_gen, LEVEL_0, 0));
+            } else {
+                vec_ver_edit.append(&mut self.new_file_edits(vec![new_gen], level, index));
+            }
+            rebuilt_gen = Some(new_gen);
+        }
+        self.ver_status().log_and_apply(vec_ver_edit).await?;
+        // 隔离后Key可能回退至旧版本或不再存在
+        if let Some(row_cache) = &self.store_inner.row_cache {
+            row_cache.invalidate_all();
+        }
+
+        let report = QuarantineReport {
+            gen,
+            level,
+            path,
+            len: ss_table.len(),
+            readable_len,
+            lost_blocks: lost_ranges.len(),
+            repaired_len,
+            rebuilt_gen,
+        };
+        warn!("[Compactor][Quarantine]: {:?}", report);
+        self.config().notify_quarantine(&report);
+
+        Ok(report)
+    }
+
+    /// 通过`Config::read_fallback`读取无法读取的数据Block中的数据
+    ///
+    /// 仅保留SSTable原本范围内的数据，避免重建的SSTable与同Level的其他SSTable重叠，
+    /// 读取失败的范围仅进行记录，其中的数据在重建后丢失
+    async fn fetch_lost_ranges(&self, lost_ranges: &[LostRange], scope: &Scope) -> Vec<KeyValue> {
+        let mut vec_data = Vec::new();
+        let Some(read_fallback) = &self.config().read_fallback else {
+            return vec_data;
+        };
+
+        for LostRange { start, end } in lost_ranges {
+            // 不限下界时由scope.start去除末尾字节后的位置开始，使Key与scope.start相同的数据同样被读取
+            let start = start.as_deref()
+                .unwrap_or(&scope.start[..scope.start.len().saturating_sub(1)]);
+
+            match read_fallback.scan(start, end).await {
+                Ok(items) => vec_data.extend(
+                    items.into_iter()
+                        .filter(|(key, _)| key.as_ref() > start && key <= end && scope.meet_with_key(key))
+                        .map(|(key, value)| (key, Some(value)))
+                ),
+                Err(err) => warn!("[Compactor][fetch_lost_ranges][Range: {:?}..={:?}][Error]: {:?}", start, end, err),
+            }
+        }
+
+        vec_data
+    }
+
+    /// 将格式版本低于to_version的SSTable重写为当前格式，返回重写的SSTable数量
+    ///
+    /// 会先持久化MemTable，使WAL中的数据以当前格式写入SSTable
+    ///
+    /// Level 1-6中的SSTable逐个重写并置于原位置，每个SSTable的重写均单独持久化至Version，
+    /// 因此中断后再次执行时仅会重写剩余的旧格式SSTable；
+    /// Level 0的SSTable之间以位置决定新旧，而新SSTable总是被置于Level 0的最尾，
+    /// 因此存在旧格式时按原顺序整体重写
+    pub(crate) async fn migrate(&mut self, to_version: u32) -> Result<usize> {
+        if to_version > FORMAT_VERSION {
+            return Err(KernelError::UnsupportedFormatVersion(to_version));
+        }
+        self.check_then_compaction(None).await?;
+
+        let is_outdated = |ss_table: &SSTable| ss_table.get_format_version() < to_version;
+        let mut migrated = 0;
+
+        let ss_tables_l0 = self.ver_status().current().await
+            .get_ss_tables_for_level(LEVEL_0).await;
+        if ss_tables_l0.iter().any(is_outdated) {
+            let mut vec_ver_edit = vec![VersionEdit::DeleteFile((SSTable::collect_gen(&ss_tables_l0)?, LEVEL_0))];
+            let mut vec_new_gen = Vec::with_capacity(ss_tables_l0.len());
+
+            for ss_table in ss_tables_l0.iter() {
+                let (new_gen, mut vec_attr_edit) = self.rewrite_ss_table(ss_table, LEVEL_0).await?;
+                vec_new_gen.push(new_gen);
+                vec_ver_edit.append(&mut vec_attr_edit);
+            }
+            vec_ver_edit.append(&mut self.new_file_edits(vec_new_gen, LEVEL_0, 0));
+            self.ver_status().log_and_apply(vec_ver_edit).await?;
+            migrated += ss_tables_l0.iter().filter(|ss_table| is_outdated(ss_table)).count();
+        }
+
+        for level in 1..7 {
+            let ss_tables = self.ver_status().current().await
+                .get_ss_tables_for_level(level).await;
+
+            for ss_table in ss_tables.iter().filter(|ss_table| is_outdated(ss_table)) {
+                let gen = ss_table.get_gen();
+                let index = self.ver_status().current().await
+                    .get_index(level, gen)
+                    .ok_or(KernelError::SSTableLost)?;
+                let (new_gen, mut vec_attr_edit) = self.rewrite_ss_table(ss_table, level).await?;
+                let mut vec_ver_edit = vec![VersionEdit::DeleteFile((vec![gen], level))];
+
+                vec_ver_edit.append(&mut self.new_file_edits(vec![new_gen], level, index));
+                vec_ver_edit.append(&mut vec_attr_edit);
+                self.ver_status().log_and_apply(vec_ver_edit).await?;
+                migrated += 1;
+            }
+        }
+        info!("[Compactor][Migrate][To Version: {}][Migrated: {}]", to_version, migrated);
+
+        Ok(migrated)
+    }
+
+    /// 以当前格式重写SSTable，返回新SSTable的Gen，以及继承原SSTable属性所需的VersionEdit
+    async fn rewrite_ss_table(&self, ss_table: &SSTable, level: usize) -> Result<(i64, Vec<VersionEdit>)> {
+        let gen = ss_table.get_gen();
+        let version = self.ver_status().current().await;
+        let vec_data = Self::ss_table_load_data(&version.block_cache, ss_table, |_| true).await?;
+        let new_gen = Gen::create();
+        let new_ss_table = self.create_output(&version, new_gen, vec_data, level, ss_table.get_sequence_range())?;
+        self.ver_status().insert_vec_ss_table(vec![new_ss_table]).await?;
+
+        let mut vec_attr_edit = Vec::new();
+        if let Some(window_start) = self.ver_status().get_window(gen).await {
+            vec_attr_edit.push(VersionEdit::Window(vec![new_gen], window_start));
+        }
+        if let Some(max_expiry) = self.ver_status().get_expiry(gen).await {
+            vec_attr_edit.push(VersionEdit::Expiry(vec![new_gen], max_expiry));
+        }
+        info!(
+            "[Compactor][Migrate][SSTable: {}][Format Version: {}] rewritten to {}",
+            gen, ss_table.get_format_version(), new_gen
+        );
+
+        Ok((new_gen, vec_attr_edit))
+    }
+
     /// 创建gen
     ///
     /// 需要保证获取到了MemTable的写锁以保证wal在switch时MemTable的数据和Wal不一致(多出几条)
@@ -93,28 +493,234 @@ impl Compactor {
     /// 持久化immutable_table为SSTable
     ///
     /// 请注意：vec_values必须是依照key值有序的
-    pub(crate) async fn minor_compaction(&self, gen: i64, values: Vec<KeyValue>) -> Result<()> {
+    ///
+    /// 指定了SSTable选项的前缀的数据被写入单独的SSTable，其余的数据仍以gen写入同一SSTable
+    pub(crate) async fn minor_compaction(
+        &self,
+        gen: i64,
+        values: Vec<KeyValue>,
+        sequence_range: SequenceRange
+    ) -> Result<()> {
         if !values.is_empty() {
+            let version = self.ver_status().current().await;
+            let mut vec_ss_table = Vec::new();
             // 从内存表中将数据持久化为ss_table
-            let ss_table = SSTable::create_for_mem_table(
-                self.config(),
-                gen,
-                self.sst_factory(),
-                values,
-                LEVEL_0
-            )?;
-
-            self.ver_status().insert_vec_ss_table(vec![ss_table]).await?;
+            for (i, vec_data) in split_with_options(values, version.prefix_options()).into_iter().enumerate() {
+                let gen = if i == 0 { gen } else { Gen::create() };
+                vec_ss_table.push(self.create_output(&version, gen, vec_data, LEVEL_0, sequence_range)?);
+            }
+            let vec_gen = SSTable::collect_gen(&vec_ss_table)?;
+            self.ver_status().insert_vec_ss_table(vec_ss_table.clone()).await?;
 
             // `Compactor::data_loading_with_level`中会检测是否达到压缩阈值，因此此处直接调用Major压缩
-            self.major_compaction(
-                LEVEL_0,
-                vec![VersionEdit::NewFile((vec![gen], 0), 0)]
-            ).await?;
+            let mut vec_ver_edit = self.new_file_edits(vec_gen, LEVEL_0, 0);
+            vec_ver_edit.push(VersionEdit::LastSequence(Sequence::latest()));
+
+            self.compaction_with_style(&vec_ss_table, vec_ver_edit).await?;
+        }
+        Ok(())
+    }
+
+    /// Level 0新增SSTable后，依照各SSTable所属前缀的压缩方式进行压缩
+    async fn compaction_with_style(&self, vec_ss_table: &[SSTable], mut vec_ver_edit: Vec<VersionEdit>) -> Result<()> {
+        let version = self.ver_status().current().await;
+        let now = self.config().clock.now_millis();
+
+        for ss_table in vec_ss_table {
+            if let CompactionStyle::TimeWindow { window, ttl } = self.compaction_style(&version, ss_table) {
+                let gen = ss_table.get_gen();
+                let window_start = now - now.rem_euclid(window_millis(window));
+
+                // 窗口内写入的数据在窗口结束ttl后全部过期
+                if let Some(ttl) = ttl {
+                    let max_expiry = window_start + window_millis(window) + ttl.as_millis() as i64;
+                    vec_ver_edit.push(VersionEdit::Expiry(vec![gen], max_expiry));
+                }
+                vec_ver_edit.push(VersionEdit::Window(vec![gen], window_start));
+            }
+        }
+        if self.compaction_styles(&version).any(|style| style != CompactionStyle::Leveled) {
+            self.time_window_compaction(now, vec_ver_edit).await?;
+            vec_ver_edit = Vec::new();
+        }
+        if self.compaction_styles(&version).any(|style| style == CompactionStyle::Leveled) {
+            self.major_compaction(LEVEL_0, vec_ver_edit).await?;
+        }
+        Ok(())
+    }
+
+    /// 时间窗口压缩
+    ///
+    /// Level 0中的SSTable由旧至新排列，因此同一窗口的SSTable总是相邻，窗口结束后将其合并为Level 1中的SSTable，
+    /// Level 1需保证SSTable之间范围不重叠且其中的数据旧于Level 0，因此由旧至新依次处理已结束的窗口，
+    /// 遇到与Level 1范围重叠的窗口(如Key未以时间戳为前缀)时停止，该窗口及之后的窗口保留在Level 0中
+    ///
+    /// 不同SSTable选项的数据互不重叠，因此各自以其窗口分组并独立处理，分层压缩的SSTable不参与
+    ///
+    /// 未记录窗口的SSTable(如切换压缩方式前写入的)视为最旧的窗口，过期的窗口由`Compactor::drop_expired_ss_tables`直接删除
+    async fn time_window_compaction(&self, now: i64, mut vec_ver_edit: Vec<VersionEdit>) -> Result<()> {
+        let version = self.ver_status().current().await;
+        let config = self.config();
+
+        let mut vec_class: Vec<(TableOptions, Duration, Vec<WindowGroup>)> = Vec::new();
+        for ss_table in version.get_ss_tables_for_level(LEVEL_0).await {
+            let table_options = version.table_options(&ss_table.get_scope().start);
+            let CompactionStyle::TimeWindow { window, .. } = table_options.compaction_style(config) else {
+                continue
+            };
+            let index = match vec_class.iter().position(|(class_options, ..)| *class_options == table_options) {
+                Some(index) => index,
+                None => {
+                    vec_class.push((table_options, window, Vec::new()));
+                    vec_class.len() - 1
+                }
+            };
+            let vec_window = &mut vec_class[index].2;
+            let window_start = self.ver_status().get_window(ss_table.get_gen()).await;
+            match vec_window.last_mut() {
+                Some((last_start, vec_ss_table)) if *last_start == window_start => vec_ss_table.push(ss_table),
+                _ => vec_window.push((window_start, vec![ss_table])),
+            }
+        }
+        let mut vec_ss_table_l1 = version.get_ss_tables_for_level(1).await;
+
+        for (_, window, vec_window) in vec_class {
+            let current_window = now - now.rem_euclid(window_millis(window));
+
+            for (window_start, vec_ss_table) in vec_window {
+                let del_gens = SSTable::collect_gen(&vec_ss_table)?;
+                let scope = Scope::fusion_from_vec_ss_table(&vec_ss_table)?;
+                if window_start.is_some_and(|window_start| window_start >= current_window)
+                    || vec_ss_table_l1.iter().any(|ss_table| ss_table.get_scope().meet(&scope))
+                {
+                    break
+                }
+                let start = Instant::now();
+                // 合并后的SSTable仅在所有数据均记录了过期时间时才可过期
+                let mut option_max_expiry = Some(i64::MIN);
+                for gen in del_gens.iter() {
+                    option_max_expiry = option_max_expiry.zip(self.ver_status().get_expiry(*gen).await)
+                        .map(|(max_expiry, expiry)| max_expiry.max(expiry));
+                }
+                let sequence_range = SequenceRange::fusion_from_vec_ss_table(&vec_ss_table);
+                let drop_tombstones = self.is_tombstone_droppable(&version, 1, &scope, sequence_range).await;
+                let vec_new_ss_table = self.merge_with_discard(
+                    &version,
+                    vec_ss_table,
+                    vec![],
+                    config.target_file_size(1),
+                    drop_tombstones
+                ).await?
+                    .into_iter()
+                    .map(|(gen, sharding)| self.create_output(&version, gen, sharding, 1, sequence_range))
+                    .try_collect::<_, Vec<_>, _>()?;
+                let vec_new_sst_gen = vec_new_ss_table.iter()
+                    .map(SSTable::get_gen)
+                    .collect_vec();
+
+                vec_ss_table_l1.extend(vec_new_ss_table.iter().cloned());
+                self.ver_status()
+                    .insert_vec_ss_table(vec_new_ss_table).await?;
+                vec_ver_edit.extend(self.new_file_edits(vec_new_sst_gen.clone(), 1, 0));
+                if let Some(max_expiry) = option_max_expiry {
+                    vec_ver_edit.push(VersionEdit::Expiry(vec_new_sst_gen.clone(), max_expiry));
+                }
+                if let Some(window_start) = window_start {
+                    vec_ver_edit.push(VersionEdit::Window(vec_new_sst_gen, window_start));
+                }
+                vec_ver_edit.push(VersionEdit::DeleteFile((del_gens, LEVEL_0)));
+                info!("[LsmStore][Time Window Compaction][Window: {:?}][Time: {:?}]", window_start, start.elapsed());
+            }
         }
+        if !vec_ver_edit.is_empty() {
+            self.ver_status()
+                .log_and_apply(vec_ver_edit).await?;
+        }
+        Ok(())
+    }
+
+    /// 直接删除所有数据均已过期的SSTable，无需读取与重写数据
+    pub(crate) async fn drop_expired_ss_tables(&self) -> Result<()> {
+        let version = self.ver_status().current().await;
+        let vec_expired = version
+            .get_expired_files(self.config().clock.now_millis()).await;
+        if vec_expired.is_empty() {
+            return Ok(());
+        }
+        info!("[Compactor][Drop Expired][SSTables: {:?}]", vec_expired);
+        // 存在Key空间的订阅方时，读取被删除的SSTable以发布其中的过期数据
+        if self.store_inner.keyspace_events.is_subscribed() {
+            let discard_filter = self.discard_filter(&version);
+            let expired_gens: HashSet<i64> = vec_expired.iter()
+                .flat_map(|(vec_gen, _)| vec_gen.iter().copied())
+                .collect();
+
+            for ss_table in version.get_all_ss_tables().await.into_iter().flatten() {
+                if expired_gens.contains(&ss_table.get_gen()) {
+                    for key_value in Self::ss_table_iter_data(&version.block_cache, &ss_table, |_| true)? {
+                        let _ = discard_filter.is_discarded(&key_value);
+                    }
+                }
+            }
+            self.publish_expired(discard_filter.take_expired_keys()).await?;
+        }
+        let vec_ver_edit = vec_expired.into_iter()
+            .map(VersionEdit::DeleteFile)
+            .collect_vec();
+        self.ver_status()
+            .log_and_apply(vec_ver_edit).await?;
+        // 删除的SSTable中的Key可能回退至旧版本或不再存在
+        if let Some(row_cache) = &self.store_inner.row_cache {
+            row_cache.invalidate_all();
+        }
+
         Ok(())
     }
 
+    /// 删除Key前缀，如被删除的列族
+    ///
+    /// 仅记录于Manifest中并删除只包含该前缀数据的SSTable，因此无需读取与重写数据即可立即完成，
+    /// 与其他前缀的数据共存于同一SSTable中的数据在此后的压缩中被丢弃，返回删除的SSTable数量
+    pub(crate) async fn drop_prefix(&self, prefix: Bytes) -> Result<usize> {
+        self.ver_status()
+            .log_and_apply(vec![VersionEdit::DropPrefix(prefix.to_vec())]).await?;
+        info!("[Compactor][Drop Prefix][Prefix: {:?}]", prefix);
+
+        self.drop_prefix_ss_tables().await
+    }
+
+    /// 记录Key前缀的TTL，如设置了TTL的列族，其过期的数据在此后的压缩中被丢弃
+    pub(crate) async fn set_prefix_ttl(&self, prefix: Bytes, ttl: Duration) -> Result<()> {
+        self.ver_status()
+            .log_and_apply(vec![VersionEdit::PrefixTtl(prefix.to_vec(), ttl.as_millis() as i64)]).await
+    }
+
+    /// 记录Key前缀的SSTable选项，如指定了压缩方式的列族，其数据在此后的Flush与压缩中被写入单独的SSTable
+    pub(crate) async fn set_prefix_options(&self, prefix: Bytes, table_options: TableOptions) -> Result<()> {
+        self.ver_status()
+            .log_and_apply(vec![VersionEdit::PrefixOptions(prefix.to_vec(), table_options)]).await
+    }
+
+    /// 直接删除所有数据均属于被删除前缀的SSTable，返回删除的SSTable数量
+    async fn drop_prefix_ss_tables(&self) -> Result<usize> {
+        let vec_dropped = self.ver_status().current().await
+            .get_dropped_prefix_files().await;
+        let dropped_len = vec_dropped.iter()
+            .map(|(vec_gen, _)| vec_gen.len())
+            .sum();
+        if dropped_len == 0 {
+            return Ok(0);
+        }
+        info!("[Compactor][Drop Prefix][SSTables: {:?}]", vec_dropped);
+        let vec_ver_edit = vec_dropped.into_iter()
+            .map(VersionEdit::DeleteFile)
+            .collect_vec();
+        self.ver_status()
+            .log_and_apply(vec_ver_edit).await?;
+
+        Ok(dropped_len)
+    }
+
     /// Major压缩，负责将不同Level之间的数据向下层压缩转移
     /// 目前Major压缩的大体步骤是
     /// 1. 获取当前Version，读取当前Level的指定数量SSTable，命名为vec_ss_table_l
@@ -136,22 +742,18 @@ impl Compactor {
         }
 
         while level < 7 {
-            if let Some((index, (del_gens_l, del_gens_ll), vec_sharding)) =
+            if let Some((index, (del_gens_l, del_gens_ll), vec_sharding, sequence_range)) =
                 self.data_loading_with_level(level).await?
             {
 
                 let start = Instant::now();
+                let version = self.ver_status().current().await;
                 // 并行创建SSTable
                 let ss_table_futures = vec_sharding.into_iter()
                     .map(|(gen, sharding)| {
+                        let version = &version;
                         async move {
-                            SSTable::create_for_mem_table(
-                                self.config(),
-                                gen,
-                                self.sst_factory(),
-                                sharding,
-                                level + 1
-                            )
+                            self.create_output(version, gen, sharding, level + 1, sequence_range)
                         }
                     });
                 let vec_new_ss_table: Vec<SSTable> = future::try_join_all(ss_table_futures).await?;
@@ -161,71 +763,247 @@ impl Compactor {
                     .collect_vec();
                 self.ver_status()
                     .insert_vec_ss_table(vec_new_ss_table).await?;
+                vec_ver_edit.extend(self.new_file_edits(vec_new_sst_gen, level + 1, index));
                 vec_ver_edit.append(&mut vec![
-                    VersionEdit::NewFile((vec_new_sst_gen, level + 1), index),
                     VersionEdit::DeleteFile((del_gens_l, level)),
-                    VersionEdit::DeleteFile((del_gens_ll, level))
+                    VersionEdit::DeleteFile((del_gens_ll, level + 1))
                 ]);
                 info!("[LsmStore][Major Compaction][recreate_sst][Level: {}][Time: {:?}]", level, start.elapsed());
                 level += 1;
             } else { break }
         }
+        if !vec_ver_edit.is_empty() {
+            self.ver_status()
+                .log_and_apply(vec_ver_edit).await?;
+        }
+        Ok(())
+    }
+
+    /// Level 0内部压缩
+    ///
+    /// 当Level 0累积了较多的小SSTable却尚未达到Major压缩阈值时，
+    /// 将其合并为较大的Level 0 SSTable以降低读放大，而不需要等待Level 0至Level 1的完整合并
+    ///
+    /// Level 0的SSTable以Gen区分新旧，而合并生成的SSTable的Gen总是最新的，
+    /// 因此仅选取Level 0中最新的连续的小SSTable进行合并，
+    /// 时间窗口压缩的SSTable与其余SSTable的数据互不重叠，因此不参与且不影响选取
+    async fn level_0_intra_compaction(&self) -> Result<()> {
+        let config = self.config();
+        let threshold = match config.level_0_intra_threshold {
+            Some(threshold) => threshold.max(2),
+            None => return Ok(()),
+        };
+        let version = self.ver_status().current().await;
+
+        if version.is_threshold_exceeded_major(config, self.major_threshold(), LEVEL_0) {
+            return Ok(());
+        }
+        let small_file_size = config.small_file_size(LEVEL_0);
+        let vec_ss_table = version.get_ss_tables_for_level(LEVEL_0).await
+            .into_iter()
+            .rev()
+            .filter(|ss_table| self.is_leveled(&version, ss_table))
+            .take_while(|ss_table| ss_table.get_size_of_disk() < small_file_size)
+            .collect_vec();
+
+        if vec_ss_table.len() < threshold {
+            return Ok(());
+        }
+        let start = Instant::now();
+        let del_gens = SSTable::collect_gen(&vec_ss_table)?;
+        let sequence_range = SequenceRange::fusion_from_vec_ss_table(&vec_ss_table);
+        let vec_sharding = self.merge_with_discard(
+            &version,
+            vec_ss_table,
+            vec![],
+            config.target_file_size(LEVEL_0),
+            false
+        ).await?;
+        let vec_new_ss_table = vec_sharding.into_iter()
+            .map(|(gen, sharding)| self.create_output(&version, gen, sharding, LEVEL_0, sequence_range))
+            .try_collect::<_, Vec<_>, _>()?;
+        let vec_new_sst_gen = vec_new_ss_table.iter()
+            .map(SSTable::get_gen)
+            .collect_vec();
+
+        self.ver_status()
+            .insert_vec_ss_table(vec_new_ss_table).await?;
+        let mut vec_ver_edit = self.new_file_edits(vec_new_sst_gen, LEVEL_0, 0);
+        vec_ver_edit.push(VersionEdit::DeleteFile((del_gens, LEVEL_0)));
+
         self.ver_status()
             .log_and_apply(vec_ver_edit).await?;
+        info!("[LsmStore][Level 0 Intra Compaction][Time: {:?}]", start.elapsed());
+
+        Ok(())
+    }
+
+    /// Level 1-6小文件合并
+    ///
+    /// 频繁的小批量Flush会使Level 1-6中堆积大量远小于目标文件大小的SSTable，导致打开的文件与索引的内存占用过多
+    /// 因此在Major压缩之后，对尚未达到Major压缩阈值的Level，将Key范围相邻的连续小SSTable合并为接近目标文件大小的SSTable
+    ///
+    /// 由于被合并的SSTable在该Level中是相邻的，合并后的SSTable不会与该Level中其他SSTable的范围重叠，
+    /// 时间窗口压缩的SSTable不参与合并，以免其窗口与过期时间被丢失
+    async fn small_file_merge(&self) -> Result<()> {
+        let config = self.config();
+        let threshold = match config.small_file_merge_threshold {
+            Some(threshold) => threshold.max(2),
+            None => return Ok(()),
+        };
+        let version = self.ver_status().current().await;
+        let mut vec_ver_edit = Vec::new();
+
+        for level in 1..7 {
+            if version.is_threshold_exceeded_major(config, self.major_threshold(), level) {
+                continue
+            }
+            let small_file_size = config.small_file_size(level);
+            let vec_small_runs = version.get_sorted_ss_tables(level).await
+                .into_iter()
+                .group_by(|ss_table| {
+                    ss_table.get_size_of_disk() < small_file_size && self.is_leveled(&version, ss_table)
+                })
+                .into_iter()
+                .filter(|(is_small, _)| *is_small)
+                .map(|(_, group)| group.collect_vec())
+                .filter(|vec_ss_table| vec_ss_table.len() >= threshold)
+                .collect_vec();
+
+            for vec_ss_table in vec_small_runs {
+                let start = Instant::now();
+                let index = SSTable::find_index_with_level(
+                    vec_ss_table.first().map(SSTable::get_gen),
+                    &version,
+                    level
+                );
+                let del_gens = SSTable::collect_gen(&vec_ss_table)?;
+                let sequence_range = SequenceRange::fusion_from_vec_ss_table(&vec_ss_table);
+                let vec_sharding = self.merge_with_discard(
+                    &version,
+                    vec_ss_table,
+                    vec![],
+                    config.target_file_size(level),
+                    false
+                ).await?;
+                let vec_new_ss_table = vec_sharding.into_iter()
+                    .map(|(gen, sharding)| self.create_output(&version, gen, sharding, level, sequence_range))
+                    .try_collect::<_, Vec<_>, _>()?;
+                let vec_new_sst_gen = vec_new_ss_table.iter()
+                    .map(SSTable::get_gen)
+                    .collect_vec();
+
+                self.ver_status()
+                    .insert_vec_ss_table(vec_new_ss_table).await?;
+                vec_ver_edit.extend(self.new_file_edits(vec_new_sst_gen, level, index));
+                vec_ver_edit.push(VersionEdit::DeleteFile((del_gens, level)));
+                info!("[LsmStore][Small File Merge][Level: {}][Time: {:?}]", level, start.elapsed());
+            }
+        }
+
+        if !vec_ver_edit.is_empty() {
+            self.ver_status()
+                .log_and_apply(vec_ver_edit).await?;
+        }
+
         Ok(())
     }
 
     /// 通过Level进行归并数据加载
-    async fn data_loading_with_level(&self, level: usize) -> Result<Option<(usize, DelGenVec, MergeShardingVec)>> {
+    ///
+    /// 同时返回被合并数据的Sequence范围
+    ///
+    /// 该Level中仅选取分层压缩的SSTable，时间窗口压缩的SSTable仅在位于下一Level且与被合并的范围重叠时一同被合并
+    async fn data_loading_with_level(
+        &self,
+        level: usize
+    ) -> Result<Option<(usize, DelGenVec, MergeShardingVec, SequenceRange)>> {
         let version = self.ver_status().current().await;
         let config = self.config();
         let major_select_file_size = config.major_select_file_size;
 
         // 如果该Level的SSTables数量尚未越出阈值则提取返回空
-        if level > 5 || !version.is_threshold_exceeded_major(config, level) { return Ok(None); }
+        if level > 5 || !version.is_threshold_exceeded_major(config, self.major_threshold(), level) { return Ok(None); }
 
         // 此处vec_ss_table_l指此level的Vec<SSTable>, vec_ss_table_ll则是下一级的Vec<SSTable>
         // 类似罗马数字
-        if let Some(mut vec_ss_table_l) = version
-            .get_first_vec_ss_table_with_size(level, major_select_file_size).await
-        {
+        let mut vec_ss_table_l = version.get_ss_tables_for_level(level).await
+            .into_iter()
+            .filter(|ss_table| self.is_leveled(&version, ss_table))
+            .take(major_select_file_size)
+            .collect_vec();
+        if !vec_ss_table_l.is_empty() {
             let start = Instant::now();
-            let scope_l = Scope::fusion_from_vec_ss_table(&vec_ss_table_l)?;
-            // 获取下一级中有重复键值范围的SSTable
-            let ss_tables_ll = version.get_meet_scope_ss_tables(level + 1, &scope_l).await;
+
+            // 若为Level 0则与获取同级下是否存在有键值范围冲突数据并插入至vec_ss_table_l中
+            if level == LEVEL_0 {
+                let scope_l = Scope::fusion_from_vec_ss_table(&vec_ss_table_l)?;
+                vec_ss_table_l.extend(
+                    version.get_meet_scope_ss_tables(level, &scope_l).await
+                        .into_iter()
+                        .filter(|ss_table| self.is_leveled(&version, ss_table))
+                )
+            }
+            // 此处vec_ss_table_l与vec_ss_table_ll之间相互扩展直至稳定
+            // 以保证压缩后Level l + 1中新旧SSTable的范围不会重叠，且被删除的SSTable的数据均参与了合并
+            let mut vec_ss_table_l = vec_ss_table_l.into_iter()
+                .unique_by(SSTable::get_gen)
+                .collect_vec();
+            let ss_tables_ll = loop {
+                let scope_l = Scope::fusion_from_vec_ss_table(&vec_ss_table_l)?;
+                // 获取下一级中有重复键值范围的SSTable
+                let ss_tables_ll = version.get_meet_scope_ss_tables(level + 1, &scope_l).await;
+                let scope_all = Scope::fusion_from_vec_ss_table(
+                    &vec_ss_table_l.iter()
+                        .chain(ss_tables_ll.iter())
+                        .cloned()
+                        .collect_vec()
+                )?;
+                let len_l = vec_ss_table_l.len();
+
+                vec_ss_table_l = vec_ss_table_l.into_iter()
+                    .chain(
+                        version.get_meet_scope_ss_tables(level, &scope_all).await
+                            .into_iter()
+                            .filter(|ss_table| self.is_leveled(&version, ss_table))
+                    )
+                    .unique_by(SSTable::get_gen)
+                    .collect_vec();
+
+                if vec_ss_table_l.len() == len_l {
+                    break ss_tables_ll;
+                }
+            };
             let index = SSTable::find_index_with_level(
                 ss_tables_ll.first().map(SSTable::get_gen),
                 &version,
                 level + 1
             );
 
-            // 若为Level 0则与获取同级下是否存在有键值范围冲突数据并插入至del_gen_l中
-            if level == LEVEL_0 {
-                vec_ss_table_l.append(
-                    &mut version.get_meet_scope_ss_tables(level, &scope_l).await
-                )
-            }
-
             // 收集需要清除的SSTable
             let del_gen_l = SSTable::collect_gen(&vec_ss_table_l)?;
             let del_gen_ll = SSTable::collect_gen(&ss_tables_ll)?;
 
-            // 此处没有chain vec_ss_table_l是因为在vec_ss_table_ll是由vec_ss_table_l检测冲突而获取到的
-            // 因此使用vec_ss_table_ll向上检测冲突时获取的集合应当含有vec_ss_table_l的元素
-            let ss_tables_l_final = match Scope::fusion_from_vec_ss_table(&ss_tables_ll) {
-                Ok(scope_ll) => version.get_meet_scope_ss_tables(level, &scope_ll).await,
-                Err(_) => vec_ss_table_l
-            }.into_iter()
-                .unique_by(SSTable::get_gen)
+            let vec_ss_table_all = vec_ss_table_l.iter()
+                .chain(ss_tables_ll.iter())
+                .cloned()
                 .collect_vec();
+            let sequence_range = SequenceRange::fusion_from_vec_ss_table(&vec_ss_table_all);
+            let drop_tombstones = self.is_tombstone_droppable(
+                &version,
+                level + 1,
+                &Scope::fusion_from_vec_ss_table(&vec_ss_table_all)?,
+                sequence_range
+            ).await;
 
             // 数据合并并切片
             let vec_merge_sharding =
-                Self::data_merge_and_sharding(
-                    ss_tables_l_final,
+                self.merge_with_discard(
+                    &version,
+                    vec_ss_table_l,
                     ss_tables_ll,
-                    &version.block_cache,
-                    config.sst_file_size
+                    config.target_file_size(level + 1),
+                    drop_tombstones
                 ).await?;
 
             info!(
@@ -233,22 +1011,108 @@ impl Compactor {
                 start.elapsed()
             );
 
-            Ok(Some((index, (del_gen_l, del_gen_ll), vec_merge_sharding)))
+            Ok(Some((index, (del_gen_l, del_gen_ll), vec_merge_sharding, sequence_range)))
         } else {
             Ok(None)
         }
     }
 
+    /// 判断合并至target_level的数据是否可以丢弃删除标记
+    ///
+    /// 被覆盖的旧版本在合并时总是被丢弃，而删除标记需遮蔽更低Level中的旧数据，
+    /// 因此仅当更低的Level中不存在与scope重叠的SSTable，
+    /// 且被合并数据的Sequence均早于最旧的存活快照时才可丢弃
+    async fn is_tombstone_droppable(
+        &self,
+        version: &Version,
+        target_level: usize,
+        scope: &Scope,
+        sequence_range: SequenceRange
+    ) -> bool {
+        let is_before_snapshots = self.mem_table().oldest_snapshot()
+            .is_none_or(|seq_id| sequence_range.is_before(seq_id));
+        if !is_before_snapshots {
+            return false;
+        }
+        for level in target_level + 1..7 {
+            if !version.get_meet_scope_ss_tables(level, scope).await.is_empty() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// 以version的丢弃规则合并数据并切片，存在Key空间的订阅方时发布其中因过期被丢弃的Key
+    async fn merge_with_discard(
+        &self,
+        version: &Version,
+        ss_tables_l: Vec<SSTable>,
+        ss_tables_ll: Vec<SSTable>,
+        sst_file_size: usize,
+        drop_tombstones: bool
+    ) -> Result<MergeShardingVec> {
+        let discard_filter = self.discard_filter(version);
+        let vec_sharding = Self::data_merge_and_sharding(
+            ss_tables_l,
+            ss_tables_ll,
+            &version.block_cache,
+            &discard_filter,
+            version.prefix_options(),
+            sst_file_size,
+            drop_tombstones
+        ).await?;
+        self.publish_expired(discard_filter.take_expired_keys()).await?;
+
+        Ok(vec_sharding)
+    }
+
+    fn discard_filter<'a>(&self, version: &'a Version) -> DiscardFilter<'a> {
+        version.discard_filter(self.config().clock.now_millis())
+            .collect_expired(self.store_inner.keyspace_events.is_subscribed())
+    }
+
+    /// 发布因过期被丢弃的Key，需在丢弃其数据的Version应用前调用
+    ///
+    /// 仅当Key的最新数据已过期时发布，被丢弃的可能是已被新数据覆盖或删除的旧版本
+    async fn publish_expired(&self, expired_keys: Vec<Bytes>) -> Result<()> {
+        if expired_keys.is_empty() {
+            return Ok(());
+        }
+        let now = self.config().clock.now_millis();
+        let version = self.ver_status().current().await;
+        let mut vec_expired = Vec::with_capacity(expired_keys.len());
+
+        for key in expired_keys.into_iter().unique() {
+            let value = match self.mem_table().find(&key) {
+                Some(value) => value,
+                None => version.find_data_for_ss_tables(&key).await?,
+            };
+            if value.is_some_and(|value| column_family::is_expired(&value, now)) {
+                vec_expired.push(key);
+            }
+        }
+        self.store_inner.keyspace_events.publish_expired(&vec_expired);
+
+        Ok(())
+    }
+
     /// 以SSTables的数据归并再排序后切片，获取以KeyValue的Key值由小到大的切片排序
     /// 1. 并行获取Level l(当前等级)的待合并SSTables_l的全量数据
     /// 2. 基于SSTables_l获取唯一KeySet用于迭代过滤
     /// 3. 并行对Level ll的SSTables_ll通过KeySet进行迭代同时过滤数据
     /// 4. 组合SSTables_l和SSTables_ll的数据合并并进行唯一，排序处理
+    ///
+    /// drop_tombstones为true时，合并后丢弃删除标记，discard_filter所匹配的数据则总是被丢弃，
+    /// 切片时不同SSTable选项的数据被切分至不同的分片
     async fn data_merge_and_sharding(
         ss_tables_l: Vec<SSTable>,
         ss_tables_ll: Vec<SSTable>,
         block_cache: &BlockCache,
-        sst_file_size: usize
+        discard_filter: &DiscardFilter<'_>,
+        prefix_options: &[(Bytes, TableOptions)],
+        sst_file_size: usize,
+        drop_tombstones: bool
     ) -> Result<MergeShardingVec> {
         // SSTables的Gen会基于时间有序生成,所有以此作为SSTables的排序依据
         let map_futures_l = ss_tables_l.iter()
@@ -278,13 +1142,29 @@ impl Compactor {
             .flatten()
             .rev()
             .unique_by(|(key, _)| key.clone())
+            .filter(|(_, value)| !drop_tombstones || value.is_some())
+            .filter(|key_value| !discard_filter.is_discarded(key_value))
             .sorted_unstable_by_key(|(key, _)| key.clone())
             .collect();
-        Ok(data_sharding(vec_cmd_data, sst_file_size))
+        Ok(data_sharding_with_options(vec_cmd_data, sst_file_size, prefix_options))
     }
 
     async fn ss_table_load_data<F>(block_cache: &BlockCache, ss_table: &SSTable, fn_is_filter: F) -> Result<Vec<KeyValue>>
         where F: Fn(&Bytes) -> bool
+    {
+        // 压缩时整表顺序读取且读取后即被合并删除，
+        // 因此提示系统增大预读，并在读取后释放其页缓存，避免挤占热数据
+        // 提示仅影响性能，失败时忽略
+        let _ignore = ss_table.advise(FileAdvice::Sequential);
+        let result = Self::ss_table_iter_data(block_cache, ss_table, fn_is_filter);
+        let _ignore1 = ss_table.advise(FileAdvice::DontNeed);
+        let _ignore2 = ss_table.advise(FileAdvice::Normal);
+
+        result
+    }
+
+    fn ss_table_iter_data<F>(block_cache: &BlockCache, ss_table: &SSTable, fn_is_filter: F) -> Result<Vec<KeyValue>>
+        where F: Fn(&Bytes) -> bool
     {
         let mut iter = SSTableIter::new(ss_table, block_cache)?;
         let mut vec_cmd = Vec::with_capacity(iter.len());
@@ -313,18 +1193,29 @@ impl Compactor {
     }
 }
 
+/// 时间窗口的长度(毫秒)，至少为1
+fn window_millis(window: Duration) -> i64 {
+    (window.as_millis() as i64).max(1)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::hash_map::RandomState;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::Duration;
     use bytes::Bytes;
     use tempfile::TempDir;
     use crate::kernel::io::{FileExtension, IoFactory};
-    use crate::kernel::lsm::compactor::Compactor;
-    use crate::kernel::lsm::lsm_kv::Config;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::block::BlockCache;
+    use crate::kernel::lsm::compactor::{Compactor, LEVEL_0};
+    use crate::kernel::lsm::lsm_kv::{CompactionStyle, Config, LsmStore};
     use crate::kernel::lsm::ss_table::SSTable;
-    use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
+    use crate::kernel::lsm::version::{DEFAULT_SS_TABLE_PATH, DiscardFilter};
+    use crate::kernel::lsm::{Footer, FORMAT_VERSION, TABLE_FOOTER_SIZE};
+    use crate::kernel::utils::clock::VirtualClock;
     use crate::kernel::Result;
-    use crate::kernel::utils::lru_cache::ShardingLruCache;
+    use crate::KernelError;
 
     #[test]
     fn test_data_merge() -> Result<()> {
@@ -335,11 +1226,7 @@ mod tests {
             config.dir_path.join(DEFAULT_SS_TABLE_PATH),
             FileExtension::SSTable
         )?;
-        let cache = ShardingLruCache::new(
-            config.block_cache_size,
-            16,
-            RandomState::default()
-        )?;
+        let cache = BlockCache::new(&config)?;
         let ss_table_1 = SSTable::create_for_mem_table(
             &config,
             1,
@@ -388,7 +1275,10 @@ mod tests {
                 vec![ss_table_1, ss_table_2],
                 vec![ss_table_3, ss_table_4],
                 &cache,
-                config.sst_file_size
+                &DiscardFilter::default(),
+                &[],
+                config.sst_file_size,
+                false
             ).await
         })?[0];
 
@@ -401,4 +1291,305 @@ mod tests {
         ]);
         Ok(())
     }
+
+    #[test]
+    fn test_drop_tombstones() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.into_path())
+                .major_threshold_with_sst_size(1);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+
+            kv_store.remove(b"k1").await?;
+            kv_store.flush().await?;
+            // 新的SSTable在下一次压缩时才会被合并至Level 1
+            kv_store.set(b"k3", Bytes::from_static(b"v3")).await?;
+            kv_store.flush().await?;
+
+            let version = kv_store.current_version().await;
+            let len = version.get_ss_tables_for_level(1).await
+                .iter()
+                .map(SSTable::len)
+                .sum::<usize>();
+            // Level 1之下不存在数据，且不存在快照，因此k1的删除标记被丢弃
+            assert_eq!(len, 1);
+            assert_eq!(kv_store.get(b"k1").await?, None);
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_quarantine() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .rebuild_quarantined(true);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            kv_store.set(b"k3", Bytes::from_static(b"v3")).await?;
+            kv_store.flush().await?;
+            // 新的SSTable在下一次压缩时才会被合并至Level 1
+            kv_store.set(b"k4", Bytes::from_static(b"v4")).await?;
+            kv_store.flush().await?;
+
+            let ss_table = kv_store.current_version().await
+                .get_ss_tables_for_level(1).await
+                .into_iter()
+                .find(|ss_table| ss_table.get_scope().meet_with_key(b"k1"))
+                .expect("k1 should be compacted into level 1");
+            // 数据均可读取时重建的SSTable保留所有数据
+            let report = kv_store.quarantine(ss_table.get_gen()).await?;
+            assert_eq!((report.level, report.lost_blocks), (1, 0));
+            assert_eq!(report.readable_len, report.len);
+            assert!(report.path.exists());
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            // 模拟位翻转: 破坏重建后SSTable的首个数据Block
+            let rebuilt_gen = report.rebuilt_gen.expect("quarantined SSTable should be rebuilt");
+            let path = FileExtension::SSTable
+                .path_with_gen(&temp_dir.path().join(DEFAULT_SS_TABLE_PATH), rebuilt_gen);
+            let mut bytes = fs::read(&path)?;
+            bytes[8] = !bytes[8];
+            fs::write(&path, bytes)?;
+
+            let report = kv_store.quarantine(rebuilt_gen).await?;
+            assert_eq!((report.readable_len, report.lost_blocks), (0, 1));
+            assert_eq!(report.rebuilt_gen, None);
+            assert_eq!(kv_store.get(b"k1").await?, None);
+            assert_eq!(kv_store.get(b"k4").await?, Some(Bytes::from_static(b"v4")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_level_0_intra_compaction() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.into_path())
+                .major_threshold_with_sst_size(10)
+                .level_0_intra_threshold(3);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            for i in 0..3u8 {
+                kv_store.set(&[i], Bytes::from(vec![i])).await?;
+                // 覆盖上一轮的数据，合并后需保留最新的值
+                kv_store.set(b"key", Bytes::from(vec![i])).await?;
+                kv_store.flush().await?;
+            }
+            let version = kv_store.current_version().await;
+
+            assert_eq!(version.level_sst_count()[LEVEL_0], 1);
+            for i in 0..3u8 {
+                assert_eq!(kv_store.get(&[i]).await?, Some(Bytes::from(vec![i])));
+            }
+            assert_eq!(kv_store.get(b"key").await?, Some(Bytes::from(vec![2])));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_small_file_merge() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.into_path())
+                .major_threshold_with_sst_size(1)
+                .small_file_merge_threshold(3);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            // 每次Flush的Key范围互不重叠，使Level 1中堆积相邻的小SSTable
+            for i in 0..5u8 {
+                kv_store.set(&[i], Bytes::from(vec![i])).await?;
+                kv_store.flush().await?;
+            }
+            let version = kv_store.current_version().await;
+
+            // 前三个小SSTable被合并为一个，第四个仍独立存在
+            assert_eq!(version.level_sst_count()[1], 2);
+            for i in 0..5u8 {
+                assert_eq!(kv_store.get(&[i]).await?, Some(Bytes::from(vec![i])));
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_verify_compaction_output() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .verify_compaction_output(true);
+            let sst_factory = IoFactory::new(
+                config.dir_path.join(DEFAULT_SS_TABLE_PATH),
+                FileExtension::SSTable
+            )?;
+            let ss_table = SSTable::create_for_mem_table(
+                &config,
+                1,
+                &sst_factory,
+                vec![
+                    (Bytes::from_static(b"1"), Some(Bytes::from_static(b"1"))),
+                    (Bytes::from_static(b"2"), None)
+                ],
+                0
+            )?;
+            ss_table.verify_output(2)?;
+            assert!(matches!(ss_table.verify_output(3), Err(KernelError::Corrupted { .. })));
+
+            // 写入后损坏的数据Block无法通过校验
+            let mut bytes = fs::read(ss_table.get_path())?;
+            bytes[0] ^= 0xFF;
+            fs::write(ss_table.get_path(), bytes)?;
+            assert!(ss_table.verify_output(2).is_err());
+            fs::remove_file(ss_table.get_path())?;
+
+            let kv_store = LsmStore::open_with_config(config).await?;
+            for i in 0..3u8 {
+                kv_store.set(&[i], Bytes::from(vec![i])).await?;
+                kv_store.flush().await?;
+            }
+            // 经过校验的压缩输出正常写入Manifest
+            assert_eq!(kv_store.current_version().await.level_sst_count()[1], 2);
+            for i in 0..3u8 {
+                assert_eq!(kv_store.get(&[i]).await?, Some(Bytes::from(vec![i])));
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_time_window_compaction() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let config = Config::new(temp_dir.into_path())
+                .compaction_style(CompactionStyle::TimeWindow {
+                    window: Duration::from_millis(200),
+                    ttl: Some(Duration::from_millis(1000)),
+                })
+                .clock(Arc::new(clock.clone()));
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"t1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            clock.advance(Duration::from_millis(250));
+            // 上一窗口已结束，其SSTable被合并至Level 1
+            kv_store.set(b"t2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+
+            let version = kv_store.current_version().await;
+            assert_eq!(version.level_sst_count()[LEVEL_0], 1);
+            assert_eq!(version.level_sst_count()[1], 1);
+            assert_eq!(kv_store.get(b"t1").await?, Some(Bytes::from_static(b"v1")));
+
+            // 超出ttl的窗口整个被删除
+            clock.advance(Duration::from_millis(1300));
+            kv_store.set(b"t3", Bytes::from_static(b"v3")).await?;
+            kv_store.flush().await?;
+
+            let version = kv_store.current_version().await;
+            assert_eq!(version.level_sst_count()[LEVEL_0], 1);
+            assert_eq!(version.level_sst_count()[1], 0);
+            assert_eq!(kv_store.get(b"t1").await?, None);
+            assert_eq!(kv_store.get(b"t2").await?, None);
+            assert_eq!(kv_store.get(b"t3").await?, Some(Bytes::from_static(b"v3")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_drop_expired_ss_tables() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let config = Config::new(temp_dir.path())
+                .compaction_style(CompactionStyle::TimeWindow {
+                    window: Duration::from_millis(100),
+                    ttl: Some(Duration::from_millis(200)),
+                })
+                .clock(Arc::new(clock.clone()));
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+
+            kv_store.set(b"t1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            drop(kv_store);
+
+            // 过期时间随Version持久化，重启后仍然生效
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert_eq!(kv_store.get(b"t1").await?, Some(Bytes::from_static(b"v1")));
+            clock.advance(Duration::from_millis(400));
+            // 无新数据写入时同样删除过期的SSTable
+            kv_store.flush().await?;
+
+            assert_eq!(kv_store.current_version().await.level_sst_count().iter().sum::<usize>(), 0);
+            assert_eq!(kv_store.get(b"t1").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_migrate() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path());
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            drop(kv_store);
+
+            // 去除MetaBlock末尾的压缩方式，模拟格式版本3的SSTable
+            for entry in fs::read_dir(temp_dir.path().join(DEFAULT_SS_TABLE_PATH))? {
+                let path = entry?.path();
+                let mut bytes = fs::read(&path)?;
+                let mut footer: Footer = bincode::deserialize(&bytes[bytes.len() - TABLE_FOOTER_SIZE..])?;
+                bytes.truncate((footer.meta_offset + footer.meta_len) as usize - 4);
+                footer.meta_len -= 4;
+                footer.size_of_disk -= 4;
+                bytes.append(&mut bincode::serialize(&footer)?);
+                fs::write(&path, bytes)?;
+            }
+
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert!(matches!(
+                kv_store.migrate(FORMAT_VERSION + 1).await,
+                Err(KernelError::UnsupportedFormatVersion(_))
+            ));
+            assert_eq!(kv_store.migrate(FORMAT_VERSION - 1).await?, 0);
+            assert_eq!(kv_store.migrate(FORMAT_VERSION).await?, 2);
+            assert_eq!(kv_store.migrate(FORMAT_VERSION).await?, 0);
+
+            assert_eq!(kv_store.current_version().await.level_sst_count()[LEVEL_0], 2);
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
+
+            Ok(())
+        })
+    }
 }
@@ -0,0 +1,98 @@
+use serde_json::{Map, Value};
+use crate::kernel::Result;
+use crate::KernelError;
+
+/// 以JSON Pointer(RFC 6901)将value写入文档中对应的位置
+///
+/// pointer为空时替换整个文档，路径上不存在的字段会以对象的形式创建(下一级为`-`时创建为数组)，
+/// 数组的下标为其当前长度或`-`时追加元素
+pub(crate) fn pointer_set(doc: &mut Value, pointer: &str, value: Value) -> Result<()> {
+    if pointer.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let tokens = pointer.strip_prefix('/')
+        .ok_or_else(|| KernelError::InvalidJsonPointer(pointer.to_owned()))?
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"));
+    let mut target = doc;
+
+    for token in tokens {
+        if target.is_null() {
+            *target = if token == "-" { Value::Array(Vec::new()) } else { Value::Object(Map::new()) };
+        }
+        target = match target {
+            Value::Object(map) => map.entry(token).or_insert(Value::Null),
+            Value::Array(vec) => {
+                let index = if token == "-" { Some(vec.len()) } else { parse_index(&token) };
+
+                if index == Some(vec.len()) {
+                    vec.push(Value::Null);
+                }
+                index.and_then(|index| vec.get_mut(index))
+                    .ok_or_else(|| KernelError::InvalidJsonPointer(pointer.to_owned()))?
+            }
+            _ => return Err(KernelError::InvalidJsonPointer(pointer.to_owned())),
+        };
+    }
+    *target = value;
+
+    Ok(())
+}
+
+/// 与serde_json读取时的规则一致，不允许前导0与正号
+fn parse_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() != 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::doc::pointer_set;
+    use crate::kernel::lsm::lsm_kv::LsmStore;
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_pointer_set() -> Result<()> {
+        let mut doc = json!(null);
+
+        pointer_set(&mut doc, "/user/name", json!("kip"))?;
+        pointer_set(&mut doc, "/user/tags", json!(["a"]))?;
+        pointer_set(&mut doc, "/user/tags/-", json!("b"))?;
+        pointer_set(&mut doc, "/user/tags/0", json!("c"))?;
+        pointer_set(&mut doc, "/a~1b", json!(1))?;
+        assert_eq!(doc, json!({ "user": { "name": "kip", "tags": ["c", "b"] }, "a/b": 1 }));
+
+        assert!(pointer_set(&mut doc, "/user/tags/3", json!(0)).is_err());
+        assert!(pointer_set(&mut doc, "/user/name/first", json!(0)).is_err());
+        assert!(pointer_set(&mut doc, "user", json!(0)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_set_and_get() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+
+            kv_store.json_set(b"doc", "/count", json!(1)).await?;
+            kv_store.json_set(b"doc", "/items/-", json!("x")).await?;
+            assert_eq!(kv_store.json_get(b"doc", "").await?, Some(json!({ "count": 1, "items": ["x"] })));
+            assert_eq!(kv_store.json_get(b"doc", "/items/0").await?, Some(json!("x")));
+            assert_eq!(kv_store.json_get(b"doc", "/missing").await?, None);
+            assert_eq!(kv_store.json_get(b"none", "").await?, None);
+
+            kv_store.set(b"raw", bytes::Bytes::from_static(b"not json")).await?;
+            assert!(kv_store.json_set(b"raw", "/a", json!(1)).await.is_err());
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+use bytes::Bytes;
+use std::time::Duration;
+
+/// 启动恢复过程中的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RecoveryEvent {
+    /// Manifest重放完成，其中记录的SSTable均已打开
+    TablesOpened { tables: usize },
+    /// WAL的一个分段重放完成
+    ///
+    /// segment为该分段的序号(从0开始)，segments为该Gen的分段总数，bytes为该分段的大小
+    WalSegmentReplayed { gen: i64, segment: usize, segments: usize, bytes: u64 },
+    /// 恢复完成，此后写入不再等待
+    Completed { entries: usize, elapsed: Duration },
+    /// 后台重放WAL失败，此后写入均返回错误
+    Failed { reason: String },
+}
+
+/// 后台校验过程中的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScrubEvent {
+    /// SSTable中存在无法通过校验的Block
+    Corrupted { gen: i64, level: usize, reason: String },
+    /// 完成一轮对所有SSTable的校验
+    PassCompleted { tables: usize, bytes: u64, elapsed: Duration },
+}
+
+/// 损坏的SSTable被隔离后的报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct QuarantineReport {
+    pub gen: i64,
+    pub level: usize,
+    /// 隔离目录中该SSTable的副本
+    pub path: PathBuf,
+    /// 该SSTable中的数据条数
+    pub len: usize,
+    /// 可通过校验的数据条数
+    pub readable_len: usize,
+    /// 无法通过校验的数据Block数量
+    pub lost_blocks: usize,
+    /// 重建时由`ReadFallback`补全的数据条数
+    pub repaired_len: usize,
+    /// 以可读取的数据重建的SSTable的Gen，未重建时为None
+    ///
+    /// 未重建时该SSTable中Key的旧版本数据(若存在于更深的Level中)将重新可见
+    pub rebuilt_gen: Option<i64>,
+}
+
+/// 本地读取因数据损坏失败，转由`ReadFallback`读取的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReadFallbackEvent {
+    pub key: Bytes,
+    /// 损坏的SSTable的Gen，无法定位时为None
+    pub gen: Option<i64>,
+    pub reason: String,
+    /// 是否由`ReadFallback`成功读取
+    pub recovered: bool,
+}
+
+/// 内核事件监听器
+///
+/// 回调在内核的执行路径中同步调用，应避免耗时操作
+pub trait EventListener: Debug + Send + Sync {
+    #[inline]
+    fn on_recovery(&self, _event: &RecoveryEvent) {}
+
+    #[inline]
+    fn on_scrub(&self, _event: &ScrubEvent) {}
+
+    #[inline]
+    fn on_quarantine(&self, _report: &QuarantineReport) {}
+
+    #[inline]
+    fn on_read_fallback(&self, _event: &ReadFallbackEvent) {}
+}
@@ -0,0 +1,240 @@
+use std::fmt::Debug;
+use async_trait::async_trait;
+use bytes::Bytes;
+use crate::kernel::Result;
+use crate::KernelError;
+
+/// 本地数据损坏时的读取来源
+///
+/// 读取时本地的SSTable无法通过校验，则以Key从此处(如副本节点上的`RemoteStore`)读取，而非直接返回错误
+///
+/// 开启`Config::quarantine_corrupted`时，损坏的SSTable于下一次压缩时在后台被隔离，
+/// 若同时开启`Config::rebuild_quarantined`，重建时会通过`scan`读取无法通过校验的数据Block所在的Key范围，
+/// 与本地可读取的数据以及此前`get`所取回的数据一同写入重建的SSTable，以此修复本地的数据
+///
+/// 重建的SSTable置于原SSTable的位置(Level 0中位于原本更新的SSTable之前)，因此不会覆盖其后的写入
+#[async_trait]
+pub trait ReadFallback: Debug + Send + Sync {
+    async fn get(&self, key: &[u8]) -> Result<Option<Bytes>>;
+
+    /// 以Key升序读取Key位于(start, end]内的所有数据
+    ///
+    /// 默认不支持，此时重建的SSTable中仅包含本地可读取的数据
+    #[inline]
+    async fn scan(&self, _start: &[u8], _end: &[u8]) -> Result<Vec<(Bytes, Bytes)>> {
+        Err(KernelError::NotSupport("ReadFallback::scan"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+    use crate::kernel::io::FileExtension;
+    use crate::kernel::lsm::event::{EventListener, QuarantineReport, ReadFallbackEvent};
+    use crate::kernel::lsm::fallback::ReadFallback;
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
+    use crate::kernel::{KVStore, Result};
+
+    const REPLICA_DATA: [(&[u8], &[u8]); 4] = [(b"k1", b"v1"), (b"k2", b"v2"), (b"k3", b"v3"), (b"k4", b"v4")];
+
+    #[derive(Debug)]
+    struct Replica;
+
+    #[async_trait]
+    impl ReadFallback for Replica {
+        async fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+            Ok(REPLICA_DATA.iter()
+                .find(|(replica_key, _)| *replica_key == key)
+                .map(|(_, value)| Bytes::from_static(value)))
+        }
+
+        async fn scan(&self, start: &[u8], end: &[u8]) -> Result<Vec<(Bytes, Bytes)>> {
+            Ok(REPLICA_DATA.iter()
+                .filter(|(key, _)| *key > start && *key <= end)
+                .map(|(key, value)| (Bytes::from_static(key), Bytes::from_static(value)))
+                .collect())
+        }
+    }
+
+    /// 仅支持以Key读取的副本
+    #[derive(Debug)]
+    struct GetOnlyReplica;
+
+    #[async_trait]
+    impl ReadFallback for GetOnlyReplica {
+        async fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+            Replica.get(key).await
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct FallbackRecorder(Mutex<Vec<ReadFallbackEvent>>, Mutex<Vec<QuarantineReport>>);
+
+    impl EventListener for FallbackRecorder {
+        fn on_quarantine(&self, report: &QuarantineReport) {
+            self.1.lock().push(report.clone());
+        }
+
+        fn on_read_fallback(&self, event: &ReadFallbackEvent) {
+            self.0.lock().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn test_read_fallback() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            let gen = kv_store.current_version().await
+                .get_ss_tables_for_level(0).await[0]
+                .get_gen();
+            drop(kv_store);
+
+            // 模拟位翻转: 破坏SSTable的首个数据Block
+            let path = FileExtension::SSTable
+                .path_with_gen(&temp_dir.path().join(DEFAULT_SS_TABLE_PATH), gen);
+            let mut bytes = fs::read(&path)?;
+            bytes[8] = !bytes[8];
+            fs::write(&path, bytes)?;
+
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            assert!(kv_store.get(b"k1").await.is_err_and(|err| err.is_corruption()));
+            drop(kv_store);
+
+            let recorder = Arc::new(FallbackRecorder::default());
+            let listener: Arc<dyn EventListener> = Arc::<FallbackRecorder>::clone(&recorder);
+            let config = Config::new(temp_dir.path())
+                .read_fallback(Arc::new(Replica))
+                .add_event_listener(listener);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            let events = recorder.0.lock().clone();
+            assert_eq!(events.len(), 1);
+            assert_eq!((events[0].gen, events[0].recovered), (Some(gen), true));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_read_fallback_repair() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            kv_store.set(b"k3", Bytes::from_static(b"v3")).await?;
+            kv_store.flush().await?;
+            // 新的SSTable在下一次压缩时才会被合并至Level 1
+            kv_store.set(b"k4", Bytes::from_static(b"v4")).await?;
+            kv_store.flush().await?;
+            let ss_table = kv_store.current_version().await
+                .get_ss_tables_for_level(1).await
+                .into_iter()
+                .find(|ss_table| ss_table.get_scope().meet_with_key(b"k1"))
+                .expect("k1 should be compacted into level 1");
+            let (gen, len) = (ss_table.get_gen(), ss_table.len());
+            drop(kv_store);
+
+            // 模拟位翻转: 破坏Level 1中SSTable的首个数据Block
+            let path = FileExtension::SSTable
+                .path_with_gen(&temp_dir.path().join(DEFAULT_SS_TABLE_PATH), gen);
+            let mut bytes = fs::read(&path)?;
+            bytes[8] = !bytes[8];
+            fs::write(&path, bytes)?;
+
+            let recorder = Arc::new(FallbackRecorder::default());
+            let listener: Arc<dyn EventListener> = Arc::<FallbackRecorder>::clone(&recorder);
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .quarantine_corrupted(true)
+                .rebuild_quarantined(true)
+                .read_fallback(Arc::new(Replica))
+                .add_event_listener(listener);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            // 损坏的SSTable在压缩时被隔离，并以副本中该范围的数据重建
+            kv_store.flush().await?;
+            let reports = recorder.1.lock().clone();
+            assert_eq!(reports.len(), 1);
+            assert_eq!((reports[0].gen, reports[0].readable_len, reports[0].repaired_len), (gen, 0, len));
+            assert!(reports[0].rebuilt_gen.is_some());
+
+            // 修复后由本地读取，不再经过ReadFallback
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
+            assert_eq!(kv_store.get(b"k4").await?, Some(Bytes::from_static(b"v4")));
+            assert_eq!(recorder.0.lock().len(), 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_read_fallback_repair_level_0() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2_new")).await?;
+            kv_store.flush().await?;
+            let ss_tables = kv_store.current_version().await
+                .get_ss_tables_for_level(0).await;
+            assert_eq!(ss_tables.len(), 2);
+            let (gen, newer_gen) = (ss_tables[0].get_gen(), ss_tables[1].get_gen());
+            drop(kv_store);
+
+            // 模拟位翻转: 破坏Level 0中较旧的SSTable的首个数据Block
+            let path = FileExtension::SSTable
+                .path_with_gen(&temp_dir.path().join(DEFAULT_SS_TABLE_PATH), gen);
+            let mut bytes = fs::read(&path)?;
+            bytes[8] = !bytes[8];
+            fs::write(&path, bytes)?;
+
+            let recorder = Arc::new(FallbackRecorder::default());
+            let listener: Arc<dyn EventListener> = Arc::<FallbackRecorder>::clone(&recorder);
+            let config = Config::new(temp_dir.path())
+                .quarantine_corrupted(true)
+                .rebuild_quarantined(true)
+                .read_fallback(Arc::new(GetOnlyReplica))
+                .add_event_listener(listener);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            // 不支持scan时，get所取回的数据在重建时写回
+            kv_store.flush().await?;
+            let reports = recorder.1.lock().clone();
+            assert_eq!(reports.len(), 1);
+            assert_eq!((reports[0].gen, reports[0].level, reports[0].readable_len, reports[0].repaired_len), (gen, 0, 0, 1));
+
+            // 重建的SSTable仍位于原本更新的SSTable之前，不会覆盖其中的写入
+            let level_0 = kv_store.current_version().await
+                .get_ss_tables_for_level(0).await;
+            assert_eq!(level_0.len(), 2);
+            assert_eq!(Some(level_0[0].get_gen()), reports[0].rebuilt_gen);
+            assert_ne!(level_0[1].get_gen(), newer_gen);
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2_new")));
+            assert_eq!(recorder.0.lock().len(), 1);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use crate::kernel::io;
+use crate::kernel::lsm::block::{BlockCache, BlockKey};
+use crate::kernel::lsm::lsm_kv::Config;
+use crate::kernel::lsm::version::Version;
+use crate::kernel::Result;
+
+/// 热度图文件，记录关闭时Block缓存中的Block
+pub(crate) const DEFAULT_HEAT_MAP_FILE: &str = "heat_map";
+
+/// 将Block缓存中所有Block的Key作为热度图写入数据目录，返回写入的Key数量
+pub(crate) fn save(config: &Config, block_cache: &BlockCache) -> Result<usize> {
+    let keys = block_cache.keys();
+    // 先写入临时文件再重命名，避免中途退出时留下写入一半的热度图
+    let path = config.path().join(DEFAULT_HEAT_MAP_FILE);
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bincode::serialize(&keys)?)?;
+    io::rename_replace(&tmp_path, &path)?;
+
+    Ok(keys.len())
+}
+
+fn load(config: &Config) -> Result<Vec<BlockKey>> {
+    match fs::read(config.path().join(DEFAULT_HEAT_MAP_FILE)) {
+        Ok(bytes) => Ok(bincode::deserialize(&bytes)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// 按热度图将Block预先加载至Block缓存，返回加载的Block数量
+///
+/// 所属SSTable已被压缩移除的Block直接跳过，读取失败的Block同样跳过，留待正常读取时报告
+pub(crate) async fn warm_up(config: &Config, version: &Version) -> Result<usize> {
+    let keys = load(config)?;
+    if keys.is_empty() {
+        return Ok(0);
+    }
+    let ss_tables = version.get_all_ss_tables().await
+        .into_iter()
+        .flatten()
+        .map(|ss_table| (ss_table.get_gen(), ss_table))
+        .collect::<HashMap<_, _>>();
+    let block_cache = &version.block_cache;
+    let mut loaded = 0;
+
+    for (gen, index) in keys {
+        let Some(ss_table) = ss_tables.get(&gen) else {
+            continue
+        };
+        let is_loaded = match index {
+            Some(index) => matches!(ss_table.get_data_block(index, block_cache), Ok(Some(_))),
+            None => ss_table.get_index_block(block_cache).is_ok(),
+        };
+        if is_loaded {
+            loaded += 1;
+        }
+    }
+
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_warm_up() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path()).block_size(64).persist_heat_map(true);
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            // 不存在热度图时无需加载
+            assert_eq!(kv_store.warm_up().await?, 0);
+
+            for i in 0..100_u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from(vec![0; 32])).await?;
+            }
+            kv_store.flush().await?;
+            // 再次Flush使数据不再存在于Immutable MemTable中
+            kv_store.set(b"k", Bytes::from_static(b"v")).await?;
+            kv_store.flush().await?;
+            for i in 0..10_u32 {
+                assert!(kv_store.get(&i.to_be_bytes()).await?.is_some());
+            }
+            let block_count = kv_store.save_heat_map()?;
+            assert!(block_count > 1);
+            // 关闭时再次写入热度图
+            drop(kv_store);
+
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert_eq!(kv_store.warm_up().await?, block_count);
+            // 预热后的读取直接命中缓存
+            let block_cache = kv_store.statistics().await.block_cache;
+            assert!(kv_store.get(&0_u32.to_be_bytes()).await?.is_some());
+            assert_eq!(kv_store.statistics().await.block_cache.misses, block_cache.misses);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,162 @@
+//! Manifest的历史，用于在错误的导入或压缩之后将数据目录恢复至此前的Version
+//!
+//! 回滚以Manifest中的变更(如一次Flush或一次压缩)为单位，
+//! 需开启`Config::manifest_history`以保留此前的Version所引用的SSTable
+//!
+//! ```ignore
+//! // 先只读地确认回滚后的数据，再回滚数据目录
+//! let store = HistoricalStore::open(config.clone(), 3).await?;
+//! assert!(store.get(b"key").await?.is_some());
+//! drop(store);
+//! let _ = history::rollback_manifest(config, 3).await?;
+//! ```
+use std::sync::Arc;
+use bytes::Bytes;
+use tracing::error;
+use crate::kernel::{DEFAULT_LOCK_FILE, lock_or_time_out, Result};
+use crate::kernel::io::FileExtension;
+use crate::kernel::lsm::iterator::version_iter::VersionIter;
+use crate::kernel::lsm::log::LogLoader;
+use crate::kernel::utils::lock_file::LockFile;
+use crate::kernel::lsm::lsm_kv::{Config, DEFAULT_WAL_PATH, Gen};
+use crate::kernel::lsm::version::VersionStatus;
+
+/// 以回滚若干次变更后的Version只读打开的数据目录
+///
+/// 仅包含当时已持久化为SSTable的数据，不会读取WAL中的数据，
+/// 打开期间持有数据目录的锁，因此Store无法同时打开
+#[allow(missing_debug_implementations)]
+pub struct HistoricalStore {
+    ver_status: VersionStatus,
+    config: Config,
+    lock_file: LockFile,
+}
+
+impl HistoricalStore {
+    /// 以回滚steps次变更后的Version打开，steps为0时即为当前的Version
+    ///
+    /// 该Version所引用的SSTable已被删除时返回错误
+    #[inline]
+    pub async fn open(config: Config, steps: usize) -> Result<Self> {
+        config.spawner.check()?;
+        let lock_file = lock_or_time_out(&config.path().join(DEFAULT_LOCK_FILE)).await?;
+        let ver_status = load(&config, steps).await?;
+
+        Ok(HistoricalStore { ver_status, config, lock_file })
+    }
+
+    #[inline]
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        self.ver_status.current().await
+            .find_data_for_ss_tables(key).await
+    }
+
+    #[inline]
+    pub async fn iter(&self) -> Result<VersionIter<'_>> {
+        VersionIter::new(
+            self.ver_status.current().await,
+            self.config.max_sequential_skip
+        ).await
+    }
+
+    /// 该Version中各Level的SSTable数量
+    #[inline]
+    pub async fn level_sst_count(&self) -> Vec<usize> {
+        self.ver_status.current().await
+            .level_sst_count()
+    }
+}
+
+impl Drop for HistoricalStore {
+    #[inline]
+    fn drop(&mut self) {
+        if let Err(err) = self.lock_file.unlock() {
+            error!("[HistoricalStore][drop][LockFile unlock failed]: {:?}", err);
+        }
+    }
+}
+
+/// 将数据目录的Manifest回滚steps次变更，返回回滚后剩余的变更次数，需在Store未打开时执行
+///
+/// 回滚前先以回滚后的Version载入，确认其所引用的SSTable均存在，
+/// 原有的Manifest文件仍保留于目录中，而WAL中尚未持久化为SSTable的数据仍会在Store下次打开时重放
+#[inline]
+pub async fn rollback_manifest(config: Config, steps: usize) -> Result<usize> {
+    let mut lock_file = lock_or_time_out(&config.path().join(DEFAULT_LOCK_FILE)).await?;
+    drop(load(&config, steps).await?);
+    let remaining = VersionStatus::rollback(&config, steps)?;
+    lock_file.unlock()?;
+
+    Ok(remaining)
+}
+
+async fn load(config: &Config, steps: usize) -> Result<VersionStatus> {
+    Gen::init();
+    let (wal, _) = LogLoader::reload_(
+        config.clone(),
+        DEFAULT_WAL_PATH,
+        FileExtension::Log,
+        config.wal_io_type
+    )?;
+
+    VersionStatus::load_with_steps(config.clone(), Arc::new(wal), steps).await
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::history::{self, HistoricalStore};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::utils::runtime::DeterministicExecutor;
+    use crate::kernel::Result;
+    use crate::KernelError;
+
+    #[test]
+    fn test_rollback_manifest() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let executor = DeterministicExecutor::new(42);
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .manifest_history(16)
+                .spawner(executor.spawner());
+            let kv_store = executor.run_until(LsmStore::open_with_config(config.clone())).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            executor.run_until(kv_store.flush()).await?;
+            // 模拟错误的写入，并经压缩覆盖旧的数据
+            kv_store.set(b"k1", Bytes::from_static(b"bad")).await?;
+            executor.run_until(kv_store.flush()).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            executor.run_until(kv_store.flush()).await?;
+            assert_eq!(kv_store.current_version().await.level_sst_count()[0], 1);
+            drop(kv_store);
+            // 执行Cleaner直至其处理完被删除的SSTable
+            let _ = executor.run_until_idle();
+
+            // 被压缩删除的SSTable仍被保留，因此可读取此前的各个Version
+            let mut steps = 0;
+            loop {
+                let store = executor.run_until(HistoricalStore::open(config.clone(), steps)).await?;
+                if store.get(b"k1").await? == Some(Bytes::from_static(b"v1")) {
+                    break;
+                }
+                steps += 1;
+            }
+            assert!(steps > 0);
+            assert!(matches!(
+                executor.run_until(HistoricalStore::open(config.clone(), usize::MAX)).await,
+                Err(KernelError::ManifestHistoryExceeded(_))
+            ));
+
+            let _ = executor.run_until(history::rollback_manifest(config.clone(), steps)).await?;
+            let kv_store = executor.run_until(LsmStore::open_with_config(config)).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k2").await?, None);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! 写入前的钩子，用于在写入WAL前校验或拒绝写入，如限制Value的大小或校验其格式
+//!
+//! ```ignore
+//! let config = Config::new(path)
+//!     .add_write_hook(b"user:", Arc::new(MaxValueSize(1024)))
+//!     .add_cf_write_hook(b"index", Arc::new(IndexSchema));
+//! ```
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use bytes::Bytes;
+use parking_lot::RwLock;
+use crate::kernel::lsm::chunk::INTERNAL_KEY_PREFIX;
+use crate::kernel::lsm::column_family;
+use crate::kernel::Result;
+use crate::KernelError;
+
+/// 写入前的钩子
+///
+/// 在写入的执行路径中同步调用，应避免耗时操作
+pub trait WriteHook: Debug + Send + Sync {
+    /// value为None时表示删除，返回错误时拒绝该写入
+    ///
+    /// 批量写入与事务中任一写入被拒绝时，整个批量写入或事务均不会写入
+    fn before_write(&self, key: &[u8], value: Option<&[u8]>) -> std::result::Result<(), WriteRejection>;
+}
+
+/// 写入钩子拒绝写入的原因，通过`KernelError::WriteRejected`返回给调用方，
+/// 并以`ConnectionError::WriteRejected`返回给远程的客户端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteRejection {
+    /// 由钩子定义的错误码
+    pub code: u32,
+    pub reason: String,
+    /// 被拒绝写入的Key，列族中的Key不包含列族的前缀，由Store填充
+    pub key: Vec<u8>,
+}
+
+impl WriteRejection {
+    #[inline]
+    pub fn new(code: u32, reason: impl Into<String>) -> Self {
+        WriteRejection { code, reason: reason.into(), key: vec![] }
+    }
+}
+
+/// 写入钩子的作用范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum HookScope {
+    /// Key以该前缀开头的写入，不包括内部Key
+    Prefix(Vec<u8>),
+    /// 该名称的列族中的写入
+    ColumnFamily(Vec<u8>),
+}
+
+pub(crate) struct WriteHooks {
+    hooks: Vec<(HookScope, Arc<dyn WriteHook>)>,
+    /// 已打开的列族的Key前缀 -> (列族名称, 是否设置了TTL)
+    column_families: RwLock<HashMap<Bytes, (Vec<u8>, bool)>>,
+}
+
+impl WriteHooks {
+    pub(crate) fn new(hooks: Vec<(HookScope, Arc<dyn WriteHook>)>) -> Self {
+        WriteHooks { hooks, column_families: RwLock::new(HashMap::new()) }
+    }
+
+    /// 记录列族的Key前缀与名称，使其中的写入可由列族的钩子校验
+    pub(crate) fn bind_column_family(&self, prefix: &Bytes, name: &[u8], has_ttl: bool) {
+        let has_hook = self.hooks.iter()
+            .any(|(scope, _)| matches!(scope, HookScope::ColumnFamily(cf_name) if cf_name == name));
+
+        if has_hook {
+            let _ = self.column_families.write()
+                .insert(prefix.clone(), (name.to_vec(), has_ttl));
+        }
+    }
+
+    pub(crate) fn unbind_column_family(&self, prefix: &[u8]) {
+        let _ = self.column_families.write().remove(prefix);
+    }
+
+    /// 依次以匹配的钩子校验各写入，任一写入被拒绝时返回`KernelError::WriteRejected`
+    pub(crate) fn check<'a>(
+        &self,
+        writes: impl IntoIterator<Item = (&'a [u8], Option<&'a [u8]>)>
+    ) -> Result<()> {
+        if self.hooks.is_empty() {
+            return Ok(());
+        }
+        for (key, value) in writes {
+            match column_family::split_cf_key(key) {
+                Some((prefix, cf_key)) => {
+                    let column_families = self.column_families.read();
+                    let Some((name, has_ttl)) = column_families.get(prefix) else {
+                        continue
+                    };
+                    let value = value.map(|value| if *has_ttl { column_family::strip_expire_at(value) } else { value });
+
+                    self.check_with(|scope| matches!(scope, HookScope::ColumnFamily(cf_name) if cf_name == name), cf_key, value)?;
+                }
+                None if key.starts_with(INTERNAL_KEY_PREFIX) => (),
+                None => self.check_with(|scope| matches!(scope, HookScope::Prefix(prefix) if key.starts_with(prefix)), key, value)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_with(
+        &self,
+        fn_match: impl Fn(&HookScope) -> bool,
+        key: &[u8],
+        value: Option<&[u8]>
+    ) -> Result<()> {
+        for (_, hook) in self.hooks.iter().filter(|(scope, _)| fn_match(scope)) {
+            if let Err(mut rejection) = hook.before_write(key, value) {
+                rejection.key = key.to_vec();
+                return Err(KernelError::WriteRejected(rejection));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::column_family::WriteBatch;
+    use crate::kernel::lsm::hook::{WriteHook, WriteRejection};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::Result;
+    use crate::KernelError;
+
+    #[derive(Debug)]
+    struct MaxValueSize(usize);
+
+    impl WriteHook for MaxValueSize {
+        fn before_write(&self, _key: &[u8], value: Option<&[u8]>) -> std::result::Result<(), WriteRejection> {
+            match value {
+                Some(value) if value.len() > self.0 => Err(WriteRejection::new(1, "value too large")),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReadOnly;
+
+    impl WriteHook for ReadOnly {
+        fn before_write(&self, _key: &[u8], _value: Option<&[u8]>) -> std::result::Result<(), WriteRejection> {
+            Err(WriteRejection::new(2, "read only"))
+        }
+    }
+
+    #[test]
+    fn test_write_hooks() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .add_write_hook(b"user:", Arc::new(MaxValueSize(4)))
+                .add_cf_write_hook(b"frozen", Arc::new(ReadOnly));
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"user:1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"other", Bytes::from_static(b"too large")).await?;
+            let Err(KernelError::WriteRejected(rejection)) = kv_store.set(b"user:2", Bytes::from_static(b"too large")).await else {
+                panic!("write is not rejected");
+            };
+            assert_eq!(rejection.code, 1);
+            assert_eq!(rejection.key, b"user:2");
+            assert_eq!(kv_store.get(b"user:2").await?, None);
+
+            // 批量写入中任一写入被拒绝时，整个批量写入均不会写入
+            let frozen = kv_store.column_families().create(b"frozen").await?;
+            let mut batch = WriteBatch::default();
+            let _ = batch.set(b"user:3", Bytes::from_static(b"v3"))
+                .set_cf(&frozen, b"k1", Bytes::from_static(b"v1"));
+            let Err(KernelError::WriteRejected(rejection)) = kv_store.write(batch).await else {
+                panic!("write is not rejected");
+            };
+            assert_eq!(rejection.key, b"k1");
+            assert_eq!(kv_store.get(b"user:3").await?, None);
+            assert!(matches!(frozen.set(b"k2", Bytes::new()).await, Err(KernelError::WriteRejected(_))));
+
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(b"user:4", Bytes::from_static(b"too large"));
+            assert!(matches!(transaction.commit().await, Err(KernelError::WriteRejected(_))));
+            assert_eq!(kv_store.get(b"user:4").await?, None);
+
+            Ok(())
+        })
+    }
+}
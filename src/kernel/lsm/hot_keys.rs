@@ -0,0 +1,151 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use bytes::Bytes;
+use itertools::Itertools;
+use parking_lot::Mutex;
+
+/// Count-Min Sketch的行数
+const SKETCH_DEPTH: usize = 4;
+
+/// Count-Min Sketch每行的计数器数量
+const SKETCH_WIDTH: usize = 1 << 12;
+
+/// 访问次数每达到计数器总数的该倍数时，所有计数减半，使统计偏向近期的访问
+const DECAY_INTERVAL: u64 = (SKETCH_DEPTH * SKETCH_WIDTH * 8) as u64;
+
+/// 热点Key统计
+///
+/// 以Count-Min Sketch估算各Key的访问次数，其估算值仅会偏大而不会偏小，
+/// 并保留估算值最大的capacity个Key作为候选，使读取路径上仅需进行几次原子操作，
+/// 仅当估算值超过候选中的最小值时才需获取锁
+///
+/// 所有计数会周期性减半，因此访问次数为近期访问的加权值而非累计值
+pub(crate) struct HotKeys<S = RandomState> {
+    counters: Vec<AtomicU32>,
+    accesses: AtomicU64,
+    /// 候选已满时其中的最小估算值，未满时为0
+    threshold: AtomicU64,
+    candidates: Mutex<HashMap<Bytes, u64>>,
+    capacity: usize,
+    hasher: S,
+}
+
+impl HotKeys {
+    pub(crate) fn new(capacity: usize) -> Self {
+        HotKeys {
+            counters: (0..SKETCH_DEPTH * SKETCH_WIDTH)
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            accesses: AtomicU64::new(0),
+            threshold: AtomicU64::new(0),
+            candidates: Mutex::new(HashMap::with_capacity(capacity + 1)),
+            capacity: capacity.max(1),
+            hasher: RandomState::default(),
+        }
+    }
+}
+
+impl<S: BuildHasher> HotKeys<S> {
+    /// 记录一次对Key的访问
+    pub(crate) fn record(&self, key: &[u8]) {
+        let hash = self.hasher.hash_one(key);
+        // 以两个Hash值的线性组合模拟各行独立的Hash函数
+        let (hash_1, hash_2) = (hash as u32 as usize, (hash >> 32) as usize | 1);
+        let estimate = (0..SKETCH_DEPTH)
+            .map(|row| {
+                let index = row * SKETCH_WIDTH + hash_1.wrapping_add(row.wrapping_mul(hash_2)) % SKETCH_WIDTH;
+                u64::from(self.counters[index].fetch_add(1, Ordering::Relaxed)) + 1
+            })
+            .min()
+            .unwrap_or(0);
+
+        if (self.accesses.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(DECAY_INTERVAL) {
+            self.decay();
+        }
+        if estimate > self.threshold.load(Ordering::Relaxed) {
+            self.offer(key, estimate);
+        }
+    }
+
+    fn offer(&self, key: &[u8], estimate: u64) {
+        let mut candidates = self.candidates.lock();
+
+        if let Some(count) = candidates.get_mut(key) {
+            *count = estimate;
+        } else {
+            let _ = candidates.insert(Bytes::copy_from_slice(key), estimate);
+            if candidates.len() > self.capacity {
+                if let Some(coldest) = candidates.iter()
+                    .min_by_key(|(_, count)| **count)
+                    .map(|(key, _)| key.clone())
+                {
+                    let _ = candidates.remove(&coldest);
+                }
+            }
+        }
+        self.update_threshold(&candidates);
+    }
+
+    fn update_threshold(&self, candidates: &HashMap<Bytes, u64>) {
+        let threshold = if candidates.len() < self.capacity { 0 } else {
+            candidates.values().min().copied().unwrap_or(0)
+        };
+        self.threshold.store(threshold, Ordering::Relaxed);
+    }
+
+    /// 所有计数减半
+    ///
+    /// Tips: 与并发的记录之间不保证原子性，少量的访问可能未被减半或丢失，对估算的影响可忽略
+    fn decay(&self) {
+        for counter in &self.counters {
+            counter.store(counter.load(Ordering::Relaxed) / 2, Ordering::Relaxed);
+        }
+        let mut candidates = self.candidates.lock();
+        candidates.retain(|_, count| {
+            *count /= 2;
+            *count > 0
+        });
+        self.update_threshold(&candidates);
+    }
+
+    /// 获取估算访问次数最多的n个Key，以访问次数降序排列
+    pub(crate) fn top_keys(&self, n: usize) -> Vec<(Bytes, u64)> {
+        self.candidates.lock()
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .sorted_unstable_by(|(key_a, count_a), (key_b, count_b)| {
+                count_b.cmp(count_a).then_with(|| key_a.cmp(key_b))
+            })
+            .take(n)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use crate::kernel::lsm::hot_keys::HotKeys;
+
+    #[test]
+    fn test_hot_keys() {
+        let hot_keys = HotKeys::new(2);
+
+        for i in 0..1000u32 {
+            hot_keys.record(b"hot");
+            if i % 10 == 0 {
+                hot_keys.record(b"warm");
+            }
+            hot_keys.record(&i.to_be_bytes());
+        }
+        let top_keys = hot_keys.top_keys(3);
+
+        assert_eq!(top_keys.len(), 2);
+        assert_eq!(top_keys[0].0, Bytes::from_static(b"hot"));
+        assert_eq!(top_keys[1].0, Bytes::from_static(b"warm"));
+        // 估算值仅会偏大
+        assert!(top_keys[0].1 >= 1000);
+        assert!(top_keys[1].1 >= 100);
+    }
+}
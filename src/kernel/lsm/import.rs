@@ -0,0 +1,188 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::mem;
+use std::path::Path;
+use std::thread;
+use bytes::Bytes;
+#[cfg(feature = "rocksdb_import")]
+use rocksdb::{DB, IteratorMode, Options};
+use tokio::sync::mpsc;
+use crate::kernel::Result;
+use crate::kernel::lsm::lsm_kv::LsmStore;
+use crate::kernel::utils::rdb::RdbReader;
+use crate::KernelError;
+
+/// 单次导入的数据大小上限
+pub const DEFAULT_IMPORT_BATCH_SIZE: usize = 64 * 1024 * 1024;
+
+/// Redis RDB的导入结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RdbImportReport {
+    /// 导入的字符串类型Key数量
+    pub imported: usize,
+    /// 导入时带有过期时间的Key数量，KipDB不支持Key的过期，因此导入后不再过期
+    pub with_ttl: usize,
+    /// 已过期而未导入的Key数量
+    pub expired: usize,
+    /// 非字符串类型而未导入的Key数量
+    pub skipped: usize,
+    /// 其他数据库中而未导入的Key数量
+    pub other_db: usize,
+}
+
+/// 从RocksDB或LevelDB的数据目录导入数据，返回导入的键值对数量
+///
+/// 以只读方式打开源目录，在独立线程中按Key的顺序读取，每积累batch_size大小的数据即通过`LsmStore::ingest`导入，
+/// 读取与导入之间仅缓冲一个批次，因此内存占用与源数据的大小无关
+///
+/// Tips:
+/// - RocksDB可直接打开LevelDB的数据目录，源Store需使用默认的字节序比较器，且仅导入默认列族
+/// - 导入的数据会覆盖目标Store中已存在的同Key数据
+#[cfg(feature = "rocksdb_import")]
+#[inline]
+pub async fn import_rocksdb(src: impl AsRef<Path>, kv_store: &LsmStore, batch_size: usize) -> Result<usize> {
+    let src = src.as_ref().to_path_buf();
+    let (tx, mut rx) = mpsc::channel(1);
+
+    let reader = thread::spawn(move || -> Result<()> {
+        let db = DB::open_for_read_only(&Options::default(), src, false)?;
+        let mut batch = Vec::new();
+        let mut batch_bytes = 0;
+
+        for item in db.iterator(IteratorMode::Start) {
+            let (key, value) = item?;
+            batch_bytes += key.len() + value.len();
+            batch.push((Bytes::from(key), Bytes::from(value)));
+
+            if batch_bytes >= batch_size {
+                // 导入失败时接收端被丢弃，此时直接结束读取
+                if tx.blocking_send(mem::take(&mut batch)).is_err() {
+                    return Ok(());
+                }
+                batch_bytes = 0;
+            }
+        }
+        if !batch.is_empty() {
+            let _ignore = tx.blocking_send(batch);
+        }
+
+        Ok(())
+    });
+
+    let mut imported = 0;
+    while let Some(batch) = rx.recv().await {
+        imported += batch.len();
+        kv_store.ingest(batch).await?;
+    }
+    // 发送端已被丢弃，读取线程已结束
+    reader.join()
+        .map_err(|_| KernelError::ChannelClose)??;
+
+    Ok(imported)
+}
+
+/// 从Redis的RDB快照导入指定数据库中的字符串类型Key
+///
+/// 与`import_rocksdb`相同，在独立线程中流式读取，每积累batch_size大小的数据即排序后通过`LsmStore::ingest`导入，
+/// 已过期的Key被丢弃，其余带有过期时间的Key导入后不再过期，非字符串类型的Key被跳过，详见返回的报告
+///
+/// Tips: RDB中同一数据库内的Key不重复，因此分批导入不会使旧数据覆盖新数据
+#[inline]
+pub async fn import_rdb(src: impl AsRef<Path>, kv_store: &LsmStore, db: u64, batch_size: usize) -> Result<RdbImportReport> {
+    let src = src.as_ref().to_path_buf();
+    let now_ms = kv_store.config().clock.now_millis();
+    let (tx, mut rx) = mpsc::channel::<Vec<(Bytes, Bytes)>>(1);
+
+    let reader = thread::spawn(move || -> Result<RdbImportReport> {
+        let mut rdb_reader = RdbReader::new(BufReader::new(File::open(src)?))?;
+        let mut report = RdbImportReport::default();
+        let mut batch = Vec::new();
+        let mut batch_bytes = 0;
+
+        while let Some(entry) = rdb_reader.next_entry()? {
+            if entry.db != db {
+                report.other_db += 1;
+                continue
+            }
+            match entry.expire_ms {
+                Some(expire_ms) if expire_ms <= now_ms => {
+                    report.expired += 1;
+                    continue
+                }
+                Some(_) => report.with_ttl += 1,
+                None => (),
+            }
+            batch_bytes += entry.key.len() + entry.value.len();
+            batch.push((Bytes::from(entry.key), Bytes::from(entry.value)));
+
+            if batch_bytes >= batch_size {
+                if tx.blocking_send(mem::take(&mut batch)).is_err() {
+                    return Ok(report);
+                }
+                batch_bytes = 0;
+            }
+        }
+        if !batch.is_empty() {
+            let _ignore = tx.blocking_send(batch);
+        }
+        report.skipped = rdb_reader.skipped();
+
+        Ok(report)
+    });
+
+    let mut imported = 0;
+    while let Some(mut batch) = rx.recv().await {
+        imported += batch.len();
+        batch.sort_unstable_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+        kv_store.ingest(batch).await?;
+    }
+    let mut report = reader.join()
+        .map_err(|_| KernelError::ChannelClose)??;
+    report.imported = imported;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::import::{import_rdb, RdbImportReport};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_import_rdb() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let rdb_path = temp_dir.path().join("dump.rdb");
+
+        let mut rdb = b"REDIS0011\xFE\x00".to_vec();
+        rdb.extend_from_slice(b"\x00\x02k2\x02v2\x00\x02k1\x02v1");
+        // 已过期与未过期的Key
+        rdb.push(0xFC);
+        rdb.extend_from_slice(&1_000i64.to_le_bytes());
+        rdb.extend_from_slice(b"\x00\x02k3\x02v3");
+        rdb.push(0xFC);
+        rdb.extend_from_slice(&i64::MAX.to_le_bytes());
+        rdb.extend_from_slice(b"\x00\x02k4\x02v4");
+        rdb.extend_from_slice(b"\x02\x03set\x01\x01a\xFE\x01\x00\x02k5\x02v5\xFF");
+        fs::write(&rdb_path, rdb)?;
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open_with_config(Config::new(temp_dir.path().join("data"))).await?;
+            // 单个Key即达到批次上限，使数据分多批导入
+            let report = import_rdb(&rdb_path, &kv_store, 0, 1).await?;
+
+            assert_eq!(report, RdbImportReport { imported: 3, with_ttl: 1, expired: 1, skipped: 1, other_db: 1 });
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k3").await?, None);
+            assert_eq!(kv_store.get(b"k4").await?, Some(Bytes::from_static(b"v4")));
+            assert_eq!(kv_store.get(b"k5").await?, None);
+
+            Ok(())
+        })
+    }
+}
@@ -1,35 +1,35 @@
-use core::slice::SlicePattern;
 use std::cmp::min;
 use std::iter::Iterator;
-use bytes::Bytes;
-use itertools::Itertools;
+use std::sync::Arc;
+use bytes::{BufMut, Bytes, BytesMut};
 use crate::kernel::lsm::iterator::{Seek, DiskIter};
-use crate::kernel::lsm::block::{Block, BlockItem};
+use crate::kernel::lsm::block::{Block, BlockItem, Value};
 use crate::kernel::Result;
 use crate::KernelError;
 
 /// Block迭代器
 ///
 /// Tips: offset偏移会额外向上偏移一位以使用0作为迭代的下界判断是否向前溢出了
-pub(crate) struct BlockIter<'a, T> {
-    block: &'a Block<T>,
+///
+/// 持有Block的Arc句柄，因此Block被缓存驱逐后迭代器依旧有效
+pub(crate) struct BlockIter<T> {
+    block: Arc<Block<T>>,
     entry_len: usize,
 
     offset: usize,
-    buf_shared_key: &'a [u8]
+    /// 当前共享前缀所属的Entry位置与前缀长度
+    shared_key: (usize, usize)
 }
 
-impl<'a, T> BlockIter<'a, T> where T: BlockItem {
-    pub(crate) fn new(block: &'a Block<T>) -> BlockIter<'a, T> {
-        let buf_shared_key = block.shared_key_prefix(
-            0, block.restart_shared_len(0)
-        );
+impl<T> BlockIter<T> where T: BlockItem {
+    pub(crate) fn new(block: Arc<Block<T>>) -> BlockIter<T> {
+        let shared_key = (0, block.restart_shared_len(0));
 
         BlockIter {
-            block,
             entry_len: block.entry_len(),
+            block,
             offset: 0,
-            buf_shared_key,
+            shared_key,
         }
     }
 
@@ -37,17 +37,22 @@ impl<'a, T> BlockIter<'a, T> where T: BlockItem {
         let offset = self.offset - 1;
         let entry = self.block.get_entry(offset);
 
-        (if offset % self.block.restart_interval() != 0 {
-            Bytes::from(self.buf_shared_key.iter()
-                .chain(entry.key().as_slice())
-                .copied()
-                .collect_vec())
-        } else { entry.key().clone() }, entry.item().clone())
+        // 一次性分配完整Key所需的内存，避免逐字节拼接时的多次扩容
+        let key = if offset % self.block.restart_interval() != 0 {
+            let (index, shared_len) = self.shared_key;
+            let shared_key_prefix = self.block.shared_key_prefix(index, shared_len);
+
+            let mut buf = BytesMut::with_capacity(shared_key_prefix.len() + entry.key().len());
+            buf.put_slice(shared_key_prefix);
+            buf.put_slice(entry.key());
+            buf.freeze()
+        } else { Bytes::copy_from_slice(entry.key()) };
+
+        (key, entry.item().clone())
     }
 
     fn offset_move(&mut self, offset: usize) -> Result<(Bytes, T)>{
-        let block = self.block;
-        let restart_interval = block.restart_interval();
+        let restart_interval = self.block.restart_interval();
 
         let old_offset = self.offset;
         self.offset = offset;
@@ -55,16 +60,45 @@ impl<'a, T> BlockIter<'a, T> where T: BlockItem {
         if offset > 0 {
             let real_offset = offset - 1;
             if old_offset - 1 / restart_interval != real_offset / restart_interval {
-                self.buf_shared_key = block.shared_key_prefix(
-                    real_offset, block.restart_shared_len(real_offset)
-                );
+                self.shared_key = (real_offset, self.block.restart_shared_len(real_offset));
             }
             Ok(self.item())
         } else { Err(KernelError::OutOfBounds) }
     }
 }
 
-impl<V> DiskIter<Vec<u8>, V> for BlockIter<'_, V>
+impl BlockIter<Value> {
+    /// 从end(不含)向前定位至最近的未被删除的Entry
+    ///
+    /// 被跳过的Entry不会进行Key的还原，其数量累加至skipped
+    fn live_move(&mut self, end: usize, skipped: &mut usize) -> Result<(Bytes, Value)> {
+        match self.block.rposition_live(end) {
+            Some(index) => {
+                *skipped += end - index - 1;
+                self.offset = index + 1;
+                self.shared_key = (index, self.block.restart_shared_len(index));
+                Ok(self.item())
+            }
+            None => {
+                *skipped += end;
+                self.offset = 0;
+                Err(KernelError::OutOfBounds)
+            }
+        }
+    }
+
+    /// 向前跳过删除标记，定位至前方最近的未被删除的Entry
+    pub(crate) fn prev_live(&mut self, skipped: &mut usize) -> Result<(Bytes, Value)> {
+        self.live_move(self.offset.saturating_sub(1), skipped)
+    }
+
+    /// 定位至最后一个未被删除的Entry
+    pub(crate) fn last_live(&mut self, skipped: &mut usize) -> Result<(Bytes, Value)> {
+        self.live_move(self.entry_len, skipped)
+    }
+}
+
+impl<V> DiskIter<Vec<u8>, V> for BlockIter<V>
     where V: Sync + Send + BlockItem
 {
     type Item = (Bytes, V);
@@ -101,7 +135,7 @@ impl<V> DiskIter<Vec<u8>, V> for BlockIter<'_, V>
     }
 }
 
-impl<V: Sync + Send + BlockItem> Iterator for BlockIter<'_, V> {
+impl<V: Sync + Send + BlockItem> Iterator for BlockIter<V> {
     type Item = (Bytes, V);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -111,6 +145,7 @@ impl<V: Sync + Send + BlockItem> Iterator for BlockIter<'_, V> {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
     use std::vec;
     use bincode::Options;
     use bytes::Bytes;
@@ -127,7 +162,7 @@ mod tests {
             (Bytes::from(vec![b'4']), Value::from(None)),
         ];
         let block = Block::new(data, DEFAULT_DATA_RESTART_INTERVAL);
-        let mut iterator = BlockIter::new(&block);
+        let mut iterator = BlockIter::new(Arc::new(block));
 
         assert!(!iterator.is_valid());
 
@@ -177,7 +212,7 @@ mod tests {
             );
         }
         let block = Block::new(vec_data.clone(), DEFAULT_DATA_RESTART_INTERVAL);
-        let mut iterator = BlockIter::new(&block);
+        let mut iterator = BlockIter::new(Arc::new(block));
 
         for i in 0..times {
             assert_eq!(iterator.next_err()?, vec_data[i]);
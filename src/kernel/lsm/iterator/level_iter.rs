@@ -15,12 +15,17 @@ pub(crate) struct LevelIter<'a> {
 
     offset: usize,
     sst_iter: SSTableIter<'a>,
+
+    tables_probed: usize,
+    blocks_read: usize,
 }
 
 impl<'a> LevelIter<'a> {
     #[allow(dead_code)]
     pub(crate) fn new(ss_tables: &'a Vec<SSTable>, level: usize, block_cache: &'a BlockCache) -> Result<LevelIter<'a>> {
-        let sst_iter = SSTableIter::new(&ss_tables[0], block_cache)?;
+        let first = ss_tables.first()
+            .ok_or(KernelError::DataEmpty)?;
+        let sst_iter = SSTableIter::new(first, block_cache)?;
 
         Ok(Self {
             ss_tables,
@@ -28,15 +33,35 @@ impl<'a> LevelIter<'a> {
             level,
             offset: 0,
             sst_iter,
+            tables_probed: 1,
+            blocks_read: 0,
         })
     }
 
+    pub(crate) fn level(&self) -> usize {
+        self.level
+    }
+
+    /// 该迭代器访问的SSTable数量
+    pub(crate) fn tables_probed(&self) -> usize {
+        self.tables_probed
+    }
+
+    /// 该迭代器读取的DataBlock数量
+    pub(crate) fn blocks_read(&self) -> usize {
+        self.blocks_read + self.sst_iter.blocks_read()
+    }
+
     fn sst_iter_seek(&mut self, seek: Seek, offset: usize) -> Result<KeyValue> {
         self.offset = offset;
         if self.is_valid() {
             let ss_table = &self.ss_tables[offset];
             if ss_table.get_gen() != self.sst_iter.get_gen() {
-                self.sst_iter = SSTableIter::new(ss_table, self.block_cache)?;
+                let sst_iter = SSTableIter::new(ss_table, self.block_cache)?;
+
+                self.blocks_read += self.sst_iter.blocks_read();
+                self.tables_probed += 1;
+                self.sst_iter = sst_iter;
             }
             self.sst_iter.seek(seek)
         } else { Err(KernelError::OutOfBounds) }
@@ -46,11 +71,36 @@ impl<'a> LevelIter<'a> {
         if self.level == 0 {
             return Err(KernelError::NotSupport(LEVEL_0_SEEK_MESSAGE));
         }
+        // Level 1-6中SSTable之间范围不重叠，因此仅有唯一一个SSTable可能包含该key
         let offset = self.ss_tables
-            .binary_search_by(|ss_table| ss_table.get_scope().start.as_ref().cmp(key))
-            .unwrap_or_else(|index| index.saturating_sub(1));
+            .partition_point(|ss_table| ss_table.get_scope().start.as_ref() <= key)
+            .saturating_sub(1);
+
+        match self.sst_iter_seek(seek, offset) {
+            // key落于两个SSTable之间时，Backward的结果为下一个SSTable的首个元素
+            Err(KernelError::OutOfBounds) if matches!(seek, Seek::Backward(_)) => {
+                self.sst_iter_seek(Seek::First, offset + 1)
+            }
+            result => result
+        }
+    }
+}
 
-        self.sst_iter_seek(seek, offset)
+impl LevelIter<'_> {
+    /// 向前跳过删除标记，定位至前方最近的未被删除的数据
+    pub(crate) fn prev_live(&mut self, skipped: &mut usize) -> Result<KeyValue> {
+        loop {
+            match self.sst_iter.prev_live(skipped) {
+                Err(KernelError::OutOfBounds) if self.offset > 0 => {
+                    let item = self.sst_iter_seek(Seek::Last, self.offset - 1)?;
+                    if item.1.is_some() {
+                        return Ok(item);
+                    }
+                    *skipped += 1;
+                }
+                result => return result
+            }
+        }
     }
 }
 
@@ -115,18 +165,17 @@ impl Iterator for LevelIter<'_> {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::hash_map::RandomState;
     use bincode::Options;
     use bytes::Bytes;
     use tempfile::TempDir;
     use crate::kernel::io::{FileExtension, IoFactory};
+    use crate::kernel::lsm::block::BlockCache;
     use crate::kernel::lsm::lsm_kv::Config;
     use crate::kernel::lsm::ss_table::SSTable;
     use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
     use crate::kernel::Result;
     use crate::kernel::lsm::iterator::{DiskIter, Seek};
     use crate::kernel::lsm::iterator::level_iter::LevelIter;
-    use crate::kernel::utils::lru_cache::ShardingLruCache;
 
     #[test]
     fn test_iterator() -> Result<()> {
@@ -170,11 +219,7 @@ mod tests {
             slice_2.to_vec(),
             1
         )?;
-        let cache = ShardingLruCache::new(
-            config.block_cache_size,
-            16,
-            RandomState::default()
-        )?;
+        let cache = BlockCache::new(&config)?;
         // 注意，SSTables的新旧顺序为旧->新
         let ss_tables = vec![ss_table_1, ss_table_2];
 
@@ -195,6 +240,16 @@ mod tests {
 
         assert_eq!(iterator.seek(Seek::Backward(&vec_data[2048].0))?, vec_data[2048]);
 
+        // 位于两个SSTable之间的key
+        let gap_key = vec_data[1999].0.iter()
+            .copied()
+            .chain([0])
+            .collect::<Vec<u8>>();
+
+        assert_eq!(iterator.seek(Seek::Forward(&gap_key))?, vec_data[1999]);
+
+        assert_eq!(iterator.seek(Seek::Backward(&gap_key))?, vec_data[2000]);
+
         assert_eq!(iterator.seek(Seek::First)?, vec_data[0]);
 
         assert_eq!(iterator.seek(Seek::Last)?, vec_data[3999]);
@@ -0,0 +1,148 @@
+use crate::kernel::lsm::block::BlockCache;
+use crate::kernel::lsm::compactor::LEVEL_0;
+use crate::kernel::lsm::iterator::{DiskIter, Seek};
+use crate::kernel::lsm::iterator::level_iter::LevelIter;
+use crate::kernel::lsm::iterator::sstable_iter::SSTableIter;
+use crate::kernel::lsm::mem_table::KeyValue;
+use crate::kernel::lsm::ss_table::SSTable;
+use crate::kernel::Result;
+use crate::KernelError;
+
+/// 归并迭代器的数据源
+///
+/// 各数据源内部的数据需以Key升序排列且不重复
+pub(crate) enum MergeSource<'a> {
+    Mem(std::vec::IntoIter<KeyValue>),
+    Table(SSTableIter<'a>),
+    Level(LevelIter<'a>),
+}
+
+impl<'a> MergeSource<'a> {
+    /// 以内存表中已排序的数据构建数据源
+    pub(crate) fn mem(vec_data: Vec<KeyValue>) -> (Self, Option<KeyValue>) {
+        let mut iter = vec_data.into_iter();
+        let first = iter.next();
+
+        (MergeSource::Mem(iter), first)
+    }
+
+    /// 以SSTable构建数据源，并定位至start之后
+    pub(crate) fn table(
+        ss_table: &'a SSTable,
+        block_cache: &'a BlockCache,
+        start: Option<&[u8]>
+    ) -> Result<(Self, Option<KeyValue>)> {
+        let mut iter = SSTableIter::new(ss_table, block_cache)?;
+        let first = Self::seek_after(&mut iter, start)?;
+
+        Ok((MergeSource::Table(iter), first))
+    }
+
+    /// 以Level 1-6的SSTable构建数据源，并定位至start之后
+    pub(crate) fn level(
+        ss_tables: &'a Vec<SSTable>,
+        level: usize,
+        block_cache: &'a BlockCache,
+        start: Option<&[u8]>
+    ) -> Result<(Self, Option<KeyValue>)> {
+        let mut iter = LevelIter::new(ss_tables, level, block_cache)?;
+        let first = Self::seek_after(&mut iter, start)?;
+
+        Ok((MergeSource::Level(iter), first))
+    }
+
+    /// 以Version中所有的SSTable构建数据源，以由新至旧排列，并定位至start之后
+    pub(crate) fn tables(
+        all_ss_tables: &'a [Vec<SSTable>],
+        block_cache: &'a BlockCache,
+        start: Option<&[u8]>
+    ) -> Result<Vec<(Self, Option<KeyValue>)>> {
+        let mut sources = Vec::new();
+
+        // Level 0中较新的SSTable优先
+        for ss_table in all_ss_tables[LEVEL_0].iter().rev() {
+            sources.push(Self::table(ss_table, block_cache, start)?);
+        }
+        for (level, ss_tables) in all_ss_tables.iter().enumerate().skip(1) {
+            if !ss_tables.is_empty() {
+                sources.push(Self::level(ss_tables, level, block_cache, start)?);
+            }
+        }
+
+        Ok(sources)
+    }
+
+    /// 定位至Key大于start的第一个元素，start为None时定位至第一个元素
+    fn seek_after<I, V>(iter: &mut I, start: Option<&[u8]>) -> Result<Option<KeyValue>>
+        where I: DiskIter<Vec<u8>, V, Item = KeyValue>
+    {
+        let item = Self::out_of_bounds_to_none(
+            iter.seek(start.map_or(Seek::First, Seek::Backward))
+        )?;
+
+        match (item, start) {
+            (Some((key, _)), Some(start)) if key.as_ref() == start => {
+                Self::out_of_bounds_to_none(iter.next_err())
+            }
+            (item, _) => Ok(item)
+        }
+    }
+
+    fn out_of_bounds_to_none(result: Result<KeyValue>) -> Result<Option<KeyValue>> {
+        match result {
+            Ok(item) => Ok(Some(item)),
+            Err(KernelError::OutOfBounds) => Ok(None),
+            Err(err) => Err(err)
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<KeyValue>> {
+        match self {
+            MergeSource::Mem(iter) => Ok(iter.next()),
+            MergeSource::Table(iter) => Self::out_of_bounds_to_none(iter.next_err()),
+            MergeSource::Level(iter) => Self::out_of_bounds_to_none(iter.next_err()),
+        }
+    }
+}
+
+/// 多个有序数据源的归并迭代器
+///
+/// 数据源以优先级由高至低排列(即由新至旧)，相同Key仅保留优先级最高的数据源中的数据，
+/// 删除标记同样会被返回，以便调用方判断该Key已被删除
+pub(crate) struct MergingIter<'a> {
+    sources: Vec<(MergeSource<'a>, Option<KeyValue>)>,
+}
+
+impl<'a> MergingIter<'a> {
+    /// sources中的元素为数据源与其当前的首个元素
+    pub(crate) fn new(sources: Vec<(MergeSource<'a>, Option<KeyValue>)>) -> Self {
+        MergingIter { sources }
+    }
+
+    pub(crate) fn next_err(&mut self) -> Result<Option<KeyValue>> {
+        // 相同Key时保留索引较小(优先级较高)的数据源
+        let min_index = self.sources.iter()
+            .enumerate()
+            .filter_map(|(index, (_, head))| head.as_ref().map(|(key, _)| (index, key)))
+            .min_by(|(index_a, key_a), (index_b, key_b)| {
+                key_a.cmp(key_b).then_with(|| index_a.cmp(index_b))
+            })
+            .map(|(index, _)| index);
+
+        let min_index = match min_index {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let item = self.sources[min_index].1.take();
+        let min_key = item.as_ref().map(|(key, _)| key.clone());
+
+        // 推进所有首个元素为该Key的数据源
+        for (index, (source, head)) in self.sources.iter_mut().enumerate() {
+            if index == min_index || head.as_ref().map(|(key, _)| key) == min_key.as_ref() {
+                *head = source.next()?;
+            }
+        }
+
+        Ok(item)
+    }
+}
@@ -2,6 +2,7 @@ pub(crate) mod block_iter;
 pub(crate) mod sstable_iter;
 pub(crate) mod level_iter;
 pub(crate) mod version_iter;
+pub(crate) mod merging_iter;
 
 use crate::kernel::Result;
 
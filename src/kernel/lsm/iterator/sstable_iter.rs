@@ -3,14 +3,16 @@ use crate::kernel::lsm::iterator::{DiskIter, Seek};
 use crate::kernel::lsm::iterator::block_iter::BlockIter;
 use crate::kernel::lsm::mem_table::KeyValue;
 use crate::kernel::lsm::ss_table::SSTable;
+use crate::kernel::lsm::stats::PerfContext;
 use crate::kernel::Result;
 use crate::KernelError;
 
 pub(crate) struct SSTableIter<'a> {
     ss_table: &'a SSTable,
-    data_iter: BlockIter<'a, Value>,
-    index_iter: BlockIter<'a, Index>,
-    block_cache: &'a BlockCache
+    data_iter: BlockIter<Value>,
+    index_iter: BlockIter<Index>,
+    block_cache: &'a BlockCache,
+    blocks_read: usize,
 }
 
 impl<'a> SSTableIter<'a> {
@@ -26,11 +28,12 @@ impl<'a> SSTableIter<'a> {
             ss_table,
             data_iter,
             index_iter,
-            block_cache
+            block_cache,
+            blocks_read: 1,
         })
     }
 
-    fn data_iter_init(ss_table: &'a SSTable, block_cache: &'a BlockCache, index: Index) -> Result<BlockIter<'a, Value>> {
+    fn data_iter_init(ss_table: &SSTable, block_cache: &BlockCache, index: Index) -> Result<BlockIter<Value>> {
         Ok(BlockIter::new(
             ss_table.get_data_block(index, block_cache)?
                 .ok_or(KernelError::DataEmpty)?
@@ -39,6 +42,7 @@ impl<'a> SSTableIter<'a> {
 
     fn data_iter_seek(&mut self, seek: Seek, index: Index) -> Result<KeyValue> {
         self.data_iter = Self::data_iter_init(self.ss_table, self.block_cache, index)?;
+        self.blocks_read += 1;
         self.data_iter.seek(seek).map(|(key, value)| (key, value.bytes))
     }
 
@@ -46,9 +50,34 @@ impl<'a> SSTableIter<'a> {
         self.ss_table.len()
     }
 
+    /// 向前跳过删除标记，定位至前方最近的未被删除的数据
+    ///
+    /// 当前DataBlock中不存在未被删除的数据时，通过IndexBlock定位至上一个DataBlock
+    pub(crate) fn prev_live(&mut self, skipped: &mut usize) -> Result<KeyValue> {
+        let mut result = self.data_iter.prev_live(skipped);
+
+        loop {
+            match result {
+                Ok((key, value)) => return Ok((key, value.bytes)),
+                Err(KernelError::OutOfBounds) => {
+                    let index = self.index_iter.prev_err()?.1;
+                    self.data_iter = Self::data_iter_init(self.ss_table, self.block_cache, index)?;
+                    self.blocks_read += 1;
+                    result = self.data_iter.last_live(skipped);
+                }
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
     pub(crate) fn get_gen(&self) -> i64 {
         self.ss_table.get_gen()
     }
+
+    /// 该迭代器读取的DataBlock数量
+    pub(crate) fn blocks_read(&self) -> usize {
+        self.blocks_read
+    }
 }
 
 impl DiskIter<Vec<u8>, Vec<u8>> for SSTableIter<'_> {
@@ -81,10 +110,20 @@ impl DiskIter<Vec<u8>, Vec<u8>> for SSTableIter<'_> {
     }
 
     fn seek(&mut self, seek: Seek) -> Result<Self::Item> {
+        PerfContext::record(|perf_context| perf_context.seek_count += 1);
         let index = self.index_iter.seek(
             if let Some(key) = seek.get_key() { Seek::Backward(key) } else { seek }
         )?.1;
-        self.data_iter_seek(seek, index)
+        let item = self.data_iter_seek(seek, index)?;
+
+        // IndexBlock的Key为分隔Key，介于Block的last_key与分隔Key之间的key会定位至该Block
+        // 此时Backward需要向后移动至下一个Block的首个Key
+        if let Seek::Backward(key) = seek {
+            if item.0.as_ref() < key {
+                return DiskIter::next_err(self);
+            }
+        }
+        Ok(item)
     }
 }
 
@@ -98,18 +137,17 @@ impl Iterator for SSTableIter<'_> {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::hash_map::RandomState;
     use bincode::Options;
     use bytes::Bytes;
     use tempfile::TempDir;
     use crate::kernel::io::{FileExtension, IoFactory};
+    use crate::kernel::lsm::block::BlockCache;
     use crate::kernel::lsm::lsm_kv::Config;
     use crate::kernel::lsm::ss_table::SSTable;
     use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
     use crate::kernel::Result;
     use crate::kernel::lsm::iterator::{DiskIter, Seek};
     use crate::kernel::lsm::iterator::sstable_iter::SSTableIter;
-    use crate::kernel::utils::lru_cache::ShardingLruCache;
 
     #[test]
     fn test_iterator() -> Result<()> {
@@ -145,11 +183,7 @@ mod tests {
             vec_data.clone(),
             0
         )?;
-        let cache = ShardingLruCache::new(
-            config.block_cache_size,
-            16,
-            RandomState::default()
-        )?;
+        let cache = BlockCache::new(&config)?;
 
         let mut iterator = SSTableIter::new(&ss_table, &cache)?;
 
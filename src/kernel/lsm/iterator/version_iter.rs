@@ -1,11 +1,13 @@
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull;
 use std::sync::Arc;
+use crate::kernel::io::FileAdvice;
 use crate::kernel::lsm::compactor::LEVEL_0;
 use crate::kernel::lsm::iterator::{DiskIter, Seek};
 use crate::kernel::lsm::iterator::level_iter::LevelIter;
 use crate::kernel::lsm::mem_table::KeyValue;
 use crate::kernel::lsm::ss_table::SSTable;
+use crate::kernel::lsm::stats::IterPerfContext;
 use crate::kernel::lsm::version::Version;
 use crate::kernel::Result;
 use crate::KernelError;
@@ -43,6 +45,9 @@ impl<T> DerefMut for InnerPtr<T> {
 ///
 /// Tips: VersionIter与其他迭代器有一个不同点：VersionIter不支持DiskIter
 /// 因为VersionIter中各个层级直接的数据是范围重复的，这导致无法实现Seek以支持良好的range查询
+///
+/// 迭代时会跳过删除标记，连续跳过的删除标记达到`max_sequential_skip`时，
+/// 不再逐条还原Key，而是直接定位至前方最近的未被删除的数据，以跳过大范围的删除数据
 pub struct VersionIter<'a> {
     // 该死的生命周期
     all_ss_tables: InnerPtr<Vec<Vec<SSTable>>>,
@@ -50,11 +55,15 @@ pub struct VersionIter<'a> {
 
     init_buf: Option<KeyValue>,
     offset: usize,
-    level_iter: LevelIter<'a>
+    level_iter: Option<LevelIter<'a>>,
+
+    max_sequential_skip: usize,
+    perf_context: IterPerfContext,
+    one_shot: bool,
 }
 
 impl<'a> VersionIter<'a> {
-    pub(crate) async fn new(version: Arc<Version>) -> Result<VersionIter<'a>> {
+    pub(crate) async fn new(version: Arc<Version>, max_sequential_skip: usize) -> Result<VersionIter<'a>> {
         let all_ss_tables: InnerPtr<Vec<Vec<SSTable>>> = InnerPtr(
             Box::leak(Box::new(
                 version.get_all_ss_tables().await
@@ -67,28 +76,57 @@ impl<'a> VersionIter<'a> {
             )).into()
         );
 
-        let mut level_iter = unsafe {
-            LevelIter::new(
-                &all_ss_tables.as_ref()[0], LEVEL_0, &version.0.as_ref().block_cache
-            )?
-        };
-        let init_buf = level_iter.seek(Seek::Last).ok();
-
-        Ok(Self {
+        let mut iter = Self {
             all_ss_tables,
-            offset: 0,
-            level_iter,
+            offset: LEVEL_0,
+            level_iter: None,
             version,
-            init_buf,
-        })
+            init_buf: None,
+            max_sequential_skip,
+            perf_context: IterPerfContext::default(),
+            one_shot: false,
+        };
+        iter.init_buf = iter.iter_sync(LEVEL_0, Seek::Last).ok();
+
+        Ok(iter)
     }
 
     fn is_valid(&self) -> bool {
         self.offset < 7
     }
 
-    fn iter_sync(&mut self, offset: usize, seek: Seek) -> Result<KeyValue> {
-        let is_level_eq = self.offset != offset;
+    /// 标记为一次性的大范围扫描
+    ///
+    /// 迭代器被释放时提示系统释放所扫描的SSTable的页缓存，避免一次性的扫描挤占热数据
+    #[inline]
+    pub fn one_shot(mut self) -> Self {
+        self.one_shot = true;
+        self
+    }
+
+    /// 该迭代器的性能上下文
+    #[inline]
+    pub fn perf_context(&self) -> IterPerfContext {
+        let mut perf_context = self.perf_context;
+
+        if let Some(level_iter) = &self.level_iter {
+            Self::merge_level_context(&mut perf_context, level_iter);
+        }
+        perf_context
+    }
+
+    fn merge_level_context(perf_context: &mut IterPerfContext, level_iter: &LevelIter) {
+        perf_context.tables_probed[level_iter.level()] += level_iter.tables_probed() as u64;
+        perf_context.blocks_read += level_iter.blocks_read() as u64;
+    }
+
+    /// 定位至offset及之后第一个非空的Level并进行seek
+    fn iter_sync(&mut self, mut offset: usize, seek: Seek) -> Result<KeyValue> {
+        // 跳过空的Level，LevelIter无法以空的SSTable集合进行构建
+        while offset < 7 && unsafe { self.all_ss_tables.as_ref()[offset].is_empty() } {
+            offset += 1;
+        }
+        let is_level_eq = self.offset != offset || self.level_iter.is_none();
         self.offset = offset;
 
         if !self.is_valid() {
@@ -96,15 +134,21 @@ impl<'a> VersionIter<'a> {
         }
 
         if is_level_eq {
-            unsafe {
-                self.level_iter = LevelIter::new(
+            let level_iter = unsafe {
+                LevelIter::new(
                     &self.all_ss_tables.as_ref()[offset],
                     offset,
                     &self.version.0.as_ref().block_cache
-                )?;
+                )?
+            };
+
+            if let Some(old_iter) = self.level_iter.replace(level_iter) {
+                Self::merge_level_context(&mut self.perf_context, &old_iter);
             }
         }
-        self.level_iter.seek(seek)
+        self.level_iter.as_mut()
+            .ok_or(KernelError::OutOfBounds)?
+            .seek(seek)
     }
 }
 
@@ -113,16 +157,40 @@ impl Iterator for VersionIter<'_> {
 
     fn next(&mut self) -> Option<Self::Item> {
         // 弹出初始化seek时的第一位数据
-        if let Some(item) = self.init_buf.take() {
-            return Some(item);
-        }
-
-        match self.level_iter.prev_err() {
-            Ok(item) => Some(item),
-            Err(KernelError::OutOfBounds) => {
-                self.iter_sync(self.offset + 1, Seek::Last).ok()
-            },
-            Err(_) => None
+        let mut result = match self.init_buf.take() {
+            Some(item) => Ok(item),
+            None => self.level_iter.as_mut()?.prev_err(),
+        };
+        let mut sequential_skip = 0;
+
+        loop {
+            match result {
+                Ok((_, None)) => {
+                    self.perf_context.keys_merged += 1;
+                    self.perf_context.skipped_deletions += 1;
+                    sequential_skip += 1;
+
+                    result = if sequential_skip >= self.max_sequential_skip {
+                        let mut skipped = 0;
+                        let live = self.level_iter.as_mut()?.prev_live(&mut skipped);
+
+                        self.perf_context.reseek_count += 1;
+                        self.perf_context.skipped_deletions += skipped as u64;
+                        self.perf_context.keys_merged += skipped as u64;
+                        live
+                    } else {
+                        self.level_iter.as_mut()?.prev_err()
+                    };
+                }
+                Ok(item) => {
+                    self.perf_context.keys_merged += 1;
+                    return Some(item)
+                },
+                Err(KernelError::OutOfBounds) if self.is_valid() => {
+                    result = self.iter_sync(self.offset + 1, Seek::Last);
+                },
+                Err(_) => return None
+            }
         }
     }
 }
@@ -130,6 +198,15 @@ impl Iterator for VersionIter<'_> {
 #[allow(clippy::drop_copy)]
 impl Drop for VersionIter<'_> {
     fn drop(&mut self) {
+        if self.one_shot {
+            // 提示仅影响性能，失败时忽略
+            unsafe { self.all_ss_tables.as_ref() }
+                .iter()
+                .flatten()
+                .for_each(|ss_table| {
+                    let _ignore = ss_table.advise(FileAdvice::DontNeed);
+                });
+        }
         drop(self.all_ss_tables.as_ptr());
         drop(self.version.as_ptr());
     }
@@ -191,6 +268,50 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_skip_deletions() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path().to_str().unwrap())
+                .wal_enable(false)
+                .max_sequential_skip(4);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            for i in 0..100u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from(i.to_be_bytes().to_vec())).await?;
+            }
+            // 删除中间连续的大范围数据
+            for i in 10..90u32 {
+                kv_store.remove(&i.to_be_bytes()).await?;
+            }
+            kv_store.flush().await?;
+
+            let mut iterator = kv_store.disk_iter().await?.one_shot();
+            let keys = iterator.by_ref()
+                .map(|(key, _)| key)
+                .collect_vec();
+            let live_keys = (0..10u32).chain(90..100)
+                .rev()
+                .map(|i| Bytes::from(i.to_be_bytes().to_vec()))
+                .collect_vec();
+
+            assert_eq!(keys, live_keys);
+            let perf_context = iterator.perf_context();
+            assert_eq!(perf_context.skipped_deletions, 80);
+            assert!(perf_context.reseek_count > 0);
+            assert_eq!(perf_context.keys_merged, 100);
+            assert_eq!(perf_context.total_tables_probed(), 1);
+            assert!(perf_context.blocks_read > 0);
+
+            // 释放页缓存后数据依旧可读
+            drop(iterator);
+            assert_eq!(kv_store.get(&99u32.to_be_bytes()).await?, Some(Bytes::from(99u32.to_be_bytes().to_vec())));
+
+            Ok(())
+        })
+    }
+
     fn kv_trans(kv: (Vec<u8>, Vec<u8>)) -> KeyValue {
         let (key, value) = kv;
         (Bytes::from(key), Some(Bytes::from(value)))
@@ -0,0 +1,184 @@
+//! Key空间的通知，用于缓存层等订阅方感知Key的变化，并区分Key因删除还是过期而失效
+//!
+//! ```ignore
+//! let mut rx = kv_store.subscribe_keyspace();
+//! while let Ok(event) = rx.recv().await {
+//!     match event.kind {
+//!         KeyspaceEventKind::Expired => cache.evict_expired(&event.key),
+//!         _ => cache.invalidate(&event.key),
+//!     }
+//! }
+//! ```
+use std::collections::HashMap;
+use bytes::Bytes;
+use parking_lot::RwLock;
+use tokio::sync::broadcast;
+use crate::kernel::lsm::chunk::INTERNAL_KEY_PREFIX;
+use crate::kernel::lsm::column_family;
+use crate::kernel::lsm::mem_table::KeyValue;
+
+/// 订阅方未及时接收时可缓冲的事件数量，超出时订阅方收到`RecvError::Lagged`
+pub(crate) const DEFAULT_KEYSPACE_EVENT_CAPACITY: usize = 1024;
+
+/// Key空间的事件，不包括租约、锁与列族元数据等内部Key
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeyspaceEvent {
+    pub kind: KeyspaceEventKind,
+    /// Key所属列族的名称，不属于列族时为None
+    pub column_family: Option<Bytes>,
+    /// 列族中的Key不包含列族的前缀
+    pub key: Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyspaceEventKind {
+    /// Key被写入，于写入可见后发布
+    Set,
+    /// Key被显式删除，于删除可见后发布
+    Deleted,
+    /// 设置了TTL的列族中的Key过期
+    ///
+    /// 于过期的数据在压缩中被丢弃时发布，因此晚于其到期时间，
+    /// 且仅当该数据仍为Key的最新数据时发布，过期后被重新写入或删除的Key不会发布
+    Expired,
+}
+
+pub(crate) struct KeyspaceEvents {
+    tx: broadcast::Sender<KeyspaceEvent>,
+    /// 已打开的列族的Key前缀 -> 列族名称
+    column_families: RwLock<HashMap<Bytes, Bytes>>,
+}
+
+impl KeyspaceEvents {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+
+        KeyspaceEvents { tx, column_families: RwLock::new(HashMap::new()) }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<KeyspaceEvent> {
+        self.tx.subscribe()
+    }
+
+    /// 存在订阅方时返回true，无订阅方时无需收集事件
+    pub(crate) fn is_subscribed(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
+
+    /// 记录列族的Key前缀与名称，使其中的事件附带列族名称发布
+    pub(crate) fn bind_column_family(&self, prefix: &Bytes, name: &[u8]) {
+        let _ = self.column_families.write()
+            .insert(prefix.clone(), Bytes::copy_from_slice(name));
+    }
+
+    pub(crate) fn unbind_column_family(&self, prefix: &[u8]) {
+        let _ = self.column_families.write().remove(prefix);
+    }
+
+    /// 发布写入的事件，value为None时表示删除
+    pub(crate) fn publish_writes<'a>(&self, writes: impl IntoIterator<Item = &'a KeyValue>) {
+        if !self.is_subscribed() {
+            return;
+        }
+        for (key, value) in writes {
+            let kind = if value.is_some() { KeyspaceEventKind::Set } else { KeyspaceEventKind::Deleted };
+            self.publish(kind, key);
+        }
+    }
+
+    pub(crate) fn publish_expired<'a>(&self, keys: impl IntoIterator<Item = &'a Bytes>) {
+        if !self.is_subscribed() {
+            return;
+        }
+        for key in keys {
+            self.publish(KeyspaceEventKind::Expired, key);
+        }
+    }
+
+    /// 列族中的Key需其列族在此次打开后被获取过才会发布，内部Key不会发布
+    fn publish(&self, kind: KeyspaceEventKind, key: &Bytes) {
+        let event = match column_family::split_cf_key(key) {
+            Some((prefix, cf_key)) => {
+                let Some(name) = self.column_families.read().get(prefix).cloned() else {
+                    return;
+                };
+                KeyspaceEvent { kind, column_family: Some(name), key: key.slice_ref(cf_key) }
+            }
+            None if key.starts_with(INTERNAL_KEY_PREFIX) => return,
+            None => KeyspaceEvent { kind, column_family: None, key: key.clone() },
+        };
+        // 发送时订阅方可能均已退出，此时丢弃事件
+        let _ignore = self.tx.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::column_family::ColumnFamilyOptions;
+    use crate::kernel::lsm::keyspace::{KeyspaceEvent, KeyspaceEventKind};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::utils::clock::VirtualClock;
+    use crate::kernel::Result;
+
+    fn event(kind: KeyspaceEventKind, column_family: Option<&'static [u8]>, key: &'static [u8]) -> KeyspaceEvent {
+        KeyspaceEvent {
+            kind,
+            column_family: column_family.map(Bytes::from_static),
+            key: Bytes::from_static(key),
+        }
+    }
+
+    #[test]
+    fn test_keyspace_events() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let config = Config::new(temp_dir.path())
+                .major_threshold_with_sst_size(1)
+                .clock(Arc::new(clock.clone()));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let mut rx = kv_store.subscribe_keyspace();
+
+            // 租约等内部Key不会发布
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            let _ = kv_store.leases().grant(Duration::from_secs(1)).await?;
+            kv_store.remove(b"k1").await?;
+            assert_eq!(rx.try_recv().ok(), Some(event(KeyspaceEventKind::Set, None, b"k1")));
+            assert_eq!(rx.try_recv().ok(), Some(event(KeyspaceEventKind::Deleted, None, b"k1")));
+            assert!(rx.try_recv().is_err());
+
+            let options = ColumnFamilyOptions::default().ttl(Duration::from_secs(60));
+            let sessions = kv_store.column_families().create_with_options(b"sessions", options).await?;
+            sessions.set(b"1", Bytes::from_static(b"kip")).await?;
+            sessions.set(b"2", Bytes::from_static(b"kip")).await?;
+            kv_store.flush().await?;
+            clock.advance(Duration::from_secs(61));
+
+            // 过期后被重新写入的Key不会发布过期
+            sessions.set(b"2", Bytes::from_static(b"db")).await?;
+            kv_store.flush().await?;
+            sessions.set(b"3", Bytes::from_static(b"db")).await?;
+            kv_store.flush().await?;
+
+            let mut events = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                events.push(event);
+            }
+            assert_eq!(events.iter().filter(|event| event.kind == KeyspaceEventKind::Set).count(), 4);
+            assert_eq!(
+                events.into_iter().filter(|event| event.kind == KeyspaceEventKind::Expired).collect::<Vec<_>>(),
+                vec![event(KeyspaceEventKind::Expired, Some(b"sessions"), b"1")]
+            );
+
+            Ok(())
+        })
+    }
+}
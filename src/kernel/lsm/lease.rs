@@ -0,0 +1,244 @@
+use std::time::Duration;
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::time;
+use crate::kernel::lsm::lsm_kv::{Gen, LsmStore};
+use crate::kernel::Result;
+use crate::KernelError;
+
+const LEASE_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-Lease\x00";
+
+const LOCK_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-Lock\x00";
+
+/// `lock`等待锁释放时的重试间隔
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 租约，在TTL内未续约时过期
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lease {
+    pub id: i64,
+    pub ttl: Duration,
+}
+
+/// 租约与分布式锁的视图，通过`LsmStore::leases`获取
+///
+/// 与etcd相似，锁由租约持有，租约过期或被撤销后其持有的锁自动失效，可被其他租约获取，
+/// 因此持有者异常退出时无需等待人工释放
+///
+/// 租约与锁均以内部Key持久化，租约的到期时间以`Config::clock`的时间记录，因此重启后仍然有效
+///
+/// Tips: 过期的判断依赖各调用方所在节点的系统时间，仅适用于粗粒度的协调，
+/// 持有者需在租约过期前续约，且不应假设过期后的操作仍受锁的保护
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy)]
+pub struct Leases<'a> {
+    kv_store: &'a LsmStore,
+}
+
+impl<'a> Leases<'a> {
+    pub(crate) fn new(kv_store: &'a LsmStore) -> Self {
+        Leases { kv_store }
+    }
+
+    /// 创建TTL为ttl的租约
+    #[inline]
+    pub async fn grant(&self, ttl: Duration) -> Result<Lease> {
+        let lease = Lease { id: Gen::create(), ttl };
+        self.put_lease(&lease).await?;
+
+        Ok(lease)
+    }
+
+    /// 续约，使租约由当前起重新计算TTL，返回false时表示租约已过期或不存在
+    #[inline]
+    pub async fn keep_alive(&self, id: i64) -> Result<bool> {
+        let lease_key = lease_key(id);
+        let _guard = self.kv_store.latches.lock(&lease_key).await;
+
+        match self.get_lease(id).await? {
+            Some((deadline, ttl)) if deadline > self.now_millis() => {
+                self.put_lease(&Lease { id, ttl }).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// 撤销租约，其持有的锁随之失效
+    #[inline]
+    pub async fn revoke(&self, id: i64) -> Result<()> {
+        let lease_key = lease_key(id);
+        let _guard = self.kv_store.latches.lock(&lease_key).await;
+
+        let _ = self.kv_store.append_cmd_data((lease_key, None), None).await?;
+
+        Ok(())
+    }
+
+    /// 租约的剩余时间，租约已过期或不存在时返回None
+    #[inline]
+    pub async fn time_to_live(&self, id: i64) -> Result<Option<Duration>> {
+        Ok(self.get_lease(id).await?
+            .map(|(deadline, _)| deadline - self.now_millis())
+            .filter(|remaining| *remaining > 0)
+            .map(|remaining| Duration::from_millis(remaining as u64)))
+    }
+
+    /// 尝试以租约获取锁，返回false时表示锁已被其他有效的租约持有
+    ///
+    /// 已由该租约持有的锁可重复获取，租约已过期或不存在时返回`KernelError::LeaseExpired`
+    #[inline]
+    pub async fn try_lock(&self, name: &[u8], lease_id: i64) -> Result<bool> {
+        let lock_key = lock_key(name);
+        let _guard = self.kv_store.latches.lock(&lock_key).await;
+
+        if self.time_to_live(lease_id).await?.is_none() {
+            return Err(KernelError::LeaseExpired(lease_id));
+        }
+        if let Some(holder) = self.lock_holder(name).await? {
+            if holder != lease_id {
+                return Ok(false);
+            }
+        }
+        let _ = self.kv_store.append_cmd_data(
+            (lock_key, Some(Bytes::copy_from_slice(&lease_id.to_be_bytes()))),
+            None
+        ).await?;
+
+        Ok(true)
+    }
+
+    /// 以租约获取锁，锁被其他租约持有时等待至其释放或过期
+    #[inline]
+    pub async fn lock(&self, name: &[u8], lease_id: i64) -> Result<()> {
+        while !self.try_lock(name, lease_id).await? {
+            time::sleep(LOCK_RETRY_INTERVAL).await;
+        }
+
+        Ok(())
+    }
+
+    /// 释放租约持有的锁，返回false时表示该锁并非由此租约持有
+    #[inline]
+    pub async fn unlock(&self, name: &[u8], lease_id: i64) -> Result<bool> {
+        let lock_key = lock_key(name);
+        let _guard = self.kv_store.latches.lock(&lock_key).await;
+
+        if self.raw_holder(&lock_key).await? != Some(lease_id) {
+            return Ok(false);
+        }
+        let _ = self.kv_store.append_cmd_data((lock_key, None), None).await?;
+
+        Ok(true)
+    }
+
+    /// 持有锁的有效租约，锁未被持有或其租约已失效时返回None
+    #[inline]
+    pub async fn lock_holder(&self, name: &[u8]) -> Result<Option<i64>> {
+        match self.raw_holder(&lock_key(name)).await? {
+            Some(holder) if self.time_to_live(holder).await?.is_some() => Ok(Some(holder)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn raw_holder(&self, lock_key: &[u8]) -> Result<Option<i64>> {
+        self.kv_store.get_local(lock_key).await?
+            .map(|bytes| decode_i64(&bytes))
+            .transpose()
+    }
+
+    /// 以当前时间 + TTL作为到期时间写入租约
+    async fn put_lease(&self, lease: &Lease) -> Result<()> {
+        let ttl_millis = lease.ttl.as_millis() as i64;
+        let mut value = BytesMut::with_capacity(16);
+        value.put_i64(self.now_millis() + ttl_millis);
+        value.put_i64(ttl_millis);
+
+        let _ = self.kv_store.append_cmd_data((lease_key(lease.id), Some(value.freeze())), None).await?;
+
+        Ok(())
+    }
+
+    fn now_millis(&self) -> i64 {
+        self.kv_store.config().clock.now_millis()
+    }
+
+    /// 返回租约的到期时间(毫秒)与TTL
+    async fn get_lease(&self, id: i64) -> Result<Option<(i64, Duration)>> {
+        let Some(bytes) = self.kv_store.get_local(&lease_key(id)).await? else {
+            return Ok(None);
+        };
+        let (deadline, ttl) = bytes.split_at_checked(8)
+            .ok_or(KernelError::ValueNotNumeric)?;
+
+        Ok(Some((decode_i64(deadline)?, Duration::from_millis(decode_i64(ttl)? as u64))))
+    }
+}
+
+fn lease_key(id: i64) -> Bytes {
+    let mut key = BytesMut::with_capacity(LEASE_KEY_PREFIX.len() + 8);
+    key.put_slice(LEASE_KEY_PREFIX);
+    key.put_i64(id);
+
+    key.freeze()
+}
+
+fn lock_key(name: &[u8]) -> Bytes {
+    let mut key = BytesMut::with_capacity(LOCK_KEY_PREFIX.len() + name.len());
+    key.put_slice(LOCK_KEY_PREFIX);
+    key.put_slice(name);
+
+    key.freeze()
+}
+
+fn decode_i64(bytes: &[u8]) -> Result<i64> {
+    <[u8; 8]>::try_from(bytes)
+        .map(i64::from_be_bytes)
+        .map_err(|_| KernelError::ValueNotNumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::utils::clock::VirtualClock;
+    use crate::kernel::Result;
+    use crate::KernelError;
+
+    #[test]
+    fn test_lease_lock() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let config = Config::new(temp_dir.path())
+                .clock(Arc::new(clock.clone()));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let leases = kv_store.leases();
+
+            let lease_1 = leases.grant(Duration::from_millis(200)).await?;
+            let lease_2 = leases.grant(Duration::from_secs(10)).await?;
+            assert!(leases.time_to_live(lease_1.id).await?.is_some());
+
+            assert!(leases.try_lock(b"job", lease_1.id).await?);
+            assert!(leases.try_lock(b"job", lease_1.id).await?);
+            assert!(!leases.try_lock(b"job", lease_2.id).await?);
+            assert!(!leases.unlock(b"job", lease_2.id).await?);
+            assert_eq!(leases.lock_holder(b"job").await?, Some(lease_1.id));
+
+            // 租约过期后，其持有的锁可被其他租约获取
+            clock.advance(Duration::from_millis(300));
+            assert!(!leases.keep_alive(lease_1.id).await?);
+            assert_eq!(leases.lock_holder(b"job").await?, None);
+            leases.lock(b"job", lease_2.id).await?;
+            assert!(matches!(leases.try_lock(b"job", lease_1.id).await, Err(KernelError::LeaseExpired(_))));
+
+            // 撤销租约后锁随之失效
+            leases.revoke(lease_2.id).await?;
+            assert_eq!(leases.lock_holder(b"job").await?, None);
+
+            Ok(())
+        })
+    }
+}
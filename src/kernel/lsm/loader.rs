@@ -0,0 +1,123 @@
+use std::fmt::Debug;
+use async_trait::async_trait;
+use bytes::Bytes;
+use crate::kernel::Result;
+
+/// 外部数据源的加载器，使Store作为该数据源(如旧有的数据库)的持久化读穿缓存
+///
+/// 通过`Config::cache_loader`设置后，`get`在本地不存在该Key时由`load`读取，
+/// 读取到的数据写入本地，此后的读取不再访问数据源
+///
+/// Tips: `get`与`compare_and_swap`、`fetch_update`、`increment`等读-改-写操作会读穿，
+/// 事务、范围扫描、时间点读取以及列族、数据结构与租约的元数据仅读取本地的数据
+#[async_trait]
+pub trait CacheLoader: Debug + Send + Sync {
+    /// 从数据源读取Key，返回None时表示数据源中同样不存在，此时不会写入本地
+    async fn load(&self, key: &[u8]) -> Result<Option<Bytes>>;
+
+    /// 写穿: `set`与`remove`在写入本地前调用，value为None时表示删除，返回错误时本地不会写入
+    ///
+    /// 默认不写入数据源，此时本地删除的Key会在下一次读取时重新由数据源加载
+    #[inline]
+    async fn write(&self, _key: &[u8], _value: Option<&[u8]>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+    use crate::kernel::lsm::loader::CacheLoader;
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::{KVStore, Result};
+
+    #[derive(Debug, Default)]
+    struct LegacyDb {
+        data: Mutex<HashMap<Vec<u8>, Bytes>>,
+        loads: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl CacheLoader for LegacyDb {
+        async fn load(&self, key: &[u8]) -> Result<Option<Bytes>> {
+            let _ = self.loads.fetch_add(1, Ordering::Relaxed);
+
+            Ok(self.data.lock().get(key).cloned())
+        }
+
+        async fn write(&self, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+            let _ = match value {
+                Some(value) => self.data.lock().insert(key.to_vec(), Bytes::copy_from_slice(value)),
+                None => self.data.lock().remove(key),
+            };
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cache_loader() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let legacy_db = Arc::new(LegacyDb::default());
+            let _ = legacy_db.data.lock().insert(b"k1".to_vec(), Bytes::from_static(b"v1"));
+            let loader: Arc<dyn CacheLoader> = Arc::<LegacyDb>::clone(&legacy_db);
+            let config = Config::new(temp_dir.path()).cache_loader(loader);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            // 首次读取由数据源加载并写入本地，此后不再访问数据源
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k2").await?, None);
+            assert_eq!(legacy_db.loads.load(Ordering::Relaxed), 2);
+
+            // 写穿至数据源
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            assert_eq!(legacy_db.data.lock().get(b"k2".as_slice()), Some(&Bytes::from_static(b"v2")));
+            kv_store.remove(b"k1").await?;
+            assert!(legacy_db.data.lock().get(b"k1".as_slice()).is_none());
+            assert_eq!(kv_store.get(b"k1").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_cache_loader_with_latch() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let legacy_db = Arc::new(LegacyDb::default());
+            let _ = legacy_db.data.lock().insert(b"k1".to_vec(), Bytes::from_static(b"v1"));
+            let loader: Arc<dyn CacheLoader> = Arc::<LegacyDb>::clone(&legacy_db);
+            let config = Config::new(temp_dir.path()).cache_loader(loader);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            // 持有Latch的操作在读穿时不会再次获取Latch
+            let latched = async {
+                assert!(kv_store.compare_and_swap(b"absent", None, Some(Bytes::from_static(b"v"))).await?);
+                // 数据源中存在的Key同样对条件写入可见
+                assert!(!kv_store.compare_and_swap(b"k1", None, Some(Bytes::from_static(b"v"))).await?);
+                assert_eq!(kv_store.increment(b"counter", 1).await?, 1);
+                let _ = kv_store.column_families().create(b"cf").await?;
+                assert!(kv_store.column_families().drop(b"cf").await?);
+                let _ = kv_store.structures().set_add(b"set", &[b"m1"]).await?;
+
+                Ok::<_, crate::KernelError>(())
+            };
+            tokio::time::timeout(Duration::from_secs(3), latched).await
+                .expect("latched operations deadlocked with the cache loader")?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            Ok(())
+        })
+    }
+}
@@ -1,24 +1,101 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Debug, Formatter};
+use std::fs;
 use std::io::{Cursor, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use bytes::Bytes;
 use itertools::Itertools;
 use parking_lot::Mutex;
+use tracing::error;
 use crate::kernel::{Result, sorted_gen_list};
 use crate::kernel::io::{FileExtension, IoFactory, IoType, IoWriter};
 use crate::kernel::io::IoReader;
+use crate::kernel::lsm::InlineKey;
 use crate::kernel::lsm::block::{Entry, Value};
-use crate::kernel::lsm::lsm_kv::{Config, Gen};
+use crate::kernel::lsm::lsm_kv::{Config, Gen, Sequence};
 use crate::kernel::lsm::mem_table::KeyValue;
+use crate::KernelError;
+
+/// WAL中的Entry不进行前缀压缩，因此以shared_len标记记录的类型
+const RECORD_DATA: usize = 0;
+/// 预提交的批量数据，Key为凭证，Value为编码后的批量数据
+const RECORD_PREPARE: usize = 1;
+/// 提交标记，Key为凭证，其后紧跟该批量数据的普通记录
+const RECORD_COMMIT: usize = 2;
+/// 回滚标记，Key为凭证
+const RECORD_ROLLBACK: usize = 3;
+/// Sequence标记，Key为其后紧跟的写入所分配的Sequence，重放时以此恢复Sequence
+const RECORD_SEQUENCE: usize = 4;
+
+/// 凭证 -> 预提交的批量数据
+type PreparedBatches = BTreeMap<i64, Vec<KeyValue>>;
+
+type ArchiveFn = dyn Fn(i64, &Path) -> Result<()> + Send + Sync;
+
+/// WAL归档回调
+///
+/// WAL文件被删除(或回收)前，以其所属的Gen与文件路径按写入顺序逐个调用，
+/// 可用于CDC或备份等需要消费WAL的场景，返回错误时保留该Gen的文件并在下次清理时重试
+#[derive(Clone)]
+pub struct WalArchiver {
+    archive_fn: Arc<ArchiveFn>,
+}
+
+impl WalArchiver {
+    #[inline]
+    pub fn new<F>(archive_fn: F) -> Self
+        where F: Fn(i64, &Path) -> Result<()> + Send + Sync + 'static
+    {
+        WalArchiver { archive_fn: Arc::new(archive_fn) }
+    }
+}
 
+impl Debug for WalArchiver {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WalArchiver").finish()
+    }
+}
+
+/// 日志
+///
+/// 每个Gen的日志以`{gen}.{extension}`作为首个分段，
+/// 开启分段大小上限时，超出上限后的分段存储于`{gen}/`目录下，并以分段创建时的下一个Sequence命名，
+/// 即分段内数据的Sequence均不小于其文件名
 pub(crate) struct LogLoader {
     factory: IoFactory,
     config: Config,
+    dir_path: PathBuf,
+    extension: FileExtension,
+    log_type: IoType,
+    /// 单个分段的大小上限，为None时不分段
+    segment_size: Option<usize>,
+    archiver: Option<WalArchiver>,
     inner: Mutex<Inner>,
+    /// 已写入的日志次数，作为每次写入的编号
+    written: AtomicU64,
+    /// 已同步至磁盘的日志次数
+    synced: AtomicU64,
+    /// 组同步的领导者锁，同一时刻仅有一个写入方进行同步
+    sync_lock: tokio::sync::Mutex<()>,
+    /// 当前Gen的各分段已写入的大小之和
+    gen_len: AtomicUsize,
 }
 
 struct Inner {
     current_gen: i64,
     writer: Box<dyn IoWriter>,
-    vec_gen: VecDeque<i64>
+    vec_gen: VecDeque<i64>,
+    /// 当前分段已写入的大小
+    segment_len: usize,
+    /// 当前分段的Sequence，为None时当前分段为首个分段
+    segment_seq: Option<i64>,
+    /// 已预提交但尚未提交或回滚的批量数据
+    prepared: PreparedBatches,
 }
 
 impl LogLoader {
@@ -39,7 +116,8 @@ impl LogLoader {
         Ok((loader, reload_data))
     }
 
-    fn reload_(
+    /// 重新载入日志但不读取数据，返回最后一个Gen，其数据可稍后通过`load_with_progress`读取
+    pub(crate) fn reload_(
         config: Config,
         path_name: &str,
         extension: FileExtension,
@@ -50,7 +128,8 @@ impl LogLoader {
         let factory = IoFactory::new(
             wal_path.clone(),
             extension
-        )?;
+        )?.write_buffer_size(config.wal_buffer_size)
+            .fault_injector(config.fault_injector.clone());
 
         let vec_gen = VecDeque::from_iter(
             sorted_gen_list(&wal_path, extension)?
@@ -58,96 +137,480 @@ impl LogLoader {
         let last_gen = vec_gen.back()
             .cloned()
             .unwrap_or(Gen::create());
+        // 继续写入最后一个分段
+        let segment_seq = Self::segment_seqs_(&wal_path, extension, last_gen)?
+            .pop();
+        let writer = match segment_seq {
+            Some(seq) => Self::segment_factory_(&wal_path, extension, &config, last_gen)?
+                .writer(seq, log_type)?,
+            None => factory.writer(last_gen, log_type)?,
+        };
 
         let inner = Mutex::new(
             Inner {
                 current_gen: last_gen,
-                writer: factory.writer(last_gen, log_type)?,
+                writer,
                 vec_gen,
+                segment_len: 0,
+                segment_seq,
+                prepared: BTreeMap::new(),
             }
         );
 
         Ok((LogLoader {
             factory,
             config,
+            dir_path: wal_path,
+            extension,
+            log_type,
+            segment_size: None,
+            archiver: None,
             inner,
+            written: AtomicU64::new(0),
+            synced: AtomicU64::new(0),
+            sync_lock: tokio::sync::Mutex::new(()),
+            gen_len: AtomicUsize::new(0),
         }, last_gen))
     }
 
-    pub(crate) fn log(&self, data: KeyValue) -> Result<()> {
+    /// 设置单个分段的大小上限，写入后分段大小达到上限时切换至新的分段
+    pub(crate) fn segment_size(mut self, segment_size: Option<usize>) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+
+    pub(crate) fn archiver(mut self, archiver: Option<WalArchiver>) -> Self {
+        self.archiver = archiver;
+        self
+    }
+
+    /// 分配Sequence并与数据一同写入日志，返回该次写入的编号(用于`sync_until`)与Sequence
+    ///
+    /// Sequence在日志的锁内分配，使日志中的写入顺序与Sequence的顺序一致
+    pub(crate) fn log_with_sequence(&self, data: KeyValue) -> Result<(u64, i64)> {
         let bytes = Self::data_to_bytes(data)?;
 
-        let _ = self.inner.lock()
-            .writer.io_write(bytes)?;
+        let mut guard = self.inner.lock();
+        let seq_id = Sequence::create();
+        let mut record = Self::sequence_to_bytes(seq_id)?;
+        record.extend(bytes);
+        let (_, len) = guard.writer.io_write(record)?;
+        let ticket = self.written.fetch_add(1, Ordering::AcqRel) + 1;
+        self.rotate_if_full(&mut guard, len)?;
+
+        Ok((ticket, seq_id))
+    }
+
+    /// 当前分段达到大小上限时切换至新的分段
+    fn rotate_if_full(&self, inner: &mut Inner, written_len: usize) -> Result<()> {
+        inner.segment_len += written_len;
+        let _ = self.gen_len.fetch_add(written_len, Ordering::Relaxed);
+
+        let Some(segment_size) = self.segment_size else {
+            return Ok(());
+        };
+        if inner.segment_len < segment_size {
+            return Ok(());
+        }
+        // 同步旧分段，使组同步仅需同步当前分段
+        inner.writer.io_sync()?;
+        let _ = self.mark_synced();
+
+        // 未有新数据分配Sequence时，以上一分段的Sequence递增避免重名
+        let next_seq = inner.segment_seq
+            .map_or(Sequence::latest() + 1, |seq| (seq + 1).max(Sequence::latest() + 1));
+        let segment_factory = self.segment_factory(inner.current_gen)?;
+        let writer = segment_factory.writer(next_seq, self.log_type)?;
+        writer.preallocate(segment_size.min(self.config.wal_preallocate_size) as u64)?;
+        segment_factory.sync_dir()?;
+
+        inner.writer = writer;
+        inner.segment_len = 0;
+        inner.segment_seq = Some(next_seq);
+
+        Ok(())
+    }
+
+    fn segment_factory(&self, gen: i64) -> Result<IoFactory> {
+        Self::segment_factory_(&self.dir_path, self.extension, &self.config, gen)
+    }
+
+    fn segment_factory_(dir_path: &Path, extension: FileExtension, config: &Config, gen: i64) -> Result<IoFactory> {
+        Ok(IoFactory::new(dir_path.join(gen.to_string()), extension)?
+            .write_buffer_size(config.wal_buffer_size)
+            .fault_injector(config.fault_injector.clone()))
+    }
+
+    /// 该Gen除首个分段外的各分段的Sequence，以写入顺序排列
+    fn segment_seqs_(dir_path: &Path, extension: FileExtension, gen: i64) -> Result<Vec<i64>> {
+        let segment_path = dir_path.join(gen.to_string());
+
+        if !fs::try_exists(&segment_path)? {
+            return Ok(Vec::new());
+        }
+        sorted_gen_list(&segment_path, extension)
+    }
+
+    /// 该Gen的所有分段文件路径，以写入顺序排列
+    fn segment_paths(&self, gen: i64) -> Result<Vec<PathBuf>> {
+        let segment_path = self.dir_path.join(gen.to_string());
+
+        Ok(iter::once(self.extension.path_with_gen(&self.dir_path, gen))
+            .chain(
+                Self::segment_seqs_(&self.dir_path, self.extension, gen)?
+                    .into_iter()
+                    .map(|seq| self.extension.path_with_gen(&segment_path, seq))
+            )
+            .collect_vec())
+    }
+
+    /// 删除前对该Gen的所有分段进行归档
+    fn archive(&self, gen: i64) -> Result<()> {
+        if let Some(WalArchiver { archive_fn }) = &self.archiver {
+            for path in self.segment_paths(gen)? {
+                if fs::try_exists(&path)? {
+                    archive_fn(gen, &path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 删除该Gen首个分段之外的分段
+    fn clean_segments(&self, gen: i64) -> Result<()> {
+        let segment_path = self.dir_path.join(gen.to_string());
+
+        if fs::try_exists(&segment_path)? {
+            fs::remove_dir_all(segment_path)?;
+            self.factory.sync_dir()?;
+        }
+
         Ok(())
     }
 
+    fn record_to_bytes(record: usize, key: &[u8], value: Option<Bytes>) -> Result<Vec<u8>> {
+        Entry::new(record, key.len(), InlineKey::from_slice(key), Value::from(value)).encode()
+    }
+
+    fn sequence_to_bytes(seq_id: i64) -> Result<Vec<u8>> {
+        Self::record_to_bytes(RECORD_SEQUENCE, &seq_id.to_be_bytes(), None)
+    }
+
     fn data_to_bytes(data: KeyValue) -> Result<Vec<u8>> {
         let (key, value) = data;
-        Entry::new(0, key.len(), key, Value::from(value)).encode()
+        Self::record_to_bytes(RECORD_DATA, &key, value)
     }
 
-    pub(crate) fn log_batch(&self, vec_data: Vec<KeyValue>) -> Result<()> {
-        let bytes = vec_data.into_iter()
+    fn batch_to_bytes(vec_data: Vec<KeyValue>) -> Vec<u8> {
+        vec_data.into_iter()
             .filter_map(|data| Self::data_to_bytes(data).ok())
             .flatten()
-            .collect_vec();
+            .collect_vec()
+    }
+
+    fn prepare_to_bytes(token: i64, vec_data: Vec<KeyValue>) -> Result<Vec<u8>> {
+        Self::record_to_bytes(
+            RECORD_PREPARE,
+            &token.to_be_bytes(),
+            Some(Bytes::from(Self::batch_to_bytes(vec_data)))
+        )
+    }
+
+    /// 写入并刷新至文件，返回该次写入的编号
+    fn append(&self, inner: &mut Inner, bytes: Vec<u8>) -> Result<u64> {
+        let (_, len) = inner.writer.io_write(bytes)?;
+        inner.writer.flush()?;
+        let ticket = self.written.fetch_add(1, Ordering::AcqRel) + 1;
+        self.rotate_if_full(inner, len)?;
+
+        Ok(ticket)
+    }
+
+    /// 批量写入日志，返回该次写入的编号，用于`sync_until`
+    pub(crate) fn log_batch(&self, vec_data: Vec<KeyValue>) -> Result<u64> {
+        let bytes = Self::batch_to_bytes(vec_data);
+
+        self.append(&mut self.inner.lock(), bytes)
+    }
+
+    /// 分配Sequence并与批量数据一同写入日志，返回该次写入的编号与Sequence
+    pub(crate) fn log_batch_with_sequence(&self, vec_data: Vec<KeyValue>) -> Result<(u64, i64)> {
+        let bytes = Self::batch_to_bytes(vec_data);
 
         let mut guard = self.inner.lock();
-        let _ = guard.writer.io_write(bytes)?;
-        guard.writer.flush()?;
-        Ok(())
+        let seq_id = Sequence::create();
+        let mut record = Self::sequence_to_bytes(seq_id)?;
+        record.extend(bytes);
+
+        Ok((self.append(&mut guard, record)?, seq_id))
+    }
+
+    /// 写入预提交的批量数据，返回该次写入的编号
+    ///
+    /// 预提交的数据在提交前不会被重放，且切换Gen时会被重新写入新的日志，直至提交或回滚
+    pub(crate) fn prepare(&self, token: i64, vec_data: Vec<KeyValue>) -> Result<u64> {
+        let bytes = Self::prepare_to_bytes(token, vec_data.clone())?;
+
+        let mut guard = self.inner.lock();
+        let ticket = self.append(&mut guard, bytes)?;
+        let _ = guard.prepared.insert(token, vec_data);
+
+        Ok(ticket)
     }
 
+    /// 写入提交标记与该批量数据，返回该次写入的编号、Sequence与批量数据
+    pub(crate) fn commit_prepared(&self, token: i64) -> Result<(u64, i64, Vec<KeyValue>)> {
+        let mut guard = self.inner.lock();
+        let vec_data = guard.prepared.remove(&token)
+            .ok_or(KernelError::PreparedNotFound(token))?;
+
+        let seq_id = Sequence::create();
+        let mut bytes = Self::sequence_to_bytes(seq_id)?;
+        bytes.append(&mut Self::record_to_bytes(RECORD_COMMIT, &token.to_be_bytes(), None)?);
+        bytes.append(&mut Self::batch_to_bytes(vec_data.clone()));
+        let ticket = self.append(&mut guard, bytes)?;
+
+        Ok((ticket, seq_id, vec_data))
+    }
+
+    /// 写入回滚标记，返回该次写入的编号
+    pub(crate) fn rollback_prepared(&self, token: i64) -> Result<u64> {
+        let mut guard = self.inner.lock();
+        let _ = guard.prepared.remove(&token)
+            .ok_or(KernelError::PreparedNotFound(token))?;
+
+        let bytes = Self::record_to_bytes(RECORD_ROLLBACK, &token.to_be_bytes(), None)?;
+        self.append(&mut guard, bytes)
+    }
+
+    /// 已预提交但尚未提交或回滚的凭证，以预提交顺序排列
+    pub(crate) fn prepared_tokens(&self) -> Vec<i64> {
+        self.inner.lock()
+            .prepared.keys()
+            .copied()
+            .collect_vec()
+    }
+
+    /// 将缓冲区中的日志写入文件
     pub(crate) fn flush(&self) -> Result<()> {
         self.inner.lock()
             .writer.io_flush()
     }
 
+    /// 将缓冲区中的日志写入文件并同步至磁盘，返回此次新同步的写入次数
+    pub(crate) fn sync(&self) -> Result<u64> {
+        let mut guard = self.inner.lock();
+        guard.writer.io_sync()?;
+
+        Ok(self.mark_synced())
+    }
+
+    /// 需在持有inner锁且同步后调用，使写入编号与同步的数据一致
+    fn mark_synced(&self) -> u64 {
+        let written = self.written.load(Ordering::Acquire);
+        let synced = self.synced.fetch_max(written, Ordering::AcqRel);
+
+        written.saturating_sub(synced)
+    }
+
+    /// 组同步: 等待编号为ticket的写入同步至磁盘
+    ///
+    /// 首个到达的写入方作为领导者，等待window时长以积攒其余写入方的日志后统一同步，
+    /// 其余写入方等待领导者同步完成后直接返回，以此将多次fsync合并为一次
+    ///
+    /// 由该调用进行同步时返回此次同步的写入次数，否则返回None
+    pub(crate) async fn sync_until(&self, ticket: u64, window: Duration) -> Result<Option<u64>> {
+        if self.synced.load(Ordering::Acquire) >= ticket {
+            return Ok(None);
+        }
+        let _guard = self.sync_lock.lock().await;
+        if self.synced.load(Ordering::Acquire) >= ticket {
+            return Ok(None);
+        }
+        if !window.is_zero() {
+            tokio::time::sleep(window).await;
+        }
+
+        self.sync().map(Some)
+    }
+
+    /// 当前Gen的各分段已写入的大小之和，即重启时需重放的WAL大小
+    pub(crate) fn gen_len(&self) -> usize {
+        self.gen_len.load(Ordering::Relaxed)
+    }
+
     /// 弹出此日志的Gen并重新以新Gen进行日志记录
+    ///
+    /// 超出日志个数阈值时归档并去除最旧的一半日志，其中最旧的日志文件会被回收作为新日志的文件
     pub(crate) fn switch(&self, next_gen: i64) -> Result<i64> {
-        let next_writer = self.factory.writer(next_gen, IoType::Direct)?;
         let mut inner = self.inner.lock();
 
         let current_gen = inner.current_gen;
-        inner.writer.io_flush()?;
+        inner.writer.io_sync()?;
+        let _ = self.mark_synced();
 
         // 去除一半的SSTable
         let vec_len = inner.vec_gen.len();
 
         if vec_len >= self.config.wal_threshold {
+            let mut is_recycled = false;
+
             for _ in 0..vec_len / 2 {
                 if let Some(gen) = inner.vec_gen.pop_front() {
-                    self.factory.clean(gen)?;
+                    if let Err(err) = self.archive(gen) {
+                        error!("[LogLoader][archive][Gen: {}][Error]: {:?}", gen, err);
+                        inner.vec_gen.push_front(gen);
+                        break;
+                    }
+                    self.clean_segments(gen)?;
+
+                    if !is_recycled && gen != current_gen && self.factory.recycle(gen, next_gen)? {
+                        is_recycled = true;
+                    } else {
+                        self.factory.clean(gen)?;
+                    }
                 }
             }
         }
 
+        let mut next_writer = self.factory.writer(next_gen, self.log_type)?;
+        next_writer.preallocate(self.config.wal_preallocate_size as u64)?;
+        // 未决的预提交数据随之迁移至新的日志，使旧日志可被安全清理
+        let mut segment_len = 0;
+        for (token, vec_data) in &inner.prepared {
+            let (_, len) = next_writer.io_write(Self::prepare_to_bytes(*token, vec_data.clone())?)?;
+            segment_len += len;
+        }
+        next_writer.io_sync()?;
+        self.factory.sync_dir()?;
+
         inner.vec_gen.push_back(next_gen);
         inner.writer = next_writer;
         inner.current_gen = next_gen;
+        inner.segment_len = segment_len;
+        inner.segment_seq = None;
+        self.gen_len.store(segment_len, Ordering::Relaxed);
 
         Ok(current_gen)
     }
 
     /// 通过Gen载入数据进行读取
     pub(crate) fn load(&self, gen: i64) -> Result<Vec<KeyValue>> {
-        Ok(Entry::<Value>::decode_with_cursor(&mut Cursor::new(
-            IoReader::bytes(self.factory.reader(gen, IoType::MMap)?.as_ref())?
-        ))?.into_iter()
-            .map(|(_, Entry{ key, item, .. })| (key, item.bytes))
-            .collect_vec())
+        self.load_with_progress(gen, |_, _, _| ())
+    }
+
+    /// 通过Gen载入数据进行读取，每读取一个分段以(分段序号, 分段总数, 分段大小)调用fn_progress
+    ///
+    /// 仅返回普通写入与已提交的数据，载入当前Gen时其中未决的预提交数据会被恢复并重新写入日志
+    pub(crate) fn load_with_progress<F>(&self, gen: i64, mut fn_progress: F) -> Result<Vec<KeyValue>>
+        where F: FnMut(usize, usize, u64)
+    {
+        let segment_seqs = Self::segment_seqs_(&self.dir_path, self.extension, gen)?;
+        let segments = segment_seqs.len() + 1;
+
+        let mut bytes = IoReader::bytes(self.factory.reader(gen, IoType::MMap)?.as_ref())?;
+        fn_progress(0, segments, bytes.len() as u64);
+
+        if !segment_seqs.is_empty() {
+            let segment_factory = self.segment_factory(gen)?;
+
+            for (index, seq) in segment_seqs.into_iter().enumerate() {
+                let mut segment_bytes = IoReader::bytes(segment_factory.reader(seq, IoType::MMap)?.as_ref())?;
+                fn_progress(index + 1, segments, segment_bytes.len() as u64);
+                bytes.append(&mut segment_bytes);
+            }
+        }
+
+        // 重新打开后继续写入最后一个Gen，因此计入其已有的大小
+        if gen == self.inner.lock().current_gen {
+            let _ = self.gen_len.fetch_add(bytes.len(), Ordering::Relaxed);
+        }
+        let (vec_data, prepared) = Self::decode_records(bytes)?;
+        self.restore_prepared(gen, prepared)?;
+
+        Ok(vec_data)
+    }
+
+    /// 解码日志记录，返回普通写入与已提交的数据，以及未决的预提交数据
+    fn decode_records(bytes: Vec<u8>) -> Result<(Vec<KeyValue>, PreparedBatches)> {
+        let mut vec_data = Vec::new();
+        let mut prepared = BTreeMap::new();
+
+        for (_, entry) in Entry::<Value>::decode_with_cursor(&mut Cursor::new(bytes))? {
+            let record = entry.shared_len();
+            let Entry { key, item, .. } = entry;
+
+            if record == RECORD_DATA {
+                vec_data.push((Bytes::copy_from_slice(&key), item.bytes));
+                continue;
+            }
+            let token = <[u8; 8]>::try_from(key.as_ref())
+                .map(i64::from_be_bytes)
+                .map_err(|_| KernelError::WalLoad)?;
+
+            match record {
+                RECORD_PREPARE => {
+                    let (batch, _) = Self::decode_records(item.bytes.map_or(Vec::new(), |bytes| bytes.to_vec()))?;
+                    let _ = prepared.insert(token, batch);
+                }
+                RECORD_COMMIT | RECORD_ROLLBACK => {
+                    let _ = prepared.remove(&token);
+                }
+                // 重放的数据会重新分配Sequence，因此仅需保证此后分配的Sequence大于崩溃前已分配的Sequence
+                RECORD_SEQUENCE => Sequence::init(token),
+                _ => return Err(KernelError::WalLoad),
+            }
+        }
+
+        Ok((vec_data, prepared))
+    }
+
+    /// 当前Gen的日志重新打开后由头写入，因此需将恢复的预提交数据重新写入
+    fn restore_prepared(&self, gen: i64, prepared: PreparedBatches) -> Result<()> {
+        let mut guard = self.inner.lock();
+        if gen != guard.current_gen {
+            return Ok(());
+        }
+
+        for (token, vec_data) in prepared {
+            if !guard.prepared.contains_key(&token) {
+                let bytes = Self::prepare_to_bytes(token, vec_data.clone())?;
+                let _ = self.append(&mut guard, bytes)?;
+                let _ = guard.prepared.insert(token, vec_data);
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
     use bytes::Bytes;
+    use itertools::Itertools;
+    use parking_lot::Mutex;
     use tempfile::TempDir;
     use crate::kernel::io::{FileExtension, IoType};
-    use crate::kernel::lsm::log::LogLoader;
+    use crate::kernel::lsm::log::{LogLoader, WalArchiver};
     use crate::kernel::Result;
-    use crate::kernel::lsm::lsm_kv::{Config, DEFAULT_WAL_PATH, Gen};
+    use crate::kernel::lsm::lsm_kv::{Config, DEFAULT_WAL_PATH, Gen, Sequence};
+
+    #[test]
+    fn test_sequence_record() -> Result<()> {
+        // 模拟崩溃前已分配且写入WAL的Sequence
+        let seq_id = Sequence::latest() + 1000;
+        let mut bytes = LogLoader::sequence_to_bytes(seq_id)?;
+        bytes.append(&mut LogLoader::data_to_bytes((Bytes::from_static(b"kip"), None))?);
+
+        let (vec_data, _) = LogLoader::decode_records(bytes)?;
+        assert_eq!(vec_data, vec![(Bytes::from_static(b"kip"), None)]);
+        // 重放后分配的Sequence大于WAL中记录的Sequence
+        assert!(Sequence::create() > seq_id);
+
+        Ok(())
+    }
 
     #[test]
     fn test_log_load() -> Result<()> {
@@ -165,8 +628,8 @@ mod tests {
         let data_1 = (Bytes::from_static(b"kip_key_1"), Some(Bytes::from_static(b"kip_value")));
         let data_2 = (Bytes::from_static(b"kip_key_2"), Some(Bytes::from_static(b"kip_value")));
 
-        wal.log(data_1.clone())?;
-        wal.log(data_2.clone())?;
+        let _ = wal.log_with_sequence(data_1.clone())?;
+        let _ = wal.log_with_sequence(data_2.clone())?;
 
         let gen = wal.switch(Gen::create())?;
 
@@ -200,8 +663,8 @@ mod tests {
         let data_1 = (Bytes::from_static(b"kip_key_1"), Some(Bytes::from_static(b"kip_value")));
         let data_2 = (Bytes::from_static(b"kip_key_2"), Some(Bytes::from_static(b"kip_value")));
 
-        wal_1.log(data_1.clone())?;
-        wal_1.log(data_2.clone())?;
+        let _ = wal_1.log_with_sequence(data_1.clone())?;
+        let _ = wal_1.log_with_sequence(data_2.clone())?;
 
         wal_1.flush()?;
         // wal_1尚未drop时，则开始reload，模拟SUCCESS_FS未删除的情况(即停机异常)，触发数据恢复
@@ -217,6 +680,95 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_log_recycle() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        let config = Config::new(temp_dir.into_path())
+            .wal_threshold(2);
+
+        let (wal, _) = LogLoader::reload(
+            config,
+            DEFAULT_WAL_PATH,
+            FileExtension::Log,
+            IoType::Buf
+        )?;
+
+        let data_1 = (Bytes::from_static(b"kip_key_1"), Some(Bytes::from_static(b"kip_value")));
+        let data_2 = (Bytes::from_static(b"kip_key_2"), Some(Bytes::from_static(b"kip_value")));
+
+        let (gen_1, gen_2, gen_3) = (Gen::create(), Gen::create(), Gen::create());
+
+        let _ = wal.switch(gen_1)?;
+        let _ = wal.log_with_sequence(data_1)?;
+        let _ = wal.switch(gen_2)?;
+        // 超出阈值，最旧的日志文件被回收作为新日志的文件
+        let _ = wal.switch(gen_3)?;
+        assert!(!wal.factory.has_gen(gen_1)?);
+        assert!(wal.factory.has_gen(gen_2)?);
+
+        // 回收的文件已被清空，不会读取到旧日志的数据
+        let _ = wal.log_with_sequence(data_2.clone())?;
+        wal.flush()?;
+        assert_eq!(wal.load(gen_3)?, vec![data_2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_segment() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        let config = Config::new(temp_dir.into_path())
+            .wal_threshold(2);
+        let archived = Arc::new(Mutex::new(Vec::new()));
+        let archived_1 = Arc::clone(&archived);
+
+        let (wal, _) = LogLoader::reload(
+            config,
+            DEFAULT_WAL_PATH,
+            FileExtension::Log,
+            IoType::Buf
+        )?;
+        let wal = wal.segment_size(Some(64))
+            .archiver(Some(WalArchiver::new(move |gen, path| {
+                archived_1.lock().push((gen, path.to_path_buf()));
+                Ok(())
+            })));
+
+        let (gen_1, gen_2, gen_3) = (Gen::create(), Gen::create(), Gen::create());
+        let vec_data = (0..10u8)
+            .map(|i| (Bytes::from(vec![i; 16]), Some(Bytes::from_static(b"kip_value"))))
+            .collect_vec();
+
+        let _ = wal.switch(gen_1)?;
+        for data in vec_data.iter() {
+            let _ = wal.log_with_sequence(data.clone())?;
+        }
+        wal.flush()?;
+
+        // 超出分段大小后的数据写入后续分段，读取时按写入顺序合并
+        let segment_paths = wal.segment_paths(gen_1)?;
+        assert!(segment_paths.len() > 1);
+        assert_eq!(wal.load(gen_1)?, vec_data);
+
+        let _ = wal.switch(gen_2)?;
+        let _ = wal.switch(gen_3)?;
+
+        // 清理前按写入顺序对所有分段进行归档
+        let archived = archived.lock()
+            .iter()
+            .map(|(gen, path)| {
+                assert_eq!(*gen, gen_1);
+                path.clone()
+            })
+            .collect_vec();
+        assert_eq!(archived, segment_paths);
+        assert!(archived.iter().skip(1).all(|path| !path.exists()));
+
+        Ok(())
+    }
 }
 
 
@@ -1,25 +1,55 @@
+use std::cmp::min;
+use std::collections::hash_map::RandomState;
 use std::fs;
+use std::{iter, mem};
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU8, Ordering};
 use async_trait::async_trait;
 use bytes::Bytes;
 use chrono::Local;
-use fslock::LockFile;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use skiplist::SkipMap;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tokio::sync::oneshot;
-use tracing::{error, info};
+use tokio::sync::{broadcast, Notify, oneshot, RwLock};
+use tracing::{error, info, warn};
 use crate::kernel::{DEFAULT_LOCK_FILE, KVStore, lock_or_time_out};
-use crate::kernel::io::{FileExtension, IoType};
-use crate::kernel::lsm::{block, is_exceeded_then_minor};
+use crate::kernel::io::{DEFAULT_WRITE_BUFFER_SIZE, FileExtension, IoType};
+use crate::kernel::io::fault::FaultInjector;
+use crate::kernel::lsm::{block, chunk, heat_map, DEFAULT_SST_PATH_ID, is_exceeded_then_minor};
+use crate::kernel::lsm::chunk::{ChunkManifest, INTERNAL_KEY_PREFIX};
 use crate::kernel::lsm::compactor::{Compactor, CompactTask};
+use crate::kernel::lsm::event::{EventListener, QuarantineReport, ReadFallbackEvent, RecoveryEvent, ScrubEvent};
+use crate::kernel::lsm::fallback::ReadFallback;
+use crate::kernel::lsm::iterator::merging_iter::{MergeSource, MergingIter};
 use crate::kernel::lsm::iterator::version_iter::VersionIter;
+use crate::kernel::lsm::loader::CacheLoader;
 use crate::kernel::lsm::log::LogLoader;
+pub use crate::kernel::lsm::log::WalArchiver;
 use crate::kernel::lsm::mem_table::{InternalKey, KeyValue, MemMap, MemTable};
 use crate::kernel::lsm::mvcc::Transaction;
-use crate::kernel::lsm::version::{Version, VersionStatus};
+use crate::kernel::lsm::options::{Ack, MutableOptions, PersistentOptions, ReadOptions, with_deadline, WriteOptions};
+use crate::kernel::lsm::hot_keys::HotKeys;
+use crate::kernel::lsm::hook::{HookScope, WriteHook, WriteHooks};
+use crate::kernel::lsm::keyspace::{DEFAULT_KEYSPACE_EVENT_CAPACITY, KeyspaceEvent, KeyspaceEvents};
+use crate::kernel::lsm::write_quota::WriteQuotas;
+use crate::kernel::lsm::row_cache::RowCache;
+use crate::kernel::lsm::scrub::scrub_periodically;
+use crate::kernel::lsm::structures::Structures;
+use crate::kernel::lsm::lease::Leases;
+use crate::kernel::lsm::column_family::{ColumnFamilies, TableOptions, WriteBatch};
+use crate::kernel::lsm::stats::{dump_periodically, HotKey, MemoryUsage, Statistics, StatsSnapshot};
+use crate::kernel::lsm::version::{DEFAULT_SS_TABLE_PATH, Version, VersionStatus};
 use crate::kernel::Result;
+use crate::kernel::utils::latch::Latches;
+use crate::kernel::utils::lock_file::LockFile;
+use crate::kernel::utils::clock::{Clock, SystemClock};
+use crate::kernel::utils::runtime::Spawner;
+use crate::kernel::utils::quota::Quota;
 use crate::KernelError;
 
 pub(crate) const DEFAULT_MINOR_THRESHOLD_WITH_LEN: usize = 2333;
@@ -38,12 +68,35 @@ pub(crate) const DEFAULT_BLOCK_CACHE_SIZE: usize = 3200;
 
 pub(crate) const DEFAULT_TABLE_CACHE_SIZE: usize = 1024;
 
+pub(crate) const DEFAULT_INDEX_CACHE_SIZE: usize = 1024;
+
 pub(crate) const DEFAULT_WAL_THRESHOLD: usize = 20;
 
 pub(crate) const DEFAULT_WAL_PATH: &str = "wal";
 
 pub(crate) const DEFAULT_WAL_IO_TYPE: IoType = IoType::Buf;
 
+pub(crate) const DEFAULT_WAL_PREALLOCATE_SIZE: usize = 4 * 1024 * 1024;
+
+const RECOVERY_RUNNING: u8 = 0;
+
+const RECOVERY_DONE: u8 = 1;
+
+const RECOVERY_FAILED: u8 = 2;
+
+pub(crate) const DEFAULT_LATCHES_SIZE: usize = 64;
+
+pub(crate) const DEFAULT_SECONDARY_CACHE_SIZE: usize = 256 * 1024 * 1024;
+
+pub(crate) const DEFAULT_TARGET_FILE_SIZE_MULTIPLIER: usize = 1;
+
+pub(crate) const DEFAULT_MAX_SEQUENTIAL_SKIP: usize = 8;
+
+pub(crate) const DEFAULT_NEGATIVE_CACHE_SIZE: usize = 4096;
+
+/// 统计数据中附带的热点Key数量
+pub(crate) const HOT_KEYS_IN_STATS: usize = 10;
+
 static SEQ_COUNT: AtomicI64 = AtomicI64::new(1);
 
 static GEN_BUF: AtomicI64 = AtomicI64::new(0);
@@ -56,7 +109,10 @@ pub struct LsmStore {
     /// 避免多进程进行数据读写
     lock_file: LockFile,
     /// Compactor 通信器
-    compactor_tx: UnboundedSender<CompactTask>
+    compactor_tx: UnboundedSender<CompactTask>,
+    /// Key分片锁
+    /// 保证写入与compare_and_swap等读-改-写操作之间的原子性
+    pub(crate) latches: Arc<Latches>,
 }
 
 pub(crate) struct StoreInner {
@@ -75,37 +131,392 @@ pub(crate) struct StoreInner {
     /// `Config.wal_threshold`用于控制WalLoader的的SSTable数据日志个数
     /// 超出个数阈值时会清空最旧的一半日志
     pub(crate) wal: Arc<LogLoader>,
+    /// 运行时统计
+    pub(crate) stats: Statistics,
+    /// 行缓存，为None时不启用
+    pub(crate) row_cache: Option<RowCache>,
+    /// 热点Key统计，为None时不启用
+    hot_keys: Option<HotKeys>,
+    /// WAL重放状态，后台重放WAL时写入需等待其完成
+    recovery_state: AtomicU8,
+    recovered: Notify,
+    /// WAL同步失败后置为true，此后拒绝所有写入
+    ///
+    /// 同步失败后文件系统可能丢弃未落盘的页，再次同步成功也无法保证此前的写入已落盘
+    wal_failed: AtomicBool,
+    /// 写入去重分块的引用时持有读锁，回收去重分块时持有写锁
+    blob_gate: RwLock<()>,
+    /// 引用被删除过的去重分块的摘要，压缩时检查其是否仍被引用
+    ///
+    /// 仅保存在内存中，重启前未被检查的分块不会被回收
+    blob_candidates: parking_lot::Mutex<HashSet<u128>>,
+    /// 后台校验发现损坏的SSTable的Gen，压缩时对其进行隔离
+    corrupted_gens: parking_lot::Mutex<HashSet<i64>>,
+    /// 读取损坏的SSTable时经`ReadFallback`取回的数据，重建该SSTable时写回
+    fallback_reads: parking_lot::Mutex<HashMap<i64, Vec<(Bytes, Bytes)>>>,
+    /// 写入前的钩子
+    pub(crate) write_hooks: WriteHooks,
+    /// 列族的写入配额与统计
+    pub(crate) write_quotas: WriteQuotas,
+    /// Key空间的通知
+    pub(crate) keyspace_events: KeyspaceEvents,
+    /// 该Store已写入MemTable的最大Sequence，写入可见后推进
+    applied_seq: AtomicI64,
+    applied: Notify,
 }
 
 impl StoreInner {
-    pub(crate) async fn new(config: Config) -> Result<Self> {
-        let (wal, reload_data) = LogLoader::reload(
+    /// 开启`Config.background_wal_replay`时，返回值中附带待后台重放的WAL Gen
+    pub(crate) async fn new(config: Config) -> Result<(Self, Option<i64>)> {
+        let start = Instant::now();
+        let (wal, last_gen) = LogLoader::reload_(
             config.clone(),
             DEFAULT_WAL_PATH,
             FileExtension::Log,
             config.wal_io_type
         )?;
-        let wal = Arc::new(wal);
-        // Q: 为什么INIT_SEQ作为Seq id?
-        // A: 因为此处是当存在有停机异常时使用wal恢复数据,此处也不存在有Version(VersionStatus的初始化在此代码之后)
-        // 因此不会影响Version的读取顺序
-        let mem_map = MemMap::from_iter(
-            reload_data.into_iter()
-                .map(|(key, value)| (InternalKey::new_with_seq(key, 0), value))
+        let wal = Arc::new(
+            wal.segment_size(config.wal_segment_size)
+                .archiver(config.wal_archiver.clone())
         );
-
         // 初始化wal日志
         let ver_status = VersionStatus::load_with_path(config.clone(), Arc::clone(&wal)).await?;
+        let version = ver_status.current().await;
+        config.notify_recovery(&RecoveryEvent::TablesOpened {
+            tables: version.level_sst_count().into_iter().sum()
+        });
 
-        let mem_table = MemTable::new(mem_map);
-
-        Ok(StoreInner {
+        // 以Manifest中持久化的Sequence作为起点，避免重启后Sequence回退
+        // 此处是当存在有停机异常时使用wal恢复数据，按日志顺序重新分配Seq id以保证同Key的新旧顺序
+        Sequence::init(version.get_last_sequence());
+
+        let (mem_table, recovery_state, pending_gen) = if config.background_wal_replay {
+            (MemTable::new(MemMap::new()), RECOVERY_RUNNING, Some(last_gen))
+        } else {
+            let reload_data = Self::load_wal(&config, &wal, last_gen)?;
+            let entries = reload_data.len();
+            let mem_map = MemMap::from_iter(
+                reload_data.into_iter()
+                    .map(|(key, value)| (InternalKey::new(&key), value))
+            );
+            config.notify_recovery(&RecoveryEvent::Completed { entries, elapsed: start.elapsed() });
+
+            (MemTable::new(mem_map), RECOVERY_DONE, None)
+        };
+        let mem_table = mem_table.with_max_snapshot_age(config.max_snapshot_age)
+            .with_version_retention(config.version_retention)
+            .with_clock(Arc::clone(&config.clock));
+        let row_cache = config.row_cache_size
+            .map(RowCache::new)
+            .transpose()?;
+        let hot_keys = config.hot_key_capacity
+            .map(HotKeys::new);
+        let write_hooks = WriteHooks::new(config.write_hooks.clone());
+        let write_quotas = WriteQuotas::new(config.cf_write_quotas.clone());
+        let keyspace_events = KeyspaceEvents::new(DEFAULT_KEYSPACE_EVENT_CAPACITY);
+
+        Ok((StoreInner {
             mem_table,
             ver_status,
             config,
             wal,
+            stats: Statistics::default(),
+            row_cache,
+            hot_keys,
+            recovery_state: AtomicU8::new(recovery_state),
+            recovered: Notify::new(),
+            wal_failed: AtomicBool::new(false),
+            blob_gate: RwLock::new(()),
+            blob_candidates: parking_lot::Mutex::new(HashSet::new()),
+            corrupted_gens: parking_lot::Mutex::new(HashSet::new()),
+            fallback_reads: parking_lot::Mutex::new(HashMap::new()),
+            write_hooks,
+            write_quotas,
+            keyspace_events,
+            // 此时已分配的Sequence均已落盘或重放，待后台重放的数据在重放时推进
+            applied_seq: AtomicI64::new(Sequence::latest()),
+            applied: Notify::new(),
+        }, pending_gen))
+    }
+
+    /// 写入MemTable，并使行缓存中对应Key的缓存行失效
+    pub(crate) fn insert_data(&self, data: KeyValue) -> Result<(usize, i64)> {
+        let key = data.0.clone();
+        let (data_len, seq_id) = self.mem_table.insert_data(data)?;
+        self.invalidate_rows(iter::once(&key));
+        self.mark_applied(seq_id);
+
+        Ok((data_len, seq_id))
+    }
+
+    /// 以已分配的seq_id写入MemTable，并使行缓存中对应Key的缓存行失效
+    pub(crate) fn insert_data_with_seq(&self, data: KeyValue, seq_id: i64) -> Result<usize> {
+        let key = data.0.clone();
+        let data_len = self.mem_table.insert_data_with_seq(data, seq_id)?;
+        self.invalidate_rows(iter::once(&key));
+        self.mark_applied(seq_id);
+
+        Ok(data_len)
+    }
+
+    /// 分配Sequence并写入WAL(开启时)，返回WAL写入的编号与Sequence
+    pub(crate) fn log_data(&self, data: KeyValue) -> Result<(Option<u64>, i64)> {
+        if !self.config.wal_enable {
+            return Ok((None, Sequence::create()));
+        }
+        let (ticket, seq_id) = self.wal.log_with_sequence(data)?;
+
+        Ok((Some(ticket), seq_id))
+    }
+
+    /// 分配Sequence并批量写入WAL(开启时)，返回WAL写入的编号与Sequence
+    pub(crate) fn log_batch_data(&self, batch_data: Vec<KeyValue>) -> Result<(Option<u64>, i64)> {
+        if !self.config.wal_enable {
+            return Ok((None, Sequence::create()));
+        }
+        let (ticket, seq_id) = self.wal.log_batch_with_sequence(batch_data)?;
+
+        Ok((Some(ticket), seq_id))
+    }
+
+    /// 批量写入MemTable，并使行缓存中对应Key的缓存行失效
+    pub(crate) fn insert_batch_data(&self, batch_data: Vec<KeyValue>, seq_id: i64) -> Result<usize> {
+        let keys = self.row_cache.is_some()
+            .then(|| batch_data.iter().map(|(key, _)| key.clone()).collect_vec());
+        let data_len = self.mem_table.insert_batch_data(batch_data, seq_id)?;
+        self.invalidate_rows(keys.iter().flatten());
+        self.mark_applied(seq_id);
+
+        Ok(data_len)
+    }
+
+    /// 推进已应用的Sequence并唤醒`LsmStore::wait_for_sequence`的等待方
+    ///
+    /// 并发的写入可能乱序写入MemTable，但写入返回的Sequence总是在其写入MemTable之后，
+    /// 因此以写入返回的Sequence等待时，推进至该Sequence即表示该写入已可见
+    fn mark_applied(&self, seq_id: i64) {
+        if self.applied_seq.fetch_max(seq_id, Ordering::AcqRel) < seq_id {
+            self.applied.notify_waiters();
+        }
+    }
+
+    /// 标记损坏的SSTable，使其在下一次压缩时被隔离
+    pub(crate) fn mark_corrupted(&self, gen: i64) {
+        let _ = self.corrupted_gens.lock().insert(gen);
+    }
+
+    pub(crate) fn take_corrupted(&self) -> HashSet<i64> {
+        mem::take(&mut *self.corrupted_gens.lock())
+    }
+
+    /// 记录读取损坏的SSTable时经`ReadFallback`取回的数据
+    pub(crate) fn record_fallback_read(&self, gen: i64, key: &[u8], value: Bytes) {
+        self.fallback_reads.lock()
+            .entry(gen)
+            .or_default()
+            .push((Bytes::copy_from_slice(key), value));
+    }
+
+    pub(crate) fn take_fallback_reads(&self, gen: i64) -> Vec<(Bytes, Bytes)> {
+        self.fallback_reads.lock()
+            .remove(&gen)
+            .unwrap_or_default()
+    }
+
+    /// 删除不再被引用的去重分块
+    ///
+    /// 由压缩时调用，逐个检查引用被删除过的去重分块是否仍存在其他引用，
+    /// 检查期间持有写锁，因此不会与新引用的写入交错
+    pub(crate) async fn sweep_blobs(&self) -> Result<()> {
+        let candidates = mem::take(&mut *self.blob_candidates.lock());
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let _gate = self.blob_gate.write().await;
+        let version = self.ver_status.current().await;
+        let all_ss_tables = version.get_all_ss_tables().await;
+
+        for digest in candidates {
+            let prefix = chunk::blob_ref_prefix(digest);
+            let mut sources = vec![
+                MergeSource::mem(self.mem_table.range_with_sequence(Some(&prefix), Sequence::latest()))
+            ];
+            sources.append(&mut MergeSource::tables(&all_ss_tables, &version.block_cache, Some(&prefix))?);
+
+            let mut merging_iter = MergingIter::new(sources);
+            let mut is_referenced = false;
+            while let Some((key, value)) = merging_iter.next_err()? {
+                if !key.starts_with(&prefix) {
+                    break
+                }
+                if value.is_some() {
+                    is_referenced = true;
+                    break
+                }
+            }
+
+            if !is_referenced {
+                let data = (chunk::blob_key(digest), None);
+                let (ticket, seq_id) = self.log_data(data.clone())?;
+                let _ = self.insert_data_with_seq(data, seq_id)?;
+                if let Some(ticket) = ticket {
+                    self.wal_sync(ticket).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 需在写入的数据可见后调用
+    pub(crate) fn invalidate_rows<'a>(&self, keys: impl Iterator<Item = &'a Bytes>) {
+        if let Some(row_cache) = &self.row_cache {
+            for key in keys {
+                row_cache.invalidate(key);
+            }
+        }
+    }
+
+    fn load_wal(config: &Config, wal: &LogLoader, gen: i64) -> Result<Vec<KeyValue>> {
+        wal.load_with_progress(gen, |segment, segments, bytes| {
+            config.notify_recovery(&RecoveryEvent::WalSegmentReplayed { gen, segment, segments, bytes });
         })
     }
+
+    /// 后台重放WAL，完成前Store仅可读取已落盘的数据
+    pub(crate) fn replay_wal(&self, gen: i64) {
+        let start = Instant::now();
+
+        let result = Self::load_wal(&self.config, &self.wal, gen)
+            .and_then(|reload_data| {
+                let entries = reload_data.len();
+                for data in reload_data {
+                    let _ = self.insert_data(data)?;
+                }
+                Ok(entries)
+            });
+        let state = match result {
+            Ok(entries) => {
+                self.config.notify_recovery(&RecoveryEvent::Completed { entries, elapsed: start.elapsed() });
+                RECOVERY_DONE
+            }
+            Err(err) => {
+                error!("[LsmStore][replay_wal][Gen: {}][Error]: {:?}", gen, err);
+                self.config.notify_recovery(&RecoveryEvent::Failed { reason: err.to_string() });
+                RECOVERY_FAILED
+            }
+        };
+        self.recovery_state.store(state, Ordering::Release);
+        self.recovered.notify_waiters();
+    }
+
+    /// 等待WAL重放完成，所有写入均需在此之后进行
+    ///
+    /// WAL同步失败后返回`KernelError::WalSyncFailed`
+    pub(crate) async fn wait_recovered(&self) -> Result<()> {
+        if self.wal_failed.load(Ordering::Acquire) {
+            return Err(KernelError::WalSyncFailed);
+        }
+        loop {
+            let notified = self.recovered.notified();
+
+            match self.recovery_state.load(Ordering::Acquire) {
+                RECOVERY_DONE => return Ok(()),
+                RECOVERY_FAILED => return Err(KernelError::RecoveryFailed),
+                _ => notified.await,
+            }
+        }
+    }
+
+    /// 按`Config.wal_sync_window_micros`等待编号为ticket的WAL写入同步至磁盘
+    pub(crate) async fn wal_sync(&self, ticket: u64) -> Result<()> {
+        let Some(window_micros) = self.config.wal_sync_window_micros else {
+            return Ok(());
+        };
+
+        self.wal_sync_until(ticket, Duration::from_micros(window_micros)).await
+    }
+
+    /// 按写入的确认级别等待编号为ticket的WAL写入
+    pub(crate) async fn wal_sync_with(&self, ticket: u64, ack: Ack) -> Result<()> {
+        match ack {
+            Ack::Memory => Ok(()),
+            Ack::Wal => self.wal_sync(ticket).await,
+            Ack::Fsync => {
+                let window = self.config.wal_sync_window_micros
+                    .map_or(Duration::ZERO, Duration::from_micros);
+
+                self.wal_sync_until(ticket, window).await
+            }
+        }
+    }
+
+    /// 无论是否开启组同步，均等待编号为ticket的WAL写入同步至磁盘
+    ///
+    /// 同步失败时该写入已写入MemTable并可见，因此将Store标记为只读，避免此后的写入继续依赖无法落盘的WAL
+    pub(crate) async fn wal_sync_until(&self, ticket: u64, window: Duration) -> Result<()> {
+        let result = self.wal.sync_until(ticket, window).await;
+        if let Err(err) = &result {
+            error!("[LsmStore][wal_sync][Ticket: {}][Error]: {:?}", ticket, err);
+            self.wal_failed.store(true, Ordering::Release);
+        }
+        if let Some(writes) = result? {
+            let _ = self.stats.wal_sync_count.fetch_add(1, Ordering::Relaxed);
+            let _ = self.stats.wal_synced_writes.fetch_add(writes, Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    /// 获取MemTable与各缓存的内存占用
+    pub(crate) async fn memory_usage(&self) -> MemoryUsage {
+        let version = self.ver_status.current().await;
+
+        MemoryUsage {
+            mem_table: self.mem_table.memory_usage(),
+            mem_table_arena: self.mem_table.arena_usage(),
+            block_cache: version.block_cache.data_memory_usage(),
+            index_cache: version.block_cache.index_memory_usage(),
+            table_cache: self.ver_status.table_cache_usage().await,
+        }
+    }
+
+    /// 获取统计数据快照
+    pub(crate) async fn statistics(&self) -> StatsSnapshot {
+        let version = self.ver_status.current().await;
+        let (level_tombstones, live_size_estimate) = version.get_space_usage().await;
+        StatsSnapshot {
+            level_sst_count: version.level_sst_count(),
+            len: version.get_len(),
+            size_of_disk: version.get_size_of_disk(),
+            level_tombstones,
+            live_size_estimate,
+            block_cache: version.block_cache.stats(),
+            index_cache: version.block_cache.index_stats(),
+            table_cache: self.ver_status.table_cache_stats().await,
+            row_cache: self.row_cache.as_ref()
+                .map(RowCache::stats)
+                .unwrap_or_default(),
+            flush_wait_micros: self.stats.flush_wait_micros.load(Ordering::Relaxed),
+            compaction_pending: self.stats.compaction_pending.load(Ordering::Relaxed),
+            minor_compaction_count: self.stats.minor_compaction_count.load(Ordering::Relaxed),
+            compaction_micros: self.stats.compaction_micros.load(Ordering::Relaxed),
+            get_latency: self.stats.get_latency.snapshot(),
+            set_latency: self.stats.set_latency.snapshot(),
+            flush_latency: self.stats.flush_latency.snapshot(),
+            compaction_latency: self.stats.compaction_latency.snapshot(),
+            memory_usage: self.memory_usage().await,
+            wal_sync_count: self.stats.wal_sync_count.load(Ordering::Relaxed),
+            wal_synced_writes: self.stats.wal_synced_writes.load(Ordering::Relaxed),
+            major_threshold: self.stats.major_threshold.load(Ordering::Relaxed),
+            hot_keys: self.hot_keys.as_ref()
+                .map(|hot_keys| hot_keys.top_keys(HOT_KEYS_IN_STATS))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, count)| HotKey { key: key.to_vec(), count })
+                .collect(),
+        }
+    }
 }
 
 #[async_trait]
@@ -122,44 +533,36 @@ impl KVStore for LsmStore {
 
     #[inline]
     async fn flush(&self) -> Result<()> {
-        let (tx, rx) = oneshot::channel();
-
-        self.compactor_tx.send(CompactTask::Flush(Some(tx)))?;
-        self.wal().flush()?;
-
-        rx.await.map_err(|_| KernelError::ChannelClose)?;
-
-        Ok(())
+        self.flush_with_options(&WriteOptions::default()).await
     }
 
     #[inline]
     async fn set(&self, key: &[u8], value: Bytes) -> Result<()> {
-        self.append_cmd_data(
-            (Bytes::copy_from_slice(key), Some(value))
-        ).await
+        let _ = self.set_with_sequence(key, value).await?;
+
+        Ok(())
     }
 
     #[inline]
     async fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
-        if let Some(value) = self.mem_table().find(key) {
-            return Ok(Some(value));
-        }
-
-        if let Some(value) = self.current_version().await
-            .find_data_for_ss_tables(key).await?
-        {
-            return Ok(Some(value));
+        let start = Instant::now();
+        if let Some(hot_keys) = &self.inner.hot_keys {
+            hot_keys.record(key);
         }
+        let result = match self.get_local(key).await {
+            Ok(None) => self.load_through(key).await,
+            result => result,
+        };
+        self.inner.stats.get_latency.record(start.elapsed());
 
-        Ok(None)
+        result
     }
 
     #[inline]
     async fn remove(&self, key: &[u8]) -> Result<()> {
-        match self.get(key).await? {
-            Some(_) => self.append_cmd_data((Bytes::copy_from_slice(key), None)).await,
-            None => Err(KernelError::KeyNotFound)
-        }
+        let _ = self.remove_with_sequence(key).await?;
+
+        Ok(())
     }
 
     #[inline]
@@ -183,385 +586,2798 @@ impl KVStore for LsmStore {
 
 impl Drop for LsmStore {
     #[inline]
-    #[allow(clippy::expect_used)]
     fn drop(&mut self) {
-        self.lock_file.unlock()
-            .expect("LockFile unlock failed!");
+        if self.inner.config.persist_heat_map {
+            if let Err(err) = self.save_heat_map() {
+                error!("[LsmStore][drop][HeatMap save failed]: {:?}", err);
+            }
+        }
+        if let Err(err) = self.lock_file.unlock() {
+            error!("[LsmStore][drop][LockFile unlock failed]: {:?}", err);
+        }
     }
 }
 
 impl LsmStore {
 
-    /// 追加数据
-    async fn append_cmd_data(&self, data: KeyValue) -> Result<()> {
-        // Wal与MemTable双写
-        if self.is_enable_wal() {
-            self.wal().log(data.clone())?;
+    /// 读取本地的数据，不经`Config::cache_loader`读穿
+    ///
+    /// Latch以Key的Hash进行分片且不可重入，因此持有任意Latch的内部读取(如列族、数据结构与租约)
+    /// 需使用此方法，以免读穿时再次获取Latch
+    pub(crate) async fn get_local(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        let result = match self.get_(key).await {
+            Ok(value) => self.resolve_chunks(key, value).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Err(err) if err.is_corruption() => self.read_fallback(key, err).await,
+            result => result,
         }
+    }
 
-        is_exceeded_then_minor(
-            self.mem_table().insert_data(data)?,
-            &self.compactor_tx,
-            self.config()
-        )?;
+    /// 持有该Key的Latch时读取，本地不存在时由`Config::cache_loader`加载
+    async fn get_locked(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        match self.get_local(key).await {
+            Ok(None) => self.load_locked(key).await,
+            result => result,
+        }
+    }
+
+    async fn get_(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        let Some(row_cache) = &self.inner.row_cache else {
+            return self.get_uncached(key).await;
+        };
+        if let Some(value) = row_cache.get(key) {
+            return Ok(value);
+        }
+        // 写入序号需在读取前获取，以免缓存读取期间被覆盖的数据
+        let sequence = row_cache.sequence(key);
+        let value = self.get_uncached(key).await?;
+        row_cache.fill(key, value.clone(), sequence);
+
+        Ok(value)
+    }
+
+    /// Value为分块清单时读取并拼接各分块，否则直接返回
+    ///
+    /// 读取期间Value被覆盖或删除时，旧的分块可能已被删除，此时以最新的Value重试
+    async fn resolve_chunks(&self, key: &[u8], mut value: Option<Bytes>) -> Result<Option<Bytes>> {
+        loop {
+            let Some(manifest) = self.manifest_of(key, value.as_deref()).await? else {
+                return Ok(value);
+            };
+            let mut chunks = Vec::with_capacity(manifest.digests().len());
+            for digest in manifest.digests() {
+                match self.get_(&manifest.chunk_key(key, *digest)).await? {
+                    Some(chunk) => chunks.push(chunk),
+                    None => break,
+                }
+            }
+            if chunks.len() == manifest.digests().len() {
+                return manifest.assemble(chunks).map(Some);
+            }
+
+            let latest = self.get_(key).await?;
+            if latest == value {
+                return Err(KernelError::CrcMisMatch);
+            }
+            value = latest;
+        }
+    }
+
+    /// 以分块的方式写入Value，并删除旧Value中不再被引用的分块
+    ///
+    /// 需持有Key的锁，分块先于清单写入，旧分块晚于清单删除，
+    /// 因此中途失败时仅会残留无用的分块，而不会出现清单所引用的分块缺失
+    async fn set_chunked(&self, key: &[u8], value: Bytes, chunk_size: usize, options: &WriteOptions) -> Result<i64> {
+        // WAL按顺序同步，因此分块无需等待，由清单的写入按确认级别一并等待
+        let chunk_options = WriteOptions { ack: Ack::Memory, ..*options };
+        let dedup = self.config().value_dedup;
+        // 写入去重分块的引用期间，避免该分块被回收
+        let _gate = if dedup { Some(self.inner.blob_gate.read().await) } else { None };
+
+        let old_value = self.get_(key).await?;
+        let old_manifest = self.manifest_of(key, old_value.as_deref()).await?;
+        // 去重方式变更时，旧清单中的分块均无法复用
+        let reusable = old_manifest.as_ref()
+            .filter(|manifest| manifest.is_dedup() == dedup)
+            .map_or(&[][..], ChunkManifest::digests);
+        let key = Bytes::copy_from_slice(key);
+
+        let (seq_id, digests, manifest_value) = if ChunkManifest::is_required(&value, chunk_size) {
+            let (manifest, chunks) = ChunkManifest::split(&value, chunk_size, dedup);
+
+            // 内容未变化的分块无需重复写入
+            for (digest, chunk) in chunks.into_iter().unique_by(|(digest, _)| *digest) {
+                if reusable.contains(&digest) {
+                    continue
+                }
+                // 去重分块总是重新写入，以覆盖可能已写入的回收删除，
+                // 内容相同的分块共用同一Key，因此压缩合并后仅存储一份
+                let _ = self.append_with_options((manifest.chunk_key(&key, digest), Some(chunk)), &chunk_options).await?;
+                if dedup {
+                    let ref_key = chunk::blob_ref_key(digest, &key);
+                    let _ = self.append_with_options((ref_key, Some(Bytes::new())), &chunk_options).await?;
+                }
+            }
+            let manifest_value = manifest.to_value()?;
+            // 标记先于清单写入，使读取到清单时总可找到其标记
+            let mark_key = chunk::manifest_mark_key(&key, &manifest_value);
+            let _ = self.append_with_options((mark_key, Some(Bytes::new())), &chunk_options).await?;
+            let seq_id = self.append_with_options((key.clone(), Some(manifest_value.clone())), options).await?;
+
+            (seq_id, manifest.digests().to_vec(), Some(manifest_value))
+        } else {
+            (self.append_with_options((key.clone(), Some(value)), options).await?, vec![], None)
+        };
+        if let (Some(old_manifest), Some(old_value)) = (&old_manifest, &old_value) {
+            let retained = if old_manifest.is_dedup() == dedup { &digests[..] } else { &[] };
+            self.release_chunks(&key, old_manifest, retained).await?;
+            if manifest_value.as_ref() != Some(old_value) {
+                let _ = self.append_cmd_data((chunk::manifest_mark_key(&key, old_value), None), None).await?;
+            }
+        }
+
+        Ok(seq_id)
+    }
+
+    /// 解析Key的Value中的清单，仅在存在该清单的标记时视为清单，否则为普通的Value
+    async fn manifest_of(&self, key: &[u8], value: Option<&[u8]>) -> Result<Option<ChunkManifest>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let Some(manifest) = ChunkManifest::from_value(value) else {
+            return Ok(None);
+        };
+        let is_marked = self.get_(&chunk::manifest_mark_key(key, value)).await?.is_some();
+
+        Ok(is_marked.then_some(manifest))
+    }
+
+    /// 释放清单中不在retained中的分块
+    ///
+    /// 未去重的分块直接删除，去重的分块仅删除该Key的引用，分块本身在压缩时回收
+    async fn release_chunks(&self, key: &[u8], manifest: &ChunkManifest, retained: &[u128]) -> Result<()> {
+        for digest in manifest.digests().iter().unique() {
+            if retained.contains(digest) {
+                continue
+            }
+            if manifest.is_dedup() {
+                let _ = self.append_cmd_data((chunk::blob_ref_key(*digest, key), None), None).await?;
+                let _ = self.inner.blob_candidates.lock().insert(*digest);
+            } else {
+                let _ = self.append_cmd_data((manifest.chunk_key(key, *digest), None), None).await?;
+            }
+        }
 
         Ok(())
     }
 
-    fn is_enable_wal(&self) -> bool {
-        self.config().wal_enable
+    async fn get_uncached(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        if let Some(value) = self.mem_table().find(key) {
+            return Ok(value);
+        }
+
+        if let Some(value) = self.current_version().await
+            .find_data_for_ss_tables(key).await?
+        {
+            return Ok(Some(value));
+        }
+
+        Ok(None)
     }
 
-    /// 使用Config进行LsmStore初始化
-    #[inline]
-    pub async fn open_with_config(config: Config) -> Result<Self> where Self: Sized {
-        info!("
-            █████   ████  ███            ██████████   ███████████
-            ▒▒███   ███▒  ▒▒▒            ▒▒███▒▒▒▒███ ▒▒███▒▒▒▒▒███
-             ▒███  ███    ████  ████████  ▒███   ▒▒███ ▒███    ▒███
-             ▒███████    ▒▒███ ▒▒███▒▒███ ▒███    ▒███ ▒██████████
-             ▒███▒▒███    ▒███  ▒███ ▒███ ▒███    ▒███ ▒███▒▒▒▒▒███
-             ▒███ ▒▒███   ▒███  ▒███ ▒███ ▒███    ███  ▒███    ▒███
-             █████ ▒▒████ █████ ▒███████  ██████████   ███████████
-            ▒▒▒▒▒   ▒▒▒▒ ▒▒▒▒▒  ▒███▒▒▒  ▒▒▒▒▒▒▒▒▒▒   ▒▒▒▒▒▒▒▒▒▒▒
-                                ▒███
-                                █████
-                               ▒▒▒▒▒
-            Version: 0.1.0-beta.0");
-        Gen::init();
-        // 若lockfile的文件夹路径不存在则创建
-        fs::create_dir_all(&config.dir_path)?;
-        let lock_file = lock_or_time_out(
-            &config.path().join(DEFAULT_LOCK_FILE)
-        ).await?;
+    /// 追加数据
+    ///
+    /// 返回该数据写入时所分配的Sequence
+    /// deadline仅作用于写入WAL前的等待
+    ///
+    /// 取消安全: 写入WAL至写入MemTable之间不存在await，因此在任意await处丢弃该Future时，
+    /// 数据要么均未写入，要么已同时写入WAL与MemTable，其后仅等待组同步落盘，
+    /// 因此开启组同步时数据可能在落盘前即可被读取
+    pub(crate) async fn append_cmd_data(&self, data: KeyValue, deadline: Option<Instant>) -> Result<i64> {
+        self.append_with_options(data, &WriteOptions { deadline, ack: Ack::Wal }).await
+    }
 
-        let inner = Arc::new(StoreInner::new(config.clone()).await?);
+    /// 以写入选项追加数据，返回前按其确认级别等待WAL的写入
+    async fn append_with_options(&self, data: KeyValue, options: &WriteOptions) -> Result<i64> {
+        with_deadline(options.deadline, self.inner.wait_recovered()).await??;
 
-        let mut compactor = Compactor::new(
-            Arc::clone(&inner),
-        );
+        // Wal与MemTable双写
+        let (ticket, seq_id) = self.inner.log_data(data.clone())?;
+        let key_value = self.inner.keyspace_events.is_subscribed()
+            .then(|| data.clone());
+        let data_len = self.inner.insert_data_with_seq(data, seq_id)?;
+        self.inner.keyspace_events.publish_writes(key_value.iter());
 
-        let (task_tx, mut task_rx) = unbounded_channel();
+        is_exceeded_then_minor(
+            data_len,
+            &self.compactor_tx,
+            &self.inner
+        ).await?;
+        if let Some(ticket) = ticket {
+            self.inner.wal_sync_with(ticket, options.ack).await?;
+        }
 
-        let _ignore = tokio::spawn(async move {
-            while let Some(CompactTask::Flush(option_tx)) = task_rx.recv().await {
-                if let Err(err) = compactor.check_then_compaction(option_tx).await {
-                    error!("[Compactor][compaction][error happen]: {:?}", err);
-                }
-            }
-        });
+        Ok(seq_id)
+    }
 
-        Ok(LsmStore { inner, lock_file, compactor_tx: task_tx })
+    /// 设置键值对，并返回此次写入的Sequence
+    #[inline]
+    pub async fn set_with_sequence(&self, key: &[u8], value: Bytes) -> Result<i64> {
+        self.set_with_options(key, value, &WriteOptions::default()).await
     }
 
-    pub(crate) fn config(&self) -> &Config {
-        &self.inner.config
+    /// 以写入选项设置键值对，并返回此次写入的Sequence
+    ///
+    /// 确认级别在当前配置下无法达到时返回`KernelError::NotSupport`
+    #[inline]
+    pub async fn set_with_options(&self, key: &[u8], value: Bytes, options: &WriteOptions) -> Result<i64> {
+        let start = Instant::now();
+        options.ack.check(self.is_enable_wal())?;
+        self.write_hooks().check(iter::once((key, Some(value.as_ref()))))?;
+        with_deadline(options.deadline, self.write_quotas().throttle(iter::once((key, Some(value.as_ref()))))).await?;
+        let _guard = with_deadline(options.deadline, self.latches.lock(key)).await?;
+
+        if let Some(loader) = &self.config().cache_loader {
+            loader.write(key, Some(&value)).await?;
+        }
+        let result = self.set_locked(key, value, options).await;
+        self.inner.stats.set_latency.record(start.elapsed());
+
+        result
     }
 
-    pub(crate) fn wal(&self) -> &Arc<LogLoader> {
-        &self.inner.wal
+    /// 设置键值对，需持有该Key的Latch
+    async fn set_locked(&self, key: &[u8], value: Bytes, options: &WriteOptions) -> Result<i64> {
+        match self.config().value_chunk_size {
+            Some(chunk_size) => self.set_chunked(key, value, chunk_size, options).await,
+            None => self.append_with_options((Bytes::copy_from_slice(key), Some(value)), options).await,
+        }
     }
 
-    fn mem_table(&self) -> &MemTable {
-        &self.inner.mem_table
+    /// 本地不存在Key时由`Config::cache_loader`加载，并写入本地
+    ///
+    /// 加载在该Key的Latch内进行，使并发的读取仅加载一次，且加载期间的写入不会被加载到的旧数据覆盖
+    async fn load_through(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        if self.config().cache_loader.is_none() {
+            return Ok(None);
+        }
+        let _guard = self.latches.lock(key).await;
+
+        // 等待Latch期间该Key可能已被其他读取加载或被写入
+        self.get_locked(key).await
     }
 
-    async fn current_version(&self) -> Arc<Version> {
-        self.inner.ver_status.current().await
+    /// 由`Config::cache_loader`加载Key并写入本地，需持有该Key的Latch
+    async fn load_locked(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        let Some(loader) = &self.config().cache_loader else {
+            return Ok(None);
+        };
+        let value = loader.load(key).await?;
+        if let Some(value) = &value {
+            let _ = self.set_locked(key, value.clone(), &WriteOptions::default()).await?;
+        }
+
+        Ok(value)
     }
 
-    /// 创建事务
+    /// 删除键值对，并返回此次删除的Sequence
     #[inline]
-    pub async fn new_transaction(&self) -> Transaction {
-        let _ = self.mem_table().tx_count
-            .fetch_add(1, Ordering::Release);
+    pub async fn remove_with_sequence(&self, key: &[u8]) -> Result<i64> {
+        self.remove_with_options(key, &WriteOptions::default()).await
+    }
 
-        Transaction {
-            store_inner: Arc::clone(&self.inner),
-            version: self.current_version().await,
-            compactor_tx: self.compactor_tx.clone(),
+    /// 以写入选项删除键值对，并返回此次删除的Sequence
+    #[inline]
+    pub async fn remove_with_options(&self, key: &[u8], options: &WriteOptions) -> Result<i64> {
+        options.ack.check(self.is_enable_wal())?;
+        self.write_hooks().check(iter::once((key, None)))?;
+        with_deadline(options.deadline, self.write_quotas().throttle(iter::once((key, None)))).await?;
+        let _guard = with_deadline(options.deadline, self.latches.lock(key)).await?;
+
+        let value = with_deadline(options.deadline, self.get_(key)).await??;
+        // 配置了加载器时，本地不存在的Key仍可能存在于数据源中，因此同样进行删除
+        match &self.config().cache_loader {
+            Some(loader) => loader.write(key, None).await?,
+            None if value.is_none() => return Err(KernelError::KeyNotFound),
+            None => (),
+        }
+        let seq_id = self.append_with_options((Bytes::copy_from_slice(key), None), options).await?;
 
-            seq_id: Sequence::create(),
-            writer_buf: SkipMap::new(),
+        if let Some(value) = &value {
+            if let Some(manifest) = self.manifest_of(key, Some(value)).await? {
+                self.release_chunks(key, &manifest, &[]).await?;
+                let _ = self.append_cmd_data((chunk::manifest_mark_key(key, value), None), None).await?;
+            }
         }
+        Ok(seq_id)
     }
 
+    /// 以读取选项获取Key对应的Value
     #[inline]
-    pub async fn disk_iter(&self) -> Result<VersionIter> {
-        VersionIter::new(self.current_version().await).await
+    pub async fn get_with_options(&self, key: &[u8], options: &ReadOptions) -> Result<Option<Bytes>> {
+        with_deadline(options.deadline, self.get(key)).await?
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Config {
-    /// 数据目录地址
-    pub(crate) dir_path: PathBuf,
-    /// WAL数量阈值
-    pub(crate) wal_threshold: usize,
-    /// SSTable文件大小
-    pub(crate) sst_file_size: usize,
-    /// Minor触发数据长度
-    pub(crate) minor_threshold_with_len: usize,
-    /// Major压缩触发阈值
-    pub(crate) major_threshold_with_sst_size: usize,
-    /// Major压缩选定文件数
-    /// Major压缩时通过选定个别SSTable(即该配置项)进行下一级的SSTable选定，
-    /// 并将确定范围的下一级SSTable再次对当前等级的SSTable进行范围判定，
-    /// 找到最合理的上下级数据范围并压缩
-    pub(crate) major_select_file_size: usize,
-    /// 每级SSTable数量倍率
-    pub(crate) level_sst_magnification: usize,
-    /// 布隆过滤器 期望的错误概率
-    pub(crate) desired_error_prob: f64,
-    /// Block数据块缓存的数量
-    /// 由于使用ShardingCache作为并行，以16为单位
-    pub(crate) block_cache_size: usize,
-    /// 用于缓存SSTable
-    pub(crate) table_cache_size: usize,
-    /// 开启wal日志写入
-    /// 在开启状态时，会在SSTable文件读取失败时生效，避免数据丢失
-    /// 不过在设备IO容易成为瓶颈，或使用多节点冗余写入时，建议关闭以提高写入性能
-    pub(crate) wal_enable: bool,
-    /// WAL写入类型
-    /// 直写: Direct
-    /// 异步: Buf、Mmap
-    pub(crate) wal_io_type: IoType,
-    /// 每个Block之间的大小, 单位为B
-    pub(crate) block_size: usize,
-    /// DataBloc的前缀压缩Restart间隔
-    pub(crate) data_restart_interval: usize,
-    /// IndexBloc的前缀压缩Restart间隔
-    pub(crate) index_restart_interval: usize,
-}
-
-impl Config {
+    /// 以写入选项持久化MemTable
+    ///
+    /// 等待压缩超时后返回`KernelError::TimedOut`，此时压缩仍会在后台完成
     #[inline]
-    pub fn new(path: impl Into<PathBuf> + Send) -> Config {
-        Config {
-            dir_path: path.into(),
-            minor_threshold_with_len: DEFAULT_MINOR_THRESHOLD_WITH_LEN,
-            wal_threshold: DEFAULT_WAL_THRESHOLD,
-            sst_file_size: DEFAULT_SST_FILE_SIZE,
-            major_threshold_with_sst_size: DEFAULT_MAJOR_THRESHOLD_WITH_SST_SIZE,
-            major_select_file_size: DEFAULT_MAJOR_SELECT_FILE_SIZE,
-            level_sst_magnification: DEFAULT_LEVEL_SST_MAGNIFICATION,
-            desired_error_prob: DEFAULT_DESIRED_ERROR_PROB,
-            block_cache_size: DEFAULT_BLOCK_CACHE_SIZE,
-            table_cache_size: DEFAULT_TABLE_CACHE_SIZE,
-            wal_enable: true,
-            wal_io_type: DEFAULT_WAL_IO_TYPE,
-            block_size: block::DEFAULT_BLOCK_SIZE,
-            data_restart_interval: block::DEFAULT_DATA_RESTART_INTERVAL,
-            index_restart_interval: block::DEFAULT_INDEX_RESTART_INTERVAL,
-        }
+    pub async fn flush_with_options(&self, options: &WriteOptions) -> Result<()> {
+        with_deadline(options.deadline, self.inner.wait_recovered()).await??;
+        let (tx, rx) = oneshot::channel();
+        let start = Instant::now();
+
+        let _ = self.inner.stats.compaction_pending.fetch_add(1, Ordering::Relaxed);
+        self.compactor_tx.send(CompactTask::Flush(Some(tx)))?;
+        self.wal().flush()?;
+
+        with_deadline(options.deadline, rx).await?
+            .map_err(|_| KernelError::ChannelClose)?;
+        Statistics::add_micros(&self.inner.stats.flush_wait_micros, start.elapsed());
+        self.inner.stats.flush_latency.record(start.elapsed());
+
+        Ok(())
     }
 
-    pub(crate) fn path(&self) -> &PathBuf {
-        &self.dir_path
+    /// 获取最新已分配的Sequence
+    ///
+    /// Sequence单调递增，可作为上层复制、缓存与幂等的提交标记
+    /// 每次写入的Sequence随数据记录于WAL中，因此重启后保证大于崩溃前已写入WAL的Sequence
+    #[inline]
+    pub fn latest_sequence(&self) -> i64 {
+        Sequence::latest()
     }
 
+    /// 读取Key在sequence时的数据，用于审计与调试，如以此前记录的Sequence读取数分钟前的数据
+    ///
+    /// 未开启`Config::version_retention`时仅可读取最近一次Flush之后的Sequence，
+    /// sequence早于可读取的范围时返回`KernelError::SequenceNotRetained`
     #[inline]
-    pub fn dir_path(mut self, dir_path: PathBuf) -> Self {
-        self.dir_path = dir_path;
-        self
+    pub async fn get_at(&self, key: &[u8], sequence: i64) -> Result<Option<Bytes>> {
+        let value = loop {
+            let epoch = self.mem_table().epoch();
+            let version = self.current_version().await;
+            let Some(point) = self.mem_table().find_at(key, sequence, epoch)? else {
+                // 期间发生了Flush，重新获取Version
+                continue
+            };
+            break match point.found {
+                Some(value) => value,
+                None => point.base.unwrap_or(version)
+                    .find_data_for_ss_tables(key).await?,
+            };
+        };
+
+        self.resolve_chunks(key, value).await
     }
 
+    /// 以Key升序读取sequence时Key大于start的至多limit条数据，start为None时由首个Key开始
+    ///
+    /// 可读取的范围与`LsmStore::get_at`相同
     #[inline]
-    pub fn minor_threshold_with_len(mut self, minor_threshold_with_len: usize) -> Self {
-        self.minor_threshold_with_len = minor_threshold_with_len;
-        self
+    pub async fn scan_at(
+        &self,
+        start: Option<&[u8]>,
+        limit: usize,
+        sequence: i64
+    ) -> Result<Vec<(Bytes, Bytes)>> {
+        let mut items = Vec::with_capacity(min(limit, 1024));
+
+        if limit > 0 {
+            self.scan_with(start, sequence, |key, value| {
+                items.push((key, value));
+                items.len() < limit
+            }).await?;
+        }
+
+        Ok(items)
+    }
+
+    /// 以Key升序遍历sequence时Key大于start的数据，`fn_scan`返回false时停止遍历
+    ///
+    /// 数据由归并迭代器逐条读取，无需将其全部载入内存，可读取的范围与`LsmStore::get_at`相同
+    pub(crate) async fn scan_with<F>(
+        &self,
+        start: Option<&[u8]>,
+        sequence: i64,
+        mut fn_scan: F
+    ) -> Result<()>
+        where F: FnMut(Bytes, Bytes) -> bool
+    {
+        let (point, version) = loop {
+            let epoch = self.mem_table().epoch();
+            let version = self.current_version().await;
+            if let Some(point) = self.mem_table().range_at(start, sequence, epoch)? {
+                break (point, version);
+            }
+        };
+        let version = point.base.unwrap_or(version);
+        let all_ss_tables = version.get_all_ss_tables().await;
+
+        let mut sources = vec![MergeSource::mem(point.found)];
+        sources.append(&mut MergeSource::tables(&all_ss_tables, &version.block_cache, start)?);
+
+        let mut merging_iter = MergingIter::new(sources);
+
+        while let Some((key, value)) = merging_iter.next_err()? {
+            if key.starts_with(INTERNAL_KEY_PREFIX) {
+                continue
+            }
+            if let Some(value) = self.resolve_chunks(&key, value).await? {
+                if !fn_scan(key, value) {
+                    break
+                }
+            }
+        }
+
+        Ok(())
     }
 
+    /// 获取Key被保留的至多limit个历史版本，由新至旧排列，用于审计日志等场景
+    ///
+    /// 仅返回内存中的版本，即最近一次Flush之后的写入与`Config::version_retention`内被保留的写入，
+    /// SSTable中不保存Sequence与写入时间，因此已落盘且未被保留的版本不会返回，可通过`get`读取其最新的数据
+    ///
+    /// 注意: 分块存储的Value返回其分块清单，而不进行拼接
     #[inline]
-    pub fn block_size(mut self, block_size: usize) -> Self {
-        self.block_size = block_size;
-        self
+    pub fn get_versions(&self, key: &[u8], limit: usize) -> Vec<KeyVersion> {
+        self.mem_table().versions(key, limit)
+            .into_iter()
+            .map(|(sequence, timestamp, value)| KeyVersion { sequence, timestamp, value })
+            .collect_vec()
     }
 
+    /// 等待至Store已应用sequence，超出等待时长时返回false
+    ///
+    /// 以写入返回的Sequence作为令牌进行等待，可保证此后的读取可见该写入(read-your-writes)，
+    /// 对于以复制方式应用写入的Store，Sequence在应用复制的数据后推进
     #[inline]
-    pub fn data_restart_interval(mut self, data_restart_interval: usize) -> Self {
-        self.data_restart_interval = data_restart_interval;
-        self
+    pub async fn wait_for_sequence(&self, sequence: i64, timeout: Duration) -> bool {
+        let wait_applied = async {
+            loop {
+                let notified = self.inner.applied.notified();
+
+                if self.applied_sequence() >= sequence {
+                    return;
+                }
+                notified.await;
+            }
+        };
+
+        tokio::time::timeout(timeout, wait_applied).await.is_ok()
     }
 
+    /// 该Store已写入MemTable(即已可见)的最大Sequence
+    ///
+    /// 与`latest_sequence`不同，不包含其他Store或仍在写入中的Sequence
     #[inline]
-    pub fn index_restart_interval(mut self, index_restart_interval: usize) -> Self {
-        self.index_restart_interval = index_restart_interval;
-        self
+    pub fn applied_sequence(&self) -> i64 {
+        self.inner.applied_seq.load(Ordering::Acquire)
+    }
+
+    fn is_enable_wal(&self) -> bool {
+        self.config().wal_enable
     }
 
+    /// 使用Config进行LsmStore初始化
     #[inline]
-    pub fn wal_threshold(mut self, wal_threshold: usize) -> Self {
-        self.wal_threshold = wal_threshold;
-        self
+    pub async fn open_with_config(config: Config) -> Result<Self> where Self: Sized {
+        info!("
+            █████   ████  ███            ██████████   ███████████
+            ▒▒███   ███▒  ▒▒▒            ▒▒███▒▒▒▒███ ▒▒███▒▒▒▒▒███
+             ▒███  ███    ████  ████████  ▒███   ▒▒███ ▒███    ▒███
+             ▒███████    ▒▒███ ▒▒███▒▒███ ▒███    ▒███ ▒██████████
+             ▒███▒▒███    ▒███  ▒███ ▒███ ▒███    ▒███ ▒███▒▒▒▒▒███
+             ▒███ ▒▒███   ▒███  ▒███ ▒███ ▒███    ███  ▒███    ▒███
+             █████ ▒▒████ █████ ▒███████  ██████████   ███████████
+            ▒▒▒▒▒   ▒▒▒▒ ▒▒▒▒▒  ▒███▒▒▒  ▒▒▒▒▒▒▒▒▒▒   ▒▒▒▒▒▒▒▒▒▒▒
+                                ▒███
+                                █████
+                               ▒▒▒▒▒
+            Version: 0.1.0-beta.0");
+        Gen::init();
+        config.spawner.check()?;
+        // 若lockfile的文件夹路径不存在则创建
+        fs::create_dir_all(&config.dir_path)?;
+        let lock_file = lock_or_time_out(
+            &config.path().join(DEFAULT_LOCK_FILE)
+        ).await?;
+        PersistentOptions::check_and_persist(&config)?;
+
+        let (inner, pending_gen) = StoreInner::new(config.clone()).await?;
+        let inner = Arc::new(inner);
+
+        if let Some(gen) = pending_gen {
+            let replay_inner = Arc::clone(&inner);
+            config.spawner.spawn(async move {
+                replay_inner.replay_wal(gen);
+            });
+        }
+
+        let mut compactor = Compactor::new(
+            Arc::clone(&inner),
+        );
+
+        let (task_tx, mut task_rx) = unbounded_channel();
+
+        if let Some(period) = config.stats_dump_period {
+            config.spawner.spawn(dump_periodically(Arc::downgrade(&inner), period));
+        }
+        if let Some(rate) = config.scrub_rate {
+            config.spawner.spawn(scrub_periodically(Arc::downgrade(&inner), rate));
+        }
+
+        let stats_inner = Arc::clone(&inner);
+        config.spawner.spawn(async move {
+            while let Some(task) = task_rx.recv().await {
+                match task {
+                    CompactTask::Flush(option_tx) => {
+                        let _ = stats_inner.stats.compaction_pending.fetch_sub(1, Ordering::Relaxed);
+                        if let Err(err) = compactor.check_then_compaction(option_tx).await {
+                            error!("[Compactor][compaction][error happen]: {:?}", err);
+                        }
+                    }
+                    CompactTask::Ingest(values, tx) => {
+                        let _ignore = tx.send(compactor.ingest(values).await);
+                    }
+                    CompactTask::Quarantine(gen, tx) => {
+                        let _ignore = tx.send(compactor.quarantine(gen).await);
+                    }
+                    CompactTask::Migrate(to_version, tx) => {
+                        let _ignore = tx.send(compactor.migrate(to_version).await);
+                    }
+                    CompactTask::DropPrefix(prefix, tx) => {
+                        let _ignore = tx.send(compactor.drop_prefix(prefix).await);
+                    }
+                    CompactTask::PrefixTtl(prefix, ttl, tx) => {
+                        let _ignore = tx.send(compactor.set_prefix_ttl(prefix, ttl).await);
+                    }
+                    CompactTask::PrefixOptions(prefix, table_options, tx) => {
+                        let _ignore = tx.send(compactor.set_prefix_options(prefix, table_options).await);
+                    }
+                }
+            }
+        });
+
+        Ok(LsmStore {
+            inner,
+            lock_file,
+            compactor_tx: task_tx,
+            latches: Arc::new(Latches::new(DEFAULT_LATCHES_SIZE, RandomState::default())),
+        })
     }
 
+    /// 当Key对应的Value与expected一致时，将其替换为new
+    ///
+    /// expected为None时表示期望Key不存在，new为None时表示删除该Key
+    /// 返回值表示是否替换成功
     #[inline]
-    pub fn sst_file_size(mut self, sst_file_size: usize) -> Self {
-        self.sst_file_size = sst_file_size;
-        self
+    pub async fn compare_and_swap(
+        &self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Option<Bytes>
+    ) -> Result<bool> {
+        let _guard = self.latches.lock(key).await;
+
+        if self.get_locked(key).await?.as_deref() != expected {
+            return Ok(false);
+        }
+        if expected.is_some() || new.is_some() {
+            self.write_hooks().check(iter::once((key, new.as_deref())))?;
+            let _ = self.append_cmd_data((Bytes::copy_from_slice(key), new), None).await?;
+        }
+
+        Ok(true)
     }
 
+    /// 通过fn_update对Key对应的Value进行原子性的更新
+    ///
+    /// fn_update传入当前的Value，返回None时删除该Key
+    /// 返回值为更新前的Value
     #[inline]
-    pub fn major_threshold_with_sst_size(mut self, major_threshold_with_sst_size: usize) -> Self {
-        self.major_threshold_with_sst_size = major_threshold_with_sst_size;
-        self
+    pub async fn fetch_update<F>(&self, key: &[u8], fn_update: F) -> Result<Option<Bytes>>
+        where F: FnOnce(Option<&Bytes>) -> Option<Bytes> + Send
+    {
+        let _guard = self.latches.lock(key).await;
+
+        let old_value = self.get_locked(key).await?;
+        let new_value = fn_update(old_value.as_ref());
+
+        if old_value.is_some() || new_value.is_some() {
+            self.write_hooks().check(iter::once((key, new_value.as_deref())))?;
+            let _ = self.append_cmd_data((Bytes::copy_from_slice(key), new_value), None).await?;
+        }
+
+        Ok(old_value)
     }
 
+    /// 对Key对应的数值进行原子性的自增，并返回自增后的数值
+    ///
+    /// 数值以i64的大端序字节存储，Key不存在时视为0
     #[inline]
-    pub fn major_select_file_size(mut self, major_select_file_size: usize) -> Self {
-        self.major_select_file_size = major_select_file_size;
-        self
+    pub async fn increment(&self, key: &[u8], delta: i64) -> Result<i64> {
+        let _guard = self.latches.lock(key).await;
+
+        let value = match self.get_locked(key).await? {
+            Some(bytes) => <[u8; 8]>::try_from(bytes.as_ref())
+                .map(i64::from_be_bytes)
+                .map_err(|_| KernelError::ValueNotNumeric)?
+                .checked_add(delta)
+                .ok_or(KernelError::NumericOverflow)?,
+            None => delta
+        };
+        let value_bytes = Bytes::copy_from_slice(&value.to_be_bytes());
+        self.write_hooks().check(iter::once((key, Some(value_bytes.as_ref()))))?;
+        let _ = self.append_cmd_data((Bytes::copy_from_slice(key), Some(value_bytes)), None).await?;
+
+        Ok(value)
     }
 
+    /// 获取计数器、集合与列表等数据结构的视图
     #[inline]
-    pub fn level_sst_magnification(mut self, level_sst_magnification: usize) -> Self {
-        self.level_sst_magnification = level_sst_magnification;
-        self
+    pub fn structures(&self) -> Structures<'_> {
+        Structures::new(self)
     }
 
+    /// 获取租约与分布式锁的视图
     #[inline]
-    pub fn desired_error_prob(mut self, desired_error_prob: f64) -> Self {
-        self.desired_error_prob = desired_error_prob;
-        self
+    pub fn leases(&self) -> Leases<'_> {
+        Leases::new(self)
     }
 
+    /// 订阅Key空间的通知，详见`KeyspaceEvent`
+    ///
+    /// 仅包含订阅后的写入、删除与过期，接收落后超过缓冲的事件数量时收到`RecvError::Lagged`，
+    /// 此时订阅方应视其缓存的所有Key均已失效
     #[inline]
-    pub fn block_cache_size(mut self, cache_size: usize) -> Self {
-        self.block_cache_size = cache_size;
-        self
+    pub fn subscribe_keyspace(&self) -> broadcast::Receiver<KeyspaceEvent> {
+        self.inner.keyspace_events.subscribe()
     }
 
+    /// 获取列族的管理视图，用于列族的创建、获取与删除
     #[inline]
-    pub fn table_cache_size(mut self, cache_size: usize) -> Self {
-        self.table_cache_size = cache_size;
-        self
+    pub fn column_families(&self) -> ColumnFamilies<'_> {
+        ColumnFamilies::new(self)
     }
 
+    /// 按上次关闭时保存的热度图将Block预先加载至缓存，返回加载的Block数量
+    ///
+    /// 用于读多的服务在重启后避免冷缓存导致的延迟陡增，热度图不存在时不进行加载
+    ///
+    /// Tips: 加载期间会同步读取SSTable，建议在开始提供服务前调用
     #[inline]
-    pub fn wal_enable(mut self, wal_enable: bool) -> Self {
-        self.wal_enable = wal_enable;
-        self
+    pub async fn warm_up(&self) -> Result<usize> {
+        heat_map::warm_up(&self.inner.config, &*self.current_version().await).await
     }
 
+    /// 将Block缓存中的Block作为热度图写入数据目录，返回写入的Block数量
+    ///
+    /// 开启`Config::persist_heat_map`时关闭Store时会自动写入
     #[inline]
-    pub fn wal_io_type(mut self, wal_io_type: IoType) -> Self {
-        self.wal_io_type = wal_io_type;
-        self
+    pub fn save_heat_map(&self) -> Result<usize> {
+        heat_map::save(&self.inner.config, self.inner.ver_status.block_cache())
     }
-}
 
-/// 插入时Sequence id生成器
-///
-/// 与`Gen`比较大的不同在于
-/// - `Sequence`随着每次重启都会重置为0，而seq上限很高，可以在此次运行时生成有序且不相同的id
-/// - `Gen`以时间戳为基础，每次保证每次重启都保证时间有序，但不足以作为Seq的生成，因为上限较低
-pub(crate) struct Sequence {}
+    /// 将Key对应的Value视为JSON文档，以JSON Pointer将value写入其中的指定位置
+    ///
+    /// Key不存在时视为空文档，路径上不存在的字段会以对象的形式创建，数组的下标为`-`时追加元素
+    /// 与`increment`相同，在Key的分片锁内完成读取与写入，调用方无需读取整个文档再写回
+    #[cfg(feature = "doc")]
+    #[inline]
+    pub async fn json_set(&self, key: &[u8], pointer: &str, value: serde_json::Value) -> Result<()> {
+        let _guard = self.latches.lock(key).await;
+
+        let mut doc = match self.get_locked(key).await? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => serde_json::Value::Null,
+        };
+        crate::kernel::lsm::doc::pointer_set(&mut doc, pointer, value)?;
+        let _ = self.append_cmd_data(
+            (Bytes::copy_from_slice(key), Some(Bytes::from(serde_json::to_vec(&doc)?))),
+            None
+        ).await?;
 
-pub(crate) struct Gen {}
+        Ok(())
+    }
 
-impl Sequence {
-    pub(crate) fn create() -> i64 {
-        SEQ_COUNT.fetch_add(1, Ordering::Relaxed)
+    /// 读取Key对应JSON文档中JSON Pointer所指向的值，Key或该位置不存在时返回None
+    #[cfg(feature = "doc")]
+    #[inline]
+    pub async fn json_get(&self, key: &[u8], pointer: &str) -> Result<Option<serde_json::Value>> {
+        match self.get(key).await? {
+            Some(bytes) => {
+                let mut doc: serde_json::Value = serde_json::from_slice(&bytes)?;
+                Ok(doc.pointer_mut(pointer).map(serde_json::Value::take))
+            }
+            None => Ok(None),
+        }
     }
-}
 
-impl Gen {
-    /// 将GEN_BUF初始化至当前时间戳
-    ///
-    /// 与create_gen相对应，需要将GEN初始化为当前时间戳
-    pub(crate) fn init() {
-        GEN_BUF.store(Local::now().timestamp_millis(), Ordering::Relaxed);
+    pub(crate) fn config(&self) -> &Config {
+        &self.inner.config
     }
 
-    pub(crate) fn create() -> i64 {
-        GEN_BUF.fetch_add(1, Ordering::Relaxed)
+    pub(crate) fn write_hooks(&self) -> &WriteHooks {
+        &self.inner.write_hooks
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::thread::sleep;
-    use std::time::{Duration, Instant};
-    use bytes::Bytes;
-    use itertools::Itertools;
-    use tempfile::TempDir;
-    use crate::kernel::lsm::lsm_kv::{Config, Gen, LsmStore, Sequence};
-    use crate::kernel::{KVStore, Result};
+    pub(crate) fn write_quotas(&self) -> &WriteQuotas {
+        &self.inner.write_quotas
+    }
 
-    #[test]
-    fn test_seq_create() {
-        let i_1 = Sequence::create();
+    pub(crate) fn keyspace_events(&self) -> &KeyspaceEvents {
+        &self.inner.keyspace_events
+    }
 
-        let i_2 = Sequence::create();
+    pub(crate) fn wal(&self) -> &Arc<LogLoader> {
+        &self.inner.wal
+    }
 
-        assert!(i_1 < i_2);
+    fn mem_table(&self) -> &MemTable {
+        &self.inner.mem_table
     }
 
-    #[test]
-    fn test_gen_create() {
-        let i_1 = Gen::create();
+    pub(crate) async fn current_version(&self) -> Arc<Version> {
+        self.inner.ver_status.current().await
+    }
 
-        let i_2 = Gen::create();
+    /// 创建事务
+    #[inline]
+    pub async fn new_transaction(&self) -> Transaction {
+        let seq_id = Sequence::create();
+        self.mem_table().register_snapshot(seq_id);
 
-        assert!(i_1 < i_2);
+        Transaction {
+            store_inner: Arc::clone(&self.inner),
+            version: self.current_version().await,
+            compactor_tx: self.compactor_tx.clone(),
+            latches: Arc::clone(&self.latches),
 
-        sleep(Duration::from_millis(10));
-        Gen::init();
+            seq_id,
+            writer_buf: SkipMap::new(),
+            undo_log: Vec::new(),
+            is_released: false,
+        }
+    }
+
+    /// 最旧的存活事务快照的Sequence，不存在快照时返回None
+    ///
+    /// 早于该Sequence的删除标记才可在压缩时丢弃，该值长时间不变通常意味着存在泄漏的事务，
+    /// 可通过`Config::max_snapshot_age`强制释放
+    #[inline]
+    pub fn oldest_pinned_sequence(&self) -> Option<i64> {
+        self.mem_table().oldest_snapshot()
+    }
+
+    /// 在运行时修改选项，无需重启即可在缓存与其他组件间调整内存
+    #[inline]
+    pub async fn set_options(&self, options: MutableOptions) -> Result<()> {
+        let inner = &self.inner;
+
+        if let Some(capacity) = options.block_cache_capacity {
+            inner.ver_status.current().await
+                .block_cache
+                .set_capacity(capacity / inner.config.block_size)?;
+        }
+        if let Some(cache_size) = options.table_cache_size {
+            inner.ver_status.set_table_cache_size(cache_size).await?;
+        }
+
+        Ok(())
+    }
+
+    /// 获取统计数据快照
+    #[inline]
+    pub async fn statistics(&self) -> StatsSnapshot {
+        self.inner.statistics().await
+    }
+
+    /// 获取近期读取次数最多的n个Key及其估算的读取次数，以次数降序排列
+    ///
+    /// 需通过`Config::hot_key_capacity`开启，未开启时返回空
+    #[inline]
+    pub fn top_keys(&self, n: usize) -> Vec<(Bytes, u64)> {
+        self.inner.hot_keys.as_ref()
+            .map(|hot_keys| hot_keys.top_keys(n))
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub async fn disk_iter(&self) -> Result<VersionIter> {
+        VersionIter::new(
+            self.current_version().await,
+            self.inner.config.max_sequential_skip
+        ).await
+    }
+
+    /// 本地数据损坏时从`Config::read_fallback`读取，并标记损坏的SSTable以待隔离
+    ///
+    /// 未配置或读取失败时返回本地的错误
+    async fn read_fallback(&self, key: &[u8], err: KernelError) -> Result<Option<Bytes>> {
+        let config = &self.inner.config;
+        let Some(read_fallback) = &config.read_fallback else {
+            return Err(err);
+        };
+        let gen = match &err {
+            KernelError::Corrupted { gen, .. } => Some(*gen),
+            _ => None,
+        };
+        if let Some(gen) = gen.filter(|_| config.quarantine_corrupted) {
+            self.inner.mark_corrupted(gen);
+        }
+
+        let result = read_fallback.get(key).await;
+        // 取回的数据于重建该SSTable时写回，置于原SSTable的位置以免覆盖其后的写入
+        if let (Some(gen), Ok(Some(value))) = (gen, &result) {
+            if config.quarantine_corrupted && config.rebuild_quarantined {
+                self.inner.record_fallback_read(gen, key, value.clone());
+            }
+        }
+        warn!("[LsmStore][read_fallback][SSTable: {:?}][Recovered: {}]: {:?}", gen, result.is_ok(), err);
+        config.notify_read_fallback(&ReadFallbackEvent {
+            key: Bytes::copy_from_slice(key),
+            gen,
+            reason: err.to_string(),
+            recovered: result.is_ok(),
+        });
+
+        result.map_err(|_| err)
+    }
+
+    /// 隔离指定Gen的SSTable
+    ///
+    /// 将其复制至`quarantine`目录后从Version中移除，使读取不再因其损坏而失败，
+    /// 开启`Config::rebuild_quarantined`时会以其中可读取的数据进行重建，详见返回的报告
+    #[inline]
+    pub async fn quarantine(&self, gen: i64) -> Result<QuarantineReport> {
+        let (tx, rx) = oneshot::channel();
+
+        self.compactor_tx.send(CompactTask::Quarantine(gen, tx))?;
+        rx.await.map_err(|_| KernelError::ChannelClose)?
+    }
+
+    /// 丢弃以prefix开头的所有Key，返回随即删除的SSTable数量
+    ///
+    /// 仅包含该前缀数据的SSTable直接删除，其余数据在此后的压缩中被丢弃
+    pub(crate) async fn drop_prefix(&self, prefix: Bytes) -> Result<usize> {
+        let (tx, rx) = oneshot::channel();
+
+        self.compactor_tx.send(CompactTask::DropPrefix(prefix, tx))?;
+        rx.await.map_err(|_| KernelError::ChannelClose)?
+    }
+
+    /// 记录prefix中数据的存活时长，此后的压缩中丢弃其中过期的数据
+    pub(crate) async fn set_prefix_ttl(&self, prefix: Bytes, ttl: Duration) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.compactor_tx.send(CompactTask::PrefixTtl(prefix, ttl, tx))?;
+        rx.await.map_err(|_| KernelError::ChannelClose)?
+    }
+
+    /// 记录prefix中数据的SSTable选项，此后的Flush与压缩中其数据以该选项写入单独的SSTable
+    pub(crate) async fn set_prefix_options(&self, prefix: Bytes, table_options: TableOptions) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.compactor_tx.send(CompactTask::PrefixOptions(prefix, table_options, tx))?;
+        rx.await.map_err(|_| KernelError::ChannelClose)?
+    }
+
+    /// 将格式版本低于to_version的SSTable重写为当前格式，返回重写的SSTable数量
+    ///
+    /// to_version不能高于`FORMAT_VERSION`，重写以SSTable为单位逐个进行并持久化，
+    /// 中断后再次执行时仅会重写剩余的旧格式SSTable
+    #[inline]
+    pub async fn migrate(&self, to_version: u32) -> Result<usize> {
+        let (tx, rx) = oneshot::channel();
+
+        self.compactor_tx.send(CompactTask::Migrate(to_version, tx))?;
+        let migrated = rx.await.map_err(|_| KernelError::ChannelClose)??;
+        PersistentOptions::migrated(self.config(), to_version)?;
+
+        Ok(migrated)
+    }
+
+    /// 批量导入数据
+    ///
+    /// 数据需以Key严格升序排列，会被直接构建为SSTable并导入，跳过MemTable与WAL，
+    /// 适用于初次的大量数据导入，导入的数据会覆盖此前已写入的同Key数据
+    #[inline]
+    pub async fn ingest(&self, data: Vec<(Bytes, Bytes)>) -> Result<()> {
+        if data.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(KernelError::IngestNotSorted);
+        }
+        self.write_hooks().check(data.iter().map(|(key, value)| (key.as_ref(), Some(value.as_ref()))))?;
+        self.inner.wait_recovered().await?;
+        let keys = self.inner.row_cache.is_some()
+            .then(|| data.iter().map(|(key, _)| key.clone()).collect_vec());
+        let values: Vec<KeyValue> = data.into_iter()
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+        let (tx, rx) = oneshot::channel();
+
+        self.compactor_tx.send(CompactTask::Ingest(values, tx))?;
+        let result = rx.await.map_err(|_| KernelError::ChannelClose)?;
+        // 导入的数据直接写入SSTable，同样需使对应的缓存行失效
+        self.inner.invalidate_rows(keys.iter().flatten());
+
+        result
+    }
+
+    /// 原子性地写入批量数据，并返回此次写入的Sequence
+    ///
+    /// 批量数据可跨越多个列族，以单条记录写入WAL并以同一Sequence写入MemTable，
+    /// 因此读取时要么看到全部写入要么均未看到，崩溃恢复时同样整体重放或整体丢弃，
+    /// 且与其前后的写入保持在WAL中的顺序
+    ///
+    /// Tips: 与`set`不同，批量数据中的大Value不会被分块存储
+    #[inline]
+    pub async fn write(&self, batch: WriteBatch) -> Result<i64> {
+        if batch.is_empty() {
+            return Ok(self.applied_sequence());
+        }
+        let batch_data = batch.into_data();
+        self.write_hooks().check(batch_data.iter().map(|(key, value)| (key.as_ref(), value.as_deref())))?;
+        self.write_quotas().throttle(batch_data.iter().map(|(key, value)| (key.as_ref(), value.as_deref()))).await;
+        self.inner.wait_recovered().await?;
+
+        // Wal与MemTable双写
+        let (ticket, seq_id) = self.inner.log_batch_data(batch_data.clone())?;
+        let published = self.inner.keyspace_events.is_subscribed()
+            .then(|| batch_data.clone());
+        let data_len = self.inner.insert_batch_data(batch_data, seq_id)?;
+        self.inner.keyspace_events.publish_writes(published.iter().flatten());
+
+        is_exceeded_then_minor(data_len, &self.compactor_tx, &self.inner).await?;
+        if let Some(ticket) = ticket {
+            self.inner.wal_sync(ticket).await?;
+        }
+
+        Ok(seq_id)
+    }
+
+    /// 两阶段提交: 预提交批量数据
+    ///
+    /// 批量数据(Value为None时表示删除)会持久化至WAL但不可见，
+    /// 直至通过`commit_prepared`提交或`rollback_prepared`回滚，重启后仍可通过`prepared_tokens`取回
+    #[inline]
+    pub async fn prepare(&self, batch: Vec<(Bytes, Option<Bytes>)>) -> Result<PreparedToken> {
+        if !self.is_enable_wal() {
+            return Err(KernelError::NotSupport("two-phase commit requires WAL"));
+        }
+        self.write_hooks().check(batch.iter().map(|(key, value)| (key.as_ref(), value.as_deref())))?;
+        self.inner.wait_recovered().await?;
+        let token = Gen::create();
+
+        let ticket = self.wal().prepare(token, batch)?;
+        // 预提交的数据需在返回前落盘，以保证协调者决定提交后数据不会丢失
+        self.inner.wal_sync_with(ticket, Ack::Fsync).await?;
+
+        Ok(PreparedToken(token))
+    }
+
+    /// 两阶段提交: 提交预提交的批量数据，并返回此次提交的Sequence
+    #[inline]
+    pub async fn commit_prepared(&self, token: PreparedToken) -> Result<i64> {
+        self.inner.wait_recovered().await?;
+
+        let (ticket, seq_id, batch_data) = self.wal().commit_prepared(token.0)?;
+        let published = self.inner.keyspace_events.is_subscribed()
+            .then(|| batch_data.clone());
+        let data_len = self.inner.insert_batch_data(batch_data, seq_id)?;
+        self.inner.keyspace_events.publish_writes(published.iter().flatten());
+
+        is_exceeded_then_minor(data_len, &self.compactor_tx, &self.inner).await?;
+        self.inner.wal_sync(ticket).await?;
+
+        Ok(seq_id)
+    }
+
+    /// 两阶段提交: 回滚预提交的批量数据
+    #[inline]
+    pub async fn rollback_prepared(&self, token: PreparedToken) -> Result<()> {
+        self.inner.wait_recovered().await?;
+
+        let ticket = self.wal().rollback_prepared(token.0)?;
+        self.inner.wal_sync(ticket).await
+    }
+
+    /// 已预提交但尚未提交或回滚的凭证，用于重启后向协调者确认其结果
+    #[inline]
+    pub async fn prepared_tokens(&self) -> Result<Vec<PreparedToken>> {
+        self.inner.wait_recovered().await?;
+
+        Ok(self.wal().prepared_tokens()
+            .into_iter()
+            .map(PreparedToken)
+            .collect())
+    }
+
+    /// 分页范围扫描
+    ///
+    /// 以Key升序返回至多limit条数据，并在数据未读尽时返回下一页的游标，
+    /// 游标记录了本页最后的Key与首页的Sequence，因此无需在请求之间持有迭代器
+    ///
+    /// 注意: SSTable中不保存Sequence，因此Sequence仅对内存表中的数据生效，
+    /// 已落盘的数据以每次请求时的当前Version读取
+    #[inline]
+    pub async fn scan_page(
+        &self,
+        cursor: Option<&ScanCursor>,
+        limit: usize
+    ) -> Result<(Vec<(Bytes, Bytes)>, Option<ScanCursor>)> {
+        let seq_id = cursor.map_or_else(Sequence::latest, |cursor| cursor.seq_id);
+        let start = cursor.map(|cursor| cursor.last_key.as_slice());
+
+        if limit == 0 {
+            return Ok((vec![], cursor.cloned()));
+        }
+
+        let version = self.current_version().await;
+        let all_ss_tables = version.get_all_ss_tables().await;
+        let block_cache = &version.block_cache;
+
+        let mut sources = vec![
+            MergeSource::mem(self.mem_table().range_with_sequence(start, seq_id))
+        ];
+        sources.append(&mut MergeSource::tables(&all_ss_tables, block_cache, start)?);
+
+        let mut merging_iter = MergingIter::new(sources);
+        let mut items = Vec::with_capacity(limit);
+
+        while let Some((key, value)) = merging_iter.next_err()? {
+            if key.starts_with(INTERNAL_KEY_PREFIX) {
+                continue
+            }
+            if let Some(value) = value {
+                items.push((key, value));
+
+                if items.len() >= limit {
+                    break
+                }
+            }
+        }
+
+        let next_cursor = (items.len() >= limit)
+            .then(|| items.last())
+            .flatten()
+            .map(|(key, _)| ScanCursor { last_key: key.to_vec(), seq_id });
+        let mut resolved_items = Vec::with_capacity(items.len());
+        for (key, value) in items {
+            // 读取分块期间被删除的Key不再返回
+            if let Some(value) = self.resolve_chunks(&key, Some(value)).await? {
+                resolved_items.push((key, value));
+            }
+        }
+
+        Ok((resolved_items, next_cursor))
+    }
+
+    /// 以Key升序读取以prefix开头的至多limit条数据，after不为None时仅读取Key大于after的数据
+    ///
+    /// 与`scan_page`不同，不跳过内部Key且不读取分块，用于内部Key的范围读取
+    pub(crate) async fn scan_prefix(
+        &self,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize
+    ) -> Result<Vec<(Bytes, Bytes)>> {
+        // 由prefix去除末尾字节后的位置开始，使Key与prefix相同的数据同样被读取
+        let start = after.unwrap_or(&prefix[..prefix.len().saturating_sub(1)]);
+        let version = self.current_version().await;
+        let all_ss_tables = version.get_all_ss_tables().await;
+
+        let mut sources = vec![
+            MergeSource::mem(self.mem_table().range_with_sequence(Some(start), Sequence::latest()))
+        ];
+        sources.append(&mut MergeSource::tables(&all_ss_tables, &version.block_cache, Some(start))?);
+
+        let mut merging_iter = MergingIter::new(sources);
+        let mut items = Vec::new();
+
+        while items.len() < limit {
+            let Some((key, value)) = merging_iter.next_err()? else {
+                break
+            };
+            if !key.starts_with(prefix) {
+                if key.as_ref() < prefix {
+                    continue
+                }
+                break
+            }
+            if let Some(value) = value {
+                items.push((key, value));
+            }
+        }
+
+        Ok(items)
+    }
+}
+
+/// Key的一个历史版本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyVersion {
+    /// 写入时分配的Sequence，可用于`LsmStore::get_at`
+    pub sequence: i64,
+    /// 写入时间(Unix时间戳，毫秒)，由WAL重放的数据为重放时的时间
+    pub timestamp: i64,
+    /// 写入的Value，为None时表示该版本为删除
+    pub value: Option<Bytes>,
+}
+
+impl KeyVersion {
+    /// 该版本是否为删除
+    #[inline]
+    pub fn is_remove(&self) -> bool {
+        self.value.is_none()
+    }
+}
+
+/// 分页扫描的游标
+///
+/// 对调用方不透明，可通过`to_bytes`与`from_bytes`在请求之间传递
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanCursor {
+    last_key: Vec<u8>,
+    seq_id: i64,
+}
+
+impl ScanCursor {
+    /// 由Key大于last_key的数据开始读取，seq_id为读取内存表时的Sequence
+    pub(crate) fn new(last_key: Vec<u8>, seq_id: i64) -> Self {
+        ScanCursor { last_key, seq_id }
+    }
+
+    #[inline]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    #[inline]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// 两阶段提交中预提交批量数据的凭证
+///
+/// 可通过`id`与`from_id`由协调者持久化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PreparedToken(i64);
+
+impl PreparedToken {
+    #[inline]
+    pub fn id(&self) -> i64 {
+        self.0
+    }
+
+    #[inline]
+    pub fn from_id(id: i64) -> Self {
+        PreparedToken(id)
+    }
+}
+
+/// 压缩方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum CompactionStyle {
+    /// 分层压缩，数据由Level 0逐层向下合并
+    #[default]
+    Leveled,
+    /// 时间窗口压缩，适用于Key以时间戳为前缀的时序数据
+    ///
+    /// SSTable以Flush时所在的时间窗口分组，窗口结束后该窗口在Level 0中的SSTable被合并至Level 1，
+    /// 不同窗口的数据不会被合并，因此窗口结束超过ttl后可以直接删除整个SSTable，ttl为None时不删除
+    ///
+    /// Tips: ttl以整个窗口为单位生效，过期的SSTable于下一次压缩检查时被删除，在此之前其中的数据仍可被读取
+    TimeWindow { window: Duration, ttl: Option<Duration> },
+}
+
+/// DataBlock的压缩算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Compression {
+    /// 不压缩，适用于已自行压缩或难以压缩的Value
+    None,
+    /// LZ4，Store默认的压缩算法
+    Lz4,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 数据目录地址
+    pub(crate) dir_path: PathBuf,
+    /// WAL数量阈值
+    pub(crate) wal_threshold: usize,
+    /// SSTable文件大小
+    /// 同时作为各Level目标文件大小的基数
+    pub(crate) sst_file_size: usize,
+    /// 每级SSTable目标文件大小倍率
+    /// Level n的压缩产出以`sst_file_size * target_file_size_multiplier^n`进行切分
+    pub(crate) target_file_size_multiplier: usize,
+    /// Minor触发数据长度
+    pub(crate) minor_threshold_with_len: usize,
+    /// Major压缩触发阈值
+    pub(crate) major_threshold_with_sst_size: usize,
+    /// Major压缩选定文件数
+    /// Major压缩时通过选定个别SSTable(即该配置项)进行下一级的SSTable选定，
+    /// 并将确定范围的下一级SSTable再次对当前等级的SSTable进行范围判定，
+    /// 找到最合理的上下级数据范围并压缩
+    pub(crate) major_select_file_size: usize,
+    /// 每级SSTable数量倍率
+    pub(crate) level_sst_magnification: usize,
+    /// 布隆过滤器 期望的错误概率
+    pub(crate) desired_error_prob: f64,
+    /// Block数据块缓存的数量
+    /// 由于使用ShardingCache作为并行，以16为单位
+    pub(crate) block_cache_size: usize,
+    /// IndexBlock缓存的数量，与DataBlock的缓存相互独立
+    /// 由于使用ShardingCache作为并行，以16为单位
+    pub(crate) index_cache_size: usize,
+    /// 用于缓存SSTable
+    pub(crate) table_cache_size: usize,
+    /// 开启wal日志写入
+    /// 在开启状态时，会在SSTable文件读取失败时生效，避免数据丢失
+    /// 不过在设备IO容易成为瓶颈，或使用多节点冗余写入时，建议关闭以提高写入性能
+    pub(crate) wal_enable: bool,
+    /// WAL写入类型
+    /// 直写: Direct
+    /// 异步: Buf、Mmap
+    pub(crate) wal_io_type: IoType,
+    /// WAL写入缓冲区大小，仅在WAL写入类型为Buf时生效
+    pub(crate) wal_buffer_size: usize,
+    /// 每个WAL文件预先分配的磁盘空间, 单位为B，为0时不预分配
+    pub(crate) wal_preallocate_size: usize,
+    /// WAL组同步的等待窗口, 单位为微秒，为None时写入不等待同步
+    /// 窗口内多个写入方的fsync会被合并为一次，以有界的延迟换取同步写入的吞吐
+    pub(crate) wal_sync_window_micros: Option<u64>,
+    /// WAL单个分段文件的大小上限, 单位为B，为None时不分段
+    pub(crate) wal_segment_size: Option<usize>,
+    /// 当前MemTable对应的WAL大小上限, 单位为B，超出时即使MemTable未满也触发Minor压缩
+    pub(crate) max_wal_size: Option<usize>,
+    /// WAL文件删除前的归档回调
+    pub(crate) wal_archiver: Option<WalArchiver>,
+    /// 启动时在后台重放WAL，使Store可更快地开始提供读取
+    /// 重放完成前，读取仅可见已落盘的数据，写入会等待重放完成
+    pub(crate) background_wal_replay: bool,
+    /// 事件监听器
+    pub(crate) event_listeners: Vec<Arc<dyn EventListener>>,
+    /// 写入前的钩子及其作用范围
+    pub(crate) write_hooks: Vec<(HookScope, Arc<dyn WriteHook>)>,
+    /// 列族名称 -> 列族的写入配额
+    pub(crate) cf_write_quotas: HashMap<Vec<u8>, Quota>,
+    /// 每个Block之间的大小, 单位为B
+    pub(crate) block_size: usize,
+    /// DataBloc的前缀压缩Restart间隔
+    pub(crate) data_restart_interval: usize,
+    /// IndexBloc的前缀压缩Restart间隔
+    pub(crate) index_restart_interval: usize,
+    /// 后台任务派发器
+    pub(crate) spawner: Spawner,
+    /// 时间窗口压缩与租约等逻辑所使用的时钟
+    pub(crate) clock: Arc<dyn Clock>,
+    /// WAL、Manifest与SSTable的文件读写所使用的故障注入器，为None时不注入
+    pub(crate) fault_injector: Option<Arc<FaultInjector>>,
+    /// 统计数据输出周期，为None时不输出
+    pub(crate) stats_dump_period: Option<Duration>,
+    /// 输出统计数据时同时写入数据目录下的JSON文件
+    pub(crate) stats_dump_json: bool,
+    /// MemTable与各缓存的全局内存预算，为None时不限制
+    pub(crate) memory_budget: Option<usize>,
+    /// Level 0内部压缩触发阈值，为None时不进行
+    pub(crate) level_0_intra_threshold: Option<usize>,
+    /// 自适应压缩时Major压缩阈值的调整范围，为None时固定使用`major_threshold_with_sst_size`
+    pub(crate) adaptive_compaction: Option<RangeInclusive<usize>>,
+    /// 压缩方式
+    pub(crate) compaction_style: CompactionStyle,
+    /// Level 1-6小文件合并触发阈值，为None时不进行
+    pub(crate) small_file_merge_threshold: Option<usize>,
+    /// 各Level的SSTable存储路径，以索引对应Level，超出部分的Level使用最后一个路径
+    /// 为空时所有Level均使用`dir_path/ss_table`
+    pub(crate) level_paths: Vec<PathBuf>,
+    /// 二级Block缓存目录(如本地SSD)，为None时不启用
+    pub(crate) secondary_cache_path: Option<PathBuf>,
+    /// 二级Block缓存容量, 单位为B
+    pub(crate) secondary_cache_size: usize,
+    /// 迭代时连续跳过删除标记的阈值，达到后直接定位至下一个未被删除的数据
+    pub(crate) max_sequential_skip: usize,
+    /// 不存在Key的缓存时长，为None时不缓存
+    pub(crate) negative_cache_ttl: Option<Duration>,
+    /// 不存在Key的缓存数量
+    /// 由于使用ShardingCache作为并行，以16为单位
+    pub(crate) negative_cache_size: usize,
+    /// 行缓存的数量，为None时不启用
+    /// 由于使用ShardingCache作为并行，以16为单位
+    pub(crate) row_cache_size: Option<usize>,
+    /// 热点Key统计所保留的Key数量，为None时不启用
+    pub(crate) hot_key_capacity: Option<usize>,
+    /// 大Value的分块大小，超出该大小的Value分块存储，为None时不分块
+    pub(crate) value_chunk_size: Option<usize>,
+    /// 是否对分块进行去重
+    pub(crate) value_dedup: bool,
+    /// 事务快照的存活时长上限，为None时不限制
+    pub(crate) max_snapshot_age: Option<Duration>,
+    /// 时间点读取的版本保留时长，为None时不保留
+    pub(crate) version_retention: Option<Duration>,
+    /// 后台校验的速率，单位为B/s，为None时不进行
+    pub(crate) scrub_rate: Option<u64>,
+    /// 是否隔离后台校验所发现的损坏SSTable
+    pub(crate) quarantine_corrupted: bool,
+    /// 本地数据损坏时的读取来源，为None时直接返回错误
+    pub(crate) read_fallback: Option<Arc<dyn ReadFallback>>,
+    /// 读穿与写穿的外部数据源，为None时不启用
+    pub(crate) cache_loader: Option<Arc<dyn CacheLoader>>,
+    /// 隔离SSTable时是否以其中可读取的数据进行重建
+    pub(crate) rebuild_quarantined: bool,
+    /// 关闭时是否将Block缓存的热度图写入数据目录，供`LsmStore::warm_up`使用
+    pub(crate) persist_heat_map: bool,
+    /// 是否在压缩生成的SSTable写入Manifest前重新读取并校验
+    pub(crate) verify_compaction_output: bool,
+    /// 为Manifest回滚而暂不删除的SSTable组数，为0时不保留
+    pub(crate) manifest_history: usize,
+}
+
+impl Config {
+    #[inline]
+    pub fn new(path: impl Into<PathBuf> + Send) -> Config {
+        Config {
+            dir_path: path.into(),
+            minor_threshold_with_len: DEFAULT_MINOR_THRESHOLD_WITH_LEN,
+            wal_threshold: DEFAULT_WAL_THRESHOLD,
+            sst_file_size: DEFAULT_SST_FILE_SIZE,
+            target_file_size_multiplier: DEFAULT_TARGET_FILE_SIZE_MULTIPLIER,
+            major_threshold_with_sst_size: DEFAULT_MAJOR_THRESHOLD_WITH_SST_SIZE,
+            major_select_file_size: DEFAULT_MAJOR_SELECT_FILE_SIZE,
+            level_sst_magnification: DEFAULT_LEVEL_SST_MAGNIFICATION,
+            desired_error_prob: DEFAULT_DESIRED_ERROR_PROB,
+            block_cache_size: DEFAULT_BLOCK_CACHE_SIZE,
+            index_cache_size: DEFAULT_INDEX_CACHE_SIZE,
+            table_cache_size: DEFAULT_TABLE_CACHE_SIZE,
+            wal_enable: true,
+            wal_io_type: DEFAULT_WAL_IO_TYPE,
+            wal_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            wal_preallocate_size: DEFAULT_WAL_PREALLOCATE_SIZE,
+            wal_sync_window_micros: None,
+            wal_segment_size: None,
+            max_wal_size: None,
+            wal_archiver: None,
+            background_wal_replay: false,
+            event_listeners: Vec::new(),
+            write_hooks: Vec::new(),
+            cf_write_quotas: HashMap::new(),
+            block_size: block::DEFAULT_BLOCK_SIZE,
+            data_restart_interval: block::DEFAULT_DATA_RESTART_INTERVAL,
+            index_restart_interval: block::DEFAULT_INDEX_RESTART_INTERVAL,
+            spawner: Spawner::default(),
+            clock: Arc::new(SystemClock),
+            fault_injector: None,
+            stats_dump_period: None,
+            stats_dump_json: false,
+            memory_budget: None,
+            level_0_intra_threshold: None,
+            adaptive_compaction: None,
+            compaction_style: CompactionStyle::default(),
+            small_file_merge_threshold: None,
+            level_paths: Vec::new(),
+            secondary_cache_path: None,
+            secondary_cache_size: DEFAULT_SECONDARY_CACHE_SIZE,
+            max_sequential_skip: DEFAULT_MAX_SEQUENTIAL_SKIP,
+            negative_cache_ttl: None,
+            negative_cache_size: DEFAULT_NEGATIVE_CACHE_SIZE,
+            row_cache_size: None,
+            hot_key_capacity: None,
+            value_chunk_size: None,
+            value_dedup: false,
+            max_snapshot_age: None,
+            version_retention: None,
+            scrub_rate: None,
+            quarantine_corrupted: false,
+            read_fallback: None,
+            cache_loader: None,
+            rebuild_quarantined: false,
+            persist_heat_map: false,
+            verify_compaction_output: false,
+            manifest_history: 0,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &PathBuf {
+        &self.dir_path
+    }
+
+    /// 该Level的SSTable目标文件大小
+    pub(crate) fn target_file_size(&self, level: usize) -> usize {
+        self.sst_file_size.saturating_mul(
+            self.target_file_size_multiplier.saturating_pow(level as u32)
+        )
+    }
+
+    /// 该Level中小SSTable的大小上限，小于该值的SSTable会参与内部压缩与小文件合并
+    pub(crate) fn small_file_size(&self, level: usize) -> u64 {
+        (self.target_file_size(level) / 2) as u64
+    }
+
+    /// SSTable的各存储路径
+    ///
+    /// 序号0为默认路径`dir_path/ss_table`，其后依次为`level_paths`
+    pub(crate) fn sst_paths(&self) -> Vec<PathBuf> {
+        iter::once(self.dir_path.join(DEFAULT_SS_TABLE_PATH))
+            .chain(self.level_paths.iter().cloned())
+            .collect()
+    }
+
+    /// 获取Level对应的SSTable存储路径序号
+    pub(crate) fn sst_path_id(&self, level: usize) -> usize {
+        if self.level_paths.is_empty() {
+            DEFAULT_SST_PATH_ID
+        } else {
+            min(level, self.level_paths.len() - 1) + 1
+        }
+    }
+
+    #[inline]
+    pub fn dir_path(mut self, dir_path: PathBuf) -> Self {
+        self.dir_path = dir_path;
+        self
+    }
+
+    #[inline]
+    pub fn minor_threshold_with_len(mut self, minor_threshold_with_len: usize) -> Self {
+        self.minor_threshold_with_len = minor_threshold_with_len;
+        self
+    }
+
+    #[inline]
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    #[inline]
+    pub fn data_restart_interval(mut self, data_restart_interval: usize) -> Self {
+        self.data_restart_interval = data_restart_interval;
+        self
+    }
+
+    #[inline]
+    pub fn index_restart_interval(mut self, index_restart_interval: usize) -> Self {
+        self.index_restart_interval = index_restart_interval;
+        self
+    }
+
+    #[inline]
+    pub fn wal_threshold(mut self, wal_threshold: usize) -> Self {
+        self.wal_threshold = wal_threshold;
+        self
+    }
+
+    #[inline]
+    pub fn sst_file_size(mut self, sst_file_size: usize) -> Self {
+        self.sst_file_size = sst_file_size;
+        self
+    }
+
+    /// 设置每级SSTable目标文件大小倍率
+    ///
+    /// 压缩产出会以`sst_file_size * target_file_size_multiplier^level`切分为多个SSTable，
+    /// 默认为1，即所有Level使用相同的目标文件大小
+    #[inline]
+    pub fn target_file_size_multiplier(mut self, target_file_size_multiplier: usize) -> Self {
+        self.target_file_size_multiplier = target_file_size_multiplier.max(1);
+        self
+    }
+
+    #[inline]
+    pub fn major_threshold_with_sst_size(mut self, major_threshold_with_sst_size: usize) -> Self {
+        self.major_threshold_with_sst_size = major_threshold_with_sst_size;
+        self
+    }
+
+    #[inline]
+    pub fn major_select_file_size(mut self, major_select_file_size: usize) -> Self {
+        self.major_select_file_size = major_select_file_size;
+        self
+    }
+
+    #[inline]
+    pub fn level_sst_magnification(mut self, level_sst_magnification: usize) -> Self {
+        self.level_sst_magnification = level_sst_magnification;
+        self
+    }
+
+    #[inline]
+    pub fn desired_error_prob(mut self, desired_error_prob: f64) -> Self {
+        self.desired_error_prob = desired_error_prob;
+        self
+    }
+
+    #[inline]
+    pub fn block_cache_size(mut self, cache_size: usize) -> Self {
+        self.block_cache_size = cache_size;
+        self
+    }
+
+    /// 设置IndexBlock缓存的数量
+    ///
+    /// IndexBlock缓存于独立的分区中，不会被扫描读取的DataBlock驱逐
+    #[inline]
+    pub fn index_cache_size(mut self, cache_size: usize) -> Self {
+        self.index_cache_size = cache_size;
+        self
+    }
+
+    #[inline]
+    pub fn table_cache_size(mut self, cache_size: usize) -> Self {
+        self.table_cache_size = cache_size;
+        self
+    }
+
+    #[inline]
+    pub fn wal_enable(mut self, wal_enable: bool) -> Self {
+        self.wal_enable = wal_enable;
+        self
+    }
+
+    #[inline]
+    pub fn wal_io_type(mut self, wal_io_type: IoType) -> Self {
+        self.wal_io_type = wal_io_type;
+        self
+    }
+
+    #[inline]
+    pub fn wal_buffer_size(mut self, wal_buffer_size: usize) -> Self {
+        self.wal_buffer_size = wal_buffer_size;
+        self
+    }
+
+    #[inline]
+    pub fn wal_preallocate_size(mut self, wal_preallocate_size: usize) -> Self {
+        self.wal_preallocate_size = wal_preallocate_size;
+        self
+    }
+
+    /// 开启WAL同步写入，每次写入在返回前均已同步至磁盘
+    ///
+    /// 为0时每次写入立即同步，但并发的写入依旧会被合并
+    #[inline]
+    pub fn wal_sync_window_micros(mut self, wal_sync_window_micros: u64) -> Self {
+        self.wal_sync_window_micros = Some(wal_sync_window_micros);
+        self
+    }
+
+    /// 设置WAL单个分段文件的大小上限，避免单个WAL文件无限增长
+    #[inline]
+    pub fn wal_segment_size(mut self, wal_segment_size: usize) -> Self {
+        self.wal_segment_size = Some(wal_segment_size);
+        self
+    }
+
+    /// 设置当前MemTable对应的WAL大小上限，以限制重启时重放WAL的耗时
+    ///
+    /// 大量覆盖写入同一Key时MemTable的大小增长缓慢，而WAL仍记录每次写入，
+    /// 超出上限时即使MemTable未满也切换MemTable并以新的WAL记录
+    #[inline]
+    pub fn max_wal_size(mut self, max_wal_size: usize) -> Self {
+        self.max_wal_size = Some(max_wal_size);
+        self
+    }
+
+    #[inline]
+    pub fn wal_archiver(mut self, wal_archiver: WalArchiver) -> Self {
+        self.wal_archiver = Some(wal_archiver);
+        self
+    }
+
+    #[inline]
+    pub fn background_wal_replay(mut self, background_wal_replay: bool) -> Self {
+        self.background_wal_replay = background_wal_replay;
+        self
+    }
+
+    #[inline]
+    pub fn add_event_listener(mut self, listener: Arc<dyn EventListener>) -> Self {
+        self.event_listeners.push(listener);
+        self
+    }
+
+    /// 添加写入前的钩子，Key以prefix开头的写入(包括删除)在写入WAL前由其校验，被拒绝时返回`KernelError::WriteRejected`
+    ///
+    /// 作用于`set`、`remove`、条件写入、批量写入、事务、两阶段提交的预提交与`ingest`，
+    /// 列族与数据结构等内部Key的写入不经过该钩子
+    #[inline]
+    pub fn add_write_hook(mut self, prefix: &[u8], hook: Arc<dyn WriteHook>) -> Self {
+        self.write_hooks.push((HookScope::Prefix(prefix.to_vec()), hook));
+        self
+    }
+
+    /// 添加列族的写入钩子，与`Config::add_write_hook`相同，钩子收到的Key与Value不包含列族的前缀与到期时间
+    #[inline]
+    pub fn add_cf_write_hook(mut self, name: &[u8], hook: Arc<dyn WriteHook>) -> Self {
+        self.write_hooks.push((HookScope::ColumnFamily(name.to_vec()), hook));
+        self
+    }
+
+    /// 设置列族的写入配额，以每秒写入的Key数量与Key、Value的字节数之和限制该列族的写入速率
+    ///
+    /// 作用于`set`、`remove`、批量写入与事务中该列族的写入，超出配额时写入方在获取Key的锁前等待，
+    /// 等待的次数与时长记录于`ColumnFamily::write_stats`
+    #[inline]
+    pub fn column_family_write_quota(mut self, name: &[u8], quota: Quota) -> Self {
+        let _ = self.cf_write_quotas.insert(name.to_vec(), quota);
+        self
+    }
+
+    pub(crate) fn notify_recovery(&self, event: &RecoveryEvent) {
+        for listener in &self.event_listeners {
+            listener.on_recovery(event);
+        }
+    }
+
+    pub(crate) fn notify_scrub(&self, event: &ScrubEvent) {
+        for listener in &self.event_listeners {
+            listener.on_scrub(event);
+        }
+    }
+
+    pub(crate) fn notify_quarantine(&self, report: &QuarantineReport) {
+        for listener in &self.event_listeners {
+            listener.on_quarantine(report);
+        }
+    }
+
+    pub(crate) fn notify_read_fallback(&self, event: &ReadFallbackEvent) {
+        for listener in &self.event_listeners {
+            listener.on_read_fallback(event);
+        }
+    }
+
+    /// 替换后台任务派发器，用于非tokio运行时的环境，或以`DeterministicExecutor`进行确定性的调度
+    #[inline]
+    pub fn spawner(mut self, spawner: Spawner) -> Self {
+        self.spawner = spawner;
+        self
+    }
+
+    /// 替换时钟，测试中可使用`VirtualClock`控制时间窗口压缩与租约的过期
+    #[inline]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 为文件读写注入延迟与错误，用于演练存储设备性能下降或故障时服务的表现
+    ///
+    /// 持有injector即可在运行时调整各类IO操作的故障，见`FaultInjector::set_fault`
+    #[inline]
+    pub fn fault_injector(mut self, injector: Arc<FaultInjector>) -> Self {
+        self.fault_injector = Some(injector);
+        self
+    }
+
+    /// 开启后台任务以该周期将统计数据输出至日志
+    #[inline]
+    pub fn stats_dump_period(mut self, period: Duration) -> Self {
+        self.stats_dump_period = Some(period);
+        self
+    }
+
+    #[inline]
+    pub fn stats_dump_json(mut self, stats_dump_json: bool) -> Self {
+        self.stats_dump_json = stats_dump_json;
+        self
+    }
+
+    /// 设置全局内存预算，超出时提前触发MemTable的Flush
+    ///
+    /// 缓存本身由`block_cache_size`与`table_cache_size`限制容量，预算需大于缓存的占用才能生效
+    #[inline]
+    pub fn memory_budget(mut self, memory_budget: usize) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// 设置Level 0内部压缩触发阈值
+    ///
+    /// Level 0中最新的连续小SSTable数量达到该值且尚未触发Major压缩时，将其合并为较大的SSTable
+    #[inline]
+    pub fn level_0_intra_threshold(mut self, level_0_intra_threshold: usize) -> Self {
+        self.level_0_intra_threshold = Some(level_0_intra_threshold);
+        self
+    }
+
+    /// 开启自适应压缩，根据读写比例与压缩积压在bounds内自动调整Major压缩阈值
+    ///
+    /// 以`major_threshold_with_sst_size`作为初始值，读多写少时降低阈值以减少读放大，
+    /// 写多读少或写入方等待压缩时提高阈值以减少写放大
+    #[inline]
+    pub fn adaptive_compaction(mut self, bounds: RangeInclusive<usize>) -> Self {
+        self.adaptive_compaction = Some(bounds);
+        self
+    }
+
+    /// 设置压缩方式，默认为`CompactionStyle::Leveled`
+    ///
+    /// 时间窗口压缩不进行Level 0内部压缩、小文件合并与Level 1之后的Major压缩
+    #[inline]
+    pub fn compaction_style(mut self, compaction_style: CompactionStyle) -> Self {
+        self.compaction_style = compaction_style;
+        self
+    }
+
+    /// 设置Level 1-6小文件合并触发阈值
+    ///
+    /// 同一Level中Key范围相邻的连续小SSTable数量达到该值时，将其合并为接近`sst_file_size`的SSTable
+    #[inline]
+    pub fn small_file_merge_threshold(mut self, small_file_merge_threshold: usize) -> Self {
+        self.small_file_merge_threshold = Some(small_file_merge_threshold);
+        self
+    }
+
+    /// 设置各Level的SSTable存储路径
+    ///
+    /// 以索引对应Level，超出部分的Level使用最后一个路径，
+    /// 如`vec![nvme.clone(), nvme, hdd]`使Level 0-1位于nvme，其余Level位于hdd
+    #[inline]
+    pub fn level_paths(mut self, level_paths: Vec<PathBuf>) -> Self {
+        self.level_paths = level_paths;
+        self
+    }
+
+    /// 设置二级Block缓存目录
+    ///
+    /// 从SSTable中读取的DataBlock会同时写入该目录，被内存缓存驱逐后可从此处读取而不需要访问冷存储
+    #[inline]
+    pub fn secondary_cache_path(mut self, secondary_cache_path: impl Into<PathBuf>) -> Self {
+        self.secondary_cache_path = Some(secondary_cache_path.into());
+        self
+    }
+
+    #[inline]
+    pub fn secondary_cache_size(mut self, secondary_cache_size: usize) -> Self {
+        self.secondary_cache_size = secondary_cache_size;
+        self
+    }
+
+    /// 设置迭代时连续跳过删除标记的阈值
+    ///
+    /// 连续跳过的删除标记达到该值时，迭代器不再逐条读取，而是直接定位至下一个未被删除的数据
+    #[inline]
+    pub fn max_sequential_skip(mut self, max_sequential_skip: usize) -> Self {
+        self.max_sequential_skip = max_sequential_skip;
+        self
+    }
+
+    /// 开启不存在Key的缓存
+    ///
+    /// 在SSTable中确认不存在的Key会在ttl内直接返回None，以避免热点的不存在Key反复访问磁盘，
+    /// 新增SSTable(如Flush与压缩)时缓存会被清空，因此不会影响此后写入数据的可见性
+    #[inline]
+    pub fn negative_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_cache_ttl = Some(ttl);
+        self
+    }
+
+    #[inline]
+    pub fn negative_cache_size(mut self, negative_cache_size: usize) -> Self {
+        self.negative_cache_size = negative_cache_size;
+        self
+    }
+
+    /// 开启行缓存
+    ///
+    /// 缓存Key的查询结果，使热点Key的读取无需经过MemTable与SSTable，写入同一Key时对应的缓存行失效
+    #[inline]
+    pub fn row_cache_size(mut self, row_cache_size: usize) -> Self {
+        self.row_cache_size = Some(row_cache_size);
+        self
+    }
+
+    /// 开启热点Key统计，保留近期读取次数最多的hot_key_capacity个Key
+    ///
+    /// 通过`LsmStore::top_keys`或统计数据中的`hot_keys`获取，用于定位造成分片不均或缓存抖动的热点Key
+    #[inline]
+    pub fn hot_key_capacity(mut self, hot_key_capacity: usize) -> Self {
+        self.hot_key_capacity = Some(hot_key_capacity);
+        self
+    }
+
+    /// 开启大Value的分块存储
+    ///
+    /// 通过set写入且超出value_chunk_size的Value会被切分为多个分块，各分块以派生的Key写入，
+    /// 原Key下仅写入分块的清单，get与scan_page时透明地拼接，
+    /// 避免巨大的Value产生巨大的Block，导致缓存失效与压缩时内存暴涨
+    ///
+    /// 注意: 开启后每次set与remove均需读取旧Value以删除不再被引用的分块，
+    /// 事务、批量写入与导入不进行分块，len中包含分块的数量
+    #[inline]
+    pub fn value_chunk_size(mut self, value_chunk_size: usize) -> Self {
+        self.value_chunk_size = Some(value_chunk_size);
+        self
+    }
+
+    /// 开启分块的去重，需同时开启`value_chunk_size`
+    ///
+    /// 以分块内容的摘要作为Key，使不同Key中内容相同的分块仅存储一份，
+    /// 适用于存储大量相同大对象的场景(如容器镜像层、附件等)
+    ///
+    /// 各Key对分块的引用在覆盖与删除时即被删除，而分块本身在压缩时检查引用后回收，
+    /// 因此删除后的空间在下一次压缩后才会释放
+    #[inline]
+    pub fn value_dedup(mut self, value_dedup: bool) -> Self {
+        self.value_dedup = value_dedup;
+        self
+    }
+
+    /// 设置事务快照的存活时长上限
+    ///
+    /// 存活的快照会阻止Compaction与删除标记的丢弃，超出上限的快照在Compaction时被强制释放，
+    /// 此后对该事务的读取与提交均返回`KernelError::SnapshotExpired`，
+    /// 避免泄漏的事务使Store无限增长
+    ///
+    /// 注意: 事务所持有的Version在事务被Drop前仍不会释放，其SSTable文件同样会被保留
+    #[inline]
+    pub fn max_snapshot_age(mut self, max_snapshot_age: Duration) -> Self {
+        self.max_snapshot_age = Some(max_snapshot_age);
+        self
+    }
+
+    /// 设置时间点读取的版本保留时长
+    ///
+    /// 已Flush的MemTable及其Flush前的Version在version_retention内被保留，
+    /// 使`LsmStore::get_at`与`LsmStore::scan_at`可读取该时长内的各Sequence的数据，
+    /// 代价为保留的MemTable所占用的内存与被旧Version引用的SSTable所占用的磁盘空间
+    ///
+    /// 注意: 保留的MemTable仅在下一次Flush时清理，因此实际保留的时长可能更长
+    #[inline]
+    pub fn version_retention(mut self, version_retention: Duration) -> Self {
+        self.version_retention = Some(version_retention);
+        self
+    }
+
+    /// 开启后台校验，以每秒至多scrub_rate字节的速率循环读取所有SSTable并校验其Block的CRC
+    ///
+    /// 校验失败的SSTable通过`EventListener::on_scrub`报告，而不会被移出Version，
+    /// 避免在未确认的情况下丢失其中未损坏的数据
+    #[inline]
+    pub fn scrub_rate(mut self, scrub_rate: u64) -> Self {
+        self.scrub_rate = Some(scrub_rate);
+        self
+    }
+
+    /// 后台校验发现损坏的SSTable时，于下一次压缩时将其隔离，而非仅进行报告
+    ///
+    /// 隔离的结果通过`EventListener::on_quarantine`报告
+    #[inline]
+    pub fn quarantine_corrupted(mut self, enable: bool) -> Self {
+        self.quarantine_corrupted = enable;
+        self
+    }
+
+    /// 读取时本地数据损坏，则从read_fallback(如副本节点)以Key读取，并通过`EventListener::on_read_fallback`记录
+    #[inline]
+    pub fn read_fallback(mut self, read_fallback: Arc<dyn ReadFallback>) -> Self {
+        self.read_fallback = Some(read_fallback);
+        self
+    }
+
+    /// 设置外部数据源，使Store作为其读穿缓存，详见`CacheLoader`
+    ///
+    /// 注意: 配置后`remove`在本地不存在该Key时不再返回`KernelError::KeyNotFound`
+    #[inline]
+    pub fn cache_loader(mut self, cache_loader: Arc<dyn CacheLoader>) -> Self {
+        self.cache_loader = Some(cache_loader);
+        self
+    }
+
+    /// 隔离SSTable时，以其中可通过校验的数据重建新的SSTable，并写回经`Config::read_fallback`取回的数据
+    #[inline]
+    pub fn rebuild_quarantined(mut self, enable: bool) -> Self {
+        self.rebuild_quarantined = enable;
+        self
+    }
+
+    /// 关闭Store时将Block缓存中的Block作为热度图写入数据目录，重启后可通过`LsmStore::warm_up`预热缓存
+    #[inline]
+    pub fn persist_heat_map(mut self, enable: bool) -> Self {
+        self.persist_heat_map = enable;
+        self
+    }
+
+    /// 压缩(包括MemTable的Flush)生成SSTable后，写入Manifest前不经过缓存重新读取，
+    /// 校验其CRC、Key的有序性与数量是否与合并后的数据一致
+    ///
+    /// 校验失败时此次压缩被放弃并通过日志报告，原有的SSTable保持不变，
+    /// 以额外的一次读取为代价，避免写入逻辑的缺陷或硬件故障所损坏的数据替代原有的数据
+    #[inline]
+    pub fn verify_compaction_output(mut self, enable: bool) -> Self {
+        self.verify_compaction_output = enable;
+        self
+    }
+
+    /// 保留最近steps次Version变更中被删除的SSTable，
+    /// 使数据目录可通过`history::rollback_manifest`回滚，或通过`HistoricalStore`读取此前的Version
+    ///
+    /// 被保留的SSTable会占用额外的磁盘空间，直至其超出保留的次数后被删除
+    #[inline]
+    pub fn manifest_history(mut self, steps: usize) -> Self {
+        self.manifest_history = steps;
+        self
+    }
+}
+
+/// 插入时Sequence id生成器
+///
+/// 与`Gen`比较大的不同在于
+/// - `Sequence`重启时会以Manifest中持久化的最后Sequence为起点，而seq上限很高，可以生成有序且不相同的id
+/// - `Gen`以时间戳为基础，每次保证每次重启都保证时间有序，但不足以作为Seq的生成，因为上限较低
+pub(crate) struct Sequence {}
+
+pub(crate) struct Gen {}
+
+impl Sequence {
+    /// 将SEQ_COUNT推进至last_seq之后
+    ///
+    /// 仅会使SEQ_COUNT增大，避免多个Store在同一进程中时导致Sequence回退
+    pub(crate) fn init(last_seq: i64) {
+        let _ = SEQ_COUNT.fetch_max(last_seq + 1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn create() -> i64 {
+        SEQ_COUNT.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 获取最新已分配的Sequence
+    pub(crate) fn latest() -> i64 {
+        SEQ_COUNT.load(Ordering::Relaxed) - 1
+    }
+}
+
+impl Gen {
+    /// 将GEN_BUF初始化至当前时间戳
+    ///
+    /// 与create_gen相对应，需要将GEN初始化为当前时间戳
+    pub(crate) fn init() {
+        GEN_BUF.store(Local::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn create() -> i64 {
+        GEN_BUF.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
+    use bytes::Bytes;
+    use futures::future;
+    use itertools::Itertools;
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+    use crate::kernel::io::fault::{Fault, FaultInjector, IoOperation};
+    use crate::kernel::lsm::chunk;
+    use crate::kernel::lsm::chunk::ChunkManifest;
+    use crate::kernel::lsm::column_family::WriteBatch;
+    use crate::kernel::lsm::event::{EventListener, RecoveryEvent};
+    use crate::kernel::lsm::lsm_kv::{Config, Gen, LsmStore, PreparedToken, ScanCursor, Sequence};
+    use crate::kernel::lsm::options::{Ack, MutableOptions, WriteOptions};
+    use crate::kernel::lsm::stats::PerfContext;
+    use crate::kernel::utils::clock::VirtualClock;
+    use crate::kernel::utils::runtime::DeterministicExecutor;
+    use crate::kernel::{KVStore, Result};
+    use crate::KernelError;
+
+    #[test]
+    fn test_seq_create() {
+        let i_1 = Sequence::create();
+
+        let i_2 = Sequence::create();
+
+        assert!(i_1 < i_2);
+        assert!(Sequence::latest() >= i_2);
+
+        Sequence::init(i_2 + 100);
+        assert!(Sequence::create() > i_2 + 100);
+    }
+
+    #[test]
+    fn test_target_file_size() {
+        let config = Config::new("")
+            .sst_file_size(1024)
+            .target_file_size_multiplier(2);
+
+        assert_eq!(config.target_file_size(0), 1024);
+        assert_eq!(config.target_file_size(1), 2048);
+        assert_eq!(config.target_file_size(3), 8192);
+        assert_eq!(config.small_file_size(1), 1024);
+    }
+
+    #[test]
+    fn test_gen_create() {
+        let i_1 = Gen::create();
+
+        let i_2 = Gen::create();
+
+        assert!(i_1 < i_2);
+
+        sleep(Duration::from_millis(10));
+        Gen::init();
         let i_3 = Gen::create();
 
-        sleep(Duration::from_millis(10));
-        Gen::init();
-        let i_4 = Gen::create();
+        sleep(Duration::from_millis(10));
+        Gen::init();
+        let i_4 = Gen::create();
+
+        assert!(i_3 > i_2);
+        assert!(i_4 > i_3 + 1);
+    }
+
+    #[test]
+    fn test_lsm_major_compactor() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let times = 10000;
+
+            let value = b"Stray birds of summer come to my window to sing and fly away.
+            And yellow leaves of autumn, which have no songs, flutter and fall
+            there with a sign.";
+
+            let config = Config::new(temp_dir.path().to_str().unwrap())
+                .wal_enable(false)
+                .minor_threshold_with_len(1000)
+                .major_threshold_with_sst_size(4);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let mut vec_kv = Vec::new();
+
+            for i in 0..times {
+                let vec_u8 = bincode::serialize(&i)?;
+                vec_kv.push((
+                    Bytes::from(vec_u8.clone()),
+                    Bytes::from(vec_u8.into_iter()
+                        .chain(value.to_vec())
+                        .collect_vec())
+                ));
+            }
+
+            let start = Instant::now();
+            for i in 0..times {
+                kv_store.set(&vec_kv[i].0, vec_kv[i].1.clone()).await?
+            }
+            println!("[set_for][Time: {:?}]", start.elapsed());
+
+            kv_store.flush().await?;
+
+            let start = Instant::now();
+            for i in 0..times {
+                assert_eq!(kv_store.get(&vec_kv[i].0).await?, Some(vec_kv[i].1.clone()));
+            }
+            println!("[get_for][Time: {:?}]", start.elapsed());
+            kv_store.flush().await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_compare_and_swap() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            let key = b"kip_counter";
+
+            assert!(kv_store.compare_and_swap(key, None, Some(Bytes::from_static(b"1"))).await?);
+            assert!(!kv_store.compare_and_swap(key, None, Some(Bytes::from_static(b"2"))).await?);
+            assert!(!kv_store.compare_and_swap(key, Some(b"2"), Some(Bytes::from_static(b"3"))).await?);
+            assert!(kv_store.compare_and_swap(key, Some(b"1"), Some(Bytes::from_static(b"2"))).await?);
+            assert_eq!(kv_store.get(key).await?, Some(Bytes::from_static(b"2")));
+
+            assert!(kv_store.compare_and_swap(key, Some(b"2"), None).await?);
+            assert_eq!(kv_store.get(key).await?, None);
+
+            let old_value = kv_store.fetch_update(key, |value| {
+                assert!(value.is_none());
+                Some(Bytes::from_static(b"kip"))
+            }).await?;
+            assert_eq!(old_value, None);
+
+            let old_value = kv_store.fetch_update(key, |_| None).await?;
+            assert_eq!(old_value, Some(Bytes::from_static(b"kip")));
+            assert_eq!(kv_store.get(key).await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_sequence_persistence() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+
+            let seq_1 = kv_store.set_with_sequence(b"k1", Bytes::from_static(b"v1")).await?;
+            let seq_2 = kv_store.remove_with_sequence(b"k1").await?;
+            assert!(seq_1 < seq_2);
+            assert!(kv_store.latest_sequence() >= seq_2);
+            kv_store.flush().await?;
+
+            drop(kv_store);
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+
+            assert!(kv_store.set_with_sequence(b"k1", Bytes::from_static(b"v1")).await? > seq_2);
+
+            Ok(())
+        })
+    }
+
+    #[derive(Debug, Default)]
+    struct RecoveryRecorder(Mutex<Vec<RecoveryEvent>>);
 
-        assert!(i_3 > i_2);
-        assert!(i_4 > i_3 + 1);
+    impl EventListener for RecoveryRecorder {
+        fn on_recovery(&self, event: &RecoveryEvent) {
+            self.0.lock().push(event.clone());
+        }
     }
 
     #[test]
-    fn test_lsm_major_compactor() -> Result<()> {
+    fn test_background_wal_replay() -> Result<()> {
         let temp_dir = TempDir::new().expect("unable to create temporary working directory");
 
         tokio_test::block_on(async move {
-            let times = 10000;
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            for i in 0..10u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from_static(b"kip")).await?;
+            }
+            // 模拟停机异常: 数据仅存在于WAL中
+            kv_store.wal().flush()?;
+            drop(kv_store);
+
+            let recorder = Arc::new(RecoveryRecorder::default());
+            let listener: Arc<dyn EventListener> = Arc::<RecoveryRecorder>::clone(&recorder);
+            let config = Config::new(temp_dir.path())
+                .background_wal_replay(true)
+                .add_event_listener(listener);
+            let kv_store = LsmStore::open_with_config(config).await?;
 
-            let value = b"Stray birds of summer come to my window to sing and fly away.
-            And yellow leaves of autumn, which have no songs, flutter and fall
-            there with a sign.";
+            // 写入等待后台重放完成
+            kv_store.set(b"kip_key", Bytes::from_static(b"kip")).await?;
+            assert_eq!(kv_store.get(&0u32.to_be_bytes()).await?, Some(Bytes::from_static(b"kip")));
 
-            let config = Config::new(temp_dir.path().to_str().unwrap())
-                .wal_enable(false)
-                .minor_threshold_with_len(1000)
-                .major_threshold_with_sst_size(4);
+            let events = recorder.0.lock().clone();
+            assert!(matches!(events.first(), Some(RecoveryEvent::TablesOpened { tables: 0 })));
+            assert!(events.iter().any(|event| matches!(event, RecoveryEvent::WalSegmentReplayed { .. })));
+            assert!(matches!(events.last(), Some(RecoveryEvent::Completed { entries: 10, .. })));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_two_phase_commit() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            kv_store.set(b"kip_2", Bytes::from_static(b"old")).await?;
+
+            let token_1 = kv_store.prepare(vec![
+                (Bytes::from_static(b"kip_1"), Some(Bytes::from_static(b"1"))),
+            ]).await?;
+            let token_2 = kv_store.prepare(vec![
+                (Bytes::from_static(b"kip_2"), Some(Bytes::from_static(b"2"))),
+                (Bytes::from_static(b"kip_3"), Some(Bytes::from_static(b"3"))),
+            ]).await?;
+            assert_eq!(kv_store.get(b"kip_1").await?, None);
+
+            let _ = kv_store.commit_prepared(token_1).await?;
+            assert_eq!(kv_store.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert!(matches!(
+                kv_store.commit_prepared(token_1).await,
+                Err(KernelError::PreparedNotFound(_))
+            ));
+            // 切换WAL后未决的预提交数据仍被保留
+            kv_store.flush().await?;
+            drop(kv_store);
+
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            assert_eq!(kv_store.prepared_tokens().await?, vec![token_2]);
+            assert_eq!(kv_store.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert_eq!(kv_store.get(b"kip_2").await?, Some(Bytes::from_static(b"old")));
+
+            let _ = kv_store.commit_prepared(PreparedToken::from_id(token_2.id())).await?;
+            assert_eq!(kv_store.get(b"kip_2").await?, Some(Bytes::from_static(b"2")));
+            assert_eq!(kv_store.get(b"kip_3").await?, Some(Bytes::from_static(b"3")));
+
+            let token_4 = kv_store.prepare(vec![
+                (Bytes::from_static(b"kip_4"), Some(Bytes::from_static(b"4"))),
+            ]).await?;
+            kv_store.rollback_prepared(token_4).await?;
+            kv_store.wal().flush()?;
+            drop(kv_store);
+
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            assert!(kv_store.prepared_tokens().await?.is_empty());
+            assert_eq!(kv_store.get(b"kip_3").await?, Some(Bytes::from_static(b"3")));
+            assert_eq!(kv_store.get(b"kip_4").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_cancelled_write() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            // 组同步窗口远长于超时，使写入在等待落盘时被取消
+            let config = Config::new(temp_dir.path())
+                .wal_sync_window_micros(10_000_000);
             let kv_store = LsmStore::open_with_config(config).await?;
-            let mut vec_kv = Vec::new();
 
+            tokio::select! {
+                _ = kv_store.set(b"kip_1", Bytes::from_static(b"1")) => unreachable!(),
+                _ = tokio::time::sleep(Duration::from_millis(10)) => (),
+            }
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(b"kip_2", Bytes::from_static(b"2"));
+            tokio::select! {
+                _ = transaction.commit() => unreachable!(),
+                _ = tokio::time::sleep(Duration::from_millis(10)) => (),
+            }
+            // 被取消的写入已同时写入WAL与MemTable
+            assert_eq!(kv_store.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert_eq!(kv_store.get(b"kip_2").await?, Some(Bytes::from_static(b"2")));
+            kv_store.wal().flush()?;
+            drop(kv_store);
+
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            assert_eq!(kv_store.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert_eq!(kv_store.get(b"kip_2").await?, Some(Bytes::from_static(b"2")));
+
+            // 被丢弃的事务不会阻塞Compaction
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(b"kip_3", Bytes::from_static(b"3"));
+            drop(transaction);
+            kv_store.flush().await?;
+            assert_eq!(kv_store.get(b"kip_3").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_wal_sync_failed() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let injector = Arc::new(FaultInjector::new(0));
+            let config = Config::new(temp_dir.path())
+                .fault_injector(Arc::clone(&injector));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let fsync = WriteOptions::default().ack(Ack::Fsync);
+
+            injector.set_fault(IoOperation::Sync, Fault::default().error_rate(1_000_000));
+            assert!(kv_store.set_with_options(b"kip_1", Bytes::from_static(b"1"), &fsync).await.is_err());
+            injector.clear();
+
+            // 同步失败的写入已可见，而此后的写入均被拒绝
+            assert_eq!(kv_store.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert!(matches!(kv_store.set(b"kip_2", Bytes::from_static(b"2")).await, Err(KernelError::WalSyncFailed)));
+            let mut batch = WriteBatch::default();
+            let _ = batch.set(b"kip_2", Bytes::from_static(b"2"));
+            assert!(matches!(kv_store.write(batch).await, Err(KernelError::WalSyncFailed)));
+            assert_eq!(kv_store.get(b"kip_2").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_level_paths() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let hot_path = temp_dir.path().join("hot");
+            let cold_path = temp_dir.path().join("cold");
+            let config = Config::new(temp_dir.path().join("data"))
+                .wal_enable(false)
+                .minor_threshold_with_len(100)
+                .major_threshold_with_sst_size(2)
+                .level_paths(vec![hot_path.clone(), cold_path.clone()]);
+            let times = 500;
+
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
             for i in 0..times {
-                let vec_u8 = bincode::serialize(&i)?;
-                vec_kv.push((
-                    Bytes::from(vec_u8.clone()),
-                    Bytes::from(vec_u8.into_iter()
-                        .chain(value.to_vec())
-                        .collect_vec())
-                ));
+                let key = bincode::serialize(&i)?;
+                kv_store.set(&key, Bytes::from(key.clone())).await?;
             }
+            kv_store.flush().await?;
+            drop(kv_store);
 
-            let start = Instant::now();
+            // Level 0位于hot，Level 1及以上位于cold
+            assert!(fs::read_dir(&cold_path)?.next().is_some());
+
+            let kv_store = LsmStore::open_with_config(config).await?;
             for i in 0..times {
-                kv_store.set(&vec_kv[i].0, vec_kv[i].1.clone()).await?
+                let key = bincode::serialize(&i)?;
+                assert_eq!(kv_store.get(&key).await?, Some(Bytes::from(key)));
             }
-            println!("[set_for][Time: {:?}]", start.elapsed());
 
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_increment() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            let key = b"kip_counter";
+
+            let _ = future::try_join_all(
+                (0..100).map(|_| kv_store.increment(key, 2))
+            ).await?;
+            assert_eq!(kv_store.increment(key, -100).await?, 100);
+
+            kv_store.set(key, Bytes::from_static(b"kip")).await?;
+            assert!(kv_store.increment(key, 1).await.is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_wal_group_sync() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .wal_sync_window_micros(1000);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            let keys = (0..32u32).map(u32::to_be_bytes).collect_vec();
+            let _ = future::try_join_all(
+                keys.iter().map(|key| kv_store.set(key, Bytes::from_static(b"kip")))
+            ).await?;
+
+            // 并发写入的fsync被合并
+            let stats = kv_store.statistics().await;
+            assert_eq!(stats.wal_synced_writes, 32);
+            assert!(stats.wal_sync_count < 32);
+            assert!(stats.wal_writes_per_sync() > 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_max_wal_size() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let executor = DeterministicExecutor::new(42);
+            let config = Config::new(temp_dir.path())
+                .max_wal_size(16 * 1024)
+                .spawner(executor.spawner());
+            let kv_store = executor.run_until(LsmStore::open_with_config(config.clone())).await?;
+
+            // 覆盖写入同一Key时MemTable几乎不增长，由WAL的大小触发MemTable的切换
+            for i in 0..1000u32 {
+                kv_store.set(b"kip", Bytes::from(i.to_be_bytes().repeat(16))).await?;
+            }
+            // 执行写入时派发的Flush直至Compactor空闲，期间未主动Flush
+            let _ = executor.run_until_idle();
+            assert!(kv_store.current_version().await.level_sst_count().iter().sum::<usize>() > 0);
+            assert!(kv_store.wal().gen_len() < 16 * 1024);
+            kv_store.wal().flush()?;
+            drop(kv_store);
+
+            let kv_store = executor.run_until(LsmStore::open_with_config(config)).await?;
+            assert_eq!(kv_store.get(b"kip").await?, Some(Bytes::from(999u32.to_be_bytes().repeat(16))));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_write_ack() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            // 组同步窗口远长于超时，Ack::Memory的写入不等待落盘
+            let config = Config::new(temp_dir.path().join("group"))
+                .wal_sync_window_micros(10_000_000);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let options = WriteOptions::default().ack(Ack::Memory);
+            let _ = tokio::time::timeout(
+                Duration::from_millis(100),
+                kv_store.set_with_options(b"k1", Bytes::from_static(b"v1"), &options)
+            ).await.expect("memory acknowledgment waited for WAL sync")?;
+            assert_eq!(kv_store.statistics().await.wal_sync_count, 0);
+            drop(kv_store);
+
+            // 未开启组同步时，仅Ack::Fsync的写入等待落盘
+            let kv_store = LsmStore::open(temp_dir.path().join("default")).await?;
+            let _ = kv_store.set_with_options(b"k1", Bytes::from_static(b"v1"), &WriteOptions::default()).await?;
+            assert_eq!(kv_store.statistics().await.wal_sync_count, 0);
+            let _ = kv_store.remove_with_options(b"k1", &WriteOptions::default().ack(Ack::Fsync)).await?;
+            assert_eq!(kv_store.statistics().await.wal_sync_count, 1);
+
+            let no_wal = LsmStore::open_with_config(Config::new(temp_dir.path().join("no_wal")).wal_enable(false)).await?;
+            assert!(matches!(
+                no_wal.set_with_options(b"k1", Bytes::new(), &WriteOptions::default().ack(Ack::Fsync)).await,
+                Err(KernelError::NotSupport(_))
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_scan_page() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+
+            for key in [b"k0", b"k1", b"k2", b"k3"] {
+                kv_store.set(key, Bytes::from_static(b"old")).await?;
+            }
             kv_store.flush().await?;
+            kv_store.remove(b"k1").await?;
+            kv_store.set(b"k2", Bytes::from_static(b"new")).await?;
+            kv_store.set(b"k4", Bytes::from_static(b"new")).await?;
+
+            let (items, cursor) = kv_store.scan_page(None, 2).await?;
+            assert_eq!(items, vec![
+                (Bytes::from_static(b"k0"), Bytes::from_static(b"old")),
+                (Bytes::from_static(b"k2"), Bytes::from_static(b"new")),
+            ]);
+            // 游标之后写入的数据不可见
+            kv_store.set(b"k5", Bytes::from_static(b"new")).await?;
+
+            let cursor = ScanCursor::from_bytes(&cursor.unwrap().to_bytes()?)?;
+            let (items, cursor) = kv_store.scan_page(Some(&cursor), 3).await?;
+            assert_eq!(items, vec![
+                (Bytes::from_static(b"k3"), Bytes::from_static(b"old")),
+                (Bytes::from_static(b"k4"), Bytes::from_static(b"new")),
+            ]);
+            assert_eq!(cursor, None);
 
-            let start = Instant::now();
-            for i in 0..times {
-                assert_eq!(kv_store.get(&vec_kv[i].0).await?, Some(vec_kv[i].1.clone()));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_ingest() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"old")).await?;
+
+            kv_store.ingest(vec![
+                (Bytes::from_static(b"k0"), Bytes::from_static(b"v0")),
+                (Bytes::from_static(b"k1"), Bytes::from_static(b"v1")),
+                (Bytes::from_static(b"k2"), Bytes::from_static(b"v2")),
+            ]).await?;
+            assert_eq!(kv_store.get(b"k0").await?, Some(Bytes::from_static(b"v0")));
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
+
+            assert!(matches!(
+                kv_store.ingest(vec![
+                    (Bytes::from_static(b"k4"), Bytes::from_static(b"v4")),
+                    (Bytes::from_static(b"k3"), Bytes::from_static(b"v3")),
+                ]).await,
+                Err(KernelError::IngestNotSorted)
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_wait_for_sequence() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path().join("a")).await?;
+            let other_store = LsmStore::open(temp_dir.path().join("b")).await?;
+            let seq_id = kv_store.set_with_sequence(b"k1", Bytes::from_static(b"v1")).await?;
+
+            assert!(kv_store.wait_for_sequence(seq_id, Duration::ZERO).await);
+            // 未被分配的Sequence在等待时长后返回false
+            assert!(!kv_store.wait_for_sequence(i64::MAX, Duration::from_millis(10)).await);
+            // 其他Store分配的Sequence不会被视为已应用
+            assert!(!other_store.wait_for_sequence(seq_id, Duration::from_millis(10)).await);
+
+            // 等待方在写入应用后被唤醒
+            let (is_applied, seq_id) = futures::join!(
+                kv_store.wait_for_sequence(seq_id + 1, Duration::from_secs(10)),
+                kv_store.set_with_sequence(b"k2", Bytes::from_static(b"v2"))
+            );
+            assert!(seq_id? > 0);
+            assert!(is_applied);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_point_in_time_read() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path().join("retained"))
+                .major_threshold_with_sst_size(1)
+                .version_retention(Duration::from_secs(60));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let seq_1 = kv_store.set_with_sequence(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            let seq_2 = kv_store.set_with_sequence(b"k1", Bytes::from_static(b"v2")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            kv_store.remove(b"k1").await?;
+            kv_store.flush().await?;
+
+            // 旧数据已被压缩覆盖，仍可由保留的版本读取
+            assert_eq!(kv_store.get(b"k1").await?, None);
+            assert_eq!(kv_store.get_at(b"k1", seq_1).await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get_at(b"k1", seq_2).await?, Some(Bytes::from_static(b"v2")));
+            assert_eq!(kv_store.get_at(b"k2", seq_1).await?, None);
+            assert_eq!(
+                kv_store.scan_at(None, 10, seq_1).await?,
+                vec![(Bytes::from_static(b"k1"), Bytes::from_static(b"v1"))]
+            );
+            assert_eq!(kv_store.scan_at(None, 10, kv_store.latest_sequence()).await?.len(), 1);
+
+            let kv_store = LsmStore::open(temp_dir.path().join("unretained")).await?;
+            let seq_1 = kv_store.set_with_sequence(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            assert!(matches!(kv_store.get_at(b"k1", seq_1).await, Err(KernelError::SequenceNotRetained(_))));
+            assert_eq!(
+                kv_store.get_at(b"k1", kv_store.latest_sequence()).await?,
+                Some(Bytes::from_static(b"v2"))
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_get_versions() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(1_000);
+            let config = Config::new(temp_dir.path())
+                .clock(Arc::new(clock.clone()))
+                .version_retention(Duration::from_secs(60));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            clock.advance(Duration::from_secs(300));
+            kv_store.set(b"k1", Bytes::from_static(b"v2")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            clock.advance(Duration::from_secs(1));
+            kv_store.remove(b"k1").await?;
+
+            let versions = kv_store.get_versions(b"k1", 10);
+            assert_eq!(versions.len(), 3);
+            assert!(versions[0].is_remove());
+            assert_eq!(versions[0].timestamp, 302_000);
+            assert_eq!(versions[1].value, Some(Bytes::from_static(b"v2")));
+            assert_eq!(versions[1].timestamp, 301_000);
+            assert_eq!(versions[2].timestamp, 1_000);
+            assert_eq!(
+                kv_store.get_at(b"k1", versions[2].sequence).await?,
+                Some(Bytes::from_static(b"v1"))
+            );
+            assert_eq!(kv_store.get_versions(b"k1", 2), versions[..2]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_value_chunking() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path()).value_chunk_size(1024);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let value = Bytes::from((0..10 * 1024_u32).map(|i| (i % 251) as u8).collect_vec());
+
+            kv_store.set(b"k1", value.clone()).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(value.clone()));
+
+            let raw_value = kv_store.get_(b"k1").await?.expect("manifest not found");
+            let manifest = ChunkManifest::from_value(&raw_value).expect("value is not chunked");
+            let chunk_key = manifest.chunk_key(b"k1", manifest.digests()[0]);
+            assert!(kv_store.get_(&chunk_key).await?.is_some());
+
+            // 扫描时不包含分块的Key，且Value被拼接
+            let (items, _) = kv_store.scan_page(None, 10).await?;
+            assert_eq!(items, vec![
+                (Bytes::from_static(b"k1"), value),
+                (Bytes::from_static(b"k2"), Bytes::from_static(b"v2")),
+            ]);
+
+            // 覆盖为小Value后旧分块被删除
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get_(&chunk_key).await?, None);
+
+            kv_store.set(b"k2", Bytes::from(vec![7; 4096])).await?;
+            let raw_value = kv_store.get_(b"k2").await?.expect("manifest not found");
+            let manifest = ChunkManifest::from_value(&raw_value).expect("value is not chunked");
+            kv_store.remove(b"k2").await?;
+            assert_eq!(kv_store.get(b"k2").await?, None);
+            for digest in manifest.digests() {
+                assert_eq!(kv_store.get_(&manifest.chunk_key(b"k2", *digest)).await?, None);
             }
-            println!("[get_for][Time: {:?}]", start.elapsed());
+
+            // 未经分块写入的Value即使以清单前缀开头，也不会被误认为清单
+            let mut batch = WriteBatch::default();
+            let _ = batch.set(b"k1", raw_value.clone())
+                .set(b"k3", raw_value.clone());
+            let _ = kv_store.write(batch).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(raw_value.clone()));
+            assert_eq!(kv_store.get(b"k3").await?, Some(raw_value));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_value_dedup() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .value_chunk_size(1024)
+                .value_dedup(true);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let value = Bytes::from((0..8 * 1024_u32).map(|i| (i % 251) as u8).collect_vec());
+
+            kv_store.set(b"k1", value.clone()).await?;
+            kv_store.set(b"k2", value.clone()).await?;
+
+            let raw_value = kv_store.get_(b"k1").await?.expect("manifest not found");
+            let manifest = ChunkManifest::from_value(&raw_value).expect("value is not chunked");
+            assert!(manifest.is_dedup());
+            // 不同Key中内容相同的分块共用同一Key
+            let blob_key = manifest.chunk_key(b"k1", manifest.digests()[0]);
+            assert_eq!(blob_key, manifest.chunk_key(b"k2", manifest.digests()[0]));
+
+            // 仍存在引用时，压缩不会回收分块
+            kv_store.remove(b"k1").await?;
+            kv_store.flush().await?;
+            assert_eq!(kv_store.get(b"k2").await?, Some(value));
+            assert!(kv_store.get_(&chunk::blob_ref_key(manifest.digests()[0], b"k1")).await?.is_none());
+
+            // 没有引用后，分块在压缩时被回收
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+            let items = kv_store.new_transaction().await
+                .scan(None, 100).await?;
+            assert_eq!(items, vec![(Bytes::from_static(b"k2"), Bytes::from_static(b"v2"))]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_set_options() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path()).block_size(64);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            for i in 0..100_u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from(vec![0; 32])).await?;
+            }
+            kv_store.flush().await?;
+            // 再次Flush使数据不再存在于Immutable MemTable中
+            kv_store.set(b"k", Bytes::from_static(b"v")).await?;
+            kv_store.flush().await?;
+            for i in 0..100_u32 {
+                assert!(kv_store.get(&i.to_be_bytes()).await?.is_some());
+            }
+            let block_cache = Arc::clone(&kv_store.inner.ver_status.current().await.block_cache);
+            assert!(block_cache.len() > 16);
+
+            // 缩小至16个Block，超出的Block立即被驱逐
+            kv_store.set_options(MutableOptions::default().block_cache_capacity(64 * 16)).await?;
+            assert_eq!(block_cache.capacity(), 16);
+            assert!(block_cache.len() <= 16);
+
+            kv_store.set_options(MutableOptions::default().table_cache_size(16)).await?;
+            assert!(matches!(
+                kv_store.set_options(MutableOptions::default().table_cache_size(0)).await,
+                Err(KernelError::CacheSizeOverFlow)
+            ));
+            for i in 0..100_u32 {
+                assert!(kv_store.get(&i.to_be_bytes()).await?.is_some());
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_negative_cache() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path()).negative_cache_ttl(Duration::from_secs(60));
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"k3", Bytes::from_static(b"v3")).await?;
+            kv_store.flush().await?;
+            kv_store.set(b"k4", Bytes::from_static(b"v4")).await?;
+            kv_store.flush().await?;
+
+            let (value, perf_context) = PerfContext::scope(kv_store.get(b"k2")).await;
+            assert_eq!(value?, None);
+            assert!(perf_context.bloom_filter_checked > 0);
+            // 再次查询时直接命中不存在Key的缓存
+            let (value, perf_context) = PerfContext::scope(kv_store.get(b"k2")).await;
+            assert_eq!(value?, None);
+            assert_eq!(perf_context.bloom_filter_checked, 0);
+
+            // 写入后的数据不受缓存影响
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
+            kv_store.flush().await?;
+            kv_store.set(b"k5", Bytes::from_static(b"v5")).await?;
             kv_store.flush().await?;
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
 
             Ok(())
         })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_row_cache() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path()).row_cache_size(1024);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.statistics().await.row_cache.hits, 1);
+
+            // 各写入路径均使缓存行失效
+            kv_store.set(b"k1", Bytes::from_static(b"v2")).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v2")));
+
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(b"k1", Bytes::from_static(b"v3"));
+            let _ = transaction.commit().await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v3")));
+
+            kv_store.ingest(vec![(Bytes::from_static(b"k1"), Bytes::from_static(b"v4"))]).await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v4")));
+
+            Ok(())
+        })
+    }
+}
@@ -1,20 +1,32 @@
 use std::cmp::Ordering;
-use std::collections::Bound;
+use std::collections::{BTreeMap, Bound, VecDeque};
+use std::iter;
 use std::mem;
+use std::sync::Arc;
 use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering::Acquire;
-use bytes::Bytes;
+use std::sync::atomic::Ordering::{Acquire, Release};
+use std::time::{Duration, Instant};
+use bytes::{Bytes, BytesMut};
 use itertools::Itertools;
 use parking_lot::Mutex;
 use skiplist::SkipMap;
+use tracing::warn;
 use crate::kernel::Result;
+use crate::KernelError;
+use crate::kernel::lsm::InlineKey;
 use crate::kernel::lsm::lsm_kv::Sequence;
+use crate::kernel::lsm::ss_table::SequenceRange;
+use crate::kernel::lsm::version::Version;
+use crate::kernel::utils::clock::{Clock, SystemClock};
 
 /// Value为此Key的Records(Key与seq_id)
 pub(crate) type MemMap = SkipMap<InternalKey, Option<Bytes>>;
 
 pub(crate) type KeyValue = (Bytes, Option<Bytes>);
 
+/// Arena每次预分配的内存块大小
+const ARENA_BLOCK_SIZE: usize = 64 * 1024;
+
 /// seq_id的上限值
 ///
 /// 用于默认的key的填充(补充使UserKey为高位，因此默认获取最新的seq_id数据)
@@ -26,7 +38,7 @@ pub(crate) fn key_value_bytes_len(key_value: &KeyValue) -> usize {
 
 #[derive(PartialEq, Eq, Debug)]
 pub(crate) struct InternalKey {
-    key: Bytes,
+    key: InlineKey,
     seq_id: i64,
 }
 
@@ -48,52 +60,254 @@ impl Ord for InternalKey {
 }
 
 impl InternalKey {
-    pub(crate) fn new(key: Bytes) -> Self {
-        InternalKey { key, seq_id: Sequence::create() }
+    pub(crate) fn new(key: &[u8]) -> Self {
+        InternalKey { key: InlineKey::from_slice(key), seq_id: Sequence::create() }
     }
 
-    pub(crate) fn new_with_seq(key: Bytes, seq_id: i64) -> Self {
-        InternalKey { key, seq_id }
+    pub(crate) fn new_with_seq(key: &[u8], seq_id: i64) -> Self {
+        InternalKey { key: InlineKey::from_slice(key), seq_id }
     }
 
-    pub(crate) fn get_key(&self) -> &Bytes {
+    pub(crate) fn get_key(&self) -> &[u8] {
         &self.key
     }
 }
 
 pub(crate) struct MemTable {
     inner: Mutex<TableInner>,
-    pub(crate) tx_count: AtomicUsize
+    pub(crate) tx_count: AtomicUsize,
+    /// 存活的事务快照的Sequence及其创建时间
+    snapshots: Mutex<BTreeMap<i64, Instant>>,
+    /// 快照的存活时长上限，为None时不限制
+    max_snapshot_age: Option<Duration>,
+    /// 已Flush的MemTable的保留时长，为None时不保留
+    version_retention: Option<Duration>,
+    /// 用于记录写入时间的时钟
+    clock: Arc<dyn Clock>,
 }
 
 struct TableInner {
     _mem: MemMap,
-    _immut: Option<MemMap>
+    _immut: Option<MemMap>,
+    /// _mem中数据的大小
+    _mem_size: usize,
+    /// _immut中数据的大小
+    _immut_size: usize,
+    /// _mem的Value所使用的Arena
+    _arena: Arena,
+    /// _immut的Arena所预分配的大小
+    _immut_arena_size: usize,
+    /// _immut中数据的Sequence范围
+    _immut_range: SequenceRange,
+    /// _immut被Flush前的Version，仅在开启版本保留时持有
+    _immut_base: Option<Arc<Version>>,
+    /// _immut转为Immutable MemTable的时间
+    _immut_sealed_at: Instant,
+    /// 因版本保留而保留的已Flush的Immutable MemTable，由旧至新排列
+    _history: VecDeque<RetainedMem>,
+    /// 内存中不存在任何Immutable MemTable时，可进行时间点读取的最旧Sequence
+    _floor: i64,
+    /// 每次Swap或丢弃Immutable MemTable时递增，用于确认读取期间获取的Version仍与内存中的数据一致
+    _epoch: u64,
+    /// 写入时间的索引，每毫秒记录该毫秒内首个写入的Sequence及其时间戳，按Sequence升序排列
+    _seq_times: VecDeque<(i64, i64)>,
+}
+
+/// 因版本保留而保留的Immutable MemTable
+struct RetainedMem {
+    mem_map: MemMap,
+    sequence_range: SequenceRange,
+    /// 该MemTable被Flush前的Version，仅包含早于其数据的SSTable
+    base: Arc<Version>,
+    sealed_at: Instant,
+}
+
+/// 时间点读取在内存中的结果
+pub(crate) struct PointInTime<T> {
+    pub(crate) found: T,
+    /// 需继续读取的Version，为None时使用读取前所获取的当前Version
+    pub(crate) base: Option<Arc<Version>>,
+}
+
+/// MemTable的Value分配器
+///
+/// Key以`InlineKey`的形式存储于`InternalKey`中，短Key不会额外分配内存
+///
+/// 以`ARENA_BLOCK_SIZE`为单位预分配内存块，并将数据依次拷贝至块中，
+/// 分配出的Bytes共享所在内存块，因此当MemTable被Flush且其数据均被Drop后整块释放，
+/// 避免高频写入时大量零碎的内存分配
+struct Arena {
+    block: BytesMut,
+    /// 已预分配的内存大小
+    allocated: usize,
+}
+
+impl Arena {
+    fn new() -> Self {
+        Arena { block: BytesMut::new(), allocated: 0 }
+    }
+
+    fn alloc(&mut self, data: Bytes) -> Bytes {
+        // 较大的数据直接沿用写入方的Bytes，避免拷贝及浪费内存块的剩余空间
+        if data.len() > ARENA_BLOCK_SIZE / 4 {
+            self.allocated += data.len();
+            return data;
+        }
+        if self.block.capacity() < data.len() {
+            self.block = BytesMut::with_capacity(ARENA_BLOCK_SIZE);
+            self.allocated += ARENA_BLOCK_SIZE;
+        }
+        self.block.extend_from_slice(&data);
+
+        self.block.split().freeze()
+    }
+}
+
+/// 估算单条数据在MemTable中所占用的内存大小
+///
+/// 内联的Key已包含在`InternalKey`的大小中，仅溢出至堆上的Key需额外计算
+fn entry_size(key: &InlineKey, value: &Option<Bytes>) -> usize {
+    size_of::<InternalKey>()
+        + size_of::<Option<Bytes>>()
+        + if key.spilled() { key.len() } else { 0 }
+        + value.as_ref().map(Bytes::len).unwrap_or(0)
 }
 
 impl MemTable {
     pub(crate) fn new(mem_map: MemMap) -> Self {
+        let _mem_size = mem_map.iter()
+            .map(|(key, value)| entry_size(&key.key, value))
+            .sum();
+        // 重放WAL时数据被重新分配Sequence，因此其之前的Sequence均仅存在于SSTable中
+        let _floor = mem_map.iter()
+            .map(|(key, _)| key.seq_id - 1)
+            .min()
+            .unwrap_or_else(Sequence::latest);
+
         MemTable {
             inner: Mutex::new(TableInner {
-                _mem: mem_map, _immut: None
+                _mem: mem_map,
+                _immut: None,
+                _mem_size,
+                _immut_size: 0,
+                _arena: Arena::new(),
+                _immut_arena_size: 0,
+                _immut_range: SequenceRange::UNKNOWN,
+                _immut_base: None,
+                _immut_sealed_at: Instant::now(),
+                _history: VecDeque::new(),
+                _floor,
+                _epoch: 0,
+                _seq_times: VecDeque::new(),
             }),
             tx_count: AtomicUsize::new(0),
+            snapshots: Mutex::new(BTreeMap::new()),
+            max_snapshot_age: None,
+            version_retention: None,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    pub(crate) fn with_max_snapshot_age(mut self, max_snapshot_age: Option<Duration>) -> Self {
+        self.max_snapshot_age = max_snapshot_age;
+        self
+    }
+
+    pub(crate) fn with_version_retention(mut self, version_retention: Option<Duration>) -> Self {
+        self.version_retention = version_retention;
+        self
+    }
+
+    /// 设置记录写入时间的时钟，此前已存在的数据(如重放的WAL)以当前时间作为其写入时间
+    pub(crate) fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now_millis();
+        let seq_times = &mut self.inner.get_mut()._seq_times;
+        seq_times.clear();
+        seq_times.push_back((i64::MIN, now));
+        self.clock = clock;
+        self
+    }
+
+    /// 登记事务的快照，在其释放前Compaction需等待
+    pub(crate) fn register_snapshot(&self, seq_id: i64) {
+        let mut snapshots = self.snapshots.lock();
+
+        let _ = snapshots.insert(seq_id, Instant::now());
+        let _ = self.tx_count.fetch_add(1, Release);
+    }
+
+    /// 释放事务的快照，返回该快照是否仍存活(未被强制释放)
+    pub(crate) fn release_snapshot(&self, seq_id: i64) -> bool {
+        let mut snapshots = self.snapshots.lock();
+
+        snapshots.remove(&seq_id)
+            .map(|_| self.tx_count.fetch_sub(1, Release))
+            .is_some()
+    }
+
+    pub(crate) fn is_snapshot_alive(&self, seq_id: i64) -> bool {
+        self.snapshots.lock().contains_key(&seq_id)
+    }
+
+    /// 强制释放存活时长超出上限的快照，返回释放的数量
+    pub(crate) fn expire_snapshots(&self) -> usize {
+        let Some(max_age) = self.max_snapshot_age else {
+            return 0;
+        };
+        let mut snapshots = self.snapshots.lock();
+        let expired = snapshots.iter()
+            .filter(|(_, created_at)| created_at.elapsed() >= max_age)
+            .map(|(seq_id, _)| *seq_id)
+            .collect_vec();
+
+        for seq_id in expired.iter() {
+            let _ = snapshots.remove(seq_id);
+            let _ = self.tx_count.fetch_sub(1, Release);
+            warn!("[MemTable][expire_snapshots][Seq: {}]: snapshot exceeded the max age and was released", seq_id);
+        }
+        expired.len()
+    }
+
+    /// 最旧的存活快照的Sequence，不存在快照时返回None
+    pub(crate) fn oldest_snapshot(&self) -> Option<i64> {
+        self.snapshots.lock()
+            .keys()
+            .next()
+            .copied()
+    }
+
     /// 插入并判断是否溢出
     ///
     /// 插入时不会去除重复键值，而是进行追加
+    /// 返回插入后的数据长度与该数据所分配的seq_id
     pub(crate) fn insert_data(
         &self,
         data: KeyValue,
-    ) -> Result<usize> {
+    ) -> Result<(usize, i64)> {
+        self.insert_data_(data, None)
+    }
+
+    /// 以已分配的seq_id插入，返回插入后的数据长度
+    pub(crate) fn insert_data_with_seq(&self, data: KeyValue, seq_id: i64) -> Result<usize> {
+        self.insert_data_(data, Some(seq_id))
+            .map(|(data_len, _)| data_len)
+    }
+
+    fn insert_data_(&self, data: KeyValue, seq_id: Option<i64>) -> Result<(usize, i64)> {
         let (key, value) = data;
         let mut inner = self.inner.lock();
 
-        let _ = inner._mem.insert(InternalKey::new(key), value);
-
-        Ok(inner._mem.len())
+        let value = value.map(|value| inner._arena.alloc(value));
+        let internal_key = match seq_id {
+            Some(seq_id) => InternalKey::new_with_seq(&key, seq_id),
+            None => InternalKey::new(&key),
+        };
+        let seq_id = internal_key.seq_id;
+        inner._mem_size += entry_size(&internal_key.key, &value);
+        let _ = inner._mem.insert(internal_key, value);
+        self.record_time(&mut inner, seq_id);
+
+        Ok((inner._mem.len(), seq_id))
     }
 
     pub(crate) fn insert_batch_data(
@@ -104,12 +318,27 @@ impl MemTable {
         let mut inner = self.inner.lock();
 
         for (key, value) in vec_data {
-            let _ = inner._mem.insert(InternalKey::new_with_seq(key, seq_id), value);
+            let value = value.map(|value| inner._arena.alloc(value));
+            let internal_key = InternalKey::new_with_seq(&key, seq_id);
+            inner._mem_size += entry_size(&internal_key.key, &value);
+            let _ = inner._mem.insert(internal_key, value);
         }
+        self.record_time(&mut inner, seq_id);
 
         Ok(inner._mem.len())
     }
 
+    /// 记录seq_id的写入时间，同一毫秒内仅记录首个写入
+    ///
+    /// 批量写入的Sequence在加锁前分配，因此可能小于已记录的Sequence，此时沿用已记录的时间
+    fn record_time(&self, inner: &mut TableInner, seq_id: i64) {
+        let now = self.clock.now_millis();
+
+        if inner._seq_times.back().is_none_or(|(seq, millis)| *seq < seq_id && *millis != now) {
+            inner._seq_times.push_back((seq_id, now));
+        }
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.inner.lock()._mem.is_empty()
     }
@@ -118,8 +347,27 @@ impl MemTable {
         self.inner.lock()._mem.len()
     }
 
+    /// MemTable与Immutable MemTable的内存占用
+    pub(crate) fn memory_usage(&self) -> usize {
+        let inner = self.inner.lock();
+
+        inner._mem_size + inner._immut_size
+    }
+
+    /// MemTable与Immutable MemTable的Arena所预分配的内存大小
+    pub(crate) fn arena_usage(&self) -> usize {
+        let inner = self.inner.lock();
+
+        inner._arena.allocated + inner._immut_arena_size
+    }
+
     /// MemTable将数据弹出并转移到immutable中  (弹出数据为有序的)
-    pub(crate) fn swap(&self) -> Option<Vec<KeyValue>> {
+    ///
+    /// 同时返回弹出数据的Sequence范围
+    ///
+    /// base为弹出的数据被Flush前的Version，开启版本保留时由Immutable MemTable持有，
+    /// 使时间点读取可以读取到其数据之前的SSTable
+    pub(crate) fn swap(&self, base: Option<Arc<Version>>) -> Option<(Vec<KeyValue>, SequenceRange)> {
         loop {
             if 0 == self.tx_count.load(Acquire) {
                 let mut inner = self.inner.lock();
@@ -131,31 +379,98 @@ impl MemTable {
                 if 0 != self.tx_count.load(Acquire) {
                     continue
                 }
+                let inner = &mut *inner;
+                if let Some(retention) = self.version_retention {
+                    while inner._history.front()
+                        .is_some_and(|retained| retained.sealed_at.elapsed() >= retention)
+                    {
+                        let _ = inner._history.pop_front();
+                    }
+                }
                 return (!inner._mem.is_empty())
                     .then(|| {
                         let mut vec_data = inner._mem.iter()
-                            .map(|(k, v)| (k.key.clone(), v.clone()))
                             // rev以使用最后(最新)的key
                             .rev()
-                            .unique_by(|(k, _)| k.clone())
+                            .unique_by(|(k, _)| &k.key)
+                            .map(|(k, v)| (Bytes::copy_from_slice(&k.key), v.clone()))
                             .collect_vec();
 
                         vec_data.reverse();
-
-                        inner._immut = Some(mem::replace(
-                            &mut inner._mem, SkipMap::new()
-                        ));
-
-                        vec_data
+                        let (min, max) = inner._mem.iter()
+                            .map(|(k, _)| k.seq_id)
+                            .minmax()
+                            .into_option()
+                            .unwrap_or_default();
+
+                        let sealed = Some(mem::replace(&mut inner._mem, SkipMap::new()));
+                        let sequence_range = SequenceRange::new(min, max);
+                        let base = base.filter(|_| self.version_retention.is_some());
+
+                        if let (Some(mem_map), Some(base)) = (mem::replace(&mut inner._immut, sealed), inner._immut_base.take()) {
+                            inner._history.push_back(RetainedMem {
+                                mem_map,
+                                sequence_range: inner._immut_range,
+                                base,
+                                sealed_at: inner._immut_sealed_at,
+                            });
+                        }
+                        inner._immut_range = sequence_range;
+                        inner._immut_base = base;
+                        inner._immut_sealed_at = Instant::now();
+                        inner._epoch += 1;
+
+                        // 清理内存中已不存在的数据的写入时间
+                        let oldest = inner._history.front()
+                            .map_or(min, |retained| retained.sequence_range.min);
+                        while inner._seq_times.get(1).is_some_and(|(seq, _)| *seq <= oldest) {
+                            let _ = inner._seq_times.pop_front();
+                        }
+                        inner._immut_size = mem::replace(&mut inner._mem_size, 0);
+                        inner._immut_arena_size = mem::replace(&mut inner._arena, Arena::new()).allocated;
+
+                        (vec_data, sequence_range)
                     });
             }
+            // 等待期间强制释放超出存活时长的快照，避免泄漏的事务使Compaction一直等待
+            let _ = self.expire_snapshots();
+            std::hint::spin_loop();
+        }
+    }
+
+    /// 丢弃已持久化的Immutable MemTable
+    ///
+    /// 用于使其后导入至SSTable的数据不被Immutable MemTable中较旧的数据遮蔽，
+    /// 与`MemTable::swap`相同需等待所有事务结束
+    ///
+    /// 保留的历史同样被丢弃，此后仅可读取导入之后的Sequence
+    pub(crate) fn discard_immut(&self) {
+        loop {
+            if 0 == self.tx_count.load(Acquire) {
+                let mut inner = self.inner.lock();
+                if 0 != self.tx_count.load(Acquire) {
+                    continue
+                }
+                inner._immut = None;
+                inner._immut_size = 0;
+                inner._immut_arena_size = 0;
+                inner._immut_base = None;
+                inner._history.clear();
+                inner._floor = Sequence::latest();
+                inner._epoch += 1;
+                return;
+            }
+            // 等待期间强制释放超出存活时长的快照，避免泄漏的事务使Compaction一直等待
+            let _ = self.expire_snapshots();
             std::hint::spin_loop();
         }
     }
 
-    pub(crate) fn find(&self, key: &[u8]) -> Option<Bytes> {
+    /// 查询Key的最新数据，返回Some(None)时表示该Key已被删除，
+    /// 此时不可继续向SSTable查询，以免读取到删除前的数据
+    pub(crate) fn find(&self, key: &[u8]) -> Option<Option<Bytes>> {
         // 填充SEQ_MAX使其变为最高位以尽可能获取最新数据
-        let internal_key = InternalKey::new_with_seq(Bytes::copy_from_slice(key), SEQ_MAX);
+        let internal_key = InternalKey::new_with_seq(key, SEQ_MAX);
         let inner = self.inner.lock();
 
         Self::find_(&internal_key, &inner._mem)
@@ -166,8 +481,8 @@ impl MemTable {
     }
 
     /// 查询时附带seq_id进行历史数据查询
-    pub(crate) fn find_with_sequence_id(&self, key: &[u8], seq_id: i64) -> Option<Bytes> {
-        let internal_key = InternalKey::new_with_seq(Bytes::copy_from_slice(key), seq_id);
+    pub(crate) fn find_with_sequence_id(&self, key: &[u8], seq_id: i64) -> Option<Option<Bytes>> {
+        let internal_key = InternalKey::new_with_seq(key, seq_id);
         let inner = self.inner.lock();
 
         if let Some(value) = MemTable::find_(&internal_key, &inner._mem) {
@@ -179,13 +494,146 @@ impl MemTable {
         }
     }
 
-    fn find_(internal_key: &InternalKey, mem_map: &MemMap) -> Option<Bytes> {
+    /// 获取Key大于start的数据中各Key在seq_id时的最新版本
+    ///
+    /// 返回的数据以Key升序排列，删除标记同样会被返回
+    pub(crate) fn range_with_sequence(&self, start: Option<&[u8]>, seq_id: i64) -> Vec<KeyValue> {
+        let inner = self.inner.lock();
+
+        // 先遍历_immut，使_mem中较新的数据覆盖之
+        Self::range_(inner._immut.iter().chain(iter::once(&inner._mem)), start, seq_id)
+    }
+
+    /// 当前的Swap次数，需在获取时间点读取所用的当前Version前获取
+    pub(crate) fn epoch(&self) -> u64 {
+        self.inner.lock()._epoch
+    }
+
+    /// 查询Key在seq_id时的数据，包含因版本保留而保留的Immutable MemTable
+    ///
+    /// 期间发生Swap而使epoch时获取的Version不再一致时返回None，
+    /// seq_id早于可读取的范围时返回`KernelError::SequenceNotRetained`
+    pub(crate) fn find_at(
+        &self,
+        key: &[u8],
+        seq_id: i64,
+        epoch: u64
+    ) -> Result<Option<PointInTime<Option<Option<Bytes>>>>> {
+        let internal_key = InternalKey::new_with_seq(key, seq_id);
+
+        self.read_at(seq_id, epoch, |mem_maps| {
+            // 由新至旧查询，首个存在的版本即为seq_id时的数据
+            mem_maps.iter()
+                .rev()
+                .find_map(|mem_map| Self::find_(&internal_key, mem_map))
+        })
+    }
+
+    /// 获取Key大于start的数据中各Key在seq_id时的版本，包含因版本保留而保留的Immutable MemTable
+    ///
+    /// 返回None与错误的情况同`MemTable::find_at`
+    pub(crate) fn range_at(
+        &self,
+        start: Option<&[u8]>,
+        seq_id: i64,
+        epoch: u64
+    ) -> Result<Option<PointInTime<Vec<KeyValue>>>> {
+        self.read_at(seq_id, epoch, |mem_maps| {
+            Self::range_(mem_maps.iter().copied(), start, seq_id)
+        })
+    }
+
+    fn read_at<T>(
+        &self,
+        seq_id: i64,
+        epoch: u64,
+        fn_read: impl FnOnce(&[&MemMap]) -> T
+    ) -> Result<Option<PointInTime<T>>> {
+        let inner = self.inner.lock();
+        if inner._epoch != epoch {
+            return Ok(None);
+        }
+        // 存在保留的历史时，最旧的数据之前的Sequence由其被Flush前的Version读取，
+        // 否则SSTable中可能存在Immutable MemTable中的数据，因此仅可读取其最新的Sequence之后
+        let (floor, base) = match (inner._history.front(), &inner._immut) {
+            (Some(retained), _) => (retained.sequence_range.min - 1, Some(&retained.base)),
+            (None, Some(_)) => match &inner._immut_base {
+                Some(base) => (inner._immut_range.min - 1, Some(base)),
+                None => (inner._immut_range.max, None),
+            },
+            (None, None) => (inner._floor, None),
+        };
+        if seq_id < floor {
+            return Err(KernelError::SequenceNotRetained(floor));
+        }
+        // 由旧至新排列
+        let mem_maps = inner._history.iter()
+            .map(|retained| &retained.mem_map)
+            .chain(inner._immut.iter())
+            .chain(iter::once(&inner._mem))
+            .collect_vec();
+
+        Ok(Some(PointInTime {
+            found: fn_read(&mem_maps),
+            base: base.map(Arc::clone),
+        }))
+    }
+
+    /// 获取Key在内存中的至多limit个版本，由新至旧排列，包含因版本保留而保留的Immutable MemTable
+    ///
+    /// 返回各版本的Sequence、写入时间(Unix时间戳，毫秒)与Value，Value为None时表示删除
+    pub(crate) fn versions(&self, key: &[u8], limit: usize) -> Vec<(i64, i64, Option<Bytes>)> {
+        let inner = self.inner.lock();
+        let min = InternalKey::new_with_seq(key, i64::MIN);
+        let max = InternalKey::new_with_seq(key, SEQ_MAX);
+
+        iter::once(&inner._mem)
+            .chain(inner._immut.iter())
+            .chain(inner._history.iter().rev().map(|retained| &retained.mem_map))
+            .flat_map(|mem_map| mem_map.range(Bound::Included(&min), Bound::Included(&max)).rev())
+            .take(limit)
+            .map(|(internal_key, value)| {
+                let index = inner._seq_times.partition_point(|(seq, _)| *seq <= internal_key.seq_id);
+                let timestamp = index.checked_sub(1)
+                    .and_then(|index| inner._seq_times.get(index))
+                    .map_or(0, |(_, millis)| *millis);
+
+                (internal_key.seq_id, timestamp, value.clone())
+            })
+            .collect_vec()
+    }
+
+    /// 由旧至新遍历mem_maps，获取各Key在seq_id时的最新版本
+    fn range_<'a>(
+        mem_maps: impl Iterator<Item = &'a MemMap>,
+        start: Option<&[u8]>,
+        seq_id: i64
+    ) -> Vec<KeyValue> {
+        let mut latest = BTreeMap::new();
+
+        for mem_map in mem_maps {
+            let min_key = start.map(|key| InternalKey::new_with_seq(key, SEQ_MAX));
+            let min = min_key.as_ref().map_or(Bound::Unbounded, Bound::Excluded);
+
+            for (internal_key, value) in mem_map.range(min, Bound::Unbounded) {
+                if internal_key.seq_id <= seq_id {
+                    let _ = latest.insert(
+                        Bytes::copy_from_slice(internal_key.get_key()),
+                        value.clone()
+                    );
+                }
+            }
+        }
+
+        latest.into_iter().collect_vec()
+    }
+
+    fn find_(internal_key: &InternalKey, mem_map: &MemMap) -> Option<Option<Bytes>> {
         mem_map.upper_bound(Bound::Included(internal_key))
             .and_then(|(intern_key, value)| {
-                (internal_key.get_key() == &intern_key.key)
+                (internal_key.get_key() == intern_key.get_key())
                     .then(|| value.clone())
             })
-            .flatten()
     }
 }
 
@@ -194,7 +642,8 @@ mod tests {
     use bytes::Bytes;
     use crate::kernel::lsm::lsm_kv::Sequence;
     use crate::kernel::Result;
-    use crate::kernel::lsm::mem_table::{MemMap, MemTable};
+    use crate::kernel::lsm::INLINE_KEY_SIZE;
+    use crate::kernel::lsm::mem_table::{ARENA_BLOCK_SIZE, InternalKey, MemMap, MemTable};
 
     #[test]
     fn test_mem_table_find() -> Result<()> {
@@ -203,35 +652,63 @@ mod tests {
         let data_1 = (Bytes::from(vec![b'k']), Some(Bytes::from(vec![b'1'])));
         let data_2 = (Bytes::from(vec![b'k']), Some(Bytes::from(vec![b'2'])));
 
-        assert_eq!(mem_table.insert_data(data_1)?, 1);
+        assert_eq!(mem_table.insert_data(data_1)?.0, 1);
 
         let old_seq_id = Sequence::create();
 
-        assert_eq!(mem_table.find(&vec![b'k']), Some(Bytes::from(vec![b'1'])));
+        assert_eq!(mem_table.find(&vec![b'k']), Some(Some(Bytes::from(vec![b'1']))));
 
-        assert_eq!(mem_table.insert_data(data_2)?, 2);
+        assert_eq!(mem_table.insert_data(data_2)?.0, 2);
 
-        assert_eq!(mem_table.find(&vec![b'k']), Some(Bytes::from(vec![b'2'])));
+        assert_eq!(mem_table.find(&vec![b'k']), Some(Some(Bytes::from(vec![b'2']))));
 
-        assert_eq!(mem_table.find_with_sequence_id(&vec![b'k'], old_seq_id), Some(Bytes::from(vec![b'1'])));
+        assert_eq!(mem_table.find_with_sequence_id(&vec![b'k'], old_seq_id), Some(Some(Bytes::from(vec![b'1']))));
 
         let new_seq_id = Sequence::create();
 
-        assert_eq!(mem_table.find_with_sequence_id(&vec![b'k'], new_seq_id), Some(Bytes::from(vec![b'2'])));
+        assert_eq!(mem_table.find_with_sequence_id(&vec![b'k'], new_seq_id), Some(Some(Bytes::from(vec![b'2']))));
 
         Ok(())
     }
 
     #[test]
-    fn test_mem_table_swap() -> Result<()> {
+    fn test_inline_key() -> Result<()> {
         let mem_table = MemTable::new(MemMap::new());
+        let short_key = vec![b'k'; INLINE_KEY_SIZE];
+        let long_key = vec![b'k'; INLINE_KEY_SIZE + 1];
+
+        assert!(!InternalKey::new(&short_key).key.spilled());
+        assert!(InternalKey::new(&long_key).key.spilled());
+
+        let _ = mem_table.insert_data((Bytes::from(short_key.clone()), Some(Bytes::from_static(b"short"))))?;
+        let _ = mem_table.insert_data((Bytes::from(long_key.clone()), Some(Bytes::from_static(b"long"))))?;
 
-        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'1']), Some(Bytes::from(vec![b'1']))))?, 1);
-        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'1']), Some(Bytes::from(vec![b'2']))))?, 2);
-        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'2']), Some(Bytes::from(vec![b'1']))))?, 3);
-        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'2']), Some(Bytes::from(vec![b'2']))))?, 4);
+        assert_eq!(mem_table.find(&short_key), Some(Some(Bytes::from_static(b"short"))));
+        assert_eq!(mem_table.find(&long_key), Some(Some(Bytes::from_static(b"long"))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mem_table_swap() -> Result<()> {
+        let mem_table = MemTable::new(MemMap::new());
 
-        let mut vec_unique_sort_with_cmd_key = mem_table.swap().unwrap();
+        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'1']), Some(Bytes::from(vec![b'1']))))?.0, 1);
+        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'1']), Some(Bytes::from(vec![b'2']))))?.0, 2);
+        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'2']), Some(Bytes::from(vec![b'1']))))?.0, 3);
+        assert_eq!(mem_table.insert_data((Bytes::from(vec![b'k', b'2']), Some(Bytes::from(vec![b'2']))))?.0, 4);
+        assert_eq!(mem_table.arena_usage(), ARENA_BLOCK_SIZE);
+
+        let (mut vec_unique_sort_with_cmd_key, _) = mem_table.swap(None).unwrap();
+        assert_eq!(mem_table.arena_usage(), ARENA_BLOCK_SIZE);
+        let _ = mem_table.insert_data((Bytes::from(vec![b'k', b'3']), Some(Bytes::from(vec![0; ARENA_BLOCK_SIZE]))))?;
+        // Immutable的内存块 + 单独分配的大Value(Key为内联存储，不占用新Arena的内存块)
+        assert_eq!(mem_table.arena_usage(), ARENA_BLOCK_SIZE * 2);
+        // 已Flush的Immutable被替换或丢弃时，其Arena整体释放
+        let _ = mem_table.swap(None);
+        assert_eq!(mem_table.arena_usage(), ARENA_BLOCK_SIZE);
+        mem_table.discard_immut();
+        assert_eq!(mem_table.arena_usage(), 0);
 
         assert_eq!(vec_unique_sort_with_cmd_key.pop(), Some((Bytes::from(vec![b'k', b'2']), Some(Bytes::from(vec![b'2'])))));
         assert_eq!(vec_unique_sort_with_cmd_key.pop(), Some((Bytes::from(vec![b'k', b'1']), Some(Bytes::from(vec![b'2'])))));
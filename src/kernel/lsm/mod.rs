@@ -1,17 +1,26 @@
 use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use bytes::Bytes;
 use growable_bloom_filter::GrowableBloom;
+use itertools::Itertools;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::warn;
 use crate::kernel::Result;
 use crate::kernel::io::{IoFactory, IoReader, IoType};
+use crate::kernel::lsm::block::CompressType;
+use crate::kernel::lsm::column_family::TableOptions;
 use crate::kernel::lsm::compactor::{CompactTask, LEVEL_0, MergeShardingVec};
 use crate::kernel::lsm::log::LogLoader;
-use crate::kernel::lsm::lsm_kv::{Config, Gen};
+use crate::kernel::lsm::lsm_kv::{Config, Gen, StoreInner};
 use crate::kernel::lsm::mem_table::{key_value_bytes_len, KeyValue};
-use crate::kernel::lsm::ss_table::{Scope, SSTable};
-use crate::kernel::utils::lru_cache::ShardingLruCache;
+use crate::kernel::lsm::secondary_cache::SecondaryCache;
+use crate::kernel::lsm::ss_table::{DATA_COMPRESS_TYPE, Scope, SequenceRange, SSTable};
+use crate::kernel::utils::lru_cache::{CacheStats, ShardingLruCache};
 use crate::KernelError;
 
 mod ss_table;
@@ -23,6 +32,54 @@ mod mvcc;
 mod block;
 mod mem_table;
 mod iterator;
+mod secondary_cache;
+mod negative_cache;
+mod row_cache;
+mod chunk;
+mod scrub;
+mod hot_keys;
+mod adaptive;
+mod heat_map;
+pub mod stats;
+pub mod event;
+pub mod options;
+pub mod fallback;
+pub mod import;
+pub mod structures;
+pub mod lease;
+pub mod column_family;
+pub mod history;
+pub mod hook;
+pub mod keyspace;
+pub mod loader;
+pub mod write_quota;
+#[cfg(feature = "doc")]
+mod doc;
+
+/// 内联存储的Key长度上限
+pub(crate) const INLINE_KEY_SIZE: usize = 24;
+
+/// 内部使用的Key类型
+///
+/// 不超过`INLINE_KEY_SIZE`的短Key直接内联存储，避免小Key场景下频繁的堆分配
+pub(crate) type InlineKey = SmallVec<[u8; INLINE_KEY_SIZE]>;
+
+/// 当前写入的SSTable格式版本，可通过`LsmStore::migrate`将旧格式的SSTable重写为当前格式
+///
+/// - 1: MetaBlock未记录Sequence范围
+/// - 2: MetaBlock未记录删除标记数量
+/// - 3: MetaBlock未记录DataBlock的压缩方式
+/// - 4: 当前格式
+pub const FORMAT_VERSION: u32 = 4;
+
+pub(crate) const LEGACY_FORMAT_VERSION: u32 = 1;
+
+const UNCOUNTED_FORMAT_VERSION: u32 = 2;
+
+const UNTYPED_FORMAT_VERSION: u32 = 3;
+
+/// 默认SSTable存储路径(`dir_path/ss_table`)的序号
+pub(crate) const DEFAULT_SST_PATH_ID: usize = 0;
 
 /// Footer序列化长度定长
 /// 注意Footer序列化时，需要使用类似BinCode这样的定长序列化框架，否则若类似Rmp的话会导致Footer在不同数据时，长度不一致
@@ -46,32 +103,198 @@ struct MetaBlock {
     len: usize,
     index_restart_interval: usize,
     data_restart_interval: usize,
+    sequence_range: SequenceRange,
+    /// 删除标记的数量
+    tombstone_len: usize,
+    /// DataBlock的压缩方式
+    compress_type: CompressType,
+}
+
+/// 未记录DataBlock压缩方式的旧版本MetaBlock，其DataBlock均以`DATA_COMPRESS_TYPE`压缩
+#[derive(Deserialize)]
+struct UntypedMetaBlock {
+    scope: Scope,
+    filter: GrowableBloom,
+    len: usize,
+    index_restart_interval: usize,
+    data_restart_interval: usize,
+    sequence_range: SequenceRange,
+    tombstone_len: usize,
+}
+
+impl From<UntypedMetaBlock> for MetaBlock {
+    fn from(untyped: UntypedMetaBlock) -> Self {
+        let UntypedMetaBlock {
+            scope, filter, len, index_restart_interval, data_restart_interval, sequence_range, tombstone_len
+        } = untyped;
+
+        MetaBlock {
+            scope,
+            filter,
+            len,
+            index_restart_interval,
+            data_restart_interval,
+            sequence_range,
+            tombstone_len,
+            compress_type: DATA_COMPRESS_TYPE,
+        }
+    }
+}
+
+/// 未记录删除标记数量的旧版本MetaBlock，其删除标记数量视为0
+#[derive(Deserialize)]
+struct UncountedMetaBlock {
+    scope: Scope,
+    filter: GrowableBloom,
+    len: usize,
+    index_restart_interval: usize,
+    data_restart_interval: usize,
+    sequence_range: SequenceRange,
+}
+
+impl From<UncountedMetaBlock> for MetaBlock {
+    fn from(uncounted: UncountedMetaBlock) -> Self {
+        let UncountedMetaBlock {
+            scope, filter, len, index_restart_interval, data_restart_interval, sequence_range
+        } = uncounted;
+
+        MetaBlock {
+            scope,
+            filter,
+            len,
+            index_restart_interval,
+            data_restart_interval,
+            sequence_range,
+            tombstone_len: 0,
+            compress_type: DATA_COMPRESS_TYPE,
+        }
+    }
+}
+
+/// 未记录Sequence范围的旧版本MetaBlock
+#[derive(Deserialize)]
+struct LegacyMetaBlock {
+    scope: Scope,
+    filter: GrowableBloom,
+    len: usize,
+    index_restart_interval: usize,
+    data_restart_interval: usize,
+}
+
+impl From<LegacyMetaBlock> for MetaBlock {
+    fn from(legacy: LegacyMetaBlock) -> Self {
+        let LegacyMetaBlock { scope, filter, len, index_restart_interval, data_restart_interval } = legacy;
+
+        MetaBlock {
+            scope,
+            filter,
+            len,
+            index_restart_interval,
+            data_restart_interval,
+            sequence_range: SequenceRange::UNKNOWN,
+            tombstone_len: 0,
+            compress_type: DATA_COMPRESS_TYPE,
+        }
+    }
 }
 
 pub(crate) struct SSTableLoader {
     inner: ShardingLruCache<i64, SSTable>,
-    factory: Arc<IoFactory>,
+    /// 以路径序号排列的各SSTable存储路径的IoFactory
+    factories: Vec<Arc<IoFactory>>,
+    /// SSTable的Gen所在的存储路径序号，未记录的Gen位于默认路径
+    gen_paths: Mutex<HashMap<i64, usize>>,
+    /// 时间窗口压缩时SSTable的Gen所属的窗口起始时间(毫秒)
+    gen_windows: Mutex<HashMap<i64, i64>>,
+    /// SSTable的Gen中所有数据的最大过期时间(毫秒)，未记录的Gen不会过期
+    gen_expiries: Mutex<HashMap<i64, i64>>,
+    /// 由该Loader提供的SSTable所共享的二级Block缓存
+    secondary_cache: Option<Arc<SecondaryCache>>,
     config: Config,
     wal: Arc<LogLoader>
 }
 
 impl SSTableLoader {
-    pub(crate) fn new(config: Config, factory: Arc<IoFactory>, wal: Arc<LogLoader>) -> Result<Self> {
+    pub(crate) fn new(config: Config, factories: Vec<Arc<IoFactory>>, wal: Arc<LogLoader>) -> Result<Self> {
         let inner = ShardingLruCache::new(
             config.table_cache_size,
             16,
             RandomState::default()
         )?;
-        Ok(SSTableLoader { inner, factory, config, wal })
+        let secondary_cache = config.secondary_cache_path.as_ref()
+            .map(|path| SecondaryCache::new(path, config.secondary_cache_size).map(Arc::new))
+            .transpose()?;
+
+        Ok(SSTableLoader {
+            inner,
+            factories,
+            gen_paths: Mutex::new(HashMap::new()),
+            gen_windows: Mutex::new(HashMap::new()),
+            gen_expiries: Mutex::new(HashMap::new()),
+            secondary_cache,
+            config,
+            wal
+        })
     }
 
-    pub(crate) fn insert(&mut self, ss_table: SSTable) -> Option<SSTable> {
+    /// 记录SSTable所在的存储路径序号
+    pub(crate) fn set_path(&self, vec_gen: &[i64], path_id: usize) {
+        let mut gen_paths = self.gen_paths.lock();
+
+        for gen in vec_gen {
+            let _ignore = gen_paths.insert(*gen, path_id);
+        }
+    }
+
+    /// 记录SSTable所属的时间窗口
+    pub(crate) fn set_window(&self, vec_gen: &[i64], window_start: i64) {
+        let mut gen_windows = self.gen_windows.lock();
+
+        for gen in vec_gen {
+            let _ignore = gen_windows.insert(*gen, window_start);
+        }
+    }
+
+    pub(crate) fn get_window(&self, gen: i64) -> Option<i64> {
+        self.gen_windows.lock()
+            .get(&gen)
+            .copied()
+    }
+
+    /// 记录SSTable中所有数据的最大过期时间
+    pub(crate) fn set_expiry(&self, vec_gen: &[i64], max_expiry: i64) {
+        let mut gen_expiries = self.gen_expiries.lock();
+
+        for gen in vec_gen {
+            let _ignore = gen_expiries.insert(*gen, max_expiry);
+        }
+    }
+
+    pub(crate) fn get_expiry(&self, gen: i64) -> Option<i64> {
+        self.gen_expiries.lock()
+            .get(&gen)
+            .copied()
+    }
+
+    /// 获取Gen所在存储路径的IoFactory
+    ///
+    /// 当记录的路径已不在配置中时使用默认路径
+    fn factory(&self, gen: i64) -> &IoFactory {
+        self.gen_paths.lock()
+            .get(&gen)
+            .and_then(|path_id| self.factories.get(*path_id))
+            .unwrap_or(&self.factories[DEFAULT_SST_PATH_ID])
+    }
+
+    pub(crate) fn insert(&mut self, mut ss_table: SSTable) -> Option<SSTable> {
+        ss_table.set_secondary_cache(self.secondary_cache.clone());
         self.inner.put(ss_table.get_gen(), ss_table)
+            .map(|ss_table| SSTable::clone(&ss_table))
     }
 
     pub(crate) fn get(&self, gen: i64) -> Option<SSTable> {
         self.inner.get_or_insert(gen, |gen| {
-            let sst_factory = &self.factory;
+            let sst_factory = self.factory(*gen);
 
             let ss_table = match sst_factory.reader(*gen, IoType::Direct)
                 .and_then(SSTable::load_from_file)
@@ -88,14 +311,30 @@ impl SSTableLoader {
                 }
             };
 
-            Ok(ss_table)
+            Ok(ss_table.with_secondary_cache(self.secondary_cache.clone()))
         })
-            .map(SSTable::clone)
+            .map(|ss_table| SSTable::clone(&ss_table))
             .ok()
     }
 
+    /// 已缓存的SSTable所占用的内存大小
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.inner.sum_by(SSTable::get_meta_size)
+    }
+
+    /// SSTable缓存的统计数据
+    pub(crate) fn cache_stats(&self) -> CacheStats {
+        self.inner.stats()
+    }
+
+    /// 修改可缓存的SSTable数量
+    pub(crate) fn set_cache_size(&self, cache_size: usize) -> Result<()> {
+        Ok(self.inner.set_capacity(cache_size)?)
+    }
+
     pub(crate) fn remove(&mut self, gen: &i64) -> Option<SSTable> {
         self.inner.remove(gen)
+            .map(|ss_table| SSTable::clone(&ss_table))
     }
 
     #[allow(dead_code)]
@@ -104,14 +343,26 @@ impl SSTableLoader {
     }
 
     pub(crate) fn clean(&self, gen: i64) -> Result<()> {
-        self.factory.clean(gen)
+        self.factory(gen).clean(gen)?;
+        let _ignore = self.gen_paths.lock().remove(&gen);
+        let _ignore = self.gen_windows.lock().remove(&gen);
+        let _ignore1 = self.gen_expiries.lock().remove(&gen);
+
+        Ok(())
     }
 }
 
 impl Footer {
     /// 从对应文件的IOHandler中将Footer读取出来
     fn read_to_file(reader: &dyn IoReader) -> Result<Self> {
-        let start_pos = reader.file_size()? - TABLE_FOOTER_SIZE as u64;
+        let start_pos = reader.file_size()?
+            .checked_sub(TABLE_FOOTER_SIZE as u64)
+            .ok_or_else(|| KernelError::Corrupted {
+                path: reader.get_path(),
+                gen: reader.get_gen(),
+                offset: 0,
+                reason: "file is smaller than the footer".to_owned(),
+            })?;
         Ok(bincode::deserialize(
             &reader.read_with_pos(start_pos, TABLE_FOOTER_SIZE)?
         )?)
@@ -151,12 +402,43 @@ fn data_sharding(mut vec_data: Vec<KeyValue>, file_size: usize) -> MergeSharding
     vec_sharding
 }
 
-fn is_exceeded_then_minor(
+/// 将有序的数据按Key所属前缀的SSTable选项切分为连续的数段，使每段数据仅对应同一SSTable选项
+///
+/// 前缀均为连续的Key范围，因此有序数据中同一选项的数据总是相邻
+fn split_with_options(vec_data: Vec<KeyValue>, prefix_options: &[(Bytes, TableOptions)]) -> Vec<Vec<KeyValue>> {
+    if prefix_options.is_empty() {
+        return vec![vec_data];
+    }
+    let mut vec_split = Vec::new();
+
+    for (_, group) in &vec_data.into_iter().group_by(|(key, _)| TableOptions::with_key(prefix_options, key)) {
+        vec_split.push(group.collect_vec());
+    }
+    vec_split
+}
+
+/// 先以`split_with_options`切分再各自进行分片，使每个分片仅对应同一SSTable选项
+fn data_sharding_with_options(
+    vec_data: Vec<KeyValue>,
+    file_size: usize,
+    prefix_options: &[(Bytes, TableOptions)]
+) -> MergeShardingVec {
+    split_with_options(vec_data, prefix_options)
+        .into_iter()
+        .flat_map(|vec_data| data_sharding(vec_data, file_size))
+        .collect()
+}
+
+async fn is_exceeded_then_minor(
     data_len: usize,
     tx: &UnboundedSender<CompactTask>,
-    config: &Config
+    store_inner: &StoreInner
 ) -> Result<()> {
-    if data_len >= config.minor_threshold_with_len {
+    if data_len >= store_inner.config.minor_threshold_with_len
+        || is_exceeded_wal_size(store_inner)
+        || is_exceeded_memory_budget(store_inner).await
+    {
+        let _ = store_inner.stats.compaction_pending.fetch_add(1, Ordering::Relaxed);
         tx.send(CompactTask::Flush(None))
             .map_err(|_| KernelError::ChannelClose)?;
     }
@@ -164,21 +446,45 @@ fn is_exceeded_then_minor(
     Ok(())
 }
 
+/// 判断当前MemTable对应的WAL大小是否超出`Config::max_wal_size`
+fn is_exceeded_wal_size(store_inner: &StoreInner) -> bool {
+    store_inner.config.max_wal_size
+        .is_some_and(|max_wal_size| store_inner.wal.gen_len() >= max_wal_size)
+}
+
+/// 判断内存占用是否超出`Config::memory_budget`
+///
+/// 统计缓存占用需要获取各分片的锁，因此仅在MemTable每增长预算的1/32时进行检测
+/// 超出时由调用方提前触发Minor压缩以释放MemTable
+async fn is_exceeded_memory_budget(store_inner: &StoreInner) -> bool {
+    let Some(budget) = store_inner.config.memory_budget else {
+        return false
+    };
+    let watermark = &store_inner.stats.memory_check_watermark;
+    let mem_table_usage = store_inner.mem_table.memory_usage();
+
+    if mem_table_usage < watermark.load(Ordering::Relaxed) {
+        return false
+    }
+    watermark.store(mem_table_usage + budget / 32, Ordering::Relaxed);
+
+    store_inner.memory_usage().await.total() >= budget
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::hash_map::RandomState;
     use std::sync::Arc;
     use bincode::Options;
     use bytes::Bytes;
     use tempfile::TempDir;
     use crate::kernel::io::{FileExtension, IoFactory, IoType};
     use crate::kernel::lsm::{Footer, SSTableLoader, TABLE_FOOTER_SIZE};
+    use crate::kernel::lsm::block::BlockCache;
     use crate::kernel::lsm::log::LogLoader;
     use crate::kernel::lsm::lsm_kv::{Config, DEFAULT_WAL_PATH};
     use crate::kernel::lsm::ss_table::SSTable;
     use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
     use crate::kernel::Result;
-    use crate::kernel::utils::lru_cache::ShardingLruCache;
 
     #[test]
     fn test_footer() -> Result<()> {
@@ -207,11 +513,7 @@ mod tests {
             config.dir_path.join(DEFAULT_SS_TABLE_PATH),
             FileExtension::SSTable
         )?);
-        let cache = ShardingLruCache::new(
-            config.table_cache_size,
-            16,
-            RandomState::default()
-        )?;
+        let cache = BlockCache::new(&config)?;
         let mut vec_data = Vec::new();
         let times = 2333;
         let (wal, _) = LogLoader::reload(
@@ -222,7 +524,7 @@ mod tests {
         for i in 0..times {
             let key_value = (Bytes::from(bincode::options().with_big_endian().serialize(&i)?), Some(value.clone()));
 
-            wal.log(key_value.clone())?;
+            let _ = wal.log_with_sequence(key_value.clone())?;
             vec_data.push(key_value);
         }
         wal.flush()?;
@@ -234,7 +536,7 @@ mod tests {
             0
         )?;
         let mut loader = SSTableLoader::new(
-            config, sst_factory.clone(), Arc::new(wal)
+            config, vec![sst_factory.clone()], Arc::new(wal)
         )?;
 
         assert!(loader.insert(ss_table).is_none());
@@ -244,7 +546,7 @@ mod tests {
         let ss_table_old_1 = loader.get(1).unwrap();
 
         for i in 0..times {
-            assert_eq!(ss_table_old_1.query_with_key(&vec_data[i].0, &cache)?, Some(value.clone()))
+            assert_eq!(tokio_test::block_on(ss_table_old_1.query_with_key(&vec_data[i].0, &cache))?, Some(Some(value.clone())))
         }
 
         // 模拟SSTable异常而使用Wal进行恢复的情况
@@ -256,7 +558,7 @@ mod tests {
         let ss_table_old_2 = loader.get(1).unwrap();
 
         for i in 0..times {
-            assert_eq!(ss_table_old_2.query_with_key(&vec_data[i].0, &cache)?, Some(value.clone()))
+            assert_eq!(tokio_test::block_on(ss_table_old_2.query_with_key(&vec_data[i].0, &cache))?, Some(Some(value.clone())))
         }
         Ok(())
     }
@@ -1,39 +1,53 @@
+use std::collections::Bound;
 use std::sync::Arc;
-use std::sync::atomic::Ordering;
 use bytes::Bytes;
 use itertools::Itertools;
 use skiplist::SkipMap;
 use tokio::sync::mpsc::UnboundedSender;
 use crate::kernel::lsm::compactor::CompactTask;
 use crate::kernel::lsm::is_exceeded_then_minor;
-use crate::kernel::lsm::log::LogLoader;
+use crate::kernel::lsm::iterator::merging_iter::{MergeSource, MergingIter};
 use crate::kernel::Result;
-use crate::kernel::lsm::lsm_kv::{Config, Sequence, StoreInner};
+use crate::kernel::lsm::lsm_kv::StoreInner;
 use crate::kernel::lsm::mem_table::MemTable;
 use crate::kernel::lsm::version::Version;
+use crate::kernel::utils::latch::Latches;
 use crate::KernelError;
 
 pub struct Transaction {
     pub(crate) store_inner: Arc<StoreInner>,
     pub(crate) compactor_tx: UnboundedSender<CompactTask>,
+    /// 与LsmStore共享的Key分片锁，提交时持有写入的所有Key的锁
+    pub(crate) latches: Arc<Latches>,
 
     pub(crate) version: Arc<Version>,
     pub(crate) writer_buf: SkipMap<Bytes, Option<Bytes>>,
     pub(crate) seq_id: i64,
+    /// 每次写入前writer_buf中该Key的数据，用于回滚至保存点
+    pub(crate) undo_log: Vec<(Bytes, Option<Option<Bytes>>)>,
+    /// 是否已释放对MemTable的持有
+    pub(crate) is_released: bool,
 }
 
+/// 事务内的保存点
+///
+/// 回滚至某一保存点后，在其之后创建的保存点随之失效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint(usize);
+
 impl Transaction {
 
     /// 通过Key获取对应的Value
     ///
     /// 此处不需要等待压缩，因为在Transaction存活时不会触发Compaction
     pub async fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
-        if let Some(value) = self.writer_buf.get(key).and_then(Option::clone) {
-            return Ok(Some(value));
+        self.check_snapshot()?;
+        if let Some(value) = self.writer_buf.get(key) {
+            return Ok(value.clone());
         }
 
         if let Some(value) = self.mem_table().find_with_sequence_id(key, self.seq_id) {
-            return Ok(Some(value));
+            return Ok(value);
         }
 
         if let Some(value) = self.version.find_data_for_ss_tables(key).await? {
@@ -43,64 +57,162 @@ impl Transaction {
         Ok(None)
     }
 
+    /// 范围扫描，以Key升序返回Key大于start的至多limit条数据，start为None时由首个Key开始
+    ///
+    /// 事务内未提交的写入优先于事务快照中的数据，因此可读取到事务自身的写入与删除
+    #[inline]
+    pub async fn scan(&self, start: Option<&[u8]>, limit: usize) -> Result<Vec<(Bytes, Bytes)>> {
+        self.check_snapshot()?;
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let start_key = start.map(Bytes::copy_from_slice);
+        let writes = self.writer_buf
+            .range(start_key.as_ref().map_or(Bound::Unbounded, Bound::Excluded), Bound::Unbounded)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect_vec();
+
+        let all_ss_tables = self.version.get_all_ss_tables().await;
+        let block_cache = &self.version.block_cache;
+
+        let mut sources = vec![
+            MergeSource::mem(writes),
+            MergeSource::mem(self.mem_table().range_with_sequence(start, self.seq_id)),
+        ];
+        sources.append(&mut MergeSource::tables(&all_ss_tables, block_cache, start)?);
+
+        let mut merging_iter = MergingIter::new(sources);
+        let mut items = Vec::with_capacity(limit);
+
+        while let Some((key, value)) = merging_iter.next_err()? {
+            if let Some(value) = value {
+                items.push((key, value));
+
+                if items.len() >= limit {
+                    break
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
     pub fn set(&mut self, key: &[u8], value: Bytes) {
-        let _ignore = self.writer_buf.insert(
-            Bytes::copy_from_slice(key), Some(value)
-        );
+        self.write_buf(key, Some(value));
     }
 
     pub async fn remove(&mut self, key: &[u8]) -> Result<()> {
         let _ = self.get(key).await?
             .ok_or(KernelError::KeyNotFound)?;
 
-        let _ignore = self.writer_buf
-            .insert(Bytes::copy_from_slice(key), None);
+        self.write_buf(key, None);
+
+        Ok(())
+    }
+
+    fn write_buf(&mut self, key: &[u8], value: Option<Bytes>) {
+        let key = Bytes::copy_from_slice(key);
+        let prev = self.writer_buf.insert(key.clone(), value);
+
+        self.undo_log.push((key, prev));
+    }
+
+    /// 创建保存点，可通过`rollback_to_savepoint`撤销其后的写入
+    #[inline]
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint(self.undo_log.len())
+    }
+
+    /// 撤销保存点之后的写入，事务本身仍可继续使用
+    #[inline]
+    pub fn rollback_to_savepoint(&mut self, savepoint: Savepoint) -> Result<()> {
+        if savepoint.0 > self.undo_log.len() {
+            return Err(KernelError::SavepointNotFound);
+        }
+
+        for (key, prev) in self.undo_log.drain(savepoint.0..).rev() {
+            let _ignore = match prev {
+                Some(value) => self.writer_buf.insert(key, value),
+                None => self.writer_buf.remove(&key),
+            };
+        }
 
         Ok(())
     }
 
-    pub async fn commit(self) -> Result<()> {
+    /// 提交事务，并返回此次提交的Sequence
+    ///
+    /// 取消安全: 与`LsmStore`的写入相同，丢弃该Future时数据要么均未写入，要么已同时写入WAL与MemTable
+    pub async fn commit(mut self) -> Result<i64> {
+        self.check_snapshot()?;
+        self.store_inner.wait_recovered().await?;
         let batch_data = self.writer_buf.iter()
             .map(|(key, value)| (key.clone(), value.clone()))
             .collect_vec();
+        self.store_inner.write_hooks
+            .check(batch_data.iter().map(|(key, value)| (key.as_ref(), value.as_deref())))?;
+        self.store_inner.write_quotas
+            .throttle(batch_data.iter().map(|(key, value)| (key.as_ref(), value.as_deref()))).await;
+        // 避免与同一Key上的compare_and_swap等读-改-写操作交错
+        let latches = Arc::clone(&self.latches);
+        let _guards = latches.lock_all(batch_data.iter().map(|(key, _)| key.as_ref())).await;
 
         // Wal与MemTable双写
-        if self.config().wal_enable {
-            self.wal().log_batch(batch_data.clone())?;
+        let (ticket, seq_id) = self.store_inner.log_batch_data(batch_data.clone())?;
+        let published = self.store_inner.keyspace_events.is_subscribed()
+            .then(|| batch_data.clone());
+        let data_len = self.store_inner.insert_batch_data(batch_data, seq_id)?;
+        self.store_inner.keyspace_events.publish_writes(published.iter().flatten());
+        self.release();
+
+        is_exceeded_then_minor(data_len, &self.compactor_tx, &self.store_inner).await?;
+        if let Some(ticket) = ticket {
+            self.store_inner.wal_sync(ticket).await?;
         }
 
-        let mem_table = self.mem_table();
-        let data_len = mem_table.insert_batch_data(batch_data, Sequence::create())?;
-
-        let _ = mem_table.tx_count
-            .fetch_sub(1, Ordering::Release);
+        Ok(seq_id)
+    }
 
-        is_exceeded_then_minor(data_len, &self.compactor_tx, self.config())?;
+    /// 释放对MemTable的持有，使等待事务结束的Compaction得以继续
+    fn release(&mut self) {
+        if !self.is_released {
+            self.is_released = true;
+            let _ = self.mem_table().release_snapshot(self.seq_id);
+        }
+    }
 
+    /// 快照超出`Config::max_snapshot_age`被强制释放后，事务不可再使用
+    fn check_snapshot(&self) -> Result<()> {
+        if !self.mem_table().is_snapshot_alive(self.seq_id) {
+            return Err(KernelError::SnapshotExpired);
+        }
         Ok(())
     }
 
     fn mem_table(&self) -> &MemTable {
         &self.store_inner.mem_table
     }
+}
 
-    fn wal(&self) -> &LogLoader {
-        &self.store_inner.wal
-    }
-
-    fn config(&self) -> &Config {
-        &self.store_inner.config
+impl Drop for Transaction {
+    /// 事务未提交即被丢弃(包括提交的Future被取消)时同样需释放，否则Compaction将一直等待
+    #[inline]
+    fn drop(&mut self) {
+        self.release();
     }
 }
 
 /// TODO: 更多的Test Case
 #[cfg(test)]
 mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
     use bytes::Bytes;
     use itertools::Itertools;
     use tempfile::TempDir;
     use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
     use crate::kernel::{KVStore, Result};
+    use crate::KernelError;
 
     #[test]
     fn test_transaction() -> Result<()> {
@@ -150,7 +262,7 @@ mod tests {
                 assert_eq!(kv_store.get(&vec_kv[i].0).await?, None);
             }
 
-            transaction.commit().await?;
+            let _ = transaction.commit().await?;
 
             for i in 0..times - 1 {
                 assert_eq!(kv_store.get(&vec_kv[i].0).await?, Some(vec_kv[i].1.clone()));
@@ -159,4 +271,129 @@ mod tests {
             Ok(())
         })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_commit_with_latches() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            let guard = kv_store.latches.lock(b"k2".as_slice()).await;
+
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(b"k1", Bytes::from_static(b"v1"));
+            transaction.set(b"k2", Bytes::from_static(b"v2"));
+            // 其他读-改-写操作持有Key的锁时，提交需等待其完成
+            assert!(tokio::time::timeout(Duration::from_millis(50), transaction.commit()).await.is_err());
+            assert_eq!(kv_store.get(b"k1").await?, None);
+            drop(guard);
+
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(b"k1", Bytes::from_static(b"v1"));
+            transaction.set(b"k2", Bytes::from_static(b"v2"));
+            let _ = transaction.commit().await?;
+            assert!(kv_store.compare_and_swap(b"k2", Some(b"v2"), Some(Bytes::from_static(b"v3"))).await?);
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v3")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_max_snapshot_age() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .max_snapshot_age(Duration::from_millis(10));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+
+            let transaction = kv_store.new_transaction().await;
+            assert_eq!(kv_store.oldest_pinned_sequence(), Some(transaction.seq_id));
+            assert_eq!(transaction.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            // 超出存活时长后，Compaction不再等待该事务
+            sleep(Duration::from_millis(20));
+            kv_store.flush().await?;
+            assert_eq!(kv_store.oldest_pinned_sequence(), None);
+            assert!(matches!(transaction.get(b"k1").await, Err(KernelError::SnapshotExpired)));
+            assert!(matches!(transaction.commit().await, Err(KernelError::SnapshotExpired)));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_savepoint() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            let mut transaction = kv_store.new_transaction().await;
+
+            transaction.set(b"kip_1", Bytes::from_static(b"1"));
+            let savepoint_1 = transaction.savepoint();
+            transaction.set(b"kip_1", Bytes::from_static(b"2"));
+            transaction.set(b"kip_2", Bytes::from_static(b"2"));
+            let savepoint_2 = transaction.savepoint();
+            transaction.set(b"kip_3", Bytes::from_static(b"3"));
+
+            transaction.rollback_to_savepoint(savepoint_2)?;
+            assert_eq!(transaction.get(b"kip_3").await?, None);
+            assert_eq!(transaction.get(b"kip_2").await?, Some(Bytes::from_static(b"2")));
+
+            transaction.rollback_to_savepoint(savepoint_1)?;
+            assert_eq!(transaction.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert_eq!(transaction.get(b"kip_2").await?, None);
+            // 回滚至更早的保存点后，其后的保存点失效
+            assert!(matches!(
+                transaction.rollback_to_savepoint(savepoint_2),
+                Err(KernelError::SavepointNotFound)
+            ));
+
+            let _ = transaction.commit().await?;
+            assert_eq!(kv_store.get(b"kip_1").await?, Some(Bytes::from_static(b"1")));
+            assert_eq!(kv_store.get(b"kip_2").await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_transaction_scan() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .minor_threshold_with_len(10);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            for i in 0..20u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from_static(b"committed")).await?;
+            }
+            kv_store.flush().await?;
+
+            let mut transaction = kv_store.new_transaction().await;
+            transaction.set(&3u32.to_be_bytes(), Bytes::from_static(b"pending"));
+            transaction.set(&25u32.to_be_bytes(), Bytes::from_static(b"pending"));
+            transaction.remove(&5u32.to_be_bytes()).await?;
+            // 事务开启后的写入对事务不可见
+            kv_store.set(&21u32.to_be_bytes(), Bytes::from_static(b"committed")).await?;
+
+            let items = transaction.scan(Some(&2u32.to_be_bytes()), 3).await?;
+            assert_eq!(items, vec![
+                (Bytes::copy_from_slice(&3u32.to_be_bytes()), Bytes::from_static(b"pending")),
+                (Bytes::copy_from_slice(&4u32.to_be_bytes()), Bytes::from_static(b"committed")),
+                (Bytes::copy_from_slice(&6u32.to_be_bytes()), Bytes::from_static(b"committed")),
+            ]);
+
+            let items = transaction.scan(Some(&18u32.to_be_bytes()), 10).await?;
+            assert_eq!(items.into_iter().map(|(key, _)| key).collect_vec(), vec![
+                Bytes::copy_from_slice(&19u32.to_be_bytes()),
+                Bytes::copy_from_slice(&25u32.to_be_bytes()),
+            ]);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::hash_map::RandomState;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use crate::kernel::Result;
+use crate::kernel::utils::lru_cache::ShardingLruCache;
+
+/// 不存在Key的缓存
+///
+/// 缓存在所有SSTable中(包括经布隆过滤器判定)确认不存在的Key，
+/// TTL内再次查询时直接跳过SSTable的读取，避免热点的不存在Key反复访问磁盘
+///
+/// 缓存归属于Version，SSTable新增时随新的Version重建，因此不会遮蔽此后写入的数据
+pub(crate) struct NegativeCache {
+    inner: ShardingLruCache<Bytes, Instant>,
+    cap: usize,
+    ttl: Duration,
+}
+
+impl NegativeCache {
+    pub(crate) fn new(cap: usize, ttl: Duration) -> Result<Self> {
+        Ok(NegativeCache {
+            inner: ShardingLruCache::new(cap, 16, RandomState::default())?,
+            cap,
+            ttl,
+        })
+    }
+
+    /// 以相同的配置创建空的缓存
+    pub(crate) fn renew(&self) -> Result<Self> {
+        Self::new(self.cap, self.ttl)
+    }
+
+    /// 判断Key是否在TTL内被确认为不存在
+    pub(crate) fn is_absent(&self, key: &[u8]) -> bool {
+        let key = Bytes::copy_from_slice(key);
+
+        match self.inner.get(&key) {
+            Some(cached_at) if cached_at.elapsed() < self.ttl => true,
+            Some(_) => {
+                let _ignore = self.inner.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn insert(&self, key: &[u8]) {
+        let _ignore = self.inner.put(Bytes::copy_from_slice(key), Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+    use crate::kernel::lsm::negative_cache::NegativeCache;
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_negative_cache() -> Result<()> {
+        let cache = NegativeCache::new(16, Duration::from_millis(50))?;
+
+        assert!(!cache.is_absent(b"k1"));
+        cache.insert(b"k1");
+        assert!(cache.is_absent(b"k1"));
+        assert!(!cache.renew()?.is_absent(b"k1"));
+
+        // 超出TTL后需重新查询
+        sleep(Duration::from_millis(60));
+        assert!(!cache.is_absent(b"k1"));
+
+        Ok(())
+    }
+}
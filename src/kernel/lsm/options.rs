@@ -0,0 +1,369 @@
+use std::fs;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::kernel::io;
+use crate::kernel::Result;
+use crate::kernel::lsm::{FORMAT_VERSION, LEGACY_FORMAT_VERSION};
+use crate::kernel::lsm::lsm_kv::Config;
+use crate::kernel::lsm::ss_table::DATA_COMPRESS_TYPE;
+use crate::KernelError;
+
+/// 数据目录中记录生效选项的文件
+pub(crate) const DEFAULT_OPTIONS_FILE: &str = "OPTIONS";
+
+/// Key的比较方式，目前仅支持按字节序比较
+const COMPARATOR: &str = "bytewise";
+
+/// 读取选项
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadOptions {
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl ReadOptions {
+    /// 读取的截止时间，超出时返回`KernelError::TimedOut`
+    #[inline]
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// 以当前时间起的时长作为截止时间
+    #[inline]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+}
+
+/// 写入的确认级别，即写入返回前所需达到的持久性，级别越高延迟越大
+///
+/// 写入在等待WAL同步前即已写入MemTable并可见，因此等待同步时返回错误的写入同样已生效，
+/// 并会随此后的Flush持久化，而Store随即转为只读，此后的写入返回`KernelError::WalSyncFailed`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ack {
+    /// 写入MemTable后即返回，不等待WAL的同步，进程崩溃时可能丢失
+    Memory,
+    /// 写入WAL后返回，开启`Config::wal_sync_window_micros`时等待组同步落盘
+    #[default]
+    Wal,
+    /// 等待WAL同步至磁盘后返回，未开启组同步时同样以组同步的方式等待，
+    /// 使并发的Fsync写入合并为一次同步，需开启WAL
+    Fsync,
+}
+
+impl Ack {
+    /// 校验该确认级别在当前配置下是否可以达到
+    pub(crate) fn check(&self, wal_enable: bool) -> Result<()> {
+        match self {
+            Ack::Fsync if !wal_enable => Err(KernelError::NotSupport("fsync acknowledgment requires WAL")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 写入选项
+///
+/// 截止时间仅作用于写入WAL前的等待(如Key锁、WAL重放与压缩)，
+/// 写入WAL后不再中断，以保证WAL与MemTable的数据一致
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) ack: Ack,
+}
+
+impl WriteOptions {
+    /// 写入的截止时间，超出时返回`KernelError::TimedOut`
+    #[inline]
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// 以当前时间起的时长作为截止时间
+    #[inline]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// 写入的确认级别，默认为`Ack::Wal`
+    #[inline]
+    pub fn ack(mut self, ack: Ack) -> Self {
+        self.ack = ack;
+        self
+    }
+}
+
+/// 运行时可修改的选项
+///
+/// 通过`LsmStore::set_options`生效，未设置的选项保持不变
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MutableOptions {
+    pub(crate) block_cache_capacity: Option<usize>,
+    pub(crate) table_cache_size: Option<usize>,
+}
+
+impl MutableOptions {
+    /// Block缓存的容量, 单位为B，以`Config::block_size`换算为可缓存的Block数量
+    ///
+    /// 缩小时立即驱逐超出容量的Block
+    #[inline]
+    pub fn block_cache_capacity(mut self, block_cache_capacity: usize) -> Self {
+        self.block_cache_capacity = Some(block_cache_capacity);
+        self
+    }
+
+    /// 可缓存的SSTable数量
+    #[inline]
+    pub fn table_cache_size(mut self, table_cache_size: usize) -> Self {
+        self.table_cache_size = Some(table_cache_size);
+        self
+    }
+}
+
+/// 持久化于OPTIONS文件中的选项
+///
+/// 仅记录影响已写入数据解析的选项，打开时与当前配置进行校验，
+/// 避免不兼容的配置被静默地应用于已有的数据
+///
+/// Tips: comparator与compression目前为编译期的常量，仅在以不同的构建打开同一数据目录时才可能不一致
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PersistentOptions {
+    /// 数据目录中可能存在的最旧的SSTable格式版本，仅在`LsmStore::migrate`完成后提高
+    format_version: u32,
+    /// 写入新SSTable的格式版本，高于当前支持的版本时说明数据目录曾由更新的版本写入
+    #[serde(default)]
+    writer_format_version: u32,
+    comparator: String,
+    compression: String,
+    /// SSTable存储路径的数量，即默认路径与`Config::level_paths`
+    sst_path_count: usize,
+    /// 是否曾开启`Config::value_chunk_size`，开启后即保持为true
+    #[serde(default)]
+    value_chunking: bool,
+}
+
+impl PersistentOptions {
+    pub(crate) fn from_config(config: &Config) -> Self {
+        PersistentOptions {
+            format_version: FORMAT_VERSION,
+            writer_format_version: FORMAT_VERSION,
+            comparator: COMPARATOR.to_owned(),
+            compression: DATA_COMPRESS_TYPE.name().to_owned(),
+            sst_path_count: config.sst_paths().len(),
+            value_chunking: config.value_chunk_size.is_some(),
+        }
+    }
+
+    /// 校验当前配置与已持久化的选项是否兼容，兼容时以当前配置覆盖OPTIONS文件
+    ///
+    /// 不存在OPTIONS文件时(如新的数据目录或旧版本创建的数据目录)直接写入，
+    /// 此时若已存在SSTable则无法得知其格式，因此视为最旧的格式
+    pub(crate) fn check_and_persist(config: &Config) -> Result<()> {
+        let mut options = Self::from_config(config);
+
+        match Self::load(config)? {
+            Some(persisted) => {
+                options.check_compatible(&persisted)?;
+                // 旧格式的SSTable可能仍未被重写，因此保持已记录的最旧格式
+                options.format_version = persisted.format_version;
+            }
+            None if Self::has_ss_table(config) => options.format_version = LEGACY_FORMAT_VERSION,
+            None => (),
+        }
+
+        options.persist(config)
+    }
+
+    /// `LsmStore::migrate`完成后，格式版本低于to_version的SSTable均已被重写
+    pub(crate) fn migrated(config: &Config, to_version: u32) -> Result<()> {
+        let Some(mut options) = Self::load(config)? else {
+            return Ok(());
+        };
+        if options.format_version < to_version {
+            options.format_version = to_version;
+            options.persist(config)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(config: &Config) -> Result<Option<PersistentOptions>> {
+        let path = config.path().join(DEFAULT_OPTIONS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_slice(&fs::read(&path)?)?))
+    }
+
+    fn persist(&self, config: &Config) -> Result<()> {
+        let path = config.path().join(DEFAULT_OPTIONS_FILE);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        io::rename_replace(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    fn has_ss_table(config: &Config) -> bool {
+        config.sst_paths()
+            .iter()
+            .any(|path| fs::read_dir(path).is_ok_and(|mut dir| dir.next().is_some()))
+    }
+
+    fn check_compatible(&self, persisted: &PersistentOptions) -> Result<()> {
+        let persisted_version = persisted.writer_format_version.max(persisted.format_version);
+        if persisted_version > self.writer_format_version {
+            return Err(KernelError::IncompatibleOptions(format!(
+                "data format version {} is newer than the supported version {}",
+                persisted_version, self.writer_format_version
+            )));
+        }
+        if persisted.comparator != self.comparator {
+            return Err(KernelError::IncompatibleOptions(format!(
+                "comparator `{}` does not match the persisted `{}`", self.comparator, persisted.comparator
+            )));
+        }
+        if persisted.compression != self.compression {
+            return Err(KernelError::IncompatibleOptions(format!(
+                "compression `{}` does not match the persisted `{}`", self.compression, persisted.compression
+            )));
+        }
+        // SSTable仅记录存储路径的序号，减少路径后原路径中的SSTable将无法找到
+        if persisted.sst_path_count > self.sst_path_count {
+            return Err(KernelError::IncompatibleOptions(format!(
+                "{} SSTable paths configured but {} persisted, level_paths can not be removed",
+                self.sst_path_count, persisted.sst_path_count
+            )));
+        }
+        // 关闭分块后覆盖已分块的Value时不会释放其分块
+        if persisted.value_chunking && !self.value_chunking {
+            return Err(KernelError::IncompatibleOptions(
+                "value_chunk_size can not be disabled once values have been chunked".to_owned()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 在截止时间前等待future完成，超时后future被丢弃并返回`KernelError::TimedOut`
+pub(crate) async fn with_deadline<F: Future>(deadline: Option<Instant>, future: F) -> Result<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), future).await
+            .map_err(|_| KernelError::TimedOut),
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::{FORMAT_VERSION, LEGACY_FORMAT_VERSION};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::lsm::options::{DEFAULT_OPTIONS_FILE, with_deadline};
+    use crate::kernel::Result;
+    use crate::KernelError;
+
+    #[test]
+    fn test_with_deadline() -> Result<()> {
+        tokio_test::block_on(async move {
+            assert_eq!(with_deadline(None, async { 1 }).await?, 1);
+            assert_eq!(with_deadline(Some(Instant::now() + Duration::from_secs(1)), async { 1 }).await?, 1);
+
+            let pending = tokio::time::sleep(Duration::from_secs(10));
+            assert!(matches!(
+                with_deadline(Some(Instant::now() + Duration::from_millis(10)), pending).await,
+                Err(KernelError::TimedOut)
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_options_file() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let level_path = temp_dir.path().join("level");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path().join("data"));
+            let kv_store = LsmStore::open_with_config(config.clone().level_paths(vec![level_path])).await?;
+            kv_store.flush().await?;
+            drop(kv_store);
+
+            // 移除存储路径后，其中的SSTable将无法找到
+            assert!(matches!(
+                LsmStore::open_with_config(config.clone()).await,
+                Err(KernelError::IncompatibleOptions(_))
+            ));
+
+            let options_path = config.path().join(DEFAULT_OPTIONS_FILE);
+            let options = fs::read_to_string(&options_path)?;
+            fs::write(&options_path, options.replace("bytewise", "reverse"))?;
+            assert!(matches!(
+                LsmStore::open(config.path()).await,
+                Err(KernelError::IncompatibleOptions(_))
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_options_format_version() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path());
+            let options_path = config.path().join(DEFAULT_OPTIONS_FILE);
+            let format_version = || -> Result<serde_json::Value> {
+                Ok(serde_json::from_slice::<serde_json::Value>(&fs::read(&options_path)?)?["format_version"].clone())
+            };
+
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            kv_store.set(b"k", Bytes::from_static(b"v")).await?;
+            kv_store.flush().await?;
+            drop(kv_store);
+            assert_eq!(format_version()?, FORMAT_VERSION);
+
+            // 模拟旧版本创建且未记录OPTIONS的数据目录，其中的SSTable视为最旧的格式
+            fs::remove_file(&options_path)?;
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            drop(kv_store);
+            assert_eq!(format_version()?, LEGACY_FORMAT_VERSION);
+
+            // 再次打开时不会覆盖为当前的格式版本，直至迁移完成
+            let kv_store = LsmStore::open_with_config(config.clone()).await?;
+            assert_eq!(format_version()?, LEGACY_FORMAT_VERSION);
+            let _ = kv_store.migrate(FORMAT_VERSION).await?;
+            assert_eq!(format_version()?, FORMAT_VERSION);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_options_value_chunking() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path());
+            let kv_store = LsmStore::open_with_config(config.clone().value_chunk_size(1024)).await?;
+            drop(kv_store);
+
+            // 关闭分块后已分块的Value在覆盖时无法释放其分块
+            assert!(matches!(
+                LsmStore::open_with_config(config.clone()).await,
+                Err(KernelError::IncompatibleOptions(_))
+            ));
+            let _ = LsmStore::open_with_config(config.value_chunk_size(4096)).await?;
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use bytes::Bytes;
+use crate::kernel::Result;
+use crate::kernel::utils::lru_cache::{CacheStats, ShardingLruCache};
+
+/// 写入序号的分片数量
+const SEQUENCE_SLOTS: usize = 1024;
+
+/// 缓存行，sequence为填充时Key所属分片的写入序号
+struct Row {
+    value: Option<Bytes>,
+    sequence: u64,
+}
+
+/// 行缓存
+///
+/// 缓存Key对应的查询结果(包括不存在或已删除)，使热点Key的读取无需经过MemTable与SSTable
+///
+/// 以Key的Hash值分片记录写入序号，写入时在数据可见后递增所属分片的序号，
+/// 缓存行仅在其填充时的序号与当前序号一致时有效，
+/// 因此填充与写入并发时旧数据不会被缓存，同一分片中其他Key的写入仅会造成额外的未命中
+pub(crate) struct RowCache<S = RandomState> {
+    inner: ShardingLruCache<Bytes, Row>,
+    sequences: Vec<AtomicU64>,
+    hasher: S,
+}
+
+impl RowCache {
+    pub(crate) fn new(cap: usize) -> Result<Self> {
+        let sequences = (0..SEQUENCE_SLOTS)
+            .map(|_| AtomicU64::new(0))
+            .collect();
+
+        Ok(RowCache {
+            inner: ShardingLruCache::new(cap, 16, RandomState::default())?,
+            sequences,
+            hasher: RandomState::default(),
+        })
+    }
+}
+
+impl<S: BuildHasher> RowCache<S> {
+    fn slot(&self, key: &[u8]) -> &AtomicU64 {
+        &self.sequences[self.hasher.hash_one(key) as usize % self.sequences.len()]
+    }
+
+    /// 获取有效的缓存行，未命中时返回None
+    pub(crate) fn get(&self, key: &[u8]) -> Option<Option<Bytes>> {
+        let sequence = self.sequence(key);
+
+        self.inner.get(&Bytes::copy_from_slice(key))
+            .filter(|row| row.sequence == sequence)
+            .map(|row| row.value.clone())
+    }
+
+    /// 获取Key所属分片当前的写入序号
+    ///
+    /// 需在读取数据前获取，并在填充时传入
+    pub(crate) fn sequence(&self, key: &[u8]) -> u64 {
+        self.slot(key).load(Ordering::Acquire)
+    }
+
+    pub(crate) fn fill(&self, key: &[u8], value: Option<Bytes>, sequence: u64) {
+        let _ignore = self.inner.put(Bytes::copy_from_slice(key), Row { value, sequence });
+    }
+
+    /// 使Key的缓存行失效，需在写入的数据可见后调用
+    pub(crate) fn invalidate(&self, key: &[u8]) {
+        let _ = self.slot(key).fetch_add(1, Ordering::Release);
+    }
+
+    /// 使所有缓存行失效，用于SSTable被移除等无法确定受影响Key的情况
+    pub(crate) fn invalidate_all(&self) {
+        for slot in &self.sequences {
+            let _ = slot.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use crate::kernel::lsm::row_cache::RowCache;
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_row_cache() -> Result<()> {
+        let row_cache = RowCache::new(16)?;
+        let value = Some(Bytes::from_static(b"v1"));
+
+        assert_eq!(row_cache.get(b"k1"), None);
+        row_cache.fill(b"k1", value.clone(), row_cache.sequence(b"k1"));
+        assert_eq!(row_cache.get(b"k1"), Some(value.clone()));
+
+        // 读取期间发生写入时，填充的缓存行无效
+        let sequence = row_cache.sequence(b"k1");
+        row_cache.invalidate(b"k1");
+        assert_eq!(row_cache.get(b"k1"), None);
+        row_cache.fill(b"k1", value, sequence);
+        assert_eq!(row_cache.get(b"k1"), None);
+
+        // 不存在的Key同样可以被缓存
+        row_cache.fill(b"k2", None, row_cache.sequence(b"k2"));
+        assert_eq!(row_cache.get(b"k2"), Some(None));
+
+        Ok(())
+    }
+}
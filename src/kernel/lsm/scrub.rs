@@ -0,0 +1,136 @@
+use std::sync::Weak;
+use std::time::{Duration, Instant};
+use tokio::time;
+use tracing::error;
+use crate::kernel::lsm::event::ScrubEvent;
+use crate::kernel::lsm::lsm_kv::StoreInner;
+
+/// 两轮校验之间的最小间隔，避免没有SSTable时空转
+const SCRUB_IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 后台校验(Scrubbing)
+///
+/// 以rate(字节/秒)限制的速率逐个读取SSTable并校验其所有Block的CRC，
+/// 在用户查询之前发现静默的数据损坏，并通过`EventListener::on_scrub`报告
+///
+/// 每轮开始时记录所有SSTable的Gen，校验前确认其仍存在于当前Version中，
+/// 因此已被压缩删除的SSTable会被跳过，且校验期间不会长时间持有旧的Version
+pub(crate) async fn scrub_periodically(store_inner: Weak<StoreInner>, rate: u64) {
+    loop {
+        let start = Instant::now();
+        let Some(gens) = tables_gen(&store_inner).await else {
+            return
+        };
+        let (mut tables, mut bytes) = (0, 0);
+
+        for gen in gens {
+            let Some(inner) = store_inner.upgrade() else {
+                return
+            };
+            let version = inner.ver_status.current().await;
+            let Some((level, ss_table)) = version.get_all_ss_tables().await
+                .into_iter()
+                .enumerate()
+                .find_map(|(level, ss_tables)| {
+                    ss_tables.into_iter()
+                        .find(|ss_table| ss_table.get_gen() == gen)
+                        .map(|ss_table| (level, ss_table))
+                })
+            else {
+                continue
+            };
+            drop(version);
+
+            let size = ss_table.get_size_of_disk();
+            if let Err(err) = ss_table.verify() {
+                error!("[Scrub][SSTable: {}][Level: {}][Corrupted]: {:?}", gen, level, err);
+                inner.config.notify_scrub(&ScrubEvent::Corrupted { gen, level, reason: err.to_string() });
+                if inner.config.quarantine_corrupted {
+                    inner.mark_corrupted(gen);
+                }
+            }
+            tables += 1;
+            bytes += size;
+            drop(inner);
+
+            time::sleep(Duration::from_micros(size * 1_000_000 / rate.max(1))).await;
+        }
+
+        if let Some(inner) = store_inner.upgrade() {
+            inner.config.notify_scrub(&ScrubEvent::PassCompleted { tables, bytes, elapsed: start.elapsed() });
+        }
+        time::sleep(SCRUB_IDLE_INTERVAL).await;
+    }
+}
+
+async fn tables_gen(store_inner: &Weak<StoreInner>) -> Option<Vec<i64>> {
+    let inner = store_inner.upgrade()?;
+    let version = inner.ver_status.current().await;
+
+    Some(version.get_all_ss_tables().await
+        .iter()
+        .flatten()
+        .map(|ss_table| ss_table.get_gen())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use parking_lot::Mutex;
+    use tempfile::TempDir;
+    use crate::kernel::lsm::event::{EventListener, ScrubEvent};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
+    use crate::kernel::{KVStore, Result};
+
+    #[derive(Debug, Default)]
+    struct ScrubRecorder(Mutex<Vec<ScrubEvent>>);
+
+    impl EventListener for ScrubRecorder {
+        fn on_scrub(&self, event: &ScrubEvent) {
+            self.0.lock().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn test_scrub() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let recorder = Arc::new(ScrubRecorder::default());
+            let listener: Arc<dyn EventListener> = Arc::<ScrubRecorder>::clone(&recorder);
+            let config = Config::new(temp_dir.path())
+                .scrub_rate(u64::MAX)
+                .add_event_listener(listener);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            for i in 0..100u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from(vec![b'k'; 64])).await?;
+            }
+            kv_store.flush().await?;
+
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            let events = recorder.0.lock().clone();
+            assert!(!events.is_empty());
+            assert!(events.iter().all(|event| matches!(event, ScrubEvent::PassCompleted { .. })));
+
+            // 模拟位翻转: 破坏SSTable的首个数据Block
+            for entry in fs::read_dir(temp_dir.path().join(DEFAULT_SS_TABLE_PATH))? {
+                let path = entry?.path();
+                let mut bytes = fs::read(&path)?;
+                if let Some(byte) = bytes.get_mut(8) {
+                    *byte = !*byte;
+                }
+                fs::write(&path, bytes)?;
+            }
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            assert!(recorder.0.lock().iter()
+                .any(|event| matches!(event, ScrubEvent::Corrupted { .. })));
+
+            Ok(())
+        })
+    }
+}
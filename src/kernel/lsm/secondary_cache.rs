@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use parking_lot::Mutex;
+use tracing::warn;
+use crate::kernel::Result;
+
+/// 二级缓存文件的扩展名
+const SECONDARY_CACHE_EXTENSION: &str = "blk";
+
+/// 二级Block缓存
+///
+/// 以本地目录(如SSD)中的文件存储DataBlock的原始数据(即压缩后的数据)，容量以字节计算并按LRU淘汰
+///
+/// DataBlock从SSTable中读取时会同时写入二级缓存，
+/// 因此被内存中的BlockCache驱逐的Block可以从二级缓存中读取，而不需要再次访问冷存储
+pub(crate) struct SecondaryCache {
+    dir_path: PathBuf,
+    capacity: usize,
+    inner: Mutex<SecondaryInner>,
+}
+
+struct SecondaryInner {
+    /// (gen, offset) -> (Block长度, 最近访问的序号)
+    entries: HashMap<(i64, u32), (usize, u64)>,
+    /// 最近访问的序号 -> (gen, offset)，序号最小者为最久未访问
+    lru: BTreeMap<u64, (i64, u32)>,
+    tick: u64,
+    size: usize,
+}
+
+impl SecondaryInner {
+    fn touch(&mut self, key: (i64, u32)) {
+        self.tick += 1;
+        let tick = self.tick;
+
+        if let Some((_, old_tick)) = self.entries.get_mut(&key) {
+            let _ignore = self.lru.remove(old_tick);
+            *old_tick = tick;
+            let _ignore1 = self.lru.insert(tick, key);
+        }
+    }
+
+    fn remove(&mut self, key: &(i64, u32)) {
+        if let Some((len, tick)) = self.entries.remove(key) {
+            let _ignore = self.lru.remove(&tick);
+            self.size -= len;
+        }
+    }
+}
+
+impl SecondaryCache {
+    /// 创建二级缓存
+    ///
+    /// 二级缓存的索引仅存在于内存中，因此会清除目录中残留的缓存文件
+    pub(crate) fn new(dir_path: impl Into<PathBuf>, capacity: usize) -> Result<Self> {
+        let dir_path = dir_path.into();
+        fs::create_dir_all(&dir_path)?;
+
+        for entry in fs::read_dir(&dir_path)? {
+            let path = entry?.path();
+            if path.extension() == Some(SECONDARY_CACHE_EXTENSION.as_ref()) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(SecondaryCache {
+            dir_path,
+            capacity,
+            inner: Mutex::new(SecondaryInner {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+                size: 0,
+            }),
+        })
+    }
+
+    fn file_path(&self, gen: i64, offset: u32) -> PathBuf {
+        self.dir_path.join(format!("{gen}_{offset}.{SECONDARY_CACHE_EXTENSION}"))
+    }
+
+    /// 获取SSTable中对应位置的Block数据
+    pub(crate) fn get(&self, gen: i64, offset: u32, len: usize) -> Option<Vec<u8>> {
+        let key = (gen, offset);
+        let mut inner = self.inner.lock();
+
+        if inner.entries.get(&key).map(|(cache_len, _)| *cache_len) != Some(len) {
+            return None;
+        }
+        match fs::read(self.file_path(gen, offset)) {
+            Ok(bytes) if bytes.len() == len => {
+                inner.touch(key);
+                Some(bytes)
+            }
+            _ => {
+                // 缓存文件异常时移除该缓存，由调用方从SSTable中读取
+                inner.remove(&key);
+                None
+            }
+        }
+    }
+
+    /// 写入SSTable中对应位置的Block数据
+    ///
+    /// 二级缓存仅作为加速，因此写入失败时仅进行警告
+    pub(crate) fn insert(&self, gen: i64, offset: u32, bytes: &[u8]) {
+        if bytes.len() > self.capacity {
+            return;
+        }
+        let key = (gen, offset);
+        let mut inner = self.inner.lock();
+
+        if inner.entries.contains_key(&key) {
+            return;
+        }
+        if let Err(err) = fs::write(self.file_path(gen, offset), bytes) {
+            warn!("[SecondaryCache][insert][Gen: {}][Offset: {}]: {:?}", gen, offset, err);
+            return;
+        }
+        let _ignore = inner.entries.insert(key, (bytes.len(), 0));
+        inner.size += bytes.len();
+        inner.touch(key);
+
+        while inner.size > self.capacity {
+            let oldest = inner.lru.iter()
+                .next()
+                .map(|(_, key)| *key);
+
+            match oldest {
+                Some(oldest) => {
+                    inner.remove(&oldest);
+                    let _ignore = fs::remove_file(self.file_path(oldest.0, oldest.1));
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// 二级缓存中数据的总大小
+    #[allow(dead_code)]
+    pub(crate) fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use crate::kernel::lsm::secondary_cache::SecondaryCache;
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_secondary_cache() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let cache = SecondaryCache::new(temp_dir.path(), 7)?;
+
+        cache.insert(1, 0, b"kip");
+        cache.insert(1, 3, b"db");
+        assert_eq!(cache.get(1, 0, 3), Some(b"kip".to_vec()));
+        // 长度不一致时视为未命中
+        assert_eq!(cache.get(1, 3, 3), None);
+        assert_eq!(cache.size(), 5);
+
+        // 超出容量时淘汰最久未访问的(1, 3)
+        cache.insert(2, 0, b"lsm");
+        assert_eq!(cache.get(1, 3, 2), None);
+        assert_eq!(cache.get(1, 0, 3), Some(b"kip".to_vec()));
+        assert_eq!(cache.get(2, 0, 3), Some(b"lsm".to_vec()));
+        assert_eq!(cache.size(), 6);
+
+        // 重新打开时清除残留的缓存文件
+        drop(cache);
+        let cache = SecondaryCache::new(temp_dir.path(), 7)?;
+        assert_eq!(cache.get(1, 0, 3), None);
+        assert_eq!(temp_dir.path().read_dir()?.count(), 0);
+
+        Ok(())
+    }
+}
@@ -1,26 +1,36 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use bytes::Bytes;
 use growable_bloom_filter::GrowableBloom;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tracing::info;
-use crate::kernel::io::{IoFactory, IoReader, IoType};
-use crate::kernel::lsm::{MetaBlock, Footer, TABLE_FOOTER_SIZE};
+use crate::kernel::io::{FileAdvice, IoFactory, IoReader, IoType};
+use crate::kernel::lsm::{MetaBlock, Footer, LegacyMetaBlock, UncountedMetaBlock, UntypedMetaBlock, TABLE_FOOTER_SIZE, FORMAT_VERSION, LEGACY_FORMAT_VERSION, UNCOUNTED_FORMAT_VERSION, UNTYPED_FORMAT_VERSION};
 use crate::kernel::lsm::block::{Block, BlockBuilder, BlockCache, BlockItem, BlockOptions, BlockType, CompressType, Index, Value};
+use crate::kernel::lsm::column_family::TableOptions;
 use crate::kernel::lsm::lsm_kv::Config;
 use crate::kernel::lsm::mem_table::KeyValue;
+use crate::kernel::lsm::secondary_cache::SecondaryCache;
+use crate::kernel::lsm::stats::PerfContext;
 use crate::kernel::lsm::version::Version;
 use crate::kernel::Result;
 use crate::KernelError;
 
+/// DataBlock默认的压缩方式，未记录压缩方式的旧格式SSTable同样以此压缩
+pub(crate) const DATA_COMPRESS_TYPE: CompressType = CompressType::LZ4;
+
 pub(crate) struct SSTable {
-    inner: Arc<SSTableInner>
+    inner: Arc<SSTableInner>,
+    /// 二级Block缓存，由SSTableLoader设置
+    secondary_cache: Option<Arc<SecondaryCache>>,
 }
 
 impl Clone for SSTable {
     fn clone(&self) -> Self {
         SSTable {
-            inner: Arc::clone(&self.inner)
+            inner: Arc::clone(&self.inner),
+            secondary_cache: self.secondary_cache.clone(),
         }
     }
 }
@@ -37,10 +47,21 @@ pub(crate) struct SSTableInner {
     gen: i64,
     // 统计信息存储Block
     meta: MetaBlock,
+    // 文件的格式版本
+    format_version: u32,
 }
 
 /// 数据范围索引
 /// 用于缓存SSTable中所有数据的第一个和最后一个数据的Key
+/// 无法读取的数据Block中Key的范围，即(start, end]，start为None时不限下界
+///
+/// 由Index Block中前后两个数据Block的分隔Key确定，因此可能略大于Block中数据实际的范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LostRange {
+    pub(crate) start: Option<Bytes>,
+    pub(crate) end: Bytes,
+}
+
 /// 标明数据的范围以做到快速区域定位
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Scope {
@@ -78,8 +99,7 @@ impl Scope {
 
     /// 判断scope之间是否相交
     pub(crate) fn meet(&self, target: &Scope) -> bool {
-        (self.start.le(&target.start) && self.end.ge(&target.start))
-            || (self.start.le(&target.end) && self.end.ge(&target.end))
+        self.start.le(&target.end) && self.end.ge(&target.start)
     }
 
     /// 判断key与Scope是否相交
@@ -113,6 +133,37 @@ impl Scope {
     }
 }
 
+/// SSTable中数据的Sequence范围
+///
+/// 合并生成的SSTable以被合并的SSTable的范围的并集记录，因此可能大于实际数据的范围，但总是包含之
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SequenceRange {
+    pub(crate) min: i64,
+    pub(crate) max: i64,
+}
+
+impl SequenceRange {
+    /// 无法得知数据的Sequence时使用(如由WAL恢复或旧版本的SSTable)，不会被判定为早于任何快照
+    pub(crate) const UNKNOWN: SequenceRange = SequenceRange { min: 0, max: i64::MAX };
+
+    pub(crate) fn new(min: i64, max: i64) -> Self {
+        SequenceRange { min, max }
+    }
+
+    /// 由一组SSTable融合成一个范围，为空时视为未知
+    pub(crate) fn fusion_from_vec_ss_table(vec_ss_table: &[SSTable]) -> Self {
+        vec_ss_table.iter()
+            .map(SSTable::get_sequence_range)
+            .reduce(|a, b| SequenceRange::new(a.min.min(b.min), a.max.max(b.max)))
+            .unwrap_or(Self::UNKNOWN)
+    }
+
+    /// 数据是否均早于seq_id
+    pub(crate) fn is_before(&self, seq_id: i64) -> bool {
+        self.max < seq_id
+    }
+}
+
 impl SSTable {
     #[allow(dead_code)]
     pub(crate) fn get_level(&self) -> usize {
@@ -131,10 +182,32 @@ impl SSTable {
         self.inner.footer.size_of_disk as u64
     }
 
+    pub(crate) fn get_path(&self) -> PathBuf {
+        self.inner.reader.get_path()
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.inner.meta.len
     }
 
+    pub(crate) fn get_format_version(&self) -> u32 {
+        self.inner.format_version
+    }
+
+    /// 删除标记的数量
+    pub(crate) fn tombstone_len(&self) -> usize {
+        self.inner.meta.tombstone_len
+    }
+
+    pub(crate) fn get_sequence_range(&self) -> SequenceRange {
+        self.inner.meta.sequence_range
+    }
+
+    /// 常驻内存的MetaBlock(Scope与布隆过滤器)大小
+    pub(crate) fn get_meta_size(&self) -> usize {
+        self.inner.footer.meta_len as usize
+    }
+
     /// 通过已经存在的文件构建SSTable
     ///
     /// 使用原有的路径与分区大小恢复出一个有内容的SSTable
@@ -147,71 +220,278 @@ impl SSTable {
             size_of_disk ,
             reader.get_type()
         );
-        let meta = bincode::deserialize(
-            &reader.read_with_pos(*meta_offset as u64, *meta_len as usize)?
-        )?;
+        let meta_bytes = reader.read_with_pos(*meta_offset as u64, *meta_len as usize)?;
+        let (meta, format_version) = bincode::deserialize(&meta_bytes)
+            .map(|meta| (meta, FORMAT_VERSION))
+            .or_else(|_| bincode::deserialize::<UntypedMetaBlock>(&meta_bytes)
+                .map(|meta| (MetaBlock::from(meta), UNTYPED_FORMAT_VERSION)))
+            .or_else(|_| bincode::deserialize::<UncountedMetaBlock>(&meta_bytes)
+                .map(|meta| (MetaBlock::from(meta), UNCOUNTED_FORMAT_VERSION)))
+            .or_else(|_| bincode::deserialize::<LegacyMetaBlock>(&meta_bytes)
+                .map(|meta| (MetaBlock::from(meta), LEGACY_FORMAT_VERSION)))?;
         Ok(SSTable {
             inner : Arc::new(
-                SSTableInner { footer, gen, reader, meta, }
-            )
+                SSTableInner { footer, gen, reader, meta, format_version }
+            ),
+            secondary_cache: None,
         })
     }
 
-    /// 查询Key对应的Value
-    pub(crate) fn query_with_key(
+    /// 对该SSTable的文件给出访问模式的提示
+    pub(crate) fn advise(&self, advice: FileAdvice) -> Result<()> {
+        self.inner.reader.advise(advice)
+    }
+
+    pub(crate) fn set_secondary_cache(&mut self, secondary_cache: Option<Arc<SecondaryCache>>) {
+        self.secondary_cache = secondary_cache;
+    }
+
+    pub(crate) fn with_secondary_cache(mut self, secondary_cache: Option<Arc<SecondaryCache>>) -> Self {
+        self.set_secondary_cache(secondary_cache);
+        self
+    }
+
+    /// 查询Key对应的Value，返回Some(None)时表示该Key在此SSTable中已被删除
+    ///
+    /// 并发查询同一未缓存的Block时仅进行一次读取
+    pub(crate) async fn query_with_key(
         &self,
         key: &[u8],
         block_cache: &BlockCache
-    ) -> Result<Option<Bytes>> {
+    ) -> Result<Option<Option<Bytes>>> {
         let inner = &self.inner;
-        let reader = inner.reader.as_ref();
-        if inner.meta.filter.contains(key) {
-            let index_block = self.get_index_block(block_cache)?;
-
-            if let BlockType::Data(data_block) =  block_cache.get_or_insert(
+        let secondary_cache = self.secondary_cache.as_deref();
+        let is_contains = inner.meta.filter.contains(key);
+
+        PerfContext::record(|perf_context| {
+            perf_context.bloom_filter_checked += 1;
+            if !is_contains { perf_context.bloom_filter_useful += 1; }
+        });
+        if is_contains {
+            let index_block = match &*Self::cache_get_or_insert_async(
+                block_cache,
+                (self.get_gen(), None),
+                |_| Self::get_index_block_(inner, inner.reader.as_ref())
+            ).await? {
+                BlockType::Index(index_block) => Arc::clone(index_block),
+                BlockType::Data(_) => return Err(KernelError::DataEmpty),
+            };
+            PerfContext::record(|perf_context| perf_context.seek_count += 1);
+
+            if let BlockType::Data(data_block) = &*Self::cache_get_or_insert_async(
+                block_cache,
                 (self.get_gen(), Some(index_block.find_with_upper(key))),
                 |(_, index)| {
                     let index = (*index).ok_or_else(|| KernelError::DataEmpty)?;
-                    Ok(Self::get_data_block_(inner, reader, index)?)
+                    Self::get_data_block_(inner, secondary_cache, index)
                 }
-            )? { return Ok(data_block.find(key)); }
+            ).await? { return Ok(data_block.find(key)); }
         }
 
         Ok(None)
     }
 
-    pub(crate) fn get_data_block<'a>(&'a self, index: Index, block_cache: &'a BlockCache) -> Result<Option<&Block<Value>>> {
+    /// 从BlockCache中获取Block，未命中时进行加载，并在PerfContext中记录命中
+    fn cache_get_or_insert<F>(
+        block_cache: &BlockCache,
+        key: (i64, Option<Index>),
+        fn_load: F
+    ) -> Result<Arc<BlockType>>
+        where F: FnOnce(&(i64, Option<Index>)) -> Result<BlockType>
+    {
+        let mut is_miss = false;
+        let block_type = block_cache.get_or_insert(key, |key| {
+            is_miss = true;
+            fn_load(key)
+        })?;
+        if !is_miss {
+            PerfContext::record(|perf_context| perf_context.block_cache_hit += 1);
+        }
+
+        Ok(block_type)
+    }
+
+    /// `cache_get_or_insert`的异步版本，同一Block的并发加载仅由首个任务执行
+    async fn cache_get_or_insert_async<F>(
+        block_cache: &BlockCache,
+        key: (i64, Option<Index>),
+        fn_load: F
+    ) -> Result<Arc<BlockType>>
+        where F: FnOnce(&(i64, Option<Index>)) -> Result<BlockType>
+    {
+        let mut is_miss = false;
+        let block_type = block_cache.get_or_insert_async(key, |key| {
+            is_miss = true;
+            let result = fn_load(key);
+            async move { result }
+        }).await?;
+        if !is_miss {
+            PerfContext::record(|perf_context| perf_context.block_cache_hit += 1);
+        }
+
+        Ok(block_type)
+    }
+
+    pub(crate) fn get_data_block(&self, index: Index, block_cache: &BlockCache) -> Result<Option<Arc<Block<Value>>>> {
         let inner = &self.inner;
-        Ok(block_cache.get_or_insert(
+        let secondary_cache = self.secondary_cache.as_deref();
+        Self::cache_get_or_insert(
+            block_cache,
             (self.get_gen(), Some(index)),
             |(_, index)| {
                 let index = (*index).ok_or_else(|| KernelError::DataEmpty)?;
-                Ok(Self::get_data_block_(inner, inner.reader.as_ref(), index)?)
+                Self::get_data_block_(inner, secondary_cache, index)
             }
         ).map(|block_type| {
-            match block_type {
-                BlockType::Data(data_block) => Some(data_block),
+            match &*block_type {
+                BlockType::Data(data_block) => Some(Arc::clone(data_block)),
                 _ => None
             }
-        })?)
+        })
     }
 
-    fn get_data_block_(inner: &Arc<SSTableInner>, reader: &dyn IoReader, index: Index) -> Result<BlockType> {
-        Ok(BlockType::Data(
-            Self::loading_block(
-                reader, index.offset(), index.len(), CompressType::LZ4, inner.meta.data_restart_interval
-            )?
-        ))
+    /// 读取DataBlock
+    ///
+    /// 存在二级缓存时优先从二级缓存中读取，未命中时从SSTable中读取并写入二级缓存
+    fn get_data_block_(
+        inner: &Arc<SSTableInner>,
+        secondary_cache: Option<&SecondaryCache>,
+        index: Index
+    ) -> Result<BlockType> {
+        let (offset, len) = (index.offset(), index.len());
+        let bytes = match secondary_cache.and_then(|cache| cache.get(inner.gen, offset, len)) {
+            Some(bytes) => bytes,
+            None => {
+                let bytes = Self::read_block_bytes(inner.reader.as_ref(), offset, len)?;
+                if let Some(cache) = secondary_cache {
+                    cache.insert(inner.gen, offset, &bytes);
+                }
+                bytes
+            }
+        };
+
+        Ok(BlockType::Data(Arc::new(
+            Block::decode(bytes, inner.meta.compress_type, inner.meta.data_restart_interval)
+                .map_err(|err| err.with_location(inner.reader.get_path(), inner.gen, u64::from(offset)))?
+        )))
+    }
+
+    /// 不经过缓存读取并校验所有Block
+    ///
+    /// 用于后台校验，读取的Block不会写入各级缓存，避免挤占热数据
+    pub(crate) fn verify(&self) -> Result<()> {
+        let inner = &self.inner;
+        let _ignore = self.advise(FileAdvice::Sequential);
+
+        let result = Self::get_index_block_(inner, inner.reader.as_ref())
+            .and_then(|block_type| {
+                let BlockType::Index(index_block) = block_type else {
+                    return Err(KernelError::DataEmpty);
+                };
+                for i in 0..index_block.entry_len() {
+                    let _ = Self::get_data_block_(inner, None, *index_block.get_entry(i).item())?;
+                }
+                Ok(())
+            });
+        let _ignore1 = self.advise(FileAdvice::DontNeed);
+        let _ignore2 = self.advise(FileAdvice::Normal);
+
+        result
+    }
+
+    /// 不经过缓存重新读取新生成的SSTable，校验所有Block的CRC，
+    /// 以及Key严格升序、与Scope一致且数量为expected_len
+    ///
+    /// 用于压缩生成的SSTable写入Manifest之前，避免写入逻辑的缺陷或硬件故障所损坏的数据替代原有的数据
+    pub(crate) fn verify_output(&self, expected_len: usize) -> Result<()> {
+        let inner = &self.inner;
+        let BlockType::Index(index_block) = Self::get_index_block_(inner, inner.reader.as_ref())? else {
+            return Err(KernelError::DataEmpty);
+        };
+        let mut option_last_key: Option<Vec<u8>> = None;
+        let mut len = 0;
+
+        for i in 0..index_block.entry_len() {
+            let index = *index_block.get_entry(i).item();
+            let BlockType::Data(data_block) = Self::get_data_block_(inner, None, index)? else {
+                return Err(KernelError::DataEmpty);
+            };
+            for j in 0..data_block.entry_len() {
+                let entry = data_block.get_entry(j);
+                let key = [data_block.shared_key_prefix(j, entry.shared_len()), entry.key()].concat();
+
+                if option_last_key.as_ref().is_some_and(|last_key| last_key >= &key) {
+                    return Err(self.output_corrupted(index.offset(), "keys are not in strictly ascending order"));
+                }
+                if len == 0 && key != inner.meta.scope.start {
+                    return Err(self.output_corrupted(index.offset(), "first key does not match the scope"));
+                }
+                option_last_key = Some(key);
+                len += 1;
+            }
+        }
+        if option_last_key.is_some_and(|last_key| last_key != inner.meta.scope.end) {
+            return Err(self.output_corrupted(0, "last key does not match the scope"));
+        }
+        if len != expected_len {
+            return Err(self.output_corrupted(0, format!("expected {expected_len} keys but read {len}")));
+        }
+
+        Ok(())
+    }
+
+    fn output_corrupted(&self, offset: u32, reason: impl Into<String>) -> KernelError {
+        KernelError::Corrupted {
+            path: self.get_path(),
+            gen: self.get_gen(),
+            offset: u64::from(offset),
+            reason: reason.into(),
+        }
     }
 
-    pub(crate) fn get_index_block<'a>(&'a self, block_cache: &'a BlockCache) -> Result<&Block<Index>> {
+    /// 不经过缓存读取所有可通过校验的数据Block，用于隔离损坏的SSTable后重建
+    ///
+    /// 返回其中的数据与无法读取的数据Block的Key范围，Index Block损坏时无法定位任何数据Block，
+    /// 此时以整个SSTable作为一个无法读取的范围
+    pub(crate) fn salvage(&self) -> (Vec<KeyValue>, Vec<LostRange>) {
         let inner = &self.inner;
-        block_cache.get_or_insert(
+        let Ok(BlockType::Index(index_block)) = Self::get_index_block_(inner, inner.reader.as_ref()) else {
+            return (Vec::new(), vec![LostRange { start: None, end: self.get_scope().end.clone() }]);
+        };
+        let mut vec_data = Vec::with_capacity(self.len());
+        let mut lost_ranges = Vec::new();
+        let mut prev_separator = None;
+
+        for i in 0..index_block.entry_len() {
+            let index_entry = index_block.get_entry(i);
+            let separator = Bytes::from(
+                [index_block.shared_key_prefix(i, index_entry.shared_len()), index_entry.key()].concat()
+            );
+            let start = prev_separator.replace(separator.clone());
+            let Ok(BlockType::Data(data_block)) = Self::get_data_block_(inner, None, *index_entry.item()) else {
+                lost_ranges.push(LostRange { start, end: separator });
+                continue
+            };
+            for j in 0..data_block.entry_len() {
+                let entry = data_block.get_entry(j);
+                let key = [data_block.shared_key_prefix(j, entry.shared_len()), entry.key()].concat();
+
+                vec_data.push((Bytes::from(key), entry.item().bytes.clone()));
+            }
+        }
+
+        (vec_data, lost_ranges)
+    }
+
+    pub(crate) fn get_index_block(&self, block_cache: &BlockCache) -> Result<Arc<Block<Index>>> {
+        let inner = &self.inner;
+        Self::cache_get_or_insert(
+            block_cache,
             (self.get_gen(), None),
-            |_| Ok(Self::get_index_block_(inner, inner.reader.as_ref())?)
+            |_| Self::get_index_block_(inner, inner.reader.as_ref())
         ).map(|block_type| {
-            match block_type {
-                BlockType::Index(data_block) => Some(data_block),
+            match &*block_type {
+                BlockType::Index(index_block) => Some(Arc::clone(index_block)),
                 _ => None
             }
         })?.ok_or(KernelError::DataEmpty)
@@ -219,11 +499,11 @@ impl SSTable {
 
     fn get_index_block_(inner: &Arc<SSTableInner>, reader: &dyn IoReader) -> Result<BlockType> {
         let Footer { index_offset, index_len, .. } = inner.footer;
-        Ok(BlockType::Index(
+        Ok(BlockType::Index(Arc::new(
             Self::loading_block(
                 reader, index_offset, index_len as usize, CompressType::None, inner.meta.index_restart_interval
             )?
-        ))
+        )))
     }
 
     fn loading_block<T>(
@@ -236,8 +516,19 @@ impl SSTable {
         where T: BlockItem
     {
         Block::decode(
-            reader.read_with_pos(offset as u64, len)?, compress_type, restart_interval
-        )
+            Self::read_block_bytes(reader, offset, len)?, compress_type, restart_interval
+        ).map_err(|err| err.with_location(reader.get_path(), reader.get_gen(), u64::from(offset)))
+    }
+
+    /// 从SSTable中读取Block数据，并在PerfContext中记录读取
+    fn read_block_bytes(reader: &dyn IoReader, offset: u32, len: usize) -> Result<Vec<u8>> {
+        let bytes = reader.read_with_pos(offset as u64, len)?;
+        PerfContext::record(|perf_context| {
+            perf_context.block_read_count += 1;
+            perf_context.block_read_bytes += bytes.len() as u64;
+        });
+
+        Ok(bytes)
     }
 
     /// 通过一组SSTable收集对应的Gen
@@ -256,23 +547,47 @@ impl SSTable {
 
     /// 通过内存表构建持久化并构建SSTable
     /// 使用目标路径与文件大小，分块大小构建一个有内容的SSTable
+    ///
+    /// 数据的Sequence范围未知，需记录时使用`SSTable::create_with_sequence`
     pub(crate) fn create_for_mem_table(
         config: &Config,
         gen: i64,
         io_factory: &IoFactory,
         vec_mem_data: Vec<KeyValue>,
         level: usize
+    ) -> Result<SSTable>{
+        Self::create_with_sequence(
+            config, gen, io_factory, vec_mem_data, level, SequenceRange::UNKNOWN, &TableOptions::default()
+        )
+    }
+
+    /// 构建SSTable，并记录其数据的Sequence范围
+    ///
+    /// table_options为数据所属前缀的SSTable选项，决定DataBlock的大小与压缩方式
+    pub(crate) fn create_with_sequence(
+        config: &Config,
+        gen: i64,
+        io_factory: &IoFactory,
+        vec_mem_data: Vec<KeyValue>,
+        level: usize,
+        sequence_range: SequenceRange,
+        table_options: &TableOptions
     ) -> Result<SSTable>{
         // 获取数据的Key涵盖范围
         let scope = Scope::from_vec_data(&vec_mem_data)?;
         let len = vec_mem_data.len();
+        let tombstone_len = vec_mem_data.iter()
+            .filter(|(_, value)| value.is_none())
+            .count();
         let data_restart_interval = config.data_restart_interval;
         let index_restart_interval = config.index_restart_interval;
+        let compress_type = table_options.compress_type();
         let mut filter = GrowableBloom::new(config.desired_error_prob, len);
 
         let mut builder = BlockBuilder::new(
             BlockOptions::from(config)
-                .compress_type(CompressType::LZ4)
+                .block_size(table_options.block_size(config))
+                .compress_type(compress_type)
                 .data_restart_interval(data_restart_interval)
                 .index_restart_interval(index_restart_interval)
         );
@@ -287,6 +602,9 @@ impl SSTable {
             len,
             index_restart_interval,
             data_restart_interval,
+            sequence_range,
+            tombstone_len,
+            compress_type,
         };
 
         let (data_bytes, index_bytes) = builder.build()?;
@@ -300,6 +618,7 @@ impl SSTable {
             size_of_disk: (data_bytes.len() + index_bytes.len() + meta_bytes.len() + TABLE_FOOTER_SIZE) as u32,
         };
         let mut writer = io_factory.writer(gen, IoType::Direct)?;
+        writer.preallocate(u64::from(footer.size_of_disk))?;
         let _ = writer.io_write(
             data_bytes.into_iter()
                 .chain(index_bytes)
@@ -307,7 +626,8 @@ impl SSTable {
                 .chain(bincode::serialize(&footer)?)
                 .collect_vec()
         )?;
-        writer.io_flush()?;
+        writer.io_sync()?;
+        io_factory.sync_dir()?;
         info!("[SsTable: {}][create_form_index][MetaBlock]: {:?}", gen, meta);
         Ok(SSTable {
             inner: Arc::new(
@@ -316,8 +636,10 @@ impl SSTable {
                     reader: io_factory.reader(gen, IoType::Direct)?,
                     gen,
                     meta,
+                    format_version: FORMAT_VERSION,
                 }
-            )
+            ),
+            secondary_cache: None,
         })
 
     }
@@ -326,16 +648,16 @@ impl SSTable {
 #[cfg(test)]
 mod tests {
 
-    use std::collections::hash_map::RandomState;
     use bincode::Options;
     use bytes::Bytes;
     use tempfile::TempDir;
     use crate::kernel::io::{FileExtension, IoFactory, IoType};
+    use crate::kernel::lsm::block::BlockCache;
+    use crate::kernel::lsm::column_family::TableOptions;
     use crate::kernel::lsm::lsm_kv::Config;
-    use crate::kernel::lsm::ss_table::SSTable;
+    use crate::kernel::lsm::ss_table::{SequenceRange, SSTable};
     use crate::kernel::lsm::version::DEFAULT_SS_TABLE_PATH;
     use crate::kernel::Result;
-    use crate::kernel::utils::lru_cache::ShardingLruCache;
 
     #[test]
     fn test_sstable() -> Result<()> {
@@ -347,11 +669,7 @@ mod tests {
             config.dir_path.join(DEFAULT_SS_TABLE_PATH),
             FileExtension::SSTable
         )?;
-        let cache = ShardingLruCache::new(
-            config.block_cache_size,
-            16,
-            RandomState::default()
-        )?;
+        let cache = BlockCache::new(&config)?;
         let mut vec_data = Vec::new();
         let times = 2333;
 
@@ -368,16 +686,56 @@ mod tests {
             0
         )?;
         for i in 0..times {
-            assert_eq!(ss_table.query_with_key(&vec_data[i].0, &cache)?, Some(value.clone()))
+            assert_eq!(tokio_test::block_on(ss_table.query_with_key(&vec_data[i].0, &cache))?, Some(Some(value.clone())))
         }
         drop(ss_table);
         let ss_table = SSTable::load_from_file(
             sst_factory.reader(1, IoType::MMap)?
         )?;
         for i in 0..times {
-            assert_eq!(ss_table.query_with_key(&vec_data[i].0, &cache)?, Some(value.clone()))
+            assert_eq!(tokio_test::block_on(ss_table.query_with_key(&vec_data[i].0, &cache))?, Some(Some(value.clone())))
         }
 
         Ok(())
     }
+
+    #[test]
+    fn test_sequence_range() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        let config = Config::new(temp_dir.into_path());
+        let sst_factory = IoFactory::new(
+            config.dir_path.join(DEFAULT_SS_TABLE_PATH),
+            FileExtension::SSTable
+        )?;
+        let data = vec![(Bytes::from_static(b"k1"), Some(Bytes::from_static(b"v1")))];
+
+        let ss_table_1 = SSTable::create_for_mem_table(&config, 1, &sst_factory, data.clone(), 0)?;
+        let _ = SSTable::create_with_sequence(
+            &config, 2, &sst_factory, data, 0, SequenceRange::new(3, 7), &TableOptions::default()
+        )?;
+        assert_eq!(ss_table_1.get_sequence_range(), SequenceRange::UNKNOWN);
+        assert!(!SequenceRange::UNKNOWN.is_before(i64::MAX));
+
+        let ss_table_2 = SSTable::load_from_file(sst_factory.reader(2, IoType::Direct)?)?;
+        assert_eq!(ss_table_2.get_sequence_range(), SequenceRange::new(3, 7));
+        assert!(ss_table_2.get_sequence_range().is_before(8));
+        assert!(!ss_table_2.get_sequence_range().is_before(7));
+
+        let ss_table_3 = SSTable::create_with_sequence(
+            &config,
+            3,
+            &sst_factory,
+            vec![(Bytes::from_static(b"k2"), None)],
+            0,
+            SequenceRange::new(1, 4),
+            &TableOptions::default()
+        )?;
+        assert_eq!(
+            SequenceRange::fusion_from_vec_ss_table(&[ss_table_2, ss_table_3]),
+            SequenceRange::new(1, 7)
+        );
+
+        Ok(())
+    }
 }
\ No newline at end of file
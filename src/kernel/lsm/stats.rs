@@ -0,0 +1,511 @@
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::future::Future;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tracing::{error, info};
+use crate::kernel::io;
+use crate::kernel::lsm::lsm_kv::StoreInner;
+use crate::kernel::utils::lru_cache::CacheStats;
+use crate::kernel::Result;
+
+pub(crate) const DEFAULT_STATS_FILE: &str = "stats.json";
+
+/// 直方图桶数，第i个桶记录[2^i, 2^(i+1))微秒的耗时，最后一个桶包含所有更大的耗时
+const HISTOGRAM_BUCKETS: usize = 32;
+
+/// 以2的幂次为边界的固定桶延迟直方图
+#[derive(Debug, Default)]
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; HISTOGRAM_BUCKETS],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub(crate) fn record(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let index = ((u64::BITS - micros.leading_zeros()) as usize)
+            .saturating_sub(1)
+            .min(HISTOGRAM_BUCKETS - 1);
+
+        let _ = self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        let _ = self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        let _ = self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: self.buckets.iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum_micros: self.sum_micros.load(Ordering::Relaxed),
+            max_micros: self.max_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 延迟直方图快照，单位均为微秒
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct HistogramSnapshot {
+    /// 第i个元素为耗时位于[2^i, 2^(i+1))微秒的次数
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_micros: u64,
+    pub max_micros: u64,
+}
+
+impl HistogramSnapshot {
+    /// 获取百分位数(0~100)的近似值，返回所在桶的上界且不超过最大值
+    #[inline]
+    pub fn percentile(&self, percent: u64) -> u64 {
+        let target = (self.count * percent.min(100)).div_ceil(100).max(1);
+        let mut passed = 0;
+
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            passed += bucket;
+            if passed >= target {
+                return ((1_u64 << (index + 1)) - 1).min(self.max_micros);
+            }
+        }
+
+        self.max_micros
+    }
+
+    #[inline]
+    pub fn mean(&self) -> u64 {
+        self.sum_micros.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+impl Display for HistogramSnapshot {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "count {} mean {}us p50 {}us p99 {}us max {}us",
+            self.count,
+            self.mean(),
+            self.percentile(50),
+            self.percentile(99),
+            self.max_micros
+        )
+    }
+}
+
+/// LsmStore运行时的统计计数
+#[derive(Debug, Default)]
+pub(crate) struct Statistics {
+    /// 已提交但未被Compactor处理的压缩任务数
+    pub(crate) compaction_pending: AtomicU64,
+    /// 已完成的Minor压缩次数
+    pub(crate) minor_compaction_count: AtomicU64,
+    /// Minor压缩及其所触发的压缩的累计耗时
+    pub(crate) compaction_micros: AtomicU64,
+    /// 调用`flush`等待Compactor完成Minor压缩的累计耗时，写入本身不会因压缩而等待
+    pub(crate) flush_wait_micros: AtomicU64,
+    pub(crate) get_latency: Histogram,
+    pub(crate) set_latency: Histogram,
+    pub(crate) flush_latency: Histogram,
+    pub(crate) compaction_latency: Histogram,
+    /// WAL组同步的次数
+    pub(crate) wal_sync_count: AtomicU64,
+    /// 经WAL组同步的写入次数
+    pub(crate) wal_synced_writes: AtomicU64,
+    /// MemTable占用达到该值时才进行内存预算检测
+    pub(crate) memory_check_watermark: AtomicUsize,
+    /// 当前生效的Major压缩阈值
+    pub(crate) major_threshold: AtomicUsize,
+}
+
+impl Statistics {
+    pub(crate) fn add_micros(counter: &AtomicU64, duration: Duration) {
+        let _ = counter.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// 统计数据快照
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct StatsSnapshot {
+    /// 各Level的SSTable数量
+    pub level_sst_count: Vec<usize>,
+    /// SSTable中的数据条数
+    pub len: usize,
+    /// SSTable占用的磁盘大小
+    pub size_of_disk: u64,
+    /// 各Level中删除标记的数量
+    pub level_tombstones: Vec<usize>,
+    /// SSTable中存活数据所占磁盘大小的估算值
+    pub live_size_estimate: u64,
+    /// DataBlock缓存的统计数据
+    pub block_cache: CacheStats,
+    /// IndexBlock缓存的统计数据
+    pub index_cache: CacheStats,
+    /// SSTable缓存的统计数据
+    pub table_cache: CacheStats,
+    /// 行缓存的统计数据，未启用时均为0
+    pub row_cache: CacheStats,
+    pub flush_wait_micros: u64,
+    pub compaction_pending: u64,
+    pub minor_compaction_count: u64,
+    pub compaction_micros: u64,
+    pub get_latency: HistogramSnapshot,
+    pub set_latency: HistogramSnapshot,
+    pub flush_latency: HistogramSnapshot,
+    pub compaction_latency: HistogramSnapshot,
+    pub memory_usage: MemoryUsage,
+    /// WAL组同步的次数
+    pub wal_sync_count: u64,
+    /// 经WAL组同步的写入次数
+    pub wal_synced_writes: u64,
+    /// 近期读取次数最多的Key，未开启热点Key统计时为空
+    pub hot_keys: Vec<HotKey>,
+    /// 当前生效的Major压缩阈值，开启自适应压缩时随负载变化
+    pub major_threshold: usize,
+}
+
+/// 热点Key及其近期的估算读取次数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct HotKey {
+    pub key: Vec<u8>,
+    pub count: u64,
+}
+
+/// 内存占用，单位为Byte
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct MemoryUsage {
+    /// MemTable与Immutable MemTable中的数据大小
+    pub mem_table: usize,
+    /// MemTable的数据中由Arena预分配的大小，已包含于`mem_table`的数据中，不计入总和
+    pub mem_table_arena: usize,
+    /// Block缓存中DataBlock的大小
+    pub block_cache: usize,
+    /// Block缓存中IndexBlock的大小
+    pub index_cache: usize,
+    /// SSTable缓存中常驻内存的MetaBlock大小
+    pub table_cache: usize,
+}
+
+tokio::task_local! {
+    static PERF_CONTEXT: RefCell<PerfContext>;
+}
+
+/// 单次操作的性能上下文
+///
+/// 通过`PerfContext::scope`包裹get或scan等操作，以获取该操作期间读取路径上的各项计数，
+/// 用于排查个别查询缓慢的原因
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PerfContext {
+    /// Block缓存命中次数
+    pub block_cache_hit: u64,
+    /// 从SSTable中读取Block的次数
+    pub block_read_count: u64,
+    /// 从SSTable中读取Block的字节数
+    pub block_read_bytes: u64,
+    /// 布隆过滤器的检测次数
+    pub bloom_filter_checked: u64,
+    /// 布隆过滤器判定不存在而跳过SSTable的次数
+    pub bloom_filter_useful: u64,
+    /// 在SSTable中通过IndexBlock进行定位的次数
+    pub seek_count: u64,
+}
+
+impl PerfContext {
+    /// 在记录PerfContext的作用域中执行future，返回其结果与期间记录的PerfContext
+    ///
+    /// 作用域外的操作不会进行记录
+    #[inline]
+    pub async fn scope<F: Future>(future: F) -> (F::Output, PerfContext) {
+        PERF_CONTEXT.scope(RefCell::new(PerfContext::default()), async move {
+            let output = future.await;
+
+            (output, PERF_CONTEXT.with(|perf_context| *perf_context.borrow()))
+        }).await
+    }
+
+    /// 处于作用域中时进行记录
+    pub(crate) fn record<F: FnOnce(&mut PerfContext)>(fn_record: F) {
+        let _ignore = PERF_CONTEXT.try_with(|perf_context| fn_record(&mut perf_context.borrow_mut()));
+    }
+}
+
+/// 单个迭代器的性能上下文
+///
+/// 可供上层(如基于KipDB的SQL引擎)用于估算与解释扫描的代价
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct IterPerfContext {
+    /// 各Level中被访问的SSTable数量
+    pub tables_probed: [u64; 7],
+    /// 读取的DataBlock数量(包括命中缓存)
+    pub blocks_read: u64,
+    /// 从各Level中取出的数据数量(包括删除标记)
+    pub keys_merged: u64,
+    /// 迭代时跳过的删除标记数量
+    pub skipped_deletions: u64,
+    /// 连续删除标记超过阈值时进行重定位的次数
+    pub reseek_count: u64,
+}
+
+impl IterPerfContext {
+    /// 所有Level中被访问的SSTable数量
+    #[inline]
+    pub fn total_tables_probed(&self) -> u64 {
+        self.tables_probed.iter().sum()
+    }
+}
+
+impl MemoryUsage {
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.mem_table + self.block_cache + self.index_cache + self.table_cache
+    }
+}
+
+impl StatsSnapshot {
+    /// Block缓存命中率，以百分比表示
+    #[inline]
+    pub fn block_cache_hit_percent(&self) -> u64 {
+        self.block_cache.hit_percent()
+    }
+
+    /// 空间放大的估算值，即SSTable占用的磁盘大小与存活数据大小之比，以百分比表示，不存在存活数据时为0
+    ///
+    /// 该值或删除标记持续较高时，可考虑手动进行压缩或调整压缩方式
+    #[inline]
+    pub fn space_amplification_percent(&self) -> u64 {
+        (self.size_of_disk * 100)
+            .checked_div(self.live_size_estimate)
+            .unwrap_or(0)
+    }
+
+    /// WAL每次组同步平均合并的写入次数
+    #[inline]
+    pub fn wal_writes_per_sync(&self) -> u64 {
+        self.wal_synced_writes
+            .checked_div(self.wal_sync_count)
+            .unwrap_or(0)
+    }
+}
+
+impl Display for StatsSnapshot {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "levels: {:?}, len: {}, disk: {}B, tombstones: {:?}, space_amp: {}%, block_cache_hit: {}%({}/{}, evicted {}), table_cache_hit: {}%({}/{}, load avg {}us), flush_wait: {}us, compaction: pending {} minor {} cost {}us, \
+            get: [{}], set: [{}], flush: [{}], compaction: [{}], memory: {}B, wal_sync: {} ({} writes/sync)",
+            self.level_sst_count,
+            self.len,
+            self.size_of_disk,
+            self.level_tombstones,
+            self.space_amplification_percent(),
+            self.block_cache_hit_percent(),
+            self.block_cache.hits,
+            self.block_cache.hits + self.block_cache.misses,
+            self.block_cache.evictions,
+            self.table_cache.hit_percent(),
+            self.table_cache.hits,
+            self.table_cache.hits + self.table_cache.misses,
+            self.table_cache.load_micros_avg(),
+            self.flush_wait_micros,
+            self.compaction_pending,
+            self.minor_compaction_count,
+            self.compaction_micros,
+            self.get_latency,
+            self.set_latency,
+            self.flush_latency,
+            self.compaction_latency,
+            self.memory_usage.total(),
+            self.wal_sync_count,
+            self.wal_writes_per_sync(),
+        )
+    }
+}
+
+/// 周期性输出统计数据，Store关闭后自动退出
+pub(crate) async fn dump_periodically(store_inner: Weak<StoreInner>, period: Duration) {
+    loop {
+        time::sleep(period).await;
+
+        let Some(store_inner) = store_inner.upgrade() else {
+            return
+        };
+        if let Err(err) = dump(&store_inner).await {
+            error!("[Stats][dump][error happen]: {:?}", err);
+        }
+    }
+}
+
+async fn dump(store_inner: &Arc<StoreInner>) -> Result<()> {
+    let snapshot = store_inner.statistics().await;
+    info!("[Stats]: {}", snapshot);
+
+    let config = &store_inner.config;
+    if config.stats_dump_json {
+        // 先写入临时文件再重命名，避免读取方读到写入一半的文件
+        let path = config.path().join(DEFAULT_STATS_FILE);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&snapshot)?)?;
+        io::rename_replace(&tmp_path, &path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::{KVStore, Result};
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::lsm::stats::{DEFAULT_STATS_FILE, Histogram, PerfContext, StatsSnapshot};
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+        let snapshot = histogram.snapshot();
+
+        assert_eq!(snapshot.count, 100);
+        assert_eq!(snapshot.mean(), 50);
+        assert_eq!(snapshot.max_micros, 100);
+        assert_eq!(snapshot.percentile(50), 63);
+        assert_eq!(snapshot.percentile(99), 100);
+        assert_eq!(snapshot.percentile(1), 1);
+    }
+
+    #[test]
+    fn test_memory_budget() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .minor_threshold_with_len(usize::MAX)
+                .memory_budget(64 * 1024);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            for i in 0..1000_u32 {
+                kv_store.set(&i.to_be_bytes(), Bytes::from(vec![b'v'; 256])).await?;
+            }
+            kv_store.flush().await?;
+
+            let snapshot = kv_store.statistics().await;
+            // 除最后主动的Flush外，预算需触发过提前的Flush
+            assert!(snapshot.minor_compaction_count > 1);
+            assert!(snapshot.memory_usage.total() > 0);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_perf_context() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            // 再次Flush使k1不再存在于Immutable MemTable中
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.flush().await?;
+
+            let (value, perf_context) = PerfContext::scope(kv_store.get(b"k1")).await;
+            assert_eq!(value?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(perf_context.bloom_filter_checked, 1);
+            assert_eq!(perf_context.seek_count, 1);
+            // IndexBlock与DataBlock各访问一次
+            assert_eq!(perf_context.block_cache_hit + perf_context.block_read_count, 2);
+
+            // 再次查询时Block均已被缓存
+            let (_, perf_context) = PerfContext::scope(kv_store.get(b"k1")).await;
+            assert_eq!(perf_context.block_cache_hit, 2);
+            assert_eq!(perf_context.block_read_count, 0);
+            assert_eq!(perf_context.block_read_bytes, 0);
+
+            // 作用域外不进行记录
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_statistics() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .stats_dump_period(Duration::from_millis(50))
+                .stats_dump_json(true);
+            let kv_store = LsmStore::open_with_config(config).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.flush().await?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+
+            let snapshot = kv_store.statistics().await;
+            assert_eq!(snapshot.level_sst_count[0], 1);
+            assert_eq!(snapshot.len, 1);
+            assert_eq!(snapshot.compaction_pending, 0);
+            assert_eq!(snapshot.minor_compaction_count, 1);
+            assert_eq!(snapshot.get_latency.count, 1);
+            assert_eq!(snapshot.set_latency.count, 1);
+            assert_eq!(snapshot.flush_latency.count, 1);
+            assert_eq!(snapshot.compaction_latency.count, 1);
+
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let dump: StatsSnapshot = serde_json::from_slice(
+                &std::fs::read(temp_dir.path().join(DEFAULT_STATS_FILE))?
+            )?;
+            assert_eq!(dump.level_sst_count, snapshot.level_sst_count);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_space_usage() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open_with_config(Config::new(temp_dir.path())).await?;
+
+            kv_store.set(b"k1", Bytes::from_static(b"v1")).await?;
+            kv_store.set(b"k2", Bytes::from_static(b"v2")).await?;
+            kv_store.remove(b"k2").await?;
+            kv_store.flush().await?;
+
+            let snapshot = kv_store.statistics().await;
+            assert_eq!(snapshot.level_tombstones[0], 1);
+            assert_eq!(snapshot.level_tombstones[1..].iter().sum::<usize>(), 0);
+            // 一半的数据为删除标记
+            assert_eq!(snapshot.live_size_estimate, snapshot.size_of_disk / 2);
+            assert!((200..=202).contains(&snapshot.space_amplification_percent()));
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,230 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use crate::kernel::lsm::lsm_kv::LsmStore;
+use crate::kernel::Result;
+use crate::kernel::utils::keys::KeyEncoder;
+use crate::KernelError;
+
+/// 数据结构Key的前缀，属于内部Key，因此不会与普通的Key冲突，也不会出现在分页扫描的结果中
+const STRUCT_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-Struct\x00";
+
+const COUNTER_TAG: u8 = b'c';
+
+const SET_TAG: u8 = b's';
+
+const LIST_LEN_TAG: u8 = b'l';
+
+const LIST_ITEM_TAG: u8 = b'i';
+
+/// 基于KV的数据结构视图，提供计数器、集合与列表，通过`LsmStore::structures`获取
+///
+/// 各数据结构以名称区分，不同类型的数据结构可以使用相同的名称
+/// - 计数器: 以i64的大端序存储于单个Key中
+/// - 集合: 每个成员各占用一个Key，因此添加与删除成员时无需读写整个集合
+/// - 列表: 长度与各元素各占用一个Key，追加时仅写入新元素与长度
+///
+/// 同一数据结构的读-改-写在其Key的分片锁内完成，因此并发的写入不会相互覆盖，
+/// 但单次写入多个成员或元素时不具备原子性，期间的读取可能看到部分写入
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy)]
+pub struct Structures<'a> {
+    kv_store: &'a LsmStore,
+}
+
+impl<'a> Structures<'a> {
+    pub(crate) fn new(kv_store: &'a LsmStore) -> Self {
+        Structures { kv_store }
+    }
+
+    /// 对计数器进行原子性的自增，并返回自增后的数值，计数器不存在时视为0
+    #[inline]
+    pub async fn counter_incr(&self, name: &[u8], delta: i64) -> Result<i64> {
+        self.kv_store.increment(&struct_key(COUNTER_TAG, name), delta).await
+    }
+
+    /// 获取计数器的数值，计数器不存在时返回0
+    #[inline]
+    pub async fn counter_get(&self, name: &[u8]) -> Result<i64> {
+        self.kv_store.get_local(&struct_key(COUNTER_TAG, name)).await?
+            .map_or(Ok(0), |bytes| decode_i64(&bytes))
+    }
+
+    /// 向集合中添加成员，返回此前不存在的成员数量
+    #[inline]
+    pub async fn set_add(&self, name: &[u8], members: &[&[u8]]) -> Result<usize> {
+        let prefix = struct_key(SET_TAG, name);
+        let _guard = self.kv_store.latches.lock(&prefix).await;
+        let mut added = 0;
+
+        for member in members {
+            let member_key = member_key(&prefix, member);
+
+            if self.kv_store.get_local(&member_key).await?.is_none() {
+                let _ = self.kv_store.append_cmd_data((member_key, Some(Bytes::new())), None).await?;
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// 从集合中删除成员，返回实际被删除的成员数量
+    #[inline]
+    pub async fn set_remove(&self, name: &[u8], members: &[&[u8]]) -> Result<usize> {
+        let prefix = struct_key(SET_TAG, name);
+        let _guard = self.kv_store.latches.lock(&prefix).await;
+        let mut removed = 0;
+
+        for member in members {
+            let member_key = member_key(&prefix, member);
+
+            if self.kv_store.get_local(&member_key).await?.is_some() {
+                let _ = self.kv_store.append_cmd_data((member_key, None), None).await?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// 成员是否存在于集合中
+    #[inline]
+    pub async fn set_contains(&self, name: &[u8], member: &[u8]) -> Result<bool> {
+        let member_key = member_key(&struct_key(SET_TAG, name), member);
+
+        Ok(self.kv_store.get_local(&member_key).await?.is_some())
+    }
+
+    /// 以字节序升序返回集合中的所有成员
+    #[inline]
+    pub async fn set_members(&self, name: &[u8]) -> Result<Vec<Bytes>> {
+        let prefix = struct_key(SET_TAG, name);
+
+        Ok(self.kv_store.scan_prefix(&prefix, None, usize::MAX).await?
+            .into_iter()
+            .map(|(key, _)| key.slice(prefix.len()..))
+            .collect())
+    }
+
+    /// 向列表末尾追加元素，并返回追加后的列表长度
+    #[inline]
+    pub async fn list_append(&self, name: &[u8], items: Vec<Bytes>) -> Result<u64> {
+        let len_key = struct_key(LIST_LEN_TAG, name);
+        let _guard = self.kv_store.latches.lock(&len_key).await;
+        let item_prefix = struct_key(LIST_ITEM_TAG, name);
+        let mut len = self.list_len(name).await?;
+
+        // 先写入元素再写入长度，中断时已写入的元素超出长度，因此不可见且会在下次追加时被覆盖
+        for item in items {
+            let _ = self.kv_store.append_cmd_data((item_key(&item_prefix, len), Some(item)), None).await?;
+            len += 1;
+        }
+        let _ = self.kv_store.append_cmd_data(
+            (len_key, Some(Bytes::copy_from_slice(&len.to_be_bytes()))),
+            None
+        ).await?;
+
+        Ok(len)
+    }
+
+    /// 获取列表长度，列表不存在时返回0
+    #[inline]
+    pub async fn list_len(&self, name: &[u8]) -> Result<u64> {
+        self.kv_store.get_local(&struct_key(LIST_LEN_TAG, name)).await?
+            .map_or(Ok(0), |bytes| decode_i64(&bytes).map(|len| len as u64))
+    }
+
+    /// 获取列表中下标位于[start, stop]的元素
+    ///
+    /// 与Redis的LRANGE一致，负数的下标表示由末尾倒数，超出范围的下标被截断至列表的边界
+    #[inline]
+    pub async fn list_range(&self, name: &[u8], start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let len = self.list_len(name).await? as i64;
+        let to_index = |index: i64| if index < 0 { len + index } else { index };
+        let (start, stop) = (to_index(start).max(0), to_index(stop).min(len - 1));
+
+        if start > stop {
+            return Ok(vec![]);
+        }
+        let item_prefix = struct_key(LIST_ITEM_TAG, name);
+        let after = (start > 0).then(|| item_key(&item_prefix, start as u64 - 1));
+
+        Ok(self.kv_store.scan_prefix(&item_prefix, after.as_deref(), (stop - start + 1) as usize).await?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect())
+    }
+}
+
+/// 前缀 + 类型标识 + 保序编码的名称
+fn struct_key(tag: u8, name: &[u8]) -> Bytes {
+    let encoded_name = KeyEncoder::new().bytes(name).finish();
+    let mut key = BytesMut::with_capacity(STRUCT_KEY_PREFIX.len() + 1 + encoded_name.len());
+    key.put_slice(STRUCT_KEY_PREFIX);
+    key.put_u8(tag);
+    key.put_slice(&encoded_name);
+
+    key.freeze()
+}
+
+fn member_key(prefix: &[u8], member: &[u8]) -> Bytes {
+    let mut key = BytesMut::with_capacity(prefix.len() + member.len());
+    key.put_slice(prefix);
+    key.put_slice(member);
+
+    key.freeze()
+}
+
+/// 下标以大端序编码，使元素按下标升序排列
+fn item_key(prefix: &[u8], index: u64) -> Bytes {
+    member_key(prefix, &index.to_be_bytes())
+}
+
+fn decode_i64(bytes: &[u8]) -> Result<i64> {
+    <[u8; 8]>::try_from(bytes)
+        .map(i64::from_be_bytes)
+        .map_err(|_| KernelError::ValueNotNumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::lsm_kv::LsmStore;
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_structures() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let kv_store = LsmStore::open(temp_dir.path()).await?;
+            let structures = kv_store.structures();
+
+            assert_eq!(structures.counter_incr(b"hits", 2).await?, 2);
+            assert_eq!(structures.counter_incr(b"hits", -3).await?, -1);
+            assert_eq!(structures.counter_get(b"misses").await?, 0);
+
+            assert_eq!(structures.set_add(b"tags", &[b"b", b"a", b"", b"b"]).await?, 3);
+            assert_eq!(structures.set_add(b"tags2", &[b"c"]).await?, 1);
+            assert_eq!(structures.set_remove(b"tags", &[b"b", b"d"]).await?, 1);
+            assert!(structures.set_contains(b"tags", b"a").await?);
+            assert_eq!(structures.set_members(b"tags").await?, vec![Bytes::new(), Bytes::from_static(b"a")]);
+
+            let items = (0..5u8).map(|i| Bytes::from(vec![i])).collect::<Vec<_>>();
+            assert_eq!(structures.list_append(b"log", items[..3].to_vec()).await?, 3);
+            assert_eq!(structures.list_append(b"log", items[3..].to_vec()).await?, 5);
+            assert_eq!(structures.list_range(b"log", 0, -1).await?, items);
+            assert_eq!(structures.list_range(b"log", 1, 2).await?, items[1..3].to_vec());
+            assert_eq!(structures.list_range(b"log", -2, 100).await?, items[3..].to_vec());
+            assert!(structures.list_range(b"log", 3, 1).await?.is_empty());
+
+            // 数据结构的Key不会出现在普通的扫描结果中
+            kv_store.set(b"plain", Bytes::from_static(b"v")).await?;
+            let (items, _) = kv_store.scan_page(None, 100).await?;
+            assert_eq!(items.len(), 1);
+
+            Ok(())
+        })
+    }
+}
@@ -1,5 +1,5 @@
-use std::collections::hash_map::RandomState;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::mem;
 use std::sync::Arc;
 use bytes::Bytes;
 use tokio::sync::mpsc::error::TrySendError;
@@ -7,26 +7,131 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::{Receiver, Sender, channel};
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use crate::kernel::Result;
 use crate::kernel::io::{FileExtension, IoFactory, IoType};
 use crate::kernel::lsm::SSTableLoader;
 use crate::kernel::lsm::block::BlockCache;
+use crate::kernel::lsm::column_family;
+use crate::kernel::lsm::column_family::TableOptions;
 use crate::kernel::lsm::compactor::LEVEL_0;
 use crate::kernel::lsm::log::LogLoader;
-use crate::kernel::lsm::lsm_kv::Config;
+use crate::kernel::lsm::lsm_kv::{Config, Gen};
+use crate::kernel::lsm::mem_table::KeyValue;
+use crate::kernel::lsm::negative_cache::NegativeCache;
 use crate::kernel::lsm::ss_table::{Scope, SSTable};
-use crate::kernel::utils::lru_cache::ShardingLruCache;
+use crate::kernel::utils::invariant;
+use crate::kernel::utils::lru_cache::CacheStats;
+use crate::KernelError;
 use crate::KernelError::SSTableLost;
 
 pub(crate) const DEFAULT_SS_TABLE_PATH: &str = "ss_table";
 
 pub(crate) const DEFAULT_VERSION_PATH: &str = "version";
 
+pub(crate) const DEFAULT_QUARANTINE_PATH: &str = "quarantine";
+
 pub(crate) type LevelSlice = [Vec<i64>; 7];
 
 pub(crate) type FileVec = (Vec<i64>, usize);
 
+/// 压缩时丢弃数据的规则，丢弃属于被删除前缀的数据与超出其前缀TTL的数据
+#[derive(Debug, Default)]
+pub(crate) struct DiscardFilter<'a> {
+    dropped_prefixes: &'a [Bytes],
+    prefix_ttls: &'a [(Bytes, i64)],
+    now: i64,
+    /// 因过期被丢弃的Key，为None时不收集
+    expired_keys: Option<parking_lot::Mutex<Vec<Bytes>>>,
+}
+
+impl DiscardFilter<'_> {
+    /// 收集因过期被丢弃的Key，用于发布Key空间的过期通知
+    pub(crate) fn collect_expired(mut self, is_collect: bool) -> Self {
+        self.expired_keys = is_collect.then(Default::default);
+        self
+    }
+
+    /// 删除标记不受TTL影响，以免遮蔽的旧数据重新可见
+    pub(crate) fn is_discarded(&self, (key, value): &KeyValue) -> bool {
+        if self.dropped_prefixes.iter().any(|prefix| key.starts_with(prefix)) {
+            return true;
+        }
+        let is_expired = value.as_ref().is_some_and(|value| {
+            self.prefix_ttls.iter()
+                .any(|(prefix, _)| key.starts_with(prefix) && column_family::is_expired(value, self.now))
+        });
+        if is_expired {
+            if let Some(expired_keys) = &self.expired_keys {
+                expired_keys.lock().push(key.clone());
+            }
+        }
+
+        is_expired
+    }
+
+    pub(crate) fn take_expired_keys(&self) -> Vec<Bytes> {
+        self.expired_keys.as_ref()
+            .map(|expired_keys| mem::take(&mut *expired_keys.lock()))
+            .unwrap_or_default()
+    }
+}
+
+/// Level中SSTable的区间索引
+///
+/// Level 1-6中SSTable之间的数据范围互不重叠，以Scope的start排序后end同样有序，
+/// 因此可以通过二分查找定位Key或Scope所在的SSTable，而不需要遍历该Level的所有SSTable
+#[derive(Clone, Default)]
+pub(crate) struct ScopeIndex {
+    vec_scope: Vec<(Scope, i64)>,
+}
+
+impl ScopeIndex {
+    fn new(level_gens: &[i64], ss_table_loader: &SSTableLoader) -> Self {
+        let vec_scope = level_gens.iter()
+            .filter_map(|gen| {
+                ss_table_loader.get(*gen)
+                    .map(|ss_table| (ss_table.get_scope().clone(), *gen))
+            })
+            .sorted_unstable_by(|(scope_a, _), (scope_b, _)| scope_a.start.cmp(&scope_b.start))
+            .collect_vec();
+
+        ScopeIndex { vec_scope }
+    }
+
+    /// 检测是否存在范围重叠的SSTable
+    fn is_overlapped(&self) -> bool {
+        self.vec_scope.windows(2)
+            .any(|window| window[0].0.end >= window[1].0.start)
+    }
+
+    /// 获取范围包含key的SSTable的Gen
+    fn find_with_key(&self, key: &[u8]) -> Option<i64> {
+        let index = self.vec_scope
+            .partition_point(|(scope, _)| scope.start.as_ref() <= key);
+
+        index.checked_sub(1)
+            .and_then(|index| {
+                let (scope, gen) = &self.vec_scope[index];
+                scope.meet_with_key(key).then_some(*gen)
+            })
+    }
+
+    /// 获取与scope相交的SSTable的Gen，以start由小到大排序
+    fn find_with_scope<'a>(&'a self, scope: &'a Scope) -> impl Iterator<Item = i64> + 'a {
+        let start = self.vec_scope
+            .partition_point(|(ss_table_scope, _)| ss_table_scope.end < scope.start);
+
+        self.vec_scope[start..].iter()
+            .take_while(|(ss_table_scope, _)| ss_table_scope.start <= scope.end)
+            .map(|(_, gen)| *gen)
+    }
+}
+
+/// Manifest中所记录的Version变更
+///
+/// 仅记录SSTable的Gen而不记录任何路径，文件路径均在启动时根据`Config::path`拼接
+/// 因此数据目录可以被整体复制或挂载至其他位置后再次打开
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) enum VersionEdit {
     DeleteFile(FileVec),
@@ -35,6 +140,24 @@ pub(crate) enum VersionEdit {
     NewFile(FileVec, usize),
     // // Level and SSTable Gen List
     // CompactPoint(usize, Vec<i64>),
+    // 持久化时已分配的最后Sequence，用于重启时恢复Sequence
+    LastSequence(i64),
+    // SSTable所在的存储路径序号，对应`Config::sst_paths`
+    // 仅记录序号而非实际路径，使存储路径可以被迁移
+    FilePath(Vec<i64>, usize),
+    // 时间窗口压缩时SSTable所属的窗口起始时间(毫秒)
+    Window(Vec<i64>, i64),
+    // SSTable中所有数据的最大过期时间(毫秒)，到期后无需重写数据即可删除整个SSTable
+    Expiry(Vec<i64>, i64),
+    // 被删除的Key前缀(如被删除的列族)，其数据在压缩时被丢弃
+    DropPrefix(Vec<u8>),
+    // 一次log_and_apply的结束标记，作为Manifest回滚的单位
+    Step,
+    // 以下变更追加于Step之后，保持此前的变更在Manifest中的序号不变
+    // Key前缀中数据的存活时长(毫秒)，如设置了TTL的列族，其过期的数据在压缩时被丢弃
+    PrefixTtl(Vec<u8>, i64),
+    // Key前缀中数据的SSTable选项，如指定了压缩方式的列族，其数据被写入单独的SSTable
+    PrefixOptions(Vec<u8>, TableOptions),
 }
 
 #[derive(Debug)]
@@ -51,18 +174,25 @@ struct Cleaner {
     ss_table_loader: Arc<RwLock<SSTableLoader>>,
     tag_rx: Receiver<CleanTag>,
     del_gens: Vec<(u64, Vec<i64>)>,
-
+    /// 暂不删除的SSTable的组数，对应`Config::manifest_history`
+    history: usize,
+    /// 已不被任何Version使用，但为了Manifest回滚而暂不删除的SSTable，以被删除的先后排列
+    retained: VecDeque<Vec<i64>>,
 }
 
 impl Cleaner {
     fn new(
         ss_table_loader: &Arc<RwLock<SSTableLoader>>,
-        tag_rx: Receiver<CleanTag>
+        tag_rx: Receiver<CleanTag>,
+        history: usize,
+        retained: VecDeque<Vec<i64>>,
     ) -> Self {
         Self {
             ss_table_loader: Arc::clone(ss_table_loader),
             tag_rx,
             del_gens: vec![],
+            history,
+            retained,
         }
     }
 
@@ -104,11 +234,17 @@ impl Cleaner {
             if index == 0 {
                 let mut ss_table_loader = self.ss_table_loader.write().await;
                 // 当此Version处于第一位时，直接将其删除
-                for gen in vec_gen {
-                    let _ignore = ss_table_loader.remove(&gen);
-                    if let Err(err) = ss_table_loader.clean(gen) {
-                        error!("[Cleaner][clean][SSTables{}]: Remove Error!: {:?}", gen, err);
-                    };
+                // 开启Manifest历史时仅删除超出保留组数的最旧的SSTable
+                for gen in &vec_gen {
+                    let _ignore = ss_table_loader.remove(gen);
+                }
+                self.retained.push_back(vec_gen);
+                while self.retained.len() > self.history {
+                    for gen in self.retained.pop_front().into_iter().flatten() {
+                        if let Err(err) = ss_table_loader.clean(gen) {
+                            error!("[Cleaner][clean][SSTables{}]: Remove Error!: {:?}", gen, err);
+                        };
+                    }
                 }
             } else {
                 // 若非Version并非第一位，为了不影响前面Version对SSTable的读取处理，将待删除的SSTable的gen转移至前一位
@@ -137,9 +273,12 @@ struct VersionInner {
 pub(crate) struct VersionStatus {
     inner: RwLock<VersionInner>,
     ss_table_loader: Arc<RwLock<SSTableLoader>>,
-    sst_factory: Arc<IoFactory>,
+    /// 以路径序号排列的各SSTable存储路径的IoFactory
+    sst_factories: Vec<Arc<IoFactory>>,
     /// TODO: 日志快照
     ver_log: LogLoader,
+    /// 各Version共享的Block缓存
+    block_cache: Arc<BlockCache>,
     /// 用于Drop时通知Cleaner drop
     _cleaner_tx: Sender<CleanTag>,
 }
@@ -162,40 +301,81 @@ pub(crate) struct Version {
     /// 以索引0为level-0这样的递推，存储文件的gen值
     /// 每个Version各持有各自的Gen矩阵
     level_slice: LevelSlice,
+    /// Level 1-6的SSTable区间索引，Level 0的SSTable之间范围可能重叠因此不使用
+    scope_index: [ScopeIndex; 7],
     /// 统计数据
     meta_data: VersionMeta,
+    /// 持久化的最后Sequence
+    last_sequence: i64,
+    /// 被删除的Key前缀
+    dropped_prefixes: Vec<Bytes>,
+    /// 设置了TTL的Key前缀及其TTL(毫秒)
+    prefix_ttls: Vec<(Bytes, i64)>,
+    /// 指定了SSTable选项的Key前缀及其选项
+    prefix_options: Vec<(Bytes, TableOptions)>,
     /// 稀疏区间数据Block缓存
     pub(crate) block_cache: Arc<BlockCache>,
+    /// 不存在Key的缓存，仅对该Version的SSTable有效
+    negative_cache: Option<Arc<NegativeCache>>,
     /// 清除信号发送器
     /// Drop时通知Cleaner进行删除
     clean_sender: Sender<CleanTag>
 }
 
 impl VersionStatus {
-    pub(crate) fn get_sst_factory_ref(&self) -> &IoFactory {
-        &self.sst_factory
+    /// 获取指定存储路径序号的IoFactory
+    pub(crate) fn get_sst_factory_ref(&self, path_id: usize) -> &IoFactory {
+        &self.sst_factories[path_id]
+    }
+
+    pub(crate) fn block_cache(&self) -> &Arc<BlockCache> {
+        &self.block_cache
+    }
+
+    pub(crate) async fn table_cache_usage(&self) -> usize {
+        self.ss_table_loader.read().await
+            .memory_usage()
+    }
+
+    pub(crate) async fn table_cache_stats(&self) -> CacheStats {
+        self.ss_table_loader.read().await
+            .cache_stats()
+    }
+
+    pub(crate) async fn set_table_cache_size(&self, cache_size: usize) -> Result<()> {
+        self.ss_table_loader.read().await
+            .set_cache_size(cache_size)
     }
 
     pub(crate) async fn load_with_path(
         config: Config,
         wal: Arc<LogLoader>,
     ) -> Result<Self> {
-        let sst_path = config.path().join(DEFAULT_SS_TABLE_PATH);
-
-        let block_cache = Arc::new(ShardingLruCache::new(
-            config.block_cache_size,
-            16,
-            RandomState::default()
-        )?);
-        let sst_factory = Arc::new(
-            IoFactory::new(
-                sst_path.clone(),
-                FileExtension::SSTable
-            )?
-        );
+        Self::load_with_steps(config, wal, 0).await
+    }
+
+    /// 以回滚rollback_steps次变更后的Version载入
+    ///
+    /// 回滚的Version所引用的SSTable可能已被删除，其保留取决于`Config::manifest_history`
+    pub(crate) async fn load_with_steps(
+        config: Config,
+        wal: Arc<LogLoader>,
+        rollback_steps: usize,
+    ) -> Result<Self> {
+        let block_cache = Arc::new(BlockCache::new(&config)?);
+        let negative_cache = config.negative_cache_ttl
+            .map(|ttl| NegativeCache::new(config.negative_cache_size, ttl).map(Arc::new))
+            .transpose()?;
+        let sst_factories = config.sst_paths()
+            .into_iter()
+            .map(|sst_path| {
+                IoFactory::new(sst_path, FileExtension::SSTable)
+                    .map(|factory| Arc::new(factory.fault_injector(config.fault_injector.clone())))
+            })
+            .try_collect::<_, Vec<_>, _>()?;
 
         let ss_table_loader = Arc::new(RwLock::new(
-            SSTableLoader::new(config.clone(), Arc::clone(&sst_factory), wal)?
+            SSTableLoader::new(config.clone(), sst_factories.clone(), wal)?
         ));
 
         let (ver_log, vec_reload_edit) = LogLoader::reload(
@@ -205,9 +385,25 @@ impl VersionStatus {
             IoType::Direct
         )?;
 
-        let vec_log = vec_reload_edit
-            .into_iter()
-            .filter_map(|key_value| bincode::deserialize(&key_value.0).ok())
+        let vec_step = Self::rollback_steps(vec_reload_edit, rollback_steps)?;
+        // 重启后以最近被删除的SSTable继续保留，避免其成为不会被删除的残留文件
+        let mut retained = VecDeque::new();
+        let vec_step_del_gen = vec_step.iter()
+            .rev()
+            .map(|vec_edit| vec_edit.iter()
+                .filter_map(|edit| match edit {
+                    VersionEdit::DeleteFile((vec_gen, _)) => Some(vec_gen.iter().copied()),
+                    _ => None,
+                })
+                .flatten()
+                .collect_vec())
+            .filter(|vec_gen| !vec_gen.is_empty())
+            .take(config.manifest_history);
+        for vec_gen in vec_step_del_gen {
+            retained.push_front(vec_gen);
+        }
+        let vec_log = vec_step.into_iter()
+            .flatten()
             .collect_vec();
 
         // TODO: 对channel进行配置
@@ -217,28 +413,82 @@ impl VersionStatus {
                 vec_log,
                 &ss_table_loader,
                 &block_cache,
+                negative_cache,
                 tag_sender.clone()
             ).await?
         );
 
         let mut cleaner = Cleaner::new(
             &ss_table_loader,
-            tag_rev
+            tag_rev,
+            config.manifest_history,
+            retained
         );
 
-        let _ignore = tokio::spawn(async move {
+        config.spawner.spawn(async move {
             cleaner.listen().await;
         });
 
         Ok(Self {
             inner: RwLock::new(VersionInner { inner: version }),
             ss_table_loader,
-            sst_factory,
+            sst_factories,
             ver_log,
+            block_cache,
             _cleaner_tx: tag_sender,
         })
     }
 
+    /// 将Manifest回滚rollback_steps次变更，返回剩余的变更次数
+    ///
+    /// 回滚后的Manifest写入新的日志文件，原有的日志文件仍保留于目录中
+    pub(crate) fn rollback(config: &Config, rollback_steps: usize) -> Result<usize> {
+        let (ver_log, vec_reload_edit) = LogLoader::reload(
+            config.clone(),
+            DEFAULT_VERSION_PATH,
+            FileExtension::Manifest,
+            IoType::Direct
+        )?;
+        let vec_step = Self::rollback_steps(vec_reload_edit, rollback_steps)?;
+        let steps = vec_step.len();
+        let vec_data = vec_step.into_iter()
+            .flat_map(|vec_edit| vec_edit.into_iter().chain([VersionEdit::Step]))
+            .map(|edit| bincode::serialize(&edit).map(|key| (Bytes::from(key), None)))
+            .try_collect()?;
+
+        let _ = ver_log.switch(Gen::create())?;
+        let _ = ver_log.log_batch(vec_data)?;
+        let _ = ver_log.sync()?;
+
+        Ok(steps)
+    }
+
+    /// 以Step标记将Manifest中的VersionEdit划分为各次变更，并去除最后的rollback_steps次变更
+    ///
+    /// 未记录Step标记的旧Manifest中的VersionEdit视为一次变更
+    fn rollback_steps(vec_reload_edit: Vec<KeyValue>, rollback_steps: usize) -> Result<Vec<Vec<VersionEdit>>> {
+        let mut vec_step = vec![vec![]];
+        for edit in vec_reload_edit.into_iter()
+            .filter_map(|key_value| bincode::deserialize(&key_value.0).ok())
+        {
+            match edit {
+                VersionEdit::Step => vec_step.push(vec![]),
+                edit => if let Some(vec_edit) = vec_step.last_mut() {
+                    vec_edit.push(edit);
+                }
+            }
+        }
+        vec_step.retain(|vec_edit| !vec_edit.is_empty());
+
+        let steps = vec_step.len();
+        if rollback_steps > steps {
+            return Err(KernelError::ManifestHistoryExceeded(steps));
+        }
+        vec_step.truncate(steps - rollback_steps);
+
+        Ok(vec_step)
+    }
+
     fn ss_table_insert(
         ss_table_loader: &mut SSTableLoader,
         ss_table: SSTable,
@@ -253,6 +503,18 @@ impl VersionStatus {
         )
     }
 
+    /// 获取SSTable所属的时间窗口起始时间，未记录时返回None
+    pub(crate) async fn get_window(&self, gen: i64) -> Option<i64> {
+        self.ss_table_loader.read().await
+            .get_window(gen)
+    }
+
+    /// 获取SSTable中所有数据的最大过期时间，未记录时返回None
+    pub(crate) async fn get_expiry(&self, gen: i64) -> Option<i64> {
+        self.ss_table_loader.read().await
+            .get_expiry(gen)
+    }
+
     pub(crate) async fn insert_vec_ss_table(&self, vec_ss_table: Vec<SSTable>) -> Result<()> {
         let mut ss_table_loader = self.ss_table_loader.write().await;
         for ss_table in vec_ss_table {
@@ -262,6 +524,18 @@ impl VersionStatus {
         Ok(())
     }
 
+    /// 移除并删除未能生效的SSTable
+    async fn discard_ss_tables(&self, vec_gen: &[i64]) {
+        let mut ss_table_loader = self.ss_table_loader.write().await;
+
+        for gen in vec_gen {
+            let _ignore = ss_table_loader.remove(gen);
+            if let Err(err) = ss_table_loader.clean(*gen) {
+                error!("[VersionStatus][discard_ss_tables][SSTables{}]: Remove Error!: {:?}", gen, err);
+            }
+        }
+    }
+
     /// 对一组VersionEdit持久化并应用
     pub(crate) async fn log_and_apply(
         &self,
@@ -273,18 +547,36 @@ impl VersionStatus {
         );
 
         let vec_data = vec_version_edit.iter()
+            .chain([&VersionEdit::Step])
             .filter_map(|edit| {
                 bincode::serialize(&edit).ok()
                     .map(|key| (Bytes::from(key), None))
             })
             .collect_vec();
 
-        new_version.apply(vec_version_edit, false).await?;
+        let vec_new_gen = vec_version_edit.iter()
+            .filter_map(|edit| match edit {
+                VersionEdit::NewFile((vec_gen, _), _) => Some(vec_gen.iter()),
+                _ => None,
+            })
+            .flatten()
+            .filter(|gen| !new_version.level_slice.iter().flatten().contains(gen))
+            .copied()
+            .collect_vec();
+
+        if let Err(err) = new_version.apply(vec_version_edit, false).await {
+            // VersionEdit未被持久化，其新增的SSTable不会被任何Version引用，需在此处清理
+            self.discard_ss_tables(&vec_new_gen).await;
+            return Err(err);
+        }
 
         version_display(&new_version, "log_and_apply");
 
-        self.ver_log
+        let _ = self.ver_log
             .log_batch(vec_data)?;
+        // Manifest需先于新Version生效落盘，避免崩溃后引用已被清理的SSTable
+        let _ = self.ver_log
+            .sync()?;
         self.inner
             .write().await
             .inner = Arc::new(new_version);
@@ -306,18 +598,122 @@ impl Version {
         self.meta_data.size_of_disk
     }
 
+    pub(crate) fn get_last_sequence(&self) -> i64 {
+        self.last_sequence
+    }
+
+    /// 获取各Level的SSTable数量
+    pub(crate) fn level_sst_count(&self) -> Vec<usize> {
+        self.level_slice.iter()
+            .map(Vec::len)
+            .collect_vec()
+    }
+
+    /// 获取各Level中删除标记的数量，以及存活数据所占磁盘大小的估算值
+    ///
+    /// 以最底层的非空Level作为全量数据，并按其中非删除标记数据的占比估算存活数据的大小，
+    /// 更高Level中的数据视为对其的覆盖，因此仅存在于更高Level中的新Key会使空间放大被高估
+    pub(crate) async fn get_space_usage(&self) -> (Vec<usize>, u64) {
+        let all_ss_tables = self.get_all_ss_tables().await;
+        let level_tombstones = all_ss_tables.iter()
+            .map(|vec_ss_table| vec_ss_table.iter().map(SSTable::tombstone_len).sum())
+            .collect_vec();
+        let live_size = all_ss_tables.iter()
+            .rev()
+            .find(|vec_ss_table| !vec_ss_table.is_empty())
+            .map_or(0, |vec_ss_table| {
+                vec_ss_table.iter()
+                    .filter(|ss_table| ss_table.len() > 0)
+                    .map(|ss_table| {
+                        let live_len = ss_table.len() - ss_table.tombstone_len();
+                        ss_table.get_size_of_disk() * live_len as u64 / ss_table.len() as u64
+                    })
+                    .sum()
+            });
+
+        (level_tombstones, live_size)
+    }
+
+    /// 获取各Level中所有数据均已过期的SSTable
+    pub(crate) async fn get_expired_files(&self, now: i64) -> Vec<FileVec> {
+        let ss_tables_map = self.ss_tables_map.read().await;
+
+        self.level_slice.iter()
+            .enumerate()
+            .map(|(level, vec_gen)| {
+                let vec_expired_gen = vec_gen.iter()
+                    .filter(|gen| ss_tables_map.get_expiry(**gen).is_some_and(|max_expiry| max_expiry <= now))
+                    .copied()
+                    .collect_vec();
+                (vec_expired_gen, level)
+            })
+            .filter(|(vec_gen, _)| !vec_gen.is_empty())
+            .collect_vec()
+    }
+
+    pub(crate) fn dropped_prefixes(&self) -> &[Bytes] {
+        &self.dropped_prefixes
+    }
+
+    pub(crate) fn prefix_options(&self) -> &[(Bytes, TableOptions)] {
+        &self.prefix_options
+    }
+
+    pub(crate) fn table_options(&self, key: &[u8]) -> TableOptions {
+        TableOptions::with_key(&self.prefix_options, key)
+    }
+
+    /// 以now(毫秒)作为当前时间，获取压缩时丢弃数据的规则
+    pub(crate) fn discard_filter(&self, now: i64) -> DiscardFilter<'_> {
+        DiscardFilter {
+            dropped_prefixes: self.dropped_prefixes(),
+            prefix_ttls: &self.prefix_ttls,
+            now,
+            expired_keys: None,
+        }
+    }
+
+    /// 获取各Level中所有数据均属于同一被删除前缀的SSTable
+    pub(crate) async fn get_dropped_prefix_files(&self) -> Vec<FileVec> {
+        if self.dropped_prefixes.is_empty() {
+            return vec![];
+        }
+        let ss_tables_map = self.ss_tables_map.read().await;
+        let is_dropped = |scope: &Scope| self.dropped_prefixes.iter()
+            .any(|prefix| scope.start.starts_with(prefix) && scope.end.starts_with(prefix));
+
+        self.level_slice.iter()
+            .enumerate()
+            .map(|(level, vec_gen)| {
+                let vec_dropped_gen = vec_gen.iter()
+                    .filter(|gen| ss_tables_map.get(**gen).is_some_and(|ss_table| is_dropped(ss_table.get_scope())))
+                    .copied()
+                    .collect_vec();
+                (vec_dropped_gen, level)
+            })
+            .filter(|(vec_gen, _)| !vec_gen.is_empty())
+            .collect_vec()
+    }
+
     /// 创建一个空的Version
     fn new(
         ss_table_loader: &Arc<RwLock<SSTableLoader>>,
         block_cache: &Arc<BlockCache>,
+        negative_cache: Option<Arc<NegativeCache>>,
         clean_sender: Sender<CleanTag>,
     ) -> Self {
         Self {
             version_num: 0,
             ss_tables_map: Arc::clone(ss_table_loader),
             level_slice: Self::level_slice_new(),
+            scope_index: Default::default(),
             block_cache: Arc::clone(block_cache),
+            negative_cache,
             meta_data: VersionMeta { size_of_disk: 0, len: 0 },
+            last_sequence: 0,
+            dropped_prefixes: vec![],
+            prefix_ttls: vec![],
+            prefix_options: vec![],
             clean_sender,
         }
     }
@@ -327,11 +723,13 @@ impl Version {
         vec_log: Vec<VersionEdit>,
         ss_table_loader: &Arc<RwLock<SSTableLoader>>,
         block_cache: &Arc<BlockCache>,
+        negative_cache: Option<Arc<NegativeCache>>,
         sender: Sender<CleanTag>
     ) -> Result<Self>{
         let mut version = Self::new(
             ss_table_loader,
             block_cache,
+            negative_cache,
             sender,
         );
 
@@ -355,10 +753,16 @@ impl Version {
         // 避免日志重溯时对最终状态不存在的SSTable进行数据统计处理
         // 导致SSTableMap不存在此SSTable而抛出`KvsError::SSTableLostError`
         let mut gen_set = HashSet::new();
+        let mut is_new_file = false;
+        // SSTable的属性在确认VersionEdit可被应用后再写入共享的SSTableLoader
+        let mut vec_attr_edit = vec![];
 
         for version_edit in vec_version_edit {
             match version_edit {
                 VersionEdit::DeleteFile((mut vec_gen, level)) => {
+                    if level >= self.level_slice.len() {
+                        return Err(KernelError::LevelOver);
+                    }
                     if !is_init {
                         Self::apply_del_on_running(
                             &mut self.meta_data,
@@ -375,6 +779,10 @@ impl Version {
                     del_gens.append(&mut vec_gen);
                 }
                 VersionEdit::NewFile((vec_gen, level), index) => {
+                    if level >= self.level_slice.len() {
+                        return Err(KernelError::LevelOver);
+                    }
+                    is_new_file = true;
                     if !is_init {
                         Self::apply_add(
                             &mut self.meta_data,
@@ -396,6 +804,11 @@ impl Version {
                             self.level_slice[level].push(gen);
                         }
                     } else {
+                        invariant!(
+                            index <= self.level_slice[level].len(),
+                            "NewFile index {} out of bounds for level {} with {} SSTables",
+                            index, level, self.level_slice[level].len()
+                        );
                         for gen in vec_gen
                             .into_iter()
                             .sorted()
@@ -405,6 +818,55 @@ impl Version {
                         }
                     }
                 }
+                VersionEdit::LastSequence(seq_id) => {
+                    self.last_sequence = self.last_sequence.max(seq_id);
+                }
+                // 路径需立即记录，以便范围校验时能够读取到新的SSTable
+                VersionEdit::FilePath(vec_gen, path_id) => {
+                    ss_tables_map.set_path(&vec_gen, path_id);
+                }
+                edit @ (VersionEdit::Window(..) | VersionEdit::Expiry(..)) => vec_attr_edit.push(edit),
+                VersionEdit::DropPrefix(prefix) => {
+                    self.prefix_ttls.retain(|(ttl_prefix, _)| ttl_prefix[..] != prefix[..]);
+                    self.prefix_options.retain(|(options_prefix, _)| options_prefix[..] != prefix[..]);
+                    if !self.dropped_prefixes.iter().any(|dropped| dropped[..] == prefix[..]) {
+                        self.dropped_prefixes.push(Bytes::from(prefix));
+                    }
+                }
+                VersionEdit::PrefixTtl(prefix, ttl_millis) => {
+                    self.prefix_ttls.retain(|(ttl_prefix, _)| ttl_prefix[..] != prefix[..]);
+                    self.prefix_ttls.push((Bytes::from(prefix), ttl_millis));
+                }
+                VersionEdit::PrefixOptions(prefix, table_options) => {
+                    self.prefix_options.retain(|(options_prefix, _)| options_prefix[..] != prefix[..]);
+                    self.prefix_options.push((Bytes::from(prefix), table_options));
+                }
+                VersionEdit::Step => (),
+            }
+        }
+        for level in 1..7 {
+            let scope_index = ScopeIndex::new(&self.level_slice[level], &ss_tables_map);
+
+            // Level 1-6需保证SSTable之间范围不重叠，运行时拒绝会破坏此约束的VersionEdit
+            // 此时VersionEdit尚未持久化，因此当前Version不受影响
+            // 初始化时仅进行警告，以兼容此前已持久化的Version
+            if scope_index.is_overlapped() {
+                if !is_init {
+                    return Err(KernelError::LevelOverlap(level));
+                }
+                warn!("[Version][apply][Level: {}]: SSTable scopes overlapped", level);
+            }
+            self.scope_index[level] = scope_index;
+        }
+        for edit in vec_attr_edit {
+            match edit {
+                VersionEdit::Window(vec_gen, window_start) => {
+                    ss_tables_map.set_window(&vec_gen, window_start);
+                }
+                VersionEdit::Expiry(vec_gen, max_expiry) => {
+                    ss_tables_map.set_expiry(&vec_gen, max_expiry);
+                }
+                _ => (),
             }
         }
         // 在初始化时进行统计数据累加
@@ -417,6 +879,12 @@ impl Version {
             ).await?;
         }
 
+        // 新增的SSTable中可能存在此前不存在的Key
+        if is_new_file {
+            self.negative_cache = self.negative_cache.as_ref()
+                .map(|negative_cache| negative_cache.renew().map(Arc::new))
+                .transpose()?;
+        }
         self.version_num += 1;
 
         self.clean_sender.send(
@@ -461,46 +929,6 @@ impl Version {
             .map(|(index, _)| index)
     }
 
-    /// FIXME: 使用范围索引相关的使每层SSTable不需要遍历所有SSTable
-    async fn rfind_ss_table<F>(
-        level_slice: &LevelSlice,
-        ss_table_loader: &SSTableLoader,
-        level: usize,
-        fn_rfind: F
-    ) -> Option<SSTable>
-        where F: Fn(&SSTable) -> bool
-    {
-        for gen in level_slice[level].iter() {
-            if let Some(ss_table) = ss_table_loader.get(*gen) {
-                if fn_rfind(&ss_table) {
-                    return Some(ss_table);
-                }
-            }
-        }
-
-        None
-    }
-
-    pub(crate) async fn get_first_vec_ss_table_with_size(&self, level: usize, size: usize) -> Option<Vec<SSTable>> {
-        let mut vec = Vec::new();
-        let ss_table_loader = self.ss_tables_map.read().await;
-
-        if self.level_slice[level].is_empty() {
-            return None
-        }
-
-        for gen in self.level_slice[level]
-            .iter()
-            .take(size)
-        {
-            if let Some(ss_table) = ss_table_loader.get(*gen) {
-                vec.push(ss_table);
-            }
-        }
-        Some(vec)
-    }
-
-    #[allow(dead_code)]
     pub(crate) async fn get_ss_tables_for_level(&self, level: usize) -> Vec<SSTable> {
         let ss_table_loader = self.ss_tables_map.read().await;
 
@@ -510,6 +938,15 @@ impl Version {
             .collect_vec()
     }
 
+    /// 获取Level 1-6中以Scope的start由小到大排序的SSTable
+    pub(crate) async fn get_sorted_ss_tables(&self, level: usize) -> Vec<SSTable> {
+        let ss_table_loader = self.ss_tables_map.read().await;
+
+        self.scope_index[level].vec_scope.iter()
+            .filter_map(|(_, gen)| ss_table_loader.get(*gen))
+            .collect_vec()
+    }
+
     /// 获取所有ss_table
     pub(crate) async fn get_all_ss_tables(&self) -> Vec<Vec<SSTable>> {
         let ss_table_loader = self.ss_tables_map.read().await;
@@ -529,22 +966,27 @@ impl Version {
 
     /// 获取指定level中与scope冲突的
     pub(crate) async fn get_meet_scope_ss_tables(&self, level: usize, scope: &Scope) -> Vec<SSTable> {
-        let mut vec = Vec::new();
         let ss_table_loader = self.ss_tables_map.read().await;
 
-        for gen in self.level_slice[level].iter()
-        {
-            if let Some(ss_table) = ss_table_loader.get(*gen) {
-                if ss_table.get_scope().meet(scope) {
-                    vec.push(ss_table);
-                }
-            }
+        if level == LEVEL_0 {
+            self.level_slice[level].iter()
+                .filter_map(|gen| ss_table_loader.get(*gen))
+                .filter(|ss_table| ss_table.get_scope().meet(scope))
+                .collect_vec()
+        } else {
+            self.scope_index[level].find_with_scope(scope)
+                .filter_map(|gen| ss_table_loader.get(gen))
+                .collect_vec()
         }
-        vec
     }
 
     /// 使用Key从现有SSTables中获取对应的数据
+    ///
+    /// 由新至旧查询，遇到该Key的删除记录时即返回None，而不会读取到更旧的SSTable中删除前的数据
     pub(crate) async fn find_data_for_ss_tables(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        if self.negative_cache.as_ref().is_some_and(|cache| cache.is_absent(key)) {
+            return Ok(None);
+        }
         let ss_table_loader = self.ss_tables_map.read().await;
         let block_cache = &self.block_cache;
 
@@ -556,44 +998,48 @@ impl Version {
             if let Some(ss_table) = ss_table_loader.get(*gen) {
                 if ss_table.get_scope().meet_with_key(key) {
                     if let Some(value) =
-                        Self::query_with_ss_table(key, block_cache, &ss_table)?
+                        Self::query_with_ss_table(key, block_cache, &ss_table).await?
                     {
-                        return Ok(Some(value))
+                        return Ok(value)
                     }
                 }
             }
         }
         // Level 1-7的数据排布有序且唯一，因此在每一个等级可以直接找到唯一一个Key可能在范围内的SSTable
         for level in 1..7 {
-            if let Some(ss_table) = Self::rfind_ss_table(
-                &self.level_slice,
-                &ss_table_loader,
-                level,
-                |ss_table| ss_table.get_scope().meet_with_key(key)
-            ).await {
+            if let Some(ss_table) = self.scope_index[level]
+                .find_with_key(key)
+                .and_then(|gen| ss_table_loader.get(gen))
+            {
                 if let Some(value) =
-                    Self::query_with_ss_table(key, block_cache, &ss_table)?
+                    Self::query_with_ss_table(key, block_cache, &ss_table).await?
                 {
-                    return Ok(Some(value))
+                    return Ok(value)
                 }
             }
         }
 
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.insert(key);
+        }
+
         Ok(None)
     }
 
-    fn query_with_ss_table(
+    async fn query_with_ss_table(
         key: &[u8],
         block_cache: &BlockCache,
         ss_table: &SSTable
-    ) -> Result<Option<Bytes>> {
-        ss_table.query_with_key(key, block_cache)
+    ) -> Result<Option<Option<Bytes>>> {
+        ss_table.query_with_key(key, block_cache).await
     }
 
     /// 判断是否溢出指定的SSTable数量
-    pub(crate) fn is_threshold_exceeded_major(&self, config: &Config, level: usize) -> bool {
+    ///
+    /// major_threshold为Level 0的阈值，开启自适应压缩时由Compactor调整，否则为`Config::major_threshold_with_sst_size`
+    pub(crate) fn is_threshold_exceeded_major(&self, config: &Config, major_threshold: usize, level: usize) -> bool {
         self.level_slice[level].len() >=
-            (config.major_threshold_with_sst_size * config.level_sst_magnification.pow(level as u32))
+            (major_threshold * config.level_sst_magnification.pow(level as u32))
     }
 }
 
@@ -641,14 +1087,16 @@ mod tests {
     use std::sync::Arc;
     use std::time::Duration;
     use bytes::Bytes;
+    use itertools::Itertools;
     use tempfile::TempDir;
     use tokio::time;
     use crate::kernel::io::{FileExtension, IoFactory, IoType};
     use crate::kernel::lsm::log::LogLoader;
     use crate::kernel::lsm::lsm_kv::{Config, DEFAULT_WAL_PATH};
-    use crate::kernel::lsm::ss_table::SSTable;
+    use crate::kernel::lsm::ss_table::{Scope, SSTable};
     use crate::kernel::lsm::version::{DEFAULT_SS_TABLE_PATH, Version, VersionEdit, VersionStatus};
     use crate::kernel::Result;
+    use crate::KernelError;
 
     #[test]
     fn test_version_clean() -> Result<()> {
@@ -701,6 +1149,21 @@ mod tests {
 
             ver_status.log_and_apply(vec_edit_1).await?;
 
+            let ss_table_3 = SSTable::create_for_mem_table(
+                &config,
+                3,
+                &sst_factory,
+                vec![(Bytes::from_static(b"test"), None)],
+                0
+            )?;
+            ver_status.insert_vec_ss_table(vec![ss_table_3]).await?;
+            // 超出Level上限的VersionEdit返回错误而非Panic，且其新增的SSTable被删除
+            assert!(matches!(
+                ver_status.log_and_apply(vec![VersionEdit::NewFile((vec![3], 7), 0)]).await,
+                Err(KernelError::LevelOver)
+            ));
+            assert!(!sst_factory.has_gen(3)?);
+
             let version_1 = Arc::clone(&ver_status.current().await);
 
             let vec_edit_2 = vec![
@@ -811,6 +1274,96 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn test_scope_index() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+
+            let config = Config::new(temp_dir.into_path());
+
+            let (wal, _) = LogLoader::reload(
+                config.clone(),
+                DEFAULT_WAL_PATH,
+                FileExtension::Log,
+                IoType::Direct
+            )?;
+
+            let ver_status =
+                VersionStatus::load_with_path(config.clone(), Arc::new(wal)).await?;
+
+            let sst_factory = IoFactory::new(
+                config.dir_path.join(DEFAULT_SS_TABLE_PATH),
+                FileExtension::SSTable
+            )?;
+
+            let mut vec_ss_table = Vec::new();
+            for (gen, keys) in [(1, [b"a", b"c"]), (2, [b"e", b"g"]), (3, [b"i", b"k"])] {
+                vec_ss_table.push(SSTable::create_for_mem_table(
+                    &config,
+                    gen,
+                    &sst_factory,
+                    keys.iter()
+                        .map(|key| (Bytes::from_static(*key), Some(Bytes::from_static(*key))))
+                        .collect(),
+                    1
+                )?);
+            }
+            ver_status.insert_vec_ss_table(vec_ss_table).await?;
+            // 故意乱序插入，区间索引不依赖level_slice中的顺序
+            ver_status.log_and_apply(vec![
+                VersionEdit::NewFile((vec![3], 1), 0),
+                VersionEdit::NewFile((vec![1], 1), 0),
+                VersionEdit::NewFile((vec![2], 1), 0),
+            ]).await?;
+
+            let version = ver_status.current().await;
+
+            assert_eq!(version.find_data_for_ss_tables(b"e").await?, Some(Bytes::from_static(b"e")));
+            assert_eq!(version.find_data_for_ss_tables(b"k").await?, Some(Bytes::from_static(b"k")));
+            assert_eq!(version.find_data_for_ss_tables(b"d").await?, None);
+            assert_eq!(version.find_data_for_ss_tables(b"z").await?, None);
+
+            let scope = Scope { start: Bytes::from_static(b"b"), end: Bytes::from_static(b"h") };
+            let vec_gen = version.get_meet_scope_ss_tables(1, &scope).await
+                .iter()
+                .map(SSTable::get_gen)
+                .collect_vec();
+            assert_eq!(vec_gen, vec![1, 2]);
+
+            // 完全包含SSTable的scope同样视为相交
+            let scope = Scope { start: Bytes::from_static(b"d"), end: Bytes::from_static(b"z") };
+            let vec_gen = version.get_meet_scope_ss_tables(1, &scope).await
+                .iter()
+                .map(SSTable::get_gen)
+                .collect_vec();
+            assert_eq!(vec_gen, vec![2, 3]);
+
+            // 范围重叠的SSTable不允许进入Level 1
+            let ss_table_4 = SSTable::create_for_mem_table(
+                &config,
+                4,
+                &sst_factory,
+                vec![(Bytes::from_static(b"b"), None)],
+                1
+            )?;
+            ver_status.insert_vec_ss_table(vec![ss_table_4]).await?;
+            assert!(matches!(
+                ver_status.log_and_apply(vec![
+                    VersionEdit::NewFile((vec![4], 1), 0),
+                    VersionEdit::Window(vec![4], 1),
+                ]).await,
+                Err(KernelError::LevelOverlap(1))
+            ));
+            assert_eq!(ver_status.current().await.level_slice[1].len(), 3);
+            // 被拒绝的SSTable不应残留于SSTableLoader与磁盘中
+            assert_eq!(ver_status.get_window(4).await, None);
+            assert!(!sst_factory.has_gen(4)?);
+
+            Ok(())
+        })
+    }
 }
 
 
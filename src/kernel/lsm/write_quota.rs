@@ -0,0 +1,153 @@
+//! 列族的写入配额与写入统计
+//!
+//! 各列族共享同一Store的MemTable、Flush与压缩，单个列族的大量写入所引发的Flush与压缩
+//! 会占用其余列族的磁盘带宽，因此以配额限制其写入速率，超出时写入方等待令牌补足
+//!
+//! ```ignore
+//! let config = Config::new(path)
+//!     .column_family_write_quota(b"events", Quota::default().bytes_per_sec(16 * 1024 * 1024));
+//! ```
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use bytes::Bytes;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use crate::kernel::lsm::column_family;
+use crate::kernel::utils::quota::{Quota, QuotaLimiter};
+
+/// 列族的写入统计，自Store打开后首次获取该列族时起计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ColumnFamilyWriteStats {
+    /// 写入(包括删除)的Key数量
+    pub writes: u64,
+    /// 写入的Key与Value的字节数之和
+    pub written_bytes: u64,
+    /// 因超出写入配额而等待的写入次数
+    pub throttled_writes: u64,
+    /// 因超出写入配额而等待的总时长(微秒)
+    pub throttled_micros: u64,
+}
+
+#[derive(Default)]
+struct WriteState {
+    limiter: Option<Mutex<QuotaLimiter>>,
+    writes: AtomicU64,
+    written_bytes: AtomicU64,
+    throttled_writes: AtomicU64,
+    throttled_micros: AtomicU64,
+}
+
+impl WriteState {
+    /// 超出配额时等待令牌补足后再消耗，并记录写入
+    ///
+    /// 等待期间被取消时不消耗配额，也不计入统计
+    async fn throttle(&self, writes: usize, bytes_len: usize) {
+        if let Some(limiter) = &self.limiter {
+            let start = Instant::now();
+            let mut is_throttled = false;
+
+            loop {
+                let wait_time = limiter.lock().wait_time();
+                let Some(wait_time) = wait_time else {
+                    break;
+                };
+                is_throttled = true;
+                time::sleep(wait_time).await;
+            }
+            limiter.lock().consume_requests(writes, bytes_len);
+
+            if is_throttled {
+                let _ = self.throttled_writes.fetch_add(1, Ordering::Relaxed);
+                let _ = self.throttled_micros.fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+            }
+        }
+        let _ = self.writes.fetch_add(writes as u64, Ordering::Relaxed);
+        let _ = self.written_bytes.fetch_add(bytes_len as u64, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> ColumnFamilyWriteStats {
+        ColumnFamilyWriteStats {
+            writes: self.writes.load(Ordering::Relaxed),
+            written_bytes: self.written_bytes.load(Ordering::Relaxed),
+            throttled_writes: self.throttled_writes.load(Ordering::Relaxed),
+            throttled_micros: self.throttled_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) struct WriteQuotas {
+    /// 列族名称 -> 写入配额
+    quotas: HashMap<Vec<u8>, Quota>,
+    /// 已打开的列族的Key前缀 -> 写入状态
+    column_families: RwLock<HashMap<Bytes, Arc<WriteState>>>,
+}
+
+impl WriteQuotas {
+    pub(crate) fn new(quotas: HashMap<Vec<u8>, Quota>) -> Self {
+        WriteQuotas { quotas, column_families: RwLock::new(HashMap::new()) }
+    }
+
+    /// 记录列族的Key前缀与名称，使其中的写入计入该列族的配额与统计
+    ///
+    /// 同一列族多次获取时沿用已有的写入状态
+    pub(crate) fn bind_column_family(&self, prefix: &Bytes, name: &[u8]) {
+        if self.column_families.read().contains_key(prefix) {
+            return;
+        }
+        let limiter = self.quotas.get(name)
+            .map(|quota| Mutex::new(QuotaLimiter::new(*quota)));
+
+        let _ = self.column_families.write()
+            .entry(prefix.clone())
+            .or_insert_with(|| Arc::new(WriteState { limiter, ..WriteState::default() }));
+    }
+
+    pub(crate) fn unbind_column_family(&self, prefix: &[u8]) {
+        let _ = self.column_families.write().remove(prefix);
+    }
+
+    pub(crate) fn stats(&self, prefix: &[u8]) -> ColumnFamilyWriteStats {
+        self.column_families.read()
+            .get(prefix)
+            .map(|state| state.stats())
+            .unwrap_or_default()
+    }
+
+    /// 按列族汇总写入并计入其统计，超出列族的写入配额时等待
+    ///
+    /// 应在获取Key的锁之前调用，避免等待期间阻塞同一Key上的其余操作
+    pub(crate) async fn throttle<'a>(
+        &self,
+        writes: impl IntoIterator<Item = (&'a [u8], Option<&'a [u8]>)>
+    ) {
+        let mut cf_writes: Vec<(Arc<WriteState>, usize, usize)> = Vec::new();
+        {
+            let column_families = self.column_families.read();
+            if column_families.is_empty() {
+                return;
+            }
+            for (key, value) in writes {
+                let Some(state) = column_family::split_cf_key(key)
+                    .and_then(|(prefix, _)| column_families.get(prefix)) else {
+                    continue
+                };
+                let bytes_len = key.len() + value.map_or(0, <[u8]>::len);
+
+                match cf_writes.iter_mut().find(|(cf_state, ..)| Arc::ptr_eq(cf_state, state)) {
+                    Some((_, writes, total_len)) => {
+                        *writes += 1;
+                        *total_len += bytes_len;
+                    }
+                    None => cf_writes.push((Arc::clone(state), 1, bytes_len)),
+                }
+            }
+        }
+        for (state, writes, bytes_len) in cf_writes {
+            state.throttle(writes, bytes_len).await;
+        }
+    }
+}
@@ -5,16 +5,17 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
 use bytes::Bytes;
-use fslock::LockFile;
 use futures::future;
 use itertools::Itertools;
 use tokio::time;
 
 use crate::kernel::io::{FileExtension, IoReader, IoWriter};
+use crate::kernel::utils::lock_file::LockFile;
 use crate::KernelError;
 use crate::proto::net_pb::{CommandOption, KeyValue};
 
 pub mod hash_kv;
+#[cfg(feature = "sled")]
 pub mod sled_kv;
 pub mod lsm;
 pub mod io;
@@ -25,13 +26,16 @@ pub type Result<T> = std::result::Result<T, KernelError>;
 pub(crate) const DEFAULT_LOCK_FILE: &str = "KipDB.lock";
 
 /// KV持久化内核 操作定义
+///
+/// 除`name`与`open`外均可通过`dyn KVStore`调用，
+/// 因此嵌入式的内核与远程的`RemoteStore`可以统一以`Box<dyn KVStore>`或`Arc<dyn KVStore>`使用
 #[async_trait]
-pub trait KVStore: Send + Sync + 'static + Sized {
+pub trait KVStore: Send + Sync + 'static {
     /// 获取内核名
     fn name() -> &'static str where Self: Sized;
 
     /// 通过数据目录路径开启数据库
-    async fn open(path: impl Into<PathBuf> + Send) -> Result<Self>;
+    async fn open(path: impl Into<PathBuf> + Send) -> Result<Self> where Self: Sized;
 
     /// 强制将数据刷入硬盘
     async fn flush(&self) -> Result<()>;
@@ -176,7 +180,8 @@ impl ByteUtils {
             }
             let len_u8 = &bytes[last_pos..pos];
             let len = Self::from_4_bit_with_start(len_u8);
-            if len < 1 || len > bytes.len() {
+            // 末尾的数据可能因停机异常而不完整
+            if len < 1 || pos + len > bytes.len() {
                 break
             }
 
@@ -256,7 +261,7 @@ impl CommandData {
     /// Command对象通过调用这个方法调用持久化内核进行命令交互
     /// 内部对该类型进行模式匹配而进行不同命令的相应操作
     #[inline]
-    pub async fn apply<K: KVStore>(self, kv_store: &K) -> Result<CommandOption>{
+    pub async fn apply<K: KVStore + ?Sized>(self, kv_store: &K) -> Result<CommandOption>{
         match self {
             CommandData::Set { key, value } => {
                 kv_store.set(&key, Bytes::from(value)).await.map(|_| options_none())
@@ -287,7 +292,7 @@ impl CommandData {
 }
 
 pub(crate) fn options_none() -> CommandOption {
-    CommandOption { r#type: 7, bytes: vec![], value: 0 }
+    CommandOption { r#type: 7, bytes: vec![], value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 }
 }
 
 impl From<KeyValue> for CommandData {
@@ -338,7 +343,7 @@ impl From<Option<Vec<u8>>> for CommandOption {
     #[inline]
     fn from(item: Option<Vec<u8>>) -> Self {
         match item {
-            Some(bytes) => CommandOption { r#type: 2, bytes, value: 0 },
+            Some(bytes) => CommandOption { r#type: 2, bytes, value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 },
             None => options_none()
         }
     }
@@ -348,7 +353,7 @@ impl From<Option<Bytes>> for CommandOption {
     #[inline]
     fn from(item: Option<Bytes>) -> Self {
         match item {
-            Some(bytes) => CommandOption { r#type: 2, bytes: bytes.to_vec(), value: 0 },
+            Some(bytes) => CommandOption { r#type: 2, bytes: bytes.to_vec(), value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 },
             None => options_none()
         }
     }
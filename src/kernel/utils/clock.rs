@@ -0,0 +1,116 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 时钟，内核中依赖系统时间的逻辑均通过`Config::clock`读取当前时间
+///
+/// 包括时间窗口压缩的窗口划分与过期、租约的过期与RDB导入时的过期判断
+///
+/// Tips: 限速与等待(如后台校验的速率、WAL的批量同步窗口)基于tokio的计时器，
+/// 测试中可通过`tokio::time::pause`与`tokio::time::advance`控制
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// 当前的Unix时间戳(毫秒)
+    fn now_millis(&self) -> i64;
+}
+
+/// 系统时钟，默认使用
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now_millis(&self) -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as i64)
+    }
+}
+
+/// 虚拟时钟，仅在调用`advance`或`set`时前进，用于编写可复现的测试
+///
+/// 克隆的VirtualClock共享同一时间，因此可在传入`Config::clock`后于测试中继续控制
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    millis: Arc<AtomicI64>,
+}
+
+impl VirtualClock {
+    /// 以start_millis(Unix时间戳，毫秒)作为起始时间
+    #[inline]
+    pub fn new(start_millis: i64) -> Self {
+        VirtualClock { millis: Arc::new(AtomicI64::new(start_millis)) }
+    }
+
+    /// 使时间前进duration
+    #[inline]
+    pub fn advance(&self, duration: Duration) {
+        let _ = self.millis.fetch_add(duration.as_millis() as i64, Ordering::AcqRel);
+    }
+
+    /// 将时间设置为millis，允许回拨以模拟系统时间的跳变
+    #[inline]
+    pub fn set(&self, millis: i64) {
+        self.millis.store(millis, Ordering::Release);
+    }
+}
+
+impl Clock for VirtualClock {
+    #[inline]
+    fn now_millis(&self) -> i64 {
+        self.millis.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use crate::kernel::KVStore;
+    use crate::kernel::lsm::lsm_kv::{CompactionStyle, Config, LsmStore};
+    use crate::kernel::Result;
+    use crate::kernel::utils::clock::VirtualClock;
+    use crate::kernel::utils::runtime::DeterministicExecutor;
+
+    #[test]
+    fn test_virtual_clock() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let clock = VirtualClock::new(0);
+            let executor = DeterministicExecutor::new(42);
+            let config = Config::new(temp_dir.path())
+                .compaction_style(CompactionStyle::TimeWindow {
+                    window: Duration::from_secs(60),
+                    ttl: Some(Duration::from_secs(600)),
+                })
+                .spawner(executor.spawner())
+                .clock(Arc::new(clock.clone()));
+            let kv_store = executor.run_until(LsmStore::open_with_config(config)).await?;
+
+            kv_store.set(b"t1", Bytes::from_static(b"v1")).await?;
+            executor.run_until(kv_store.flush()).await?;
+            // 窗口尚未结束
+            let leases = kv_store.leases();
+            let lease = leases.grant(Duration::from_secs(30)).await?;
+            assert_eq!(kv_store.current_version().await.level_sst_count()[1], 0);
+
+            clock.advance(Duration::from_secs(60));
+            kv_store.set(b"t2", Bytes::from_static(b"v2")).await?;
+            executor.run_until(kv_store.flush()).await?;
+            assert_eq!(kv_store.current_version().await.level_sst_count()[1], 1);
+            assert!(!leases.keep_alive(lease.id).await?);
+
+            // 超出ttl的窗口整个被删除
+            clock.advance(Duration::from_secs(660));
+            kv_store.set(b"t3", Bytes::from_static(b"v3")).await?;
+            executor.run_until(kv_store.flush()).await?;
+            assert_eq!(kv_store.get(b"t1").await?, None);
+            assert_eq!(kv_store.get(b"t3").await?, Some(Bytes::from_static(b"v3")));
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,221 @@
+use crate::kernel::Result;
+use crate::KernelError;
+
+const SIGN_MASK: u64 = 1 << 63;
+
+/// 变长组件中的0x00转义为0x00 0xFF
+const ESCAPE: u8 = 0xFF;
+
+/// 变长组件以0x00 0x01结尾，使较短的组件排在以其为前缀的组件之前
+const TERMINATOR: u8 = 0x01;
+
+/// 保序的复合Key编码器
+///
+/// 各组件编码后按字节序比较的结果与按组件依次比较的结果一致，
+/// 因此以复合Key进行range查询时无需额外处理
+/// - 整数以大端序编码，有符号整数翻转符号位使负数排在正数之前
+/// - 浮点数按IEEE 754的位进行变换，NaN排在所有数之后
+/// - 字节与字符串进行转义并附加终止符，使其可以出现在复合Key的中间
+///
+/// 调用`desc`后，下一个组件按降序编码
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyEncoder {
+    bytes: Vec<u8>,
+    desc: bool,
+}
+
+impl KeyEncoder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 下一个组件按降序编码
+    #[inline]
+    pub fn desc(mut self) -> Self {
+        self.desc = true;
+        self
+    }
+
+    #[inline]
+    pub fn u64(self, value: u64) -> Self {
+        self.push(&value.to_be_bytes())
+    }
+
+    #[inline]
+    pub fn i64(self, value: i64) -> Self {
+        self.u64(value as u64 ^ SIGN_MASK)
+    }
+
+    #[inline]
+    pub fn f64(self, value: f64) -> Self {
+        let bits = value.to_bits();
+
+        self.u64(if bits & SIGN_MASK != 0 { !bits } else { bits ^ SIGN_MASK })
+    }
+
+    #[inline]
+    pub fn bytes(self, value: &[u8]) -> Self {
+        let mut escaped = Vec::with_capacity(value.len() + 2);
+
+        for byte in value {
+            escaped.push(*byte);
+            if *byte == 0 {
+                escaped.push(ESCAPE);
+            }
+        }
+        escaped.extend_from_slice(&[0, TERMINATOR]);
+
+        self.push(&escaped)
+    }
+
+    #[inline]
+    pub fn str(self, value: &str) -> Self {
+        self.bytes(value.as_bytes())
+    }
+
+    #[inline]
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn push(mut self, component: &[u8]) -> Self {
+        if self.desc {
+            self.bytes.extend(component.iter().map(|byte| !byte));
+        } else {
+            self.bytes.extend_from_slice(component);
+        }
+        self.desc = false;
+        self
+    }
+}
+
+/// 保序的复合Key解码器，需以与编码时相同的顺序与方向读取各组件
+#[derive(Debug, Clone, Copy)]
+pub struct KeyDecoder<'a> {
+    bytes: &'a [u8],
+    desc: bool,
+}
+
+impl<'a> KeyDecoder<'a> {
+    #[inline]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        KeyDecoder { bytes, desc: false }
+    }
+
+    /// 下一个组件按降序解码
+    #[inline]
+    pub fn desc(&mut self) -> &mut Self {
+        self.desc = true;
+        self
+    }
+
+    #[inline]
+    pub fn u64(&mut self) -> Result<u64> {
+        let mask = self.take_mask();
+        let (component, remaining) = self.bytes.split_first_chunk::<8>()
+            .ok_or(KernelError::InvalidKey)?;
+        self.bytes = remaining;
+
+        Ok(u64::from_be_bytes(component.map(|byte| byte ^ mask)))
+    }
+
+    #[inline]
+    pub fn i64(&mut self) -> Result<i64> {
+        Ok((self.u64()? ^ SIGN_MASK) as i64)
+    }
+
+    #[inline]
+    pub fn f64(&mut self) -> Result<f64> {
+        let bits = self.u64()?;
+
+        Ok(f64::from_bits(if bits & SIGN_MASK != 0 { bits ^ SIGN_MASK } else { !bits }))
+    }
+
+    #[inline]
+    pub fn bytes(&mut self) -> Result<Vec<u8>> {
+        let mask = self.take_mask();
+        let mut value = Vec::new();
+        let mut iter = self.bytes.iter()
+            .map(|byte| byte ^ mask)
+            .enumerate();
+
+        while let Some((_, byte)) = iter.next() {
+            if byte != 0 {
+                value.push(byte);
+                continue;
+            }
+            match iter.next() {
+                Some((_, ESCAPE)) => value.push(0),
+                Some((pos, TERMINATOR)) => {
+                    self.bytes = &self.bytes[pos + 1..];
+                    return Ok(value);
+                }
+                _ => break,
+            }
+        }
+
+        Err(KernelError::InvalidKey)
+    }
+
+    #[inline]
+    pub fn str(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?)
+            .map_err(|_| KernelError::InvalidKey)
+    }
+
+    /// 是否已读取所有组件
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take_mask(&mut self) -> u8 {
+        let mask = if self.desc { u8::MAX } else { 0 };
+        self.desc = false;
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use crate::kernel::utils::keys::{KeyDecoder, KeyEncoder};
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_order_preserving() -> Result<()> {
+        let ints = [i64::MIN, -2, -1, 0, 1, i64::MAX];
+        let floats = [f64::NEG_INFINITY, -1.5, -0.0, 0.0, 2.5, f64::INFINITY];
+        let strs = ["", "a", "a\0", "a\0b", "ab", "b"];
+
+        let encoded = ints.iter()
+            .cartesian_product(floats.iter())
+            .cartesian_product(strs.iter())
+            .map(|((int, float), str)| {
+                KeyEncoder::new().i64(*int).f64(*float).desc().str(str).finish()
+            })
+            .collect_vec();
+        let mut sorted = encoded;
+        sorted.sort();
+        // 按组件依次比较时的顺序，字符串组件为降序
+        let expected = ints.iter()
+            .cartesian_product(floats.iter())
+            .cartesian_product(strs.iter().rev())
+            .map(|((int, float), str)| {
+                KeyEncoder::new().i64(*int).f64(*float).desc().str(str).finish()
+            })
+            .collect_vec();
+        assert_eq!(sorted, expected);
+
+        let key = KeyEncoder::new().u64(7).desc().i64(-3).str("a\0b").finish();
+        let mut decoder = KeyDecoder::new(&key);
+        assert_eq!(decoder.u64()?, 7);
+        assert_eq!(decoder.desc().i64()?, -3);
+        assert_eq!(decoder.str()?, "a\0b");
+        assert!(decoder.is_empty());
+        assert!(decoder.u64().is_err());
+
+        Ok(())
+    }
+}
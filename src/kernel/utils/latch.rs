@@ -0,0 +1,92 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use itertools::Itertools;
+use tokio::sync::{Mutex, MutexGuard};
+
+/// 以Key的Hash值进行分片的锁存器
+///
+/// 用于串行化同一Key上的读-改-写操作(如compare_and_swap)，
+/// 不同分片之间互不阻塞，分片冲突时仅会造成少量的额外等待
+pub(crate) struct Latches<S = RandomState> {
+    slots: Vec<Mutex<()>>,
+    hasher: S,
+}
+
+impl<S: BuildHasher> Latches<S> {
+    pub(crate) fn new(size: usize, hasher: S) -> Self {
+        let slots = (0..size.max(1))
+            .map(|_| Mutex::new(()))
+            .collect();
+
+        Latches { slots, hasher }
+    }
+
+    /// 获取Key所属分片的锁
+    pub(crate) async fn lock<K: Hash + ?Sized>(&self, key: &K) -> MutexGuard<'_, ()> {
+        self.slots[self.slot(key)]
+            .lock().await
+    }
+
+    /// 获取一组Key所属分片的锁
+    ///
+    /// 各分片以序号升序加锁，避免同时获取多个分片的调用之间互相等待而死锁
+    pub(crate) async fn lock_all<'k, K: Hash + ?Sized + 'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k K>
+    ) -> Vec<MutexGuard<'_, ()>> {
+        let slots = keys.into_iter()
+            .map(|key| self.slot(key))
+            .sorted_unstable()
+            .dedup()
+            .collect_vec();
+        let mut guards = Vec::with_capacity(slots.len());
+
+        for slot in slots {
+            guards.push(self.slots[slot].lock().await);
+        }
+        guards
+    }
+
+    fn slot<K: Hash + ?Sized>(&self, key: &K) -> usize {
+        self.hasher.hash_one(key) as usize % self.slots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+    use itertools::Itertools;
+    use crate::kernel::utils::latch::Latches;
+
+    #[test]
+    fn test_latches() {
+        let latches = Latches::new(4, RandomState::default());
+
+        tokio_test::block_on(async move {
+            let guard = latches.lock(b"kip".as_slice()).await;
+            // 同一Key在锁持有期间无法再次获取
+            assert!(latches.slots.iter().any(|slot| slot.try_lock().is_err()));
+            drop(guard);
+            assert!(latches.slots.iter().all(|slot| slot.try_lock().is_ok()));
+        })
+    }
+
+    #[test]
+    fn test_latches_lock_all() {
+        let latches = Latches::new(4, RandomState::default());
+        let keys = [b"k1".as_slice(), b"k2", b"k3", b"k4", b"k5", b"k1"];
+
+        tokio_test::block_on(async move {
+            // 重复的Key与分片冲突的Key不会重复加锁
+            let guards = latches.lock_all(keys).await;
+            let slots = keys.iter()
+                .map(|key| latches.slot(*key))
+                .unique()
+                .count();
+            assert_eq!(guards.len(), slots);
+            assert_eq!(latches.slots.iter().filter(|slot| slot.try_lock().is_err()).count(), slots);
+            drop(guards);
+            assert!(latches.slots.iter().all(|slot| slot.try_lock().is_ok()));
+        })
+    }
+}
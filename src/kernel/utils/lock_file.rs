@@ -0,0 +1,24 @@
+//! 数据目录的进程锁
+//!
+//! wasm32-wasi不提供文件锁，且数据目录由宿主映射至单个实例的沙箱内，因此以总是成功的空实现代替
+#[cfg(not(target_family = "wasm"))]
+pub(crate) use fslock::LockFile;
+
+#[cfg(target_family = "wasm")]
+#[derive(Debug)]
+pub(crate) struct LockFile;
+
+#[cfg(target_family = "wasm")]
+impl LockFile {
+    pub(crate) fn open(_path: &std::path::Path) -> std::io::Result<Self> {
+        Ok(LockFile)
+    }
+
+    pub(crate) fn try_lock(&mut self) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    pub(crate) fn unlock(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
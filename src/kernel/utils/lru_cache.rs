@@ -2,12 +2,18 @@ use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::collections::hash_map::{Iter, RandomState};
 use std::collections::HashMap;
+use std::future::Future;
 use std::hash::{BuildHasher, Hash, Hasher};
+use std::iter;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::time::Instant;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex as AsyncMutex;
 use crate::error::CacheError;
 
 pub type Result<T> = std::result::Result<T, CacheError>;
@@ -44,12 +50,92 @@ impl<K, V> DerefMut for NodeReadPtr<K, V> {
     }
 }
 
-unsafe impl<K: Send, V: Send, S: Send> Send for ShardingLruCache<K, V, S> {}
-unsafe impl<K: Sync, V: Sync, S: Sync> Sync for ShardingLruCache<K, V, S> {}
+unsafe impl<K: Send, V: Send + Sync, S: Send> Send for ShardingLruCache<K, V, S> {}
+unsafe impl<K: Sync, V: Send + Sync, S: Sync> Sync for ShardingLruCache<K, V, S> {}
 
+/// 分片的线程安全Lru缓存
+///
+/// Value以Arc的形式存储，读取时返回其Arc句柄，
+/// 因此元素被驱逐或移除后，调用方持有的句柄依旧有效，其内存在所有句柄释放后才回收
 pub(crate) struct ShardingLruCache<K, V, S = RandomState> {
-    sharding_vec: Vec<Arc<Mutex<LruCache<K, V>>>>,
+    sharding_vec: Vec<Shard<K, V>>,
     hasher: S,
+    /// 与sharding_vec一一对应的各分片计数
+    counters: Vec<ShardCounter>,
+    /// 正在通过`get_or_insert_async`加载的Key，同一Key的并发加载会等待同一个锁
+    loading: LoadingMap<K>,
+}
+
+type Shard<K, V> = Arc<Mutex<LruCache<K, Arc<V>>>>;
+
+type LoadingMap<K> = Mutex<HashMap<K, Arc<AsyncMutex<()>>>>;
+
+/// 加载结束(包括加载失败或被取消)时移除该Key正在加载的标记
+struct LoadingGuard<'a, K: Hash + Eq> {
+    loading: &'a LoadingMap<K>,
+    key: K,
+    latch: Arc<AsyncMutex<()>>,
+}
+
+impl<K: Hash + Eq> Drop for LoadingGuard<'_, K> {
+    fn drop(&mut self) {
+        let mut loading = self.loading.lock();
+
+        // 加载失败后由等待者接手加载时，标记可能已被替换为新的锁
+        if loading.get(&self.key).is_some_and(|latch| Arc::ptr_eq(latch, &self.latch)) {
+            let _ignore = loading.remove(&self.key);
+        }
+    }
+}
+
+/// 分片的计数，驱逐次数由分片自身在锁内统计
+#[derive(Default)]
+struct ShardCounter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+    load_micros: AtomicU64,
+}
+
+/// 缓存统计数据快照
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// 因容量不足而被驱逐的元素数量
+    pub evictions: u64,
+    pub insertions: u64,
+    /// 未命中时加载元素的累计耗时
+    pub load_micros: u64,
+}
+
+impl CacheStats {
+    /// 命中率，以百分比表示
+    #[inline]
+    pub fn hit_percent(&self) -> u64 {
+        (self.hits * 100)
+            .checked_div(self.hits + self.misses)
+            .unwrap_or(0)
+    }
+
+    /// 未命中时平均每次加载的耗时
+    #[inline]
+    pub fn load_micros_avg(&self) -> u64 {
+        self.load_micros
+            .checked_div(self.misses)
+            .unwrap_or(0)
+    }
+
+    fn merge(self, other: CacheStats) -> Self {
+        CacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+            insertions: self.insertions + other.insertions,
+            load_micros: self.load_micros + other.load_micros,
+        }
+    }
 }
 
 struct Node<K, V> {
@@ -101,6 +187,8 @@ pub(crate) struct LruCache<K, V> {
     tail: Option<NodeReadPtr<K, V>>,
     inner: HashMap<KeyRef<K, V>, NodeReadPtr<K, V>>,
     cap: usize,
+    /// 被驱逐的元素数量
+    evictions: u64,
     marker: PhantomData<Node<K, V>>,
 }
 
@@ -125,30 +213,39 @@ impl<K: Hash + Eq + PartialEq, V, S: BuildHasher> ShardingLruCache<K, V, S> {
         for _ in 0..sharding_size {
             sharding_vec.push(Arc::new(Mutex::new(LruCache::new(sharding_cap)?)));
         }
+        let counters = iter::repeat_with(ShardCounter::default)
+            .take(sharding_size)
+            .collect();
 
         Ok(ShardingLruCache {
             sharding_vec,
             hasher,
+            counters,
+            loading: Mutex::new(HashMap::new()),
         })
     }
 
     #[allow(dead_code)]
-    pub(crate) fn get(&self, key: &K) -> Option<&V> {
-        self.shard(key)
-            .lock()
-            .get_node(key)
-            .map(|node| {
-                unsafe { &node.as_ref().value }
-            })
+    pub(crate) fn get(&self, key: &K) -> Option<Arc<V>> {
+        let index = self.shard_index(key);
+        let option = self.get_in_shard(index, key);
+
+        let counter = &self.counters[index];
+        let _ = if option.is_some() { &counter.hits } else { &counter.misses }
+            .fetch_add(1, AtomicOrdering::Relaxed);
+        option
     }
 
-    pub(crate) fn put(&self, key: K, value: V) -> Option<V> {
-        self.shard(&key)
+    pub(crate) fn put(&self, key: K, value: V) -> Option<Arc<V>> {
+        let index = self.shard_index(&key);
+        let _ = self.counters[index].insertions.fetch_add(1, AtomicOrdering::Relaxed);
+
+        self.sharding_vec[index]
             .lock()
-            .put(key, value)
+            .put(key, Arc::new(value))
     }
 
-    pub(crate) fn remove(&self, key: &K) -> Option<V> {
+    pub(crate) fn remove(&self, key: &K) -> Option<Arc<V>> {
         self.shard(key)
             .lock()
             .remove(key)
@@ -168,24 +265,182 @@ impl<K: Hash + Eq + PartialEq, V, S: BuildHasher> ShardingLruCache<K, V, S> {
         &self,
         key: K,
         fn_once: F
-    ) -> Result<&V>
+    ) -> Result<Arc<V>>
         where F: FnOnce(&K) -> Result<V>
     {
-        self.shard(&key)
+        let index = self.shard_index(&key);
+        let counter = &self.counters[index];
+        let mut is_miss = false;
+        let result = self.sharding_vec[index]
             .lock()
-            .get_or_insert_node(key, fn_once)
-            .map(|node| unsafe { &node.as_ref().value })
+            .get_or_insert_node(key, |key| {
+                is_miss = true;
+                let start = Instant::now();
+                let result = fn_once(key).map(Arc::new);
+                let _ = counter.load_micros.fetch_add(start.elapsed().as_micros() as u64, AtomicOrdering::Relaxed);
+
+                result
+            })
+            // 在持有分片锁时复制句柄，避免元素被并发驱逐
+            .map(|node| Arc::clone(unsafe { &node.as_ref().value }));
+
+        if is_miss {
+            let _ = counter.misses.fetch_add(1, AtomicOrdering::Relaxed);
+            if result.is_ok() {
+                let _ = counter.insertions.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+        } else {
+            let _ = counter.hits.fetch_add(1, AtomicOrdering::Relaxed);
+        }
+
+        result
+    }
+
+    /// 获取缓存中的元素数量
+    pub(crate) fn len(&self) -> usize {
+        self.sharding_vec.iter()
+            .map(|lru| lru.lock().len())
+            .sum()
+    }
+
+    /// 获取缓存中的所有Key
+    pub(crate) fn keys(&self) -> Vec<K> where K: Clone {
+        self.sharding_vec.iter()
+            .flat_map(|lru| {
+                lru.lock()
+                    .iter()
+                    .map(|(key, _)| key.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// 对缓存中的所有Value进行求和统计
+    pub(crate) fn sum_by<F>(&self, fn_weight: F) -> usize
+        where F: Fn(&V) -> usize
+    {
+        self.sharding_vec.iter()
+            .map(|lru| {
+                lru.lock()
+                    .iter()
+                    .map(|(_, value)| fn_weight(value))
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    fn get_in_shard(&self, index: usize, key: &K) -> Option<Arc<V>> {
+        self.sharding_vec[index]
+            .lock()
+            .get_node(key)
+            .map(|node| Arc::clone(unsafe { &node.as_ref().value }))
+    }
+
+    /// 各分片的统计数据，以分片顺序排列
+    pub(crate) fn shard_stats(&self) -> Vec<CacheStats> {
+        self.sharding_vec.iter()
+            .zip(self.counters.iter())
+            .map(|(lru, counter)| CacheStats {
+                hits: counter.hits.load(AtomicOrdering::Relaxed),
+                misses: counter.misses.load(AtomicOrdering::Relaxed),
+                evictions: lru.lock().evictions,
+                insertions: counter.insertions.load(AtomicOrdering::Relaxed),
+                load_micros: counter.load_micros.load(AtomicOrdering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// 修改缓存的总容量，并平均分配至各分片
+    ///
+    /// 容量会向下对齐至分片数量的整数倍，且每个分片至少为1，
+    /// 容量缩小时各分片立即驱逐超出的元素
+    pub(crate) fn set_capacity(&self, cap: usize) -> Result<()> {
+        if cap < 1 {
+            return Err(CacheError::CacheSizeOverFlow)
+        }
+        let sharding_cap = (cap / self.sharding_size()).max(1);
+
+        for lru in &self.sharding_vec {
+            lru.lock().set_cap(sharding_cap)?;
+        }
+        Ok(())
+    }
+
+    /// 缓存的总容量
+    #[allow(dead_code)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.sharding_vec.iter()
+            .map(|lru| lru.lock().cap)
+            .sum()
+    }
+
+    /// 所有分片汇总的统计数据
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.shard_stats()
+            .into_iter()
+            .fold(CacheStats::default(), CacheStats::merge)
     }
 
     fn sharding_size(&self) -> usize {
         self.sharding_vec.len()
     }
 
-    /// 通过key获取hash值后对其求余获取对应分片
-    fn shard(&self, key: &K) -> Arc<Mutex<LruCache<K, V>>> {
+    /// 通过key获取hash值后对其求余获取对应分片的序号
+    fn shard_index(&self, key: &K) -> usize {
         let mut hasher = self.hasher.build_hasher();
         key.hash(&mut hasher);
-        Arc::clone(&self.sharding_vec[hasher.finish() as usize % self.sharding_size()])
+        hasher.finish() as usize % self.sharding_size()
+    }
+
+    fn shard(&self, key: &K) -> Shard<K, V> {
+        Arc::clone(&self.sharding_vec[self.shard_index(key)])
+    }
+}
+
+impl<K: Hash + Eq + PartialEq + Clone, V, S: BuildHasher> ShardingLruCache<K, V, S> {
+    /// `get_or_insert`的异步版本
+    ///
+    /// 同一Key并发未命中时仅由首个任务执行加载，其余任务等待其完成后直接读取缓存，
+    /// 加载失败时等待中的任务会依次自行加载
+    pub(crate) async fn get_or_insert_async<F, Fut>(
+        &self,
+        key: K,
+        fn_load: F
+    ) -> Result<Arc<V>>
+        where F: FnOnce(&K) -> Fut, Fut: Future<Output = Result<V>>
+    {
+        let index = self.shard_index(&key);
+        let counter = &self.counters[index];
+
+        if let Some(value) = self.get_in_shard(index, &key) {
+            let _ = counter.hits.fetch_add(1, AtomicOrdering::Relaxed);
+            return Ok(value);
+        }
+        let latch = Arc::clone(self.loading.lock().entry(key.clone()).or_default());
+        let _latch_guard = latch.lock().await;
+
+        // 等待期间其他任务可能已完成加载
+        if let Some(value) = self.get_in_shard(index, &key) {
+            let _ = counter.hits.fetch_add(1, AtomicOrdering::Relaxed);
+            return Ok(value);
+        }
+        let _loading_guard = LoadingGuard {
+            loading: &self.loading,
+            key: key.clone(),
+            latch: Arc::clone(&latch),
+        };
+        let _ = counter.misses.fetch_add(1, AtomicOrdering::Relaxed);
+
+        let start = Instant::now();
+        let result = fn_load(&key).await;
+        let _ = counter.load_micros.fetch_add(start.elapsed().as_micros() as u64, AtomicOrdering::Relaxed);
+        let value = result?;
+        let _ = counter.insertions.fetch_add(1, AtomicOrdering::Relaxed);
+
+        self.sharding_vec[index]
+            .lock()
+            .get_or_insert_node(key, |_| Ok(Arc::new(value)))
+            .map(|node| Arc::clone(unsafe { &node.as_ref().value }))
     }
 }
 
@@ -200,6 +455,7 @@ impl<K: Hash + Eq + PartialEq, V> LruCache<K, V> {
             tail: None,
             inner: HashMap::new(),
             cap,
+            evictions: 0,
             marker:PhantomData,
         })
     }
@@ -251,13 +507,34 @@ impl<K: Hash + Eq + PartialEq, V> LruCache<K, V> {
         }
     }
 
+    /// 修改容量，容量缩小时立即驱逐超出的元素
+    pub(crate) fn set_cap(&mut self, cap: usize) -> Result<()> {
+        if cap < 1 {
+            return Err(CacheError::CacheSizeOverFlow)
+        }
+        self.cap = cap;
+        while self.inner.len() > self.cap {
+            self.evict_tail();
+        }
+
+        Ok(())
+    }
+
     /// 判断并驱逐节点
     fn expulsion(&mut self) {
+        if self.inner.len() >= self.cap {
+            self.evict_tail();
+        }
+    }
+
+    /// 驱逐尾部最久未使用的节点
+    fn evict_tail(&mut self) {
         if let Some(tail) = self.tail {
-            if self.inner.len() >= self.cap {
-                self.detach(tail);
-                let _ignore = self.inner.remove(&KeyRef(tail));
-            }
+            self.detach(tail);
+            let _ignore = self.inner.remove(&KeyRef(tail));
+            self.evictions += 1;
+            // 对外不再暴露节点内的引用(ShardingLruCache返回的是Arc句柄)，因此可以直接释放
+            drop(unsafe { Box::from_raw(tail.as_ptr()) });
         }
     }
 
@@ -396,6 +673,9 @@ impl<K, V> Drop for LruCache<K, V> {
 mod tests {
     use std::collections::hash_map::RandomState;
     use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
     use crate::kernel::utils::lru_cache::{LruCache, ShardingLruCache};
 
     #[test]
@@ -434,14 +714,65 @@ mod tests {
         let lru = ShardingLruCache::new(4, 2, RandomState::default()).unwrap();
         assert!(lru.is_empty());
         assert_eq!(lru.put(1, 10), None);
-        assert_eq!(lru.get(&1), Some(&10));
+        assert_eq!(lru.get(&1), Some(Arc::new(10)));
         assert!(!lru.is_empty());
         assert_eq!(
             lru.get_or_insert(
                 9,
                 |_| Ok(9)
             ).unwrap(),
-            &9
+            Arc::new(9)
         );
+
+        // 元素被移除后已获取的句柄依旧有效
+        let handle = lru.get(&1);
+        assert_eq!(lru.remove(&1), Some(Arc::new(10)));
+        assert_eq!(lru.get(&1), None);
+        assert_eq!(handle.as_deref(), Some(&10));
+    }
+
+    #[test]
+    fn test_sharding_cache_stats() {
+        let lru = ShardingLruCache::new(2, 2, RandomState::default()).unwrap();
+        for i in 0..10 {
+            let _ = lru.get_or_insert(i, |i| Ok(*i)).unwrap();
+        }
+        let _ = lru.get_or_insert(9, |i| Ok(*i)).unwrap();
+
+        let stats = lru.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 10);
+        assert_eq!(stats.insertions, 10);
+        // 各分片容量为1，因此除各分片最后插入的元素外均被驱逐
+        assert_eq!(stats.evictions, 10 - lru.len() as u64);
+        assert_eq!(lru.shard_stats().len(), 2);
+        assert_eq!(stats.hit_percent(), 9);
+    }
+
+    #[test]
+    fn test_get_or_insert_async_singleflight() {
+        let lru = ShardingLruCache::new(4, 2, RandomState::default()).unwrap();
+        let load_count = AtomicUsize::new(0);
+
+        tokio_test::block_on(async {
+            let vec_value = futures::future::join_all((0..10).map(|_| {
+                lru.get_or_insert_async(1, |key| {
+                    let key = *key;
+                    let _ = load_count.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        Ok(key * 10)
+                    }
+                })
+            })).await;
+
+            assert!(vec_value.into_iter().all(|value| *value.unwrap() == 10));
+        });
+        assert_eq!(load_count.load(Ordering::SeqCst), 1);
+
+        let stats = lru.stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 9);
+        assert!(lru.loading.lock().is_empty());
     }
-}
\ No newline at end of file
+}
@@ -1 +1,30 @@
-pub mod lru_cache;
\ No newline at end of file
+pub mod lru_cache;
+pub mod keys;
+pub(crate) mod latch;
+pub(crate) mod lock_file;
+pub(crate) mod rdb;
+pub(crate) mod rng;
+pub mod runtime;
+pub mod clock;
+pub mod quota;
+
+/// 内部不变量检查
+///
+/// 开启`invariant_check`特性时，不满足条件则返回附带上下文的`KernelError::Internal`，
+/// 否则仅在Debug构建下进行断言，避免Release构建下的额外开销
+macro_rules! invariant {
+    ($cond:expr, $($arg:tt)+) => {
+        #[cfg(feature = "invariant_check")]
+        {
+            if !$cond {
+                return Err($crate::KernelError::Internal(format!($($arg)+)).into());
+            }
+        }
+        #[cfg(not(feature = "invariant_check"))]
+        {
+            debug_assert!($cond, $($arg)+);
+        }
+    };
+}
+
+pub(crate) use invariant;
@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+/// 请求配额
+///
+/// 以每秒请求数与每秒字节数进行限制，为None或0时不限制
+///
+/// 用于服务端命名空间的请求配额(字节数为请求与响应的bytes长度之和)
+/// 与列族的写入配额(字节数为写入的Key与Value的长度之和)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub(crate) requests_per_sec: Option<u64>,
+    pub(crate) bytes_per_sec: Option<u64>,
+}
+
+impl Quota {
+    #[inline]
+    pub fn requests_per_sec(mut self, requests_per_sec: u64) -> Self {
+        self.requests_per_sec = Some(requests_per_sec);
+        self
+    }
+
+    #[inline]
+    pub fn bytes_per_sec(mut self, bytes_per_sec: u64) -> Self {
+        self.bytes_per_sec = Some(bytes_per_sec);
+        self
+    }
+}
+
+/// 每个令牌的精度单位，令牌以纳秒级的精度进行补充
+const TOKEN_UNIT: i128 = 1_000_000_000;
+
+/// 令牌桶
+///
+/// 容量为一秒的配额，令牌允许透支，
+/// 因此超出容量的单次请求可以被处理，但其后的请求需等待透支的令牌补足
+struct TokenBucket {
+    /// 每秒补充的令牌数
+    rate: i128,
+    /// 当前令牌数，以TOKEN_UNIT为单位
+    tokens: i128,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        let rate = i128::from(rate);
+
+        TokenBucket {
+            rate,
+            tokens: rate * TOKEN_UNIT,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed_nanos = now.saturating_duration_since(self.last_refill).as_nanos() as i128;
+        self.tokens = (self.tokens + elapsed_nanos * self.rate).min(self.rate * TOKEN_UNIT);
+        self.last_refill = now;
+    }
+
+    /// 令牌补足至一个前需等待的时长，无需等待时为None
+    fn wait_time(&mut self, now: Instant) -> Option<Duration> {
+        self.refill(now);
+
+        (self.tokens < TOKEN_UNIT).then(|| {
+            let wait_nanos = (TOKEN_UNIT - self.tokens) / self.rate;
+            Duration::from_nanos(u64::try_from(wait_nanos).unwrap_or(u64::MAX))
+        })
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.tokens -= amount as i128 * TOKEN_UNIT;
+    }
+}
+
+/// 配额限制器
+pub(crate) struct QuotaLimiter {
+    requests: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+impl QuotaLimiter {
+    pub(crate) fn new(quota: Quota) -> Self {
+        Self::with_time(quota, Instant::now())
+    }
+
+    fn with_time(quota: Quota, now: Instant) -> Self {
+        QuotaLimiter {
+            requests: quota.requests_per_sec
+                .filter(|rate| *rate > 0)
+                .map(|rate| TokenBucket::new(rate, now)),
+            bytes: quota.bytes_per_sec
+                .filter(|rate| *rate > 0)
+                .map(|rate| TokenBucket::new(rate, now)),
+        }
+    }
+
+    /// 处理下一个请求前需等待的时长，无需等待时为None
+    pub(crate) fn wait_time(&mut self) -> Option<Duration> {
+        self.wait_time_at(Instant::now())
+    }
+
+    fn wait_time_at(&mut self, now: Instant) -> Option<Duration> {
+        self.requests.iter_mut()
+            .chain(self.bytes.iter_mut())
+            .filter_map(|bucket| bucket.wait_time(now))
+            .max()
+    }
+
+    /// 记录一次请求
+    pub(crate) fn consume_request(&mut self, bytes_len: usize) {
+        self.consume_requests(1, bytes_len);
+    }
+
+    /// 记录多次请求
+    pub(crate) fn consume_requests(&mut self, requests: usize, bytes_len: usize) {
+        if let Some(bucket) = &mut self.requests {
+            bucket.consume(requests);
+        }
+        self.consume_bytes(bytes_len);
+    }
+
+    /// 记录传输的字节数
+    pub(crate) fn consume_bytes(&mut self, bytes_len: usize) {
+        if let Some(bucket) = &mut self.bytes {
+            bucket.consume(bytes_len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::kernel::utils::quota::{Quota, QuotaLimiter};
+
+    #[test]
+    fn test_quota_limiter() {
+        let now = Instant::now();
+        let mut limiter = QuotaLimiter::with_time(
+            Quota::default().requests_per_sec(2).bytes_per_sec(100),
+            now
+        );
+
+        assert_eq!(limiter.wait_time_at(now), None);
+        limiter.consume_request(10);
+        limiter.consume_request(10);
+        // 请求数耗尽
+        assert!(limiter.wait_time_at(now).is_some());
+        // 半秒后恢复一个请求
+        assert_eq!(limiter.wait_time_at(now + Duration::from_millis(500)), None);
+
+        // 透支字节数后需等待其补足
+        limiter.consume_bytes(200);
+        let wait_time = limiter.wait_time_at(now + Duration::from_millis(500));
+        assert!(wait_time > Some(Duration::from_secs(1)));
+        assert_eq!(limiter.wait_time_at(now + Duration::from_secs(3)), None);
+    }
+}
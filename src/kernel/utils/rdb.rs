@@ -0,0 +1,342 @@
+use std::io::{ErrorKind, Read};
+use crate::kernel::Result;
+use crate::KernelError;
+
+const RDB_MAGIC: &[u8] = b"REDIS";
+
+/// 支持的最高RDB版本(Redis 7.4)
+const RDB_MAX_VERSION: u32 = 12;
+
+const OPCODE_SLOT_INFO: u8 = 0xF4;
+const OPCODE_FUNCTION2: u8 = 0xF5;
+const OPCODE_FUNCTION: u8 = 0xF6;
+const OPCODE_MODULE_AUX: u8 = 0xF7;
+const OPCODE_IDLE: u8 = 0xF8;
+const OPCODE_FREQ: u8 = 0xF9;
+const OPCODE_AUX: u8 = 0xFA;
+const OPCODE_RESIZEDB: u8 = 0xFB;
+const OPCODE_EXPIRETIME_MS: u8 = 0xFC;
+const OPCODE_EXPIRETIME: u8 = 0xFD;
+const OPCODE_SELECTDB: u8 = 0xFE;
+const OPCODE_EOF: u8 = 0xFF;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+
+/// 以单个字符串存储的紧凑编码类型(zipmap、ziplist、intset与listpack)
+const TYPES_SINGLE_BLOB: [u8; 8] = [9, 10, 11, 12, 13, 16, 17, 20];
+
+const ENC_INT8: u8 = 0;
+const ENC_INT16: u8 = 1;
+const ENC_INT32: u8 = 2;
+const ENC_LZF: u8 = 3;
+
+/// RDB中的字符串类型键值对
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RdbEntry {
+    pub(crate) db: u64,
+    pub(crate) key: Vec<u8>,
+    pub(crate) value: Vec<u8>,
+    /// 过期时间的Unix时间戳(毫秒)
+    pub(crate) expire_ms: Option<i64>,
+}
+
+enum Length {
+    Len(u64),
+    /// 特殊编码的字符串
+    Encoded(u8),
+}
+
+/// Redis RDB快照的流式读取器
+///
+/// 仅返回字符串类型的键值对，其他类型被跳过并计数，Stream与Module类型无法跳过因此返回错误，
+/// 不校验文件末尾的CRC64
+pub(crate) struct RdbReader<R> {
+    reader: R,
+    db: u64,
+    skipped: usize,
+    is_eof: bool,
+}
+
+impl<R: Read> RdbReader<R> {
+    pub(crate) fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0; 9];
+        reader.read_exact(&mut header)?;
+        let (magic, version) = header.split_at(RDB_MAGIC.len());
+        let version = std::str::from_utf8(version).ok()
+            .and_then(|version| version.parse::<u32>().ok())
+            .ok_or_else(|| invalid("invalid header"))?;
+
+        if magic != RDB_MAGIC {
+            return Err(invalid("invalid magic"));
+        }
+        if version > RDB_MAX_VERSION {
+            return Err(invalid(format!("unsupported version {version}")));
+        }
+        Ok(RdbReader { reader, db: 0, skipped: 0, is_eof: false })
+    }
+
+    /// 被跳过的非字符串类型的Key数量
+    pub(crate) fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// 读取下一个字符串类型的键值对，读取至文件末尾时返回None
+    pub(crate) fn next_entry(&mut self) -> Result<Option<RdbEntry>> {
+        let mut expire_ms = None;
+
+        while !self.is_eof {
+            match self.read_u8()? {
+                OPCODE_EOF => self.is_eof = true,
+                OPCODE_SELECTDB => self.db = self.read_plain_length()?,
+                OPCODE_RESIZEDB => {
+                    let _ = self.read_plain_length()?;
+                    let _ = self.read_plain_length()?;
+                }
+                OPCODE_SLOT_INFO => {
+                    for _ in 0..3 {
+                        let _ = self.read_plain_length()?;
+                    }
+                }
+                OPCODE_AUX => {
+                    let _ = self.read_string()?;
+                    let _ = self.read_string()?;
+                }
+                OPCODE_EXPIRETIME_MS => {
+                    expire_ms = Some(i64::from_le_bytes(self.read_array()?));
+                }
+                OPCODE_EXPIRETIME => {
+                    expire_ms = Some(i64::from(u32::from_le_bytes(self.read_array()?)) * 1000);
+                }
+                OPCODE_FREQ => {
+                    let _ = self.read_u8()?;
+                }
+                OPCODE_IDLE => {
+                    let _ = self.read_plain_length()?;
+                }
+                OPCODE_FUNCTION | OPCODE_FUNCTION2 => {
+                    let _ = self.read_string()?;
+                }
+                OPCODE_MODULE_AUX => return Err(invalid("module aux data is not supported")),
+                value_type => {
+                    let key = self.read_string()?;
+
+                    if value_type == TYPE_STRING {
+                        let value = self.read_string()?;
+                        return Ok(Some(RdbEntry { db: self.db, key, value, expire_ms }));
+                    }
+                    self.skip_value(value_type)?;
+                    self.skipped += 1;
+                    expire_ms = None;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn skip_value(&mut self, value_type: u8) -> Result<()> {
+        match value_type {
+            TYPE_LIST | TYPE_SET | TYPE_LIST_QUICKLIST => {
+                for _ in 0..self.read_plain_length()? {
+                    let _ = self.read_string()?;
+                }
+            }
+            TYPE_HASH => {
+                for _ in 0..self.read_plain_length()? * 2 {
+                    let _ = self.read_string()?;
+                }
+            }
+            TYPE_ZSET => {
+                for _ in 0..self.read_plain_length()? {
+                    let _ = self.read_string()?;
+                    // 以字符串存储的分值，253-255表示NaN与正负无穷
+                    let len = self.read_u8()?;
+                    if len < 253 {
+                        let _ = self.read_bytes(len as usize)?;
+                    }
+                }
+            }
+            TYPE_ZSET_2 => {
+                for _ in 0..self.read_plain_length()? {
+                    let _ = self.read_string()?;
+                    let _ = self.read_array::<8>()?;
+                }
+            }
+            TYPE_LIST_QUICKLIST_2 => {
+                for _ in 0..self.read_plain_length()? {
+                    let _ = self.read_plain_length()?;
+                    let _ = self.read_string()?;
+                }
+            }
+            value_type if TYPES_SINGLE_BLOB.contains(&value_type) => {
+                let _ = self.read_string()?;
+            }
+            value_type => return Err(invalid(format!("unsupported value type {value_type}"))),
+        }
+
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)
+            .map_err(map_eof)?;
+
+        Ok(bytes)
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let _ = self.reader.by_ref()
+            .take(len as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(invalid("unexpected end of file"));
+        }
+
+        Ok(bytes)
+    }
+
+    fn read_length(&mut self) -> Result<Length> {
+        let first = self.read_u8()?;
+
+        Ok(match first >> 6 {
+            0 => Length::Len(u64::from(first & 0x3F)),
+            1 => Length::Len(u64::from(first & 0x3F) << 8 | u64::from(self.read_u8()?)),
+            2 => match first {
+                0x80 => Length::Len(u64::from(u32::from_be_bytes(self.read_array()?))),
+                0x81 => Length::Len(u64::from_be_bytes(self.read_array()?)),
+                _ => return Err(invalid(format!("invalid length encoding {first:#x}"))),
+            },
+            _ => Length::Encoded(first & 0x3F),
+        })
+    }
+
+    fn read_plain_length(&mut self) -> Result<u64> {
+        match self.read_length()? {
+            Length::Len(len) => Ok(len),
+            Length::Encoded(_) => Err(invalid("unexpected encoded length")),
+        }
+    }
+
+    fn read_string(&mut self) -> Result<Vec<u8>> {
+        match self.read_length()? {
+            Length::Len(len) => self.read_bytes(len as usize),
+            Length::Encoded(ENC_INT8) => Ok((self.read_u8()? as i8).to_string().into_bytes()),
+            Length::Encoded(ENC_INT16) => Ok(i16::from_le_bytes(self.read_array()?).to_string().into_bytes()),
+            Length::Encoded(ENC_INT32) => Ok(i32::from_le_bytes(self.read_array()?).to_string().into_bytes()),
+            Length::Encoded(ENC_LZF) => {
+                let compressed_len = self.read_plain_length()? as usize;
+                let len = self.read_plain_length()? as usize;
+                let compressed = self.read_bytes(compressed_len)?;
+
+                lzf_decompress(&compressed, len)
+            }
+            Length::Encoded(encoding) => Err(invalid(format!("unknown string encoding {encoding}"))),
+        }
+    }
+}
+
+/// LZF解压
+fn lzf_decompress(input: &[u8], len: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(len);
+    let mut pos = 0;
+    let next = |pos: &mut usize| {
+        let byte = input.get(*pos).copied();
+        *pos += 1;
+        byte.ok_or_else(|| invalid("truncated lzf data"))
+    };
+
+    while pos < input.len() {
+        let ctrl = usize::from(next(&mut pos)?);
+
+        if ctrl < 32 {
+            // 长度为ctrl + 1的字面量
+            let literal = input.get(pos..pos + ctrl + 1)
+                .ok_or_else(|| invalid("truncated lzf data"))?;
+            output.extend_from_slice(literal);
+            pos += ctrl + 1;
+        } else {
+            // 对已解压数据的回溯引用
+            let mut ref_len = ctrl >> 5;
+            if ref_len == 7 {
+                ref_len += usize::from(next(&mut pos)?);
+            }
+            let offset = ((ctrl & 0x1F) << 8) + usize::from(next(&mut pos)?) + 1;
+            let start = output.len().checked_sub(offset)
+                .ok_or_else(|| invalid("invalid lzf back reference"))?;
+
+            for i in start..start + ref_len + 2 {
+                output.push(output[i]);
+            }
+        }
+    }
+    if output.len() != len {
+        return Err(invalid("lzf length mismatch"));
+    }
+
+    Ok(output)
+}
+
+fn map_eof(err: std::io::Error) -> KernelError {
+    if err.kind() == ErrorKind::UnexpectedEof {
+        invalid("unexpected end of file")
+    } else {
+        KernelError::Io(err)
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> KernelError {
+    KernelError::InvalidRdb(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::utils::rdb::{RdbEntry, RdbReader};
+    use crate::kernel::Result;
+
+    #[test]
+    fn test_rdb_reader() -> Result<()> {
+        let mut rdb = b"REDIS0011".to_vec();
+        // AUX与SELECTDB、RESIZEDB
+        rdb.extend_from_slice(b"\xFA\x09redis-ver\x057.2.0\xFE\x00\xFB\x04\x01");
+        rdb.extend_from_slice(b"\x00\x02k1\x02v1");
+        // 带有过期时间的整数编码Value
+        rdb.push(0xFC);
+        rdb.extend_from_slice(&1_000i64.to_le_bytes());
+        rdb.extend_from_slice(b"\x00\x02k2\xC0\x2A");
+        // 被跳过的List
+        rdb.extend_from_slice(b"\x01\x04list\x02\x01a\x01b");
+        // LZF压缩的Value: 10个a
+        rdb.extend_from_slice(b"\x00\x02k3\xC3\x05\x0A\x00a\xE0\x00\x00");
+        rdb.extend_from_slice(b"\xFE\x01\x00\x02k4\x02v4\xFF");
+        rdb.extend_from_slice(&[0; 8]);
+
+        let mut reader = RdbReader::new(&rdb[..])?;
+        let entry = |db, key: &[u8], value: &[u8], expire_ms| {
+            Some(RdbEntry { db, key: key.to_vec(), value: value.to_vec(), expire_ms })
+        };
+        assert_eq!(reader.next_entry()?, entry(0, b"k1", b"v1", None));
+        assert_eq!(reader.next_entry()?, entry(0, b"k2", b"42", Some(1_000)));
+        assert_eq!(reader.next_entry()?, entry(0, b"k3", b"aaaaaaaaaa", None));
+        assert_eq!(reader.next_entry()?, entry(1, b"k4", b"v4", None));
+        assert_eq!(reader.next_entry()?, None);
+        assert_eq!(reader.skipped(), 1);
+
+        assert!(RdbReader::new(&b"REDIS0099"[..]).is_err());
+        assert!(RdbReader::new(&rdb[..20]).and_then(|mut reader| reader.next_entry()).is_err());
+
+        Ok(())
+    }
+}
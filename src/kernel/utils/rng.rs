@@ -0,0 +1,27 @@
+/// 以xorshift64实现的伪随机数生成器
+///
+/// 用于确定性调度与压力测试等需要以seed复现的场景，不适用于任何安全相关的用途
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        // 状态为0时将始终生成0
+        XorShift64 { state: seed.max(1) }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        self.state
+    }
+
+    /// 生成[0, bound)中的数，bound需大于0
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
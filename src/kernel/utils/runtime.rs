@@ -0,0 +1,269 @@
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use futures::future::{self, BoxFuture};
+use futures::task::{self as futures_task, ArcWake, AtomicWaker};
+use parking_lot::Mutex;
+use tokio::runtime::Handle;
+use crate::kernel::utils::rng::XorShift64;
+use crate::kernel::Result;
+use crate::KernelError;
+
+type SpawnFn = dyn Fn(BoxFuture<'static, ()>) + Send + Sync;
+
+/// 后台任务派发器
+///
+/// 内核中的Compactor与Cleaner等后台任务均通过此处派发
+/// 默认派发至开启Store时所处的tokio运行时，在不使用tokio运行时的环境(如wasm32-wasi或自定义执行器)下可通过`Spawner::new`替换
+#[derive(Clone)]
+pub struct Spawner {
+    spawn_fn: Arc<SpawnFn>,
+    /// 是否为依赖tokio运行时的默认派发器
+    requires_runtime: bool,
+}
+
+impl Spawner {
+    #[inline]
+    pub fn new<F>(spawn_fn: F) -> Self
+        where F: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static
+    {
+        Spawner { spawn_fn: Arc::new(spawn_fn), requires_runtime: false }
+    }
+
+    /// 检查派发器是否可用，避免默认派发器在没有tokio运行时的环境(如wasm32-wasi的宿主)下派发时Panic
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.requires_runtime && Handle::try_current().is_err() {
+            return Err(KernelError::NotSupport("no tokio runtime for the default spawner, set `Config::spawner` instead"));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn spawn<F>(&self, future: F)
+        where F: Future<Output = ()> + Send + 'static
+    {
+        (self.spawn_fn)(Box::pin(future))
+    }
+}
+
+impl Default for Spawner {
+    #[inline]
+    fn default() -> Self {
+        Spawner {
+            spawn_fn: Arc::new(|future| {
+                let _ignore = tokio::spawn(future);
+            }),
+            requires_runtime: true,
+        }
+    }
+}
+
+impl Debug for Spawner {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Spawner").finish()
+    }
+}
+
+/// 确定性的后台任务执行器，配合`VirtualClock`用于编写可复现的集成测试
+///
+/// 通过`DeterministicExecutor::spawner`派发的任务不会立即执行，而是在`run_until`或`run_until_idle`中于当前线程轮询，
+/// 每次以seed初始化的伪随机数从就绪的任务中选取下一个，因此相同的seed总是得到相同的调度顺序，
+/// 更换seed即可覆盖不同的交错
+///
+/// ```ignore
+/// let executor = DeterministicExecutor::new(42);
+/// let clock = VirtualClock::new(0);
+/// let config = Config::new(path).spawner(executor.spawner()).clock(Arc::new(clock.clone()));
+/// let kv_store = executor.run_until(LsmStore::open_with_config(config)).await?;
+/// executor.run_until(kv_store.flush()).await?;
+/// ```
+///
+/// Tips: 任务所等待的IO、计时器或其他线程就绪后才会重新进入就绪队列，这部分的时机仍由运行时决定
+#[derive(Clone)]
+pub struct DeterministicExecutor {
+    inner: Arc<ExecutorInner>,
+}
+
+struct ExecutorInner {
+    ready: Mutex<Vec<Arc<Task>>>,
+    rng: Mutex<XorShift64>,
+    /// `run_until`所等待的Future，后台任务就绪时将其唤醒
+    waker: AtomicWaker,
+}
+
+struct Task {
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    /// 是否已在就绪队列中，避免重复唤醒时被多次加入
+    is_queued: AtomicBool,
+    executor: Arc<ExecutorInner>,
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if !arc_self.is_queued.swap(true, Ordering::AcqRel) {
+            arc_self.executor.ready.lock().push(Arc::clone(arc_self));
+            arc_self.executor.waker.wake();
+        }
+    }
+}
+
+impl DeterministicExecutor {
+    #[inline]
+    pub fn new(seed: u64) -> Self {
+        DeterministicExecutor {
+            inner: Arc::new(ExecutorInner {
+                ready: Mutex::new(Vec::new()),
+                rng: Mutex::new(XorShift64::new(seed)),
+                waker: AtomicWaker::new(),
+            }),
+        }
+    }
+
+    /// 获取将任务派发至此执行器的Spawner，用于`Config::spawner`
+    #[inline]
+    pub fn spawner(&self) -> Spawner {
+        let inner = Arc::clone(&self.inner);
+
+        Spawner::new(move |future| {
+            let task = Arc::new(Task {
+                future: Mutex::new(Some(future)),
+                is_queued: AtomicBool::new(false),
+                executor: Arc::clone(&inner),
+            });
+            ArcWake::wake(task);
+        })
+    }
+
+    /// 执行就绪的任务，直至没有就绪的任务为止，返回轮询的次数
+    #[inline]
+    pub fn run_until_idle(&self) -> usize {
+        let mut polled = 0;
+
+        while let Some(task) = self.next_task() {
+            task.is_queued.store(false, Ordering::Release);
+            let waker = futures_task::waker_ref(&task);
+            let mut cx = Context::from_waker(&waker);
+            let mut slot = task.future.lock();
+
+            if let Some(mut future) = slot.take() {
+                if future.as_mut().poll(&mut cx).is_pending() {
+                    *slot = Some(future);
+                }
+            }
+            polled += 1;
+        }
+
+        polled
+    }
+
+    /// 驱动future直至其完成，期间交替执行就绪的后台任务
+    ///
+    /// 用于等待依赖后台任务的操作，如`LsmStore::flush`需等待Compactor完成Flush
+    #[inline]
+    pub async fn run_until<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+
+        future::poll_fn(|cx| {
+            self.inner.waker.register(cx.waker());
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                    return Poll::Ready(output);
+                }
+                if self.run_until_idle() == 0 {
+                    return Poll::Pending;
+                }
+            }
+        }).await
+    }
+
+    /// 以伪随机数选取并移出下一个就绪的任务
+    fn next_task(&self) -> Option<Arc<Task>> {
+        let mut ready = self.inner.ready.lock();
+        if ready.is_empty() {
+            return None;
+        }
+        let index = self.inner.rng.lock().below(ready.len() as u64) as usize;
+
+        Some(ready.swap_remove(index))
+    }
+}
+
+impl Debug for DeterministicExecutor {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeterministicExecutor")
+            .field("ready", &self.inner.ready.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Poll;
+    use futures::executor::block_on;
+    use futures::future;
+    use parking_lot::Mutex;
+    use crate::kernel::utils::runtime::{DeterministicExecutor, Spawner};
+    use crate::KernelError;
+
+    #[test]
+    fn test_custom_spawner() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let spawner = Spawner::new(block_on);
+
+        let count_1 = Arc::clone(&count);
+        spawner.spawn(async move {
+            let _ = count_1.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+        assert!(spawner.check().is_ok());
+        // 默认派发器仅在tokio运行时中可用，否则开启Store时返回错误而非于派发时Panic
+        assert!(matches!(Spawner::default().check(), Err(KernelError::NotSupport(_))));
+        tokio_test::block_on(async {
+            assert!(Spawner::default().check().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_deterministic_executor() {
+        let run_with_seed = |seed| {
+            let executor = DeterministicExecutor::new(seed);
+            let spawner = executor.spawner();
+            let order = Arc::new(Mutex::new(Vec::new()));
+
+            for i in 0..8 {
+                let order = Arc::clone(&order);
+                spawner.spawn(async move {
+                    order.lock().push(i);
+                    // 让出一次，使任务重新进入就绪队列
+                    let mut is_yielded = false;
+                    future::poll_fn(|cx| {
+                        if mem::replace(&mut is_yielded, true) {
+                            return Poll::Ready(());
+                        }
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }).await;
+                    order.lock().push(i + 8);
+                });
+            }
+            // 派发时不会立即执行
+            assert!(order.lock().is_empty());
+            assert!(executor.run_until_idle() >= 16);
+
+            let order = order.lock().clone();
+            order
+        };
+
+        assert_eq!(run_with_seed(7), run_with_seed(7));
+        assert_ne!(run_with_seed(7), run_with_seed(8));
+    }
+}
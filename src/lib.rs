@@ -97,9 +97,17 @@ extern crate core;
 pub mod kernel;
 pub mod error;
 pub mod config;
+#[cfg(feature = "net")]
 pub mod net;
+#[cfg(feature = "net")]
 pub mod cmd;
 pub mod proto;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "stress")]
+pub mod stress;
 
 pub use crate::kernel::hash_kv::HashStore;
 pub use error::KernelError;
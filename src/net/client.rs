@@ -1,59 +1,218 @@
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
 use itertools::Itertools;
 use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::time;
 use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use crate::error::ConnectionError;
 use crate::kernel::{ByteUtils, CommandData};
+use crate::kernel::lsm::options::Ack;
 use crate::KernelError;
 use crate::net::connection::Connection;
-use crate::net::{kv_encode_with_len, option_from_key_value, Result};
-use crate::proto::net_pb::{CommandOption, KeyValue};
+use crate::net::interceptor::{Interceptor, ResponseAction};
+use crate::net::priority::Priority;
+use crate::net::typed::{BincodeCodec, TypedClient, ValueCodec};
+use crate::net::{batch_chunk_from_option, BatchItem, COMPRESSION_THRESHOLD, FEATURE_COMPRESSION, FEATURE_LENGTH_DELIMITED, handshake_from_option, kv_encode_with_len, option_from_handshake, option_from_conditional_write, option_from_key_value, option_from_lease_command, option_from_struct_command, PROTOCOL_VERSION, rejection_from_option, Result, scan_page_from_option, ServerInfo, set_option_ack, struct_reply_from_option, SUPPORTED_FEATURES, TraceContext, watch_item_from_option, WatchItem};
+use crate::proto::net_pb::{CommandOption, ConditionalOp, ConditionalWrite, Handshake, KeyValue, LeaseCommand, LeaseOp, OptionType, ScanPage, SelectNamespace, StructCommand, StructOp, StructReply};
+
+/// 批量导入时每个分块的大小
+const BULK_LOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// 等待服务端握手响应的时长，超时则视为不支持握手的旧服务端
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[allow(missing_debug_implementations)]
 pub struct Client {
-    connection: Connection
+    connection: Connection,
+    /// 握手协商的协议版本，服务端不支持握手时为0
+    protocol_version: u32,
+    /// 握手协商启用的功能
+    features: u64,
+    /// 请求所携带的链路上下文
+    trace_context: Option<TraceContext>,
+    /// 请求的优先级
+    priority: Priority,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    /// 该连接写入响应中最大的Sequence
+    last_sequence: i64,
 }
 
 impl Client {
     /// 与客户端进行连接
+    ///
+    /// 连接后会与服务端交换协议版本与功能标识，
+    /// 旧版本的服务端不会响应握手，此时以版本0继续使用既有的指令
     #[inline]
     pub async fn connect<T: ToSocketAddrs>(addr: T) -> Result<Client> {
         let socket = TcpStream::connect(addr).await?;
 
-        let connection = Connection::new(socket);
+        let mut client = Client {
+            connection: Connection::new(socket),
+            protocol_version: 0,
+            features: 0,
+            trace_context: None,
+            priority: Priority::Foreground,
+            interceptors: Vec::new(),
+            last_sequence: 0,
+        };
+        client.handshake().await?;
 
-        Ok(Client{
-            connection
-        })
+        Ok(client)
+    }
+
+    /// 与客户端进行连接，并选择所要使用的命名空间
+    #[inline]
+    pub async fn connect_with_namespace<T: ToSocketAddrs>(
+        addr: T,
+        namespace: &str,
+        token: Option<&str>
+    ) -> Result<Client> {
+        let mut client = Self::connect(addr).await?;
+        client.select(namespace, token).await?;
+
+        Ok(client)
+    }
+
+    async fn handshake(&mut self) -> Result<()> {
+        let handshake = Handshake {
+            version: PROTOCOL_VERSION,
+            features: SUPPORTED_FEATURES,
+        };
+        self.connection.write(option_from_handshake(&handshake)?).await?;
+
+        if let Ok(result_option) = time::timeout(HANDSHAKE_TIMEOUT, self.connection.read()).await {
+            let negotiated = handshake_from_option(&result_option?)?;
+            self.protocol_version = negotiated.version;
+            self.features = negotiated.features;
+        }
+        if self.is_feature_enabled(FEATURE_COMPRESSION) {
+            self.connection.enable_compression(COMPRESSION_THRESHOLD);
+        }
+        if self.is_feature_enabled(FEATURE_LENGTH_DELIMITED) {
+            self.connection.enable_length_delimited();
+        }
+
+        Ok(())
+    }
+
+    /// 设置后续请求所携带的链路上下文，为None时不再携带
+    #[inline]
+    pub fn set_trace_context(&mut self, trace_context: Option<TraceContext>) {
+        self.trace_context = trace_context;
+    }
+
+    /// 设置此后请求的优先级，备份、导出等任务可设置为`Priority::Background`以减少对业务请求的影响
+    #[inline]
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    /// 添加拦截器
+    #[inline]
+    pub fn add_interceptor(&mut self, interceptor: impl Interceptor + 'static) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
+    /// 握手协商的协议版本
+    #[inline]
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// 该功能是否在握手中被双方启用
+    #[inline]
+    pub fn is_feature_enabled(&self, feature: u64) -> bool {
+        self.features & feature == feature
     }
 
     /// 存入数据
     #[inline]
     pub async fn set(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()>{
-        let key_value = KeyValue { key, value, r#type: 1 };
-        
-        let _ignore = self.send_cmd(option_from_key_value(&key_value)?).await?;
+        let _ = self.set_with_sequence(key, value).await?;
         Ok(())
     }
 
+    /// 存入数据，并返回此次写入的Sequence
+    ///
+    /// 旧版本的服务端不返回Sequence，此时为0
+    #[inline]
+    pub async fn set_with_sequence(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<i64>{
+        self.set_with_ack(key, value, Ack::Wal).await
+    }
+
+    /// 以确认级别存入数据，并返回此次写入的Sequence
+    ///
+    /// 旧版本的服务端忽略确认级别，总是以`Ack::Wal`写入
+    #[inline]
+    pub async fn set_with_ack(&mut self, key: Vec<u8>, value: Vec<u8>, ack: Ack) -> Result<i64>{
+        let key_value = KeyValue { key, value, r#type: 1 };
+        let mut option = option_from_key_value(&key_value)?;
+        set_option_ack(&mut option, ack);
+
+        self.write_with_sequence(option).await
+    }
+
     /// 删除数据
     #[inline]
     pub async fn remove(&mut self, key: Vec<u8>) -> Result<()>{
-        let key_value = KeyValue { key, value: vec![], r#type: 2 };
-        
-        let _ignore = self.send_cmd(option_from_key_value(&key_value)?).await?;
+        let _ = self.remove_with_sequence(key).await?;
         Ok(())
     }
 
+    /// 删除数据，并返回此次删除的Sequence
+    #[inline]
+    pub async fn remove_with_sequence(&mut self, key: Vec<u8>) -> Result<i64>{
+        self.remove_with_ack(key, Ack::Wal).await
+    }
+
+    /// 以确认级别删除数据，并返回此次删除的Sequence
+    #[inline]
+    pub async fn remove_with_ack(&mut self, key: Vec<u8>, ack: Ack) -> Result<i64>{
+        let key_value = KeyValue { key, value: vec![], r#type: 2 };
+        let mut option = option_from_key_value(&key_value)?;
+        set_option_ack(&mut option, ack);
+
+        self.write_with_sequence(option).await
+    }
+
     /// 获取数据
     #[inline]
     pub async fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>>{
+        self.get_with_min_sequence(key, 0).await
+    }
+
+    /// 获取数据，要求服务端已应用min_sequence
+    ///
+    /// 以写入返回的Sequence(或`Client::last_sequence`)作为令牌，
+    /// 即使读取由其他副本处理也可以读到该写入，
+    /// 服务端在等待时长内未应用该Sequence时返回`ConnectionError::Stale`
+    #[inline]
+    pub async fn get_with_min_sequence(&mut self, key: Vec<u8>, min_sequence: i64) -> Result<Option<Vec<u8>>>{
         let key_value = KeyValue { key, value: vec![], r#type: 0 };
+        let mut option = option_from_key_value(&key_value)?;
+        option.sequence = min_sequence;
 
-        Ok(self.send_cmd(option_from_key_value(&key_value)?)
+        Ok(self.send_cmd(option)
             .await?
             .into())
     }
 
+    /// 该连接写入响应中最大的Sequence，可作为会话一致性读取的令牌
+    #[inline]
+    pub fn last_sequence(&self) -> i64 {
+        self.last_sequence
+    }
+
+    async fn write_with_sequence(&mut self, option: CommandOption) -> Result<i64> {
+        let sequence = self.send_cmd(option).await?.sequence;
+        self.last_sequence = self.last_sequence.max(sequence);
+
+        Ok(sequence)
+    }
+
     /// 刷入硬盘
     #[inline]
     pub async fn flush(&mut self) -> Result<()>{
@@ -61,6 +220,11 @@ impl Client {
             r#type: 6,
             bytes: vec![],
             value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
         };
 
         if self.send_cmd(option).await?.r#type == 6 {
@@ -68,6 +232,24 @@ impl Client {
         } else { Err(ConnectionError::FlushError) }
     }
 
+    /// 以指定的编解码方式读写Value
+    #[inline]
+    pub fn typed<C: ValueCodec>(&mut self) -> TypedClient<'_, C> {
+        TypedClient::new(self)
+    }
+
+    /// 获取数据并以bincode解码
+    #[inline]
+    pub async fn get_as<T: DeserializeOwned>(&mut self, key: impl Into<Vec<u8>>) -> Result<Option<T>> {
+        self.typed::<BincodeCodec>().get(key).await
+    }
+
+    /// 以bincode编码并存入数据
+    #[inline]
+    pub async fn set_value<T: Serialize + ?Sized>(&mut self, key: impl Into<Vec<u8>>, value: &T) -> Result<()> {
+        self.typed::<BincodeCodec>().set(key, value).await
+    }
+
     /// 批量处理
     #[inline]
     pub async fn batch(&mut self, batch_cmd: Vec<CommandData>) -> Result<Vec<Option<Vec<u8>>>>{
@@ -84,6 +266,11 @@ impl Client {
             r#type: 1,
             bytes,
             value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
         };
 
         let result_option = self.send_cmd(send_option).await?;
@@ -101,6 +288,109 @@ impl Client {
         }
     }
 
+    /// 流式批量处理
+    ///
+    /// 服务端逐段执行并响应，各指令的结果按请求中的顺序以下标传入on_item，
+    /// 单个指令的失败不会中断其他指令，返回失败的指令数量
+    ///
+    /// Tips: 与`batch`相同，同一段内的指令并行执行，因此同一Key的多次写入之间不保证顺序
+    #[inline]
+    pub async fn batch_stream<F>(&mut self, batch_cmd: Vec<CommandData>, mut on_item: F) -> Result<usize>
+        where F: FnMut(usize, BatchItem)
+    {
+        let bytes = batch_cmd
+            .into_iter()
+            .map(KeyValue::from)
+            .filter_map(|key_value|
+                kv_encode_with_len(&key_value).ok())
+            .flatten()
+            .collect_vec();
+        let mut send_option = CommandOption {
+            r#type: 19,
+            bytes,
+            value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+        if let Some(trace_context) = &self.trace_context {
+            send_option.trace_context = trace_context.to_traceparent();
+        }
+        send_option.background = self.priority == Priority::Background;
+        // 响应分多次返回，因此不经过拦截器的重试流程
+        self.connection.write(send_option).await?;
+
+        let mut index = 0;
+        let mut failed = 0;
+        loop {
+            let result_option = self.connection.read().await?;
+            if result_option.r#type == OptionType::Throttled as i32 {
+                return Err(ConnectionError::Throttled(result_option.value));
+            }
+            for result in batch_chunk_from_option(&result_option)?.results {
+                let item = BatchItem::from(result);
+                if matches!(item, BatchItem::Failed(_)) {
+                    failed += 1;
+                }
+                on_item(index, item);
+                index += 1;
+            }
+            if result_option.value == 1 {
+                return Ok(failed);
+            }
+        }
+    }
+
+    /// 批量导入
+    ///
+    /// 数据需以Key严格升序排列，会按BULK_LOAD_CHUNK_SIZE分块发送，
+    /// 由服务端直接构建为SSTable导入，跳过MemTable与WAL，返回导入的数量
+    #[inline]
+    pub async fn bulk_load(
+        &mut self,
+        pairs: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>
+    ) -> Result<usize> {
+        let mut count = 0;
+        let mut is_first = true;
+        let mut bytes = Vec::new();
+
+        for (key, value) in pairs {
+            bytes.append(&mut kv_encode_with_len(&KeyValue { key, value, r#type: 1 })?);
+
+            if bytes.len() >= BULK_LOAD_CHUNK_SIZE {
+                count += self.bulk_load_chunk(mem::take(&mut bytes), is_first).await?;
+                is_first = false;
+            }
+        }
+        if !bytes.is_empty() {
+            count += self.bulk_load_chunk(bytes, is_first).await?;
+        }
+
+        Ok(count)
+    }
+
+    async fn bulk_load_chunk(&mut self, bytes: Vec<u8>, is_first: bool) -> Result<usize> {
+        let send_option = CommandOption {
+            r#type: 14,
+            bytes,
+            value: u64::from(is_first),
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+        let result_option = self.send_cmd(send_option).await?;
+
+        if result_option.r#type == 14 {
+            Ok(result_option.value as usize)
+        } else {
+            Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+        }
+    }
+
     /// 磁盘占用
     #[inline]
     pub async fn size_of_disk(&mut self) -> Result<u64> {
@@ -114,6 +404,324 @@ impl Client {
             .map(|len| len as usize)
     }
 
+    /// 数值原子自增
+    #[inline]
+    pub async fn increment(&mut self, key: Vec<u8>, delta: i64) -> Result<i64> {
+        let send_option = CommandOption {
+            r#type: OptionType::Incr as i32,
+            bytes: key,
+            value: delta as u64,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+
+        let result_option = self.send_cmd(send_option).await?;
+
+        if result_option.r#type == 8 {
+            Ok(result_option.value as i64)
+        } else {
+            Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+        }
+    }
+
+    /// Key不存在时写入，返回值表示是否写入成功
+    #[inline]
+    pub async fn set_if_absent(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<bool> {
+        self.conditional_write(ConditionalOp::SetIfAbsent, key, vec![], value).await
+    }
+
+    /// Key对应的Value与expected一致时写入，返回值表示是否写入成功
+    #[inline]
+    pub async fn set_if_equals(&mut self, key: Vec<u8>, expected: Vec<u8>, value: Vec<u8>) -> Result<bool> {
+        self.conditional_write(ConditionalOp::SetIfEquals, key, expected, value).await
+    }
+
+    /// Key对应的Value与expected一致时删除，返回值表示是否删除成功
+    #[inline]
+    pub async fn delete_if_equals(&mut self, key: Vec<u8>, expected: Vec<u8>) -> Result<bool> {
+        self.conditional_write(ConditionalOp::DeleteIfEquals, key, expected, vec![]).await
+    }
+
+    /// 条件写入通用流程，由服务端以`LsmStore::compare_and_swap`原子性地完成比较与写入
+    async fn conditional_write(
+        &mut self,
+        op: ConditionalOp,
+        key: Vec<u8>,
+        expected: Vec<u8>,
+        value: Vec<u8>
+    ) -> Result<bool> {
+        let conditional_write = ConditionalWrite { op: op as i32, key, expected, value };
+        let result_option = self.send_cmd(option_from_conditional_write(&conditional_write)?).await?;
+
+        if result_option.r#type == 18 {
+            Ok(result_option.value == 1)
+        } else {
+            Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+        }
+    }
+
+    /// 计数器原子自增，返回自增后的数值，详见`Structures::counter_incr`
+    #[inline]
+    pub async fn counter_incr(&mut self, name: Vec<u8>, delta: i64) -> Result<i64> {
+        self.struct_cmd(StructOp::CounterIncr, name, |command| command.delta = delta).await
+            .map(|reply| reply.value)
+    }
+
+    /// 获取计数器的数值，计数器不存在时返回0
+    #[inline]
+    pub async fn counter_get(&mut self, name: Vec<u8>) -> Result<i64> {
+        self.struct_cmd(StructOp::CounterGet, name, |_| ()).await
+            .map(|reply| reply.value)
+    }
+
+    /// 向集合中添加成员，返回此前不存在的成员数量
+    #[inline]
+    pub async fn set_add(&mut self, name: Vec<u8>, members: Vec<Vec<u8>>) -> Result<usize> {
+        self.struct_cmd(StructOp::SetAdd, name, |command| command.items = members).await
+            .map(|reply| reply.value as usize)
+    }
+
+    /// 从集合中删除成员，返回实际被删除的成员数量
+    #[inline]
+    pub async fn set_remove(&mut self, name: Vec<u8>, members: Vec<Vec<u8>>) -> Result<usize> {
+        self.struct_cmd(StructOp::SetRemove, name, |command| command.items = members).await
+            .map(|reply| reply.value as usize)
+    }
+
+    /// 成员是否存在于集合中
+    #[inline]
+    pub async fn set_contains(&mut self, name: Vec<u8>, member: Vec<u8>) -> Result<bool> {
+        self.struct_cmd(StructOp::SetContains, name, |command| command.items = vec![member]).await
+            .map(|reply| reply.value != 0)
+    }
+
+    /// 以字节序升序返回集合中的所有成员
+    #[inline]
+    pub async fn set_members(&mut self, name: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        self.struct_cmd(StructOp::SetMembers, name, |_| ()).await
+            .map(|reply| reply.items)
+    }
+
+    /// 向列表末尾追加元素，并返回追加后的列表长度
+    #[inline]
+    pub async fn list_append(&mut self, name: Vec<u8>, items: Vec<Vec<u8>>) -> Result<u64> {
+        self.struct_cmd(StructOp::ListAppend, name, |command| command.items = items).await
+            .map(|reply| reply.value as u64)
+    }
+
+    /// 获取列表长度，列表不存在时返回0
+    #[inline]
+    pub async fn list_len(&mut self, name: Vec<u8>) -> Result<u64> {
+        self.struct_cmd(StructOp::ListLen, name, |_| ()).await
+            .map(|reply| reply.value as u64)
+    }
+
+    /// 获取列表中下标位于[start, stop]的元素，负数的下标表示由末尾倒数
+    #[inline]
+    pub async fn list_range(&mut self, name: Vec<u8>, start: i64, stop: i64) -> Result<Vec<Vec<u8>>> {
+        self.struct_cmd(StructOp::ListRange, name, |command| {
+            command.start = start;
+            command.stop = stop;
+        }).await
+            .map(|reply| reply.items)
+    }
+
+    /// 数据结构指令通用流程
+    async fn struct_cmd<F>(&mut self, op: StructOp, name: Vec<u8>, fn_fill: F) -> Result<StructReply>
+        where F: FnOnce(&mut StructCommand)
+    {
+        let mut struct_command = StructCommand { op: op as i32, name, ..StructCommand::default() };
+        fn_fill(&mut struct_command);
+
+        struct_reply_from_option(
+            &self.send_cmd(option_from_struct_command(&struct_command)?).await?
+        )
+    }
+
+    /// 创建TTL为ttl的租约，返回租约ID，详见`Leases`
+    #[inline]
+    pub async fn lease_grant(&mut self, ttl: Duration) -> Result<i64> {
+        self.lease_cmd(LeaseOp::Grant, 0, vec![], ttl).await
+            .map(|id| id as i64)
+    }
+
+    /// 续约，返回false时表示租约已过期或不存在
+    #[inline]
+    pub async fn lease_keep_alive(&mut self, id: i64) -> Result<bool> {
+        self.lease_cmd(LeaseOp::KeepAlive, id, vec![], Duration::ZERO).await
+            .map(|value| value == 1)
+    }
+
+    /// 撤销租约，其持有的锁随之失效
+    #[inline]
+    pub async fn lease_revoke(&mut self, id: i64) -> Result<()> {
+        let _ = self.lease_cmd(LeaseOp::Revoke, id, vec![], Duration::ZERO).await?;
+        Ok(())
+    }
+
+    /// 租约的剩余时间，租约已过期或不存在时返回None
+    #[inline]
+    pub async fn lease_time_to_live(&mut self, id: i64) -> Result<Option<Duration>> {
+        self.lease_cmd(LeaseOp::TimeToLive, id, vec![], Duration::ZERO).await
+            .map(|millis| (millis > 0).then(|| Duration::from_millis(millis)))
+    }
+
+    /// 尝试以租约获取锁，返回false时表示锁已被其他有效的租约持有
+    #[inline]
+    pub async fn try_lock(&mut self, name: Vec<u8>, lease_id: i64) -> Result<bool> {
+        match self.lease_cmd(LeaseOp::TryLock, lease_id, name, Duration::ZERO).await? {
+            2 => Err(ConnectionError::StoreErr(KernelError::LeaseExpired(lease_id))),
+            value => Ok(value == 1),
+        }
+    }
+
+    /// 以租约获取锁，锁被其他租约持有时以retry_interval为间隔重试至获取成功
+    #[inline]
+    pub async fn lock(&mut self, name: Vec<u8>, lease_id: i64, retry_interval: Duration) -> Result<()> {
+        while !self.try_lock(name.clone(), lease_id).await? {
+            time::sleep(retry_interval).await;
+        }
+        Ok(())
+    }
+
+    /// 释放租约持有的锁，返回false时表示该锁并非由此租约持有
+    #[inline]
+    pub async fn unlock(&mut self, name: Vec<u8>, lease_id: i64) -> Result<bool> {
+        self.lease_cmd(LeaseOp::Unlock, lease_id, name, Duration::ZERO).await
+            .map(|value| value == 1)
+    }
+
+    /// 持有锁的有效租约，锁未被持有时返回None
+    #[inline]
+    pub async fn lock_holder(&mut self, name: Vec<u8>) -> Result<Option<i64>> {
+        self.lease_cmd(LeaseOp::LockHolder, 0, name, Duration::ZERO).await
+            .map(|holder| (holder != 0).then_some(holder as i64))
+    }
+
+    /// 租约与锁的指令通用流程
+    async fn lease_cmd(&mut self, op: LeaseOp, id: i64, name: Vec<u8>, ttl: Duration) -> Result<u64> {
+        let lease_command = LeaseCommand { op: op as i32, id, ttl_ms: ttl.as_millis() as u64, name };
+        let result_option = self.send_cmd(option_from_lease_command(&lease_command)?).await?;
+
+        if result_option.r#type == 17 {
+            Ok(result_option.value)
+        } else {
+            Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+        }
+    }
+
+    /// 订阅当前命名空间的Key空间通知，详见`LsmStore::subscribe_keyspace`
+    ///
+    /// 此后该连接仅用于接收通知，读写需另建连接，丢弃返回的`KeyspaceWatcher`即断开连接
+    #[inline]
+    pub async fn watch(mut self) -> Result<KeyspaceWatcher> {
+        let mut send_option = CommandOption {
+            r#type: OptionType::Watch as i32,
+            bytes: vec![],
+            value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+        if let Some(trace_context) = &self.trace_context {
+            send_option.trace_context = trace_context.to_traceparent();
+        }
+        self.connection.write(send_option).await?;
+
+        Ok(KeyspaceWatcher { connection: self.connection })
+    }
+
+    /// 获取服务端信息
+    #[inline]
+    pub async fn info(&mut self) -> Result<ServerInfo> {
+        let send_option = CommandOption {
+            r#type: 10,
+            bytes: vec![],
+            value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+
+        let result_option = self.send_cmd(send_option).await?;
+
+        if result_option.r#type == 10 {
+            serde_json::from_slice(&result_option.bytes)
+                .map_err(|_| ConnectionError::DecodeErr)
+        } else {
+            Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+        }
+    }
+
+    /// 切换当前连接所使用的命名空间
+    ///
+    /// 命名空间配置了访问令牌时需提供匹配的令牌，被拒绝时保持原命名空间不变
+    #[inline]
+    pub async fn select(&mut self, namespace: &str, token: Option<&str>) -> Result<()> {
+        let select_namespace = SelectNamespace {
+            name: namespace.to_owned(),
+            token: token.unwrap_or_default().to_owned(),
+        };
+        let mut bytes = vec![];
+        select_namespace.encode(&mut bytes)
+            .map_err(|_| ConnectionError::EncodeErr)?;
+
+        let send_option = CommandOption {
+            r#type: 12,
+            bytes,
+            value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+        let result_option = self.send_cmd(send_option).await?;
+
+        match (result_option.r#type, result_option.value) {
+            (12, 1) => Ok(()),
+            (12, _) => Err(ConnectionError::PermissionDenied),
+            _ => Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+        }
+    }
+
+    /// 分页扫描
+    ///
+    /// cursor为上一页返回的游标(首页为None)，返回的游标为None时表示数据已读尽
+    #[inline]
+    pub async fn scan(
+        &mut self,
+        cursor: Option<Vec<u8>>,
+        limit: usize
+    ) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>)> {
+        let send_option = CommandOption {
+            r#type: 11,
+            bytes: cursor.unwrap_or_default(),
+            value: limit as u64,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
+        };
+
+        let ScanPage { items, cursor: next_cursor } = scan_page_from_option(
+            &self.send_cmd(send_option).await?
+        )?;
+        let items = items.into_iter()
+            .map(|KeyValue { key, value, .. }| (key, value))
+            .collect_vec();
+
+        Ok((items, (!next_cursor.is_empty()).then_some(next_cursor)))
+    }
+
     /// 数值控制选项通用流程
     async fn value_option(&mut self, type_num: i32) -> Result<u64>
     {
@@ -121,6 +729,11 @@ impl Client {
             r#type: type_num,
             bytes: vec![],
             value: 0,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
         };
 
         let result_option = self.send_cmd(send_option).await?;
@@ -134,8 +747,73 @@ impl Client {
     }
 
     #[inline]
-    async fn send_cmd(&mut self, cmd_option: CommandOption) -> Result<CommandOption>{
+    async fn send_cmd(&mut self, mut cmd_option: CommandOption) -> Result<CommandOption>{
+        if let Some(trace_context) = &self.trace_context {
+            cmd_option.trace_context = trace_context.to_traceparent();
+        }
+        cmd_option.background = self.priority == Priority::Background;
+        if self.interceptors.is_empty() {
+            return self.send_once(cmd_option).await;
+        }
+        let interceptors = self.interceptors.clone();
+        let mut attempt = 0;
+
+        loop {
+            let mut request = cmd_option.clone();
+            for interceptor in &interceptors {
+                interceptor.on_request(&mut request).await?;
+            }
+            attempt += 1;
+            let response = self.send_once(request.clone()).await;
+
+            let mut is_retry = false;
+            for interceptor in interceptors.iter().rev() {
+                if interceptor.on_response(&request, &response, attempt).await == ResponseAction::Retry {
+                    is_retry = true;
+                }
+            }
+            if !is_retry {
+                return response;
+            }
+        }
+    }
+
+    async fn send_once(&mut self, cmd_option: CommandOption) -> Result<CommandOption> {
         self.connection.write(cmd_option).await?;
-        self.connection.read().await
+        let result_option = self.connection.read().await?;
+
+        // 超出服务端配额时该请求未被处理
+        if result_option.r#type == OptionType::Throttled as i32 {
+            return Err(ConnectionError::Throttled(result_option.value));
+        }
+        if result_option.r#type == OptionType::Stale as i32 {
+            return Err(ConnectionError::Stale(result_option.sequence));
+        }
+        if result_option.r#type == OptionType::Rejected as i32 {
+            return Err(ConnectionError::WriteRejected(rejection_from_option(&result_option)?));
+        }
+        Ok(result_option)
+    }
+}
+
+/// Key空间通知的接收方，通过`Client::watch`获取
+#[allow(missing_debug_implementations)]
+pub struct KeyspaceWatcher {
+    connection: Connection,
+}
+
+impl KeyspaceWatcher {
+    /// 等待下一条通知，服务端断开时返回`ConnectionError::Disconnected`
+    #[inline]
+    pub async fn next(&mut self) -> Result<WatchItem> {
+        let result_option = self.connection.read().await?;
+        if result_option.r#type == OptionType::None as i32 {
+            return Err(ConnectionError::Disconnected);
+        }
+        if result_option.r#type == OptionType::Throttled as i32 {
+            return Err(ConnectionError::Throttled(result_option.value));
+        }
+
+        watch_item_from_option(&result_option)
     }
-}
\ No newline at end of file
+}
@@ -1,16 +1,70 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use bytes::BytesMut;
 use tokio_util::codec::{Decoder, Encoder};
 use prost::Message;
 use crate::error::ConnectionError;
 use crate::proto::net_pb::CommandOption;
 
-pub(crate) struct NetCommandCodec;
+/// varint的最大字节数
+const VARINT_MAX_LEN: usize = 10;
+
+pub(crate) struct NetCommandCodec {
+    /// 读取缓冲区的大小上限，为None时不限制
+    max_frame_size: Option<usize>,
+    /// 是否以长度前缀分隔帧，由握手协商后开启，读写两端共享
+    ///
+    /// 未开启时以单次读取的数据作为一帧，因此较大的帧可能被截断
+    length_delimited: Arc<AtomicBool>,
+}
 
 /// CommandOption编码器
 /// 用于CommandOption网络传输解析抽象
 impl NetCommandCodec {
     pub(crate) fn new() -> NetCommandCodec {
-        NetCommandCodec { }
+        NetCommandCodec { max_frame_size: None, length_delimited: Arc::default() }
+    }
+
+    pub(crate) fn with_max_frame_size(max_frame_size: usize) -> NetCommandCodec {
+        NetCommandCodec { max_frame_size: Some(max_frame_size), length_delimited: Arc::default() }
+    }
+
+    /// 用于在Framed拆分为读写两端后开启长度前缀分隔
+    pub(crate) fn length_delimited_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.length_delimited)
+    }
+
+    fn check_frame_size(&self, frame_size: usize) -> Result<(), ConnectionError> {
+        // 超出上限的帧直接视为错误，避免单个连接占用过多内存
+        match self.max_frame_size {
+            Some(max_frame_size) if frame_size > max_frame_size => {
+                Err(ConnectionError::FrameTooLarge(frame_size))
+            }
+            _ => Ok(())
+        }
+    }
+
+    fn decode_length_delimited(&self, src: &mut BytesMut) -> Result<Option<CommandOption>, ConnectionError> {
+        let frame_len = match prost::decode_length_delimiter(&src[..]) {
+            Ok(frame_len) => frame_len,
+            // 长度前缀尚未完整接收
+            Err(_) if src.len() < VARINT_MAX_LEN && src.iter().all(|byte| byte & 0x80 != 0) => {
+                return Ok(None)
+            }
+            Err(_) => return Err(ConnectionError::DecodeErr),
+        };
+        self.check_frame_size(frame_len)?;
+
+        let header_len = prost::length_delimiter_len(frame_len);
+        if src.len() < header_len + frame_len {
+            src.reserve(header_len + frame_len - src.len());
+            return Ok(None);
+        }
+        let frame = src.split_to(header_len + frame_len);
+
+        Ok(Some(CommandOption::decode(&frame[header_len..])
+            .map_err(|_| ConnectionError::DecodeErr)?))
     }
 }
 
@@ -19,9 +73,11 @@ impl Encoder<CommandOption> for NetCommandCodec {
 
     fn encode(&mut self, item: CommandOption, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let mut buf = vec![];
-        item
-            .encode(&mut buf)
-            .map_err(|_| ConnectionError::EncodeErr)?;
+        if self.length_delimited.load(Ordering::Acquire) {
+            item.encode_length_delimited(&mut buf)
+        } else {
+            item.encode(&mut buf)
+        }.map_err(|_| ConnectionError::EncodeErr)?;
         dst.extend(buf);
         Ok(())
     }
@@ -32,11 +88,113 @@ impl Decoder for NetCommandCodec {
     type Error = ConnectionError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        Ok(if src.is_empty() {
-            None
-        } else {
-            Some(CommandOption::decode(src.split())
-                .map_err(|_| ConnectionError::DecodeErr)?)
-        })
+        if src.is_empty() {
+            return Ok(None);
+        }
+        if self.length_delimited.load(Ordering::Acquire) {
+            return self.decode_length_delimited(src);
+        }
+        self.check_frame_size(src.len())?;
+
+        Ok(Some(CommandOption::decode(src.split())
+            .map_err(|_| ConnectionError::DecodeErr)?))
     }
-}
\ No newline at end of file
+}
+/// 对CommandOption的bytes进行LZ4压缩
+///
+/// bytes长度未达到threshold或压缩后未变小时保持原样
+pub(crate) fn compress_option(option: &mut CommandOption, threshold: usize) -> Result<(), ConnectionError> {
+    if option.compressed || option.bytes.len() < threshold {
+        return Ok(());
+    }
+    let mut encoder = lz4::EncoderBuilder::new()
+        .level(4)
+        .build(Vec::with_capacity(option.bytes.len()))?;
+    encoder.write_all(&option.bytes)?;
+
+    let (compressed, result) = encoder.finish();
+    result?;
+    if compressed.len() < option.bytes.len() {
+        option.bytes = compressed;
+        option.compressed = true;
+    }
+
+    Ok(())
+}
+
+/// 解压经过`compress_option`压缩的CommandOption
+pub(crate) fn decompress_option(option: &mut CommandOption) -> Result<(), ConnectionError> {
+    if option.compressed {
+        let mut decoder = lz4::Decoder::new(option.bytes.as_slice())?;
+        let mut decompressed = Vec::with_capacity(option.bytes.len() * 2);
+        let _ = decoder.read_to_end(&mut decompressed)?;
+
+        option.bytes = decompressed;
+        option.compressed = false;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use std::sync::atomic::Ordering;
+    use tokio_util::codec::{Decoder, Encoder};
+    use crate::error::ConnectionError;
+    use crate::net::codec::{compress_option, decompress_option, NetCommandCodec};
+    use crate::net::{COMPRESSION_THRESHOLD, Result};
+    use crate::proto::net_pb::CommandOption;
+
+    #[test]
+    fn test_compress_option() -> Result<()> {
+        let bytes = b"KipDB".repeat(COMPRESSION_THRESHOLD);
+        let mut option = CommandOption { r#type: 2, bytes: bytes.clone(), value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 };
+
+        compress_option(&mut option, COMPRESSION_THRESHOLD)?;
+        assert!(option.compressed);
+        assert!(option.bytes.len() < bytes.len());
+
+        decompress_option(&mut option)?;
+        assert!(!option.compressed);
+        assert_eq!(option.bytes, bytes);
+
+        // 未达到阈值时不进行压缩
+        let mut option = CommandOption { r#type: 2, bytes: b"KipDB".to_vec(), value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 };
+        compress_option(&mut option, COMPRESSION_THRESHOLD)?;
+        assert!(!option.compressed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_frame_size() {
+        let mut codec = NetCommandCodec::with_max_frame_size(4);
+        let mut src = BytesMut::from(&b"KipDB"[..]);
+
+        assert!(matches!(codec.decode(&mut src), Err(ConnectionError::FrameTooLarge(5))));
+    }
+
+    #[test]
+    fn test_length_delimited() -> Result<()> {
+        let mut codec = NetCommandCodec::new();
+        codec.length_delimited_flag().store(true, Ordering::Release);
+
+        let option = CommandOption { r#type: 2, bytes: b"KipDB".repeat(100), value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 };
+        let mut dst = BytesMut::new();
+        codec.encode(option.clone(), &mut dst)?;
+        codec.encode(option.clone(), &mut dst)?;
+
+        // 未完整接收的帧等待后续数据
+        let mut src = BytesMut::from(&dst[..10]);
+        assert_eq!(codec.decode(&mut src)?, None);
+        src.extend_from_slice(&dst[10..]);
+
+        // 粘连的帧被逐个解析
+        assert_eq!(codec.decode(&mut src)?, Some(option.clone()));
+        assert_eq!(codec.decode(&mut src)?, Some(option));
+        assert_eq!(codec.decode(&mut src)?, None);
+
+        Ok(())
+    }
+}
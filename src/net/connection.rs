@@ -1,3 +1,5 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use futures::{SinkExt, StreamExt};
 use futures::stream::{SplitSink, SplitStream};
 use tokio::net::TcpStream;
@@ -5,7 +7,7 @@ use tokio_util::codec::Framed;
 
 use crate::error::ConnectionError;
 use crate::kernel::options_none;
-use crate::net::codec::NetCommandCodec;
+use crate::net::codec::{compress_option, decompress_option, NetCommandCodec};
 use crate::net::Result;
 use crate::proto::net_pb::CommandOption;
 
@@ -14,37 +16,70 @@ type CommandFramedSink = SplitSink<Framed<TcpStream, NetCommandCodec>, CommandOp
 
 pub(crate) struct Connection {
     writer: CommandFramedSink,
-    reader: CommandFramedStream
+    reader: CommandFramedStream,
+    /// 写入时进行压缩的阈值，为None时不压缩
+    compression_threshold: Option<usize>,
+    length_delimited: Arc<AtomicBool>,
 }
 
 impl Connection {
     /// 新建连接
     pub(crate) fn new(stream: TcpStream) -> Connection {
-        let framed = Framed::new(stream, NetCommandCodec::new());
+        Self::with_codec(stream, NetCommandCodec::new())
+    }
+
+    /// 新建限制单帧大小的连接
+    pub(crate) fn with_max_frame_size(stream: TcpStream, max_frame_size: usize) -> Connection {
+        Self::with_codec(stream, NetCommandCodec::with_max_frame_size(max_frame_size))
+    }
+
+    fn with_codec(stream: TcpStream, codec: NetCommandCodec) -> Connection {
+        let length_delimited = codec.length_delimited_flag();
+        let framed = Framed::new(stream, codec);
         let (writer, reader) = framed.split::<CommandOption>();
         Connection{
             writer,
-            reader
+            reader,
+            compression_threshold: None,
+            length_delimited,
         }
     }
 
+    /// 启用以长度前缀分隔的帧
+    ///
+    /// 需在握手协商启用该功能后，且双方均无未读取的帧时调用
+    pub(crate) fn enable_length_delimited(&mut self) {
+        self.length_delimited.store(true, Ordering::Release);
+    }
+
+    /// 启用写入时的帧压缩
+    ///
+    /// 需在握手协商启用压缩功能后调用，读取时则总是解压被标记为压缩的帧
+    pub(crate) fn enable_compression(&mut self, threshold: usize) {
+        self.compression_threshold = Some(threshold);
+    }
+
     /// 读取CommandOption
     pub(crate) async fn read(&mut self) -> Result<CommandOption> {
         match self.reader.next().await {
             None => {
                 Ok(options_none())
             }
-            Some(Ok(option)) => {
+            Some(Ok(mut option)) => {
+                decompress_option(&mut option)?;
                 Ok(option)
             }
             Some(Err(e)) => {
-                panic!("{e:?}")
+                Err(e)
             }
         }
     }
 
     /// 写入CommandOption
-    pub(crate) async fn write(&mut self, option: CommandOption) -> Result<()> {
+    pub(crate) async fn write(&mut self, mut option: CommandOption) -> Result<()> {
+        if let Some(threshold) = self.compression_threshold {
+            compress_option(&mut option, threshold)?;
+        }
         if self.writer.send(option).await.is_err() {
             Err(ConnectionError::WriteFailed)
         } else {
@@ -0,0 +1,93 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use crate::error::ConnectionError;
+use crate::net::Result;
+use crate::proto::net_pb::CommandOption;
+
+/// 拦截器对响应的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseAction {
+    Continue,
+    /// 重新发送该请求，重新发送前会再次经过所有拦截器的`on_request`
+    Retry,
+}
+
+/// 客户端拦截器
+///
+/// 按添加顺序调用`on_request`，按相反顺序调用`on_response`，
+/// 可用于指标统计、日志、重试与认证令牌刷新等
+#[async_trait]
+pub trait Interceptor: Send + Sync {
+    /// 请求发送前调用，可修改请求，返回错误时中止该请求
+    #[inline]
+    async fn on_request(&self, _request: &mut CommandOption) -> Result<()> {
+        Ok(())
+    }
+
+    /// 收到响应或请求失败后调用，attempt为该请求已发送的次数
+    ///
+    /// 注意: 连接断开等IO错误后连接已不可用，此时重试并无意义
+    #[inline]
+    async fn on_response(
+        &self,
+        _request: &CommandOption,
+        _response: &Result<CommandOption>,
+        _attempt: usize
+    ) -> ResponseAction {
+        ResponseAction::Continue
+    }
+}
+
+/// 超出服务端配额时，等待服务端建议的时长后重试
+#[derive(Debug, Clone, Copy)]
+pub struct RetryThrottled {
+    max_attempts: usize,
+}
+
+impl RetryThrottled {
+    /// max_attempts为请求最多发送的次数(包括首次)
+    #[inline]
+    pub fn new(max_attempts: usize) -> Self {
+        RetryThrottled { max_attempts }
+    }
+}
+
+#[async_trait]
+impl Interceptor for RetryThrottled {
+    #[inline]
+    async fn on_response(
+        &self,
+        _request: &CommandOption,
+        response: &Result<CommandOption>,
+        attempt: usize
+    ) -> ResponseAction {
+        match response {
+            Err(ConnectionError::Throttled(retry_after_ms)) if attempt < self.max_attempts => {
+                tokio::time::sleep(Duration::from_millis(*retry_after_ms)).await;
+                ResponseAction::Retry
+            }
+            _ => ResponseAction::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ConnectionError;
+    use crate::kernel::options_none;
+    use crate::net::interceptor::{Interceptor, ResponseAction, RetryThrottled};
+
+    #[test]
+    fn test_retry_throttled() {
+        let retry = RetryThrottled::new(2);
+        let request = options_none();
+
+        tokio_test::block_on(async move {
+            let throttled = Err(ConnectionError::Throttled(1));
+            assert_eq!(retry.on_response(&request, &throttled, 1).await, ResponseAction::Retry);
+            // 达到最大发送次数后不再重试
+            assert_eq!(retry.on_response(&request, &throttled, 2).await, ResponseAction::Continue);
+            assert_eq!(retry.on_response(&request, &Ok(options_none()), 1).await, ResponseAction::Continue);
+        });
+    }
+}
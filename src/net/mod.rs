@@ -1,18 +1,449 @@
+use std::collections::BTreeMap;
 use crate::error::ConnectionError;
 
+use bytes::Bytes;
+use itertools::Itertools;
 use prost::Message;
+use serde::{Deserialize, Serialize};
 use crate::kernel::ByteUtils;
+use crate::kernel::lsm::hook::WriteRejection;
+use crate::kernel::lsm::keyspace::{KeyspaceEvent, KeyspaceEventKind};
+use crate::kernel::lsm::options::Ack;
+use crate::kernel::lsm::stats::StatsSnapshot;
 use crate::KernelError;
-use crate::proto::net_pb::{CommandOption, KeyValue};
+use crate::proto::net_pb::{AckLevel, BatchItemResult, BatchItemStatus, BatchResultChunk, CommandOption, ConditionalWrite, Handshake, KeyspaceEventType, KeyspaceNotification, KeyValue, LeaseCommand, OptionType, ScanPage, StructCommand, StructReply, WriteRejected};
 
 mod connection;
+mod namespace;
+pub mod priority;
 mod codec;
 pub mod client;
+pub mod interceptor;
+pub mod remote;
 pub mod server;
+pub mod typed;
 mod shutdown;
 
+pub use namespace::DEFAULT_NAMESPACE;
+pub use crate::kernel::utils::quota::Quota;
+
 pub type Result<T> = std::result::Result<T, ConnectionError>;
 
+/// 当前的协议版本
+///
+/// 未进行握手的连接视为版本0，即仅支持握手之前既有的指令
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 功能标识: 帧压缩
+pub const FEATURE_COMPRESSION: u64 = 1;
+/// 功能标识: 流式Scan
+pub const FEATURE_STREAMING_SCAN: u64 = 1 << 1;
+/// 功能标识: 认证
+pub const FEATURE_AUTH: u64 = 1 << 2;
+/// 功能标识: 以长度前缀分隔帧，使超出单次读取大小的帧(如批量导入)可以被完整接收
+pub const FEATURE_LENGTH_DELIMITED: u64 = 1 << 3;
+
+/// 本端所支持的功能
+pub(crate) const SUPPORTED_FEATURES: u64 = FEATURE_COMPRESSION | FEATURE_LENGTH_DELIMITED;
+
+/// 启用压缩时，bytes长度达到该值的帧才进行压缩
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// 服务端信息，由Info指令获取
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ServerInfo {
+    /// 服务端运行时长，单位为秒
+    pub uptime_secs: u64,
+    /// 当前连接的客户端数量
+    pub connected_clients: usize,
+    /// 各指令的处理次数，以指令名称为Key
+    pub command_counts: BTreeMap<String, u64>,
+    /// 当前连接所选择的命名空间
+    pub namespace: String,
+    /// 当前命名空间内各指令的处理次数
+    pub namespace_command_counts: BTreeMap<String, u64>,
+    /// 当前命名空间存储内核的统计数据
+    pub store: StatsSnapshot,
+    /// 当前命名空间的写入统计
+    #[serde(default)]
+    pub namespace_writes: NamespaceWriteStats,
+}
+
+/// 命名空间的写入统计，写入类请求为单条的Set与Remove、批量处理、批量导入、Incr与条件写入
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct NamespaceWriteStats {
+    /// 处理的写入类请求数量
+    pub writes: u64,
+    /// 写入类请求的字节数
+    pub written_bytes: u64,
+    /// 因超出写入配额而被拒绝的写入类请求数量
+    pub throttled_writes: u64,
+}
+
+/// 请求所携带的链路上下文，以W3C traceparent格式在协议中传递
+///
+/// 格式为`00-{trace_id}-{parent_span_id}-{trace_flags}`，均为小写十六进制
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub parent_span_id: String,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    #[inline]
+    pub fn new(trace_id: u128, parent_span_id: u64, sampled: bool) -> Self {
+        TraceContext {
+            trace_id: format!("{trace_id:032x}"),
+            parent_span_id: format!("{parent_span_id:016x}"),
+            sampled,
+        }
+    }
+
+    /// 解析traceparent，格式不正确或ID全为0时返回None
+    #[inline]
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let is_id = |id: &str, len: usize| {
+            id.len() == len
+                && id.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+                && id.bytes().any(|byte| byte != b'0')
+        };
+
+        match traceparent.split('-').collect_vec().as_slice() {
+            ["00", trace_id, parent_span_id, flags]
+                if is_id(trace_id, 32) && is_id(parent_span_id, 16) && flags.len() == 2 =>
+            {
+                let flags = u8::from_str_radix(flags, 16).ok()?;
+
+                Some(TraceContext {
+                    trace_id: trace_id.to_string(),
+                    parent_span_id: parent_span_id.to_string(),
+                    sampled: flags & 1 == 1,
+                })
+            }
+            _ => None
+        }
+    }
+
+    #[inline]
+    pub fn to_traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.parent_span_id, u8::from(self.sampled))
+    }
+}
+
+/// 流式批量处理中单个指令的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BatchItem {
+    /// 执行成功，Get的Key存在时为其Value，其他指令为None
+    Ok(Option<Vec<u8>>),
+    /// Get或Remove的Key不存在
+    NotFound,
+    /// 执行失败及其原因
+    Failed(String),
+}
+
+impl From<BatchItemResult> for BatchItem {
+    #[inline]
+    fn from(result: BatchItemResult) -> Self {
+        let BatchItemResult { status, value, error, has_value } = result;
+
+        match BatchItemStatus::from_i32(status) {
+            Some(BatchItemStatus::Ok) => BatchItem::Ok(has_value.then_some(value)),
+            Some(BatchItemStatus::NotFound) => BatchItem::NotFound,
+            _ => BatchItem::Failed(error),
+        }
+    }
+}
+
+/// 订阅Key空间时服务端推送的内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WatchItem {
+    Event(KeyspaceEvent),
+    /// 因接收缓慢而被丢弃的通知数量，此时应视所缓存的所有Key均已失效
+    Lagged(u64),
+}
+
+/// 服务端对客户端握手的协商结果
+///
+/// 版本取双方较低者，功能取双方的交集，使新旧版本的客户端与服务端均能以共同支持的功能通信
+fn negotiate(client: &Handshake) -> Handshake {
+    Handshake {
+        version: client.version.min(PROTOCOL_VERSION),
+        features: client.features & SUPPORTED_FEATURES,
+    }
+}
+
+/// Handshake转换为CommandOption
+fn option_from_handshake(handshake: &Handshake) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    handshake.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 9,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// CommandOption转换为Handshake
+fn handshake_from_option(option: &CommandOption) -> Result<Handshake> {
+    if option.r#type != 9 {
+        Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+    } else {
+        Ok(Handshake::decode(&*option.bytes)
+            .map_err(|_| ConnectionError::DecodeErr)?)
+    }
+}
+
+/// ScanPage转换为CommandOption
+fn option_from_scan_page(scan_page: &ScanPage) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    scan_page.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 11,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// CommandOption转换为ScanPage
+fn scan_page_from_option(option: &CommandOption) -> Result<ScanPage> {
+    if option.r#type != 11 {
+        Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+    } else {
+        Ok(ScanPage::decode(&*option.bytes)
+            .map_err(|_| ConnectionError::DecodeErr)?)
+    }
+}
+
+/// StructCommand转换为CommandOption
+fn option_from_struct_command(struct_command: &StructCommand) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    struct_command.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 16,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// LeaseCommand转换为CommandOption
+fn option_from_lease_command(lease_command: &LeaseCommand) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    lease_command.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 17,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// ConditionalWrite转换为CommandOption
+fn option_from_conditional_write(conditional_write: &ConditionalWrite) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    conditional_write.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 18,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// BatchResultChunk转换为CommandOption，is_last表示是否为最后一次响应
+fn option_from_batch_chunk(chunk: &BatchResultChunk, is_last: bool) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    chunk.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 19,
+        bytes,
+        value: u64::from(is_last),
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// KeyspaceEvent转换为Watch的推送
+fn option_from_keyspace_event(event: &KeyspaceEvent) -> Result<CommandOption> {
+    let event_type = match event.kind {
+        KeyspaceEventKind::Set => KeyspaceEventType::KeyspaceSet,
+        KeyspaceEventKind::Deleted => KeyspaceEventType::KeyspaceDeleted,
+        KeyspaceEventKind::Expired => KeyspaceEventType::KeyspaceExpired,
+    };
+    let notification = KeyspaceNotification {
+        r#type: event_type as i32,
+        column_family: event.column_family.as_ref().map_or(vec![], |name| name.to_vec()),
+        key: event.key.to_vec(),
+        has_column_family: event.column_family.is_some(),
+    };
+    let mut bytes = vec![];
+    notification.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: OptionType::Watch as i32,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// Watch的推送转换为WatchItem
+fn watch_item_from_option(option: &CommandOption) -> Result<WatchItem> {
+    if option.r#type != OptionType::Watch as i32 {
+        return Err(ConnectionError::StoreErr(KernelError::NotMatchCmd));
+    }
+    if option.value > 0 {
+        return Ok(WatchItem::Lagged(option.value));
+    }
+    let KeyspaceNotification { r#type, column_family, key, has_column_family } = KeyspaceNotification::decode(&*option.bytes)
+        .map_err(|_| ConnectionError::DecodeErr)?;
+    let kind = match KeyspaceEventType::from_i32(r#type).ok_or(ConnectionError::DecodeErr)? {
+        KeyspaceEventType::KeyspaceSet => KeyspaceEventKind::Set,
+        KeyspaceEventType::KeyspaceDeleted => KeyspaceEventKind::Deleted,
+        KeyspaceEventType::KeyspaceExpired => KeyspaceEventKind::Expired,
+    };
+
+    Ok(WatchItem::Event(KeyspaceEvent {
+        kind,
+        column_family: has_column_family.then(|| Bytes::from(column_family)),
+        key: Bytes::from(key),
+    }))
+}
+
+/// CommandOption转换为BatchResultChunk
+fn batch_chunk_from_option(option: &CommandOption) -> Result<BatchResultChunk> {
+    if option.r#type != 19 {
+        Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+    } else {
+        Ok(BatchResultChunk::decode(&*option.bytes)
+            .map_err(|_| ConnectionError::DecodeErr)?)
+    }
+}
+
+/// StructReply转换为CommandOption
+fn option_from_struct_reply(struct_reply: &StructReply) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    struct_reply.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 16,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// CommandOption转换为StructReply
+fn struct_reply_from_option(option: &CommandOption) -> Result<StructReply> {
+    if option.r#type != 16 {
+        Err(ConnectionError::StoreErr(KernelError::NotMatchCmd))
+    } else {
+        Ok(StructReply::decode(&*option.bytes)
+            .map_err(|_| ConnectionError::DecodeErr)?)
+    }
+}
+
+/// WriteRejection转换为CommandOption
+fn option_from_rejection(rejection: &WriteRejection) -> Result<CommandOption> {
+    let mut bytes = vec![];
+    WriteRejected {
+        code: rejection.code,
+        reason: rejection.reason.clone(),
+        key: rejection.key.clone(),
+    }.encode(&mut bytes)
+        .map_err(|_| ConnectionError::EncodeErr)?;
+
+    Ok(CommandOption {
+        r#type: 20,
+        bytes,
+        value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
+    })
+}
+
+/// CommandOption转换为WriteRejection
+fn rejection_from_option(option: &CommandOption) -> Result<WriteRejection> {
+    let WriteRejected { code, reason, key } = WriteRejected::decode(&*option.bytes)
+        .map_err(|_| ConnectionError::DecodeErr)?;
+
+    Ok(WriteRejection { code, reason, key })
+}
+
+/// 将写入的确认级别设置于请求中
+fn set_option_ack(option: &mut CommandOption, ack: Ack) {
+    let level = match ack {
+        Ack::Wal => AckLevel::Wal,
+        Ack::Memory => AckLevel::Memory,
+        Ack::Fsync => AckLevel::Fsync,
+    };
+    option.ack = level as i32;
+}
+
+/// 读取请求中写入的确认级别，旧版本的客户端未设置时为`Ack::Wal`
+fn ack_from_option(option: &CommandOption) -> Result<Ack> {
+    Ok(match AckLevel::from_i32(option.ack).ok_or(ConnectionError::DecodeErr)? {
+        AckLevel::Wal => Ack::Wal,
+        AckLevel::Memory => Ack::Memory,
+        AckLevel::Fsync => Ack::Fsync,
+    })
+}
+
 /// KeyValue转换为CommandOption
 fn option_from_key_value(kv: &KeyValue) -> Result<CommandOption> {
     let mut bytes = vec![];
@@ -23,6 +454,11 @@ fn option_from_key_value(kv: &KeyValue) -> Result<CommandOption> {
         r#type: 0,
         bytes,
         value: 0,
+        compressed: false,
+        trace_context: String::new(),
+        sequence: 0,
+        background: false,
+        ack: 0,
     })
 }
 
@@ -47,4 +483,92 @@ fn kv_encode_with_len(key_value: &KeyValue) -> Result<Vec<u8>> {
     } else {
         Err(ConnectionError::StoreErr(KernelError::DataEmpty))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use crate::kernel::lsm::hook::WriteRejection;
+    use crate::kernel::lsm::keyspace::{KeyspaceEvent, KeyspaceEventKind};
+    use crate::kernel::lsm::options::Ack;
+    use crate::kernel::options_none;
+    use crate::net::{ack_from_option, FEATURE_AUTH, FEATURE_COMPRESSION, handshake_from_option, negotiate, option_from_handshake, option_from_keyspace_event, option_from_rejection, PROTOCOL_VERSION, rejection_from_option, Result, set_option_ack, SUPPORTED_FEATURES, TraceContext, watch_item_from_option, WatchItem};
+    use crate::proto::net_pb::{Handshake, OptionType};
+
+    #[test]
+    fn test_handshake() -> Result<()> {
+        let client = Handshake {
+            version: PROTOCOL_VERSION + 1,
+            features: FEATURE_COMPRESSION | FEATURE_AUTH,
+        };
+        let option = option_from_handshake(&client)?;
+        assert_eq!(handshake_from_option(&option)?, client);
+
+        // 较新的客户端会降级至服务端的版本，且仅启用双方均支持的功能
+        let negotiated = negotiate(&client);
+        assert_eq!(negotiated.version, PROTOCOL_VERSION);
+        assert_eq!(negotiated.features, client.features & SUPPORTED_FEATURES);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_rejection() -> Result<()> {
+        let mut rejection = WriteRejection::new(7, "value too large");
+        rejection.key = b"user:1".to_vec();
+
+        let option = option_from_rejection(&rejection)?;
+        assert_eq!(option.r#type, OptionType::Rejected as i32);
+        assert_eq!(rejection_from_option(&option)?, rejection);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_option_ack() -> Result<()> {
+        let mut option = options_none();
+        assert_eq!(ack_from_option(&option)?, Ack::Wal);
+
+        for ack in [Ack::Memory, Ack::Wal, Ack::Fsync] {
+            set_option_ack(&mut option, ack);
+            assert_eq!(ack_from_option(&option)?, ack);
+        }
+        option.ack = 99;
+        assert!(ack_from_option(&option).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_item() -> Result<()> {
+        let events = [
+            KeyspaceEvent { kind: KeyspaceEventKind::Set, column_family: None, key: Bytes::from_static(b"k1") },
+            // 列族名称为空时仍可与不属于列族区分
+            KeyspaceEvent { kind: KeyspaceEventKind::Deleted, column_family: Some(Bytes::new()), key: Bytes::from_static(b"k2") },
+            KeyspaceEvent { kind: KeyspaceEventKind::Expired, column_family: Some(Bytes::from_static(b"sessions")), key: Bytes::from_static(b"1") },
+        ];
+        for event in events {
+            let option = option_from_keyspace_event(&event)?;
+            assert_eq!(option.r#type, OptionType::Watch as i32);
+            assert_eq!(watch_item_from_option(&option)?, WatchItem::Event(event));
+        }
+        let mut option = options_none();
+        option.r#type = OptionType::Watch as i32;
+        option.value = 3;
+        assert_eq!(watch_item_from_option(&option)?, WatchItem::Lagged(3));
+        assert!(watch_item_from_option(&options_none()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_context() {
+        let trace_context = TraceContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7, true);
+        let traceparent = trace_context.to_traceparent();
+        assert_eq!(traceparent, "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+        assert_eq!(TraceContext::parse(&traceparent), Some(trace_context));
+
+        assert_eq!(TraceContext::parse(""), None);
+        assert_eq!(TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01"), None);
+        assert_eq!(TraceContext::parse("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"), None);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Mutex;
+use tracing::info;
+use crate::error::ConnectionError;
+use crate::kernel::KVStore;
+use crate::kernel::lsm::lsm_kv::LsmStore;
+use crate::net::{NamespaceWriteStats, Result};
+use crate::kernel::utils::quota::{Quota, QuotaLimiter};
+use crate::net::server::{CommandCounts, ServerConfig};
+
+/// 默认命名空间，即连接建立后所使用的命名空间
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// 默认命名空间的数据目录
+pub(crate) const DEFAULT_NAMESPACE_DIR: &str = "./data";
+
+/// 其余命名空间的数据目录，各命名空间以其名称作为子目录
+pub(crate) const DEFAULT_NAMESPACES_DIR: &str = "./namespaces";
+
+/// 可自动创建的未声明命名空间的数量上限
+pub(crate) const DEFAULT_MAX_NAMESPACES: usize = 16;
+
+/// 命名空间名称的长度上限
+const NAMESPACE_NAME_MAX_LEN: usize = 64;
+
+/// 服务端上的逻辑数据库
+pub(crate) struct Namespace {
+    pub(crate) name: String,
+    pub(crate) kv_store: LsmStore,
+    /// 该命名空间内的指令计数
+    pub(crate) command_counts: CommandCounts,
+    /// 该命名空间内所有连接共享的配额限制器
+    pub(crate) limiter: Option<parking_lot::Mutex<QuotaLimiter>>,
+    /// 该命名空间内所有连接共享的写入配额限制器，仅计入写入类请求
+    pub(crate) write_limiter: Option<parking_lot::Mutex<QuotaLimiter>>,
+    pub(crate) write_stats: WriteStats,
+}
+
+impl Namespace {
+    async fn open(name: &str, path: impl Into<PathBuf> + Send, quota: Option<Quota>, write_quota: Option<Quota>) -> Result<Self> {
+        Ok(Namespace {
+            name: name.to_owned(),
+            kv_store: LsmStore::open(path).await?,
+            command_counts: CommandCounts::default(),
+            limiter: quota.map(|quota| parking_lot::Mutex::new(QuotaLimiter::new(quota))),
+            write_limiter: write_quota.map(|quota| parking_lot::Mutex::new(QuotaLimiter::new(quota))),
+            write_stats: WriteStats::default(),
+        })
+    }
+}
+
+/// 命名空间的写入统计
+#[derive(Default)]
+pub(crate) struct WriteStats {
+    writes: AtomicU64,
+    written_bytes: AtomicU64,
+    throttled_writes: AtomicU64,
+}
+
+impl WriteStats {
+    pub(crate) fn record(&self, bytes_len: usize) {
+        let _ = self.writes.fetch_add(1, Ordering::Relaxed);
+        let _ = self.written_bytes.fetch_add(bytes_len as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_throttled(&self) {
+        let _ = self.throttled_writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> NamespaceWriteStats {
+        NamespaceWriteStats {
+            writes: self.writes.load(Ordering::Relaxed),
+            written_bytes: self.written_bytes.load(Ordering::Relaxed),
+            throttled_writes: self.throttled_writes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 服务端的命名空间集合
+///
+/// 默认命名空间随服务端启动而打开，其余命名空间在首次被选择时打开，
+/// 各命名空间以独立的子目录存储，互不影响
+///
+/// 已声明(即配置了令牌、配额或通过`ServerConfig::declare_namespace`声明)的命名空间总是可以打开，
+/// 未声明的命名空间至多自动创建`ServerConfig::max_namespaces`个，避免任意连接无限制地创建数据目录
+pub(crate) struct Namespaces {
+    default: Arc<Namespace>,
+    opened: Mutex<HashMap<String, Arc<Namespace>>>,
+    /// 其余命名空间的数据目录
+    namespaces_dir: PathBuf,
+    /// 已声明的命名空间
+    declared: HashSet<String>,
+    /// 可自动创建的未声明命名空间的数量上限
+    max_namespaces: usize,
+    /// 命名空间 -> 允许访问的令牌
+    /// 未配置令牌的命名空间允许任意连接访问
+    tokens: HashMap<String, HashSet<String>>,
+    /// 命名空间 -> 配额
+    quotas: HashMap<String, Quota>,
+    /// 命名空间 -> 写入配额
+    write_quotas: HashMap<String, Quota>,
+}
+
+impl Namespaces {
+    pub(crate) async fn open(config: &ServerConfig) -> Result<Self> {
+        let default = Namespace::open(
+            DEFAULT_NAMESPACE,
+            &config.data_dir,
+            config.namespace_quotas.get(DEFAULT_NAMESPACE).copied(),
+            config.namespace_write_quotas.get(DEFAULT_NAMESPACE).copied()
+        ).await?;
+        let declared = config.declared_namespaces.iter()
+            .chain(config.namespace_tokens.keys())
+            .chain(config.namespace_quotas.keys())
+            .chain(config.namespace_write_quotas.keys())
+            .cloned()
+            .collect();
+
+        Ok(Namespaces {
+            default: Arc::new(default),
+            opened: Mutex::new(HashMap::new()),
+            namespaces_dir: config.namespaces_dir.clone(),
+            declared,
+            max_namespaces: config.max_namespaces,
+            tokens: config.namespace_tokens.clone(),
+            quotas: config.namespace_quotas.clone(),
+            write_quotas: config.namespace_write_quotas.clone(),
+        })
+    }
+
+    pub(crate) fn default_namespace(&self) -> &Arc<Namespace> {
+        &self.default
+    }
+
+    /// 连接建立后是否无需选择即可访问默认命名空间，即默认命名空间未配置令牌
+    pub(crate) fn is_default_open(&self) -> bool {
+        !self.tokens.contains_key(DEFAULT_NAMESPACE)
+    }
+
+    /// 选择命名空间，名称不合法、令牌不匹配或超出自动创建的数量上限时拒绝访问
+    pub(crate) async fn select(&self, name: &str, token: &str) -> Result<Arc<Namespace>> {
+        if name != DEFAULT_NAMESPACE && !is_valid_name(name) {
+            return Err(ConnectionError::PermissionDenied);
+        }
+        if let Some(tokens) = self.tokens.get(name) {
+            if !tokens.contains(token) {
+                return Err(ConnectionError::PermissionDenied);
+            }
+        }
+        if name == DEFAULT_NAMESPACE {
+            return Ok(Arc::clone(&self.default));
+        }
+
+        let mut opened = self.opened.lock().await;
+        if let Some(namespace) = opened.get(name) {
+            return Ok(Arc::clone(namespace));
+        }
+        if !self.declared.contains(name) {
+            let undeclared = opened.keys()
+                .filter(|opened_name| !self.declared.contains(*opened_name))
+                .count();
+            if undeclared >= self.max_namespaces {
+                info!("[Namespaces][Open Denied][Name: {}]: exceeded max namespaces {}", name, self.max_namespaces);
+                return Err(ConnectionError::PermissionDenied);
+            }
+        }
+        let namespace = Arc::new(
+            Namespace::open(
+                name,
+                self.namespaces_dir.join(name),
+                self.quotas.get(name).copied(),
+                self.write_quotas.get(name).copied()
+            ).await?
+        );
+        let _ = opened.insert(name.to_owned(), Arc::clone(&namespace));
+        info!("[Namespaces][Open][Name: {}]", name);
+
+        Ok(namespace)
+    }
+
+    /// 持久化所有已打开的命名空间
+    pub(crate) async fn flush(&self) -> Result<()> {
+        self.default.kv_store.flush().await?;
+
+        for namespace in self.opened.lock().await.values() {
+            namespace.kv_store.flush().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 命名空间名称仅允许字母、数字、`_`与`-`，以避免路径穿越
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= NAMESPACE_NAME_MAX_LEN
+        && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-')
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use crate::error::ConnectionError;
+    use crate::net::namespace::{DEFAULT_NAMESPACE, is_valid_name, Namespaces};
+    use crate::net::Result;
+    use crate::net::server::ServerConfig;
+
+    #[test]
+    fn test_namespaces_select() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = ServerConfig::default()
+                .data_dir(temp_dir.path().join("data"))
+                .namespaces_dir(temp_dir.path().join("namespaces"))
+                .namespace_token(DEFAULT_NAMESPACE, "token")
+                .declare_namespace("declared")
+                .max_namespaces(1);
+            let namespaces = Namespaces::open(&config).await?;
+
+            // 默认命名空间配置令牌后同样需要校验
+            assert!(!namespaces.is_default_open());
+            assert!(matches!(namespaces.select(DEFAULT_NAMESPACE, "").await, Err(ConnectionError::PermissionDenied)));
+            assert_eq!(namespaces.select(DEFAULT_NAMESPACE, "token").await?.name, DEFAULT_NAMESPACE);
+
+            // 未声明的命名空间至多自动创建max_namespaces个，已声明的命名空间不受限制
+            let _ = namespaces.select("tenant_1", "").await?;
+            assert!(matches!(namespaces.select("tenant_2", "").await, Err(ConnectionError::PermissionDenied)));
+            let _ = namespaces.select("declared", "").await?;
+            let _ = namespaces.select("tenant_1", "").await?;
+
+            assert!(temp_dir.path().join("data").exists());
+            assert!(temp_dir.path().join("namespaces").join("tenant_1").exists());
+            assert!(!temp_dir.path().join("namespaces").join("tenant_2").exists());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_namespace_name() {
+        assert!(is_valid_name("tenant_1"));
+        assert!(is_valid_name("app-2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("../data"));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name(&"a".repeat(65)));
+    }
+}
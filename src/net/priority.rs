@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use tokio::time;
+use crate::kernel::utils::quota::{Quota, QuotaLimiter};
+
+/// 请求的优先级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// 业务请求
+    #[default]
+    Foreground,
+    /// 备份、导出等后台任务的请求，排在前台请求之后处理，并受后台配额的限制
+    Background,
+}
+
+/// 前台与后台请求的调度器，由服务端的所有连接共享
+///
+/// 后台请求在处理前等待进行中的前台请求全部完成，
+/// 最长等待max_delay后仍会被处理，避免前台请求持续不断时后台请求饿死
+pub(crate) struct PriorityScheduler {
+    /// 进行中的前台请求数量
+    foreground: AtomicUsize,
+    foreground_idle: Notify,
+    max_delay: Duration,
+    /// 所有后台请求共享的配额
+    limiter: Option<Mutex<QuotaLimiter>>,
+}
+
+/// 前台请求处理期间持有，drop时视为请求完成
+pub(crate) struct ForegroundGuard<'a> {
+    scheduler: &'a PriorityScheduler,
+}
+
+impl PriorityScheduler {
+    pub(crate) fn new(quota: Option<Quota>, max_delay: Duration) -> Self {
+        PriorityScheduler {
+            foreground: AtomicUsize::new(0),
+            foreground_idle: Notify::new(),
+            max_delay,
+            limiter: quota.map(|quota| Mutex::new(QuotaLimiter::new(quota))),
+        }
+    }
+
+    pub(crate) fn enter_foreground(&self) -> ForegroundGuard<'_> {
+        let _ = self.foreground.fetch_add(1, Ordering::AcqRel);
+
+        ForegroundGuard { scheduler: self }
+    }
+
+    /// 等待进行中的前台请求全部完成，至多等待max_delay
+    pub(crate) async fn wait_for_foreground(&self) {
+        let _ignore = time::timeout(self.max_delay, async {
+            loop {
+                let notified = self.foreground_idle.notified();
+                if self.foreground.load(Ordering::Acquire) == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        }).await;
+    }
+
+    /// 后台请求处理前需等待的时长，无需等待时记录此次请求并返回None
+    pub(crate) fn throttle(&self, bytes_len: usize) -> Option<Duration> {
+        let mut limiter = self.limiter.as_ref()?.lock();
+        let wait_time = limiter.wait_time();
+
+        if wait_time.is_none() {
+            limiter.consume_request(bytes_len);
+        }
+        wait_time
+    }
+
+    /// 后台请求的响应同样计入后台配额
+    pub(crate) fn consume_bytes(&self, bytes_len: usize) {
+        if let Some(limiter) = &self.limiter {
+            limiter.lock().consume_bytes(bytes_len);
+        }
+    }
+}
+
+impl Drop for ForegroundGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.scheduler.foreground.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.scheduler.foreground_idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::net::priority::PriorityScheduler;
+    use crate::kernel::utils::quota::Quota;
+
+    #[test]
+    fn test_priority_scheduler() {
+        tokio_test::block_on(async {
+            let scheduler = PriorityScheduler::new(
+                Some(Quota::default().requests_per_sec(1)),
+                Duration::from_millis(100)
+            );
+
+            // 没有前台请求时无需等待
+            let start = Instant::now();
+            scheduler.wait_for_foreground().await;
+            assert!(start.elapsed() < Duration::from_millis(100));
+
+            // 前台请求完成时被唤醒
+            let guard = scheduler.enter_foreground();
+            let start = Instant::now();
+            let _ = tokio::join!(scheduler.wait_for_foreground(), async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drop(guard);
+            });
+            assert!(start.elapsed() < Duration::from_millis(100));
+
+            // 前台请求持续时至多等待max_delay
+            let _guard = scheduler.enter_foreground();
+            let start = Instant::now();
+            scheduler.wait_for_foreground().await;
+            assert!(start.elapsed() >= Duration::from_millis(100));
+
+            assert_eq!(scheduler.throttle(0), None);
+            assert!(scheduler.throttle(0).is_some());
+        });
+    }
+}
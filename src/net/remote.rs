@@ -0,0 +1,312 @@
+use std::fmt::{Debug, Formatter};
+use std::mem;
+use std::path::PathBuf;
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::net::ToSocketAddrs;
+use tokio::sync::Mutex;
+use crate::kernel::{CommandData, KVStore};
+use crate::kernel::lsm::fallback::ReadFallback;
+use crate::kernel::lsm::lsm_kv::ScanCursor;
+use crate::KernelError;
+use crate::net::client::Client;
+
+/// 作为副本节点补全损坏的数据时，每次分页扫描读取的数据条数
+const FALLBACK_SCAN_LIMIT: usize = 256;
+
+struct RemoteInner {
+    client: Client,
+    /// 尚未发送的写入
+    pending: Vec<CommandData>,
+}
+
+impl RemoteInner {
+    /// 将缓冲的写入以单次Batch发送
+    async fn send_pending(&mut self) -> crate::kernel::Result<()> {
+        if !self.pending.is_empty() {
+            let pending = mem::take(&mut self.pending);
+            let _ignore = self.client.batch(pending).await?;
+        }
+        Ok(())
+    }
+}
+
+/// 以`KVStore`接口访问远程服务端的Store
+///
+/// 与嵌入式的内核实现相同的接口，使应用可以通过`Box<dyn KVStore>`在本地与远程之间切换
+///
+/// 默认每次写入立即发送，通过`write_buffer`开启缓冲后，
+/// set与remove会在本地缓冲并在数量达到上限或调用`flush`时以单次Batch发送，
+/// 缓冲期间本端的get可以读取到缓冲中的写入，而其他客户端在发送后才可见
+///
+/// Tips: 内部仅持有单个连接，并发的请求会依次发送
+pub struct RemoteStore {
+    inner: Mutex<RemoteInner>,
+    write_buffer: usize,
+}
+
+impl RemoteStore {
+    /// 与服务端进行连接
+    #[inline]
+    pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::net::Result<Self> {
+        Ok(Self::from(Client::connect(addr).await?))
+    }
+
+    /// 缓冲写入的数量上限，为0时不进行缓冲
+    #[inline]
+    pub fn write_buffer(mut self, write_buffer: usize) -> Self {
+        self.write_buffer = write_buffer;
+        self
+    }
+
+    /// 取回内部的客户端，以使用`KVStore`之外的指令
+    ///
+    /// 需先调用`flush`，否则缓冲中尚未发送的写入会被丢弃
+    #[inline]
+    pub fn into_inner(self) -> Client {
+        self.inner.into_inner().client
+    }
+
+    async fn write(&self, cmd: CommandData) -> crate::kernel::Result<()> {
+        let mut inner = self.inner.lock().await;
+
+        if self.write_buffer == 0 {
+            return match cmd {
+                CommandData::Set { key, value } => Ok(inner.client.set(key, value).await?),
+                CommandData::Remove { key } => Ok(inner.client.remove(key).await?),
+                CommandData::Get { .. } => Err(KernelError::NotMatchCmd),
+            };
+        }
+        inner.pending.push(cmd);
+
+        if inner.pending.len() >= self.write_buffer {
+            inner.send_pending().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for RemoteStore {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteStore")
+            .field("write_buffer", &self.write_buffer)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 作为副本节点，在本地数据损坏时提供读取
+#[async_trait]
+impl ReadFallback for RemoteStore {
+    #[inline]
+    async fn get(&self, key: &[u8]) -> crate::kernel::Result<Option<Bytes>> {
+        KVStore::get(self, key).await
+    }
+
+    /// 以分页扫描读取，读取前会先发送缓冲中的写入
+    #[inline]
+    async fn scan(&self, start: &[u8], end: &[u8]) -> crate::kernel::Result<Vec<(Bytes, Bytes)>> {
+        let mut inner = self.inner.lock().await;
+        inner.send_pending().await?;
+
+        let mut items = Vec::new();
+        let mut cursor = Some(ScanCursor::new(start.to_vec(), i64::MAX).to_bytes()?);
+
+        while let Some(bytes) = cursor {
+            let (page, next_cursor) = inner.client.scan(Some(bytes), FALLBACK_SCAN_LIMIT).await?;
+            let is_end = !page.last().is_some_and(|(key, _)| key.as_slice() < end);
+
+            items.extend(
+                page.into_iter()
+                    .take_while(|(key, _)| key.as_slice() <= end)
+                    .map(|(key, value)| (Bytes::from(key), Bytes::from(value)))
+            );
+            cursor = next_cursor.filter(|_| !is_end);
+        }
+
+        Ok(items)
+    }
+}
+
+impl From<Client> for RemoteStore {
+    #[inline]
+    fn from(client: Client) -> Self {
+        RemoteStore {
+            inner: Mutex::new(RemoteInner { client, pending: Vec::new() }),
+            write_buffer: 0,
+        }
+    }
+}
+
+/// 获取缓冲中该Key最新的写入，Key未被写入时返回None
+fn pending_value(pending: &[CommandData], key: &[u8]) -> Option<Option<Bytes>> {
+    pending.iter()
+        .rev()
+        .find_map(|cmd| match cmd {
+            CommandData::Set { key: cmd_key, value } if cmd_key == key => {
+                Some(Some(Bytes::copy_from_slice(value)))
+            }
+            CommandData::Remove { key: cmd_key } if cmd_key == key => Some(None),
+            _ => None,
+        })
+}
+
+#[async_trait]
+impl KVStore for RemoteStore {
+    #[inline]
+    fn name() -> &'static str where Self: Sized {
+        "RemoteStore"
+    }
+
+    /// 远程Store没有本地的数据目录，需通过`RemoteStore::connect`进行连接
+    #[inline]
+    async fn open(_path: impl Into<PathBuf> + Send) -> crate::kernel::Result<Self> {
+        Err(KernelError::NotSupport("RemoteStore must be created by RemoteStore::connect"))
+    }
+
+    /// 发送缓冲的写入后，使服务端将数据刷入硬盘
+    #[inline]
+    async fn flush(&self) -> crate::kernel::Result<()> {
+        let mut inner = self.inner.lock().await;
+
+        inner.send_pending().await?;
+        Ok(inner.client.flush().await?)
+    }
+
+    #[inline]
+    async fn set(&self, key: &[u8], value: Bytes) -> crate::kernel::Result<()> {
+        self.write(CommandData::set(key.to_vec(), value.to_vec())).await
+    }
+
+    #[inline]
+    async fn get(&self, key: &[u8]) -> crate::kernel::Result<Option<Bytes>> {
+        let mut inner = self.inner.lock().await;
+
+        if let Some(value) = pending_value(&inner.pending, key) {
+            return Ok(value);
+        }
+        // 以该连接最后写入的Sequence进行读取，保证可读到本端已发送的写入
+        let min_sequence = inner.client.last_sequence();
+        Ok(inner.client.get_with_min_sequence(key.to_vec(), min_sequence).await?
+            .map(Bytes::from))
+    }
+
+    #[inline]
+    async fn remove(&self, key: &[u8]) -> crate::kernel::Result<()> {
+        self.write(CommandData::remove(key.to_vec())).await
+    }
+
+    /// 先发送缓冲的写入以保证顺序，再以单次请求发送整个批次
+    #[inline]
+    async fn batch(&self, vec_cmd: Vec<CommandData>) -> crate::kernel::Result<Vec<Option<Vec<u8>>>> {
+        let mut inner = self.inner.lock().await;
+
+        inner.send_pending().await?;
+        Ok(inner.client.batch(vec_cmd).await?)
+    }
+
+    #[inline]
+    async fn size_of_disk(&self) -> crate::kernel::Result<u64> {
+        Ok(self.inner.lock().await.client.size_of_disk().await?)
+    }
+
+    /// 缓冲中的写入发送后才会计入
+    #[inline]
+    async fn len(&self) -> crate::kernel::Result<usize> {
+        Ok(self.inner.lock().await.client.len().await?)
+    }
+
+    /// 连接异常时视为空
+    #[inline]
+    async fn is_empty(&self) -> bool {
+        self.len().await
+            .map_or(true, |len| len == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use bytes::Bytes;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::{CommandData, KVStore, Result};
+    use crate::net::remote::{pending_value, RemoteStore};
+    use crate::net::server::{self, ServerConfig};
+
+    #[test]
+    fn test_pending_value() {
+        let pending = vec![
+            CommandData::set(b"k1".to_vec(), b"v1".to_vec()),
+            CommandData::set(b"k2".to_vec(), b"v2".to_vec()),
+            CommandData::remove(b"k1".to_vec()),
+            CommandData::set(b"k2".to_vec(), b"v3".to_vec()),
+        ];
+
+        assert_eq!(pending_value(&pending, b"k1"), Some(None));
+        assert_eq!(pending_value(&pending, b"k2"), Some(Some(Bytes::from_static(b"v3"))));
+        assert_eq!(pending_value(&pending, b"k3"), None);
+    }
+
+    #[test]
+    fn test_remote_read_fallback() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            let config = ServerConfig::default()
+                .data_dir(temp_dir.path().join("replica"))
+                .namespaces_dir(temp_dir.path().join("namespaces"));
+            let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+            let server = tokio::spawn(server::run_with_config(listener, config, shutdown_rx));
+
+            let replica = RemoteStore::connect(addr).await?;
+            for (key, value) in [(b"k1", b"v1"), (b"k2", b"v2"), (b"k3", b"v3"), (b"k9", b"v9")] {
+                replica.set(key, Bytes::from_static(value)).await?;
+            }
+
+            let local_path = temp_dir.path().join("local");
+            let kv_store = LsmStore::open(&local_path).await?;
+            for (key, value) in [(b"k1", b"v1"), (b"k2", b"v2"), (b"k3", b"v3")] {
+                kv_store.set(key, Bytes::from_static(value)).await?;
+            }
+            kv_store.flush().await?;
+            let ss_table = kv_store.current_version().await
+                .get_ss_tables_for_level(0).await
+                .remove(0);
+            let (gen, path) = (ss_table.get_gen(), ss_table.get_path());
+            drop(ss_table);
+            drop(kv_store);
+
+            // 模拟位翻转: 破坏SSTable的首个数据Block
+            let mut bytes = fs::read(&path)?;
+            bytes[8] = !bytes[8];
+            fs::write(&path, bytes)?;
+
+            let config = Config::new(&local_path)
+                .quarantine_corrupted(true)
+                .rebuild_quarantined(true)
+                .read_fallback(Arc::new(replica));
+            let kv_store = LsmStore::open_with_config(config).await?;
+            assert_eq!(kv_store.get(b"k2").await?, Some(Bytes::from_static(b"v2")));
+
+            // 丢失的范围经由网络分页扫描取回，范围外的Key不会被写入
+            let report = kv_store.quarantine(gen).await?;
+            assert_eq!((report.readable_len, report.repaired_len), (0, 3));
+            assert!(report.rebuilt_gen.is_some());
+
+            // 副本下线后仍可由本地读取修复的数据
+            let _ignore = shutdown_tx.send(());
+            server.await.expect("server task panicked")?;
+            assert_eq!(kv_store.get(b"k1").await?, Some(Bytes::from_static(b"v1")));
+            assert_eq!(kv_store.get(b"k3").await?, Some(Bytes::from_static(b"v3")));
+            assert_eq!(kv_store.get(b"k9").await?, None);
+
+            Ok(())
+        })
+    }
+}
@@ -1,27 +1,299 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::future::Future;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 use bytes::Bytes;
 use chrono::Local;
+use futures::future;
 use itertools::Itertools;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time;
-use tracing::{error, info};
+use tracing::{error, field, info, info_span, Instrument, Span};
 use prost::Message;
 use crate::kernel::{ByteUtils, CommandData, KVStore, options_none};
-use crate::kernel::lsm::lsm_kv::LsmStore;
+use crate::error::ConnectionError;
+use crate::KernelError;
+use crate::kernel::lsm::lsm_kv::{LsmStore, ScanCursor};
+use crate::kernel::lsm::lease::Leases;
+use crate::kernel::lsm::options::WriteOptions;
+use crate::kernel::lsm::structures::Structures;
 use crate::net::connection::Connection;
-use crate::net::{key_value_from_option, kv_encode_with_len, Result};
+use crate::net::namespace::{DEFAULT_MAX_NAMESPACES, DEFAULT_NAMESPACE_DIR, DEFAULT_NAMESPACES_DIR, Namespace, Namespaces};
+use crate::net::priority::PriorityScheduler;
+use crate::kernel::utils::quota::{Quota, QuotaLimiter};
+use crate::net::{ack_from_option, COMPRESSION_THRESHOLD, FEATURE_COMPRESSION, FEATURE_LENGTH_DELIMITED, handshake_from_option, key_value_from_option, kv_encode_with_len, negotiate, option_from_batch_chunk, option_from_handshake, option_from_keyspace_event, option_from_rejection, option_from_scan_page, option_from_struct_reply, Result, ServerInfo, TraceContext};
 use crate::net::shutdown::Shutdown;
-use crate::proto::net_pb::{CommandOption, KeyValue};
+use crate::proto::net_pb::{BatchItemResult, BatchItemStatus, BatchResultChunk, CommandOption, ConditionalOp, ConditionalWrite, KeyValue, LeaseCommand, LeaseOp, OptionType, ScanPage, SelectNamespace, StructCommand, StructOp, StructReply};
 
-const MAX_CONNECTIONS: usize = 250;
+const DEFAULT_MAX_CONNECTIONS: usize = 250;
+
+const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// 进行计数的指令类型数量，即OptionType的取值上限
+const COMMAND_TYPE_SIZE: usize = 22;
+
+/// 超出配额时响应的指令类型
+const THROTTLED_TYPE: i32 = OptionType::Throttled as i32;
+
+/// 未应用读取所要求的Sequence时响应的指令类型
+const STALE_TYPE: i32 = OptionType::Stale as i32;
+
+/// 写入被写入钩子拒绝时响应的指令类型
+const REJECTED_TYPE: i32 = OptionType::Rejected as i32;
+
+/// 订阅Key空间时推送的指令类型
+const WATCH_TYPE: i32 = OptionType::Watch as i32;
+
+/// 流式批量处理中每次响应的指令结果数量
+const BATCH_STREAM_CHUNK_LEN: usize = 1024;
+
+const DEFAULT_SEQUENCE_WAIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+const DEFAULT_BACKGROUND_MAX_DELAY: Duration = Duration::from_millis(100);
+
+/// 服务端配置
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// 最大连接数
+    pub(crate) max_connections: usize,
+    /// 连接等待下一个请求的超时时长，为None时不限制
+    pub(crate) idle_timeout: Option<Duration>,
+    /// 写入响应的超时时长，为None时不限制
+    /// 读取缓慢的客户端会使响应积压于发送缓冲区，超时后断开该连接
+    pub(crate) write_timeout: Option<Duration>,
+    /// 单帧的大小上限，超出时断开该连接
+    pub(crate) max_frame_size: usize,
+    /// 默认命名空间的数据目录
+    pub(crate) data_dir: PathBuf,
+    /// 其余命名空间的数据目录，各命名空间以其名称作为子目录
+    pub(crate) namespaces_dir: PathBuf,
+    /// 通过`declare_namespace`声明的命名空间
+    pub(crate) declared_namespaces: HashSet<String>,
+    /// 可自动创建的未声明命名空间的数量上限
+    pub(crate) max_namespaces: usize,
+    /// 命名空间 -> 允许访问的令牌
+    pub(crate) namespace_tokens: HashMap<String, HashSet<String>>,
+    /// 每个连接的配额，为None时不限制
+    pub(crate) connection_quota: Option<Quota>,
+    /// 命名空间 -> 该命名空间内所有连接共享的配额
+    pub(crate) namespace_quotas: HashMap<String, Quota>,
+    /// 命名空间 -> 该命名空间内所有连接共享的写入配额
+    pub(crate) namespace_write_quotas: HashMap<String, Quota>,
+    /// 读取等待服务端应用其所要求的Sequence的时长
+    pub(crate) sequence_wait_timeout: Duration,
+    /// 所有后台请求共享的配额，为None时不限制
+    pub(crate) background_quota: Option<Quota>,
+    /// 后台请求等待前台请求完成的最长时长
+    pub(crate) background_max_delay: Duration,
+}
+
+impl Default for ServerConfig {
+    #[inline]
+    fn default() -> Self {
+        ServerConfig {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            idle_timeout: None,
+            write_timeout: None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            data_dir: PathBuf::from(DEFAULT_NAMESPACE_DIR),
+            namespaces_dir: PathBuf::from(DEFAULT_NAMESPACES_DIR),
+            declared_namespaces: HashSet::new(),
+            max_namespaces: DEFAULT_MAX_NAMESPACES,
+            namespace_tokens: HashMap::new(),
+            connection_quota: None,
+            namespace_quotas: HashMap::new(),
+            namespace_write_quotas: HashMap::new(),
+            sequence_wait_timeout: DEFAULT_SEQUENCE_WAIT_TIMEOUT,
+            background_quota: None,
+            background_max_delay: DEFAULT_BACKGROUND_MAX_DELAY,
+        }
+    }
+}
+
+impl ServerConfig {
+    #[inline]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    #[inline]
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    #[inline]
+    pub fn write_timeout(mut self, write_timeout: Duration) -> Self {
+        self.write_timeout = Some(write_timeout);
+        self
+    }
+
+    #[inline]
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// 设置默认命名空间的数据目录
+    #[inline]
+    pub fn data_dir(mut self, data_dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = data_dir.into();
+        self
+    }
+
+    /// 设置其余命名空间的数据目录，各命名空间以其名称作为子目录
+    #[inline]
+    pub fn namespaces_dir(mut self, namespaces_dir: impl Into<PathBuf>) -> Self {
+        self.namespaces_dir = namespaces_dir.into();
+        self
+    }
+
+    /// 声明命名空间，已声明的命名空间不计入`max_namespaces`
+    ///
+    /// 配置了令牌或配额的命名空间同样视为已声明
+    #[inline]
+    pub fn declare_namespace(mut self, namespace: impl Into<String>) -> Self {
+        let _ = self.declared_namespaces.insert(namespace.into());
+        self
+    }
+
+    /// 设置可自动创建的未声明命名空间的数量上限，为0时仅可选择已声明的命名空间
+    #[inline]
+    pub fn max_namespaces(mut self, max_namespaces: usize) -> Self {
+        self.max_namespaces = max_namespaces;
+        self
+    }
+
+    /// 为命名空间添加允许访问的令牌
+    ///
+    /// 配置令牌后仅持有其中之一的连接可以选择该命名空间，
+    /// 默认命名空间配置令牌后，连接需先以令牌选择默认命名空间才可执行其余指令
+    #[inline]
+    pub fn namespace_token(mut self, namespace: impl Into<String>, token: impl Into<String>) -> Self {
+        let _ = self.namespace_tokens
+            .entry(namespace.into())
+            .or_default()
+            .insert(token.into());
+        self
+    }
+
+    /// 设置每个连接的配额
+    #[inline]
+    pub fn connection_quota(mut self, quota: Quota) -> Self {
+        self.connection_quota = Some(quota);
+        self
+    }
+
+    /// 设置命名空间的配额，由该命名空间内的所有连接共享
+    #[inline]
+    pub fn namespace_quota(mut self, namespace: impl Into<String>, quota: Quota) -> Self {
+        let _ = self.namespace_quotas.insert(namespace.into(), quota);
+        self
+    }
+
+    /// 设置命名空间的写入配额，仅计入写入类请求，由该命名空间内的所有连接共享
+    ///
+    /// 各命名空间共享同一磁盘，以此限制单个命名空间的写入速率与写入量，
+    /// 避免其大量写入所引发的Flush与压缩占用其余命名空间的磁盘带宽，
+    /// 配额中的请求数为写入类请求数，字节数仅为请求的字节数，因此写入量越大的请求消耗越多的配额
+    #[inline]
+    pub fn namespace_write_quota(mut self, namespace: impl Into<String>, quota: Quota) -> Self {
+        let _ = self.namespace_write_quotas.insert(namespace.into(), quota);
+        self
+    }
+
+    /// 设置读取等待服务端应用其所要求的Sequence的时长，超时后响应Stale
+    #[inline]
+    pub fn sequence_wait_timeout(mut self, sequence_wait_timeout: Duration) -> Self {
+        self.sequence_wait_timeout = sequence_wait_timeout;
+        self
+    }
+
+    /// 设置所有后台请求共享的配额，通常应严于连接配额，以免备份、导出等任务影响业务请求
+    #[inline]
+    pub fn background_quota(mut self, quota: Quota) -> Self {
+        self.background_quota = Some(quota);
+        self
+    }
+
+    /// 设置后台请求等待前台请求完成的最长时长
+    #[inline]
+    pub fn background_max_delay(mut self, background_max_delay: Duration) -> Self {
+        self.background_max_delay = background_max_delay;
+        self
+    }
+}
+
+/// 以OptionType的值为索引的指令计数
+#[derive(Default)]
+pub(crate) struct CommandCounts([AtomicU64; COMMAND_TYPE_SIZE]);
+
+impl CommandCounts {
+    pub(crate) fn record(&self, type_num: i32) {
+        if let Some(counter) = usize::try_from(type_num).ok()
+            .and_then(|index| self.0.get(index))
+        {
+            let _ = counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 以指令名称为Key的计数，忽略未出现过的指令
+    fn to_map(&self) -> BTreeMap<String, u64> {
+        self.0.iter()
+            .enumerate()
+            .filter_map(|(type_num, counter)| {
+                let count = counter.load(Ordering::Relaxed);
+                (count > 0)
+                    .then(|| OptionType::from_i32(type_num as i32))
+                    .flatten()
+                    .map(|option_type| (format!("{option_type:?}"), count))
+            })
+            .collect()
+    }
+}
+
+/// 服务端统计数据
+struct ServerStats {
+    start: Instant,
+    connected_clients: AtomicUsize,
+    command_counts: CommandCounts,
+}
+
+impl ServerStats {
+    fn new() -> Self {
+        ServerStats {
+            start: Instant::now(),
+            connected_clients: AtomicUsize::new(0),
+            command_counts: CommandCounts::default(),
+        }
+    }
+
+    /// 服务端信息，其中存储统计数据为当前命名空间的数据
+    async fn info(&self, namespace: &Namespace) -> ServerInfo {
+        ServerInfo {
+            uptime_secs: self.start.elapsed().as_secs(),
+            connected_clients: self.connected_clients.load(Ordering::Relaxed),
+            command_counts: self.command_counts.to_map(),
+            namespace: namespace.name.clone(),
+            namespace_command_counts: namespace.command_counts.to_map(),
+            store: namespace.kv_store.statistics().await,
+            namespace_writes: namespace.write_stats.snapshot(),
+        }
+    }
+}
 
 /// 服务器监听器
 /// 用于监听端口的连接并分发给Handler进行多线程处理连接
 pub struct Listener {
-    kv_store_root: Arc<LsmStore>,
+    namespaces: Arc<Namespaces>,
+    config: ServerConfig,
+    stats: Arc<ServerStats>,
+    scheduler: Arc<PriorityScheduler>,
     listener: TcpListener,
     limit_connections: Arc<Semaphore>,
     notify_shutdown: broadcast::Sender<()>,
@@ -32,23 +304,52 @@ pub struct Listener {
 /// 连接处理器
 /// 用于每个连接的响应处理
 struct Handler {
-    kv_store: Arc<LsmStore>,
+    namespaces: Arc<Namespaces>,
+    /// 当前所选择的命名空间
+    namespace: Arc<Namespace>,
+    /// 是否可访问当前所选择的命名空间，默认命名空间配置令牌时需先进行选择
+    is_authorized: bool,
+    stats: Arc<ServerStats>,
+    scheduler: Arc<PriorityScheduler>,
     connection: Connection,
     shutdown: Shutdown,
+    idle_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    sequence_wait_timeout: Duration,
+    /// 握手协商的协议版本，未握手的旧客户端为0
+    protocol_version: u32,
+    /// 握手协商启用的功能
+    features: u64,
+    /// 该连接的配额限制器
+    limiter: Option<QuotaLimiter>,
+    /// 批量导入中上一分块的最后一个Key，用于保证分块之间有序
+    bulk_load_last_key: Option<Bytes>,
+    /// 当前处理的请求是否为后台请求
+    is_background: bool,
     // 用于与Listener保持连接而感应是否全部关闭
     _shutdown_complete: mpsc::Sender<()>
 }
 
 #[inline]
 pub async fn run(listener: TcpListener, shutdown: impl Future) -> Result<()> {
-    let kv_store_root = Arc::new(LsmStore::open("./data").await?);
+    run_with_config(listener, ServerConfig::default(), shutdown).await
+}
+
+#[inline]
+pub async fn run_with_config(listener: TcpListener, config: ServerConfig, shutdown: impl Future) -> Result<()> {
+    let namespaces = Arc::new(Namespaces::open(&config).await?);
+    let limit_connections = Arc::new(Semaphore::new(config.max_connections));
+    let scheduler = Arc::new(PriorityScheduler::new(config.background_quota, config.background_max_delay));
     let (notify_shutdown, _) = broadcast::channel(1);
     let (shutdown_complete_tx, shutdown_complete_rx) = mpsc::channel(1);
 
     let mut server = Listener {
         listener,
-        kv_store_root,
-        limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
+        namespaces,
+        config,
+        stats: Arc::new(ServerStats::new()),
+        scheduler,
+        limit_connections,
         notify_shutdown,
         shutdown_complete_tx,
         shutdown_complete_rx,
@@ -61,7 +362,7 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) -> Result<()> {
             }
         }
         _ = shutdown => {
-            server.kv_store_root.flush().await?;
+            server.namespaces.flush().await?;
             info!("[Listener][Shutting Down]");
         }
     }
@@ -91,7 +392,6 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) -> Result<()> {
 }
 
 impl Listener {
-    #[allow(clippy::unwrap_used)]
     async fn run(&mut self) -> Result<()> {
         info!("[Listener][Inbound Connections]");
         loop {
@@ -99,24 +399,38 @@ impl Listener {
                 .limit_connections)
                 .acquire_owned()
                 .await
-                .unwrap();
+                .map_err(|_| KernelError::Internal("connection limiter closed".to_owned()))?;
 
             let socket = self.accept().await?;
             let addr = socket.peer_addr()?;
 
             let mut handler = Handler {
-                kv_store: Arc::clone(&self.kv_store_root),
-                connection: Connection::new(socket),
+                namespace: Arc::clone(self.namespaces.default_namespace()),
+                is_authorized: self.namespaces.is_default_open(),
+                namespaces: Arc::clone(&self.namespaces),
+                stats: Arc::clone(&self.stats),
+                scheduler: Arc::clone(&self.scheduler),
+                connection: Connection::with_max_frame_size(socket, self.config.max_frame_size),
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
+                idle_timeout: self.config.idle_timeout,
+                write_timeout: self.config.write_timeout,
+                sequence_wait_timeout: self.config.sequence_wait_timeout,
+                protocol_version: 0,
+                features: 0,
+                limiter: self.config.connection_quota.map(QuotaLimiter::new),
+                bulk_load_last_key: None,
+                is_background: false,
                 _shutdown_complete: self.shutdown_complete_tx.clone()
             };
 
             let _ignore = tokio::spawn(async move {
                 info!("[Listener][New Connection][Time: {}][Ip Addr]: {}", Local::now(), &addr);
                 let start = Instant::now();
+                let _ = handler.stats.connected_clients.fetch_add(1, Ordering::Relaxed);
                 if let Err(err) = handler.run().await {
                     error!(cause = ?err,"[Listener][Handler Running Error]");
                 }
+                let _ = handler.stats.connected_clients.fetch_sub(1, Ordering::Relaxed);
                 drop(permit);
                 info!("[Listener][Connection Drop][Time: {:?}]", start.elapsed());
             });
@@ -153,7 +467,7 @@ impl Handler {
     async fn run(&mut self) -> Result<()> {
         while !self.shutdown.is_shutdown() {
             let client_option: CommandOption = tokio::select! {
-                res = self.connection.read() => res?,
+                res = with_timeout(self.idle_timeout, self.connection.read()) => res?,
                 _ = self.shutdown.recv() => {
                     // If a shutdown signal is received, return from `run`.
                     // This will result in the task terminating.
@@ -161,76 +475,536 @@ impl Handler {
                 }
             };
 
-            match client_option.r#type {
-                0 => {
-                    // 不使用`CommandData::apply`是因为避免value的内存移动开销
-                    let KeyValue { key, value, r#type } = key_value_from_option(&client_option)?;
-                    let res_option = match r#type {
-                        1 => {
-                            self.kv_store.set(&key, Bytes::from(value)).await.map(|_| options_none())?
-                        }
-                        2 => {
-                            self.kv_store.remove(&key).await.map(|_| options_none())?
-                        }
-                        _ => {
-                            self.kv_store.get(&key).await.map(CommandOption::from)?
+            let span = command_span(&client_option);
+            match self.process(client_option).instrument(span).await {
+                Ok(true) => (),
+                Ok(false) => break,
+                // 被拒绝的写入不影响连接，以Rejected响应告知客户端拒绝的原因
+                Err(ConnectionError::StoreErr(KernelError::WriteRejected(rejection))) => {
+                    self.stats.command_counts.record(REJECTED_TYPE);
+                    self.write(option_from_rejection(&rejection)?).await?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 处理单个请求，返回false时表示客户端请求断开连接
+    async fn process(&mut self, client_option: CommandOption) -> Result<bool> {
+        // 未选择命名空间前仅允许握手、断开与选择命名空间
+        if !self.is_authorized && !matches!(client_option.r#type, 7 | 9 | 12) {
+            return Err(ConnectionError::PermissionDenied);
+        }
+        self.is_background = client_option.background;
+        // 握手与断开不受配额限制
+        let is_write = is_write_request(&client_option);
+        if !matches!(client_option.r#type, 7 | 9) {
+            let wait_time = self.throttle(client_option.bytes.len(), is_write)
+                .or_else(|| {
+                    self.is_background
+                        .then(|| self.scheduler.throttle(client_option.bytes.len()))
+                        .flatten()
+                });
+            if let Some(wait_time) = wait_time {
+                self.stats.command_counts.record(THROTTLED_TYPE);
+                self.namespace.command_counts.record(THROTTLED_TYPE);
+                if is_write {
+                    self.namespace.write_stats.record_throttled();
+                }
+                // 以value告知客户端需等待的毫秒数
+                self.value_options(wait_time.as_millis() as u64, THROTTLED_TYPE).await?;
+                return Ok(true);
+            }
+        }
+        let scheduler = Arc::clone(&self.scheduler);
+        let _foreground = if self.is_background {
+            scheduler.wait_for_foreground().await;
+            None
+        } else {
+            Some(scheduler.enter_foreground())
+        };
+        self.stats.command_counts.record(client_option.r#type);
+        self.namespace.command_counts.record(client_option.r#type);
+        if is_write {
+            self.namespace.write_stats.record(client_option.bytes.len());
+        }
+        match client_option.r#type {
+            0 => {
+                // 不使用`CommandData::apply`是因为避免value的内存移动开销
+                let KeyValue { key, value, r#type } = key_value_from_option(&client_option)?;
+                let kv_store = &self.namespace.kv_store;
+                let res_option = match r#type {
+                    1 => {
+                        let options = WriteOptions::default().ack(ack_from_option(&client_option)?);
+                        kv_store.set_with_options(&key, Bytes::from(value), &options).await.map(option_with_sequence)?
+                    }
+                    2 => {
+                        let options = WriteOptions::default().ack(ack_from_option(&client_option)?);
+                        kv_store.remove_with_options(&key, &options).await.map(option_with_sequence)?
+                    }
+                    _ => {
+                        // 读取所要求的Sequence未被应用时不进行读取，避免读到旧数据
+                        if client_option.sequence > 0
+                            && !kv_store.wait_for_sequence(client_option.sequence, self.sequence_wait_timeout).await {
+                            let mut stale_option = options_none();
+                            stale_option.r#type = STALE_TYPE;
+                            stale_option.sequence = kv_store.applied_sequence();
+
+                            self.write(stale_option).await?;
+                            return Ok(true);
                         }
-                    };
+                        kv_store.get(&key).await.map(CommandOption::from)?
+                    }
+                };
 
-                    self.connection.write(res_option).await?;
+                self.write(res_option).await?;
+            }
+            1 => {
+                let vec_cmd = ByteUtils::sharding_tag_bytes(&client_option.bytes)
+                    .into_iter()
+                    .filter_map(|vec_u8| {
+                        KeyValue::decode(vec_u8).ok()
+                            .map(CommandData::from)
+                    })
+                    .collect();
+                let bytes = self.namespace.kv_store.batch(vec_cmd).await?
+                    .into_iter()
+                    .filter_map(|value_option| {
+                        let key_value = KeyValue {
+                            key: vec![],
+                            value: value_option.map_or(vec![], |value| value),
+                            r#type: 1,
+                        };
+                        kv_encode_with_len(&key_value).ok()
+                    })
+                    .flatten()
+                    .collect_vec();
+                self.write(CommandOption { r#type: 1, bytes, value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 }).await?;
+            }
+            4 => {
+                let size_of_disk = self.namespace.kv_store.size_of_disk().await?;
+                self.value_options(size_of_disk, 4).await?;
+            }
+            5 => {
+                let len = self.namespace.kv_store.len().await? as u64;
+                self.value_options(len, 5).await?;
+            }
+            6 => {
+                self.namespace.kv_store.flush().await?;
+                self.write(CommandOption { r#type: 6, bytes: vec![], value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 }).await?;
+            }
+            7 => {
+                return Ok(false);
+            }
+            8 => {
+                // 此处与客户端呼应使用value传递i64的补码
+                let value = self.namespace.kv_store
+                    .increment(&client_option.bytes, client_option.value as i64).await?;
+                self.value_options(value as u64, 8).await?;
+            }
+            9 => {
+                let negotiated = negotiate(&handshake_from_option(&client_option)?);
+                self.protocol_version = negotiated.version;
+                self.features = negotiated.features;
+                info!(
+                    "[Handler][Handshake][Version: {}][Features: {:#b}]",
+                    self.protocol_version, self.features
+                );
+
+                self.write(option_from_handshake(&negotiated)?).await?;
+                // 握手响应本身不压缩，以便客户端在确认协商结果后再启用
+                if self.features & FEATURE_COMPRESSION != 0 {
+                    self.connection.enable_compression(COMPRESSION_THRESHOLD);
                 }
-                1 => {
-                    let vec_cmd = ByteUtils::sharding_tag_bytes(&client_option.bytes)
-                        .into_iter()
-                        .filter_map(|vec_u8| {
-                            KeyValue::decode(vec_u8).ok()
-                                .map(CommandData::from)
-                        })
-                        .collect();
-                    let bytes = self.kv_store.batch(vec_cmd).await?
-                        .into_iter()
-                        .filter_map(|value_option| {
-                            let key_value = KeyValue {
-                                key: vec![],
-                                value: value_option.map_or(vec![], |value| value),
-                                r#type: 1,
-                            };
-                            kv_encode_with_len(&key_value).ok()
+                if self.features & FEATURE_LENGTH_DELIMITED != 0 {
+                    self.connection.enable_length_delimited();
+                }
+            }
+            10 => {
+                let info = self.stats.info(&self.namespace).await;
+                let bytes = serde_json::to_vec(&info)
+                    .map_err(|_| ConnectionError::EncodeErr)?;
+
+                self.write(CommandOption { r#type: 10, bytes, value: 0, compressed: false, trace_context: String::new(), sequence: 0, background: false, ack: 0 }).await?;
+            }
+            11 => {
+                let cursor = (!client_option.bytes.is_empty())
+                    .then(|| ScanCursor::from_bytes(&client_option.bytes))
+                    .transpose()?;
+                let (items, next_cursor) = self.namespace.kv_store
+                    .scan_page(cursor.as_ref(), client_option.value as usize).await?;
+                let scan_page = ScanPage {
+                    items: items.into_iter()
+                        .map(|(key, value)| KeyValue {
+                            key: key.to_vec(),
+                            value: value.to_vec(),
+                            r#type: 1,
                         })
-                        .flatten()
-                        .collect_vec();
-                    self.connection.write(CommandOption { r#type: 1, bytes, value: 0 }).await?;
+                        .collect(),
+                    cursor: next_cursor.map(|cursor| cursor.to_bytes())
+                        .transpose()?
+                        .unwrap_or_default(),
+                };
+
+                self.write(option_from_scan_page(&scan_page)?).await?;
+            }
+            12 => {
+                let SelectNamespace { name, token } = SelectNamespace::decode(&*client_option.bytes)
+                    .map_err(|_| ConnectionError::DecodeErr)?;
+                // 拒绝时保留原命名空间，并以value为0告知客户端
+                let selected = match self.namespaces.select(&name, &token).await {
+                    Ok(namespace) => {
+                        self.namespace = namespace;
+                        self.is_authorized = true;
+                        1
+                    }
+                    Err(ConnectionError::PermissionDenied) => {
+                        info!("[Handler][Select Denied][Namespace: {}]", name);
+                        0
+                    }
+                    Err(err) => return Err(err),
+                };
+
+                self.value_options(selected, 12).await?;
+            }
+            14 => {
+                if client_option.value == 1 {
+                    self.bulk_load_last_key = None;
                 }
-                4 => {
-                    let size_of_disk = self.kv_store.size_of_disk().await?;
-                    self.value_options(size_of_disk, 4).await?;
+                let data = ByteUtils::sharding_tag_bytes(&client_option.bytes)
+                    .into_iter()
+                    .map(|vec_u8| {
+                        KeyValue::decode(vec_u8)
+                            .map(|KeyValue { key, value, .. }| (Bytes::from(key), Bytes::from(value)))
+                            .map_err(|_| ConnectionError::DecodeErr)
+                    })
+                    .try_collect::<_, Vec<_>, _>()?;
+                // 分块之间同样需要有序
+                if let (Some(last_key), Some((first_key, _))) = (&self.bulk_load_last_key, data.first()) {
+                    if last_key >= first_key {
+                        return Err(KernelError::IngestNotSorted.into());
+                    }
                 }
-                5 => {
-                    let len = self.kv_store.len().await? as u64;
-                    self.value_options(len, 5).await?;
+                let len = data.len();
+                if let Some((last_key, _)) = data.last() {
+                    self.bulk_load_last_key = Some(last_key.clone());
                 }
-                6 => {
-                    self.kv_store.flush().await?;
-                    self.connection.write(CommandOption { r#type: 6, bytes: vec![], value: 0 }).await?;
+
+                self.namespace.kv_store.ingest(data).await?;
+                self.value_options(len as u64, 14).await?;
+            }
+            16 => {
+                let struct_command = StructCommand::decode(&*client_option.bytes)
+                    .map_err(|_| ConnectionError::DecodeErr)?;
+                let struct_reply = apply_struct_command(
+                    self.namespace.kv_store.structures(),
+                    struct_command
+                ).await?;
+
+                self.write(option_from_struct_reply(&struct_reply)?).await?;
+            }
+            17 => {
+                let lease_command = LeaseCommand::decode(&*client_option.bytes)
+                    .map_err(|_| ConnectionError::DecodeErr)?;
+                let value = apply_lease_command(self.namespace.kv_store.leases(), lease_command).await?;
+
+                self.value_options(value, 17).await?;
+            }
+            18 => {
+                let ConditionalWrite { op, key, expected, value } = ConditionalWrite::decode(&*client_option.bytes)
+                    .map_err(|_| ConnectionError::DecodeErr)?;
+                let (expected, new) = match ConditionalOp::from_i32(op).ok_or(ConnectionError::DecodeErr)? {
+                    ConditionalOp::SetIfAbsent => (None, Some(Bytes::from(value))),
+                    ConditionalOp::SetIfEquals => (Some(expected), Some(Bytes::from(value))),
+                    ConditionalOp::DeleteIfEquals => (Some(expected), None),
+                };
+                let swapped = self.namespace.kv_store
+                    .compare_and_swap(&key, expected.as_deref(), new).await?;
+
+                self.value_options(u64::from(swapped), 18).await?;
+            }
+            19 => {
+                // 逐段执行并响应，使内存占用与批次大小无关
+                let namespace = Arc::clone(&self.namespace);
+                let items = ByteUtils::sharding_tag_bytes(&client_option.bytes);
+                let mut chunks = items.chunks(BATCH_STREAM_CHUNK_LEN).peekable();
+
+                loop {
+                    let chunk = chunks.next().unwrap_or_default();
+                    let results = future::join_all(
+                        chunk.iter().map(|bytes| batch_item_result(&namespace.kv_store, bytes))
+                    ).await;
+                    let is_last = chunks.peek().is_none();
+
+                    self.write(option_from_batch_chunk(&BatchResultChunk { results }, is_last)?).await?;
+                    if is_last {
+                        break
+                    }
                 }
-                7 => {
-                    break;
+            }
+            21 => {
+                // 此后该连接仅用于推送通知，客户端断开或发送任意请求时结束
+                let mut rx = self.namespace.kv_store.subscribe_keyspace();
+                loop {
+                    let option = tokio::select! {
+                        event = rx.recv() => match event {
+                            Ok(event) => option_from_keyspace_event(&event)?,
+                            Err(RecvError::Lagged(skipped)) => CommandOption {
+                                r#type: WATCH_TYPE,
+                                value: skipped,
+                                ..options_none()
+                            },
+                            Err(RecvError::Closed) => return Ok(false),
+                        },
+                        _ = self.connection.read() => return Ok(false),
+                        _ = self.shutdown.recv() => return Ok(false),
+                    };
+                    self.write(option).await?;
                 }
-                _ => {}
             }
+            _ => {}
         }
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// 检查连接与命名空间的配额，写入类请求同时检查命名空间的写入配额，
+    /// 超出配额时返回需等待的时长，否则记录该请求
+    fn throttle(&mut self, bytes_len: usize, is_write: bool) -> Option<Duration> {
+        let mut namespace_limiter = self.namespace.limiter.as_ref()
+            .map(parking_lot::Mutex::lock);
+        let mut write_limiter = self.namespace.write_limiter.as_ref()
+            .filter(|_| is_write)
+            .map(parking_lot::Mutex::lock);
+        let wait_time = self.limiter.as_mut()
+            .and_then(QuotaLimiter::wait_time)
+            .max(namespace_limiter.as_mut().and_then(|limiter| limiter.wait_time()))
+            .max(write_limiter.as_mut().and_then(|limiter| limiter.wait_time()));
+
+        if wait_time.is_none() {
+            if let Some(limiter) = &mut self.limiter {
+                limiter.consume_request(bytes_len);
+            }
+            if let Some(limiter) = &mut namespace_limiter {
+                limiter.consume_request(bytes_len);
+            }
+            if let Some(limiter) = &mut write_limiter {
+                limiter.consume_request(bytes_len);
+            }
+        }
+
+        wait_time
+    }
+
+    /// 写入响应，超出write_timeout时视为读取缓慢的客户端并断开连接
+    async fn write(&mut self, option: CommandOption) -> Result<()> {
+        // 响应的数据同样计入字节配额
+        if let Some(limiter) = &mut self.limiter {
+            limiter.consume_bytes(option.bytes.len());
+        }
+        if let Some(limiter) = &self.namespace.limiter {
+            limiter.lock().consume_bytes(option.bytes.len());
+        }
+        if self.is_background {
+            self.scheduler.consume_bytes(option.bytes.len());
+        }
+        with_timeout(self.write_timeout, self.connection.write(option)).await
     }
 
     async fn value_options(&mut self, value: u64, type_num: i32) -> Result<()> {
-        self.connection.write(CommandOption {
+        self.write(CommandOption {
             r#type: type_num,
             bytes: vec![],
-            value
+            value,
+            compressed: false,
+            trace_context: String::new(),
+            sequence: 0,
+            background: false,
+            ack: 0,
         }).await?;
 
         Ok(())
     }
 }
 
+/// 执行流式批量处理中的单个指令，失败时以状态码表示而不中断整个批次
+async fn batch_item_result(kv_store: &LsmStore, bytes: &[u8]) -> BatchItemResult {
+    let cmd = match KeyValue::decode(bytes) {
+        Ok(key_value) => CommandData::from(key_value),
+        Err(err) => return BatchItemResult {
+            status: BatchItemStatus::Failed as i32,
+            error: err.to_string(),
+            ..BatchItemResult::default()
+        },
+    };
+    let is_get = matches!(cmd, CommandData::Get { .. });
+
+    match cmd.apply(kv_store).await.map(Option::<Vec<u8>>::from) {
+        Ok(Some(value)) => BatchItemResult { value, has_value: true, ..BatchItemResult::default() },
+        Ok(None) if !is_get => BatchItemResult::default(),
+        Ok(None) | Err(KernelError::KeyNotFound) => BatchItemResult {
+            status: BatchItemStatus::NotFound as i32,
+            ..BatchItemResult::default()
+        },
+        Err(err) => BatchItemResult {
+            status: BatchItemStatus::Failed as i32,
+            error: err.to_string(),
+            ..BatchItemResult::default()
+        },
+    }
+}
+
+/// 执行数据结构指令
+async fn apply_struct_command(structures: Structures<'_>, command: StructCommand) -> Result<StructReply> {
+    let StructCommand { op, name, items, delta, start, stop } = command;
+    let op = StructOp::from_i32(op)
+        .ok_or(ConnectionError::DecodeErr)?;
+    let members = || items.iter()
+        .map(Vec::as_slice)
+        .collect_vec();
+    let mut struct_reply = StructReply::default();
+
+    match op {
+        StructOp::CounterIncr => struct_reply.value = structures.counter_incr(&name, delta).await?,
+        StructOp::CounterGet => struct_reply.value = structures.counter_get(&name).await?,
+        StructOp::SetAdd => struct_reply.value = structures.set_add(&name, &members()).await? as i64,
+        StructOp::SetRemove => struct_reply.value = structures.set_remove(&name, &members()).await? as i64,
+        StructOp::SetContains => {
+            let member = items.first().map_or(&[][..], Vec::as_slice);
+            struct_reply.value = i64::from(structures.set_contains(&name, member).await?);
+        }
+        StructOp::SetMembers => {
+            struct_reply.items = structures.set_members(&name).await?
+                .into_iter()
+                .map(|member| member.to_vec())
+                .collect();
+        }
+        StructOp::ListAppend => {
+            let items = items.into_iter()
+                .map(Bytes::from)
+                .collect();
+            struct_reply.value = structures.list_append(&name, items).await? as i64;
+        }
+        StructOp::ListLen => struct_reply.value = structures.list_len(&name).await? as i64,
+        StructOp::ListRange => {
+            struct_reply.items = structures.list_range(&name, start, stop).await?
+                .into_iter()
+                .map(|item| item.to_vec())
+                .collect();
+        }
+    }
+
+    Ok(struct_reply)
+}
+
+/// 执行租约与锁的指令，结果以value表示，详见`LeaseOp`
+async fn apply_lease_command(leases: Leases<'_>, command: LeaseCommand) -> Result<u64> {
+    let LeaseCommand { op, id, ttl_ms, name } = command;
+    let op = LeaseOp::from_i32(op)
+        .ok_or(ConnectionError::DecodeErr)?;
+
+    Ok(match op {
+        LeaseOp::Grant => leases.grant(Duration::from_millis(ttl_ms)).await?.id as u64,
+        LeaseOp::KeepAlive => u64::from(leases.keep_alive(id).await?),
+        LeaseOp::Revoke => {
+            leases.revoke(id).await?;
+            0
+        }
+        LeaseOp::TimeToLive => leases.time_to_live(id).await?
+            .map_or(0, |remaining| remaining.as_millis() as u64),
+        // 租约过期属于预期内的结果，因此不作为错误断开连接
+        LeaseOp::TryLock => match leases.try_lock(&name, id).await {
+            Ok(locked) => u64::from(locked),
+            Err(KernelError::LeaseExpired(_)) => 2,
+            Err(err) => return Err(err.into()),
+        },
+        LeaseOp::Unlock => u64::from(leases.unlock(&name, id).await?),
+        LeaseOp::LockHolder => leases.lock_holder(&name).await?
+            .map_or(0, |holder| holder as u64),
+    })
+}
+
+/// 以请求所携带的链路上下文创建Span，使存储操作中的日志延续客户端的链路
+fn command_span(option: &CommandOption) -> Span {
+    let span = info_span!(
+        "command",
+        r#type = option.r#type,
+        trace_id = field::Empty,
+        parent_span_id = field::Empty
+    );
+
+    if let Some(trace_context) = TraceContext::parse(&option.trace_context) {
+        let _ = span.record("trace_id", trace_context.trace_id.as_str())
+            .record("parent_span_id", trace_context.parent_span_id.as_str());
+    }
+    span
+}
+
+/// 是否为计入命名空间写入配额的写入类请求
+///
+/// 数据结构与租约指令中读写混杂且写入量较小，因此不计入
+fn is_write_request(option: &CommandOption) -> bool {
+    match option.r#type {
+        0 => key_value_from_option(option)
+            .is_ok_and(|key_value| matches!(key_value.r#type, 1 | 2)),
+        1 | 8 | 14 | 18 | 19 => true,
+        _ => false,
+    }
+}
+
+/// 携带提交Sequence的写入响应
+fn option_with_sequence(sequence: i64) -> CommandOption {
+    let mut option = options_none();
+    option.sequence = sequence;
+    option
+}
+
+async fn with_timeout<T>(duration: Option<Duration>, future: impl Future<Output = Result<T>>) -> Result<T> {
+    match duration {
+        Some(duration) => time::timeout(duration, future).await
+            .map_err(|_| ConnectionError::Timeout)?,
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use crate::net::server::{is_write_request, CommandCounts};
+    use crate::proto::net_pb::{CommandOption, KeyValue};
+
+    #[test]
+    fn test_record_command() {
+        let command_counts = CommandCounts::default();
+
+        command_counts.record(0);
+        command_counts.record(0);
+        command_counts.record(10);
+        // 超出范围的指令类型不进行计数
+        command_counts.record(-1);
+        command_counts.record(1024);
+
+        let counts = command_counts.to_map();
+        assert_eq!(counts.get("Cmd"), Some(&2));
+        assert_eq!(counts.get("Info"), Some(&1));
+        assert_eq!(counts.values().sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn test_is_write_request() {
+        let cmd_option = |r#type| CommandOption {
+            r#type: 0,
+            bytes: KeyValue { key: b"k".to_vec(), value: vec![], r#type }.encode_to_vec(),
+            ..CommandOption::default()
+        };
+        let option = |r#type| CommandOption { r#type, ..CommandOption::default() };
+
+        assert!(!is_write_request(&cmd_option(0)));
+        assert!(is_write_request(&cmd_option(1)));
+        assert!(is_write_request(&cmd_option(2)));
+        assert!(is_write_request(&option(1)));
+        assert!(!is_write_request(&option(10)));
+    }
+}
@@ -0,0 +1,135 @@
+use std::marker::PhantomData;
+use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::error::ConnectionError;
+use crate::kernel::CommandData;
+use crate::net::client::Client;
+use crate::net::Result;
+
+/// Value的编解码方式
+pub trait ValueCodec {
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>>;
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T>;
+}
+
+/// 以bincode进行编解码
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+impl ValueCodec for BincodeCodec {
+    #[inline]
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+        bincode::serialize(value)
+            .map_err(|_| ConnectionError::EncodeErr)
+    }
+
+    #[inline]
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        bincode::deserialize(bytes)
+            .map_err(|_| ConnectionError::DecodeErr)
+    }
+}
+
+/// 以JSON进行编解码，便于与其他语言的客户端共享数据
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl ValueCodec for JsonCodec {
+    #[inline]
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+        serde_json::to_vec(value)
+            .map_err(|_| ConnectionError::EncodeErr)
+    }
+
+    #[inline]
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        serde_json::from_slice(bytes)
+            .map_err(|_| ConnectionError::DecodeErr)
+    }
+}
+
+/// 以指定编解码方式读写Value的客户端视图
+///
+/// 通过`Client::typed`获取
+#[allow(missing_debug_implementations)]
+pub struct TypedClient<'a, C> {
+    client: &'a mut Client,
+    _codec: PhantomData<C>,
+}
+
+impl<'a, C: ValueCodec> TypedClient<'a, C> {
+    pub(crate) fn new(client: &'a mut Client) -> Self {
+        TypedClient { client, _codec: PhantomData }
+    }
+
+    #[inline]
+    pub async fn get<T: DeserializeOwned>(&mut self, key: impl Into<Vec<u8>>) -> Result<Option<T>> {
+        self.client.get(key.into()).await?
+            .map(|bytes| C::decode(&bytes))
+            .transpose()
+    }
+
+    #[inline]
+    pub async fn set<T: Serialize + ?Sized>(&mut self, key: impl Into<Vec<u8>>, value: &T) -> Result<()> {
+        self.client.set(key.into(), C::encode(value)?).await
+    }
+
+    /// 批量获取
+    ///
+    /// 批量指令的响应无法区分不存在与空值，因此两者均视为None
+    #[inline]
+    pub async fn get_many<T: DeserializeOwned>(&mut self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<T>>> {
+        let vec_cmd = keys.into_iter()
+            .map(|key| CommandData::Get { key })
+            .collect_vec();
+
+        self.client.batch(vec_cmd).await?
+            .into_iter()
+            .map(|option| {
+                option.filter(|bytes| !bytes.is_empty())
+                    .map(|bytes| C::decode(&bytes))
+                    .transpose()
+            })
+            .try_collect()
+    }
+
+    /// 批量写入
+    #[inline]
+    pub async fn set_many<T: Serialize>(&mut self, items: &[(Vec<u8>, T)]) -> Result<()> {
+        let vec_cmd = items.iter()
+            .map(|(key, value)| {
+                C::encode(value).map(|value| CommandData::Set { key: key.clone(), value })
+            })
+            .try_collect()?;
+
+        let _ignore = self.client.batch(vec_cmd).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use crate::net::Result;
+    use crate::net::typed::{BincodeCodec, JsonCodec, ValueCodec};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        id: u64,
+        name: String,
+    }
+
+    #[test]
+    fn test_value_codec() -> Result<()> {
+        let user = User { id: 1, name: "kip".to_owned() };
+
+        assert_eq!(BincodeCodec::decode::<User>(&BincodeCodec::encode(&user)?)?, user);
+        assert_eq!(JsonCodec::encode(&user)?, br#"{"id":1,"name":"kip"}"#.to_vec());
+        assert_eq!(JsonCodec::decode::<User>(&JsonCodec::encode(&user)?)?, user);
+        assert!(JsonCodec::decode::<User>(b"kip").is_err());
+
+        Ok(())
+    }
+}
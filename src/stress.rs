@@ -0,0 +1,319 @@
+//! 进程内的压力测试
+//!
+//! 以多个并发的Worker对Store执行随机的读写，每个Worker独占一段Key并以`BTreeMap`作为模型，
+//! 读取的结果与模型不一致时立即以`KernelError::StressCheckFailed`结束，
+//! 可用于验证自定义的配置(如压缩方式、缓存大小)或`RemoteStore`等实现的正确性
+//!
+//! ```ignore
+//! let kv_store = LsmStore::open_with_config(config).await?;
+//! let report = stress::run(&kv_store, &StressConfig::default().seed(42)).await?;
+//! ```
+use std::collections::BTreeMap;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::future;
+use crate::kernel::{CommandData, KVStore, Result};
+use crate::kernel::utils::rng::XorShift64;
+use crate::KernelError;
+
+/// 默认的Key前缀，属于内部Key，因此不会出现在分页扫描的结果中
+const DEFAULT_STRESS_KEY_PREFIX: &[u8] = b"\xFF\xFFKipDB-Stress\x00";
+
+/// 单次Batch中的写入数量上限
+const MAX_BATCH_LEN: u64 = 4;
+
+/// 压力测试的配置
+#[derive(Debug, Clone)]
+pub struct StressConfig {
+    workers: usize,
+    operations: usize,
+    key_space: u32,
+    max_value_size: usize,
+    check_interval: usize,
+    seed: u64,
+    key_prefix: Vec<u8>,
+}
+
+impl Default for StressConfig {
+    #[inline]
+    fn default() -> Self {
+        StressConfig {
+            workers: 4,
+            operations: 10_000,
+            key_space: 1024,
+            max_value_size: 128,
+            check_interval: 1000,
+            seed: 0,
+            key_prefix: DEFAULT_STRESS_KEY_PREFIX.to_vec(),
+        }
+    }
+}
+
+impl StressConfig {
+    /// 并发的Worker数量
+    #[inline]
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// 每个Worker执行的操作数量
+    #[inline]
+    pub fn operations(mut self, operations: usize) -> Self {
+        self.operations = operations;
+        self
+    }
+
+    /// 每个Worker所使用的Key数量，越小则同一Key的覆盖与删除越频繁
+    #[inline]
+    pub fn key_space(mut self, key_space: u32) -> Self {
+        self.key_space = key_space.max(1);
+        self
+    }
+
+    /// Value的长度上限，Value的长度在[1, max_value_size]中随机选取
+    ///
+    /// Tips: SSTable中空Value与删除记录的编码相同，因此不写入空Value
+    #[inline]
+    pub fn max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = max_value_size.max(1);
+        self
+    }
+
+    /// 每个Worker每执行check_interval次操作，进行一次Flush并校验其所有Key
+    #[inline]
+    pub fn check_interval(mut self, check_interval: usize) -> Self {
+        self.check_interval = check_interval.max(1);
+        self
+    }
+
+    /// 随机数种子，相同的种子生成相同的操作序列
+    #[inline]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Key的前缀，用于在同一Store中同时进行多次压力测试，或与已有的数据进行区分
+    #[inline]
+    pub fn key_prefix(mut self, key_prefix: impl Into<Vec<u8>>) -> Self {
+        self.key_prefix = key_prefix.into();
+        self
+    }
+}
+
+/// 压力测试的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StressReport {
+    pub sets: usize,
+    pub gets: usize,
+    pub removes: usize,
+    pub batches: usize,
+    /// 校验的次数，每次校验Worker的所有Key
+    pub checks: usize,
+}
+
+impl StressReport {
+    fn merge(self, other: StressReport) -> Self {
+        StressReport {
+            sets: self.sets + other.sets,
+            gets: self.gets + other.gets,
+            removes: self.removes + other.removes,
+            batches: self.batches + other.batches,
+            checks: self.checks + other.checks,
+        }
+    }
+}
+
+/// 执行压力测试
+///
+/// 各Worker执行完所有操作后删除其写入的所有Key并进行最后一次校验，因此正常结束后不会留下数据
+///
+/// Tips: Worker在当前任务中并发执行，需要多线程的压力时可在多个任务中以不同的`key_prefix`分别调用
+#[inline]
+pub async fn run<T: KVStore + ?Sized>(kv_store: &T, config: &StressConfig) -> Result<StressReport> {
+    let reports = future::try_join_all(
+        (0..config.workers).map(|worker_id| Worker::new(kv_store, config, worker_id).run())
+    ).await?;
+
+    Ok(reports.into_iter().fold(StressReport::default(), StressReport::merge))
+}
+
+struct Worker<'a, T: ?Sized> {
+    kv_store: &'a T,
+    config: &'a StressConfig,
+    id: usize,
+    rng: XorShift64,
+    /// 该Worker的Key的模型
+    model: BTreeMap<u32, Bytes>,
+    report: StressReport,
+}
+
+impl<'a, T: KVStore + ?Sized> Worker<'a, T> {
+    fn new(kv_store: &'a T, config: &'a StressConfig, id: usize) -> Self {
+        // 以黄金分割数打散种子，使各Worker的序列互不相关
+        let seed = config.seed ^ (id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+
+        Worker { kv_store, config, id, rng: XorShift64::new(seed), model: BTreeMap::new(), report: StressReport::default() }
+    }
+
+    async fn run(mut self) -> Result<StressReport> {
+        for i in 1..=self.config.operations {
+            match self.rng.below(100) {
+                0..=44 => self.set().await?,
+                45..=74 => self.get().await?,
+                75..=89 => self.remove().await?,
+                _ => self.batch().await?,
+            }
+            if i % self.config.check_interval == 0 {
+                self.kv_store.flush().await?;
+                self.check().await?;
+            }
+        }
+        for index in self.model.keys().copied().collect::<Vec<_>>() {
+            self.kv_store.remove(&self.key(index)).await?;
+        }
+        self.model.clear();
+        self.check().await?;
+
+        Ok(self.report)
+    }
+
+    async fn set(&mut self) -> Result<()> {
+        let index = self.random_index();
+        let value = self.random_value();
+
+        self.kv_store.set(&self.key(index), value.clone()).await?;
+        let _ = self.model.insert(index, value);
+        self.report.sets += 1;
+
+        Ok(())
+    }
+
+    async fn get(&mut self) -> Result<()> {
+        let index = self.random_index();
+
+        self.check_key(index).await?;
+        self.report.gets += 1;
+
+        Ok(())
+    }
+
+    /// 删除不存在的Key时，Store需返回`KernelError::KeyNotFound`
+    async fn remove(&mut self) -> Result<()> {
+        let index = self.random_index();
+
+        match (self.kv_store.remove(&self.key(index)).await, self.model.remove(&index)) {
+            (Ok(()), Some(_)) | (Err(KernelError::KeyNotFound), None) => (),
+            (Ok(()), None) => return Err(self.failed(index, "removed a key that does not exist")),
+            (Err(KernelError::KeyNotFound), Some(_)) => return Err(self.failed(index, "key not found on remove")),
+            (Err(err), _) => return Err(err),
+        }
+        self.report.removes += 1;
+
+        Ok(())
+    }
+
+    /// Batch中的指令可能并行执行，因此仅写入互不相同的Key
+    async fn batch(&mut self) -> Result<()> {
+        let mut writes = BTreeMap::new();
+        for _ in 0..=self.rng.below(MAX_BATCH_LEN) {
+            let index = self.random_index();
+            let value = self.random_value();
+            let _ = writes.insert(index, value);
+        }
+        let vec_cmd = writes.iter()
+            .map(|(index, value)| CommandData::set(self.key(*index).to_vec(), value.to_vec()))
+            .collect();
+
+        let _ = self.kv_store.batch(vec_cmd).await?;
+        self.model.append(&mut writes);
+        self.report.batches += 1;
+
+        Ok(())
+    }
+
+    /// 校验该Worker的所有Key
+    async fn check(&mut self) -> Result<()> {
+        for index in 0..self.config.key_space {
+            self.check_key(index).await?;
+        }
+        self.report.checks += 1;
+
+        Ok(())
+    }
+
+    async fn check_key(&self, index: u32) -> Result<()> {
+        let value = self.kv_store.get(&self.key(index)).await?;
+
+        if value.as_ref() != self.model.get(&index) {
+            return Err(self.failed(index, format!(
+                "expected {:?} but read {:?}",
+                self.model.get(&index).map(Bytes::len),
+                value.as_ref().map(Bytes::len)
+            )));
+        }
+        Ok(())
+    }
+
+    /// 前缀 + Worker序号 + Key序号，均以大端序编码
+    fn key(&self, index: u32) -> Bytes {
+        let mut key = BytesMut::with_capacity(self.config.key_prefix.len() + 12);
+        key.put_slice(&self.config.key_prefix);
+        key.put_u64(self.id as u64);
+        key.put_u32(index);
+
+        key.freeze()
+    }
+
+    fn random_index(&mut self) -> u32 {
+        self.rng.below(u64::from(self.config.key_space)) as u32
+    }
+
+    fn random_value(&mut self) -> Bytes {
+        let len = self.rng.below(self.config.max_value_size as u64) as usize + 1;
+
+        (0..len).map(|_| self.rng.next_u64() as u8).collect()
+    }
+
+    fn failed(&self, index: u32, reason: impl Into<String>) -> KernelError {
+        KernelError::StressCheckFailed(format!(
+            "worker {} key {}: {}", self.id, index, reason.into()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use crate::kernel::lsm::lsm_kv::{Config, LsmStore};
+    use crate::kernel::Result;
+    use crate::stress::{self, StressConfig};
+
+    #[test]
+    fn test_stress() -> Result<()> {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        tokio_test::block_on(async move {
+            let config = Config::new(temp_dir.path())
+                .minor_threshold_with_len(100)
+                .major_threshold_with_sst_size(2);
+            let kv_store = LsmStore::open_with_config(config).await?;
+            let stress_config = StressConfig::default()
+                .workers(3)
+                .operations(2000)
+                .key_space(64)
+                .check_interval(500)
+                .seed(7);
+
+            let report = stress::run(&kv_store, &stress_config).await?;
+            assert_eq!(report.sets + report.gets + report.removes + report.batches, 6000);
+            // 每个Worker校验4次，结束时再校验1次
+            assert_eq!(report.checks, 15);
+            // 相同的种子执行相同的操作序列
+            assert_eq!(stress::run(&kv_store, &stress_config).await?, report);
+
+            Ok(())
+        })
+    }
+}
@@ -1,3 +1,4 @@
+use std::fs;
 use bytes::Bytes;
 use tempfile::TempDir;
 use walkdir::WalkDir;
@@ -218,6 +219,52 @@ fn compaction_with_kv_store<T: KVStore>() -> Result<()> {
     })
 }
 
+// Move the data directory to another path and reopen it.
+#[test]
+fn relocate_data_dir() -> Result<()> {
+    relocate_data_dir_with_kv_store::<HashStore>()?;
+    relocate_data_dir_with_kv_store::<SledStore>()?;
+    relocate_data_dir_with_kv_store::<LsmStore>()?;
+
+    Ok(())
+}
+
+fn relocate_data_dir_with_kv_store<T: KVStore>() -> Result<()> {
+    tokio_test::block_on(async move {
+        let old_dir = TempDir::new().expect("unable to create temporary working directory");
+        let new_dir = TempDir::new().expect("unable to create temporary working directory");
+
+        let kv_store = T::open(old_dir.path()).await?;
+        for key_id in 0..1000 {
+            let key = format!("key{}", key_id);
+            kv_store.set(&encode_key(key.as_str())?, Bytes::from(encode_key(key.as_str())?)).await?
+        }
+        kv_store.flush().await?;
+        drop(kv_store);
+
+        for entry in WalkDir::new(old_dir.path()) {
+            let entry = entry.expect("fail to walk directory");
+            let target = new_dir.path()
+                .join(entry.path().strip_prefix(old_dir.path()).expect("fail to strip prefix"));
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(target)?;
+            } else {
+                let _ = fs::copy(entry.path(), target)?;
+            }
+        }
+        old_dir.close()?;
+
+        let kv_store = T::open(new_dir.path()).await?;
+        for key_id in 0..1000 {
+            let key = format!("key{}", key_id);
+            assert_eq!(kv_store.get(&encode_key(key.as_str())?).await?,
+                       Some(Bytes::from(encode_key(key.as_str())?)));
+        }
+
+        Ok(())
+    })
+}
+
 #[test]
 fn test_io() -> Result<()> {
     let temp_dir = TempDir::new()
@@ -228,6 +275,11 @@ fn test_io() -> Result<()> {
     io_type_test(&factory, IoType::MMap)?;
     io_type_test(&factory, IoType::Direct)?;
 
+    // 重复删除需保持幂等
+    factory.clean(1)?;
+    factory.clean(1)?;
+    assert!(!factory.has_gen(1)?);
+
     Ok(())
 }
 
@@ -254,4 +306,26 @@ fn io_type_test(factory: &IoFactory, io_type: IoType) -> Result<()> {
 
 fn encode_key(key: &str) -> Result<Vec<u8>>{
     Ok(bincode::serialize(key)?)
-}
\ No newline at end of file
+}
+#[test]
+fn dyn_kv_store() -> Result<()> {
+    tokio_test::block_on(async move {
+        let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+        let kv_stores: Vec<Box<dyn KVStore>> = vec![
+            Box::new(HashStore::open(temp_dir.path().join("hash")).await?),
+            Box::new(SledStore::open(temp_dir.path().join("sled")).await?),
+            Box::new(LsmStore::open(temp_dir.path().join("lsm")).await?),
+        ];
+
+        for kv_store in kv_stores {
+            kv_store.set(b"key1", Bytes::from_static(b"value1")).await?;
+            kv_store.flush().await?;
+            assert_eq!(kv_store.get(b"key1").await?, Some(Bytes::from_static(b"value1")));
+            kv_store.set(b"key2", Bytes::from_static(b"value2")).await?;
+            kv_store.remove(b"key2").await?;
+            assert_eq!(kv_store.get(b"key2").await?, None);
+        }
+
+        Ok(())
+    })
+}